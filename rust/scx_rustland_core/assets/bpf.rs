@@ -11,7 +11,6 @@ use crate::bpf_skel::*;
 
 use std::ffi::c_int;
 use std::ffi::c_ulong;
-use std::ffi::CStr;
 
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -29,7 +28,7 @@ use libbpf_rs::libbpf_sys::bpf_object_open_opts;
 use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
 
-use libc::{c_char, pthread_self, pthread_setschedparam, sched_param};
+use libc::{pthread_self, pthread_setschedparam, sched_param};
 
 #[cfg(target_env = "musl")]
 use libc::timespec;
@@ -43,11 +42,15 @@ use scx_utils::uei_report;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
 
+use scx_rustland_core::SchedBackend;
 use scx_rustland_core::ALLOCATOR;
 
+// Tasks exchanged with the BPF component, defined in scx_rustland_core so that policies and
+// the scheduling runtime can be shared across schedulers.
+pub use scx_rustland_core::{DispatchedTask, QueuedTask};
+
 // Defined in UAPI
 const SCHED_EXT: i32 = 7;
-const TASK_COMM_LEN: usize = 16;
 
 // Allow to dispatch the task on any CPU.
 //
@@ -55,6 +58,15 @@ const TASK_COMM_LEN: usize = 16;
 #[allow(dead_code)]
 pub const RL_CPU_ANY: i32 = bpf_intf::RL_CPU_ANY as i32;
 
+// Helpers used to submit tasks to the BPF user ring buffer.
+unsafe impl Plain for bpf_intf::dispatched_task_ctx {}
+
+impl AsMut<bpf_intf::dispatched_task_ctx> for bpf_intf::dispatched_task_ctx {
+    fn as_mut(&mut self) -> &mut bpf_intf::dispatched_task_ctx {
+        self
+    }
+}
+
 /// High-level Rust abstraction to interact with a generic sched-ext BPF component.
 ///
 /// Overview
@@ -73,104 +85,6 @@ pub const RL_CPU_ANY: i32 = bpf_intf::RL_CPU_ANY as i32;
 ///
 /// Finally the methods exited() and shutdown_and_report() can be used respectively to test
 /// whether the BPF component exited, and to shutdown and report the exit message.
-
-// Task queued for scheduling from the BPF component (see bpf_intf::queued_task_ctx).
-#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
-pub struct QueuedTask {
-    pub pid: i32,             // pid that uniquely identifies a task
-    pub cpu: i32,             // CPU previously used by the task
-    pub nr_cpus_allowed: u64, // Number of CPUs that the task can use
-    pub flags: u64,           // task's enqueue flags
-    pub start_ts: u64,        // Timestamp since last time the task ran on a CPU (in ns)
-    pub stop_ts: u64,         // Timestamp since last time the task released a CPU (in ns)
-    pub exec_runtime: u64,    // Total cpu time since last sleep (in ns)
-    pub weight: u64,          // Task priority in the range [1..10000] (default is 100)
-    pub vtime: u64,           // Current task vruntime / deadline (set by the scheduler)
-    pub enq_cnt: u64,
-    pub comm: [c_char; TASK_COMM_LEN], // Task's executable name
-}
-
-impl QueuedTask {
-    /// Convert the task's comm field (C char array) into a Rust String.
-    #[allow(dead_code)]
-    pub fn comm_str(&self) -> String {
-        // Convert the C char array into a Rust String
-        let c_str = unsafe { CStr::from_ptr(self.comm.as_ptr()) };
-
-        // Handle potential invalid UTF-8
-        c_str.to_string_lossy().into_owned()
-    }
-}
-
-// Task queued for dispatching to the BPF component (see bpf_intf::dispatched_task_ctx).
-#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
-pub struct DispatchedTask {
-    pub pid: i32,      // pid that uniquely identifies a task
-    pub cpu: i32, // target CPU selected by the scheduler (RL_CPU_ANY = dispatch on the first CPU available)
-    pub flags: u64, // task's enqueue flags
-    pub slice_ns: u64, // time slice in nanoseconds assigned to the task (0 = use default time slice)
-    pub vtime: u64, // this value can be used to send the task's vruntime or deadline directly to the underlying BPF dispatcher
-    pub enq_cnt: u64,
-}
-
-impl DispatchedTask {
-    // Create a DispatchedTask from a QueuedTask.
-    //
-    // A dispatched task should be always originated from a QueuedTask (there is no reason to
-    // dispatch a task if it wasn't queued to the scheduler earlier).
-    pub fn new(task: &QueuedTask) -> Self {
-        DispatchedTask {
-            pid: task.pid,
-            cpu: task.cpu,
-            flags: task.flags,
-            slice_ns: 0, // use default time slice
-            vtime: 0,
-            enq_cnt: task.enq_cnt,
-        }
-    }
-}
-
-// Helpers used to submit tasks to the BPF user ring buffer.
-unsafe impl Plain for bpf_intf::dispatched_task_ctx {}
-
-impl AsMut<bpf_intf::dispatched_task_ctx> for bpf_intf::dispatched_task_ctx {
-    fn as_mut(&mut self) -> &mut bpf_intf::dispatched_task_ctx {
-        self
-    }
-}
-
-// Message received from the dispatcher (see bpf_intf::queued_task_ctx for details).
-//
-// NOTE: eventually libbpf-rs will provide a better abstraction for this.
-struct EnqueuedMessage {
-    inner: bpf_intf::queued_task_ctx,
-}
-
-impl EnqueuedMessage {
-    fn from_bytes(bytes: &[u8]) -> Self {
-        let queued_task_struct = unsafe { *(bytes.as_ptr() as *const bpf_intf::queued_task_ctx) };
-        EnqueuedMessage {
-            inner: queued_task_struct,
-        }
-    }
-
-    fn to_queued_task(&self) -> QueuedTask {
-        QueuedTask {
-            pid: self.inner.pid,
-            cpu: self.inner.cpu,
-            nr_cpus_allowed: self.inner.nr_cpus_allowed,
-            flags: self.inner.flags,
-            start_ts: self.inner.start_ts,
-            stop_ts: self.inner.stop_ts,
-            exec_runtime: self.inner.exec_runtime,
-            weight: self.inner.weight,
-            vtime: self.inner.vtime,
-            enq_cnt: self.inner.enq_cnt,
-            comm: self.inner.comm,
-        }
-    }
-}
-
 pub struct BpfScheduler<'cb> {
     pub skel: BpfSkel<'cb>,                // Low-level BPF connector
     shutdown: Arc<AtomicBool>,             // Determine scheduler shutdown
@@ -213,6 +127,7 @@ impl<'cb> BpfScheduler<'cb> {
         debug: bool,
         builtin_idle: bool,
         numa_local: bool,
+        extended_ctx: bool,
         slice_ns: u64,
         name: &str,
     ) -> Result<Self> {
@@ -226,6 +141,9 @@ impl<'cb> BpfScheduler<'cb> {
 
         // Copy one item from the ring buffer.
         //
+        // Items are shorter than BUF when the extended task context is disabled: in this case the
+        // remaining part of BUF is zeroed, so that the extended fields are always 0.
+        //
         // # Safety
         //
         // Each invocation of the callback will trigger the copy of exactly one QueuedTask item to
@@ -238,12 +156,13 @@ impl<'cb> BpfScheduler<'cb> {
             #[allow(static_mut_refs)]
             unsafe {
                 // SAFETY: copying from the BPF ring buffer to BUF is safe, since the size of BUF
-                // is exactly the size of QueuedTask and the callback operates in chunks of
-                // QueuedTask items. It also copies exactly one QueuedTask at a time, this is
+                // is the size of a full queued_task_ctx (no item can be larger) and the callback
+                // operates in chunks of QueuedTask items. It also copies exactly one QueuedTask at a time, this is
                 // guaranteed by the error code returned by this callback (see below). From a
                 // thread-safety perspective this is also correct, assuming the caller is a
                 // single-thread process (as it is for now).
-                BUF.0.copy_from_slice(data);
+                BUF.0[..data.len()].copy_from_slice(data);
+                BUF.0[data.len()..].fill(0);
             }
 
             // Return 0 to indicate successful completion of the copy.
@@ -268,6 +187,7 @@ impl<'cb> BpfScheduler<'cb> {
         skel.maps.rodata_data.as_mut().unwrap().khugepaged_pid = Self::khugepaged_pid();
         skel.maps.rodata_data.as_mut().unwrap().builtin_idle = builtin_idle;
         skel.maps.rodata_data.as_mut().unwrap().numa_local = numa_local;
+        skel.maps.rodata_data.as_mut().unwrap().extended_ctx = extended_ctx;
         skel.maps.rodata_data.as_mut().unwrap().slice_ns = slice_ns;
         skel.maps.rodata_data.as_mut().unwrap().debug = debug;
         let _ = Self::set_scx_ops_name(&mut skel.struct_ops.rustland_mut().name, name);
@@ -530,7 +450,7 @@ impl<'cb> BpfScheduler<'cb> {
             }
             1 => {
                 // A valid task is received, convert data to a proper task struct.
-                let task = unsafe { EnqueuedMessage::from_bytes(&BUF.0).to_queued_task() };
+                let task = unsafe { QueuedTask::from_bytes(&BUF.0) };
                 bss_data.nr_queued = bss_data.nr_queued.saturating_sub(1);
 
                 Ok(Some(task))
@@ -591,6 +511,38 @@ impl<'cb> BpfScheduler<'cb> {
     }
 }
 
+// Allow the BPF scheduler to drive a scx_rustland_core::SchedRuntime.
+impl SchedBackend for BpfScheduler<'_> {
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+        BpfScheduler::dequeue_task(self)
+    }
+
+    fn select_cpu(&mut self, pid: i32, prev_cpu: i32, flags: u64) -> i32 {
+        BpfScheduler::select_cpu(self, pid, prev_cpu, flags)
+    }
+
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+        BpfScheduler::dispatch_task(self, task)?;
+        Ok(())
+    }
+
+    fn notify_complete(&mut self, nr_pending: u64) {
+        BpfScheduler::notify_complete(self, nr_pending)
+    }
+
+    fn exited(&mut self) -> bool {
+        BpfScheduler::exited(self)
+    }
+
+    fn nr_sched_congested(&mut self) -> u64 {
+        *self.nr_sched_congested_mut()
+    }
+
+    fn nr_bounce_dispatches(&mut self) -> u64 {
+        *self.nr_bounce_dispatches_mut()
+    }
+}
+
 // Disconnect the low-level BPF scheduler.
 impl Drop for BpfScheduler<'_> {
     fn drop(&mut self) {
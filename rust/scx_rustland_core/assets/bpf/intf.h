@@ -82,6 +82,10 @@ struct domain_arg {
  * Task sent to the user-space scheduler by the BPF dispatcher.
  *
  * All attributes are collected from the kernel by the the BPF component.
+ *
+ * The attributes after @comm are collected only when the extended task
+ * context is enabled, otherwise they are not sent through the ring buffer at
+ * all (see QUEUED_TASK_CTX_BASE_SIZE).
  */
 struct queued_task_ctx {
 	s32 pid;
@@ -95,6 +99,14 @@ struct queued_task_ctx {
 	u64 vtime; /* Current task's vruntime */
 	u64 enq_cnt;
 	char comm[TASK_COMM_LEN]; /* Task's executable name */
+
+	/* Extended task context */
+	s32 tgid; /* Thread group (process) ID */
+	s32 waker_pid; /* PID of the task that woke up the task (0 = unknown) */
+	u64 cgroup_id; /* ID of the task's cgroup (cgroup v2) */
+	u64 nvcsw; /* Voluntary context switches since last enqueue */
+	u64 nivcsw; /* Involuntary context switches since last enqueue */
+	s32 numa_node; /* NUMA node of the CPU previously used by the task */
 };
 
 /*
@@ -111,6 +111,9 @@ const volatile bool builtin_idle;
 /* Enable NUMA-local idle CPU selection */
 const volatile bool numa_local;
 
+/* Send the extended task context to the user-space scheduler */
+const volatile bool extended_ctx;
+
 /* Allow to use bpf_printk() only when @debug is set */
 #define dbg_msg(_fmt, ...) do {						\
 	if (debug)							\
@@ -136,6 +139,11 @@ const volatile bool smt_enabled = true;
  */
 #define MAX_DISPATCH_SLOT (MAX_ENQUEUED_TASKS / 8)
 
+/*
+ * Size of a queued task without the extended task context.
+ */
+#define QUEUED_TASK_CTX_BASE_SIZE offsetof(struct queued_task_ctx, tgid)
+
 /*
  * The map containing tasks that are queued to user space from the kernel.
  *
@@ -184,6 +192,18 @@ struct task_ctx {
 	 * Task generation counter to detect duplicate enqueues.
 	 */
 	u64 enq_cnt;
+
+	/*
+	 * Context switch counters at the last enqueue (extended context only).
+	 */
+	u64 nvcsw;
+	u64 nivcsw;
+
+	/*
+	 * PID of the task that performed the last wakeup (extended context
+	 * only).
+	 */
+	s32 waker_pid;
 };
 
 /* Map that contains task-local storage. */
@@ -691,6 +711,26 @@ static void get_task_info(struct queued_task_ctx *task,
 	bpf_core_read_str(&task->comm, sizeof(task->comm), &p->comm);
 }
 
+/*
+ * Fill the extended part of @task (only sent when @extended_ctx is enabled).
+ */
+static void get_task_info_ext(struct queued_task_ctx *task,
+			      const struct task_struct *p,
+			      struct task_ctx *tctx, s32 prev_cpu)
+{
+	u64 nvcsw = p->nvcsw, nivcsw = p->nivcsw;
+
+	task->tgid = p->tgid;
+	task->waker_pid = tctx->waker_pid;
+	task->cgroup_id = BPF_CORE_READ(p, cgroups, dfl_cgrp, kn, id);
+	task->nvcsw = nvcsw - tctx->nvcsw;
+	task->nivcsw = nivcsw - tctx->nivcsw;
+	task->numa_node = __COMPAT_scx_bpf_cpu_node(prev_cpu);
+
+	tctx->nvcsw = nvcsw;
+	tctx->nivcsw = nivcsw;
+}
+
 /*
  * User-space scheduler is congested: log that and increment congested counter.
  */
@@ -728,7 +768,10 @@ static void queue_task_to_userspace(struct task_struct *p, s32 prev_cpu, u64 enq
 	 * so dispatch the task directly using the shared DSQ (the task
 	 * will be consumed by the first CPU available).
 	 */
-	task = bpf_ringbuf_reserve(&queued, sizeof(*task), 0);
+	if (extended_ctx)
+		task = bpf_ringbuf_reserve(&queued, sizeof(*task), 0);
+	else
+		task = bpf_ringbuf_reserve(&queued, QUEUED_TASK_CTX_BASE_SIZE, 0);
 	if (!task) {
 		sched_congested(p);
 		scx_bpf_dsq_insert_vtime(p, SHARED_DSQ,
@@ -743,6 +786,8 @@ static void queue_task_to_userspace(struct task_struct *p, s32 prev_cpu, u64 enq
 	 */
 	dbg_msg("enqueue: pid=%d (%s)", p->pid, p->comm);
 	get_task_info(task, p, tctx, enq_flags, prev_cpu);
+	if (extended_ctx)
+		get_task_info_ext(task, p, tctx, prev_cpu);
 	bpf_ringbuf_submit(task, 0);
 	__sync_fetch_and_add(&nr_queued, 1);
 }
@@ -928,6 +973,13 @@ void BPF_STRUCT_OPS(rustland_runnable, struct task_struct *p, u64 enq_flags)
 		return;
 
 	tctx->exec_runtime = 0;
+
+	/*
+	 * On wakeup the current task is the waker.
+	 */
+	if (extended_ctx)
+		tctx->waker_pid = (enq_flags & SCX_ENQ_WAKEUP) ?
+				  (s32)bpf_get_current_pid_tgid() : 0;
 }
 
 /*
@@ -1126,6 +1178,13 @@ s32 BPF_STRUCT_OPS(rustland_init_task, struct task_struct *p,
 	if (!tctx)
 		return -ENOMEM;
 
+	/*
+	 * Start counting context switches from now, so that the first
+	 * extended task context doesn't report the lifetime counters.
+	 */
+	tctx->nvcsw = p->nvcsw;
+	tctx->nivcsw = p->nivcsw;
+
 	return 0;
 }
 
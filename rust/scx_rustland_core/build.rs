@@ -0,0 +1,12 @@
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+fn main() {
+    // Generate the bindings of the BPF interface shared with the schedulers, so that constants
+    // such as RL_CPU_ANY are always taken from assets/bpf/intf.h.
+    scx_cargo::BpfBuilder::new()
+        .unwrap()
+        .enable_intf("assets/bpf/intf.h", "bpf_intf.rs")
+        .build()
+        .unwrap();
+}
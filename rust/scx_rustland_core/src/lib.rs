@@ -1,7 +1,29 @@
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+mod bpf_intf;
+
 mod alloc;
 pub use alloc::ALLOCATOR;
 
 mod rustland_builder;
 pub use rustland_builder::RustLandBuilder;
+
+mod task;
+pub use task::DispatchedTask;
+pub use task::QueuedTask;
+pub use task::RL_CPU_ANY;
+
+mod policy;
+pub use policy::DeadlinePolicy;
+pub use policy::FifoPolicy;
+pub use policy::RuntimeStats;
+pub use policy::SchedBackend;
+pub use policy::SchedPolicy;
+pub use policy::SchedRuntime;
+pub use policy::VruntimePolicy;
+
+mod sim;
+pub use sim::SimReport;
+pub use sim::SimTask;
+pub use sim::Simulator;
+pub use sim::TaskReport;
@@ -0,0 +1,750 @@
+// Copyright (c) Andrea Righi <andrea.righi@linux.dev>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Pluggable scheduling policies and the runtime driving them.
+//!
+//! Most user-space schedulers implement the same loop: drain the tasks queued by the BPF
+//! component, order them somehow, pick an idle CPU for the next one, dispatch it and report the
+//! amount of pending work. SchedRuntime implements that loop once, leaving only the ordering
+//! decisions to a SchedPolicy:
+//!
+//! ```ignore
+//! let bpf = BpfScheduler::init(...)?;
+//! let mut runtime = SchedRuntime::new(bpf, DeadlinePolicy::new(20_000_000, 1_000_000));
+//! runtime.run()?;
+//! runtime.backend_mut().shutdown_and_report()?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::task::DispatchedTask;
+use crate::task::QueuedTask;
+use crate::task::RL_CPU_ANY;
+
+/// Connection to the BPF dispatcher, implemented by the BpfScheduler generated in each scheduler
+/// crate (see assets/bpf.rs).
+pub trait SchedBackend {
+    /// Receive a task to be scheduled, None if there are no more queued tasks.
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32>;
+
+    /// Pick an idle CPU for the task, a negative value means that no CPU is idle.
+    fn select_cpu(&mut self, pid: i32, prev_cpu: i32, flags: u64) -> i32;
+
+    /// Send a task to the dispatcher. Failures are transient (e.g., dispatch ring buffer full)
+    /// and the task is retried on the next scheduling cycle.
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()>;
+
+    /// Report the amount of pending tasks and give control back to the dispatcher.
+    fn notify_complete(&mut self, nr_pending: u64);
+
+    /// Whether the scheduler has been asked to stop.
+    fn exited(&mut self) -> bool;
+
+    /// Counter of scheduler congestion events.
+    fn nr_sched_congested(&mut self) -> u64;
+
+    /// Counter of dispatches bounced to the shared DSQ.
+    fn nr_bounce_dispatches(&mut self) -> u64;
+
+    /// Current time in ns, passed to SchedPolicy::on_tick().
+    fn now_ns(&mut self) -> u64 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+    }
+}
+
+/// Scheduling decisions of a user-space scheduler.
+pub trait SchedPolicy {
+    /// A task became ready to run and has to be queued by the policy.
+    fn on_enqueue(&mut self, task: QueuedTask);
+
+    /// Remove the next task to run from the queue, None if the queue is empty.
+    ///
+    /// The returned task's cpu is used as the previous CPU when looking for an idle CPU, so it
+    /// should normally be left as set by DispatchedTask::new().
+    fn pick_next(&mut self) -> Option<DispatchedTask>;
+
+    /// Amount of tasks currently queued by the policy.
+    fn nr_queued(&self) -> usize;
+
+    /// An idle CPU has been found for a task returned by pick_next(), return the CPU the task
+    /// should be dispatched to. Tasks without an idle CPU are dispatched to RL_CPU_ANY.
+    fn on_cpu_idle(&mut self, _task: &DispatchedTask, cpu: i32) -> i32 {
+        cpu
+    }
+
+    /// Called at the beginning of every scheduling cycle.
+    fn on_tick(&mut self, _now_ns: u64) {}
+}
+
+/// Counters maintained by SchedRuntime.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeStats {
+    pub nr_enqueued: u64,          // tasks received from the backend
+    pub nr_dispatched: u64,        // tasks successfully dispatched
+    pub nr_dispatch_retries: u64,  // failed dispatches (retried on the next cycle)
+    pub nr_dequeue_errors: u64,    // errors receiving tasks from the backend
+    pub nr_sched_congested: u64,   // backend congestion events since the runtime started
+    pub nr_bounce_dispatches: u64, // backend bounced dispatches since the runtime started
+}
+
+/// Drives a SchedPolicy on top of a SchedBackend.
+pub struct SchedRuntime<B, P> {
+    backend: B,
+    policy: P,
+    stats: RuntimeStats,
+    congested_base: u64,
+    bounce_base: u64,
+    retry: Option<DispatchedTask>,
+    dispatch_batch: usize,
+    select_idle_cpu: bool,
+}
+
+impl<B: SchedBackend, P: SchedPolicy> SchedRuntime<B, P> {
+    pub fn new(mut backend: B, policy: P) -> Self {
+        let congested_base = backend.nr_sched_congested();
+        let bounce_base = backend.nr_bounce_dispatches();
+
+        Self {
+            backend,
+            policy,
+            stats: RuntimeStats::default(),
+            congested_base,
+            bounce_base,
+            retry: None,
+            dispatch_batch: 1,
+            select_idle_cpu: true,
+        }
+    }
+
+    /// Maximum amount of tasks dispatched per scheduling cycle (default 1).
+    ///
+    /// Dispatching a single task per cycle keeps the ordering decisions as fresh as possible,
+    /// larger batches reduce the overhead when many tasks are queued.
+    pub fn dispatch_batch(mut self, nr_tasks: usize) -> Self {
+        self.dispatch_batch = nr_tasks.max(1);
+        self
+    }
+
+    /// Look for an idle CPU for each dispatched task (default true).
+    ///
+    /// When disabled, tasks are dispatched to the CPU returned by pick_next(), that is the CPU
+    /// previously used by the task, unless the policy changes it.
+    pub fn select_idle_cpu(mut self, enabled: bool) -> Self {
+        self.select_idle_cpu = enabled;
+        self
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    pub fn into_parts(self) -> (B, P) {
+        (self.backend, self.policy)
+    }
+
+    /// Amount of tasks waiting to be dispatched.
+    pub fn nr_pending(&self) -> usize {
+        self.policy.nr_queued() + usize::from(self.retry.is_some())
+    }
+
+    pub fn stats(&mut self) -> RuntimeStats {
+        self.stats.nr_sched_congested = self
+            .backend
+            .nr_sched_congested()
+            .saturating_sub(self.congested_base);
+        self.stats.nr_bounce_dispatches = self
+            .backend
+            .nr_bounce_dispatches()
+            .saturating_sub(self.bounce_base);
+        self.stats
+    }
+
+    // Move all the tasks queued by the backend to the policy.
+    fn drain_queued_tasks(&mut self) {
+        loop {
+            match self.backend.dequeue_task() {
+                Ok(Some(task)) => {
+                    self.policy.on_enqueue(task);
+                    self.stats.nr_enqueued += 1;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    self.stats.nr_dequeue_errors += 1;
+                    break;
+                }
+            }
+        }
+    }
+
+    // Return the next task to dispatch with its target CPU assigned.
+    fn next_task(&mut self) -> Option<DispatchedTask> {
+        if let Some(task) = self.retry.take() {
+            return Some(task);
+        }
+
+        let mut task = self.policy.pick_next()?;
+        if !self.select_idle_cpu {
+            return Some(task);
+        }
+        task.cpu = match self.backend.select_cpu(task.pid, task.cpu, task.flags) {
+            cpu if cpu >= 0 => self.policy.on_cpu_idle(&task, cpu),
+            _ => RL_CPU_ANY,
+        };
+        Some(task)
+    }
+
+    fn dispatch_tasks(&mut self) {
+        for _ in 0..self.dispatch_batch {
+            let Some(task) = self.next_task() else {
+                break;
+            };
+
+            if self.backend.dispatch_task(&task).is_err() {
+                // The dispatcher is congested: keep the task and stop dispatching.
+                self.retry = Some(task);
+                self.stats.nr_dispatch_retries += 1;
+                break;
+            }
+            self.stats.nr_dispatched += 1;
+        }
+    }
+
+    /// Run a single scheduling cycle.
+    pub fn schedule(&mut self) {
+        let now_ns = self.backend.now_ns();
+        self.policy.on_tick(now_ns);
+
+        self.drain_queued_tasks();
+        self.dispatch_tasks();
+
+        // Notify the dispatcher if there are still pending tasks to be processed.
+        self.backend.notify_complete(self.nr_pending() as u64);
+    }
+
+    /// Run scheduling cycles until the backend exits.
+    pub fn run(&mut self) -> Result<()> {
+        self.run_with(|_| Ok(()))
+    }
+
+    /// Run scheduling cycles until the backend exits, calling on_cycle() after every cycle (e.g.,
+    /// to serve statistics requests).
+    pub fn run_with<F>(&mut self, mut on_cycle: F) -> Result<()>
+    where
+        F: FnMut(&mut Self) -> Result<()>,
+    {
+        while !self.backend.exited() {
+            self.schedule();
+            on_cycle(self)?;
+        }
+        Ok(())
+    }
+}
+
+/// Dispatch tasks in the same order they have been queued, with a fixed time slice.
+#[derive(Debug, Default)]
+pub struct FifoPolicy {
+    tasks: VecDeque<QueuedTask>,
+    slice_ns: u64,
+}
+
+impl FifoPolicy {
+    /// A slice_ns of 0 uses the default time slice of the BPF component.
+    pub fn new(slice_ns: u64) -> Self {
+        Self {
+            tasks: VecDeque::new(),
+            slice_ns,
+        }
+    }
+}
+
+impl SchedPolicy for FifoPolicy {
+    fn on_enqueue(&mut self, task: QueuedTask) {
+        self.tasks.push_back(task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        let task = self.tasks.pop_front()?;
+        let mut dispatched_task = DispatchedTask::new(&task);
+        dispatched_task.slice_ns = self.slice_ns;
+        Some(dispatched_task)
+    }
+
+    fn nr_queued(&self) -> usize {
+        self.tasks.len()
+    }
+}
+
+// Tasks ordered by a vruntime-derived key, shared by VruntimePolicy and DeadlinePolicy.
+#[derive(Debug, Default)]
+struct VtimeQueue {
+    tasks: BTreeMap<(u64, u64), QueuedTask>, // (key, enqueue sequence) -> task
+    seq: u64,                                // enqueue sequence, breaks ties in FIFO order
+    vruntime_now: u64,                       // global vruntime
+    slice_ns: u64,                           // maximum vruntime credit of sleeping tasks
+}
+
+impl VtimeQueue {
+    fn new(slice_ns: u64) -> Self {
+        Self {
+            slice_ns,
+            ..Default::default()
+        }
+    }
+
+    // Update the task's vruntime with the time slice it just consumed.
+    fn update_vruntime(&mut self, task: &mut QueuedTask) {
+        task.vtime = if task.vtime == 0 {
+            // Re-align new tasks to the current vruntime.
+            self.vruntime_now
+        } else {
+            // Prevent sleeping tasks from gaining more than one full slice of vruntime credit.
+            let vruntime_min = self.vruntime_now.saturating_sub(self.slice_ns);
+            task.vtime.max(vruntime_min)
+        };
+
+        let slice_ns = task.stop_ts.saturating_sub(task.start_ts);
+        let vslice = scale_by_task_weight_inverse(task, slice_ns);
+        task.vtime += vslice;
+        self.vruntime_now += vslice;
+    }
+
+    fn push(&mut self, key: u64, task: QueuedTask) {
+        self.tasks.insert((key, self.seq), task);
+        self.seq += 1;
+    }
+
+    fn pop(&mut self) -> Option<(u64, QueuedTask)> {
+        self.tasks.pop_first().map(|((key, _), task)| (key, task))
+    }
+}
+
+// Return a value proportional to the task's weight.
+fn scale_by_task_weight(task: &QueuedTask, value: u64) -> u64 {
+    value * task.weight / 100
+}
+
+// Return a value inversely proportional to the task's weight.
+fn scale_by_task_weight_inverse(task: &QueuedTask, value: u64) -> u64 {
+    value * 100 / task.weight.max(1)
+}
+
+/// Dispatch tasks in vruntime order (the task's runtime scaled by its weight), so that all the
+/// tasks get a fair share of CPU time.
+#[derive(Debug, Default)]
+pub struct VruntimePolicy {
+    queue: VtimeQueue,
+}
+
+impl VruntimePolicy {
+    /// slice_ns is the time slice assigned to each task, also used to limit the vruntime credit
+    /// of sleeping tasks.
+    pub fn new(slice_ns: u64) -> Self {
+        Self {
+            queue: VtimeQueue::new(slice_ns),
+        }
+    }
+}
+
+impl SchedPolicy for VruntimePolicy {
+    fn on_enqueue(&mut self, mut task: QueuedTask) {
+        self.queue.update_vruntime(&mut task);
+        self.queue.push(task.vtime, task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        let (vtime, task) = self.queue.pop()?;
+        let mut dispatched_task = DispatchedTask::new(&task);
+        dispatched_task.slice_ns = self.queue.slice_ns;
+        dispatched_task.vtime = vtime;
+        Some(dispatched_task)
+    }
+
+    fn nr_queued(&self) -> usize {
+        self.queue.tasks.len()
+    }
+}
+
+/// Dispatch tasks by deadline, evaluated as vruntime + exec_runtime (the policy of
+/// scx_rustland).
+///
+/// Tasks that frequently sleep accumulate less exec_runtime and get earlier deadlines, favoring
+/// latency-sensitive tasks over CPU-intensive ones.
+#[derive(Debug, Default)]
+pub struct DeadlinePolicy {
+    queue: VtimeQueue,
+    slice_ns_min: u64,
+}
+
+impl DeadlinePolicy {
+    /// slice_ns limits the vruntime credit of sleeping tasks (and exec_runtime to 100 slices),
+    /// while tasks are dispatched with slice_ns_min scaled by their weight.
+    pub fn new(slice_ns: u64, slice_ns_min: u64) -> Self {
+        Self {
+            queue: VtimeQueue::new(slice_ns),
+            slice_ns_min,
+        }
+    }
+}
+
+impl SchedPolicy for DeadlinePolicy {
+    fn on_enqueue(&mut self, mut task: QueuedTask) {
+        self.queue.update_vruntime(&mut task);
+
+        // Cap exec_runtime to 100 time slices to prevent starvation of CPU-intensive tasks.
+        let deadline = task.vtime
+            + task
+                .exec_runtime
+                .min(self.queue.slice_ns.saturating_mul(100));
+        self.queue.push(deadline, task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        let (deadline, task) = self.queue.pop()?;
+        let mut dispatched_task = DispatchedTask::new(&task);
+        dispatched_task.slice_ns = scale_by_task_weight(&task, self.slice_ns_min);
+        dispatched_task.vtime = deadline;
+        Some(dispatched_task)
+    }
+
+    fn nr_queued(&self) -> usize {
+        self.queue.tasks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MSEC: u64 = 1_000_000;
+
+    // Backend replaying a fixed set of queued tasks and recording what gets dispatched.
+    #[derive(Debug, Default)]
+    struct TestBackend {
+        queued: VecDeque<QueuedTask>,
+        idle_cpu: i32,      // returned by select_cpu(), negative if no CPU is idle
+        nr_failures: usize, // amount of dispatches failing before the next one succeeds
+        nr_cycles: usize,   // scheduling cycles before exited() returns true
+        dispatched: Vec<DispatchedTask>,
+        nr_pending: Vec<u64>, // values reported by notify_complete()
+        nr_sched_congested: u64,
+        nr_bounce_dispatches: u64,
+    }
+
+    impl TestBackend {
+        fn new(tasks: impl IntoIterator<Item = QueuedTask>, idle_cpu: i32) -> Self {
+            Self {
+                queued: tasks.into_iter().collect(),
+                idle_cpu,
+                ..Default::default()
+            }
+        }
+
+        fn dispatched_pids(&self) -> Vec<i32> {
+            self.dispatched.iter().map(|task| task.pid).collect()
+        }
+    }
+
+    impl SchedBackend for TestBackend {
+        fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+            Ok(self.queued.pop_front())
+        }
+
+        fn select_cpu(&mut self, _pid: i32, _prev_cpu: i32, _flags: u64) -> i32 {
+            self.idle_cpu
+        }
+
+        fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+            if self.nr_failures > 0 {
+                self.nr_failures -= 1;
+                anyhow::bail!("dispatch ring buffer full");
+            }
+            self.dispatched.push(task.clone());
+            Ok(())
+        }
+
+        fn notify_complete(&mut self, nr_pending: u64) {
+            self.nr_pending.push(nr_pending);
+        }
+
+        fn exited(&mut self) -> bool {
+            if self.nr_cycles == 0 {
+                return true;
+            }
+            self.nr_cycles -= 1;
+            false
+        }
+
+        fn nr_sched_congested(&mut self) -> u64 {
+            self.nr_sched_congested
+        }
+
+        fn nr_bounce_dispatches(&mut self) -> u64 {
+            self.nr_bounce_dispatches
+        }
+
+        fn now_ns(&mut self) -> u64 {
+            0
+        }
+    }
+
+    // FifoPolicy sending all the tasks with an idle CPU to a fixed CPU.
+    struct PinnedPolicy {
+        fifo: FifoPolicy,
+        cpu: i32,
+    }
+
+    impl SchedPolicy for PinnedPolicy {
+        fn on_enqueue(&mut self, task: QueuedTask) {
+            self.fifo.on_enqueue(task);
+        }
+
+        fn pick_next(&mut self) -> Option<DispatchedTask> {
+            self.fifo.pick_next()
+        }
+
+        fn nr_queued(&self) -> usize {
+            self.fifo.nr_queued()
+        }
+
+        fn on_cpu_idle(&mut self, _task: &DispatchedTask, _cpu: i32) -> i32 {
+            self.cpu
+        }
+    }
+
+    fn task(pid: i32, weight: u64) -> QueuedTask {
+        QueuedTask {
+            pid,
+            cpu: 0,
+            nr_cpus_allowed: 1,
+            flags: 0,
+            start_ts: 0,
+            stop_ts: 0,
+            exec_runtime: 0,
+            weight,
+            vtime: 0,
+            enq_cnt: 0,
+            comm: [0; 16],
+            tgid: pid,
+            waker_pid: 0,
+            cgroup_id: 0,
+            nvcsw: 0,
+            nivcsw: 0,
+            numa_node: 0,
+        }
+    }
+
+    #[test]
+    fn runtime_dispatches_in_batches() {
+        let backend = TestBackend::new((1..=3).map(|pid| task(pid, 100)), -1);
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(MSEC)).dispatch_batch(2);
+
+        runtime.schedule();
+        assert_eq!(runtime.backend().dispatched_pids(), [1, 2]);
+        assert_eq!(runtime.backend().nr_pending, [1]);
+
+        runtime.schedule();
+        assert_eq!(runtime.backend().dispatched_pids(), [1, 2, 3]);
+        assert_eq!(runtime.backend().nr_pending, [1, 0]);
+        assert!(runtime
+            .backend()
+            .dispatched
+            .iter()
+            .all(|task| task.cpu == RL_CPU_ANY && task.slice_ns == MSEC));
+    }
+
+    #[test]
+    fn runtime_lets_policy_pick_idle_cpu() {
+        let backend = TestBackend::new([task(1, 100)], 3);
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(0));
+        runtime.schedule();
+        assert_eq!(runtime.backend().dispatched[0].cpu, 3);
+
+        let backend = TestBackend::new([task(1, 100)], 3);
+        let policy = PinnedPolicy {
+            fifo: FifoPolicy::new(0),
+            cpu: 5,
+        };
+        let mut runtime = SchedRuntime::new(backend, policy);
+        runtime.schedule();
+        assert_eq!(runtime.backend().dispatched[0].cpu, 5);
+    }
+
+    #[test]
+    fn runtime_retries_failed_dispatches() {
+        let mut backend = TestBackend::new([task(1, 100), task(2, 100)], -1);
+        backend.nr_failures = 1;
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(0)).dispatch_batch(2);
+
+        runtime.schedule();
+        assert!(runtime.backend().dispatched.is_empty());
+        assert_eq!(runtime.nr_pending(), 2);
+        assert_eq!(runtime.backend().nr_pending, [2]);
+
+        runtime.schedule();
+        assert_eq!(runtime.backend().dispatched_pids(), [1, 2]);
+        assert_eq!(
+            runtime.stats(),
+            RuntimeStats {
+                nr_enqueued: 2,
+                nr_dispatched: 2,
+                nr_dispatch_retries: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn runtime_keeps_previous_cpu_without_idle_selection() {
+        let backend = TestBackend::new(
+            [
+                QueuedTask {
+                    cpu: 3,
+                    ..task(1, 100)
+                },
+                QueuedTask {
+                    cpu: 5,
+                    ..task(2, 100)
+                },
+            ],
+            0,
+        );
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(0))
+            .dispatch_batch(usize::MAX)
+            .select_idle_cpu(false);
+
+        runtime.schedule();
+        let cpus: Vec<_> = runtime
+            .backend()
+            .dispatched
+            .iter()
+            .map(|task| task.cpu)
+            .collect();
+        assert_eq!(cpus, [3, 5]);
+    }
+
+    #[test]
+    fn runtime_reports_backend_counters_since_start() {
+        let mut backend = TestBackend::new([], -1);
+        backend.nr_sched_congested = 5;
+        backend.nr_bounce_dispatches = 7;
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(0));
+
+        runtime.backend_mut().nr_sched_congested = 8;
+        runtime.backend_mut().nr_bounce_dispatches = 8;
+        let stats = runtime.stats();
+        assert_eq!(stats.nr_sched_congested, 3);
+        assert_eq!(stats.nr_bounce_dispatches, 1);
+    }
+
+    #[test]
+    fn runtime_runs_until_backend_exits() -> Result<()> {
+        let mut backend = TestBackend::new([], -1);
+        backend.nr_cycles = 3;
+        let mut runtime = SchedRuntime::new(backend, FifoPolicy::new(0));
+
+        let mut nr_cycles = 0;
+        runtime.run_with(|_| {
+            nr_cycles += 1;
+            Ok(())
+        })?;
+        assert_eq!(nr_cycles, 3);
+        assert_eq!(runtime.backend().nr_pending.len(), 3);
+
+        runtime.backend_mut().nr_cycles = 3;
+        assert!(runtime
+            .run_with(|_| Err(anyhow::anyhow!("stats server failed")))
+            .is_err());
+        assert_eq!(runtime.backend().nr_pending.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn vruntime_charges_runtime_by_weight() {
+        let mut policy = VruntimePolicy::new(10 * MSEC);
+        for (pid, weight) in [(1, 100), (2, 200)] {
+            policy.on_enqueue(QueuedTask {
+                stop_ts: 4 * MSEC,
+                vtime: 10 * MSEC,
+                ..task(pid, weight)
+            });
+        }
+        assert_eq!(policy.nr_queued(), 2);
+
+        let first = policy.pick_next().unwrap();
+        assert_eq!(
+            (first.pid, first.vtime, first.slice_ns),
+            (2, 12 * MSEC, 10 * MSEC)
+        );
+        let second = policy.pick_next().unwrap();
+        assert_eq!((second.pid, second.vtime), (1, 14 * MSEC));
+        assert!(policy.pick_next().is_none());
+    }
+
+    #[test]
+    fn vruntime_limits_sleeping_credit() {
+        let mut policy = VruntimePolicy::new(MSEC);
+        policy.on_enqueue(QueuedTask {
+            stop_ts: 10 * MSEC,
+            ..task(1, 100)
+        });
+        policy.on_enqueue(QueuedTask {
+            vtime: 1,
+            ..task(2, 100)
+        });
+
+        let first = policy.pick_next().unwrap();
+        assert_eq!((first.pid, first.vtime), (2, 9 * MSEC));
+    }
+
+    #[test]
+    fn deadline_favors_short_bursts() {
+        let mut policy = DeadlinePolicy::new(10 * MSEC, MSEC);
+        policy.on_enqueue(QueuedTask {
+            exec_runtime: 50 * MSEC,
+            ..task(1, 100)
+        });
+        policy.on_enqueue(QueuedTask {
+            exec_runtime: MSEC,
+            ..task(2, 200)
+        });
+        policy.on_enqueue(QueuedTask {
+            exec_runtime: 10_000 * MSEC,
+            ..task(3, 100)
+        });
+
+        let picked: Vec<_> = std::iter::from_fn(|| policy.pick_next())
+            .map(|picked| (picked.pid, picked.vtime, picked.slice_ns))
+            .collect();
+        assert_eq!(
+            picked,
+            [
+                (2, MSEC, 2 * MSEC),
+                (1, 50 * MSEC, MSEC),
+                // exec_runtime is capped to 100 time slices.
+                (3, 1000 * MSEC, MSEC),
+            ]
+        );
+    }
+}
@@ -0,0 +1,721 @@
+// Copyright (c) Andrea Righi <andrea.righi@linux.dev>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Deterministic simulator for user-space scheduling policies.
+//!
+//! The simulator implements SchedBackend on top of a modeled set of CPUs, so that a SchedPolicy
+//! can be exercised by a SchedRuntime without BPF, root privileges or a sched_ext kernel:
+//!
+//! ```ignore
+//! let (topo, _) = scx_utils::testutils::make_test_topo(1, 1, 2, 1);
+//! let report = Simulator::new(&topo)
+//!     .task(SimTask::new(1, "batch", 100).arrival(0, 50_000_000))
+//!     .task(SimTask::new(2, "interactive", 100).periodic(0, 10_000_000, 1_000_000, 10))
+//!     .run(DeadlinePolicy::new(20_000_000, 1_000_000));
+//! assert!(report.task(2).unwrap().max_wait_ns < 5_000_000);
+//! ```
+//!
+//! The model is intentionally simple: the user-space scheduler runs in zero time every time
+//! something happens (task wakeup, slice expiration, task completion), tasks always run until
+//! their slice expires or their work is done, and dispatch queues are ordered by vtime like the
+//! DSQs of the BPF component.
+//!
+//! Tasks created from recorded QueuedTask items (SimTask::from_queued()) start from the recorded
+//! CPU, vtime and last run duration (stop_ts - start_ts), charged at their first enqueue. The
+//! recorded timestamps are not replayed as arrivals, as a single item doesn't tell when the task
+//! wakes up again or how much work it does, and exec_runtime is not carried over because every
+//! arrival is a wakeup from sleep, which resets it.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use libc::c_char;
+use scx_utils::Topology;
+
+use crate::policy::RuntimeStats;
+use crate::policy::SchedBackend;
+use crate::policy::SchedPolicy;
+use crate::policy::SchedRuntime;
+use crate::task::DispatchedTask;
+use crate::task::QueuedTask;
+
+// Default time slice of the simulated BPF component.
+const DEFAULT_SLICE_NS: u64 = 5_000_000;
+
+/// A simulated task and the work it receives over time.
+#[derive(Debug, Clone)]
+pub struct SimTask {
+    pub pid: i32,
+    pub comm: String,
+    pub weight: u64,
+    pub arrivals: Vec<(u64, u64)>, // (wakeup time, amount of work in ns)
+    pub cpu: i32,                  // CPU used before the simulation starts
+    pub vtime: u64,                // vtime before the simulation starts (0 = new task)
+    pub last_run_ns: u64,          // duration of the last run before the simulation starts
+}
+
+impl SimTask {
+    pub fn new(pid: i32, comm: &str, weight: u64) -> Self {
+        Self {
+            pid,
+            comm: comm.to_string(),
+            weight,
+            arrivals: Vec::new(),
+            cpu: 0,
+            vtime: 0,
+            last_run_ns: 0,
+        }
+    }
+
+    /// Create a task from a recorded QueuedTask, carrying its CPU, vtime and last run duration.
+    /// Arrivals can then be replayed with arrival().
+    pub fn from_queued(task: &QueuedTask) -> Self {
+        Self {
+            cpu: task.cpu,
+            vtime: task.vtime,
+            last_run_ns: task.stop_ts.saturating_sub(task.start_ts),
+            ..Self::new(task.pid, &task.comm_str(), task.weight)
+        }
+    }
+
+    /// Wake up the task at time_ns with runtime_ns of work to do. If the task is still runnable
+    /// at that time the work is added to the pending one.
+    pub fn arrival(mut self, time_ns: u64, runtime_ns: u64) -> Self {
+        self.arrivals.push((time_ns, runtime_ns));
+        self
+    }
+
+    /// Wake up the task count times every period_ns, starting at start_ns, with runtime_ns of
+    /// work to do each time.
+    pub fn periodic(mut self, start_ns: u64, period_ns: u64, runtime_ns: u64, count: u64) -> Self {
+        for i in 0..count {
+            self.arrivals.push((start_ns + i * period_ns, runtime_ns));
+        }
+        self
+    }
+}
+
+/// Per-task simulation results.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TaskReport {
+    pub pid: i32,
+    pub comm: String,
+    pub weight: u64,
+    pub runtime_ns: u64,  // total CPU time received
+    pub wait_ns: u64,     // total time spent runnable but not running
+    pub max_wait_ns: u64, // longest time spent runnable before running
+    pub nr_runs: u64,     // amount of times the task has been put on a CPU
+    pub pending_ns: u64,  // work still to do when the simulation ended
+}
+
+impl TaskReport {
+    /// Average time spent waiting for a CPU.
+    pub fn avg_wait_ns(&self) -> u64 {
+        self.wait_ns.checked_div(self.nr_runs).unwrap_or(0)
+    }
+}
+
+/// Simulation results.
+#[derive(Debug, Default, Clone)]
+pub struct SimReport {
+    pub duration_ns: u64,
+    pub tasks: BTreeMap<i32, TaskReport>,
+    pub cpu_busy_ns: Vec<u64>, // busy time of each simulated CPU
+    pub nr_cancelled: u64,     // dispatches dropped because the task was re-enqueued
+    pub stats: RuntimeStats,   // counters of the SchedRuntime that drove the policy
+}
+
+impl SimReport {
+    pub fn task(&self, pid: i32) -> Option<&TaskReport> {
+        self.tasks.get(&pid)
+    }
+
+    /// Fraction of the total CPU capacity used during the simulation.
+    pub fn cpu_utilization(&self) -> f64 {
+        let capacity = self.duration_ns as f64 * self.cpu_busy_ns.len() as f64;
+        if capacity == 0.0 {
+            return 0.0;
+        }
+        self.cpu_busy_ns.iter().sum::<u64>() as f64 / capacity
+    }
+
+    /// Jain's fairness index of the weight-scaled runtime of the given tasks: 1.0 when all tasks
+    /// received CPU time proportional to their weight, down to 1/n when a single task got all of
+    /// it. Only meaningful for tasks that competed for the CPUs the whole time.
+    pub fn fairness(&self, pids: &[i32]) -> f64 {
+        let shares: Vec<f64> = pids
+            .iter()
+            .filter_map(|pid| self.tasks.get(pid))
+            .map(|task| task.runtime_ns as f64 * 100.0 / task.weight.max(1) as f64)
+            .collect();
+        let sum: f64 = shares.iter().sum();
+        let sum_sq: f64 = shares.iter().map(|share| share * share).sum();
+        if sum_sq == 0.0 {
+            return 1.0;
+        }
+        sum * sum / (shares.len() as f64 * sum_sq)
+    }
+}
+
+/// Simulate a set of tasks on the CPUs of a topology.
+pub struct Simulator {
+    cpu_llcs: Vec<usize>,
+    tasks: Vec<SimTask>,
+    slice_ns: u64,
+    duration_ns: Option<u64>,
+}
+
+impl Simulator {
+    /// Model all the CPUs of the topology (e.g., from scx_utils::testutils::make_test_topo()).
+    pub fn new(topo: &Topology) -> Self {
+        Self {
+            cpu_llcs: topo.all_cpus.values().map(|cpu| cpu.llc_id).collect(),
+            tasks: Vec::new(),
+            slice_ns: DEFAULT_SLICE_NS,
+            duration_ns: None,
+        }
+    }
+
+    pub fn task(mut self, task: SimTask) -> Self {
+        self.tasks.push(task);
+        self
+    }
+
+    /// Time slice used for tasks dispatched with slice_ns == 0.
+    pub fn slice_ns(mut self, slice_ns: u64) -> Self {
+        self.slice_ns = slice_ns;
+        self
+    }
+
+    /// Stop the simulation at duration_ns, otherwise run until all the work is done.
+    pub fn duration_ns(mut self, duration_ns: u64) -> Self {
+        self.duration_ns = Some(duration_ns);
+        self
+    }
+
+    /// Run the simulation, driving the policy with a SchedRuntime.
+    pub fn run<P: SchedPolicy>(&self, policy: P) -> SimReport {
+        let mut arrivals: Vec<(u64, usize, i32, u64)> = Vec::new();
+        for task in &self.tasks {
+            for (time_ns, runtime_ns) in &task.arrivals {
+                arrivals.push((*time_ns, arrivals.len(), task.pid, *runtime_ns));
+            }
+        }
+        arrivals.sort();
+        let mut arrivals = arrivals.into_iter().peekable();
+
+        let backend = SimBackend::new(&self.cpu_llcs, &self.tasks, self.slice_ns);
+        let mut runtime = SchedRuntime::new(backend, policy);
+
+        loop {
+            let now_ns = runtime.backend().now_ns;
+            while let Some((_, _, pid, runtime_ns)) =
+                arrivals.next_if(|(time_ns, ..)| *time_ns <= now_ns)
+            {
+                runtime.backend_mut().wakeup(pid, runtime_ns);
+            }
+
+            // CPUs that became idle consume the tasks already dispatched before the scheduler
+            // gets a chance to process the new ones, like the BPF dispatcher does.
+            runtime.backend_mut().fill_idle_cpus();
+
+            // Let the scheduler dispatch everything that is runnable.
+            while runtime.backend().has_queued() || runtime.nr_pending() > 0 {
+                let before = runtime.stats();
+                runtime.schedule();
+                if runtime.stats() == before && !runtime.backend().has_queued() {
+                    // The policy reports pending tasks that it doesn't return.
+                    break;
+                }
+            }
+            runtime.backend_mut().fill_idle_cpus();
+
+            let next_ns = [
+                arrivals.peek().map(|(time_ns, ..)| *time_ns),
+                runtime.backend().next_completion_ns(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            let Some(next_ns) = next_ns else {
+                break;
+            };
+            if let Some(duration_ns) = self.duration_ns {
+                if next_ns > duration_ns {
+                    runtime.backend_mut().advance(duration_ns);
+                    break;
+                }
+            }
+            runtime.backend_mut().advance(next_ns);
+        }
+
+        let stats = runtime.stats();
+        let (backend, _) = runtime.into_parts();
+        backend.into_report(stats)
+    }
+}
+
+#[derive(Debug)]
+struct SimTaskState {
+    report: TaskReport,
+    comm: [c_char; 16],
+    remaining_ns: u64,           // work left to do
+    cpu: i32,                    // last CPU used by the task
+    start_ts: u64,               // last time the task started running
+    stop_ts: u64,                // last time the task stopped running
+    exec_runtime: u64,           // CPU time since the last sleep
+    vtime: u64,                  // vtime of the last dispatch
+    enq_cnt: u64,                // enqueue generation, used to drop stale dispatches
+    runnable_since: Option<u64>, // Some(time) while waiting for a CPU
+    running: bool,
+    dispatched: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SimRun {
+    pid: i32,
+    start_ns: u64,
+    end_ns: u64,
+}
+
+#[derive(Debug, Default)]
+struct SimCpu {
+    llc_id: usize,
+    running: Option<SimRun>,
+    local: BTreeMap<(u64, u64), (i32, u64)>, // (vtime, seq) -> (pid, slice_ns)
+    busy_ns: u64,
+}
+
+impl SimCpu {
+    fn is_idle(&self) -> bool {
+        self.running.is_none() && self.local.is_empty()
+    }
+}
+
+// SchedBackend modeling the BPF component and the CPUs.
+struct SimBackend {
+    now_ns: u64,
+    slice_ns: u64,
+    cpus: Vec<SimCpu>,
+    shared: BTreeMap<(u64, u64), (i32, u64)>, // (vtime, seq) -> (pid, slice_ns)
+    queued: VecDeque<QueuedTask>,             // tasks waiting to be received by the scheduler
+    tasks: BTreeMap<i32, SimTaskState>,
+    seq: u64,
+    nr_cancelled: u64,
+}
+
+impl SimBackend {
+    fn new(cpu_llcs: &[usize], tasks: &[SimTask], slice_ns: u64) -> Self {
+        let tasks = tasks
+            .iter()
+            .map(|task| {
+                let mut comm = [0 as c_char; 16];
+                for (dst, src) in comm.iter_mut().zip(task.comm.bytes().take(15)) {
+                    *dst = src as c_char;
+                }
+                let state = SimTaskState {
+                    report: TaskReport {
+                        pid: task.pid,
+                        comm: task.comm.clone(),
+                        weight: task.weight,
+                        ..Default::default()
+                    },
+                    comm,
+                    remaining_ns: 0,
+                    cpu: if usize::try_from(task.cpu).is_ok_and(|cpu| cpu < cpu_llcs.len()) {
+                        task.cpu
+                    } else {
+                        0
+                    },
+                    start_ts: 0,
+                    stop_ts: task.last_run_ns,
+                    exec_runtime: 0,
+                    vtime: task.vtime,
+                    enq_cnt: 0,
+                    runnable_since: None,
+                    running: false,
+                    dispatched: false,
+                };
+                (task.pid, state)
+            })
+            .collect();
+
+        Self {
+            now_ns: 0,
+            slice_ns,
+            cpus: cpu_llcs
+                .iter()
+                .map(|llc_id| SimCpu {
+                    llc_id: *llc_id,
+                    ..Default::default()
+                })
+                .collect(),
+            shared: BTreeMap::new(),
+            queued: VecDeque::new(),
+            tasks,
+            seq: 0,
+            nr_cancelled: 0,
+        }
+    }
+
+    fn has_queued(&self) -> bool {
+        !self.queued.is_empty()
+    }
+
+    // Send a runnable task to the user-space scheduler.
+    fn enqueue(&mut self, pid: i32) {
+        let nr_cpus = self.cpus.len() as u64;
+        let Some(state) = self.tasks.get_mut(&pid) else {
+            return;
+        };
+
+        state.enq_cnt += 1;
+        state.dispatched = false;
+        state.runnable_since.get_or_insert(self.now_ns);
+        self.queued.push_back(QueuedTask {
+            pid,
+            cpu: state.cpu,
+            nr_cpus_allowed: nr_cpus,
+            flags: 0,
+            start_ts: state.start_ts,
+            stop_ts: state.stop_ts,
+            exec_runtime: state.exec_runtime,
+            weight: state.report.weight,
+            vtime: state.vtime,
+            enq_cnt: state.enq_cnt,
+            comm: state.comm,
+            tgid: pid,
+            waker_pid: 0,
+            cgroup_id: 0,
+            nvcsw: 0,
+            nivcsw: 0,
+            numa_node: 0,
+        });
+    }
+
+    fn wakeup(&mut self, pid: i32, runtime_ns: u64) {
+        let Some(state) = self.tasks.get_mut(&pid) else {
+            return;
+        };
+
+        let sleeping = state.remaining_ns == 0 && !state.running;
+        state.remaining_ns += runtime_ns;
+        if sleeping {
+            state.exec_runtime = 0;
+            self.enqueue(pid);
+        }
+    }
+
+    // Put the next task from the local or shared DSQ on each idle CPU.
+    fn fill_idle_cpus(&mut self) {
+        for cpu in 0..self.cpus.len() {
+            if self.cpus[cpu].running.is_some() {
+                continue;
+            }
+            let Some((_, (pid, slice_ns))) = self.cpus[cpu]
+                .local
+                .pop_first()
+                .or_else(|| self.shared.pop_first())
+            else {
+                continue;
+            };
+
+            let now_ns = self.now_ns;
+            let state = self.tasks.get_mut(&pid).expect("dispatched unknown task");
+            let wait_ns = now_ns - state.runnable_since.take().unwrap_or(now_ns);
+            state.report.wait_ns += wait_ns;
+            state.report.max_wait_ns = state.report.max_wait_ns.max(wait_ns);
+            state.report.nr_runs += 1;
+            state.running = true;
+            state.cpu = cpu as i32;
+            state.start_ts = now_ns;
+
+            self.cpus[cpu].running = Some(SimRun {
+                pid,
+                start_ns: now_ns,
+                end_ns: now_ns + state.remaining_ns.min(slice_ns.max(1)),
+            });
+        }
+    }
+
+    fn next_completion_ns(&self) -> Option<u64> {
+        self.cpus
+            .iter()
+            .filter_map(|cpu| cpu.running.map(|run| run.end_ns))
+            .min()
+    }
+
+    // Advance the simulation time, stopping the tasks whose run ends by then.
+    fn advance(&mut self, now_ns: u64) {
+        self.now_ns = now_ns;
+
+        for cpu in 0..self.cpus.len() {
+            let Some(run) = self.cpus[cpu].running else {
+                continue;
+            };
+            let end_ns = run.end_ns.min(now_ns);
+            let ran_ns = end_ns - run.start_ns;
+
+            let state = self.tasks.get_mut(&run.pid).expect("running unknown task");
+            state.remaining_ns -= ran_ns;
+            state.exec_runtime += ran_ns;
+            state.report.runtime_ns += ran_ns;
+            self.cpus[cpu].busy_ns += ran_ns;
+
+            if run.end_ns > now_ns {
+                // Still running, account the elapsed time only.
+                self.cpus[cpu].running = Some(SimRun {
+                    start_ns: now_ns,
+                    ..run
+                });
+                continue;
+            }
+
+            state.running = false;
+            state.stop_ts = end_ns;
+            self.cpus[cpu].running = None;
+            if state.remaining_ns > 0 {
+                // Slice expired, send the task back to the scheduler.
+                self.enqueue(run.pid);
+            }
+        }
+    }
+
+    // Pick an idle CPU: prev_cpu, then an idle CPU in the same LLC, then any idle CPU.
+    fn pick_idle_cpu(&self, prev_cpu: i32) -> Option<usize> {
+        let prev = usize::try_from(prev_cpu)
+            .ok()
+            .filter(|cpu| *cpu < self.cpus.len());
+        if let Some(prev) = prev {
+            if self.cpus[prev].is_idle() {
+                return Some(prev);
+            }
+        }
+
+        let llc_id = prev.map(|cpu| self.cpus[cpu].llc_id);
+        let mut idle = self
+            .cpus
+            .iter()
+            .enumerate()
+            .filter(|(_, cpu)| cpu.is_idle());
+        let first_idle = idle.clone().next().map(|(id, _)| id);
+        idle.find(|(_, cpu)| Some(cpu.llc_id) == llc_id)
+            .map(|(id, _)| id)
+            .or(first_idle)
+    }
+
+    fn into_report(self, stats: RuntimeStats) -> SimReport {
+        SimReport {
+            duration_ns: self.now_ns,
+            tasks: self
+                .tasks
+                .into_iter()
+                .map(|(pid, state)| {
+                    let mut report = state.report;
+                    report.pending_ns = state.remaining_ns;
+                    (pid, report)
+                })
+                .collect(),
+            cpu_busy_ns: self.cpus.iter().map(|cpu| cpu.busy_ns).collect(),
+            nr_cancelled: self.nr_cancelled,
+            stats,
+        }
+    }
+}
+
+impl SchedBackend for SimBackend {
+    fn dequeue_task(&mut self) -> Result<Option<QueuedTask>, i32> {
+        Ok(self.queued.pop_front())
+    }
+
+    fn select_cpu(&mut self, _pid: i32, prev_cpu: i32, _flags: u64) -> i32 {
+        self.pick_idle_cpu(prev_cpu).map_or(-1, |cpu| cpu as i32)
+    }
+
+    fn dispatch_task(&mut self, task: &DispatchedTask) -> Result<()> {
+        let Some(state) = self.tasks.get_mut(&task.pid) else {
+            self.nr_cancelled += 1;
+            return Ok(());
+        };
+        if task.enq_cnt != state.enq_cnt || state.dispatched || state.running {
+            // Stale dispatch, the task has been re-enqueued in the meantime.
+            self.nr_cancelled += 1;
+            return Ok(());
+        }
+
+        state.dispatched = true;
+        state.vtime = task.vtime;
+        let slice_ns = if task.slice_ns == 0 {
+            self.slice_ns
+        } else {
+            task.slice_ns
+        };
+        let key = (task.vtime, self.seq);
+        self.seq += 1;
+
+        match usize::try_from(task.cpu) {
+            Ok(cpu) if cpu < self.cpus.len() => {
+                self.cpus[cpu].local.insert(key, (task.pid, slice_ns));
+            }
+            _ => {
+                self.shared.insert(key, (task.pid, slice_ns));
+            }
+        }
+        Ok(())
+    }
+
+    fn notify_complete(&mut self, _nr_pending: u64) {}
+
+    fn exited(&mut self) -> bool {
+        false
+    }
+
+    fn nr_sched_congested(&mut self) -> u64 {
+        0
+    }
+
+    fn nr_bounce_dispatches(&mut self) -> u64 {
+        0
+    }
+
+    fn now_ns(&mut self) -> u64 {
+        self.now_ns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::DeadlinePolicy;
+    use crate::policy::FifoPolicy;
+    use crate::policy::VruntimePolicy;
+    use scx_utils::testutils::make_test_topo;
+
+    const MSEC: u64 = 1_000_000;
+
+    #[test]
+    fn fifo_runs_tasks_in_arrival_order() {
+        let (topo, _) = make_test_topo(1, 1, 1, 1);
+        let report = Simulator::new(&topo)
+            .task(SimTask::new(1, "a", 100).arrival(0, 10 * MSEC))
+            .task(SimTask::new(2, "b", 100).arrival(MSEC, 10 * MSEC))
+            .run(FifoPolicy::new(20 * MSEC));
+
+        assert_eq!(report.duration_ns, 20 * MSEC);
+        assert_eq!(report.task(1).unwrap().wait_ns, 0);
+        assert_eq!(report.task(2).unwrap().wait_ns, 9 * MSEC);
+        assert_eq!(report.cpu_utilization(), 1.0);
+        assert_eq!(report.stats.nr_dispatched, 2);
+    }
+
+    #[test]
+    fn recorded_tasks_keep_their_vtime() {
+        let recorded = QueuedTask {
+            pid: 1,
+            cpu: 0,
+            nr_cpus_allowed: 1,
+            flags: 0,
+            start_ts: 1000 * MSEC,
+            stop_ts: 1004 * MSEC,
+            exec_runtime: 4 * MSEC,
+            weight: 100,
+            vtime: 100 * MSEC,
+            enq_cnt: 7,
+            comm: [0; 16],
+            tgid: 1,
+            waker_pid: 0,
+            cgroup_id: 0,
+            nvcsw: 0,
+            nivcsw: 0,
+            numa_node: 0,
+        };
+        let task = SimTask::from_queued(&recorded);
+        assert_eq!(
+            (task.cpu, task.vtime, task.last_run_ns),
+            (0, 100 * MSEC, 4 * MSEC)
+        );
+
+        // The recorded task is behind a new one, even if it wakes up first.
+        let (topo, _) = make_test_topo(1, 1, 1, 1);
+        let report = Simulator::new(&topo)
+            .task(task.arrival(0, 10 * MSEC))
+            .task(SimTask::new(2, "new", 100).arrival(0, 10 * MSEC))
+            .run(VruntimePolicy::new(20 * MSEC));
+
+        assert_eq!(report.task(2).unwrap().wait_ns, 0);
+        assert_eq!(report.task(1).unwrap().wait_ns, 10 * MSEC);
+    }
+
+    #[test]
+    fn idle_cpus_are_used_before_queueing() {
+        let (topo, nr_cpus) = make_test_topo(1, 2, 2, 1);
+        let mut sim = Simulator::new(&topo);
+        for pid in 0..nr_cpus as i32 {
+            sim = sim.task(SimTask::new(pid + 1, "worker", 100).arrival(0, 5 * MSEC));
+        }
+        let report = sim.run(FifoPolicy::new(0));
+
+        assert_eq!(report.duration_ns, 5 * MSEC);
+        assert!(report.tasks.values().all(|task| task.wait_ns == 0));
+        assert!(report.cpu_busy_ns.iter().all(|busy| *busy == 5 * MSEC));
+    }
+
+    #[test]
+    fn vruntime_shares_cpu_fairly() {
+        let (topo, _) = make_test_topo(1, 1, 2, 1);
+        let mut sim = Simulator::new(&topo).duration_ns(300 * MSEC);
+        for pid in 1..=5 {
+            sim = sim.task(SimTask::new(pid, "worker", 100).arrival(0, 1000 * MSEC));
+        }
+        let report = sim.run(VruntimePolicy::new(5 * MSEC));
+
+        assert_eq!(report.cpu_utilization(), 1.0);
+        assert!(report.fairness(&[1, 2, 3, 4, 5]) > 0.99);
+    }
+
+    #[test]
+    fn deadline_scales_slice_by_weight() {
+        let (topo, _) = make_test_topo(1, 1, 1, 1);
+        let report = Simulator::new(&topo)
+            .task(SimTask::new(1, "light", 100).arrival(0, 1000 * MSEC))
+            .task(SimTask::new(2, "heavy", 200).arrival(0, 1000 * MSEC))
+            .duration_ns(300 * MSEC)
+            .run(DeadlinePolicy::new(20 * MSEC, MSEC));
+
+        let light = report.task(1).unwrap().runtime_ns;
+        let heavy = report.task(2).unwrap().runtime_ns;
+        assert_eq!(light + heavy, 300 * MSEC);
+        assert!(heavy > light * 3 / 2, "light={light} heavy={heavy}");
+        assert!(report.fairness(&[1, 2]) > 0.95);
+    }
+
+    #[test]
+    fn deadline_favors_interactive_tasks() {
+        let (topo, _) = make_test_topo(1, 1, 1, 1);
+        let workload = |sim: Simulator| {
+            sim.task(SimTask::new(1, "batch-1", 100).arrival(0, 1000 * MSEC))
+                .task(SimTask::new(2, "batch-2", 100).arrival(0, 1000 * MSEC))
+                .task(SimTask::new(3, "interactive", 100).periodic(
+                    MSEC / 2,
+                    10 * MSEC,
+                    MSEC / 2,
+                    20,
+                ))
+                .duration_ns(200 * MSEC)
+        };
+
+        let fifo = workload(Simulator::new(&topo)).run(FifoPolicy::new(20 * MSEC));
+        let deadline = workload(Simulator::new(&topo)).run(DeadlinePolicy::new(20 * MSEC, MSEC));
+
+        let fifo_wait = fifo.task(3).unwrap().avg_wait_ns();
+        let deadline_wait = deadline.task(3).unwrap().avg_wait_ns();
+        assert!(
+            deadline_wait < fifo_wait,
+            "deadline={deadline_wait} fifo={fifo_wait}"
+        );
+        assert_eq!(deadline.cpu_utilization(), 1.0);
+    }
+}
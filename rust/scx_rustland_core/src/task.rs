@@ -0,0 +1,178 @@
+// Copyright (c) Andrea Righi <andrea.righi@linux.dev>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use std::ffi::CStr;
+
+use libc::c_char;
+
+use crate::bpf_intf;
+
+const TASK_COMM_LEN: usize = 16;
+
+// Size of a queued_task_ctx sent without the extended task context (see
+// QUEUED_TASK_CTX_BASE_SIZE in assets/bpf/main.bpf.c).
+const QUEUED_TASK_CTX_BASE_SIZE: usize = std::mem::offset_of!(bpf_intf::queued_task_ctx, tgid);
+
+// Allow to dispatch the task on any CPU.
+//
+// The task will be dispatched to the global shared DSQ and it will run on the first CPU available.
+pub const RL_CPU_ANY: i32 = bpf_intf::RL_CPU_ANY as i32;
+
+// Task queued for scheduling from the BPF component (see bpf_intf::queued_task_ctx).
+//
+// The extended task context (tgid, waker_pid, cgroup_id, nvcsw, nivcsw and numa_node) is
+// collected only when the scheduler enables it in BpfScheduler::init(), otherwise it is zeroed.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
+pub struct QueuedTask {
+    pub pid: i32,             // pid that uniquely identifies a task
+    pub cpu: i32,             // CPU previously used by the task
+    pub nr_cpus_allowed: u64, // Number of CPUs that the task can use
+    pub flags: u64,           // task's enqueue flags
+    pub start_ts: u64,        // Timestamp since last time the task ran on a CPU (in ns)
+    pub stop_ts: u64,         // Timestamp since last time the task released a CPU (in ns)
+    pub exec_runtime: u64,    // Total cpu time since last sleep (in ns)
+    pub weight: u64,          // Task priority in the range [1..10000] (default is 100)
+    pub vtime: u64,           // Current task vruntime / deadline (set by the scheduler)
+    pub enq_cnt: u64,
+    pub comm: [c_char; TASK_COMM_LEN], // Task's executable name
+    pub tgid: i32,                     // Thread group (process) ID
+    pub waker_pid: i32,                // PID of the task that woke up the task (0 = unknown)
+    pub cgroup_id: u64,                // ID of the task's cgroup (cgroup v2)
+    pub nvcsw: u64,                    // Voluntary context switches since last enqueue
+    pub nivcsw: u64,                   // Involuntary context switches since last enqueue
+    pub numa_node: i32,                // NUMA node of the CPU previously used by the task
+}
+
+impl QueuedTask {
+    /// Decode a queued_task_ctx item received from the BPF ring buffer.
+    ///
+    /// Items sent without the extended task context are QUEUED_TASK_CTX_BASE_SIZE bytes long,
+    /// in this case the extended fields are 0.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        debug_assert!(data.len() >= QUEUED_TASK_CTX_BASE_SIZE);
+
+        // SAFETY: queued_task_ctx is plain data, any byte pattern is a valid value.
+        let mut ctx: bpf_intf::queued_task_ctx = unsafe { std::mem::zeroed() };
+        let len = data.len().min(std::mem::size_of_val(&ctx));
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), &mut ctx as *mut _ as *mut u8, len);
+        }
+
+        QueuedTask {
+            pid: ctx.pid,
+            cpu: ctx.cpu,
+            nr_cpus_allowed: ctx.nr_cpus_allowed,
+            flags: ctx.flags,
+            start_ts: ctx.start_ts,
+            stop_ts: ctx.stop_ts,
+            exec_runtime: ctx.exec_runtime,
+            weight: ctx.weight,
+            vtime: ctx.vtime,
+            enq_cnt: ctx.enq_cnt,
+            comm: ctx.comm,
+            tgid: ctx.tgid,
+            waker_pid: ctx.waker_pid,
+            cgroup_id: ctx.cgroup_id,
+            nvcsw: ctx.nvcsw,
+            nivcsw: ctx.nivcsw,
+            numa_node: ctx.numa_node,
+        }
+    }
+
+    /// Convert the task's comm field (C char array) into a Rust String.
+    #[allow(dead_code)]
+    pub fn comm_str(&self) -> String {
+        // Convert the C char array into a Rust String
+        let c_str = unsafe { CStr::from_ptr(self.comm.as_ptr()) };
+
+        // Handle potential invalid UTF-8
+        c_str.to_string_lossy().into_owned()
+    }
+}
+
+// Task queued for dispatching to the BPF component (see bpf_intf::dispatched_task_ctx).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Clone)]
+pub struct DispatchedTask {
+    pub pid: i32,      // pid that uniquely identifies a task
+    pub cpu: i32, // target CPU selected by the scheduler (RL_CPU_ANY = dispatch on the first CPU available)
+    pub flags: u64, // task's enqueue flags
+    pub slice_ns: u64, // time slice in nanoseconds assigned to the task (0 = use default time slice)
+    pub vtime: u64, // this value can be used to send the task's vruntime or deadline directly to the underlying BPF dispatcher
+    pub enq_cnt: u64,
+}
+
+impl DispatchedTask {
+    // Create a DispatchedTask from a QueuedTask.
+    //
+    // A dispatched task should be always originated from a QueuedTask (there is no reason to
+    // dispatch a task if it wasn't queued to the scheduler earlier).
+    pub fn new(task: &QueuedTask) -> Self {
+        DispatchedTask {
+            pid: task.pid,
+            cpu: task.cpu,
+            flags: task.flags,
+            slice_ns: 0, // use default time slice
+            vtime: 0,
+            enq_cnt: task.enq_cnt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued_task_ctx() -> bpf_intf::queued_task_ctx {
+        let mut ctx: bpf_intf::queued_task_ctx = unsafe { std::mem::zeroed() };
+        ctx.pid = 42;
+        ctx.cpu = 3;
+        ctx.weight = 100;
+        ctx.enq_cnt = 7;
+        for (dst, src) in ctx.comm.iter_mut().zip(b"worker") {
+            *dst = *src as c_char;
+        }
+        ctx.tgid = 40;
+        ctx.waker_pid = 1;
+        ctx.cgroup_id = 1234;
+        ctx.nvcsw = 5;
+        ctx.nivcsw = 2;
+        ctx.numa_node = 1;
+        ctx
+    }
+
+    fn as_bytes(ctx: &bpf_intf::queued_task_ctx) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(ctx as *const _ as *const u8, std::mem::size_of_val(ctx))
+        }
+    }
+
+    #[test]
+    fn decode_base_queued_task_ctx() {
+        let ctx = queued_task_ctx();
+        let task = QueuedTask::from_bytes(&as_bytes(&ctx)[..QUEUED_TASK_CTX_BASE_SIZE]);
+
+        assert_eq!(
+            (task.pid, task.cpu, task.weight, task.enq_cnt),
+            (42, 3, 100, 7)
+        );
+        assert_eq!(task.comm_str(), "worker");
+        assert_eq!((task.tgid, task.waker_pid, task.cgroup_id), (0, 0, 0));
+        assert_eq!((task.nvcsw, task.nivcsw, task.numa_node), (0, 0, 0));
+    }
+
+    #[test]
+    fn decode_extended_queued_task_ctx() {
+        let ctx = queued_task_ctx();
+        let task = QueuedTask::from_bytes(as_bytes(&ctx));
+
+        assert_eq!(
+            (task.pid, task.cpu, task.weight, task.enq_cnt),
+            (42, 3, 100, 7)
+        );
+        assert_eq!(task.comm_str(), "worker");
+        assert_eq!((task.tgid, task.waker_pid, task.cgroup_id), (40, 1, 1234));
+        assert_eq!((task.nvcsw, task.nivcsw, task.numa_node), (5, 2, 1));
+    }
+}
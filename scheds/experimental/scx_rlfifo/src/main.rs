@@ -26,6 +26,16 @@
 //!
 //! ## scx_rustland_core API
 //!
+//! ### trait `SchedPolicy` and struct `SchedRuntime`
+//!
+//! The scheduling loop (consume the queued tasks, pick an idle CPU, dispatch and report the
+//! pending work) is implemented by `SchedRuntime`, a scheduler only needs to decide the order and
+//! the time slice of the tasks, implementing `SchedPolicy`:
+//!
+//! - `on_enqueue(task: QueuedTask)`: Queue a task that wants to run
+//! - `pick_next()`: Return the next task to dispatch, as a DispatchedTask object
+//! - `nr_queued()`: Return the amount of tasks queued by the policy
+//!
 //! ### struct `BpfScheduler`
 //!
 //! The `BpfScheduler` struct is the core interface for interacting with `sched_ext` via BPF.
@@ -90,20 +100,58 @@ pub mod bpf_intf;
 
 #[rustfmt::skip]
 mod bpf;
+use std::collections::VecDeque;
 use std::mem::MaybeUninit;
 use std::time::SystemTime;
 
 use anyhow::Result;
 use bpf::*;
 use libbpf_rs::OpenObject;
+use scx_rustland_core::SchedPolicy;
+use scx_rustland_core::SchedRuntime;
 use scx_utils::libbpf_clap_opts::LibbpfOpts;
 use scx_utils::UserExitInfo;
 
 // Maximum time slice (in nanoseconds) that a task can use before it is re-enqueued.
 const SLICE_NS: u64 = 5_000_000;
 
+// Dispatch tasks in FIFO order, with a time slice inversely proportional to the number of tasks
+// waiting to be scheduled.
+#[derive(Default)]
+struct RoundRobinPolicy {
+    tasks: VecDeque<QueuedTask>,
+    nr_waiting: u64, // tasks waiting to be scheduled at the beginning of the scheduling cycle
+}
+
+impl SchedPolicy for RoundRobinPolicy {
+    fn on_enqueue(&mut self, task: QueuedTask) {
+        self.tasks.push_back(task);
+    }
+
+    fn pick_next(&mut self) -> Option<DispatchedTask> {
+        let task = self.tasks.pop_front()?;
+
+        // Create a new task to be dispatched from the received enqueued task.
+        //
+        // The target CPU is picked by the runtime: the most suitable idle CPU for the task,
+        // prioritizing its previously used CPU (task.cpu), or the first CPU available if no CPU
+        // is idle.
+        let mut dispatched_task = DispatchedTask::new(&task);
+
+        // Determine the task's time slice: assign value inversely proportional to the number of
+        // tasks waiting to be scheduled.
+        dispatched_task.slice_ns = SLICE_NS / (self.nr_waiting + 1);
+
+        Some(dispatched_task)
+    }
+
+    fn nr_queued(&self) -> usize {
+        self.tasks.len()
+    }
+}
+
 struct Scheduler<'a> {
-    bpf: BpfScheduler<'a>, // Connector to the sched_ext BPF backend
+    runtime: SchedRuntime<BpfScheduler<'a>, RoundRobinPolicy>, // Policy on top of the BPF backend
 }
 
 impl<'a> Scheduler<'a> {
@@ -117,53 +165,26 @@ impl<'a> Scheduler<'a> {
             false,    // debug (false = debug mode off)
             true,     // builtin_idle (true = allow BPF to use idle CPUs if available)
             false,    // numa_local (false = ignore NUMA locality when selecting target CPUs)
+            false,    // extended_ctx (false = don't collect tgid, cgroup, context switches, etc.)
             SLICE_NS, // default time slice (for tasks automatically dispatched by the backend)
             "rlfifo", // name of the scx ops
         )?;
-        Ok(Self { bpf })
-    }
-
-    fn dispatch_tasks(&mut self) {
-        // Get the amount of tasks that are waiting to be scheduled.
-        let nr_waiting = *self.bpf.nr_queued_mut();
-
-        // Start consuming and dispatching tasks, until all the CPUs are busy or there are no more
-        // tasks to be dispatched.
-        while let Ok(Some(task)) = self.bpf.dequeue_task() {
-            // Create a new task to be dispatched from the received enqueued task.
-            let mut dispatched_task = DispatchedTask::new(&task);
-
-            // Decide where the task needs to run (pick a target CPU).
-            //
-            // A call to select_cpu() will return the most suitable idle CPU for the task,
-            // prioritizing its previously used CPU (task.cpu).
-            //
-            // If we can't find any idle CPU, run on the first CPU available.
-            let cpu = self.bpf.select_cpu(task.pid, task.cpu, task.flags);
-            dispatched_task.cpu = if cpu >= 0 { cpu } else { RL_CPU_ANY };
-
-            // Determine the task's time slice: assign value inversely proportional to the number
-            // of tasks waiting to be scheduled.
-            dispatched_task.slice_ns = SLICE_NS / (nr_waiting + 1);
-
-            // Dispatch the task.
-            self.bpf.dispatch_task(&dispatched_task).unwrap();
-        }
 
-        // Notify the BPF component that tasks have been dispatched.
-        //
-        // This function will put the scheduler to sleep, until another task needs to run.
-        self.bpf.notify_complete(0);
+        // Dispatch all the queued tasks at every scheduling cycle, until all the CPUs are busy or
+        // there are no more tasks to be dispatched.
+        let runtime =
+            SchedRuntime::new(bpf, RoundRobinPolicy::default()).dispatch_batch(usize::MAX);
+        Ok(Self { runtime })
     }
 
-    fn print_stats(&mut self) {
+    fn print_stats(bpf: &mut BpfScheduler<'_>) {
         // Internal scx_rustland_core statistics.
-        let nr_user_dispatches = *self.bpf.nr_user_dispatches_mut();
-        let nr_kernel_dispatches = *self.bpf.nr_kernel_dispatches_mut();
-        let nr_cancel_dispatches = *self.bpf.nr_cancel_dispatches_mut();
-        let nr_bounce_dispatches = *self.bpf.nr_bounce_dispatches_mut();
-        let nr_failed_dispatches = *self.bpf.nr_failed_dispatches_mut();
-        let nr_sched_congested = *self.bpf.nr_sched_congested_mut();
+        let nr_user_dispatches = *bpf.nr_user_dispatches_mut();
+        let nr_kernel_dispatches = *bpf.nr_kernel_dispatches_mut();
+        let nr_cancel_dispatches = *bpf.nr_cancel_dispatches_mut();
+        let nr_bounce_dispatches = *bpf.nr_bounce_dispatches_mut();
+        let nr_failed_dispatches = *bpf.nr_failed_dispatches_mut();
+        let nr_sched_congested = *bpf.nr_sched_congested_mut();
 
         println!(
             "user={} kernel={} cancel={} bounce={} fail={} cong={}",
@@ -186,16 +207,22 @@ impl<'a> Scheduler<'a> {
     fn run(&mut self) -> Result<UserExitInfo> {
         let mut prev_ts = Self::now();
 
-        while !self.bpf.exited() {
-            self.dispatch_tasks();
+        while !self.runtime.backend_mut().exited() {
+            // Get the amount of tasks that are waiting to be scheduled.
+            let nr_waiting = *self.runtime.backend_mut().nr_queued_mut();
+            self.runtime.policy_mut().nr_waiting = nr_waiting;
+
+            // Each scheduling cycle ends with the scheduler put to sleep by notify_complete(),
+            // until another task needs to run.
+            self.runtime.schedule();
 
             let curr_ts = Self::now();
             if curr_ts > prev_ts {
-                self.print_stats();
+                Self::print_stats(self.runtime.backend_mut());
                 prev_ts = curr_ts;
             }
         }
-        self.bpf.shutdown_and_report()
+        self.runtime.backend_mut().shutdown_and_report()
     }
 }
 
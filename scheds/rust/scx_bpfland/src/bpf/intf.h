@@ -20,6 +20,11 @@ enum consts {
 	NSEC_PER_MSEC = (1000ULL * NSEC_PER_USEC),
 	NSEC_PER_SEC = (1000ULL * NSEC_PER_MSEC),
 
+	CACHELINE_SIZE		= 64,
+
+	/* Maximum number of cgroups tracked in hierarchical cgroup mode */
+	MAX_CGROUPS		= 8192,
+
 	/* Kernel definitions */
 	CLOCK_BOOTTIME		= 7,
 };
@@ -47,4 +52,17 @@ struct domain_arg {
 	s32 sibling_cpu_id;
 };
 
+/*
+ * Per-cgroup context, keyed by cgroup id (only used with --cgroup-sched).
+ */
+struct cgrp_ctx {
+	/* cpu.weight of the cgroup */
+	u64 weight;
+	/* Cached hierarchical weight, see cgrp_hweight() */
+	u64 hweight;
+	u64 hweight_gen;
+	/* Number of times a task was put aside due to cpu.max throttling */
+	u64 nr_throttled;
+};
+
 #endif /* __INTF_H */
@@ -4,6 +4,9 @@
  */
 #include <scx/common.bpf.h>
 #include <scx/percpu.bpf.h>
+#include <bpf_arena_common.bpf.h>
+#include <lib/sdt_task.h>
+#include <lib/cgroup.h>
 #include "intf.h"
 
 /*
@@ -18,6 +21,23 @@
  */
 #define MAX_CPUS	1024
 
+/*
+ * Default cgroup weight (cpu.weight).
+ */
+#define CGROUP_WEIGHT_DFL	100
+
+/*
+ * Fixed-point representation of a hierarchical cgroup weight equal to
+ * CGROUP_WEIGHT_DFL at every level.
+ */
+#define HWEIGHT_ONE		(1ULL << 16)
+
+/*
+ * Maximum depth of the cgroup hierarchy considered when evaluating the
+ * hierarchical weight.
+ */
+#define MAX_CGRP_LEVELS		16
+
 /*
  * Maximum rate of task wakeups/sec (tasks with a higher rate are capped to
  * this value).
@@ -45,6 +65,18 @@ char _license[] SEC("license") = "GPL";
  /* Report additional debugging information */
 const volatile bool debug;
 
+/*
+ * Enable hierarchical cgroup scheduling: scale the vruntime of each task by
+ * the cpu.weight of its cgroup and of all its ancestors and enforce cgroup
+ * cpu.max limits.
+ */
+const volatile bool cgroup_sched;
+
+/*
+ * PID of the user-space scheduler process (never throttled by cpu.max).
+ */
+static pid_t bpfland_pid;
+
 /*
  * Default task time slice.
  */
@@ -133,7 +165,8 @@ volatile u64 nr_kthread_dispatches, nr_direct_dispatches, nr_shared_dispatches,
 	nr_idle_global_misses, nr_waker_cpu_biases, nr_keep_running_reuses,
 	nr_keep_running_queue_empty, nr_keep_running_smt_blocked,
 	nr_keep_running_queued_work, nr_dispatch_cpu_dsq_consumes,
-	nr_dispatch_node_dsq_consumes, nr_cpu_release_reenqueue;
+	nr_dispatch_node_dsq_consumes, nr_cpu_release_reenqueue,
+	nr_cgroup_throttled;
 
 /*
  * Amount of currently running tasks.
@@ -275,6 +308,8 @@ struct task_ctx {
 	u64 timely_avg_queue_delay;
 	s64 timely_avg_queue_gradient;
 	u32 timely_hai_streak;
+	/* cgroup id of the task (valid when cgroup_sched=true) */
+	u64 cgrp_id;
 };
 
 /* Map that contains task-local storage. */
@@ -294,6 +329,95 @@ struct task_ctx *try_lookup_task_ctx(const struct task_struct *p)
 					(struct task_struct *)p, 0, 0);
 }
 
+/*
+ * Per-task arena context used by the cpu.max library (allocated only when
+ * cgroup_sched=true).
+ */
+struct task_bw_ctx {
+	/*
+	 * Do NOT change the position of atq. It must be at the beginning
+	 * of the context, see lib/cgroup_bw.bpf.c.
+	 */
+	struct scx_task_cgroup_bw atq __attribute__((aligned(CACHELINE_SIZE)));
+	pid_t pid;
+};
+typedef struct task_bw_ctx __arena task_bw_ctx;
+
+/*
+ * Per-cgroup contexts, keyed by cgroup id.
+ */
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(map_flags, BPF_F_NO_PREALLOC);
+	__uint(max_entries, MAX_CGROUPS);
+	__type(key, u64);
+	__type(value, struct cgrp_ctx);
+} cgrp_ctx_stor SEC(".maps");
+
+/*
+ * Generation of the cgroup weights, bumped every time a cpu.weight
+ * changes to invalidate the cached hierarchical weights.
+ */
+static u64 cgrp_weight_gen = 1;
+
+/*
+ * Return the hierarchical weight of cgroup @cgid in HWEIGHT_ONE units.
+ *
+ * The hierarchical weight is the product of the cpu.weight of the cgroup and
+ * all its ancestors (each normalized to CGROUP_WEIGHT_DFL), so that a cgroup
+ * with cpu.weight=200 inside a parent with cpu.weight=50 runs at the same
+ * vruntime rate as a top-level cgroup with the default weight.
+ *
+ * This is a flattened approximation of the kernel's hierarchical weight
+ * distribution: it does not account for the number of active siblings at
+ * each level, but it is cheap to evaluate and preserves the relative
+ * priorities expressed by cpu.weight.
+ */
+static u64 cgrp_hweight(u64 cgid)
+{
+	u64 gen = READ_ONCE(cgrp_weight_gen), hweight = HWEIGHT_ONE;
+	struct cgroup *cgrp;
+	struct cgrp_ctx *cgc;
+	int level, nr_levels;
+
+	cgc = bpf_map_lookup_elem(&cgrp_ctx_stor, &cgid);
+	if (!cgc)
+		return HWEIGHT_ONE;
+	if (cgc->hweight_gen == gen)
+		return cgc->hweight;
+
+	cgrp = bpf_cgroup_from_id(cgid);
+	if (!cgrp)
+		return cgc->hweight ? cgc->hweight : HWEIGHT_ONE;
+
+	/*
+	 * The root cgroup (level 0) doesn't have a configurable weight.
+	 */
+	nr_levels = MIN(cgrp->level, MAX_CGRP_LEVELS);
+	bpf_for(level, 1, nr_levels + 1) {
+		struct cgroup *anc = bpf_cgroup_ancestor(cgrp, level);
+		struct cgrp_ctx *actx;
+		u64 id;
+
+		if (!anc)
+			break;
+		id = anc->kn->id;
+		bpf_cgroup_release(anc);
+
+		actx = bpf_map_lookup_elem(&cgrp_ctx_stor, &id);
+		if (!actx)
+			continue;
+		hweight = CLAMP(hweight * actx->weight / CGROUP_WEIGHT_DFL,
+				1, HWEIGHT_ONE * CGROUP_WEIGHT_DFL);
+	}
+	bpf_cgroup_release(cgrp);
+
+	cgc->hweight = hweight;
+	cgc->hweight_gen = gen;
+
+	return hweight;
+}
+
 /*
  * Return the DSQ id of the corresponding @cpu.
  */
@@ -772,6 +896,66 @@ static u64 task_slice(const struct task_struct *p, s32 cpu)
 	return MAX(slice, slice_min);
 }
 
+/*
+ * Check if the cgroup of @p is throttled by cpu.max.
+ *
+ * If @put_aside is true and the cgroup is throttled, the task is parked in
+ * the cpu.max backlog and re-enqueued via scx_cgroup_bw_enqueue_cb() when
+ * the cgroup is unthrottled.
+ *
+ * Return 0 if the task can run, -EAGAIN if its cgroup is throttled or
+ * -errno on failure.
+ */
+static int cgroup_throttled(struct task_struct *p, struct task_ctx *tctx, bool put_aside)
+{
+	task_bw_ctx *bwc;
+	struct cgrp_ctx *cgc;
+	int ret, err;
+
+	/*
+	 * Never throttle the scheduler process itself, so it can always
+	 * make forward progress.
+	 */
+	if (p->pid == bpfland_pid)
+		return 0;
+
+	bwc = scx_task_data(p);
+	if (!bwc)
+		return 0;
+
+	ret = scx_cgroup_bw_throttled(tctx->cgrp_id, p, (u64)bwc);
+	if (ret != -EAGAIN || !put_aside || scx_cgroup_bw_is_task_throttled((u64)bwc))
+		return ret;
+
+	err = scx_cgroup_bw_put_aside(p, (u64)bwc, p->scx.dsq_vtime, tctx->cgrp_id);
+	if (err)
+		return err;
+
+	__sync_fetch_and_add(&nr_cgroup_throttled, 1);
+	cgc = bpf_map_lookup_elem(&cgrp_ctx_stor, &tctx->cgrp_id);
+	if (cgc)
+		__sync_fetch_and_add(&cgc->nr_throttled, 1);
+
+	return ret;
+}
+
+/*
+ * Return true if @p belongs to a cgroup throttled by cpu.max.
+ */
+static bool is_cgroup_throttled(struct task_struct *p)
+{
+	struct task_ctx *tctx;
+
+	if (!cgroup_sched)
+		return false;
+
+	tctx = try_lookup_task_ctx(p);
+	if (!tctx)
+		return false;
+
+	return cgroup_throttled(p, tctx, false) == -EAGAIN;
+}
+
 /*
  * Pick a target CPU for a task which is being woken up.
  *
@@ -791,6 +975,13 @@ s32 BPF_STRUCT_OPS(bpfland_select_cpu, struct task_struct *p, s32 prev_cpu, u64
 	if (!bpf_cpumask_test_cpu(prev_cpu, p->cpus_ptr))
 		prev_cpu = is_this_cpu_allowed ? this_cpu : bpf_cpumask_first(p->cpus_ptr);
 
+	/*
+	 * Don't bypass cpu.max with a direct dispatch, let ops.enqueue() put
+	 * the task aside instead.
+	 */
+	if (is_cgroup_throttled(p))
+		return prev_cpu;
+
 	/*
 	 * Try to find an idle CPU and dispatch the task directly to the
 	 * target CPU.
@@ -883,6 +1074,14 @@ void BPF_STRUCT_OPS(bpfland_enqueue, struct task_struct *p, u64 enq_flags)
 	if (!tctx)
 		return;
 
+	/*
+	 * If the task's cgroup exhausted its cpu.max quota, the task has
+	 * been put aside and will be re-enqueued once the cgroup is
+	 * unthrottled.
+	 */
+	if (cgroup_sched && cgroup_throttled(p, tctx, true) == -EAGAIN)
+		return;
+
 	if (timely_enabled)
 		tctx->timely_last_enqueued_at = bpf_ktime_get_ns();
 
@@ -1011,8 +1210,8 @@ static bool consume_first_task(u64 dsq_id, struct task_struct *p)
 
 void BPF_STRUCT_OPS(bpfland_dispatch, s32 cpu, struct task_struct *prev)
 {
-	struct task_struct *p = __COMPAT_scx_bpf_dsq_peek(cpu_dsq(cpu));
-	struct task_struct *q = __COMPAT_scx_bpf_dsq_peek(node_dsq(cpu));
+	struct task_struct *p, *q;
+	int ret;
 
 	/*
 	 * Let the CPU go idle if the system is throttled.
@@ -1020,6 +1219,16 @@ void BPF_STRUCT_OPS(bpfland_dispatch, s32 cpu, struct task_struct *prev)
 	if (is_throttled())
 		return;
 
+	/*
+	 * Re-enqueue the tasks that were put aside when their cgroup was
+	 * throttled and whose cgroup has been unthrottled since then.
+	 */
+	if (cgroup_sched && (ret = scx_cgroup_bw_reenqueue()))
+		scx_bpf_error("Failed to reenqueue backlogged tasks: %d", ret);
+
+	p = __COMPAT_scx_bpf_dsq_peek(cpu_dsq(cpu));
+	q = __COMPAT_scx_bpf_dsq_peek(node_dsq(cpu));
+
 	/*
 	 * Try to consume the first task either from the per-CPU DSQ or the
 	 * per-node DSQ, picking the one with the minimum deadline that can
@@ -1036,9 +1245,9 @@ void BPF_STRUCT_OPS(bpfland_dispatch, s32 cpu, struct task_struct *prev)
 	/*
 	 * If the current task expired its time slice and no other task wants
 	 * to run, simply replenish its time slice and let it run for another
-	 * round on the same CPU.
+	 * round on the same CPU (unless its cgroup has been throttled).
 	 */
-	if (prev && keep_running(prev, cpu))
+	if (prev && keep_running(prev, cpu) && !is_cgroup_throttled(prev))
 		scx_bpf_task_set_slice(prev, task_slice(prev, cpu));
 }
 
@@ -1167,6 +1376,19 @@ void BPF_STRUCT_OPS(bpfland_stopping, struct task_struct *p, bool runnable)
 	 * sleep.
 	 */
 	delta_vtime = scale_by_task_weight_inverse(p, slice);
+	if (cgroup_sched) {
+		/*
+		 * Tasks in cgroups with a higher hierarchical weight advance
+		 * their vruntime more slowly.
+		 */
+		delta_vtime = delta_vtime * HWEIGHT_ONE / cgrp_hweight(tctx->cgrp_id);
+
+		/*
+		 * Charge the used time to the cgroup's cpu.max quota.
+		 */
+		if (p->pid != bpfland_pid)
+			scx_cgroup_bw_consume(tctx->cgrp_id, slice, (u64)scx_task_data(p));
+	}
 	scx_bpf_task_set_dsq_vtime(p, p->scx.dsq_vtime + (delta_vtime));
 	tctx->awake_vtime += delta_vtime;
 
@@ -1213,12 +1435,195 @@ s32 BPF_STRUCT_OPS(bpfland_init_task, struct task_struct *p,
 		   struct scx_init_task_args *args)
 {
 	struct task_ctx *tctx;
+	task_bw_ctx *bwc;
 
 	tctx = bpf_task_storage_get(&task_ctx_stor, p, 0,
 				    BPF_LOCAL_STORAGE_GET_F_CREATE);
 	if (!tctx)
 		return -ENOMEM;
 
+	if (!cgroup_sched)
+		return 0;
+
+	if (args->cgroup)
+		tctx->cgrp_id = args->cgroup->kn->id;
+
+	bwc = scx_task_alloc(p);
+	if (!bwc)
+		return -ENOMEM;
+	bwc->pid = p->pid;
+
+	return 0;
+}
+
+void BPF_STRUCT_OPS(bpfland_dequeue, struct task_struct *p, u64 deq_flags)
+{
+	task_bw_ctx *bwc;
+	int ret;
+
+	if (!cgroup_sched)
+		return;
+
+	/*
+	 * Remove the task from the cpu.max backlog if it was put aside.
+	 */
+	bwc = scx_task_data(p);
+	if (!bwc || !scx_cgroup_bw_is_task_throttled((u64)bwc))
+		return;
+
+	ret = scx_cgroup_bw_cancel((u64)bwc, SCX_CGROUP_BW_CANCEL_UNLINK);
+	if (ret)
+		dbg_msg("failed to cancel throttled task %d: %d", p->pid, ret);
+}
+
+void BPF_STRUCT_OPS(bpfland_exit_task, struct task_struct *p,
+		    struct scx_exit_task_args *args)
+{
+	task_bw_ctx *bwc;
+
+	if (!cgroup_sched)
+		return;
+
+	/*
+	 * Drop the task from the cpu.max queues before releasing its arena
+	 * context.
+	 */
+	bwc = scx_task_data(p);
+	if (bwc)
+		scx_cgroup_bw_cancel((u64)bwc, SCX_CGROUP_BW_CANCEL_DROP);
+
+	scx_task_free(p);
+}
+
+s32 BPF_STRUCT_OPS_SLEEPABLE(bpfland_cgroup_init, struct cgroup *cgrp,
+			     struct scx_cgroup_init_args *args)
+{
+	struct cgrp_ctx cgc = {
+		.weight = args->weight,
+	};
+	u64 cgid = cgrp->kn->id;
+	int ret;
+
+	if (!cgroup_sched)
+		return 0;
+
+	ret = bpf_map_update_elem(&cgrp_ctx_stor, &cgid, &cgc, BPF_ANY);
+	if (ret) {
+		scx_bpf_error("Failed to create cgroup context %llu: %d", cgid, ret);
+		return ret;
+	}
+
+	ret = scx_cgroup_bw_init(cgrp, args);
+	if (ret)
+		scx_bpf_error("Failed to init cgroup %llu: %d", cgid, ret);
+
+	return ret;
+}
+
+void BPF_STRUCT_OPS(bpfland_cgroup_exit, struct cgroup *cgrp)
+{
+	u64 cgid = cgrp->kn->id;
+	int ret;
+
+	if (!cgroup_sched)
+		return;
+
+	ret = scx_cgroup_bw_exit(cgrp);
+	if (ret)
+		scx_bpf_error("Failed to exit cgroup %llu: %d", cgid, ret);
+
+	bpf_map_delete_elem(&cgrp_ctx_stor, &cgid);
+}
+
+void BPF_STRUCT_OPS(bpfland_cgroup_set_weight, struct cgroup *cgrp, u32 weight)
+{
+	u64 cgid = cgrp->kn->id;
+	struct cgrp_ctx *cgc;
+
+	if (!cgroup_sched)
+		return;
+
+	cgc = bpf_map_lookup_elem(&cgrp_ctx_stor, &cgid);
+	if (!cgc)
+		return;
+	cgc->weight = weight;
+
+	/*
+	 * Invalidate the cached hierarchical weights of all the cgroups,
+	 * since the weight of a cgroup affects all its descendants.
+	 */
+	__sync_fetch_and_add(&cgrp_weight_gen, 1);
+}
+
+void BPF_STRUCT_OPS(bpfland_cgroup_move, struct task_struct *p,
+		    struct cgroup *from, struct cgroup *to)
+{
+	struct task_ctx *tctx;
+	int ret;
+
+	if (!cgroup_sched)
+		return;
+
+	tctx = try_lookup_task_ctx(p);
+	if (!tctx)
+		return;
+	tctx->cgrp_id = to->kn->id;
+
+	ret = scx_cgroup_bw_move(p, (u64)scx_task_data(p), from, to);
+	if (ret)
+		scx_bpf_error("Failed to move task %d from cgroup %llu to %llu: %d",
+			      p->pid, from->kn->id, to->kn->id, ret);
+}
+
+void BPF_STRUCT_OPS(bpfland_cgroup_set_bandwidth, struct cgroup *cgrp,
+		    u64 period_us, u64 quota_us, u64 burst_us)
+{
+	int ret;
+
+	if (!cgroup_sched)
+		return;
+
+	ret = scx_cgroup_bw_set(cgrp, period_us, quota_us, burst_us);
+	if (ret)
+		scx_bpf_error("Failed to set bandwidth of cgroup %llu: %d",
+			      cgrp->kn->id, ret);
+}
+
+/*
+ * Re-enqueue a task that was put aside by the cpu.max library, once its
+ * cgroup is unthrottled (or is exiting).
+ *
+ * The task must be enqueued, otherwise it would never be scheduled again.
+ */
+__hidden int scx_cgroup_bw_enqueue_cb(u64 ctx)
+{
+	task_bw_ctx *bwc = (task_bw_ctx *)ctx;
+	struct task_struct *p;
+	struct task_ctx *tctx;
+	s32 cpu;
+
+	/*
+	 * If the task already exited there's nothing left to enqueue.
+	 */
+	p = bpf_task_from_pid(bwc->pid);
+	if (!p)
+		return 0;
+
+	cpu = scx_bpf_task_cpu(p);
+	tctx = try_lookup_task_ctx(p);
+	if (tctx && !is_pcpu_task(p)) {
+		scx_bpf_dsq_insert_vtime(p, node_dsq(cpu),
+					 task_slice(p, cpu), task_dl(p, cpu, tctx), 0);
+		__sync_fetch_and_add(&nr_shared_dispatches, 1);
+	} else {
+		scx_bpf_dsq_insert_vtime(p, cpu_dsq(cpu),
+					 task_slice(p, cpu), p->scx.dsq_vtime, 0);
+		__sync_fetch_and_add(&nr_direct_dispatches, 1);
+	}
+	scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
+
+	bpf_task_release(p);
+
 	return 0;
 }
 
@@ -1428,6 +1833,23 @@ s32 BPF_STRUCT_OPS_SLEEPABLE(bpfland_init)
 	if (err)
 		return err;
 
+	/*
+	 * Initialize the cpu.max library and keep track of the scheduler's
+	 * PID, so that it is never throttled.
+	 */
+	if (cgroup_sched) {
+		struct scx_cgroup_bw_config bw_config = {
+			.verbose = debug,
+		};
+
+		err = scx_cgroup_bw_lib_init(&bw_config);
+		if (err) {
+			scx_bpf_error("Failed to initialize cpu.max library: %d", err);
+			return err;
+		}
+	}
+	bpfland_pid = (u32)bpf_get_current_pid_tgid();
+
 	timer = bpf_map_lookup_elem(&throttle_timer, &key);
 	if (!timer) {
 		scx_bpf_error("Failed to lookup throttle timer");
@@ -1466,6 +1888,7 @@ void BPF_STRUCT_OPS(bpfland_cpu_release, s32 cpu, struct scx_cpu_release_args *a
 SCX_OPS_DEFINE(bpfland_ops,
 	       .select_cpu		= (void *)bpfland_select_cpu,
 	       .enqueue			= (void *)bpfland_enqueue,
+	       .dequeue			= (void *)bpfland_dequeue,
 	       .dispatch		= (void *)bpfland_dispatch,
 	       .cpu_release		= (void *)bpfland_cpu_release,
 	       .running			= (void *)bpfland_running,
@@ -1473,6 +1896,12 @@ SCX_OPS_DEFINE(bpfland_ops,
 	       .runnable		= (void *)bpfland_runnable,
 	       .enable			= (void *)bpfland_enable,
 	       .init_task		= (void *)bpfland_init_task,
+	       .exit_task		= (void *)bpfland_exit_task,
+	       .cgroup_init		= (void *)bpfland_cgroup_init,
+	       .cgroup_exit		= (void *)bpfland_cgroup_exit,
+	       .cgroup_move		= (void *)bpfland_cgroup_move,
+	       .cgroup_set_weight	= (void *)bpfland_cgroup_set_weight,
+	       .cgroup_set_bandwidth	= (void *)bpfland_cgroup_set_bandwidth,
 	       .init			= (void *)bpfland_init,
 	       .exit			= (void *)bpfland_exit,
 	       .timeout_ms		= STARVATION_MS,
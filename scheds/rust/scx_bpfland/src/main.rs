@@ -11,9 +11,15 @@ pub mod bpf_intf;
 pub use bpf_intf::*;
 
 mod stats;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ffi::{c_int, c_ulong};
 use std::fmt::Write;
 use std::mem::MaybeUninit;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -25,10 +31,15 @@ use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
 use crossbeam::channel::RecvTimeoutError;
+use libbpf_rs::skel::OpenSkel;
+use libbpf_rs::skel::Skel;
+use libbpf_rs::MapCore as _;
+use libbpf_rs::MapFlags;
 use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
 use log::warn;
 use log::{debug, info};
+use scx_arena::ArenaLib;
 use scx_stats::prelude::*;
 use scx_utils::autopower::{fetch_power_profile, PowerProfile};
 use scx_utils::build_id;
@@ -47,10 +58,78 @@ use scx_utils::Powermode;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
 use scx_utils::NR_CPU_IDS;
+use stats::CgroupMetrics;
 use stats::Metrics;
 
 const SCHEDULER_NAME: &str = "scx_bpfland";
 
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+unsafe impl plain::Plain for bpf_intf::cgrp_ctx {}
+
+// Collect the paths of all the cgroups in the cgroup v2 hierarchy, indexed by
+// cgroup id (the inode number of the cgroup directory).
+fn scan_cgroup_paths(root: &Path, dir: &Path, paths: &mut HashMap<u64, String>) {
+    let Ok(meta) = std::fs::metadata(dir) else {
+        return;
+    };
+    let rel = dir.strip_prefix(root).unwrap_or(dir);
+    paths.insert(meta.ino(), format!("/{}", rel.display()));
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            scan_cgroup_paths(root, &entry.path(), paths);
+        }
+    }
+}
+
+// Paths of the cgroups reported in the stats, the hierarchy is rescanned only when cgroups that
+// haven't been seen before show up.
+struct CgroupPaths {
+    root: PathBuf,
+    paths: HashMap<u64, String>,
+    unresolved: HashSet<u64>, // ids not found in the hierarchy (e.g., removed cgroups)
+}
+
+impl CgroupPaths {
+    fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            paths: HashMap::new(),
+            unresolved: HashSet::new(),
+        }
+    }
+
+    // Return the path of each cgroup id, scanning the hierarchy at most once.
+    fn resolve(&mut self, cgids: &[u64]) -> Vec<String> {
+        if cgids
+            .iter()
+            .any(|cgid| !self.paths.contains_key(cgid) && !self.unresolved.contains(cgid))
+        {
+            self.paths.clear();
+            scan_cgroup_paths(&self.root, &self.root, &mut self.paths);
+        }
+        self.unresolved = cgids
+            .iter()
+            .copied()
+            .filter(|cgid| !self.paths.contains_key(cgid))
+            .collect();
+
+        cgids
+            .iter()
+            .map(|cgid| {
+                self.paths
+                    .get(cgid)
+                    .cloned()
+                    .unwrap_or_else(|| format!("cgid:{}", cgid))
+            })
+            .collect()
+    }
+}
+
 // Convert an array of CPUs to the corresponding cpumask of any arbitrary size.
 fn cpus_to_cpumask(cpus: &Vec<usize>) -> String {
     if cpus.is_empty() {
@@ -243,6 +322,14 @@ struct Opts {
     #[clap(long, default_value = "500")]
     timely_control_interval_us: u64,
 
+    /// Enable hierarchical cgroup scheduling.
+    ///
+    /// Scale the vruntime of each task by the cpu.weight of its cgroup and all its ancestors and
+    /// enforce the cgroup cpu.max bandwidth limits. Per-cgroup throttling statistics are reported
+    /// in the scheduler stats.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    cgroup_sched: bool,
+
     /// Enable stats monitoring with the specified interval.
     #[clap(long)]
     stats: Option<f64>,
@@ -279,6 +366,7 @@ struct Scheduler<'a> {
     topo: Topology,
     power_profile: PowerProfile,
     stats_server: StatsServer<(), Metrics>,
+    cgroup_paths: CgroupPaths,
     user_restart: bool,
 }
 
@@ -400,6 +488,25 @@ impl<'a> Scheduler<'a> {
         // (it's never a good idea to throttle per-CPU kthreads).
         rodata.local_kthreads = opts.local_kthreads || opts.throttle_us > 0;
 
+        // Hierarchical cgroup scheduling.
+        rodata.cgroup_sched = opts.cgroup_sched;
+        if opts.cgroup_sched {
+            info!("Hierarchical cgroup scheduling enabled");
+            if !compat::struct_has_field("sched_ext_ops", "cgroup_set_bandwidth").unwrap_or(false) {
+                warn!("kernel doesn't support ops.cgroup_set_bandwidth(), cpu.max is not enforced");
+                skel.struct_ops.bpfland_ops_mut().cgroup_set_bandwidth = std::ptr::null_mut();
+            }
+        } else {
+            // Don't register the cgroup callbacks at all when cgroup scheduling is disabled.
+            let ops = skel.struct_ops.bpfland_ops_mut();
+            ops.cgroup_init = std::ptr::null_mut();
+            ops.cgroup_exit = std::ptr::null_mut();
+            ops.cgroup_move = std::ptr::null_mut();
+            ops.cgroup_set_weight = std::ptr::null_mut();
+            ops.cgroup_set_bandwidth = std::ptr::null_mut();
+            Self::disable_arena(&mut skel)?;
+        }
+
         // Set scheduler flags.
         skel.struct_ops.bpfland_ops_mut().flags = *compat::SCX_OPS_ENQ_EXITING
             | *compat::SCX_OPS_ENQ_LAST
@@ -415,9 +522,20 @@ impl<'a> Scheduler<'a> {
             skel.struct_ops.bpfland_ops_mut().flags
         );
 
+        // Size the cpu.max per-(cgroup, LLC) map to this system's LLC count before loading (a
+        // map's max_entries is fixed at load time).
+        scx_utils::resize_cgroup_bw_llc_map(skel.open_object_mut(), topo.all_llcs.len())?;
+
         // Load the BPF program for validation.
         let mut skel = scx_ops_load!(skel, bpfland_ops, uei)?;
 
+        // Initialize the arena used by the cpu.max library.
+        if opts.cgroup_sched {
+            let task_size = std::mem::size_of::<types::task_bw_ctx>();
+            let arenalib = ArenaLib::init(skel.object_mut(), task_size, *NR_CPU_IDS)?;
+            arenalib.setup()?;
+        }
+
         // Initialize the primary scheduling domain.
         Self::init_energy_domain(&mut skel, &domain).map_err(|err| {
             anyhow!(
@@ -451,6 +569,7 @@ impl<'a> Scheduler<'a> {
             topo,
             power_profile,
             stats_server,
+            cgroup_paths: CgroupPaths::new(Path::new(CGROUP_ROOT)),
             user_restart: false,
         })
     }
@@ -503,6 +622,28 @@ impl<'a> Scheduler<'a> {
         Ok(domain)
     }
 
+    // The arena is only used by the cpu.max library: don't create the arena map nor load the
+    // arena library programs without cgroup scheduling, so that the scheduler can still run on
+    // kernels without BPF arena support. The BPF code using the arena is dead code in this case.
+    fn disable_arena(skel: &mut OpenBpfSkel<'_>) -> Result<()> {
+        skel.maps.arena.set_autocreate(false)?;
+
+        let progs = &mut skel.progs;
+        for prog in [
+            &mut progs.arena_init,
+            &mut progs.arena_alloc_mask,
+            &mut progs.arena_topology_init,
+            &mut progs.arena_topology_node_init,
+            &mut progs.arena_topology_print,
+            &mut progs.scx_userspace_arena_alloc_pages,
+            &mut progs.scx_userspace_arena_free_pages,
+        ] {
+            prog.set_autoload(false);
+        }
+
+        Ok(())
+    }
+
     fn init_energy_domain(skel: &mut BpfSkel<'_>, domain: &Cpumask) -> Result<()> {
         info!("primary CPU domain = 0x{:x}", domain);
 
@@ -619,7 +760,39 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
-    fn get_metrics(&self) -> Metrics {
+    fn get_cgroup_metrics(&mut self) -> BTreeMap<String, CgroupMetrics> {
+        let mut cgroups = BTreeMap::new();
+        if !self.opts.cgroup_sched {
+            return cgroups;
+        }
+
+        let mut cgids = Vec::new();
+        let mut metrics = Vec::new();
+        let map = &self.skel.maps.cgrp_ctx_stor;
+        for key in map.keys() {
+            let Ok(Some(val)) = map.lookup(&key, MapFlags::ANY) else {
+                continue;
+            };
+            let Ok(cgc) = plain::from_bytes::<bpf_intf::cgrp_ctx>(&val) else {
+                continue;
+            };
+            let mut id_bytes = [0u8; 8];
+            id_bytes.copy_from_slice(&key[..8]);
+            cgids.push(u64::from_ne_bytes(id_bytes));
+            metrics.push(CgroupMetrics {
+                weight: cgc.weight,
+                nr_throttled: cgc.nr_throttled,
+            });
+        }
+
+        let paths = self.cgroup_paths.resolve(&cgids);
+        cgroups.extend(paths.into_iter().zip(metrics));
+
+        cgroups
+    }
+
+    fn get_metrics(&mut self) -> Metrics {
+        let cgroups = self.get_cgroup_metrics();
         let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
         Metrics {
             nr_running: bss_data.nr_running,
@@ -655,6 +828,8 @@ impl<'a> Scheduler<'a> {
             nr_dispatch_cpu_dsq_consumes: bss_data.nr_dispatch_cpu_dsq_consumes,
             nr_dispatch_node_dsq_consumes: bss_data.nr_dispatch_node_dsq_consumes,
             nr_cpu_release_reenqueue: bss_data.nr_cpu_release_reenqueue,
+            nr_cgroup_throttled: bss_data.nr_cgroup_throttled,
+            cgroups,
         }
     }
 
@@ -771,3 +946,31 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_paths_rescan() {
+        let root = std::env::temp_dir().join(format!("scx_bpfland-cgroups-{}", std::process::id()));
+        let ino = |path: &Path| std::fs::metadata(path).unwrap().ino();
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+
+        let mut cgroup_paths = CgroupPaths::new(&root);
+        let (a, b) = (ino(&root.join("a")), ino(&root.join("a/b")));
+        assert_eq!(cgroup_paths.resolve(&[a, b, 1]), ["/a", "/a/b", "cgid:1"]);
+
+        // Known and unresolved ids don't trigger a rescan.
+        std::fs::create_dir(root.join("c")).unwrap();
+        let c = ino(&root.join("c"));
+        assert_eq!(cgroup_paths.resolve(&[b, 1]), ["/a/b", "cgid:1"]);
+        assert!(!cgroup_paths.paths.contains_key(&c));
+
+        // A new id does, once for all the ids.
+        assert_eq!(cgroup_paths.resolve(&[c, 1]), ["/c", "cgid:1"]);
+        assert_eq!(cgroup_paths.unresolved, HashSet::from([1]));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -11,6 +12,16 @@ use scx_stats_derive::Stats;
 use serde::Deserialize;
 use serde::Serialize;
 
+#[stat_doc]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
+#[stat(_om_prefix = "c_", _om_label = "cgroup")]
+pub struct CgroupMetrics {
+    #[stat(desc = "cgroup cpu.weight")]
+    pub weight: u64,
+    #[stat(desc = "Number of tasks put aside due to cpu.max throttling")]
+    pub nr_throttled: u64,
+}
+
 #[stat_doc]
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
@@ -82,6 +93,10 @@ pub struct Metrics {
     pub nr_dispatch_node_dsq_consumes: u64,
     #[stat(desc = "Number of CPU release reenqueues")]
     pub nr_cpu_release_reenqueue: u64,
+    #[stat(desc = "Number of tasks put aside due to cgroup cpu.max throttling")]
+    pub nr_cgroup_throttled: u64,
+    #[stat(desc = "Per-cgroup statistics (only with --cgroup-sched)")]
+    pub cgroups: BTreeMap<String, CgroupMetrics>,
 }
 
 impl Metrics {
@@ -101,6 +116,23 @@ impl Metrics {
             self.nr_gain_floor_dispatches,
             self.nr_gain_ceiling_dispatches
         )?;
+        if self.nr_cgroup_throttled > 0 {
+            writeln!(
+                w,
+                "[{}] cgroups -> throttled: {}",
+                crate::SCHEDULER_NAME,
+                self.nr_cgroup_throttled
+            )?;
+            for (path, cgrp) in self.cgroups.iter() {
+                if cgrp.nr_throttled > 0 {
+                    writeln!(
+                        w,
+                        "    {:<40} weight: {:<5} throttled: {}",
+                        path, cgrp.weight, cgrp.nr_throttled
+                    )?;
+                }
+            }
+        }
         Ok(())
     }
 
@@ -156,6 +188,21 @@ impl Metrics {
             nr_dispatch_node_dsq_consumes: self.nr_dispatch_node_dsq_consumes
                 - rhs.nr_dispatch_node_dsq_consumes,
             nr_cpu_release_reenqueue: self.nr_cpu_release_reenqueue - rhs.nr_cpu_release_reenqueue,
+            nr_cgroup_throttled: self.nr_cgroup_throttled - rhs.nr_cgroup_throttled,
+            cgroups: self
+                .cgroups
+                .iter()
+                .map(|(path, cgrp)| {
+                    let prev = rhs.cgroups.get(path).map_or(0, |c| c.nr_throttled);
+                    (
+                        path.clone(),
+                        CgroupMetrics {
+                            nr_throttled: cgrp.nr_throttled.saturating_sub(prev),
+                            ..cgrp.clone()
+                        },
+                    )
+                })
+                .collect(),
             ..self.clone()
         }
     }
@@ -178,6 +225,7 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
     });
 
     StatsServerData::new()
+        .add_meta(CgroupMetrics::meta())
         .add_meta(Metrics::meta())
         .add_ops("top", StatsOps { open, close: None })
 }
@@ -16,6 +16,10 @@ enum chaos_consts {
 	CHAOS_NUM_PPIDS_CHECK	= 1 << 20,
 
 	CHAOS_MAX_RAND_ATTEMPTS = 512,
+
+	CHAOS_COMM_LEN		= 16,
+
+	CHAOS_MAX_MIGRATION_ATTEMPTS = 16,
 };
 
 enum chaos_match {
@@ -33,9 +37,31 @@ enum chaos_trait_kind {
 	CHAOS_TRAIT_CPU_FREQ,
 	CHAOS_TRAIT_DEGRADATION,
 	CHAOS_TRAIT_KPROBE_RANDOM_DELAYS,
+	CHAOS_TRAIT_WAKEUP_REORDER,
+	CHAOS_TRAIT_FORCED_MIGRATION,
+	CHAOS_TRAIT_STARVATION,
 	CHAOS_TRAIT_MAX,
 };
 
+/* How far a forced migration must move a task from its previous CPU */
+enum chaos_migration_scope {
+	CHAOS_MIGRATION_ANY,
+	CHAOS_MIGRATION_LLC,
+	CHAOS_MIGRATION_NUMA,
+};
+
+/*
+ * Restricts when and to which tasks a trait applies. Zeroed fields match
+ * everything.
+ */
+struct chaos_trait_scope {
+	u64  start_ns; /* relative to scheduler start */
+	u64  end_ns;
+	u64  cgroup_id;
+	int  pid;      /* matches either the pid or the tgid */
+	char comm[CHAOS_COMM_LEN];
+};
+
 struct chaos_task_ctx {
 	// chaos_task_ctx is initialised zero'd
 	enum chaos_match      match;
@@ -54,6 +80,9 @@ enum chaos_stat_idx {
 	CHAOS_STAT_CHAOS_SKIPPED,
 	CHAOS_STAT_KPROBE_RANDOM_DELAYS,
 	CHAOS_STAT_TIMER_KICKS,
+	CHAOS_STAT_TRAIT_WAKEUP_REORDER,
+	CHAOS_STAT_TRAIT_FORCED_MIGRATION,
+	CHAOS_STAT_TRAIT_STARVATION,
 	CHAOS_NR_STATS,
 };
 
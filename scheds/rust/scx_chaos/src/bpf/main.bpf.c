@@ -35,6 +35,26 @@ const volatile u32 kprobe_delays_freq_frac32 = 1;
 const volatile u64 kprobe_delays_min_ns	     = 1;
 const volatile u64 kprobe_delays_max_ns	     = 2;
 
+const volatile u64 reorder_window_ns	     = 0;
+
+const volatile u32 migration_scope	     = CHAOS_MIGRATION_ANY;
+
+const volatile u64 starvation_ns	     = 1; /* for veristat */
+
+/* 0 = use the kernel PRNG, otherwise seed a per-CPU deterministic PRNG */
+const volatile u64 chaos_seed		     = 0;
+
+const volatile struct chaos_trait_scope trait_scopes[CHAOS_TRAIT_MAX];
+
+static u64 chaos_started_at;
+
+/* only one thread is starved at a time */
+static u64 starvation_until;
+static s32 starvation_pid;
+
+bool bpf_task_under_cgroup(struct task_struct *task,
+			   struct cgroup *ancestor) __ksym __weak;
+
 #define MIN(x, y) ((x) < (y) ? (x) : (y))
 #define MAX(x, y) ((x) > (y) ? (x) : (y))
 
@@ -64,12 +84,52 @@ struct {
 	__type(value, u64);
 } chaos_stats	       SEC(".maps");
 
+struct {
+	__uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+	__uint(max_entries, 1);
+	__type(key, u32);
+	__type(value, u64);
+} chaos_prng_state     SEC(".maps");
+
 struct chaos_task_ctx *lookup_create_chaos_task_ctx(struct task_struct *p)
 {
 	return bpf_task_storage_get(&chaos_task_ctxs, p, NULL,
 				    BPF_LOCAL_STORAGE_GET_F_CREATE);
 }
 
+/*
+ * Return a pseudo random number. With chaos_seed set every CPU runs its own
+ * splitmix64 sequence derived from the seed, so the same seed produces the
+ * same sequence of decisions on each CPU.
+ */
+static __always_inline u64 chaos_get_prandom_u64(void)
+{
+	u32  zero = 0;
+	u64 *state, x;
+
+	if (!chaos_seed)
+		return get_prandom_u64();
+
+	state = bpf_map_lookup_elem(&chaos_prng_state, &zero);
+	if (!state)
+		return get_prandom_u64();
+
+	if (!*state)
+		*state = chaos_seed ^ ((u64)(bpf_get_smp_processor_id() + 1) *
+				       0xbf58476d1ce4e5b9ULL);
+
+	*state += 0x9e3779b97f4a7c15ULL;
+	x = *state;
+	x = (x ^ (x >> 30)) * 0xbf58476d1ce4e5b9ULL;
+	x = (x ^ (x >> 27)) * 0x94d049bb133111ebULL;
+	return x ^ (x >> 31);
+}
+
+static __always_inline u32 chaos_get_prandom_u32(void)
+{
+	return chaos_get_prandom_u64() >> 32;
+}
+
 static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 {
 	// Implementation of Lemire's algorithm 5 without 128-bit arithmetic.
@@ -79,7 +139,7 @@ static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 	u64 x, m_low, m_high;
 	u64 t;
 
-	x = get_prandom_u64();
+	x = chaos_get_prandom_u64();
 
 	// Compute 64-bit multiplication high and low parts
 	// m = x * s, split into m_high and m_low
@@ -95,7 +155,7 @@ static __always_inline u64 chaos_get_prandom_u64_limit(u64 s)
 			if (m_low >= t)
 				break;
 
-			x      = get_prandom_u64();
+			x      = chaos_get_prandom_u64();
 			m_high = ((x >> 32) * (s >> 32)) +
 				 (((x & 0xFFFFFFFF) * (s >> 32)) >> 32) +
 				 (((x >> 32) * (s & 0xFFFFFFFF)) >> 32);
@@ -118,20 +178,83 @@ static __always_inline void chaos_stat_inc(enum chaos_stat_idx stat)
 		(*cnt_p)++;
 }
 
+static __always_inline bool chaos_comm_matches(struct task_struct *p,
+					       const volatile char *comm)
+{
+	int i;
+
+	bpf_for(i, 0, CHAOS_COMM_LEN) {
+		if (!comm[i])
+			return true;
+		if (p->comm[i] != comm[i])
+			return false;
+	}
+
+	return true;
+}
+
+static __always_inline bool chaos_task_in_cgroup(struct task_struct *p,
+						 u64 cgroup_id)
+{
+	struct cgroup *cgrp;
+	bool	       ret;
+
+	if (!bpf_ksym_exists(bpf_task_under_cgroup))
+		return false;
+
+	if (!(cgrp = bpf_cgroup_from_id(cgroup_id)))
+		return false;
+
+	ret = bpf_task_under_cgroup(p, cgrp);
+	bpf_cgroup_release(cgrp);
+
+	return ret;
+}
+
+/*
+ * Check the time window and the task filters of a trait.
+ */
+static __always_inline bool chaos_trait_in_scope(struct task_struct *p,
+						 enum chaos_trait_kind kind)
+{
+	const volatile struct chaos_trait_scope *scope;
+	u64 elapsed;
+
+	if (kind <= CHAOS_TRAIT_NONE || kind >= CHAOS_TRAIT_MAX)
+		return true;
+
+	scope	= &trait_scopes[kind];
+	elapsed = bpf_ktime_get_ns() - chaos_started_at;
+
+	if (elapsed < scope->start_ns)
+		return false;
+	if (scope->end_ns && elapsed >= scope->end_ns)
+		return false;
+	if (scope->pid && p->pid != scope->pid && p->tgid != scope->pid)
+		return false;
+	if (scope->comm[0] && !chaos_comm_matches(p, scope->comm))
+		return false;
+	if (scope->cgroup_id && !chaos_task_in_cgroup(p, scope->cgroup_id))
+		return false;
+
+	return true;
+}
+
 static __always_inline enum chaos_trait_kind
-choose_chaos(struct chaos_task_ctx *taskc)
+choose_chaos(struct task_struct *p, struct chaos_task_ctx *taskc)
 {
 	if (taskc->match & CHAOS_MATCH_EXCLUDED) {
 		chaos_stat_inc(CHAOS_STAT_CHAOS_EXCLUDED);
 		return CHAOS_TRAIT_NONE;
 	}
 
-	u32 roll = bpf_get_prandom_u32();
+	u32 roll = chaos_get_prandom_u32();
 
 #pragma unroll
 	for (int i = 0; i < CHAOS_TRAIT_MAX; ++i) {
 		if (roll <= trait_delay_freq_frac32[i])
-			return i;
+			return chaos_trait_in_scope(p, i) ? i :
+							    CHAOS_TRAIT_NONE;
 	}
 
 	scx_bpf_error("failed to select trait in choose_chaos");
@@ -142,7 +265,9 @@ static __always_inline bool
 chaos_trait_skips_select_cpu(struct chaos_task_ctx *taskc)
 {
 	return taskc->next_trait == CHAOS_TRAIT_RANDOM_DELAYS ||
-	       taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS;
+	       taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS ||
+	       taskc->next_trait == CHAOS_TRAIT_WAKEUP_REORDER ||
+	       taskc->next_trait == CHAOS_TRAIT_STARVATION;
 }
 
 static __always_inline u64 get_cpu_delay_dsq(int cpu_idx)
@@ -291,6 +416,65 @@ __weak s32 enqueue_random_delay(struct task_struct *p	     __arg_trusted,
 	return true;
 }
 
+/*
+ * Claim the starvation window for @p. Fails while another thread is being
+ * starved, so the peers of the starved thread keep running.
+ */
+static __always_inline bool chaos_claim_starvation(struct task_struct *p)
+{
+	u64 now = bpf_ktime_get_ns();
+	u64 until = starvation_until;
+
+	if (now < until && starvation_pid != p->pid)
+		return false;
+
+	if (__sync_val_compare_and_swap(&starvation_until, until,
+					now + starvation_ns) != until)
+		return false;
+
+	starvation_pid = p->pid;
+	return true;
+}
+
+/*
+ * Pick a random CPU allowed for @p other than @prev_cpu, in a different LLC
+ * or NUMA node depending on migration_scope. Return a negative value if no
+ * such CPU was found.
+ */
+static __always_inline s32 chaos_pick_migration_cpu(struct task_struct *p,
+						    s32 prev_cpu)
+{
+	struct cpu_ctx *prev_cpuc, *cpuc;
+	s32		cpu;
+	int		i;
+
+	if (p->nr_cpus_allowed == 1 || is_migration_disabled(p))
+		return -EINVAL;
+
+	if (!(prev_cpuc = lookup_cpu_ctx(prev_cpu)))
+		return -EINVAL;
+
+	bpf_for(i, 0, CHAOS_MAX_MIGRATION_ATTEMPTS) {
+		cpu = chaos_get_prandom_u64_limit(topo_config.nr_cpus);
+		if (cpu == prev_cpu || !bpf_cpumask_test_cpu(cpu, p->cpus_ptr))
+			continue;
+
+		if (!(cpuc = lookup_cpu_ctx(cpu)))
+			continue;
+
+		if (migration_scope == CHAOS_MIGRATION_LLC &&
+		    cpuc->llc_id == prev_cpuc->llc_id)
+			continue;
+		if (migration_scope == CHAOS_MIGRATION_NUMA &&
+		    cpuc->node_id == prev_cpuc->node_id)
+			continue;
+
+		return cpu;
+	}
+
+	return -ENOENT;
+}
+
 __weak s32 enqueue_chaotic(struct task_struct *p __arg_trusted, u64 enq_flags,
 			   struct chaos_task_ctx *taskc __arg_nonnull)
 {
@@ -309,12 +493,24 @@ __weak s32 enqueue_chaotic(struct task_struct *p __arg_trusted, u64 enq_flags,
 					   random_delays_max_ns);
 		chaos_stat_inc(CHAOS_STAT_TRAIT_RANDOM_DELAYS);
 		break;
+	case CHAOS_TRAIT_STARVATION:
+		if (!chaos_claim_starvation(p)) {
+			out = false;
+			break;
+		}
+		out = enqueue_random_delay(p, enq_flags, taskc, starvation_ns,
+					   starvation_ns);
+		dbg("CHAOS[starvation][%d] ns: %llu", p->pid, starvation_ns);
+		chaos_stat_inc(CHAOS_STAT_TRAIT_STARVATION);
+		break;
 	case CHAOS_TRAIT_NONE:
 		chaos_stat_inc(CHAOS_STAT_CHAOS_SKIPPED);
 		out = false;
 		break;
 	case CHAOS_TRAIT_CPU_FREQ:
 	case CHAOS_TRAIT_DEGRADATION:
+	case CHAOS_TRAIT_WAKEUP_REORDER:
+	case CHAOS_TRAIT_FORCED_MIGRATION:
 	case CHAOS_TRAIT_MAX:
 		out = false;
 		break;
@@ -404,6 +600,8 @@ s32 BPF_STRUCT_OPS_SLEEPABLE(chaos_init)
 	struct cpu_ctx	     *cpuc;
 	int		      timer_id, ret, i;
 
+	chaos_started_at = bpf_ktime_get_ns();
+
 	bpf_for(i, 0, topo_config.nr_cpus)
 	{
 		if (!(cpuc = lookup_cpu_ctx(i)) ||
@@ -555,7 +753,8 @@ void BPF_STRUCT_OPS(chaos_enqueue, struct task_struct *p __arg_trusted,
 		goto cleanup;
 
 	if ((taskc->next_trait == CHAOS_TRAIT_RANDOM_DELAYS ||
-	     taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS) &&
+	     taskc->next_trait == CHAOS_TRAIT_KPROBE_RANDOM_DELAYS ||
+	     taskc->next_trait == CHAOS_TRAIT_STARVATION) &&
 	    enqueue_chaotic(p, enq_flags, taskc))
 		goto cleanup;
 
@@ -583,6 +782,31 @@ void BPF_STRUCT_OPS(chaos_enqueue, struct task_struct *p __arg_trusted,
 		}
 	}
 
+	// Let the task jump ahead of (or fall behind) the tasks already queued
+	// in the same DSQ. Only the wakeup is reordered, later enqueues follow
+	// the normal order.
+	if (taskc->next_trait == CHAOS_TRAIT_WAKEUP_REORDER) {
+		if (promise.kind == P2DQ_ENQUEUE_PROMISE_FIFO) {
+			promise.fifo.enq_flags |= SCX_ENQ_HEAD;
+			dbg("CHAOS[reorder][%d] head", p->pid);
+			chaos_stat_inc(CHAOS_STAT_TRAIT_WAKEUP_REORDER);
+		}
+		if (promise.kind == P2DQ_ENQUEUE_PROMISE_VTIME) {
+			u64 shift = chaos_get_uniform_u64(0, 2 * reorder_window_ns);
+
+			if (shift >= reorder_window_ns)
+				promise.vtime.vtime += shift - reorder_window_ns;
+			else if (promise.vtime.vtime > reorder_window_ns - shift)
+				promise.vtime.vtime -= reorder_window_ns - shift;
+			else
+				promise.vtime.vtime = 0;
+			dbg("CHAOS[reorder][%d] vtime: %llu", p->pid,
+			    promise.vtime.vtime);
+			chaos_stat_inc(CHAOS_STAT_TRAIT_WAKEUP_REORDER);
+		}
+		taskc->next_trait = CHAOS_TRAIT_NONE;
+	}
+
 	complete_p2dq_enqueue(&promise, p);
 	return;
 
@@ -605,7 +829,7 @@ void BPF_STRUCT_OPS(chaos_runnable, struct task_struct *p, u64 enq_flags)
 		return;
 	}
 
-	wakee_ctx->next_trait = choose_chaos(wakee_ctx);
+	wakee_ctx->next_trait = choose_chaos(p, wakee_ctx);
 }
 
 void BPF_STRUCT_OPS(chaos_running, struct task_struct *p)
@@ -638,6 +862,8 @@ s32 BPF_STRUCT_OPS(chaos_select_cpu, struct task_struct *p, s32 prev_cpu,
 		   u64 wake_flags)
 {
 	struct chaos_task_ctx *wakee_ctx;
+	s32		       cpu;
+
 	if (!(wakee_ctx = lookup_create_chaos_task_ctx(p)))
 		goto p2dq;
 
@@ -645,6 +871,19 @@ s32 BPF_STRUCT_OPS(chaos_select_cpu, struct task_struct *p, s32 prev_cpu,
 	if (chaos_trait_skips_select_cpu(wakee_ctx))
 		return prev_cpu;
 
+	// move the task away from prev_cpu without dispatching it, enqueue
+	// then queues it on the chosen CPU
+	if (wakee_ctx->next_trait == CHAOS_TRAIT_FORCED_MIGRATION) {
+		wakee_ctx->next_trait = CHAOS_TRAIT_NONE;
+		cpu = chaos_pick_migration_cpu(p, prev_cpu);
+		if (cpu >= 0) {
+			dbg("CHAOS[migration][%d] %d -> %d", p->pid, prev_cpu,
+			    cpu);
+			chaos_stat_inc(CHAOS_STAT_TRAIT_FORCED_MIGRATION);
+			return cpu;
+		}
+	}
+
 p2dq:
 	return p2dq_select_cpu_impl(p, prev_cpu, wake_flags);
 }
@@ -686,7 +925,10 @@ int generic(struct pt_regs *ctx)
 	if (!(taskc = lookup_create_chaos_task_ctx(p)))
 		return -EINVAL;
 
-	u32 roll = bpf_get_prandom_u32();
+	if (!chaos_trait_in_scope(p, CHAOS_TRAIT_KPROBE_RANDOM_DELAYS))
+		return 0;
+
+	u32 roll = chaos_get_prandom_u32();
 	if (roll <= kprobe_delays_freq_frac32) {
 		taskc->pending_trait = CHAOS_TRAIT_KPROBE_RANDOM_DELAYS;
 		dbg("GENERIC: setting pending_trait to RANDOM_DELAYS - task[%d]",
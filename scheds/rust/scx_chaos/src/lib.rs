@@ -4,13 +4,19 @@
 // GNU General Public License version 2.
 mod bpf_intf;
 mod bpf_skel;
+mod minimize;
+pub mod scenario;
 pub mod stats;
 
 use bpf_skel::BpfSkel;
+use scenario::Scenario;
+use scenario::ScopedTrait;
+use scenario::TraitScope;
 use stats::Metrics;
 
 use log::warn;
 
+use scx_p2dq::energy::EnergyProfile;
 use scx_p2dq::SchedulerOpts as P2dqOpts;
 use scx_userspace_arena::alloc::Allocator;
 use scx_userspace_arena::alloc::HeapAllocator;
@@ -45,18 +51,25 @@ use log::debug;
 use log::info;
 use nix::unistd::Pid;
 use scx_stats::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
 
 use std::alloc::Layout;
 use std::collections::HashSet;
 use std::fs::File;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
 use std::io::{BufRead, BufReader};
 use std::marker::PhantomPinned;
 use std::mem::MaybeUninit;
 use std::panic;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::process::Command;
 use std::ptr::NonNull;
 use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Condvar;
 use std::sync::Mutex;
@@ -95,7 +108,8 @@ unsafe impl Allocator for ArenaAllocator {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
 pub enum Trait {
     RandomDelays {
         frequency: f64,
@@ -111,6 +125,19 @@ pub enum Trait {
         frequency: f64,
         degradation_frac7: u64,
     },
+    WakeupReorder {
+        frequency: f64,
+        window_us: u64,
+    },
+    ForcedMigration {
+        frequency: f64,
+        #[serde(default)]
+        scope: MigrationScope,
+    },
+    Starvation {
+        frequency: f64,
+        duration_us: u64,
+    },
 }
 
 impl Trait {
@@ -119,6 +146,9 @@ impl Trait {
             Self::RandomDelays { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_RANDOM_DELAYS,
             Self::CpuFreq { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_CPU_FREQ,
             Self::PerfDegradation { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_DEGRADATION,
+            Self::WakeupReorder { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_WAKEUP_REORDER,
+            Self::ForcedMigration { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_FORCED_MIGRATION,
+            Self::Starvation { .. } => bpf_intf::chaos_trait_kind_CHAOS_TRAIT_STARVATION,
         }
     }
 
@@ -127,28 +157,97 @@ impl Trait {
             Self::RandomDelays { frequency, .. } => *frequency,
             Self::CpuFreq { frequency, .. } => *frequency,
             Self::PerfDegradation { frequency, .. } => *frequency,
+            Self::WakeupReorder { frequency, .. } => *frequency,
+            Self::ForcedMigration { frequency, .. } => *frequency,
+            Self::Starvation { frequency, .. } => *frequency,
+        }
+    }
+
+    pub fn frequency_mut(&mut self) -> &mut f64 {
+        match self {
+            Self::RandomDelays { frequency, .. } => frequency,
+            Self::CpuFreq { frequency, .. } => frequency,
+            Self::PerfDegradation { frequency, .. } => frequency,
+            Self::WakeupReorder { frequency, .. } => frequency,
+            Self::ForcedMigration { frequency, .. } => frequency,
+            Self::Starvation { frequency, .. } => frequency,
         }
     }
 }
 
-#[derive(Debug)]
+/// How far a forced migration moves a task from its previous CPU.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationScope {
+    /// Any other CPU.
+    #[default]
+    Any,
+    /// A CPU in another LLC.
+    Llc,
+    /// A CPU in another NUMA node.
+    Numa,
+}
+
+impl MigrationScope {
+    fn to_bpf(self) -> u32 {
+        match self {
+            Self::Any => bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_ANY,
+            Self::Llc => bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_LLC,
+            Self::Numa => bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_NUMA,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum RequiresPpid {
     ExcludeParent(Pid),
     IncludeParent(Pid),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "serde_json::Map<String, serde_json::Value>")]
 pub struct KprobeRandomDelays {
     pub kprobes: Vec<String>,
+    #[serde(rename = "frequency")]
     pub freq: f64,
     pub min_us: u64,
     pub max_us: u64,
+    #[serde(flatten)]
+    pub scope: TraitScope,
+}
+
+/// Fields of [`KprobeRandomDelays`] other than its scope.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KprobeRandomDelaysSpec {
+    kprobes: Vec<String>,
+    frequency: f64,
+    min_us: u64,
+    max_us: u64,
+}
+
+impl TryFrom<serde_json::Map<String, serde_json::Value>> for KprobeRandomDelays {
+    type Error = serde_json::Error;
+
+    fn try_from(fields: serde_json::Map<String, serde_json::Value>) -> serde_json::Result<Self> {
+        let (scope, spec) = scenario::split_scope(fields)?;
+        let spec: KprobeRandomDelaysSpec = serde_json::from_value(spec)?;
+        Ok(Self {
+            kprobes: spec.kprobes,
+            freq: spec.frequency,
+            min_us: spec.min_us,
+            max_us: spec.max_us,
+            scope,
+        })
+    }
 }
 
 #[derive(Debug)]
 /// State required to build a Scheduler configuration.
 pub struct Builder<'a> {
-    pub traits: Vec<Trait>,
+    pub traits: Vec<ScopedTrait>,
+    /// Seed of the BPF PRNG, 0 uses the kernel PRNG.
+    pub seed: u64,
     pub verbose: u8,
     pub kprobe_random_delays: Option<KprobeRandomDelays>,
     pub p2dq_opts: &'a P2dqOpts,
@@ -174,6 +273,26 @@ pub struct Scheduler {
     skel: Pin<Rc<SkelWithObject>>,
 }
 
+/// Maps the summed `chaos_stats` counters, indexed by `chaos_stat_idx`, to Metrics.
+fn metrics_from_stats(stats: &[u64]) -> Metrics {
+    Metrics {
+        trait_random_delays: stats
+            [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_RANDOM_DELAYS as usize],
+        trait_cpu_freq: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_CPU_FREQ as usize],
+        trait_degradation: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_DEGRADATION as usize],
+        chaos_excluded: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_CHAOS_EXCLUDED as usize],
+        chaos_skipped: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_CHAOS_SKIPPED as usize],
+        kprobe_random_delays: stats
+            [bpf_intf::chaos_stat_idx_CHAOS_STAT_KPROBE_RANDOM_DELAYS as usize],
+        timer_kicks: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TIMER_KICKS as usize],
+        trait_wakeup_reorder: stats
+            [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_WAKEUP_REORDER as usize],
+        trait_forced_migration: stats
+            [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_FORCED_MIGRATION as usize],
+        trait_starvation: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_STARVATION as usize],
+    }
+}
+
 impl Scheduler {
     fn get_metrics(&self) -> Metrics {
         let mut stats = vec![0u64; bpf_intf::chaos_stat_idx_CHAOS_NR_STATS as usize];
@@ -191,18 +310,7 @@ impl Scheduler {
             stats[stat as usize] = sum;
         }
 
-        Metrics {
-            trait_random_delays: stats
-                [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_RANDOM_DELAYS as usize],
-            trait_cpu_freq: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_CPU_FREQ as usize],
-            trait_degradation: stats
-                [bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_DEGRADATION as usize],
-            chaos_excluded: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_CHAOS_EXCLUDED as usize],
-            chaos_skipped: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_CHAOS_SKIPPED as usize],
-            kprobe_random_delays: stats
-                [bpf_intf::chaos_stat_idx_CHAOS_STAT_KPROBE_RANDOM_DELAYS as usize],
-            timer_kicks: stats[bpf_intf::chaos_stat_idx_CHAOS_STAT_TIMER_KICKS as usize],
-        }
+        metrics_from_stats(&stats)
     }
 
     pub fn observe(
@@ -252,7 +360,28 @@ impl Drop for Scheduler {
     }
 }
 
-impl Builder<'_> {
+impl<'a> Builder<'a> {
+    /// The chaos configuration of this builder as a scenario.
+    pub fn scenario(&self) -> Scenario {
+        Scenario {
+            seed: Some(self.seed),
+            traits: self.traits.clone(),
+            kprobe_random_delays: self.kprobe_random_delays.clone(),
+        }
+    }
+
+    /// A copy of this builder with the chaos configuration of @scenario.
+    pub fn with_scenario(&self, scenario: &Scenario) -> Builder<'a> {
+        Builder {
+            traits: scenario.traits.clone(),
+            seed: scenario.seed.unwrap_or(self.seed),
+            verbose: self.verbose,
+            kprobe_random_delays: scenario.kprobe_random_delays.clone(),
+            p2dq_opts: self.p2dq_opts,
+            requires_ppid: self.requires_ppid,
+        }
+    }
+
     fn attach_kprobes(&self, skel: &mut BpfSkel) -> Result<Vec<Link>> {
         let Some(kd) = &self.kprobe_random_delays else {
             return Ok(vec![]);
@@ -306,6 +435,12 @@ impl Builder<'_> {
         } else {
             Topology::new()?
         };
+        let energy_profile = self
+            .p2dq_opts
+            .energy_profile
+            .as_deref()
+            .map(EnergyProfile::load)
+            .transpose()?;
         let open_opts = LibbpfOpts::default().into_bpf_open_opts();
         let mut open_skel = scx_ops_open!(skel_builder, open_object, chaos, open_opts)?;
         #[cfg(any(target_arch = "aarch64", target_arch = "arm"))]
@@ -369,18 +504,26 @@ impl Builder<'_> {
             }
         };
 
+        rodata.chaos_seed = self.seed;
+
         if let Some(kprobe_random_delays) = &self.kprobe_random_delays {
             rodata.kprobe_delays_freq_frac32 =
                 (kprobe_random_delays.freq * 2_f64.powf(32_f64)) as u32;
             rodata.kprobe_delays_min_ns = kprobe_random_delays.min_us * 1000;
             rodata.kprobe_delays_max_ns = kprobe_random_delays.max_us * 1000;
+            rodata.trait_scopes
+                [bpf_intf::chaos_trait_kind_CHAOS_TRAIT_KPROBE_RANDOM_DELAYS as usize] =
+                kprobe_random_delays
+                    .scope
+                    .to_bpf()
+                    .context("Invalid kprobe random delays scope")?;
         }
 
         // Set up the frequency array. The first element means nothing, so should be what's
         // required to add up to 100%. The rest should be cumulative frequencies.
         let freq_array = &mut rodata.trait_delay_freq_frac32;
         freq_array.fill(0);
-        for tr in &self.traits {
+        for tr in self.traits.iter().map(|st| &st.spec) {
             let kind = tr.kind();
             if freq_array[kind as usize] != 0 {
                 bail!("trait of kind {} specified multiple times!", kind);
@@ -408,8 +551,13 @@ impl Builder<'_> {
             rodata.trait_delay_freq_frac32
         );
 
-        for tr in &self.traits {
-            match tr {
+        for st in &self.traits {
+            rodata.trait_scopes[st.spec.kind() as usize] = st
+                .scope
+                .to_bpf()
+                .with_context(|| format!("Invalid scope for {:?}", st.spec))?;
+
+            match &st.spec {
                 Trait::RandomDelays {
                     frequency: _,
                     min_us,
@@ -435,6 +583,27 @@ impl Builder<'_> {
                     rodata.degradation_freq_frac32 = (frequency * 2_f64.powf(32_f64)) as u32;
                     rodata.degradation_frac7 = *degradation_frac7;
                 }
+                Trait::WakeupReorder {
+                    frequency: _,
+                    window_us,
+                } => {
+                    rodata.reorder_window_ns = window_us * 1000;
+                }
+                Trait::ForcedMigration {
+                    frequency: _,
+                    scope,
+                } => {
+                    rodata.migration_scope = scope.to_bpf();
+                }
+                Trait::Starvation {
+                    frequency: _,
+                    duration_us,
+                } => {
+                    if *duration_us == 0 {
+                        bail!("starvation duration must be greater than 0");
+                    }
+                    rodata.starvation_ns = duration_us * 1000;
+                }
             }
         }
 
@@ -445,7 +614,7 @@ impl Builder<'_> {
         };
 
         let mut skel = scx_ops_load!(open_skel, chaos, uei)?;
-        scx_p2dq::init_skel!(&mut skel, topo);
+        scx_p2dq::init_skel!(&mut skel, topo, energy_profile.as_ref());
 
         let task_size = std::mem::size_of::<types::task_p2dq>();
         let arenalib = ArenaLib::init(skel.object_mut(), task_size, *NR_CPU_IDS)?;
@@ -556,6 +725,51 @@ pub struct KprobeArgs {
     pub kprobe_random_delay_max_us: Option<u64>,
 }
 
+/// Reorder a task's wakeup relative to the other tasks in its DSQ.
+#[derive(Debug, Parser)]
+pub struct WakeupReorderArgs {
+    /// Chance of reordering a wakeup.
+    #[clap(long, requires = "reorder_window_us")]
+    pub reorder_frequency: Option<f64>,
+
+    /// Maximum vtime shift, in either direction, applied to a reordered wakeup. Wakeups into FIFO
+    /// DSQs are queued at the head instead.
+    #[clap(long, requires = "reorder_frequency")]
+    pub reorder_window_us: Option<u64>,
+}
+
+/// Force a task to migrate away from its previous CPU on wakeup.
+#[derive(Debug, Parser)]
+pub struct ForcedMigrationArgs {
+    /// Chance of forcing a migration.
+    #[clap(long)]
+    pub migration_frequency: Option<f64>,
+
+    /// Where the task is moved to.
+    #[clap(long, value_enum, default_value_t = MigrationScope::Any)]
+    pub migration_scope: MigrationScope,
+}
+
+/// Starve a matched thread for a bounded window while its peers keep running.
+#[derive(Debug, Parser)]
+pub struct StarvationArgs {
+    /// Chance of starving a thread on wakeup.
+    #[clap(long, requires = "starvation_us")]
+    pub starvation_frequency: Option<f64>,
+
+    /// How long a starved thread is kept off the CPU.
+    #[clap(long, requires = "starvation_frequency")]
+    pub starvation_us: Option<u64>,
+
+    /// Only starve this pid or tgid.
+    #[clap(long, requires = "starvation_frequency")]
+    pub starvation_pid: Option<i32>,
+
+    /// Only starve threads whose comm starts with this prefix.
+    #[clap(long, requires = "starvation_frequency")]
+    pub starvation_comm: Option<String>,
+}
+
 /// scx_chaos: A general purpose sched_ext scheduler designed to amplify race conditions
 ///
 /// WARNING: This scheduler is a very early alpha, and hasn't been production tested yet. The CLI
@@ -604,6 +818,32 @@ pub struct Args {
     #[clap(long)]
     pub monitor: Option<f64>,
 
+    /// Load chaos traits, time windows and task filters from a TOML or JSON scenario file.
+    /// Traits given on the command line are added to the ones in the scenario.
+    #[clap(long)]
+    pub scenario: Option<PathBuf>,
+
+    /// Seed for the BPF PRNG driving the chaos decisions. A random seed is picked and printed if
+    /// not set here or in the scenario, so a failing run can be replayed.
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// After the command under test fails, re-run it while dropping traits, bisecting the kprobe
+    /// set and lowering frequencies to find the smallest chaos configuration that still
+    /// reproduces the failure. The result is saved as a scenario and printed with a command line
+    /// replaying it. Combine with `--repeat-success` to keep running until the first failure.
+    #[clap(long, action = clap::ArgAction::SetTrue, requires = "args")]
+    pub minimize: bool,
+
+    /// Number of runs of a candidate configuration before concluding that it does not reproduce
+    /// the failure.
+    #[clap(long, default_value = "3")]
+    pub minimize_runs: u32,
+
+    /// Where to save the minimized scenario.
+    #[clap(long, default_value = "scx_chaos-minimized.toml")]
+    pub minimize_output: PathBuf,
+
     #[command(flatten, next_help_heading = "Random Delays")]
     pub random_delay: RandomDelayArgs,
 
@@ -616,6 +856,15 @@ pub struct Args {
     #[command(flatten, next_help_heading = "Kprobe Random Delays")]
     pub kprobe_random_delays: KprobeArgs,
 
+    #[command(flatten, next_help_heading = "Wakeup Reordering")]
+    pub wakeup_reorder: WakeupReorderArgs,
+
+    #[command(flatten, next_help_heading = "Forced Migrations")]
+    pub forced_migration: ForcedMigrationArgs,
+
+    #[command(flatten, next_help_heading = "Starvation")]
+    pub starvation: StarvationArgs,
+
     #[command(flatten, next_help_heading = "General Scheduling")]
     pub p2dq: P2dqOpts,
 
@@ -642,12 +891,19 @@ pub struct Args {
 
 struct BuilderIterator<'a> {
     args: &'a Args,
+    scenario: &'a Scenario,
+    seed: u64,
     idx: u32,
 }
 
-impl<'a> From<&'a Args> for BuilderIterator<'a> {
-    fn from(args: &'a Args) -> BuilderIterator<'a> {
-        BuilderIterator { args, idx: 0 }
+impl<'a> BuilderIterator<'a> {
+    fn new(args: &'a Args, scenario: &'a Scenario, seed: u64) -> BuilderIterator<'a> {
+        BuilderIterator {
+            args,
+            scenario,
+            seed,
+            idx: 0,
+        }
     }
 }
 
@@ -660,7 +916,7 @@ impl<'a> Iterator for BuilderIterator<'a> {
         if self.idx > 1 {
             None
         } else {
-            let mut traits = vec![];
+            let mut traits = self.scenario.traits.clone();
 
             if let RandomDelayArgs {
                 random_delay_frequency: Some(frequency),
@@ -668,11 +924,14 @@ impl<'a> Iterator for BuilderIterator<'a> {
                 random_delay_max_us: Some(max_us),
             } = self.args.random_delay
             {
-                traits.push(Trait::RandomDelays {
-                    frequency,
-                    min_us,
-                    max_us,
-                });
+                traits.push(
+                    Trait::RandomDelays {
+                        frequency,
+                        min_us,
+                        max_us,
+                    }
+                    .into(),
+                );
             };
             if let CpuFreqArgs {
                 cpufreq_frequency: Some(frequency),
@@ -680,10 +939,52 @@ impl<'a> Iterator for BuilderIterator<'a> {
                 cpufreq_max: Some(max_freq),
             } = self.args.cpu_freq
             {
-                traits.push(Trait::CpuFreq {
-                    frequency,
-                    min_freq,
-                    max_freq,
+                traits.push(
+                    Trait::CpuFreq {
+                        frequency,
+                        min_freq,
+                        max_freq,
+                    }
+                    .into(),
+                );
+            };
+            if let WakeupReorderArgs {
+                reorder_frequency: Some(frequency),
+                reorder_window_us: Some(window_us),
+            } = self.args.wakeup_reorder
+            {
+                traits.push(
+                    Trait::WakeupReorder {
+                        frequency,
+                        window_us,
+                    }
+                    .into(),
+                );
+            };
+            if let ForcedMigrationArgs {
+                migration_frequency: Some(frequency),
+                migration_scope: scope,
+            } = self.args.forced_migration
+            {
+                traits.push(Trait::ForcedMigration { frequency, scope }.into());
+            };
+            if let StarvationArgs {
+                starvation_frequency: Some(frequency),
+                starvation_us: Some(duration_us),
+                starvation_pid,
+                starvation_comm,
+            } = &self.args.starvation
+            {
+                traits.push(ScopedTrait {
+                    spec: Trait::Starvation {
+                        frequency: *frequency,
+                        duration_us: *duration_us,
+                    },
+                    scope: TraitScope {
+                        pid: *starvation_pid,
+                        comm: starvation_comm.clone(),
+                        ..Default::default()
+                    },
                 });
             };
 
@@ -710,12 +1011,14 @@ impl<'a> Iterator for BuilderIterator<'a> {
                     freq: kprobe_random_delay_frequency.unwrap_or(0.1),
                     min_us: *min_us,
                     max_us: *max_us,
+                    scope: TraitScope::default(),
                 }),
-                _ => None,
+                _ => self.scenario.kprobe_random_delays.clone(),
             };
 
             Some(Builder {
                 traits,
+                seed: self.seed,
                 verbose: self.args.verbose,
                 kprobe_random_delays,
                 p2dq_opts: &self.args.p2dq,
@@ -725,6 +1028,13 @@ impl<'a> Iterator for BuilderIterator<'a> {
     }
 }
 
+/// Pick a random non-zero PRNG seed (0 selects the unseeded kernel PRNG).
+fn random_seed() -> u64 {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    hasher.finish().max(1)
+}
+
 pub fn validate_kprobes(kprobes: &[String]) -> Result<()> {
     let path = tracefs_mount()?;
     let file = File::open(path.join("available_filter_functions"))?;
@@ -762,10 +1072,13 @@ pub fn run(args: Args) -> Result<()> {
     let args = Arc::new(args);
 
     let shutdown = Arc::new((Mutex::new(false), Condvar::new()));
+    let interrupted = Arc::new(AtomicBool::new(false));
 
     ctrlc::set_handler({
         let shutdown = shutdown.clone();
+        let interrupted = interrupted.clone();
         move || {
+            interrupted.store(true, Ordering::Relaxed);
             let (lock, cvar) = &*shutdown;
             *lock.lock().unwrap() = true;
             cvar.notify_all();
@@ -782,6 +1095,13 @@ pub fn run(args: Args) -> Result<()> {
         return stats::monitor(Duration::from_secs_f64(intv), shutdown);
     }
 
+    let scenario = match &args.scenario {
+        Some(path) => Arc::new(Scenario::load(path)?),
+        None => Arc::new(Scenario::default()),
+    };
+    let seed = args.seed.or(scenario.seed).unwrap_or_else(random_seed);
+    info!("chaos seed: {seed}");
+
     let stats_thread = args.stats.map(|intv| {
         let shutdown = shutdown.clone();
 
@@ -792,10 +1112,11 @@ pub fn run(args: Args) -> Result<()> {
 
     let scheduler_thread = thread::spawn({
         let args = args.clone();
+        let scenario = scenario.clone();
         let shutdown = shutdown.clone();
 
         move || -> Result<()> {
-            for builder in BuilderIterator::from(&*args) {
+            for builder in BuilderIterator::new(&args, &scenario, seed) {
                 info!("{:?}", builder);
 
                 let sched: Scheduler = builder.try_into()?;
@@ -831,6 +1152,7 @@ pub fn run(args: Args) -> Result<()> {
     }
 
     let mut should_run_app = !args.args.is_empty();
+    let mut app_failed = false;
     while should_run_app {
         let (cmd, vargs) = args.args.split_first().unwrap();
 
@@ -852,8 +1174,10 @@ pub fn run(args: Args) -> Result<()> {
                     info!("app under test terminated successfully, exiting...");
                     should_run_app = false;
                 } else {
-                    info!("TODO: report what the scheduler was doing when it crashed");
-                    should_run_app &= !*shutdown.0.lock().unwrap() && args.repeat_failure;
+                    warn!("app under test failed ({s}), replay with --seed {seed}");
+                    app_failed = true;
+                    should_run_app &=
+                        !*shutdown.0.lock().unwrap() && args.repeat_failure && !args.minimize;
                 };
 
                 break;
@@ -872,7 +1196,10 @@ pub fn run(args: Args) -> Result<()> {
 
     match scheduler_thread.join() {
         Ok(Ok(())) => {}
-        Ok(Err(e)) => return Err(e),
+        Ok(Err(e)) => {
+            warn!("scheduler failed, replay with --seed {seed}");
+            return Err(e);
+        }
         Err(e) => panic::resume_unwind(e),
     }
 
@@ -882,5 +1209,156 @@ pub fn run(args: Args) -> Result<()> {
         Err(e) => panic::resume_unwind(e),
     }
 
+    if args.minimize && app_failed && !interrupted.load(Ordering::Relaxed) {
+        let builder = BuilderIterator::new(&args, &scenario, seed)
+            .next()
+            .expect("builder iterator yields a builder");
+        let minimizer =
+            minimize::Minimizer::new(&builder, &args.args, args.minimize_runs, &interrupted);
+        if let Some(minimized) = minimizer.minimize()? {
+            minimize::report(&minimized, &args.minimize_output, &args.args)?;
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder_traits(cmdline: &[&str]) -> Vec<ScopedTrait> {
+        let args = Args::try_parse_from(cmdline).unwrap();
+        let scenario = Scenario::default();
+        let builder = BuilderIterator::new(&args, &scenario, 0).next().unwrap();
+        builder.traits
+    }
+
+    #[test]
+    fn test_new_trait_args() {
+        let traits = builder_traits(&[
+            "scx_chaos",
+            "--reorder-frequency",
+            "0.5",
+            "--reorder-window-us",
+            "100",
+            "--migration-frequency",
+            "0.2",
+            "--migration-scope",
+            "numa",
+            "--starvation-frequency",
+            "0.1",
+            "--starvation-us",
+            "500",
+            "--starvation-comm",
+            "worker",
+        ]);
+
+        assert_eq!(traits.len(), 3);
+        assert!(matches!(
+            traits[0].spec,
+            Trait::WakeupReorder { window_us: 100, .. }
+        ));
+        assert!(matches!(
+            traits[1].spec,
+            Trait::ForcedMigration {
+                scope: MigrationScope::Numa,
+                ..
+            }
+        ));
+        assert!(matches!(
+            traits[2].spec,
+            Trait::Starvation {
+                duration_us: 500,
+                ..
+            }
+        ));
+        assert_eq!(traits[2].scope.comm.as_deref(), Some("worker"));
+        assert_eq!(traits[2].scope.pid, None);
+
+        // Only the starvation filters narrow their trait.
+        assert!(traits[0].scope.comm.is_none());
+        assert!(traits[1].scope.comm.is_none());
+    }
+
+    #[test]
+    fn test_new_trait_args_incomplete() {
+        assert!(Args::try_parse_from(["scx_chaos", "--reorder-frequency", "0.5"]).is_err());
+        assert!(Args::try_parse_from(["scx_chaos", "--starvation-us", "500"]).is_err());
+        assert!(Args::try_parse_from(["scx_chaos", "--starvation-pid", "1"]).is_err());
+
+        // The migration scope alone doesn't enable the trait.
+        assert!(builder_traits(&["scx_chaos", "--migration-scope", "llc"]).is_empty());
+    }
+
+    #[test]
+    fn test_new_trait_kinds() {
+        let reorder = Trait::WakeupReorder {
+            frequency: 0.5,
+            window_us: 100,
+        };
+        let migration = Trait::ForcedMigration {
+            frequency: 0.25,
+            scope: MigrationScope::Llc,
+        };
+        let starvation = Trait::Starvation {
+            frequency: 0.125,
+            duration_us: 500,
+        };
+
+        assert_eq!(
+            reorder.kind(),
+            bpf_intf::chaos_trait_kind_CHAOS_TRAIT_WAKEUP_REORDER
+        );
+        assert_eq!(
+            migration.kind(),
+            bpf_intf::chaos_trait_kind_CHAOS_TRAIT_FORCED_MIGRATION
+        );
+        assert_eq!(
+            starvation.kind(),
+            bpf_intf::chaos_trait_kind_CHAOS_TRAIT_STARVATION
+        );
+        assert_eq!(reorder.frequency(), 0.5);
+        assert_eq!(migration.frequency(), 0.25);
+        assert_eq!(starvation.frequency(), 0.125);
+
+        assert_eq!(
+            MigrationScope::Any.to_bpf(),
+            bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_ANY
+        );
+        assert_eq!(
+            MigrationScope::Llc.to_bpf(),
+            bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_LLC
+        );
+        assert_eq!(
+            MigrationScope::Numa.to_bpf(),
+            bpf_intf::chaos_migration_scope_CHAOS_MIGRATION_NUMA
+        );
+    }
+
+    #[test]
+    fn test_metrics_from_stats() {
+        let stats: Vec<u64> = (0..bpf_intf::chaos_stat_idx_CHAOS_NR_STATS as u64)
+            .map(|idx| 100 + idx)
+            .collect();
+        let metrics = metrics_from_stats(&stats);
+        let stat = |idx: u32| 100 + idx as u64;
+
+        assert_eq!(
+            metrics.trait_wakeup_reorder,
+            stat(bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_WAKEUP_REORDER)
+        );
+        assert_eq!(
+            metrics.trait_forced_migration,
+            stat(bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_FORCED_MIGRATION)
+        );
+        assert_eq!(
+            metrics.trait_starvation,
+            stat(bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_STARVATION)
+        );
+        assert_eq!(
+            metrics.trait_random_delays,
+            stat(bpf_intf::chaos_stat_idx_CHAOS_STAT_TRAIT_RANDOM_DELAYS)
+        );
+    }
+}
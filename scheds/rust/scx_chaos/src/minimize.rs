@@ -0,0 +1,440 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Failure minimization (`--minimize`).
+//!
+//! Once the command under test has failed, re-run it with smaller chaos
+//! configurations: drop traits, bisect the kprobe set and lower frequencies,
+//! keeping every change that still reproduces the failure.
+
+use std::panic;
+use std::path::Path;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Result;
+use log::info;
+use log::warn;
+
+use crate::scenario::Scenario;
+use crate::Builder;
+use crate::Scheduler;
+
+/// Maximum number of times a frequency is halved.
+const MAX_FREQUENCY_STEPS: u32 = 4;
+
+pub struct Minimizer<'a> {
+    builder: &'a Builder<'a>,
+    cmd: &'a [String],
+    runs: u32,
+    interrupted: &'a AtomicBool,
+}
+
+impl<'a> Minimizer<'a> {
+    /// Minimize the configuration of @builder, which made @cmd fail. Each
+    /// candidate is run up to @runs times before it is considered to not
+    /// reproduce the failure.
+    pub fn new(
+        builder: &'a Builder<'a>,
+        cmd: &'a [String],
+        runs: u32,
+        interrupted: &'a AtomicBool,
+    ) -> Self {
+        Self {
+            builder,
+            cmd,
+            runs: runs.max(1),
+            interrupted,
+        }
+    }
+
+    /// Run the command under test once with @scenario loaded. Return true if
+    /// it failed.
+    fn run_once(&self, scenario: &Scenario) -> Result<bool> {
+        let builder = self.builder.with_scenario(scenario);
+        let shutdown = &(Mutex::new(false), Condvar::new());
+
+        thread::scope(|s| {
+            let scheduler_thread = s.spawn(move || -> Result<()> {
+                let sched: Scheduler = builder.try_into()?;
+                sched.observe(shutdown, None)
+            });
+
+            // Always stop the scheduler before returning, even if the command
+            // under test couldn't be run, or the join below never returns.
+            let status = self.run_cmd(|| scheduler_thread.is_finished());
+
+            let (lock, cvar) = shutdown;
+            *lock.lock().unwrap() = true;
+            cvar.notify_all();
+
+            match scheduler_thread.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => return Err(e),
+                Err(e) => panic::resume_unwind(e),
+            }
+
+            match status? {
+                Some(s) => Ok(!s.success()),
+                None if self.interrupted.load(Ordering::Relaxed) => {
+                    bail!("minimization interrupted")
+                }
+                None => bail!("scheduler exited while running the command under test"),
+            }
+        })
+    }
+
+    /// Run the command under test until it exits. Return None if it was
+    /// killed because of an interruption or because @sched_exited returned
+    /// true.
+    fn run_cmd(&self, sched_exited: impl Fn() -> bool) -> Result<Option<ExitStatus>> {
+        let (cmd, vargs) = self.cmd.split_first().unwrap();
+        let mut child = Command::new(cmd).args(vargs).spawn()?;
+        loop {
+            if self.interrupted.load(Ordering::Relaxed) || sched_exited() {
+                child.kill()?;
+                child.wait()?;
+                return Ok(None);
+            }
+            if let Some(s) = child.try_wait()? {
+                return Ok(Some(s));
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Return true if @scenario makes the command under test fail within
+    /// the configured number of runs.
+    fn reproduces(&self, scenario: &Scenario) -> Result<bool> {
+        for run in 1..=self.runs {
+            if self.run_once(scenario)? {
+                info!("minimize: failure reproduced (run {}/{})", run, self.runs);
+                return Ok(true);
+            }
+        }
+        info!("minimize: failure not reproduced in {} runs", self.runs);
+
+        Ok(false)
+    }
+
+    /// Find the smallest configuration that still reproduces the failure.
+    /// Return None if the original failure can't be reproduced.
+    pub fn minimize(&self) -> Result<Option<Scenario>> {
+        let reducer = Reducer {
+            reproduces: |scenario: &Scenario| self.reproduces(scenario),
+        };
+        reducer.reduce(self.builder.scenario())
+    }
+}
+
+/// Shrinks a scenario, keeping every change for which @reproduces still
+/// returns true.
+struct Reducer<F: Fn(&Scenario) -> Result<bool>> {
+    reproduces: F,
+}
+
+impl<F: Fn(&Scenario) -> Result<bool>> Reducer<F> {
+    /// Try @candidate, replacing @best if it still reproduces the failure.
+    fn try_candidate(&self, best: &mut Scenario, candidate: Scenario, what: &str) -> Result<bool> {
+        info!("minimize: trying {}", what);
+        if !(self.reproduces)(&candidate)? {
+            return Ok(false);
+        }
+        *best = candidate;
+
+        Ok(true)
+    }
+
+    fn drop_traits(&self, best: &mut Scenario) -> Result<()> {
+        let mut i = 0;
+        while i < best.traits.len() {
+            let mut candidate = best.clone();
+            let removed = candidate.traits.remove(i);
+            if !self.try_candidate(best, candidate, &format!("without {:?}", removed.spec))? {
+                i += 1;
+            }
+        }
+
+        if best.kprobe_random_delays.is_some() {
+            let candidate = Scenario {
+                kprobe_random_delays: None,
+                ..best.clone()
+            };
+            self.try_candidate(best, candidate, "without kprobe random delays")?;
+        }
+
+        Ok(())
+    }
+
+    fn with_kprobes(best: &Scenario, kprobes: &[String]) -> Scenario {
+        let mut candidate = best.clone();
+        if let Some(kd) = candidate.kprobe_random_delays.as_mut() {
+            kd.kprobes = kprobes.to_vec();
+        }
+        candidate
+    }
+
+    fn bisect_kprobes(&self, best: &mut Scenario) -> Result<()> {
+        loop {
+            let Some(kprobes) = best
+                .kprobe_random_delays
+                .as_ref()
+                .map(|kd| kd.kprobes.clone())
+            else {
+                return Ok(());
+            };
+            if kprobes.len() < 2 {
+                return Ok(());
+            }
+
+            let (lo, hi) = kprobes.split_at(kprobes.len() / 2);
+            let candidate = Self::with_kprobes(best, lo);
+            if self.try_candidate(best, candidate, &format!("kprobes {lo:?}"))? {
+                continue;
+            }
+            let candidate = Self::with_kprobes(best, hi);
+            if !self.try_candidate(best, candidate, &format!("kprobes {hi:?}"))? {
+                break;
+            }
+        }
+
+        // Neither half reproduces on its own, drop kprobes one at a time.
+        let mut i = 0;
+        while let Some(kprobes) = best
+            .kprobe_random_delays
+            .as_ref()
+            .map(|kd| kd.kprobes.clone())
+        {
+            if i >= kprobes.len() || kprobes.len() < 2 {
+                break;
+            }
+            let mut rest = kprobes.clone();
+            let removed = rest.remove(i);
+            let candidate = Self::with_kprobes(best, &rest);
+            if !self.try_candidate(best, candidate, &format!("without kprobe {removed}"))? {
+                i += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lower_frequencies(&self, best: &mut Scenario) -> Result<()> {
+        for i in 0..best.traits.len() {
+            for _ in 0..MAX_FREQUENCY_STEPS {
+                let mut candidate = best.clone();
+                let spec = &mut candidate.traits[i].spec;
+                *spec.frequency_mut() /= 2.0;
+                let what = format!("{spec:?}");
+                if !self.try_candidate(best, candidate, &what)? {
+                    break;
+                }
+            }
+        }
+
+        for _ in 0..MAX_FREQUENCY_STEPS {
+            let mut candidate = best.clone();
+            let Some(kd) = candidate.kprobe_random_delays.as_mut() else {
+                break;
+            };
+            kd.freq /= 2.0;
+            let what = format!("kprobe random delays frequency {}", kd.freq);
+            if !self.try_candidate(best, candidate, &what)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reduce(&self, mut best: Scenario) -> Result<Option<Scenario>> {
+        info!("minimize: checking that the failure reproduces");
+        if !(self.reproduces)(&best)? {
+            warn!("minimize: failure did not reproduce, try a larger --minimize-runs");
+            return Ok(None);
+        }
+
+        self.drop_traits(&mut best)?;
+        self.bisect_kprobes(&mut best)?;
+        self.lower_frequencies(&mut best)?;
+
+        Ok(Some(best))
+    }
+}
+
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Save the minimized @scenario to @path and print it, along with the command
+/// line replaying it.
+pub fn report(scenario: &Scenario, path: &Path, cmd: &[String]) -> Result<()> {
+    scenario.save(path)?;
+
+    info!(
+        "minimize: {} trait(s), kprobe random delays {}",
+        scenario.traits.len(),
+        if scenario.kprobe_random_delays.is_some() {
+            "on"
+        } else {
+            "off"
+        }
+    );
+
+    println!("{}", scenario.to_string_pretty(true)?);
+    println!(
+        "scx_chaos --scenario {} -- {}",
+        shell_quote(&path.to_string_lossy()),
+        cmd.iter()
+            .map(|a| shell_quote(a))
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::TraitScope;
+    use crate::KprobeRandomDelays;
+    use crate::Trait;
+
+    fn scenario(traits: Vec<Trait>, kprobes: &[&str]) -> Scenario {
+        Scenario {
+            seed: Some(1),
+            traits: traits.into_iter().map(Into::into).collect(),
+            kprobe_random_delays: (!kprobes.is_empty()).then(|| KprobeRandomDelays {
+                kprobes: kprobes.iter().map(|k| k.to_string()).collect(),
+                freq: 0.8,
+                min_us: 10,
+                max_us: 20,
+                scope: TraitScope::default(),
+            }),
+        }
+    }
+
+    fn kprobes(scenario: &Scenario) -> Vec<String> {
+        scenario
+            .kprobe_random_delays
+            .as_ref()
+            .map(|kd| kd.kprobes.clone())
+            .unwrap_or_default()
+    }
+
+    fn has_kprobe(scenario: &Scenario, kprobe: &str) -> bool {
+        kprobes(scenario).iter().any(|k| k == kprobe)
+    }
+
+    fn reduce(
+        scenario: Scenario,
+        reproduces: impl Fn(&Scenario) -> Result<bool>,
+    ) -> Result<Option<Scenario>> {
+        Reducer { reproduces }.reduce(scenario)
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("stress-ng"), "stress-ng");
+        assert_eq!(shell_quote("/tmp/out.toml"), "/tmp/out.toml");
+        assert_eq!(shell_quote("--timeout=10s"), "--timeout=10s");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn test_reduce_not_reproduced() {
+        let s = scenario(
+            vec![Trait::Starvation {
+                frequency: 0.5,
+                duration_us: 100,
+            }],
+            &[],
+        );
+        assert!(reduce(s, |_| Ok(false)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reduce_error() {
+        let s = scenario(vec![], &["schedule"]);
+        assert!(reduce(s, |_| bail!("scheduler failed")).is_err());
+    }
+
+    #[test]
+    fn test_reduce_traits_and_frequency() {
+        let s = scenario(
+            vec![
+                Trait::RandomDelays {
+                    frequency: 0.5,
+                    min_us: 10,
+                    max_us: 100,
+                },
+                Trait::Starvation {
+                    frequency: 0.8,
+                    duration_us: 100,
+                },
+                Trait::WakeupReorder {
+                    frequency: 0.5,
+                    window_us: 50,
+                },
+            ],
+            &["schedule", "do_exit"],
+        );
+
+        // Fails with any starvation trait of at least 10% frequency.
+        let best = reduce(s, |s| {
+            Ok(s.traits
+                .iter()
+                .any(|t| matches!(t.spec, Trait::Starvation { frequency, .. } if frequency >= 0.1)))
+        })
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(best.seed, Some(1));
+        assert!(best.kprobe_random_delays.is_none());
+        assert_eq!(best.traits.len(), 1);
+        assert!(matches!(
+            best.traits[0].spec,
+            Trait::Starvation {
+                duration_us: 100,
+                ..
+            }
+        ));
+        assert_eq!(best.traits[0].spec.frequency(), 0.1);
+    }
+
+    #[test]
+    fn test_reduce_bisect_kprobes() {
+        let s = scenario(vec![], &["a", "b", "c", "bad", "e"]);
+        let best = reduce(s, |s| Ok(has_kprobe(s, "bad"))).unwrap().unwrap();
+        assert_eq!(kprobes(&best), vec!["bad"]);
+        assert_eq!(best.kprobe_random_delays.unwrap().freq, 0.8 / 16.0);
+    }
+
+    #[test]
+    fn test_reduce_kprobe_pair() {
+        // Neither half reproduces on its own.
+        let s = scenario(vec![], &["a", "x", "b", "y"]);
+        let best = reduce(s, |s| Ok(has_kprobe(s, "x") && has_kprobe(s, "y")))
+            .unwrap()
+            .unwrap();
+        assert_eq!(kprobes(&best), vec!["x", "y"]);
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Declarative chaos scenarios.
+//!
+//! A scenario lists the traits to apply, optionally restricted to a time
+//! window and to a pid, comm prefix or cgroup, plus the PRNG seed. Scenarios
+//! are read from TOML or JSON files, picked by file extension:
+//!
+//! ```toml
+//! seed = 42
+//!
+//! [[traits]]
+//! kind = "random_delays"
+//! frequency = 0.1
+//! min_us = 100
+//! max_us = 1000
+//! start_ms = 5000
+//! comm = "worker"
+//! ```
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Map;
+use serde_json::Value;
+
+use crate::bpf_intf;
+use crate::KprobeRandomDelays;
+use crate::Trait;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Fields of [`TraitScope`], which is flattened into the traits.
+const SCOPE_FIELDS: &[&str] = &["start_ms", "end_ms", "pid", "comm", "cgroup"];
+
+/// Time window and task filters of a trait. Unset fields match everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraitScope {
+    /// Start of the window, in ms since the scheduler was attached.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub start_ms: u64,
+    /// End of the window, in ms since the scheduler was attached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_ms: Option<u64>,
+    /// Only apply to this pid or tgid.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+    /// Only apply to tasks whose comm starts with this prefix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comm: Option<String>,
+    /// Only apply to tasks in this cgroup (or its descendants), either as an
+    /// absolute path or relative to /sys/fs/cgroup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<PathBuf>,
+}
+
+fn is_zero(v: &u64) -> bool {
+    *v == 0
+}
+
+/// Split the scope fields out of the fields of a trait. serde doesn't support
+/// `deny_unknown_fields` together with `flatten`, so scoped traits are parsed
+/// in two steps where both sides reject the fields they don't know. A typo in
+/// a filter would otherwise silently apply the trait to every task.
+pub(crate) fn split_scope(
+    mut fields: Map<String, Value>,
+) -> serde_json::Result<(TraitScope, Value)> {
+    let scope: Map<String, Value> = SCOPE_FIELDS
+        .iter()
+        .filter_map(|field| fields.remove_entry(*field))
+        .collect();
+    Ok((
+        serde_json::from_value(Value::Object(scope))?,
+        Value::Object(fields),
+    ))
+}
+
+impl TraitScope {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Convert to the BPF representation, resolving the cgroup path to its id.
+    pub fn to_bpf(&self) -> Result<bpf_intf::chaos_trait_scope> {
+        // SAFETY: chaos_trait_scope is plain old data and all zeroes is a valid value.
+        let mut out: bpf_intf::chaos_trait_scope = unsafe { std::mem::zeroed() };
+        out.start_ns = self.start_ms * 1_000_000;
+        out.end_ns = self.end_ms.map(|ms| ms * 1_000_000).unwrap_or(0);
+        out.pid = self.pid.unwrap_or(0);
+
+        if let Some(end_ms) = self.end_ms {
+            if end_ms <= self.start_ms {
+                bail!(
+                    "empty window: end_ms {} <= start_ms {}",
+                    end_ms,
+                    self.start_ms
+                );
+            }
+        }
+
+        if let Some(comm) = &self.comm {
+            let max = bpf_intf::chaos_consts_CHAOS_COMM_LEN as usize - 1;
+            if comm.len() > max {
+                bail!("comm filter {:?} longer than {} bytes", comm, max);
+            }
+            for (dst, src) in out.comm.iter_mut().zip(comm.bytes()) {
+                *dst = src as _;
+            }
+        }
+
+        if let Some(cgroup) = &self.cgroup {
+            let path = if cgroup.is_absolute() {
+                cgroup.clone()
+            } else {
+                Path::new(CGROUP_ROOT).join(cgroup)
+            };
+            out.cgroup_id = std::fs::metadata(&path)
+                .with_context(|| format!("Failed to resolve cgroup {}", path.display()))?
+                .ino();
+        }
+
+        Ok(out)
+    }
+}
+
+/// A trait together with its scope.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Map<String, Value>")]
+pub struct ScopedTrait {
+    #[serde(flatten)]
+    pub spec: Trait,
+    #[serde(flatten)]
+    pub scope: TraitScope,
+}
+
+impl TryFrom<Map<String, Value>> for ScopedTrait {
+    type Error = serde_json::Error;
+
+    fn try_from(fields: Map<String, Value>) -> serde_json::Result<Self> {
+        let (scope, spec) = split_scope(fields)?;
+        Ok(Self {
+            spec: serde_json::from_value(spec)?,
+            scope,
+        })
+    }
+}
+
+impl From<Trait> for ScopedTrait {
+    fn from(spec: Trait) -> Self {
+        Self {
+            spec,
+            scope: TraitScope::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    /// Seed of the BPF PRNG. Overridden by --seed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub traits: Vec<ScopedTrait>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kprobe_random_delays: Option<KprobeRandomDelays>,
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let buf = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario {}", path.display()))?;
+
+        let scenario: Self = if is_toml(path) {
+            toml::from_str(&buf)?
+        } else {
+            serde_json::from_str(&buf)?
+        };
+
+        Ok(scenario)
+    }
+
+    pub fn to_string_pretty(&self, toml: bool) -> Result<String> {
+        Ok(if toml {
+            toml::to_string_pretty(self)?
+        } else {
+            serde_json::to_string_pretty(self)?
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_string_pretty(is_toml(path))?)
+            .with_context(|| format!("Failed to write scenario {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(buf: &str) -> Result<Scenario> {
+        Ok(toml::from_str(buf)?)
+    }
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario = parse(
+            r#"
+            seed = 42
+
+            [[traits]]
+            kind = "random_delays"
+            frequency = 0.1
+            min_us = 100
+            max_us = 1000
+            start_ms = 5000
+            comm = "worker"
+
+            [[traits]]
+            kind = "forced_migration"
+            frequency = 0.5
+            scope = "llc"
+            cgroup = "workload.slice"
+
+            [kprobe_random_delays]
+            kprobes = ["schedule"]
+            frequency = 0.2
+            min_us = 10
+            max_us = 20
+            pid = 1234
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(scenario.seed, Some(42));
+        assert_eq!(scenario.traits.len(), 2);
+        assert!(matches!(
+            scenario.traits[0].spec,
+            Trait::RandomDelays { min_us: 100, .. }
+        ));
+        assert_eq!(scenario.traits[0].scope.start_ms, 5000);
+        assert_eq!(scenario.traits[0].scope.comm.as_deref(), Some("worker"));
+        assert!(matches!(
+            scenario.traits[1].spec,
+            Trait::ForcedMigration {
+                scope: crate::MigrationScope::Llc,
+                ..
+            }
+        ));
+        assert_eq!(
+            scenario.traits[1].scope.cgroup,
+            Some(PathBuf::from("workload.slice"))
+        );
+        let kprobes = scenario.kprobe_random_delays.unwrap();
+        assert_eq!(kprobes.kprobes, vec!["schedule".to_string()]);
+        assert_eq!(kprobes.scope.pid, Some(1234));
+    }
+
+    #[test]
+    fn test_parse_json_roundtrip() {
+        let scenario = parse(
+            r#"
+            [[traits]]
+            kind = "starvation"
+            frequency = 0.01
+            duration_us = 500
+            end_ms = 1000
+            "#,
+        )
+        .unwrap();
+
+        let json = scenario.to_string_pretty(false).unwrap();
+        let parsed: Scenario = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.traits.len(), 1);
+        assert_eq!(parsed.traits[0].scope.end_ms, Some(1000));
+        assert!(parsed.traits[0].scope.comm.is_none());
+    }
+
+    #[test]
+    fn test_parse_empty_scenario() {
+        let scenario = parse("").unwrap();
+        assert_eq!(scenario.seed, None);
+        assert!(scenario.traits.is_empty());
+        assert!(scenario.kprobe_random_delays.is_none());
+    }
+
+    #[test]
+    fn test_parse_unknown_fields() {
+        // Typo in a filter, this must not widen the trait to every task.
+        assert!(parse(
+            r#"
+            [[traits]]
+            kind = "random_delays"
+            frequency = 0.1
+            min_us = 100
+            max_us = 1000
+            comn = "worker"
+            "#
+        )
+        .is_err());
+
+        // Typo in a trait field.
+        assert!(parse(
+            r#"
+            [[traits]]
+            kind = "starvation"
+            frequency = 0.1
+            duration_us = 500
+            durration_us = 1000
+            "#
+        )
+        .is_err());
+
+        // Typo in the kprobe filters.
+        assert!(parse(
+            r#"
+            [kprobe_random_delays]
+            kprobes = ["schedule"]
+            frequency = 0.2
+            min_us = 10
+            max_us = 20
+            pids = 1234
+            "#
+        )
+        .is_err());
+
+        // Typo at the top level.
+        assert!(parse("sead = 42").is_err());
+    }
+}
@@ -29,16 +29,25 @@ pub struct Metrics {
     pub timer_kicks: u64,
     #[stat(desc = "Number of times a kprobe caused a random delay to be applied")]
     pub kprobe_random_delays: u64,
+    #[stat(desc = "Number of times wakeup reorder chaos trait was applied")]
+    pub trait_wakeup_reorder: u64,
+    #[stat(desc = "Number of times forced migration chaos trait was applied")]
+    pub trait_forced_migration: u64,
+    #[stat(desc = "Number of times starvation chaos trait was applied")]
+    pub trait_starvation: u64,
 }
 
 impl Metrics {
     fn format<W: Write>(&self, w: &mut W) -> Result<()> {
         writeln!(
             w,
-            "chaos traits: random_delays/cpu_freq/degradation {}/{}/{}\n\tchaos excluded/skipped {}/{}\n\tkprobe_random_delays {}\n\ttimer kicks: {}",
+            "chaos traits: random_delays/cpu_freq/degradation {}/{}/{}\n\treorder/migration/starvation {}/{}/{}\n\tchaos excluded/skipped {}/{}\n\tkprobe_random_delays {}\n\ttimer kicks: {}",
             self.trait_random_delays,
             self.trait_cpu_freq,
             self.trait_degradation,
+            self.trait_wakeup_reorder,
+            self.trait_forced_migration,
+            self.trait_starvation,
             self.chaos_excluded,
             self.chaos_skipped,
             self.kprobe_random_delays,
@@ -56,6 +65,9 @@ impl Metrics {
             chaos_skipped: self.chaos_skipped - rhs.chaos_skipped,
             kprobe_random_delays: self.kprobe_random_delays - rhs.kprobe_random_delays,
             timer_kicks: self.timer_kicks - rhs.timer_kicks,
+            trait_wakeup_reorder: self.trait_wakeup_reorder - rhs.trait_wakeup_reorder,
+            trait_forced_migration: self.trait_forced_migration - rhs.trait_forced_migration,
+            trait_starvation: self.trait_starvation - rhs.trait_starvation,
         }
     }
 }
@@ -92,3 +104,37 @@ pub fn monitor(intv: Duration, shutdown: Arc<(Mutex<bool>, Condvar)>) -> Result<
         |metrics| metrics.format(&mut std::io::stdout()),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trait_stats() {
+        let prev = Metrics {
+            trait_wakeup_reorder: 1,
+            trait_forced_migration: 2,
+            trait_starvation: 3,
+            ..Default::default()
+        };
+        let cur = Metrics {
+            trait_wakeup_reorder: 11,
+            trait_forced_migration: 22,
+            trait_starvation: 33,
+            ..Default::default()
+        };
+
+        let delta = cur.delta(&prev);
+        assert_eq!(delta.trait_wakeup_reorder, 10);
+        assert_eq!(delta.trait_forced_migration, 20);
+        assert_eq!(delta.trait_starvation, 30);
+
+        let mut buf = Vec::new();
+        delta.format(&mut buf).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(
+            out.contains("reorder/migration/starvation 10/20/30"),
+            "{out}"
+        );
+    }
+}
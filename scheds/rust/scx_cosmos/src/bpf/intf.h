@@ -12,6 +12,19 @@ enum consts {
 	NSEC_PER_USEC = 1000ULL,
 	NSEC_PER_MSEC = (1000ULL * NSEC_PER_USEC),
 	NSEC_PER_SEC = (1000ULL * NSEC_PER_MSEC),
+
+	/* log2 buckets of the per-task perf event histograms */
+	PERF_HIST_BUCKETS = 65,
+
+	/* Maximum amount of tracked event heavy tasks */
+	MAX_EVENT_TASKS = 1024,
+
+	TASK_COMM_LEN = 16,
+};
+
+enum event_task_flags {
+	EVENT_TASK_HEAVY = 1 << 0,
+	EVENT_TASK_STICKY = 1 << 1,
 };
 
 #ifndef __VMLINUX_H__
@@ -37,4 +50,16 @@ struct domain_arg {
 	s32 sibling_cpu_id;
 };
 
+/*
+ * A task recently classified as event heavy, keyed by pid.
+ */
+struct event_task {
+	char comm[TASK_COMM_LEN];
+	u64 perf_events;
+	u64 perf_sticky_events;
+	u64 last_seen_ns;
+	s32 tgid;
+	u32 flags;
+};
+
 #endif /* __INTF_H */
@@ -126,6 +126,19 @@ const volatile u64 perf_sticky;
  */
 volatile u64 perf_sticky_threshold;
 
+/*
+ * Collect the distribution of the per-task perf event counts, used by
+ * user-space to calibrate the thresholds (--perf-auto).
+ */
+const volatile bool perf_hist_enabled;
+
+/*
+ * log2 histograms of the perf event counts observed at each task stop
+ * (bucket N counts values in [2^(N-1), 2^N)).
+ */
+volatile u64 perf_hist[PERF_HIST_BUCKETS];
+volatile u64 perf_sticky_hist[PERF_HIST_BUCKETS];
+
 /*
  * Disable high-resolution preemption enforcement.
  */
@@ -167,6 +180,17 @@ struct {
 	__type(value, u64);
 } cpu_util_map SEC(".maps");
 
+/*
+ * Tasks recently classified as event heavy, updated by update_event_stats()
+ * and read by user-space to report per-task event stats.
+ */
+struct {
+	__uint(type, BPF_MAP_TYPE_LRU_HASH);
+	__uint(max_entries, MAX_EVENT_TASKS);
+	__type(key, s32);
+	__type(value, struct event_task);
+} event_task_map SEC(".maps");
+
 /*
  * Scheduler statistics.
  */
@@ -372,6 +396,56 @@ static inline bool is_sticky_event_heavy(const struct task_ctx *tctx)
 	return perf_sticky && tctx->perf_sticky_events > perf_sticky_threshold;
 }
 
+/*
+ * Histogram bucket of a perf event count: bucket 0 holds zero and bucket N
+ * holds values in [2^(N-1), 2^N).
+ */
+static inline u32 perf_hist_bucket(u64 count)
+{
+	return count ? log2_u64(count) : 0;
+}
+
+/*
+ * Account the last perf event counts of @p to the histograms and record it
+ * in event_task_map if it's classified as event heavy.
+ */
+static void update_event_stats(struct task_struct *p, const struct task_ctx *tctx)
+{
+	struct event_task *et, new_et = {};
+	u32 flags = 0;
+	s32 pid = p->pid;
+
+	if (perf_hist_enabled) {
+		if (perf_config)
+			__sync_fetch_and_add(&perf_hist[perf_hist_bucket(tctx->perf_events) %
+							PERF_HIST_BUCKETS], 1);
+		if (perf_sticky)
+			__sync_fetch_and_add(&perf_sticky_hist[perf_hist_bucket(tctx->perf_sticky_events) %
+							       PERF_HIST_BUCKETS], 1);
+	}
+
+	if (is_event_heavy(tctx))
+		flags |= EVENT_TASK_HEAVY;
+	if (is_sticky_event_heavy(tctx))
+		flags |= EVENT_TASK_STICKY;
+	if (!flags)
+		return;
+
+	et = bpf_map_lookup_elem(&event_task_map, &pid);
+	if (!et) {
+		bpf_probe_read_kernel_str(new_et.comm, sizeof(new_et.comm), p->comm);
+		new_et.tgid = p->tgid;
+		bpf_map_update_elem(&event_task_map, &pid, &new_et, BPF_ANY);
+		et = bpf_map_lookup_elem(&event_task_map, &pid);
+		if (!et)
+			return;
+	}
+	et->perf_events = tctx->perf_events;
+	et->perf_sticky_events = tctx->perf_sticky_events;
+	et->last_seen_ns = bpf_ktime_get_ns();
+	et->flags = flags;
+}
+
 /*
  * Exponential weighted moving average (EWMA).
  *
@@ -1376,6 +1450,7 @@ void BPF_STRUCT_OPS(cosmos_stopping, struct task_struct *p, bool runnable)
 	if (perf_config || perf_sticky) {
 		scx_pmu_event_stop(p);
 		update_counters(p, tctx, cpu);
+		update_event_stats(p, tctx);
 	}
 
 	/*
@@ -10,9 +10,10 @@ pub use bpf_skel::*;
 pub mod bpf_intf;
 pub use bpf_intf::*;
 
+mod perf_auto;
 mod stats;
-use std::collections::{HashMap, HashSet};
-use std::ffi::{c_int, c_ulong};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{c_int, c_ulong, CStr};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::mem::MaybeUninit;
@@ -33,6 +34,7 @@ use libbpf_rs::ProgramInput;
 use log::{debug, info, warn};
 use nvml_wrapper::bitmasks::InitFlags;
 use nvml_wrapper::Nvml;
+use perf_auto::PerfCalibrator;
 use scx_stats::prelude::*;
 use scx_utils::build_id;
 use scx_utils::compat;
@@ -52,10 +54,13 @@ use scx_utils::Powermode;
 use scx_utils::Topology;
 use scx_utils::UserExitInfo;
 use scx_utils::NR_CPU_IDS;
+use stats::EventTaskMetrics;
 use stats::Metrics;
 
 const SCHEDULER_NAME: &str = "scx_cosmos";
 
+unsafe impl plain::Plain for bpf_intf::event_task {}
+
 #[derive(Debug, clap::Parser)]
 #[command(
     name = "scx_cosmos",
@@ -141,6 +146,19 @@ struct Opts {
     #[clap(short = 'Y', default_value = "0", long)]
     perf_sticky_threshold: u64,
 
+    /// Automatically select the migration perf event and calibrate the thresholds.
+    ///
+    /// Pick a cache-miss / memory-stall event supported by the local CPU (overriding -e) and
+    /// periodically set the dynamic thresholds (-E 0, -Y 0) to the --perf-auto-percentile of the
+    /// observed per-task event counts.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    perf_auto: bool,
+
+    /// Percentile of the observed per-task perf event distribution used as threshold with
+    /// --perf-auto.
+    #[clap(long, default_value = "90", value_parser = clap::value_parser!(u64).range(1..100))]
+    perf_auto_percentile: u64,
+
     /// Enable GPU-aware scheduling.
     #[clap(short = 'g', long, action = clap::ArgAction::SetTrue)]
     gpu: bool,
@@ -356,6 +374,9 @@ const DYNAMIC_THRESHOLD_SLOPE_LOW: f64 = 0.58;
 /// polling (e.g. 100 ms) does not trigger expensive NVML calls every tick.
 const GPU_SYNC_INTERVAL: Duration = Duration::from_secs(1);
 
+/// Event heavy tasks not seen within this interval are not reported in the stats.
+const EVENT_TASK_MAX_AGE: Duration = Duration::from_secs(5);
+
 /// State for EMA-based dynamic threshold adjustment with hysteresis.
 ///
 /// This struct maintains the smoothed rate estimate and tracks whether we're
@@ -535,6 +556,10 @@ struct Scheduler<'a> {
     perf_threshold_state: Option<DynamicThresholdState>,
     /// Dynamic threshold state for sticky perf events (when --perf-sticky-threshold is 0/dynamic).
     perf_sticky_threshold_state: Option<DynamicThresholdState>,
+    /// Percentile calibration of the migration threshold (--perf-auto).
+    perf_calibrator: Option<PerfCalibrator>,
+    /// Percentile calibration of the sticky threshold (--perf-auto).
+    perf_sticky_calibrator: Option<PerfCalibrator>,
 }
 
 impl<'a> Scheduler<'a> {
@@ -599,6 +624,7 @@ impl<'a> Scheduler<'a> {
         // Enable perf event scheduling settings.
         rodata.perf_config = opts.perf_config.event_id;
         rodata.perf_sticky = opts.perf_sticky.event_id;
+        rodata.perf_hist_enabled = opts.perf_auto;
 
         // Normalize CPU busy threshold in the range [0 .. 1024].
         rodata.busy_threshold = opts.cpu_busy_thresh * 1024 / 100;
@@ -798,16 +824,41 @@ impl<'a> Scheduler<'a> {
         let stats_server = StatsServer::new(stats::server_data()).launch()?;
 
         // Initialize dynamic threshold states for perf events (only when using dynamic mode).
-        let perf_threshold_state = if opts.perf_config.event_id > 0 && opts.perf_threshold == 0 {
-            Some(DynamicThresholdState::new(DYNAMIC_THRESHOLD_INIT_VALUE))
-        } else {
-            None
+        // With --perf-auto the dynamic thresholds are calibrated from the event distribution
+        // instead.
+        let perf_dynamic = opts.perf_config.event_id > 0 && opts.perf_threshold == 0;
+        let perf_sticky_dynamic = opts.perf_sticky.event_id > 0 && opts.perf_sticky_threshold == 0;
+        let percentile = opts.perf_auto_percentile as f64;
+        let (perf_threshold_state, perf_calibrator) = match (perf_dynamic, opts.perf_auto) {
+            (false, _) => (None, None),
+            (true, false) => (
+                Some(DynamicThresholdState::new(DYNAMIC_THRESHOLD_INIT_VALUE)),
+                None,
+            ),
+            (true, true) => (
+                None,
+                Some(PerfCalibrator::new(
+                    "perf_threshold",
+                    percentile,
+                    DYNAMIC_THRESHOLD_INIT_VALUE,
+                )),
+            ),
         };
-        let perf_sticky_threshold_state =
-            if opts.perf_sticky.event_id > 0 && opts.perf_sticky_threshold == 0 {
-                Some(DynamicThresholdState::new(DYNAMIC_THRESHOLD_INIT_VALUE))
-            } else {
-                None
+        let (perf_sticky_threshold_state, perf_sticky_calibrator) =
+            match (perf_sticky_dynamic, opts.perf_auto) {
+                (false, _) => (None, None),
+                (true, false) => (
+                    Some(DynamicThresholdState::new(DYNAMIC_THRESHOLD_INIT_VALUE)),
+                    None,
+                ),
+                (true, true) => (
+                    None,
+                    Some(PerfCalibrator::new(
+                        "perf_sticky_threshold",
+                        percentile,
+                        DYNAMIC_THRESHOLD_INIT_VALUE,
+                    )),
+                ),
             };
 
         Ok(Self {
@@ -820,6 +871,8 @@ impl<'a> Scheduler<'a> {
             nvml,
             perf_threshold_state,
             perf_sticky_threshold_state,
+            perf_calibrator,
+            perf_sticky_calibrator,
         })
     }
 
@@ -968,12 +1021,62 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
+    /// Collect the tasks classified as event heavy within the last EVENT_TASK_MAX_AGE.
+    fn get_event_tasks(&self) -> BTreeMap<String, EventTaskMetrics> {
+        let mut tasks = BTreeMap::new();
+        let map = &self.skel.maps.event_task_map;
+        let now = perf_auto::now_monotonic();
+        let max_age = EVENT_TASK_MAX_AGE.as_nanos() as u64;
+
+        for key in map.keys() {
+            let Ok(Some(val)) = map.lookup(&key, MapFlags::ANY) else {
+                continue;
+            };
+            let Ok(et) = plain::from_bytes::<bpf_intf::event_task>(&val) else {
+                continue;
+            };
+            if now.saturating_sub(et.last_seen_ns) > max_age {
+                continue;
+            }
+
+            let pid = i32::from_ne_bytes(key[..4].try_into().unwrap());
+            let comm: Vec<u8> = et.comm.iter().map(|&c| c as u8).collect();
+            let comm = CStr::from_bytes_until_nul(&comm)
+                .map(|c| c.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut classes = vec![];
+            if et.flags & bpf_intf::event_task_flags_EVENT_TASK_HEAVY != 0 {
+                classes.push("migrate");
+            }
+            if et.flags & bpf_intf::event_task_flags_EVENT_TASK_STICKY != 0 {
+                classes.push("sticky");
+            }
+
+            tasks.insert(
+                pid.to_string(),
+                EventTaskMetrics {
+                    comm,
+                    tgid: et.tgid as u64,
+                    perf_events: et.perf_events,
+                    perf_sticky_events: et.perf_sticky_events,
+                    classes: classes.join(","),
+                },
+            );
+        }
+
+        tasks
+    }
+
     fn get_metrics(&self) -> Metrics {
         let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
         Metrics {
             nr_event_dispatches: bss_data.nr_event_dispatches,
             nr_ev_sticky_dispatches: bss_data.nr_ev_sticky_dispatches,
             nr_gpu_dispatches: bss_data.nr_gpu_dispatches,
+            perf_event: self.opts.perf_config.display_name.clone(),
+            perf_threshold: bss_data.perf_threshold,
+            perf_sticky_threshold: bss_data.perf_sticky_threshold,
+            event_tasks: self.get_event_tasks(),
         }
     }
 
@@ -1122,6 +1225,22 @@ impl<'a> Scheduler<'a> {
                     }
                 }
 
+                // Recalibrate the thresholds from the observed event distribution.
+                if self.perf_calibrator.is_some() || self.perf_sticky_calibrator.is_some() {
+                    let verbose = self.opts.verbose;
+                    let bss = self.skel.maps.bss_data.as_mut().unwrap();
+                    if let Some(ref mut cal) = self.perf_calibrator {
+                        if let Some(new_thresh) = cal.update(&bss.perf_hist, verbose) {
+                            bss.perf_threshold = new_thresh;
+                        }
+                    }
+                    if let Some(ref mut cal) = self.perf_sticky_calibrator {
+                        if let Some(new_thresh) = cal.update(&bss.perf_sticky_hist, verbose) {
+                            bss.perf_sticky_threshold = new_thresh;
+                        }
+                    }
+                }
+
                 last_update = Instant::now();
             }
 
@@ -1158,7 +1277,7 @@ impl Drop for Scheduler<'_> {
 }
 
 fn main() -> Result<()> {
-    let opts = Opts::parse();
+    let mut opts = Opts::parse();
 
     if opts.version {
         println!(
@@ -1218,6 +1337,11 @@ fn main() -> Result<()> {
         }
     }
 
+    if opts.perf_auto {
+        let fallback = opts.perf_config.clone();
+        opts.perf_config = perf_auto::resolve_event(fallback);
+    }
+
     let mut open_object = MaybeUninit::uninit();
     loop {
         let mut sched = Scheduler::init(&opts, &mut open_object)?;
@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: GPL-2.0
+//
+// Copyright (c) 2025 Andrea Righi <arighi@nvidia.com>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Automatic perf event selection and threshold calibration (--perf-auto).
+
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+
+use anyhow::bail;
+use anyhow::Result;
+use log::{debug, info, warn};
+use scx_raw_pmu::PMUManager;
+use scx_utils::perf::bindings;
+use scx_utils::perf::parse_perf_event;
+use scx_utils::perf::perf_event_open;
+use scx_utils::perf::PerfEventSpec;
+
+/// Raw cache-miss / memory-stall events, in order of preference.
+const RAW_CANDIDATES: &[&str] = &[
+    "CYCLE_ACTIVITY.STALLS_L3_MISS",
+    "LONGEST_LAT_CACHE.MISS",
+    "ls_any_fills_from_sys.dram_io_all",
+    "ls_any_fills_from_sys.mem_io_local",
+];
+
+/// Generic events used when no raw event is known for the local CPU.
+const SYMBOLIC_CANDIDATES: &[&str] = &["LLC-load-misses", "cache-misses", "stalled-cycles-backend"];
+
+/// Minimum amount of samples required to recalibrate a threshold.
+const CALIBRATION_MIN_SAMPLES: u64 = 128;
+
+/// Return true if @spec can be opened on CPU 0.
+fn probe_event(spec: &PerfEventSpec) -> bool {
+    let mut attrs = bindings::perf_event_attr {
+        type_: spec.type_,
+        config: spec.config,
+        size: std::mem::size_of::<bindings::perf_event_attr>() as u32,
+        ..Default::default()
+    };
+
+    let fd = unsafe { perf_event_open(&mut attrs, -1, 0, -1, 0) };
+    if fd < 0 {
+        debug!(
+            "perf event '{}' not available: {}",
+            spec.display_name,
+            std::io::Error::last_os_error()
+        );
+        return false;
+    }
+    drop(unsafe { OwnedFd::from_raw_fd(fd) });
+
+    true
+}
+
+/// Build the list of candidate events supported by the local CPU model.
+fn candidates() -> Vec<PerfEventSpec> {
+    let mut specs = vec![];
+
+    match PMUManager::new() {
+        Ok(pmu) => {
+            debug!("PMU codename: {}", pmu.codename);
+            for name in RAW_CANDIDATES {
+                let Some(ev) = pmu.pmus.get(*name) else {
+                    continue;
+                };
+                let Some(event) = ev.event.first() else {
+                    continue;
+                };
+                let config = (ev.umask << 8) | event;
+                if let Ok(mut spec) = parse_perf_event(&format!("{:#x}", config)) {
+                    spec.display_name = format!("{} ({:#x})", name, config);
+                    specs.push(spec);
+                }
+            }
+        }
+        Err(e) => debug!("Failed to load PMU event data: {}", e),
+    }
+
+    for name in SYMBOLIC_CANDIDATES {
+        if let Ok(spec) = parse_perf_event(name) {
+            specs.push(spec);
+        }
+    }
+
+    specs
+}
+
+/// Pick the first cache-miss / memory-stall event that can be opened on this system.
+pub fn select_event() -> Result<PerfEventSpec> {
+    for spec in candidates() {
+        if probe_event(&spec) {
+            info!("perf-auto: selected event {}", spec.display_name);
+            return Ok(spec);
+        }
+    }
+
+    bail!("perf-auto: no supported cache-miss or memory-stall perf event found")
+}
+
+/// Value at @pct percentile of a log2 histogram as filled by perf_hist_bucket()
+/// in BPF (bucket 0 holds zero and bucket N holds values in [2^(N-1), 2^N)),
+/// linearly interpolated within the matching bucket.
+///
+/// Return None if the histogram is empty.
+pub fn hist_percentile(hist: &[u64], pct: f64) -> Option<u64> {
+    let total: u64 = hist.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let target = (total as f64 * pct / 100.0).max(1.0);
+    let mut seen = 0u64;
+    for (bucket, &count) in hist.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        if (seen + count) as f64 >= target {
+            let (lo, hi) = match bucket {
+                0 => (0.0, 0.0),
+                b => ((1u64 << (b - 1)) as f64, (1u128 << b) as f64),
+            };
+            let frac = (target - seen as f64) / count as f64;
+            return Some((lo + (hi - lo) * frac).round() as u64);
+        }
+        seen += count;
+    }
+
+    None
+}
+
+/// Percentile based threshold calibration from the BPF perf event histograms.
+pub struct PerfCalibrator {
+    name: &'static str,
+    percentile: f64,
+    prev_hist: Vec<u64>,
+    threshold: u64,
+}
+
+impl PerfCalibrator {
+    pub fn new(name: &'static str, percentile: f64, threshold: u64) -> Self {
+        Self {
+            name,
+            percentile,
+            prev_hist: vec![],
+            threshold,
+        }
+    }
+
+    /// Recompute the threshold from the samples collected since the last
+    /// recalibration. Return the new threshold if it changed.
+    pub fn update(&mut self, hist: &[u64], verbose: bool) -> Option<u64> {
+        self.prev_hist.resize(hist.len(), 0);
+        let delta: Vec<u64> = hist
+            .iter()
+            .zip(self.prev_hist.iter())
+            .map(|(cur, prev)| cur.wrapping_sub(*prev))
+            .collect();
+
+        // Keep accumulating until we have enough samples.
+        if delta.iter().sum::<u64>() < CALIBRATION_MIN_SAMPLES {
+            return None;
+        }
+        self.prev_hist.copy_from_slice(hist);
+
+        let threshold = hist_percentile(&delta, self.percentile)?.max(1);
+        if threshold == self.threshold {
+            return None;
+        }
+        if verbose {
+            info!(
+                "{}: {} -> {} (p{:.0})",
+                self.name, self.threshold, threshold, self.percentile
+            );
+        }
+        self.threshold = threshold;
+
+        Some(threshold)
+    }
+}
+
+/// Resolve the migration perf event for --perf-auto, keeping @fallback if
+/// no suitable event is available.
+pub fn resolve_event(fallback: PerfEventSpec) -> PerfEventSpec {
+    match select_event() {
+        Ok(spec) => spec,
+        Err(e) => {
+            warn!("{}", e);
+            fallback
+        }
+    }
+}
+
+/// Current CLOCK_MONOTONIC time in ns (same clock as bpf_ktime_get_ns()).
+pub fn now_monotonic() -> u64 {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let ret = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+    assert!(ret == 0);
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hist(buckets: &[(usize, u64)]) -> Vec<u64> {
+        let mut hist = vec![0; 65];
+        for &(bucket, count) in buckets {
+            hist[bucket] = count;
+        }
+        hist
+    }
+
+    #[test]
+    fn test_hist_percentile_empty() {
+        assert_eq!(hist_percentile(&hist(&[]), 50.0), None);
+        assert_eq!(hist_percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_hist_percentile_zero_bucket() {
+        // Tasks without events must not inflate the percentiles.
+        let h = hist(&[(0, 90), (4, 10)]);
+        assert_eq!(hist_percentile(&h, 50.0), Some(0));
+        assert_eq!(hist_percentile(&h, 90.0), Some(0));
+        assert_eq!(hist_percentile(&h, 95.0), Some(12));
+    }
+
+    #[test]
+    fn test_hist_percentile_interpolation() {
+        // Bucket 3 holds [4, 8).
+        let h = hist(&[(3, 100)]);
+        assert_eq!(hist_percentile(&h, 50.0), Some(6));
+        assert_eq!(hist_percentile(&h, 100.0), Some(8));
+
+        let h = hist(&[(1, 99), (10, 1)]);
+        assert_eq!(hist_percentile(&h, 99.0), Some(2));
+        assert_eq!(hist_percentile(&h, 100.0), Some(1024));
+    }
+
+    #[test]
+    fn test_calibrator_min_samples() {
+        let mut cal = PerfCalibrator::new("test", 50.0, 1000);
+
+        // Not enough samples yet, keep accumulating.
+        assert_eq!(cal.update(&hist(&[(3, 100)]), false), None);
+        assert_eq!(cal.update(&hist(&[(3, 200)]), false), Some(6));
+
+        // No new samples since the last calibration.
+        assert_eq!(cal.update(&hist(&[(3, 200)]), false), None);
+    }
+
+    #[test]
+    fn test_calibrator_uses_delta() {
+        let mut cal = PerfCalibrator::new("test", 50.0, 1000);
+        assert_eq!(cal.update(&hist(&[(3, 200)]), false), Some(6));
+
+        // Only the samples collected since the last calibration count.
+        assert_eq!(cal.update(&hist(&[(3, 200), (11, 200)]), false), Some(1536));
+
+        // Same threshold as before isn't reported as a change.
+        assert_eq!(cal.update(&hist(&[(3, 200), (11, 400)]), false), None);
+    }
+
+    #[test]
+    fn test_calibrator_min_threshold() {
+        let mut cal = PerfCalibrator::new("test", 50.0, 1000);
+        assert_eq!(cal.update(&hist(&[(0, 200)]), false), Some(1));
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -11,6 +12,22 @@ use scx_stats_derive::Stats;
 use serde::Deserialize;
 use serde::Serialize;
 
+#[stat_doc]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
+#[stat(_om_prefix = "t_", _om_label = "pid")]
+pub struct EventTaskMetrics {
+    #[stat(desc = "Task name")]
+    pub comm: String,
+    #[stat(desc = "Thread group id")]
+    pub tgid: u64,
+    #[stat(desc = "Perf events during the last run (migration event)")]
+    pub perf_events: u64,
+    #[stat(desc = "Perf events during the last run (sticky event)")]
+    pub perf_sticky_events: u64,
+    #[stat(desc = "Classification: migrate, sticky or both")]
+    pub classes: String,
+}
+
 #[stat_doc]
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(top)]
@@ -21,6 +38,14 @@ pub struct Metrics {
     pub nr_ev_sticky_dispatches: u64,
     #[stat(desc = "Direct dispatch due to GPU affinity")]
     pub nr_gpu_dispatches: u64,
+    #[stat(desc = "Perf event used for migrations")]
+    pub perf_event: String,
+    #[stat(desc = "Current migration perf threshold")]
+    pub perf_threshold: u64,
+    #[stat(desc = "Current sticky perf threshold")]
+    pub perf_sticky_threshold: u64,
+    #[stat(desc = "Tasks recently classified as event heavy, keyed by pid")]
+    pub event_tasks: BTreeMap<String, EventTaskMetrics>,
 }
 
 impl Metrics {
@@ -33,6 +58,28 @@ impl Metrics {
             self.nr_ev_sticky_dispatches,
             self.nr_gpu_dispatches,
         )?;
+
+        if self.event_tasks.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            w,
+            "  perf_event={} threshold={} sticky_threshold={} event_tasks={}",
+            self.perf_event,
+            self.perf_threshold,
+            self.perf_sticky_threshold,
+            self.event_tasks.len(),
+        )?;
+
+        let mut tasks: Vec<_> = self.event_tasks.iter().collect();
+        tasks.sort_by_key(|(_, t)| std::cmp::Reverse(t.perf_events.max(t.perf_sticky_events)));
+        for (pid, t) in tasks.iter().take(10) {
+            writeln!(
+                w,
+                "  {:>8} {:<16} events={:<10} sticky_events={:<10} {}",
+                pid, t.comm, t.perf_events, t.perf_sticky_events, t.classes,
+            )?;
+        }
         Ok(())
     }
 
@@ -41,6 +88,7 @@ impl Metrics {
             nr_event_dispatches: self.nr_event_dispatches - rhs.nr_event_dispatches,
             nr_ev_sticky_dispatches: self.nr_ev_sticky_dispatches - rhs.nr_ev_sticky_dispatches,
             nr_gpu_dispatches: self.nr_gpu_dispatches - rhs.nr_gpu_dispatches,
+            ..self.clone()
         }
     }
 }
@@ -62,6 +110,7 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
     });
 
     StatsServerData::new()
+        .add_meta(EventTaskMetrics::meta())
         .add_meta(Metrics::meta())
         .add_ops("top", StatsOps { open, close: None })
 }
@@ -19,6 +19,8 @@ enum consts {
 	MAX_CPUS_U8 = MAX_CPUS / 8,
 	MAX_CELLS = 256,
 	MAX_SUBCELLS_PER_CELL = 8,
+	MAX_SUBCELL_RULES = 64,
+	SUBCELL_COMM_LEN = 16,
 	USAGE_HALF_LIFE = 100000000, /* 100ms */
 
 	MAX_CG_DEPTH = 256,
@@ -68,6 +70,8 @@ struct cpu_ctx {
 struct cgrp_ctx {
 	u32 cell;
 	bool cell_owner;
+	// Configuration seq in which this cgroup was last assigned as a cell owner
+	u32 owner_seq;
 };
 
 /*
@@ -109,11 +113,26 @@ struct subcell {
 	struct subcell_llc llcs[MAX_LLCS];
 };
 
+enum cell_policy_flags {
+	/* Use a single queue per subcell even with LLC awareness enabled */
+	CELL_POLICY_NO_LLC_AWARE = 1 << 0,
+};
+
+/* Per-cell scheduling policy, set by userspace with the cell configuration. */
+struct cell_policy {
+	// Slice of the cell's tasks, 0 for the default slice
+	u64 slice_ns;
+	// enum cell_policy_flags
+	u32 flags;
+};
+
 struct cell {
-	// cgroup ID of the cell owner (0 for cell 0 or if no owner)
+	// cgroup ID of the cell owner (0 for cell 0 or if no owner, the last
+	// assigned one if the cell has several owners)
 	u64 owner_cgid;
 	// Whether or not the cell is used
 	u32 in_use;
+	struct cell_policy policy;
 
 	// Fixed-size subcell state owned by this cell.
 	struct subcell subcells[MAX_SUBCELLS_PER_CELL];
@@ -130,6 +149,25 @@ struct cell_assignment {
 	u32 cell_id; /* cell ID to assign */
 };
 
+enum subcell_rule_flags {
+	SUBCELL_RULE_COMM = 1 << 0,
+	SUBCELL_RULE_HINT = 1 << 1,
+};
+
+/*
+ * Subcell rule: a task of @cell whose comm starts with @comm (if
+ * SUBCELL_RULE_COMM) and whose task hint is @hint (if SUBCELL_RULE_HINT) is
+ * placed in @subcell. Rules are evaluated in order, tasks matching no rule of
+ * their cell stay in subcell 0.
+ */
+struct subcell_rule {
+	u64 hint;
+	u32 cell;
+	u32 subcell;
+	u32 flags;
+	char comm[SUBCELL_COMM_LEN];
+};
+
 /*
  * cell_config: Complete cell configuration populated by userspace.
  *
@@ -138,6 +176,8 @@ struct cell_assignment {
  * - Cell-to-cgroup assignments (which cgroups own which cells)
  * - Cell cpumasks (which CPUs belong to each cell)
  * - Per-cell subcell cpumasks
+ * - Per-cell scheduling policies
+ * - Subcell rules picking the subcell of each task
  */
 struct cell_config {
 	u32 num_cell_assignments;
@@ -146,6 +186,9 @@ struct cell_config {
 	struct cell_cpumask_data cpumasks[MAX_CELLS];
 	struct cell_cpumask_data borrowable_cpumasks[MAX_CELLS];
 	struct subcell_config subcells[MAX_CELLS][MAX_SUBCELLS_PER_CELL];
+	struct cell_policy policies[MAX_CELLS];
+	u32 num_subcell_rules;
+	struct subcell_rule subcell_rules[MAX_SUBCELL_RULES];
 };
 
 #endif /* __INTF_H */
@@ -48,6 +48,32 @@ const volatile bool reject_multicpu_pinning = false;
 const volatile bool enable_borrowing = false;
 const volatile bool use_lockless_peek = false;
 const volatile bool dynamic_affinity_cpu_selection = false;
+const volatile bool task_hint_map_enabled = false;
+
+/* Slice of the tasks of @cell_idx: its policy's, or slice_ns by default. */
+static inline u64 cell_slice_ns(u32 cell_idx)
+{
+	struct cell *cell = lookup_cell(cell_idx);
+	u64 cell_slice = cell ? READ_ONCE(cell->policy.slice_ns) : 0;
+
+	return cell_slice ?: slice_ns;
+}
+
+/*
+ * Whether @cell_idx uses per-LLC queues. Cells can opt out of LLC awareness,
+ * their subcells then use the FAKE_FLAT_SUBCELL_LLC queue.
+ */
+static inline bool cell_llc_aware(u32 cell_idx)
+{
+	struct cell *cell;
+
+	if (!enable_llc_awareness)
+		return false;
+	if (!(cell = lookup_cell(cell_idx)))
+		return false;
+
+	return !(READ_ONCE(cell->policy.flags) & CELL_POLICY_NO_LLC_AWARE);
+}
 
 /*
  * Global arrays for LLC topology, populated by userspace before load.
@@ -64,6 +90,10 @@ u32 applied_cpuset_seq;
 /* Configuration struct for apply_cell_config, populated by userspace */
 struct cell_config cell_config;
 
+/* Subcell rules of the applied configuration, copied from cell_config */
+struct subcell_rule subcell_rules[MAX_SUBCELL_RULES];
+u32 nr_subcell_rules;
+
 private(all_cpumask) struct bpf_cpumask __kptr *all_cpumask;
 private(root_cgrp) struct cgroup __kptr *root_cgrp;
 
@@ -153,6 +183,34 @@ struct {
 	__type(value, struct task_ctx);
 } task_ctxs SEC(".maps");
 
+/*
+ * Task hints written by userspace, pinned with --task-hint-map. The layout
+ * matches scx_layered's task hint map so the same writers can be used.
+ */
+struct task_hint {
+	u64 hint;
+	u64 __reserved[3];
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_TASK_STORAGE);
+	__uint(map_flags, BPF_F_NO_PREALLOC);
+	__type(key, int);
+	__type(value, struct task_hint);
+} mitosis_task_hint_map SEC(".maps");
+
+#define NO_TASK_HINT ((u64)-1)
+
+static inline u64 lookup_task_hint(struct task_struct *p)
+{
+	struct task_hint *hint;
+
+	if (!task_hint_map_enabled)
+		return NO_TASK_HINT;
+	hint = bpf_task_storage_get(&mitosis_task_hint_map, p, NULL, 0);
+	return hint ? hint->hint : NO_TASK_HINT;
+}
+
 static inline struct task_ctx *lookup_task_ctx(struct task_struct *p)
 {
 	struct task_ctx *tctx;
@@ -285,7 +343,7 @@ static inline int update_task_cpumask(struct task_struct *p, struct task_ctx *tc
 		return 0;
 	}
 
-	if (enable_llc_awareness) {
+	if (cell_llc_aware(tctx->cell)) {
 		ret = update_task_llc_assignment(p, tctx, scx_bpf_task_cpu(p));
 		if (ret)
 			return ret;
@@ -294,6 +352,9 @@ static inline int update_task_cpumask(struct task_struct *p, struct task_ctx *tc
 	}
 
 	/* Non-LLC aware version */
+	if (enable_llc_awareness)
+		tctx->llc = LLC_INVALID;
+
 	tctx->dsq = get_subcell_llc_dsq_id(tctx->cell, tctx->subcell, FAKE_FLAT_SUBCELL_LLC);
 	if (dsq_is_invalid(tctx->dsq))
 		return -EINVAL;
@@ -319,6 +380,49 @@ static __always_inline int set_vtime_charge_subcell(struct task_ctx *tctx)
 	return 0;
 }
 
+static __always_inline bool comm_has_prefix(struct task_struct *p, const char *prefix)
+{
+	int i;
+
+	bpf_for(i, 0, SUBCELL_COMM_LEN) {
+		if (!prefix[i])
+			return true;
+		if (p->comm[i] != prefix[i])
+			return false;
+	}
+
+	return true;
+}
+
+/*
+ * Pick the subcell of @p within @cell: the subcell of the first matching
+ * subcell rule of @cell, or subcell 0 if none matches.
+ */
+static u32 pick_task_subcell(struct task_struct *p, u32 cell, u64 hint)
+{
+	u32 i, nr_rules = READ_ONCE(nr_subcell_rules);
+
+	bpf_for(i, 0, MAX_SUBCELL_RULES) {
+		struct subcell_rule *rule;
+
+		if (i >= nr_rules)
+			break;
+		rule = MEMBER_VPTR(subcell_rules, [i]);
+		if (!rule)
+			break;
+		if (rule->cell != cell)
+			continue;
+		if ((rule->flags & SUBCELL_RULE_HINT) &&
+		    (hint == NO_TASK_HINT || hint != rule->hint))
+			continue;
+		if ((rule->flags & SUBCELL_RULE_COMM) && !comm_has_prefix(p, rule->comm))
+			continue;
+		return rule->subcell;
+	}
+
+	return 0;
+}
+
 /*
  * Figure out the task's cell, dsq and store the corresponding cpumask in the
  * task_ctx.
@@ -372,7 +476,8 @@ static inline int update_task_cell(struct task_struct *p, struct task_ctx *tctx,
 	tctx->configuration_seq = READ_ONCE(applied_configuration_seq);
 	barrier();
 	tctx->cell = cgc->cell;
-	tctx->subcell = 0;
+	tctx->hint = lookup_task_hint(p);
+	tctx->subcell = pick_task_subcell(p, tctx->cell, tctx->hint);
 	tctx->cgid = cg->kn->id;
 
 	/*
@@ -427,6 +532,11 @@ static __always_inline int maybe_refresh_cell(struct task_struct *p, struct task
 	if (tctx->configuration_seq != READ_ONCE(applied_configuration_seq))
 		return refresh_task_cell(p, tctx);
 
+	/* A changed task hint may move the task to another subcell */
+	if (task_hint_map_enabled && READ_ONCE(nr_subcell_rules) &&
+	    lookup_task_hint(p) != tctx->hint)
+		return refresh_task_cell(p, tctx);
+
 	/*
 	 * When not using CPU controller, check if task's cgroup changed.
 	 * The cgroup is already initialized by tp_cgroup_mkdir which
@@ -470,7 +580,7 @@ static __always_inline s32 pick_idle_cpu(struct task_struct *p, s32 prev_cpu, st
 		return pick_idle_cpu_from(p, p->cpus_ptr, prev_cpu, idle_smtmask);
 	}
 
-	if (enable_llc_awareness && tctx->all_cell_cpus_allowed) {
+	if (tctx->all_cell_cpus_allowed && cell_llc_aware(tctx->cell)) {
 		struct bpf_cpumask *llc_cpumask;
 		const struct cpumask *llc_mask;
 		s32 llc = choose_task_llc(tctx, prev_cpu);
@@ -518,7 +628,7 @@ static __always_inline s32 try_pick_idle_cpu(struct task_struct *p, s32 prev_cpu
 		 */
 		if (set_vtime_charge_subcell(tctx))
 			return -1;
-		scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice_ns, 0);
+		scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, cell_slice_ns(tctx->cell), 0);
 		if (kick)
 			scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
 		return cpu;
@@ -553,7 +663,7 @@ static __always_inline s32 try_pick_idle_cpu(struct task_struct *p, s32 prev_cpu
 				return -1;
 			tctx->borrowed = true;
 			cstat_inc(CSTAT_BORROWED, tctx->cell, cctx);
-			scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, slice_ns, 0);
+			scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL_ON | cpu, cell_slice_ns(tctx->cell), 0);
 			if (kick)
 				scx_bpf_kick_cpu(cpu, SCX_KICK_IDLE);
 			return cpu;
@@ -652,7 +762,7 @@ s32 BPF_STRUCT_OPS(mitosis_select_cpu, struct task_struct *p, s32 prev_cpu, u64
 		if (idle_cpu_cleared || scx_bpf_test_and_clear_cpu_idle(cpu)) {
 			if (set_vtime_charge_subcell(tctx))
 				return prev_cpu;
-			scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL, slice_ns, 0);
+			scx_bpf_dsq_insert(p, SCX_DSQ_LOCAL, cell_slice_ns(tctx->cell), 0);
 		}
 		return cpu;
 	}
@@ -713,6 +823,8 @@ void BPF_STRUCT_OPS(mitosis_enqueue, struct task_struct *p, u64 enq_flags)
 	u64 vtime;
 	s32 cpu = -1;
 	u64 basis_vtime;
+	u64 slice;
+	bool llc_aware;
 
 	if (!(tctx = lookup_task_ctx(p)) || !(cctx = lookup_cpu_ctx(-1)))
 		return;
@@ -720,6 +832,9 @@ void BPF_STRUCT_OPS(mitosis_enqueue, struct task_struct *p, u64 enq_flags)
 	if (maybe_refresh_cell(p, tctx) < 0)
 		return;
 
+	slice = cell_slice_ns(tctx->cell);
+	llc_aware = cell_llc_aware(tctx->cell);
+
 	/*
 	 * CPU -> subcell mappings can change between enqueue() and stopping().
 	 * If that happens, the task's dsq_vtime may no longer belong to the
@@ -777,7 +892,7 @@ void BPF_STRUCT_OPS(mitosis_enqueue, struct task_struct *p, u64 enq_flags)
 		if (!(subcell = lookup_subcell(tctx->cell, tctx->subcell)))
 			return;
 
-		if (enable_llc_awareness) {
+		if (llc_aware) {
 			struct subcell_llc *llc_state;
 			s32 llc;
 
@@ -817,7 +932,7 @@ void BPF_STRUCT_OPS(mitosis_enqueue, struct task_struct *p, u64 enq_flags)
 	vtime = p->scx.dsq_vtime;
 	tctx->basis_vtime = basis_vtime;
 
-	if (time_after(vtime, basis_vtime + 8192 * slice_ns)) {
+	if (time_after(vtime, basis_vtime + 8192 * slice)) {
 		scx_bpf_error(
 			"vtime too far ahead: pid=%d vtime=%llu basis=%llu diff=%llu cell=%u subcell=%u",
 			p->pid, p->scx.dsq_vtime, basis_vtime, p->scx.dsq_vtime - basis_vtime,
@@ -828,17 +943,17 @@ void BPF_STRUCT_OPS(mitosis_enqueue, struct task_struct *p, u64 enq_flags)
 	 * Limit the amount of budget that an idling task can accumulate
 	 * to one slice.
 	 */
-	if (time_before(vtime, basis_vtime - slice_ns))
-		vtime = basis_vtime - slice_ns;
+	if (time_before(vtime, basis_vtime - slice))
+		vtime = basis_vtime - slice;
 
-	scx_bpf_dsq_insert_vtime(p, tctx->dsq.raw, slice_ns, vtime, enq_flags);
+	scx_bpf_dsq_insert_vtime(p, tctx->dsq.raw, slice, vtime, enq_flags);
 
 	/*
 	 * Account after insertion: subcell reconfiguration can orphan the selected
 	 * LLC between LLC selection and enqueue, so this is where we interlock
 	 * with refresh_subcell_llc_draining() and enable draining if needed.
 	 */
-	if (enable_llc_awareness && tctx->all_cell_cpus_allowed) {
+	if (llc_aware && tctx->all_cell_cpus_allowed) {
 		if (account_subcell_llc_enqueue(tctx->cell, tctx->subcell, (u32)tctx->llc))
 			return;
 	}
@@ -874,15 +989,20 @@ void BPF_STRUCT_OPS(mitosis_dispatch, s32 cpu, struct task_struct *prev)
 	u64 min_vtime = 0;
 
 	struct task_struct *p;
+	bool llc_aware = cell_llc_aware(cell);
 
 	/* Check the CPU's subcell-LLC DSQ. */
-	u32 llc = enable_llc_awareness ? cctx->llc : FAKE_FLAT_SUBCELL_LLC;
+	u32 llc = llc_aware ? cctx->llc : FAKE_FLAT_SUBCELL_LLC;
 	dsq_id_t subcell_dsq = get_subcell_llc_dsq_id(cell, subcell_id, llc);
 	dsq_id_t cpu_dsq = get_cpu_dsq_id(cpu);
 
 	if (dsq_is_invalid(subcell_dsq) || dsq_is_invalid(cpu_dsq))
 		return;
 
+	/*
+	 * Cells that opted out of LLC awareness still drain and steal, which
+	 * picks up tasks queued per-LLC before their policy was applied.
+	 */
 	if (enable_llc_awareness) {
 		struct subcell *subcell = lookup_subcell(cell, subcell_id);
 
@@ -933,7 +1053,7 @@ void BPF_STRUCT_OPS(mitosis_dispatch, s32 cpu, struct task_struct *prev)
 
 	/* Try the winner first */
 	if (scx_bpf_dsq_move_to_local(min_vtime_dsq.raw, 0)) {
-		if (enable_llc_awareness && min_vtime_dsq.raw == subcell_dsq.raw) {
+		if (llc_aware && min_vtime_dsq.raw == subcell_dsq.raw) {
 			struct subcell *subcell = lookup_subcell(cell, subcell_id);
 
 			if (subcell)
@@ -969,7 +1089,7 @@ void BPF_STRUCT_OPS(mitosis_running, struct task_struct *p)
 	if (!(cctx = lookup_cpu_ctx(-1)) || !(tctx = lookup_task_ctx(p)))
 		return;
 
-	if (enable_llc_awareness && tctx->all_cell_cpus_allowed) {
+	if (tctx->all_cell_cpus_allowed && cell_llc_aware(tctx->cell)) {
 		/*
 		 * The actual running CPU is known once the task starts running
 		 * after dispatch or sibling LLC stealing. Refresh the task's LLC
@@ -1062,7 +1182,7 @@ void BPF_STRUCT_OPS(mitosis_stopping, struct task_struct *p, bool runnable)
 	if (!tctx->borrowed && tctx->vtime_charge_subcell == packed_subcell) {
 		u32 llc_idx = FAKE_FLAT_SUBCELL_LLC;
 
-		if (enable_llc_awareness) {
+		if (cell_llc_aware(cidx)) {
 			if (!llc_is_valid(cctx->llc) || cctx->llc >= nr_llc) {
 				scx_bpf_error("invalid CPU LLC in stopping: %u", cctx->llc);
 				return;
@@ -1240,6 +1360,26 @@ int BPF_PROG(tp_cgroup_rmdir, struct cgroup *cgrp, const char *cgrp_path)
 	return 0;
 }
 
+/*
+ * Subcell rules match on comm, reclassify renamed tasks. The comm isn't updated
+ * yet when the tracepoint fires, so only mark the task stale and let
+ * maybe_refresh_cell() pick its subcell on the next wakeup.
+ */
+SEC("tp_btf/task_rename")
+int BPF_PROG(tp_task_rename, struct task_struct *p, const char *comm)
+{
+	struct task_ctx *tctx;
+
+	if (!READ_ONCE(nr_subcell_rules))
+		return 0;
+
+	tctx = bpf_task_storage_get(&task_ctxs, p, 0, 0);
+	if (tctx)
+		WRITE_ONCE(tctx->configuration_seq, READ_ONCE(applied_configuration_seq) - 1);
+
+	return 0;
+}
+
 void BPF_STRUCT_OPS(mitosis_set_cpumask, struct task_struct *p, const struct cpumask *cpumask)
 {
 	struct task_ctx *tctx;
@@ -1770,6 +1910,30 @@ int reset_configured_cells(void)
 	return 0;
 }
 
+int apply_configured_cell_policy(u32 cell_id, struct cell_config *config)
+{
+	struct cell_policy *policy;
+	struct cell *cell;
+
+	if (!config || cell_id >= MAX_CELLS)
+		return -EINVAL;
+
+	cell = lookup_cell(cell_id);
+	if (!cell)
+		return -EINVAL;
+
+	policy = MEMBER_VPTR(config->policies, [cell_id]);
+	if (!policy) {
+		scx_bpf_error("cell_id %d out of bounds for policies", cell_id);
+		return -EINVAL;
+	}
+
+	WRITE_ONCE(cell->policy.slice_ns, policy->slice_ns);
+	WRITE_ONCE(cell->policy.flags, policy->flags);
+
+	return 0;
+}
+
 int apply_configured_cell_cpumask(u32 cell_id, struct cell_config *config)
 {
 	struct cell_cpumask_data *cpumask_data;
@@ -1831,7 +1995,7 @@ int apply_configured_cell_cpumask(u32 cell_id, struct cell_config *config)
 			subcell = lookup_subcell(cell_id, 0);
 			if (!subcell)
 				return -ENOENT;
-			llc_idx = enable_llc_awareness && llc_is_valid(cctx->llc) ?
+			llc_idx = cell_llc_aware(cell_id) && llc_is_valid(cctx->llc) ?
 					  cctx->llc :
 					  FAKE_FLAT_SUBCELL_LLC;
 			llc_state = lookup_subcell_llc(subcell, llc_idx);
@@ -1945,6 +2109,44 @@ int apply_configured_cell_subcells(u32 cell_id, struct cell_config *config)
 	return 0;
 }
 
+int apply_configured_subcell_rules(struct cell_config *config)
+{
+	u32 i, nr_rules = config->num_subcell_rules;
+
+	if (nr_rules > MAX_SUBCELL_RULES)
+		return -EINVAL;
+
+	bpf_for(i, 0, MAX_SUBCELL_RULES)
+	{
+		struct subcell_rule *src, *dst;
+		struct subcell *subcell;
+
+		if (i >= nr_rules)
+			break;
+
+		src = MEMBER_VPTR(config->subcell_rules, [i]);
+		dst = MEMBER_VPTR(subcell_rules, [i]);
+		if (!src || !dst)
+			return -EINVAL;
+
+		if (src->cell >= config->num_cells || src->subcell >= MAX_SUBCELLS_PER_CELL)
+			return -EINVAL;
+
+		subcell = lookup_subcell(src->cell, src->subcell);
+		if (!subcell || !subcell->in_use) {
+			scx_bpf_error("subcell rule %u targets unused subcell %u of cell %u", i,
+				      src->subcell, src->cell);
+			return -EINVAL;
+		}
+
+		*dst = *src;
+	}
+
+	WRITE_ONCE(nr_subcell_rules, nr_rules);
+
+	return 0;
+}
+
 /*
  * Apply a complete cell configuration.
  *
@@ -1953,7 +2155,7 @@ int apply_configured_cell_subcells(u32 cell_id, struct cell_config *config)
  *
  * The function operates in five phases:
  * 1. Mark all cells (except cell 0) as not in use
- * 2. Apply cell cpumasks, CPU mappings, and subcell state
+ * 2. Apply cell cpumasks, CPU mappings, subcell state and subcell rules
  * 3. Apply cell assignments for owner cgroups
  * 4. Walk cgroup hierarchy to propagate cells to children
  * 5. Bump applied_configuration_seq to signal completion
@@ -1970,11 +2172,14 @@ int apply_cell_config(void *ctx)
 	struct cell *cell;
 	struct cgroup_subsys_state *root_css, *pos;
 	struct cgroup *cur_cgrp;
-	u32 i, cell_id;
+	u32 i, cell_id, seq;
 
 	/* Read configuration from global struct (populated by userspace) */
 	struct cell_config *config = &cell_config;
 
+	/* Sequence number this configuration is published with in phase 5 */
+	seq = READ_ONCE(applied_configuration_seq) + 1;
+
 	/*
 	 * Phase 1: Mark all cells (except cell 0) as not in use.
 	 * This handles cell destruction - cells not in the new config
@@ -1984,8 +2189,8 @@ int apply_cell_config(void *ctx)
 		return -EINVAL;
 
 	/*
-	 * Phase 2: Apply cell cpumasks, derive CPU-to-cell mappings, and store
-	 * subcell cpumasks for each cell.
+	 * Phase 2: Apply cell policies and cpumasks, derive CPU-to-cell
+	 * mappings, and store subcell cpumasks for each cell.
 	 * For each cell, we update the cell's cpumask and set each CPU's
 	 * cell assignment based on which cell's cpumask contains it.
 	 *
@@ -2003,6 +2208,11 @@ int apply_cell_config(void *ctx)
 		if (cell_id >= config->num_cells)
 			break;
 
+		/* Before the cpumask, which seeds vtimes in the cell's LLC queues. */
+		ret = apply_configured_cell_policy(cell_id, config);
+		if (ret)
+			return ret;
+
 		ret = apply_configured_cell_cpumask(cell_id, config);
 		if (ret)
 			return ret;
@@ -2012,6 +2222,9 @@ int apply_cell_config(void *ctx)
 			return ret;
 	}
 
+	if (apply_configured_subcell_rules(config))
+		return -EINVAL;
+
 	/* Phase 3: Apply cell-to-cgroup assignments for owner cgroups */
 	if (config->num_cell_assignments > MAX_CELLS)
 		return -EINVAL;
@@ -2054,6 +2267,7 @@ int apply_cell_config(void *ctx)
 
 		cgc->cell = cell_id;
 		cgc->cell_owner = true;
+		cgc->owner_seq = seq;
 	}
 
 	/*
@@ -2103,17 +2317,17 @@ int apply_cell_config(void *ctx)
 
 			if (cgrp_ctx->cell_owner) {
 				/*
-				 * Check if this cell is still in use and owned
-				 * by this cgroup. If not, this cgroup was a
-				 * former owner but is no longer in the new
-				 * config (or the cell ID was reused for a
-				 * different cgroup). Clear cell_owner and
-				 * inherit from parent.
+				 * Check if this cell is still in use and this
+				 * cgroup was assigned to it by this config.
+				 * If not, this cgroup was a former owner but
+				 * is no longer in the new config. Clear
+				 * cell_owner and inherit from parent. A cell
+				 * may have several owner cgroups.
 				 */
 				cell = lookup_cell(cgrp_ctx->cell);
 				if (!cell)
 					return -EINVAL;
-				if (cell->in_use && cell->owner_cgid == cur_cgrp->kn->id) {
+				if (cell->in_use && cgrp_ctx->owner_seq == seq) {
 					/* Cell owner with active cell - record in level_cells */
 					level_cells[level] = cgrp_ctx->cell;
 					continue;
@@ -112,8 +112,10 @@ struct task_ctx {
 	u64 basis_vtime;
 	/* For the sake of monitoring, each task is owned by a cell */
 	u32 cell;
-	/* Subcell within the task's cell. Defaults to subcell 0 for now. */
+	/* Subcell within the task's cell, picked by the subcell rules. */
 	u32 subcell;
+	/* Task hint the subcell was picked with, NO_TASK_HINT if none. */
+	u64 hint;
 	/* For the sake of scheduling, a task is exclusively owned by either a
 	 * subcell or a cpu.
 	 */
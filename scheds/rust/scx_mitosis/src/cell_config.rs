@@ -0,0 +1,1157 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Cells loaded from a configuration file (`--cell-config`).
+//!
+//! The file uses the `CellConfig` JSON format emitted by
+//! `scx_characterize extract mem`: a list of cells, each with a name, match
+//! clauses and optional subcells.
+//!
+//! - Top-level cells match cgroups by their path relative to the cgroup root,
+//!   with `CgroupRegex` and/or `CgroupContains`. A cgroup belongs to the first
+//!   cell it matches, otherwise to its parent's cell. The cell matching
+//!   everything (`{}`) is cell 0.
+//! - Subcells match tasks of their cell with clauses of `CommPrefix` and
+//!   `Hint` (read from the task hint map, see `--task-hint-map`). The subcell
+//!   matching everything (`[[]]`) is subcell 0, tasks matching no other
+//!   subcell land there.
+//! - Cells and subcells may reserve CPUs with `cpus` (a cpulist) and/or
+//!   `llcs` (a list of LLC ids).
+//! - Cells may set a scheduling `policy`, see [`CellPolicy`].
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "workload",
+//!     "matches": { "CgroupContains": "workload.slice" },
+//!     "llcs": [1],
+//!     "policy": { "slice_us": 5000, "borrowing": "lend_only" },
+//!     "subcells": [
+//!       { "name": "web", "matches": [[{ "CommPrefix": "web" }, { "Hint": 7 }]] },
+//!       { "name": "rest", "matches": [[]] }
+//!     ]
+//!   },
+//!   { "name": "rest", "matches": {} }
+//! ]
+//! ```
+//!
+//! Cells are static: they exist, and get CPUs, whether or not any cgroup
+//! currently matches them. Every cgroup directory is watched with inotify so
+//! that new cgroups are assigned as they are created.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{anyhow, bail, Context, Result};
+use inotify::{Inotify, WatchMask};
+use regex::Regex;
+use scx_utils::Cpumask;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::bpf_intf;
+use crate::cell_manager::{
+    holdout_displaced_claims, CellManager, CellSource, CpuAssignment, CpuRecipient,
+};
+
+const MAX_SUBCELLS_PER_CELL: usize = bpf_intf::consts_MAX_SUBCELLS_PER_CELL as usize;
+const MAX_SUBCELL_RULES: usize = bpf_intf::consts_MAX_SUBCELL_RULES as usize;
+const SUBCELL_COMM_LEN: usize = bpf_intf::consts_SUBCELL_COMM_LEN as usize;
+
+/// A single task match condition of a subcell clause.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum CellMatch {
+    CommPrefix(String),
+    Hint(u64),
+}
+
+/// Match clauses of a cell: cgroup matches for top-level cells, or a
+/// disjunction of conjunctive task clauses for subcells.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CellMatches {
+    Complex(Vec<Vec<CellMatch>>),
+    Simple(SimpleCellMatches),
+}
+
+/// Cgroup matches of a top-level cell. Both must match if both are set, an
+/// empty match matches every cgroup.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimpleCellMatches {
+    #[serde(rename = "CgroupRegex", default)]
+    pub cgroup_regex: Option<String>,
+    #[serde(rename = "CgroupContains", default)]
+    pub cgroup_contains: Option<String>,
+}
+
+impl SimpleCellMatches {
+    fn is_empty(&self) -> bool {
+        self.cgroup_regex.is_none() && self.cgroup_contains.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CellSpec {
+    pub name: String,
+    pub matches: CellMatches,
+    #[serde(default)]
+    pub subcells: Vec<CellSpec>,
+    /// CPUs reserved for the cell, as a cpulist (e.g. "0-7,16").
+    #[serde(default)]
+    pub cpus: Option<String>,
+    /// LLCs whose CPUs are reserved for the cell.
+    #[serde(default)]
+    pub llcs: Vec<usize>,
+    /// Scheduling policy of the cell. Not supported on subcells.
+    #[serde(default)]
+    pub policy: Option<CellPolicy>,
+}
+
+/// CPU borrowing between a cell and the other cells, with `--enable-borrowing`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Borrowing {
+    #[default]
+    Both,
+    /// Idle CPUs of the cell may be used by other cells, but the cell never
+    /// runs on theirs.
+    LendOnly,
+    /// The cell may run on idle CPUs of other cells, but keeps its own.
+    BorrowOnly,
+    None,
+}
+
+impl Borrowing {
+    pub fn may_borrow(self) -> bool {
+        matches!(self, Self::Both | Self::BorrowOnly)
+    }
+
+    pub fn may_lend(self) -> bool {
+        matches!(self, Self::Both | Self::LendOnly)
+    }
+}
+
+impl std::fmt::Display for Borrowing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Both => "both",
+            Self::LendOnly => "lend_only",
+            Self::BorrowOnly => "borrow_only",
+            Self::None => "none",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Scheduling policy of a cell. Unset fields keep the global behavior.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CellPolicy {
+    /// Slice of the cell's tasks, in microseconds.
+    pub slice_us: Option<u64>,
+    pub borrowing: Borrowing,
+    /// Set to false to use one queue per subcell instead of one per LLC with
+    /// `--enable-llc-awareness`.
+    pub llc_aware: bool,
+}
+
+impl Default for CellPolicy {
+    fn default() -> Self {
+        Self {
+            slice_us: None,
+            borrowing: Borrowing::default(),
+            llc_aware: true,
+        }
+    }
+}
+
+impl CellPolicy {
+    fn validate(&self) -> Result<()> {
+        if self.slice_us == Some(0) {
+            bail!("slice_us must be positive");
+        }
+        Ok(())
+    }
+
+    pub fn to_bpf(&self) -> bpf_intf::cell_policy {
+        // SAFETY: cell_policy is plain old data and all zeroes is a valid value.
+        let mut out: bpf_intf::cell_policy = unsafe { std::mem::zeroed() };
+        out.slice_ns = self.slice_us.unwrap_or(0) * 1000;
+        if !self.llc_aware {
+            out.flags |= bpf_intf::cell_policy_flags_CELL_POLICY_NO_LLC_AWARE;
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct CellConfig {
+    pub specs: Vec<CellSpec>,
+}
+
+impl CellConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let buf = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cell config {}", path.display()))?;
+        serde_json::from_str(&buf)
+            .with_context(|| format!("Failed to parse cell config {}", path.display()))
+    }
+
+    /// True if any subcell matches on task hints.
+    pub fn uses_hints(&self) -> bool {
+        self.specs
+            .iter()
+            .flat_map(|spec| &spec.subcells)
+            .any(|sub| {
+                matches!(&sub.matches, CellMatches::Complex(clauses)
+                if clauses.iter().flatten().any(|m| matches!(m, CellMatch::Hint(_))))
+            })
+    }
+}
+
+/// Rule placing tasks of `cell` whose comm starts with `comm` and whose task
+/// hint is `hint` into `subcell`. See `struct subcell_rule`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubcellRule {
+    pub cell: u32,
+    pub subcell: u32,
+    pub comm: Option<String>,
+    pub hint: Option<u64>,
+}
+
+impl SubcellRule {
+    fn from_clause(cell: u32, subcell: u32, clause: &[CellMatch]) -> Result<Self> {
+        let mut rule = Self {
+            cell,
+            subcell,
+            comm: None,
+            hint: None,
+        };
+
+        for m in clause {
+            match m {
+                CellMatch::CommPrefix(prefix) => {
+                    if rule.comm.is_some() {
+                        bail!("more than one CommPrefix in a clause");
+                    }
+                    if prefix.len() >= SUBCELL_COMM_LEN {
+                        bail!(
+                            "CommPrefix {:?} longer than {} bytes",
+                            prefix,
+                            SUBCELL_COMM_LEN - 1
+                        );
+                    }
+                    rule.comm = Some(prefix.clone());
+                }
+                CellMatch::Hint(hint) => {
+                    if rule.hint.is_some() {
+                        bail!("more than one Hint in a clause");
+                    }
+                    if *hint == u64::MAX {
+                        bail!("Hint {} is reserved", hint);
+                    }
+                    rule.hint = Some(*hint);
+                }
+            }
+        }
+
+        Ok(rule)
+    }
+
+    pub fn to_bpf(&self) -> bpf_intf::subcell_rule {
+        // SAFETY: subcell_rule is plain old data and all zeroes is a valid value.
+        let mut out: bpf_intf::subcell_rule = unsafe { std::mem::zeroed() };
+        out.cell = self.cell;
+        out.subcell = self.subcell;
+        if let Some(comm) = &self.comm {
+            out.flags |= bpf_intf::subcell_rule_flags_SUBCELL_RULE_COMM;
+            for (dst, src) in out.comm.iter_mut().zip(comm.bytes()) {
+                *dst = src as _;
+            }
+        }
+        if let Some(hint) = self.hint {
+            out.flags |= bpf_intf::subcell_rule_flags_SUBCELL_RULE_HINT;
+            out.hint = hint;
+        }
+        out
+    }
+}
+
+/// Cgroup matcher of a top-level cell.
+#[derive(Debug, Default)]
+struct CgroupMatch {
+    regex: Option<Regex>,
+    contains: Option<String>,
+}
+
+impl CgroupMatch {
+    fn new(matches: &SimpleCellMatches) -> Result<Self> {
+        Ok(Self {
+            regex: matches
+                .cgroup_regex
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .context("invalid CgroupRegex")?,
+            contains: matches.cgroup_contains.clone(),
+        })
+    }
+
+    fn is_catch_all(&self) -> bool {
+        self.regex.is_none() && self.contains.is_none()
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        self.regex.as_ref().is_none_or(|re| re.is_match(path))
+            && self
+                .contains
+                .as_deref()
+                .is_none_or(|needle| path.contains(needle))
+    }
+}
+
+#[derive(Debug)]
+struct ConfiguredSubcell {
+    id: u32,
+    name: String,
+    reservation: Option<Cpumask>,
+}
+
+#[derive(Debug)]
+struct ConfiguredCell {
+    id: u32,
+    name: String,
+    cgroups: CgroupMatch,
+    reservation: Option<Cpumask>,
+    policy: CellPolicy,
+    subcells: Vec<ConfiguredSubcell>,
+    /// Cgroups assigned to this cell whose parent is in another cell, by path.
+    owners: BTreeMap<PathBuf, u64>,
+}
+
+/// Resolve the `cpus` and `llcs` reservation of @spec.
+fn resolve_reservation(
+    spec: &CellSpec,
+    all_cpus: &Cpumask,
+    cpu_to_llc: &HashMap<usize, usize>,
+) -> Result<Option<Cpumask>> {
+    if spec.cpus.is_none() && spec.llcs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut cpus = match &spec.cpus {
+        Some(cpulist) => {
+            Cpumask::from_cpulist(cpulist).with_context(|| format!("invalid cpus {:?}", cpulist))?
+        }
+        None => Cpumask::new(),
+    };
+    for &llc in &spec.llcs {
+        let llc_cpus: Vec<usize> = cpu_to_llc
+            .iter()
+            .filter(|(cpu, cpu_llc)| **cpu_llc == llc && all_cpus.test_cpu(**cpu))
+            .map(|(cpu, _)| *cpu)
+            .collect();
+        if llc_cpus.is_empty() {
+            bail!("LLC {} has no CPUs", llc);
+        }
+        for cpu in llc_cpus {
+            cpus.set_cpu(cpu)?;
+        }
+    }
+
+    let unavailable = cpus.and(&all_cpus.not());
+    if unavailable.weight() > 0 {
+        bail!(
+            "reserved CPUs {} are not available",
+            unavailable.to_cpulist()
+        );
+    }
+    if cpus.weight() == 0 {
+        bail!("reservation is empty");
+    }
+
+    Ok(Some(cpus))
+}
+
+/// Build the subcells of cell @cell_id from @spec, appending their rules to
+/// @rules.
+fn build_subcells(
+    spec: &CellSpec,
+    cell_id: u32,
+    all_cpus: &Cpumask,
+    cpu_to_llc: &HashMap<usize, usize>,
+    rules: &mut Vec<SubcellRule>,
+) -> Result<Vec<ConfiguredSubcell>> {
+    let mut subcells = vec![ConfiguredSubcell {
+        id: 0,
+        name: "rest".to_string(),
+        reservation: None,
+    }];
+    let mut catch_all_seen = false;
+
+    for sub in &spec.subcells {
+        let what = || format!("subcell {}/{}", spec.name, sub.name);
+
+        if !sub.subcells.is_empty() {
+            bail!("{}: nested subcells are not supported", what());
+        }
+        if sub.policy.is_some() {
+            bail!("{}: policies are set on cells, not subcells", what());
+        }
+        let clauses: &[Vec<CellMatch>] = match &sub.matches {
+            CellMatches::Complex(clauses) if clauses.is_empty() => {
+                bail!("{}: no match clauses", what())
+            }
+            CellMatches::Complex(clauses) => clauses,
+            CellMatches::Simple(simple) if simple.is_empty() => &[],
+            CellMatches::Simple(_) => {
+                bail!(
+                    "{}: subcells match on CommPrefix and Hint, not cgroups",
+                    what()
+                )
+            }
+        };
+        let reservation = resolve_reservation(sub, all_cpus, cpu_to_llc).with_context(what)?;
+
+        // A subcell with an empty clause matches every task and becomes
+        // subcell 0.
+        if clauses.is_empty() || clauses.iter().any(|clause| clause.is_empty()) {
+            if catch_all_seen {
+                bail!("{}: only one subcell may match every task", what());
+            }
+            catch_all_seen = true;
+            subcells[0] = ConfiguredSubcell {
+                id: 0,
+                name: sub.name.clone(),
+                reservation,
+            };
+            continue;
+        }
+
+        let id = subcells.len() as u32;
+        if subcells.len() >= MAX_SUBCELLS_PER_CELL {
+            bail!(
+                "cell {}: more than {} subcells",
+                spec.name,
+                MAX_SUBCELLS_PER_CELL
+            );
+        }
+        for clause in clauses {
+            rules.push(SubcellRule::from_clause(cell_id, id, clause).with_context(what)?);
+        }
+        subcells.push(ConfiguredSubcell {
+            id,
+            name: sub.name.clone(),
+            reservation,
+        });
+    }
+
+    Ok(subcells)
+}
+
+/// Build the cells described by @config. Cell 0 comes first.
+fn build_cells(
+    config: &CellConfig,
+    max_cells: u32,
+    all_cpus: &Cpumask,
+    cpu_to_llc: &HashMap<usize, usize>,
+) -> Result<(Vec<ConfiguredCell>, Vec<SubcellRule>)> {
+    let mut cells = vec![ConfiguredCell {
+        id: 0,
+        name: "root".to_string(),
+        cgroups: CgroupMatch::default(),
+        reservation: None,
+        policy: CellPolicy::default(),
+        subcells: vec![ConfiguredSubcell {
+            id: 0,
+            name: "rest".to_string(),
+            reservation: None,
+        }],
+        owners: BTreeMap::new(),
+    }];
+    let mut rules = Vec::new();
+    let mut catch_all_seen = false;
+
+    for spec in &config.specs {
+        let what = || format!("cell {}", spec.name);
+
+        let cgroups = match &spec.matches {
+            CellMatches::Simple(simple) => CgroupMatch::new(simple).with_context(what)?,
+            CellMatches::Complex(_) => bail!(
+                "{}: top-level cells match on CgroupRegex and CgroupContains",
+                what()
+            ),
+        };
+        let reservation = resolve_reservation(spec, all_cpus, cpu_to_llc).with_context(what)?;
+        let policy = spec.policy.clone().unwrap_or_default();
+        policy.validate().with_context(what)?;
+
+        let id = if cgroups.is_catch_all() {
+            if catch_all_seen {
+                bail!("{}: only one cell may match every cgroup", what());
+            }
+            catch_all_seen = true;
+            0
+        } else {
+            cells.len() as u32
+        };
+        if id >= max_cells {
+            bail!("{}: more than {} cells", what(), max_cells);
+        }
+
+        let cell = ConfiguredCell {
+            id,
+            name: spec.name.clone(),
+            cgroups,
+            reservation,
+            policy,
+            subcells: build_subcells(spec, id, all_cpus, cpu_to_llc, &mut rules)?,
+            owners: BTreeMap::new(),
+        };
+        if id == 0 {
+            cells[0] = cell;
+        } else {
+            cells.push(cell);
+        }
+    }
+
+    if rules.len() > MAX_SUBCELL_RULES {
+        bail!(
+            "{} subcell match clauses, more than {}",
+            rules.len(),
+            MAX_SUBCELL_RULES
+        );
+    }
+
+    Ok((cells, rules))
+}
+
+/// Child cgroups of @dir as (path, cgid), tolerating concurrent removal.
+fn child_cgroups(dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+    };
+
+    let mut children = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("reading inode of {}", entry.path().display()))
+            }
+        };
+        if metadata.is_dir() {
+            children.push((entry.path(), metadata.ino()));
+        }
+    }
+    children.sort();
+
+    Ok(children)
+}
+
+/// Manages the static cells of a [`CellConfig`].
+pub struct ConfigCellManager {
+    cgroup_root: PathBuf,
+    inotify: Inotify,
+    cells: Vec<ConfiguredCell>,
+    rules: Vec<SubcellRule>,
+    max_assignments: usize,
+    all_cpus: Cpumask,
+    cell0_min_cpus: usize,
+    cpu_to_llc: HashMap<usize, usize>,
+    enforced_holdout: AtomicBool,
+}
+
+impl ConfigCellManager {
+    pub fn new(
+        config: &CellConfig,
+        max_cells: u32,
+        all_cpus: Cpumask,
+        cell0_min_cpus: usize,
+        cpu_to_llc: HashMap<usize, usize>,
+    ) -> Result<Self> {
+        Self::new_with_root(
+            PathBuf::from("/sys/fs/cgroup"),
+            config,
+            max_cells,
+            all_cpus,
+            cell0_min_cpus,
+            cpu_to_llc,
+        )
+    }
+
+    fn new_with_root(
+        cgroup_root: PathBuf,
+        config: &CellConfig,
+        max_cells: u32,
+        all_cpus: Cpumask,
+        cell0_min_cpus: usize,
+        cpu_to_llc: HashMap<usize, usize>,
+    ) -> Result<Self> {
+        let (cells, rules) = build_cells(config, max_cells, &all_cpus, &cpu_to_llc)?;
+        let nr_subcells: usize = cells.iter().map(|cell| cell.subcells.len()).sum();
+        if nr_subcells > all_cpus.weight() {
+            bail!(
+                "{} subcells but only {} CPUs, every subcell needs a CPU",
+                nr_subcells,
+                all_cpus.weight()
+            );
+        }
+
+        let mut mgr = Self {
+            cgroup_root,
+            inotify: Inotify::init().context("Failed to initialize inotify")?,
+            cells,
+            rules,
+            max_assignments: max_cells as usize,
+            all_cpus,
+            cell0_min_cpus,
+            cpu_to_llc,
+            enforced_holdout: AtomicBool::new(false),
+        };
+        mgr.rescan()
+            .context("Failed to scan cgroups for the cell config")?;
+
+        for cell in &mgr.cells {
+            info!(
+                "Cell {} ({}): {} owner cgroups, subcells [{}]{}{}",
+                cell.id,
+                cell.name,
+                cell.owners.len(),
+                cell.subcells
+                    .iter()
+                    .map(|subcell| format!("{}:{}", subcell.id, subcell.name))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                cell.reservation
+                    .as_ref()
+                    .map(|cpus| format!(", reserved CPUs {}", cpus.to_cpulist()))
+                    .unwrap_or_default(),
+                if cell.policy != CellPolicy::default() {
+                    format!(", policy {:?}", cell.policy)
+                } else {
+                    String::new()
+                }
+            );
+        }
+
+        Ok(mgr)
+    }
+
+    /// Path of @path relative to the cgroup root, e.g. `/workload.slice/foo`.
+    fn root_relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.cgroup_root)
+            .map(|rel| format!("/{}", rel.to_string_lossy()))
+            .unwrap_or_else(|_| path.to_string_lossy().into_owned())
+    }
+
+    fn watch(&self, dir: &Path) -> Result<()> {
+        match self.inotify.watches().add(
+            dir,
+            WatchMask::CREATE | WatchMask::DELETE | WatchMask::MOVE | WatchMask::ONLYDIR,
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to watch {}", dir.display())),
+        }
+    }
+
+    fn walk(
+        &self,
+        dir: &Path,
+        parent_cell: u32,
+        owners: &mut [BTreeMap<PathBuf, u64>],
+    ) -> Result<()> {
+        for (path, cgid) in child_cgroups(dir)? {
+            let rel = self.root_relative(&path);
+            let cell = self.cells[1..]
+                .iter()
+                .find(|cell| cell.cgroups.matches(&rel))
+                .map_or(parent_cell, |cell| cell.id);
+            if cell != parent_cell {
+                owners[cell as usize].insert(path.clone(), cgid);
+            }
+
+            self.watch(&path)?;
+            self.walk(&path, cell, owners)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walk the cgroup hierarchy and recompute the owner cgroups of each
+    /// cell. Returns true if they changed.
+    fn rescan(&mut self) -> Result<bool> {
+        let mut owners = vec![BTreeMap::new(); self.cells.len()];
+        self.watch(&self.cgroup_root)?;
+        self.walk(&self.cgroup_root, 0, &mut owners)?;
+
+        let nr_owners: usize = owners.iter().map(|owners| owners.len()).sum();
+        if nr_owners > self.max_assignments {
+            bail!(
+                "{} cgroups own cells, more than {}",
+                nr_owners,
+                self.max_assignments
+            );
+        }
+
+        let mut changed = false;
+        for (cell, owners) in self.cells.iter_mut().zip(owners) {
+            if cell.owners != owners {
+                cell.owners = owners;
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn compute_cpu_assignments_inner(
+        &self,
+        cell_demands: Option<&HashMap<u32, f64>>,
+        compute_borrowable: bool,
+    ) -> Result<Vec<CpuAssignment>> {
+        let recipients: Vec<CpuRecipient> = self
+            .cells
+            .iter()
+            .map(|cell| {
+                let weight = match cell_demands {
+                    Some(demands) => *demands
+                        .get(&cell.id)
+                        .ok_or_else(|| anyhow!("Cell {} is missing from demands map", cell.id))?,
+                    None => 1.0,
+                };
+                let mut recipient = CpuRecipient::unpinned(cell.id, weight, &self.all_cpus);
+                if let Some(cpus) = &cell.reservation {
+                    recipient = recipient.reserved(cpus);
+                }
+                // Every subcell needs a CPU of its own.
+                let nr_subcells = cell.subcells.len();
+                Ok(if cell.id == 0 {
+                    recipient.with_minimum(0, self.cell0_min_cpus.max(nr_subcells))
+                } else {
+                    recipient.with_minimum(nr_subcells, 0)
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let assignments = CellManager::compute_partitioned_cpu_assignments(
+            &self.all_cpus,
+            &self.cpu_to_llc,
+            &recipients,
+            compute_borrowable,
+        )?;
+
+        if self.cell0_min_cpus > 0 && holdout_displaced_claims(&recipients, &assignments) {
+            self.enforced_holdout.store(true, Ordering::Relaxed);
+        }
+
+        Ok(assignments)
+    }
+}
+
+impl AsFd for ConfigCellManager {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inotify.as_fd()
+    }
+}
+
+impl CellSource for ConfigCellManager {
+    fn process_events(&mut self) -> Result<Option<(Vec<(u64, u32)>, Vec<u32>)>> {
+        let mut buffer = [0; 1024];
+        let mut has_events = false;
+
+        loop {
+            match self.inotify.read_events(&mut buffer) {
+                Ok(events) => {
+                    if events.into_iter().next().is_some() {
+                        has_events = true;
+                    } else {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e).context("Failed to read inotify events"),
+            }
+        }
+
+        if !has_events || !self.rescan()? {
+            return Ok(None);
+        }
+
+        // Cells are static, only their owner cgroups changed.
+        Ok(Some((Vec::new(), Vec::new())))
+    }
+
+    fn cell_ids(&self) -> Vec<u32> {
+        self.cells.iter().map(|cell| cell.id).collect()
+    }
+
+    fn get_cell_assignments(&self) -> Vec<(u64, u32)> {
+        self.cells
+            .iter()
+            .skip(1)
+            .flat_map(|cell| cell.owners.values().map(|cgid| (*cgid, cell.id)))
+            .collect()
+    }
+
+    fn compute_cpu_assignments(&self, compute_borrowable: bool) -> Result<Vec<CpuAssignment>> {
+        self.compute_cpu_assignments_inner(None, compute_borrowable)
+    }
+
+    fn compute_demand_cpu_assignments(
+        &self,
+        cell_demands: &HashMap<u32, f64>,
+        compute_borrowable: bool,
+    ) -> Result<Vec<CpuAssignment>> {
+        self.compute_cpu_assignments_inner(Some(cell_demands), compute_borrowable)
+    }
+
+    fn subcell_recipients(&self, cell_id: u32, domain: &Cpumask) -> Option<Vec<CpuRecipient>> {
+        let cell = self.cells.get(cell_id as usize)?;
+        Some(
+            cell.subcells
+                .iter()
+                .map(|subcell| {
+                    let recipient = CpuRecipient::unpinned(subcell.id, 1.0, domain);
+                    // Reserved CPUs the cell didn't get are ignored.
+                    match subcell.reservation.as_ref().map(|cpus| cpus.and(domain)) {
+                        Some(cpus) if cpus.weight() > 0 => recipient.reserved(&cpus),
+                        _ => recipient,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn subcell_rules(&self) -> Vec<SubcellRule> {
+        self.rules.clone()
+    }
+
+    fn cell_policy(&self, cell_id: u32) -> CellPolicy {
+        self.cells
+            .get(cell_id as usize)
+            .map(|cell| cell.policy.clone())
+            .unwrap_or_default()
+    }
+
+    fn format_cell_config(&self, cpu_assignments: &[CpuAssignment]) -> String {
+        let mut sorted: Vec<_> = cpu_assignments.iter().collect();
+        sorted.sort_by_key(|a| a.id);
+
+        sorted
+            .iter()
+            .map(|assignment| {
+                let name = self
+                    .cells
+                    .get(assignment.id as usize)
+                    .map_or("?", |cell| cell.name.as_str());
+                format!(
+                    "[{}({}): {}]",
+                    assignment.id,
+                    name,
+                    assignment.primary.to_cpulist()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Owner cgroups of the cell relative to the cgroup root, comma separated.
+    /// Cell 0 is `/`.
+    fn cgroup_path_for_cell(&self, cell_id: u32) -> String {
+        if cell_id == 0 {
+            return "/".to_string();
+        }
+        self.cells
+            .get(cell_id as usize)
+            .map(|cell| {
+                cell.owners
+                    .keys()
+                    .map(|path| self.root_relative(path))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_default()
+    }
+
+    /// Configured cells reserve CPUs explicitly and ignore cgroup cpusets.
+    fn refresh_cpusets(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn enforced_holdout(&self) -> bool {
+        self.enforced_holdout.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cpumask_for_range(nr_cpus: usize) -> Cpumask {
+        scx_utils::set_cpumask_test_width(nr_cpus);
+        let mut mask = Cpumask::new();
+        for cpu in 0..nr_cpus {
+            mask.set_cpu(cpu).unwrap();
+        }
+        mask
+    }
+
+    /// Two LLCs of 8 CPUs each.
+    fn cpu_to_llc() -> HashMap<usize, usize> {
+        (0..16).map(|cpu| (cpu, cpu / 8)).collect()
+    }
+
+    fn parse(json: &str) -> CellConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn manager(root: &Path, json: &str) -> Result<ConfigCellManager> {
+        ConfigCellManager::new_with_root(
+            root.to_path_buf(),
+            &parse(json),
+            256,
+            cpumask_for_range(16),
+            0,
+            cpu_to_llc(),
+        )
+    }
+
+    // Shape of `scx_characterize extract mem` output.
+    const CHARACTERIZE_CONFIG: &str = r#"[
+        {
+            "name": "allotment",
+            "matches": { "CgroupRegex": "allot-[0-9]+" },
+            "subcells": [
+                { "name": "web", "matches": [[{ "CommPrefix": "web" }, { "Hint": 7 }]] },
+                { "name": "db", "matches": [[{ "CommPrefix": "mysqld" }], [{ "CommPrefix": "pg" }]] },
+                { "name": "rest", "matches": [[]] }
+            ]
+        },
+        { "name": "workload.slice", "matches": { "CgroupContains": "workload.slice" } },
+        { "name": "rest", "matches": {} }
+    ]"#;
+
+    #[test]
+    fn test_parse_characterize_config() {
+        let config = parse(CHARACTERIZE_CONFIG);
+        assert!(config.uses_hints());
+
+        let (cells, rules) =
+            build_cells(&config, 256, &cpumask_for_range(16), &cpu_to_llc()).unwrap();
+
+        let names: Vec<_> = cells.iter().map(|c| (c.id, c.name.as_str())).collect();
+        assert_eq!(
+            names,
+            vec![(0, "rest"), (1, "allotment"), (2, "workload.slice")]
+        );
+
+        // The catch-all subcell is subcell 0, the others follow in order.
+        let subcells: Vec<_> = cells[1]
+            .subcells
+            .iter()
+            .map(|s| (s.id, s.name.as_str()))
+            .collect();
+        assert_eq!(subcells, vec![(0, "rest"), (1, "web"), (2, "db")]);
+
+        assert_eq!(
+            rules,
+            vec![
+                SubcellRule {
+                    cell: 1,
+                    subcell: 1,
+                    comm: Some("web".to_string()),
+                    hint: Some(7),
+                },
+                SubcellRule {
+                    cell: 1,
+                    subcell: 2,
+                    comm: Some("mysqld".to_string()),
+                    hint: None,
+                },
+                SubcellRule {
+                    cell: 1,
+                    subcell: 2,
+                    comm: Some("pg".to_string()),
+                    hint: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_configs() {
+        let all_cpus = cpumask_for_range(16);
+        let invalid = [
+            // Top-level cells match on cgroups.
+            r#"[{ "name": "a", "matches": [[{ "CommPrefix": "x" }]] }]"#,
+            // Subcells match on tasks.
+            r#"[{ "name": "a", "matches": {}, "subcells": [
+                { "name": "b", "matches": { "CgroupContains": "x" } }] }]"#,
+            // No nested subcells.
+            r#"[{ "name": "a", "matches": {}, "subcells": [
+                { "name": "b", "matches": [[{ "Hint": 1 }]], "subcells": [
+                    { "name": "c", "matches": [[]] }] }] }]"#,
+            // Comm longer than the kernel's.
+            r#"[{ "name": "a", "matches": {}, "subcells": [
+                { "name": "b", "matches": [[{ "CommPrefix": "0123456789abcdef" }]] }] }]"#,
+            // Two catch-all cells.
+            r#"[{ "name": "a", "matches": {} }, { "name": "b", "matches": {} }]"#,
+            // Unknown cgroup match.
+            r#"[{ "name": "a", "matches": { "CgroupGlob": "x" } }]"#,
+            // LLC without CPUs.
+            r#"[{ "name": "a", "matches": { "CgroupContains": "x" }, "llcs": [5] }]"#,
+            // Zero slice.
+            r#"[{ "name": "a", "matches": {}, "policy": { "slice_us": 0 } }]"#,
+            // Unknown policy field and borrowing mode.
+            r#"[{ "name": "a", "matches": {}, "policy": { "weight": 2 } }]"#,
+            r#"[{ "name": "a", "matches": {}, "policy": { "borrowing": "lend" } }]"#,
+            // Policy on a subcell.
+            r#"[{ "name": "a", "matches": {}, "subcells": [
+                { "name": "b", "matches": [[]], "policy": { "slice_us": 100 } }] }]"#,
+        ];
+
+        for json in invalid {
+            let result = serde_json::from_str::<CellConfig>(json)
+                .map_err(anyhow::Error::from)
+                .and_then(|config| build_cells(&config, 256, &all_cpus, &cpu_to_llc()));
+            assert!(result.is_err(), "accepted {}", json);
+        }
+
+        let too_many_subcells: Vec<String> = (0..MAX_SUBCELLS_PER_CELL)
+            .map(|i| format!(r#"{{ "name": "s{i}", "matches": [[{{ "Hint": {i} }}]] }}"#))
+            .collect();
+        let json = format!(
+            r#"[{{ "name": "a", "matches": {{}}, "subcells": [{}] }}]"#,
+            too_many_subcells.join(",")
+        );
+        assert!(build_cells(&parse(&json), 256, &all_cpus, &cpu_to_llc()).is_err());
+    }
+
+    #[test]
+    fn test_cell_policies() {
+        let all_cpus = cpumask_for_range(16);
+        let config = parse(
+            r#"[
+              { "name": "latency", "matches": { "CgroupContains": "web" },
+                "policy": { "slice_us": 2000, "borrowing": "borrow_only", "llc_aware": false } },
+              { "name": "batch", "matches": { "CgroupContains": "batch" },
+                "policy": { "borrowing": "lend_only" } },
+              { "name": "rest", "matches": {} }
+            ]"#,
+        );
+        let (cells, _) = build_cells(&config, 256, &all_cpus, &cpu_to_llc()).unwrap();
+
+        assert_eq!(cells[0].policy, CellPolicy::default());
+
+        let latency = &cells[1].policy;
+        assert!(latency.borrowing.may_borrow() && !latency.borrowing.may_lend());
+        let bpf = latency.to_bpf();
+        assert_eq!(bpf.slice_ns, 2_000_000);
+        assert_eq!(
+            bpf.flags,
+            bpf_intf::cell_policy_flags_CELL_POLICY_NO_LLC_AWARE
+        );
+
+        let batch = &cells[2].policy;
+        assert!(!batch.borrowing.may_borrow() && batch.borrowing.may_lend());
+        assert_eq!(batch.slice_us, None);
+        assert_eq!(batch.to_bpf().flags, 0);
+    }
+
+    #[test]
+    fn test_cgroup_owners() {
+        let tmp = TempDir::new().unwrap();
+        for dir in [
+            "system.slice/sshd.service",
+            "workload.slice/allot-1/inner",
+            "workload.slice/job/allot-2",
+            "workload.slice/job/worker",
+        ] {
+            std::fs::create_dir_all(tmp.path().join(dir)).unwrap();
+        }
+
+        let mut mgr = manager(tmp.path(), CHARACTERIZE_CONFIG).unwrap();
+
+        // Owners are the topmost matching cgroups, descendants inherit.
+        let owners = |mgr: &ConfigCellManager, cell: usize| -> Vec<String> {
+            mgr.cells[cell]
+                .owners
+                .keys()
+                .map(|path| mgr.root_relative(path))
+                .collect()
+        };
+        assert!(owners(&mgr, 0).is_empty());
+        assert_eq!(
+            owners(&mgr, 1),
+            vec!["/workload.slice/allot-1", "/workload.slice/job/allot-2"]
+        );
+        assert_eq!(owners(&mgr, 2), vec!["/workload.slice"]);
+        assert_eq!(mgr.get_cell_assignments().len(), 3);
+        assert_eq!(mgr.cell_ids(), vec![0, 1, 2]);
+
+        // New matching cgroups become owners, unrelated ones don't.
+        std::fs::create_dir(tmp.path().join("workload.slice/allot-3")).unwrap();
+        std::fs::create_dir(tmp.path().join("system.slice/cron.service")).unwrap();
+        assert!(mgr.process_events().unwrap().is_some());
+        assert_eq!(owners(&mgr, 1).len(), 3);
+
+        std::fs::create_dir(tmp.path().join("system.slice/other.service")).unwrap();
+        assert!(mgr.process_events().unwrap().is_none());
+
+        std::fs::remove_dir(tmp.path().join("workload.slice/allot-3")).unwrap();
+        assert!(mgr.process_events().unwrap().is_some());
+        assert_eq!(owners(&mgr, 1).len(), 2);
+    }
+
+    #[test]
+    fn test_reserved_cpus() {
+        let tmp = TempDir::new().unwrap();
+        let mgr = manager(
+            tmp.path(),
+            r#"[
+                { "name": "a", "matches": { "CgroupContains": "a" }, "llcs": [1] },
+                { "name": "b", "matches": { "CgroupContains": "b" }, "cpus": "0-1" },
+                { "name": "c", "matches": { "CgroupContains": "c" } }
+            ]"#,
+        )
+        .unwrap();
+
+        let assignments = mgr.compute_cpu_assignments(false).unwrap();
+        let primary = |id: u32| {
+            assignments
+                .iter()
+                .find(|a| a.id == id)
+                .unwrap()
+                .primary
+                .to_cpulist()
+        };
+        assert_eq!(primary(1), "8-15");
+        assert_eq!(primary(2), "0-1");
+
+        // Cell 0 and the unreserved cell share the rest.
+        assert_eq!(
+            assignments
+                .iter()
+                .filter(|a| a.id == 0 || a.id == 3)
+                .map(|a| a.primary.weight())
+                .sum::<usize>(),
+            6
+        );
+    }
+}
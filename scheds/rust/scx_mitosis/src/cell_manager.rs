@@ -8,6 +8,9 @@
 //! This module implements the `--cell-parent-cgroup` mode where cells are created
 //! for direct child cgroups of a specified parent. Uses inotify to watch for
 //! cgroup creation/destruction and manages cell ID allocation.
+//!
+//! [`CellSource`] abstracts over it and the `--cell-config` mode implemented in
+//! `cell_config.rs`.
 
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::DirEntry;
@@ -21,6 +24,8 @@ use inotify::{Inotify, WatchMask};
 use scx_utils::Cpumask;
 use tracing::{debug, info};
 
+use crate::cell_config::{CellPolicy, SubcellRule};
+
 /// Strip the cgroup mount prefix from a stored cell path, yielding the
 /// root-relative cgroup path (e.g. `/sys/fs/cgroup/a/b` -> `/a/b`).
 fn cgroup_root_relative(path: &Path) -> String {
@@ -83,6 +88,95 @@ impl CpuRecipient {
             },
         }
     }
+
+    /// Restrict the recipient to `cpus` and claim them preferentially.
+    pub(crate) fn reserved(mut self, cpus: &Cpumask) -> Self {
+        self.allowed = cpus.clone();
+        self.claimed = Some(cpus.clone());
+        self
+    }
+
+    /// Override the protected and requested primary-CPU minimums.
+    pub(crate) fn with_minimum(mut self, protected: usize, requested: usize) -> Self {
+        self.minimum = CpuMinimum {
+            protected,
+            requested,
+        };
+        self
+    }
+}
+
+/// True if cell 0 received a CPU preferentially claimed by a workload cell,
+/// i.e. satisfying cell 0's requested minimum displaced a claim.
+pub(crate) fn holdout_displaced_claims(
+    recipients: &[CpuRecipient],
+    assignments: &[CpuAssignment],
+) -> bool {
+    let mut workload_claims = Cpumask::new();
+    for recipient in recipients.iter().filter(|recipient| recipient.id != 0) {
+        if let Some(claimed) = &recipient.claimed {
+            workload_claims = workload_claims.or(claimed);
+        }
+    }
+    assignments
+        .iter()
+        .find(|assignment| assignment.id == 0)
+        .is_some_and(|assignment| assignment.primary.and(&workload_claims).weight() > 0)
+}
+
+/// Source of cells and of the cgroups assigned to them.
+///
+/// Implemented by [`CellManager`] for `--cell-parent-cgroup` and by
+/// `ConfigCellManager` for `--cell-config`. The fd becomes readable when the
+/// cgroup hierarchy changes and [`CellSource::process_events`] should be
+/// called.
+pub trait CellSource: AsFd {
+    /// Process pending cgroup events. Returns None if the cgroup to cell
+    /// assignments didn't change, otherwise the new (cgid, cell_id) cells and
+    /// the destroyed cell ids.
+    fn process_events(&mut self) -> Result<Option<(Vec<(u64, u32)>, Vec<u32>)>>;
+
+    /// All active cell ids, including cell 0.
+    fn cell_ids(&self) -> Vec<u32>;
+
+    /// Owner cgroups of every cell but cell 0, as (cgid, cell_id) pairs.
+    fn get_cell_assignments(&self) -> Vec<(u64, u32)>;
+
+    fn compute_cpu_assignments(&self, compute_borrowable: bool) -> Result<Vec<CpuAssignment>>;
+
+    fn compute_demand_cpu_assignments(
+        &self,
+        cell_demands: &HashMap<u32, f64>,
+        compute_borrowable: bool,
+    ) -> Result<Vec<CpuAssignment>>;
+
+    /// Subcell recipients of `cell_id` over its primary CPUs `domain`, or
+    /// None to keep the subcells currently known to BPF.
+    fn subcell_recipients(&self, _cell_id: u32, _domain: &Cpumask) -> Option<Vec<CpuRecipient>> {
+        None
+    }
+
+    /// Rules placing tasks into subcells, loaded into BPF with the cell
+    /// configuration.
+    fn subcell_rules(&self) -> Vec<SubcellRule> {
+        Vec::new()
+    }
+
+    /// Scheduling policy of `cell_id`, loaded into BPF with the cell
+    /// configuration.
+    fn cell_policy(&self, _cell_id: u32) -> CellPolicy {
+        CellPolicy::default()
+    }
+
+    fn format_cell_config(&self, cpu_assignments: &[CpuAssignment]) -> String;
+
+    fn cgroup_path_for_cell(&self, cell_id: u32) -> String;
+
+    /// Re-read cgroup cpusets. Returns true if the CPU assignments need to be
+    /// recomputed.
+    fn refresh_cpusets(&mut self) -> Result<bool>;
+
+    fn enforced_holdout(&self) -> bool;
 }
 
 /// Result of CPU assignment computation, containing both primary and optional borrowable masks.
@@ -1157,6 +1251,18 @@ impl CellManager {
         CpuManager::new(domain).compute_assignments(subcells, compute_borrowable)
     }
 
+    /// Compute CPU assignments for cell recipients over `domain`, using
+    /// `cpu_to_llc` to rank CPUs when requested minimums displace claims.
+    pub(crate) fn compute_partitioned_cpu_assignments(
+        domain: &Cpumask,
+        cpu_to_llc: &HashMap<usize, usize>,
+        recipients: &[CpuRecipient],
+        compute_borrowable: bool,
+    ) -> Result<Vec<CpuAssignment>> {
+        CpuManager::with_partitions(domain, cpu_to_llc)
+            .compute_assignments(recipients, compute_borrowable)
+    }
+
     /// Internal implementation shared by equal-weight and demand-weighted assignment.
     fn compute_cpu_assignments_inner(
         &self,
@@ -1211,20 +1317,8 @@ impl CellManager {
 
         // The sticky statistic records when satisfying cell 0's requested
         // minimum displaced any CPU preferentially claimed by a workload cell.
-        if self.cell0_min_cpus > 0 {
-            let mut workload_claims = Cpumask::new();
-            for recipient in recipients.iter().filter(|recipient| recipient.id != 0) {
-                if let Some(claimed) = &recipient.claimed {
-                    workload_claims = workload_claims.or(claimed);
-                }
-            }
-            if assignments
-                .iter()
-                .find(|assignment| assignment.id == 0)
-                .is_some_and(|assignment| assignment.primary.and(&workload_claims).weight() > 0)
-            {
-                self.enforced_holdout.store(true, Ordering::Relaxed);
-            }
+        if self.cell0_min_cpus > 0 && holdout_displaced_claims(&recipients, &assignments) {
+            self.enforced_holdout.store(true, Ordering::Relaxed);
         }
 
         Ok(assignments)
@@ -1321,6 +1415,58 @@ impl AsFd for CellManager {
     }
 }
 
+impl CellSource for CellManager {
+    fn process_events(&mut self) -> Result<Option<(Vec<(u64, u32)>, Vec<u32>)>> {
+        let (new_cells, destroyed_cells) = CellManager::process_events(self)?;
+        if new_cells.is_empty() && destroyed_cells.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((new_cells, destroyed_cells)))
+    }
+
+    fn cell_ids(&self) -> Vec<u32> {
+        std::iter::once(0)
+            .chain(
+                self.get_cell_assignments()
+                    .iter()
+                    .map(|(_, cell_id)| *cell_id),
+            )
+            .collect()
+    }
+
+    fn get_cell_assignments(&self) -> Vec<(u64, u32)> {
+        CellManager::get_cell_assignments(self)
+    }
+
+    fn compute_cpu_assignments(&self, compute_borrowable: bool) -> Result<Vec<CpuAssignment>> {
+        CellManager::compute_cpu_assignments(self, compute_borrowable)
+    }
+
+    fn compute_demand_cpu_assignments(
+        &self,
+        cell_demands: &HashMap<u32, f64>,
+        compute_borrowable: bool,
+    ) -> Result<Vec<CpuAssignment>> {
+        CellManager::compute_demand_cpu_assignments(self, cell_demands, compute_borrowable)
+    }
+
+    fn format_cell_config(&self, cpu_assignments: &[CpuAssignment]) -> String {
+        CellManager::format_cell_config(self, cpu_assignments)
+    }
+
+    fn cgroup_path_for_cell(&self, cell_id: u32) -> String {
+        CellManager::cgroup_path_for_cell(self, cell_id)
+    }
+
+    fn refresh_cpusets(&mut self) -> Result<bool> {
+        CellManager::refresh_cpusets(self)
+    }
+
+    fn enforced_holdout(&self) -> bool {
+        CellManager::enforced_holdout(self)
+    }
+}
+
 #[cfg(test)]
 impl CellManager {
     /// Returns the number of cells created for cgroups.
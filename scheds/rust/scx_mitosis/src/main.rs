@@ -7,25 +7,31 @@
 mod bpf_skel;
 pub use bpf_skel::*;
 pub mod bpf_intf;
+mod cell_config;
 mod cell_manager;
 mod stats;
 mod topology;
 mod undefok_flags;
 
-use cell_manager::{CellManager, CpuAssignment, CpuRecipient};
+use cell_config::{CellConfig, CellPolicy, ConfigCellManager};
+use cell_manager::{CellManager, CellSource, CpuAssignment, CpuRecipient};
 
 use std::cmp::max;
 use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
 use std::fmt;
 use std::fmt::Display;
 use std::mem::MaybeUninit;
 use std::os::fd::AsFd;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU32;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use anyhow::anyhow;
 use anyhow::bail;
@@ -58,11 +64,14 @@ use tracing_subscriber::filter::EnvFilter;
 
 use stats::CellMetrics;
 use stats::Metrics;
+use stats::RebalanceCellDecision;
+use stats::RebalanceDecision;
 use topology::MitosisTopology;
 
 const SCHEDULER_NAME: &str = "scx_mitosis";
 const MAX_CELLS: usize = bpf_intf::consts_MAX_CELLS as usize;
 const MAX_SUBCELLS_PER_CELL: usize = bpf_intf::consts_MAX_SUBCELLS_PER_CELL as usize;
+const MAX_SUBCELL_RULES: usize = bpf_intf::consts_MAX_SUBCELL_RULES as usize;
 const NR_CSTATS: usize = bpf_intf::cell_stat_idx_NR_CSTATS as usize;
 /// Epoll token for inotify events (cgroup creation/destruction)
 const INOTIFY_TOKEN: u64 = 1;
@@ -157,16 +166,32 @@ struct Opts {
     enable_work_stealing: bool,
 
     /// Parent cgroup path whose direct children become cells.
-    /// Scheduler startup requires this unless running in --monitor or --version mode.
+    /// Scheduler startup requires this or --cell-config unless running in
+    /// --monitor or --version mode.
     /// Example: --cell-parent-cgroup /workloads
-    #[clap(long, required_unless_present_any = ["monitor", "version"])]
+    #[clap(long, required_unless_present_any = ["monitor", "version", "cell_config"])]
     cell_parent_cgroup: Option<String>,
 
+    /// Load cells from a CellConfig JSON file, as emitted by
+    /// `scx_characterize extract mem`. Cells match cgroups by path regex or
+    /// substring, subcells match tasks by comm prefix and task hint, and both
+    /// may reserve CPUs (`cpus`) or LLCs (`llcs`). Cells are static and the
+    /// whole cgroup hierarchy is watched for matching cgroups.
+    #[clap(long, conflicts_with_all = ["cell_parent_cgroup", "cell_exclude"])]
+    cell_config: Option<PathBuf>,
+
+    /// Pin the task hint map at this path. Hints written there by userspace
+    /// are matched by the `Hint` clauses of --cell-config subcells. The map
+    /// has the same layout as scx_layered's --task-hint-map and is only
+    /// accessible by root.
+    #[clap(long)]
+    task_hint_map: Option<String>,
+
     /// Exact directory name of a direct child cgroup to exclude from cell creation
     /// (excluded cgroups remain in cell 0). Matched against the directory basename,
     /// not the full path. Can be specified multiple times. Requires --cell-parent-cgroup.
     /// Example: --cell-exclude systemd-workaround.service
-    #[clap(long)]
+    #[clap(long, requires = "cell_parent_cgroup")]
     cell_exclude: Vec<String>,
 
     /// Reserve up to this many CPUs for cell 0 (the root/catch-all cell)
@@ -203,6 +228,11 @@ struct Opts {
     #[clap(long, default_value = "0.3", value_parser = parse_ewma_factor)]
     demand_smoothing: f64,
 
+    /// Compute and report rebalancing decisions without applying them. The
+    /// decisions are logged and exposed through the stats server.
+    #[clap(long, action = clap::ArgAction::SetTrue, requires = "enable_rebalancing")]
+    rebalance_dry_run: bool,
+
     /// Dynamically reassign multi-CPU affinitized tasks on each wake: prefer an
     /// idle CPU within the mask, fall back to random. Redistribute at enqueue if
     /// the target CPU already has queued work.
@@ -292,10 +322,14 @@ struct Scheduler<'a> {
     last_configuration_seq: Option<u32>,
     /// Last observed cpuset_seq for cpuset change detection
     last_cpuset_seq: u32,
-    /// Cell manager for the cgroup passed via --cell-parent-cgroup.
-    cell_manager: CellManager,
+    /// Cell source for --cell-parent-cgroup or --cell-config.
+    cell_manager: Box<dyn CellSource>,
     /// Whether CPU borrowing is enabled
     enable_borrowing: bool,
+    /// Whether LLC awareness is enabled
+    enable_llc_awareness: bool,
+    /// Default slice, used by cells whose policy doesn't set one
+    slice_ns: u64,
     /// Whether demand-based rebalancing is enabled
     enable_rebalancing: bool,
     /// Utilization spread threshold for triggering rebalancing
@@ -310,6 +344,10 @@ struct Scheduler<'a> {
     last_rebalance: Instant,
     /// Number of rebalancing events
     rebalance_count: u64,
+    /// Only compute and report rebalancing decisions
+    rebalance_dry_run: bool,
+    /// Number of rebalancing decisions, applied or not
+    rebalance_decision_seq: u64,
     /// Epoll instance for waiting on multiple fds (inotify, stats wakeup)
     epoll: Epoll,
     /// EventFd to wake up main loop when stats are requested
@@ -364,9 +402,9 @@ impl Display for DistributionStats {
 
 impl<'a> Scheduler<'a> {
     fn managed_cell_parent<'b>(opts: &'b Opts) -> Result<&'b str> {
-        opts.cell_parent_cgroup
-            .as_deref()
-            .ok_or_else(|| anyhow!("--cell-parent-cgroup is required to run the scheduler"))
+        opts.cell_parent_cgroup.as_deref().ok_or_else(|| {
+            anyhow!("--cell-parent-cgroup or --cell-config is required to run the scheduler")
+        })
     }
 
     fn validate_args(_opts: &Opts) -> Result<()> {
@@ -435,6 +473,16 @@ impl<'a> Scheduler<'a> {
         rodata.enable_borrowing = opts.enable_borrowing;
         rodata.use_lockless_peek = opts.use_lockless_peek;
 
+        // libbpf creates and pins the map, or reuses the one already pinned,
+        // so hints survive scheduler restarts.
+        if let Some(path) = &opts.task_hint_map {
+            skel.maps
+                .mitosis_task_hint_map
+                .set_pin_path(path)
+                .with_context(|| format!("setting task hint map pin path {}", path))?;
+            rodata.task_hint_map_enabled = true;
+        }
+
         match *compat::SCX_OPS_ALLOW_QUEUED_WAKEUP {
             0 => info!("Kernel does not support queued wakeup optimization."),
             v => skel.struct_ops.mitosis_mut().flags |= v,
@@ -447,21 +495,60 @@ impl<'a> Scheduler<'a> {
 
         let skel = scx_ops_load!(skel, mitosis, uei).context("loading BPF skeleton")?;
 
+        // Hints steer tasks between cells, keep the map writable by root only, including a
+        // reused pin that was made accessible to others.
+        if let Some(path) = &opts.task_hint_map {
+            let cpath = CString::new(path.as_bytes()).context("task hint map path")?;
+            if unsafe { libc::chmod(cpath.as_ptr(), 0o600) } != 0 {
+                warn!(
+                    "chmod 600 of task hint map {} failed: {}",
+                    path,
+                    std::io::Error::last_os_error()
+                );
+            }
+        }
+
         let stats_server = StatsServer::new(stats::server_data())
             .launch()
             .context("launching stats server")?;
 
-        let parent_cgroup = Self::managed_cell_parent(opts)?;
-        let exclude: HashSet<String> = opts.cell_exclude.iter().cloned().collect();
-        let cell_manager = CellManager::new(
-            parent_cgroup,
-            MAX_CELLS as u32,
-            topology.span.clone(),
-            exclude,
-            opts.cell0_min_cpus,
-            mitosis_topology.cpu_to_llc.into_iter().collect(),
-        )
-        .with_context(|| format!("initializing cell manager for cgroup {}", parent_cgroup))?;
+        let cell_manager: Box<dyn CellSource> = match &opts.cell_config {
+            Some(path) => {
+                let config = CellConfig::load(path)?;
+                if config.uses_hints() && opts.task_hint_map.is_none() {
+                    warn!("--cell-config matches on task hints, which never match without --task-hint-map");
+                }
+                Box::new(
+                    ConfigCellManager::new(
+                        &config,
+                        MAX_CELLS as u32,
+                        topology.span.clone(),
+                        opts.cell0_min_cpus,
+                        mitosis_topology.cpu_to_llc.into_iter().collect(),
+                    )
+                    .with_context(|| {
+                        format!("initializing cells from config {}", path.display())
+                    })?,
+                )
+            }
+            None => {
+                let parent_cgroup = Self::managed_cell_parent(opts)?;
+                let exclude: HashSet<String> = opts.cell_exclude.iter().cloned().collect();
+                Box::new(
+                    CellManager::new(
+                        parent_cgroup,
+                        MAX_CELLS as u32,
+                        topology.span.clone(),
+                        exclude,
+                        opts.cell0_min_cpus,
+                        mitosis_topology.cpu_to_llc.into_iter().collect(),
+                    )
+                    .with_context(|| {
+                        format!("initializing cell manager for cgroup {}", parent_cgroup)
+                    })?,
+                )
+            }
+        };
 
         // Create epoll instance for event-driven main loop
         let epoll = Epoll::new(EpollCreateFlags::empty()).context("creating epoll instance")?;
@@ -483,7 +570,7 @@ impl<'a> Scheduler<'a> {
 
         epoll
             .add(
-                &cell_manager,
+                cell_manager.as_fd(),
                 EpollEvent::new(EpollFlags::EPOLLIN, INOTIFY_TOKEN),
             )
             .context("registering cell manager inotify with epoll")?;
@@ -502,6 +589,8 @@ impl<'a> Scheduler<'a> {
             last_cpuset_seq: 0,
             cell_manager,
             enable_borrowing: opts.enable_borrowing,
+            enable_llc_awareness: opts.enable_llc_awareness,
+            slice_ns: scx_enums.SCX_SLICE_DFL,
             enable_rebalancing: opts.enable_rebalancing,
             rebalance_threshold: opts.rebalance_threshold,
             rebalance_cooldown: Duration::from_secs(opts.rebalance_cooldown_s),
@@ -509,6 +598,8 @@ impl<'a> Scheduler<'a> {
             smoothed_util: [0.0; MAX_CELLS],
             last_rebalance: Instant::now(),
             rebalance_count: 0,
+            rebalance_dry_run: opts.rebalance_dry_run,
+            rebalance_decision_seq: 0,
             epoll,
             stats_waker,
         })
@@ -615,14 +706,13 @@ impl<'a> Scheduler<'a> {
     /// Process cell manager events (new/destroyed cgroups)
     fn process_cell_events(&mut self) -> Result<()> {
         let (num_new, num_destroyed, new_cell_ids, destroyed_cell_ids) = {
-            let (new_cells, destroyed_cells) = self
+            let Some((new_cells, destroyed_cells)) = self
                 .cell_manager
                 .process_events()
-                .context("processing inotify events")?;
-
-            if new_cells.is_empty() && destroyed_cells.is_empty() {
+                .context("processing inotify events")?
+            else {
                 return Ok(());
-            }
+            };
 
             let new_ids: Vec<u32> = new_cells.iter().map(|(_, cell_id)| *cell_id).collect();
             (
@@ -666,16 +756,8 @@ impl<'a> Scheduler<'a> {
         new_cell_ids: &[u32],
     ) -> Result<Vec<CpuAssignment>> {
         let (cell_assignments, cpu_assignments, subcell_assignments) = {
-            let active_cell_ids: Vec<u32> = self
-                .cell_manager
-                .get_cell_assignments()
-                .iter()
-                .map(|(_, cell_id)| *cell_id)
-                .collect();
             // Cell 0 is always active
-            let all_cell_ids: Vec<u32> = std::iter::once(0)
-                .chain(active_cell_ids.iter().copied())
-                .collect();
+            let all_cell_ids: Vec<u32> = self.cell_manager.cell_ids();
 
             let cpu_assignments = if self.enable_rebalancing {
                 // Check if any existing (non-new) cell has utilization data
@@ -738,7 +820,9 @@ impl<'a> Scheduler<'a> {
         Ok(cpu_assignments)
     }
 
-    /// Check if rebalancing should be triggered and apply demand-weighted CPU assignments.
+    /// Check if rebalancing should be triggered and apply demand-weighted CPU
+    /// assignments. Every decision is recorded in the metrics; in dry-run mode
+    /// it is only recorded.
     fn maybe_rebalance(&mut self) -> Result<()> {
         // Check cooldown
         if self.last_rebalance.elapsed() < self.rebalance_cooldown {
@@ -753,13 +837,17 @@ impl<'a> Scheduler<'a> {
 
         let mut min_util = f64::MAX;
         let mut max_util = f64::MIN;
+        let mut min_cell = 0;
+        let mut max_cell = 0;
         for &cell_id in &active_cells {
             let util = self.smoothed_util[cell_id as usize];
             if util < min_util {
                 min_util = util;
+                min_cell = cell_id;
             }
             if util > max_util {
                 max_util = util;
+                max_cell = cell_id;
             }
         }
 
@@ -803,19 +891,67 @@ impl<'a> Scheduler<'a> {
             )
         };
 
+        self.rebalance_decision_seq += 1;
+        let decision = RebalanceDecision {
+            seq: self.rebalance_decision_seq,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            dry_run: self.rebalance_dry_run as u64,
+            spread_pct: spread,
+            threshold_pct: self.rebalance_threshold,
+            min_cell,
+            max_cell,
+            cells: cpu_assignments
+                .iter()
+                .map(|a| {
+                    let before = self
+                        .cells
+                        .get(&a.id)
+                        .map_or_else(Cpumask::new, |cell| cell.cpus.clone());
+                    let demand = cell_demands.get(&a.id).copied().unwrap_or(0.0);
+                    (
+                        a.id,
+                        RebalanceCellDecision::new(demand, &before, &a.primary),
+                    )
+                })
+                .collect(),
+        };
+        self.last_rebalance = Instant::now();
+
+        if self.rebalance_dry_run {
+            self.metrics.rebalance_dry_run_count += 1;
+            info!(
+                "Rebalance decision {} (dry run): {}",
+                decision.seq,
+                decision.summary()
+            );
+            info!(
+                "Rebalance dry run, not applying: {}",
+                self.cell_manager.format_cell_config(&cpu_assignments)
+            );
+            self.metrics.record_rebalance(decision);
+            return Ok(());
+        }
+
         self.apply_cell_config(&cell_assignments, &cpu_assignments, &subcell_assignments)
             .context("applying rebalanced cell configuration to BPF")?;
 
-        self.last_rebalance = Instant::now();
         self.rebalance_count += 1;
         self.metrics.rebalance_count = self.rebalance_count;
 
+        info!(
+            "Rebalance decision {}: {}",
+            decision.seq,
+            decision.summary()
+        );
         info!(
             "Rebalanced CPUs (spread={:.1}%, count={}): {}",
             spread,
             self.rebalance_count,
             self.cell_manager.format_cell_config(&cpu_assignments)
         );
+        self.metrics.record_rebalance(decision);
 
         Ok(())
     }
@@ -827,20 +963,28 @@ impl<'a> Scheduler<'a> {
         cell_cpu_assignments
             .iter()
             .map(|cell_assignment| {
-                let recipients: Vec<CpuRecipient> = self
-                    .cells
-                    .get(&cell_assignment.id)
-                    .map(|cell| {
-                        cell.subcells
-                            .iter()
-                            .map(|subcell| {
-                                CpuRecipient::unpinned(subcell.id, 1.0, &cell_assignment.primary)
-                            })
-                            .collect()
-                    })
-                    .unwrap_or_else(|| {
-                        vec![CpuRecipient::unpinned(0, 1.0, &cell_assignment.primary)]
-                    });
+                let configured = self
+                    .cell_manager
+                    .subcell_recipients(cell_assignment.id, &cell_assignment.primary);
+                let recipients: Vec<CpuRecipient> = configured.unwrap_or_else(|| {
+                    self.cells
+                        .get(&cell_assignment.id)
+                        .map(|cell| {
+                            cell.subcells
+                                .iter()
+                                .map(|subcell| {
+                                    CpuRecipient::unpinned(
+                                        subcell.id,
+                                        1.0,
+                                        &cell_assignment.primary,
+                                    )
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_else(|| {
+                            vec![CpuRecipient::unpinned(0, 1.0, &cell_assignment.primary)]
+                        })
+                });
 
                 CellManager::compute_subcell_cpu_assignments(
                     &cell_assignment.primary,
@@ -946,9 +1090,19 @@ impl<'a> Scheduler<'a> {
             config.assignments[i].cell_id = *cell_id;
         }
 
-        // Set cell cpumasks and borrowable cpumasks
+        let policies: HashMap<u32, CellPolicy> = cpu_assignments
+            .iter()
+            .map(|a| (a.id, self.cell_manager.cell_policy(a.id)))
+            .collect();
+        let borrowable = policy_borrowable_cpumasks(cpu_assignments, &policies);
+
+        // Set cell cpumasks, borrowable cpumasks and policies
         let mut max_cell_id: u32 = 0;
-        for (a, cell_subcells) in cpu_assignments.iter().zip(subcell_assignments.iter()) {
+        for ((a, cell_subcells), borrowable) in cpu_assignments
+            .iter()
+            .zip(subcell_assignments.iter())
+            .zip(borrowable.iter())
+        {
             validate_subcell_assignments(a, cell_subcells, self.enable_borrowing)?;
 
             if a.id >= bpf_intf::consts_MAX_CELLS {
@@ -961,8 +1115,9 @@ impl<'a> Scheduler<'a> {
             max_cell_id = max_cell_id.max(a.id + 1);
 
             write_cpumask_to_config(&a.primary, &mut config.cpumasks[a.id as usize].mask);
+            config.policies[a.id as usize] = policies[&a.id].to_bpf();
 
-            if let Some(ref borrowable) = a.borrowable {
+            if let Some(borrowable) = borrowable {
                 write_cpumask_to_config(
                     borrowable,
                     &mut config.borrowable_cpumasks[a.id as usize].mask,
@@ -987,6 +1142,19 @@ impl<'a> Scheduler<'a> {
         }
         config.num_cells = max_cell_id;
 
+        let rules = self.cell_manager.subcell_rules();
+        if rules.len() > MAX_SUBCELL_RULES {
+            bail!(
+                "Too many subcell rules: {} > MAX_SUBCELL_RULES ({})",
+                rules.len(),
+                MAX_SUBCELL_RULES
+            );
+        }
+        config.num_subcell_rules = rules.len() as u32;
+        for (i, rule) in rules.iter().enumerate() {
+            config.subcell_rules[i] = rule.to_bpf();
+        }
+
         // Trigger the BPF program to apply the configuration
         let prog = &mut self.skel.progs.apply_cell_config;
         let out = prog
@@ -1326,6 +1494,11 @@ impl<'a> Scheduler<'a> {
                 .and_modify(|cell_metrics| {
                     cell_metrics.num_cpus = cell.cpus.weight() as u32;
                     cell_metrics.cgroup_path = self.cell_manager.cgroup_path_for_cell(*cell_id);
+                    cell_metrics.update_policy(
+                        &self.cell_manager.cell_policy(*cell_id),
+                        self.slice_ns,
+                        self.enable_llc_awareness,
+                    );
                 });
         }
         self.metrics.num_cells = self.cells.len() as u32;
@@ -1678,6 +1851,32 @@ fn validate_subcell_assignments(
     Ok(())
 }
 
+/// Borrowable CPUs of each cell of @cpu_assignments under the borrowing
+/// policies of the cells: cells that don't borrow get none, and the primary
+/// CPUs of cells that don't lend are taken out of every other cell's.
+fn policy_borrowable_cpumasks(
+    cpu_assignments: &[CpuAssignment],
+    policies: &HashMap<u32, CellPolicy>,
+) -> Vec<Option<Cpumask>> {
+    let policy = |id: u32| policies.get(&id).cloned().unwrap_or_default();
+    let not_lent = cpu_assignments
+        .iter()
+        .filter(|a| !policy(a.id).borrowing.may_lend())
+        .fold(Cpumask::new(), |mask, a| mask.or(&a.primary));
+
+    cpu_assignments
+        .iter()
+        .map(|a| {
+            let borrowable = a.borrowable.as_ref()?;
+            Some(if policy(a.id).borrowing.may_borrow() {
+                borrowable.and(&not_lent.not())
+            } else {
+                Cpumask::new()
+            })
+        })
+        .collect()
+}
+
 fn write_cpumask_to_config(cpumask: &Cpumask, dest: &mut [u8]) {
     let raw_slice = cpumask.as_raw_slice();
     for (word_idx, word) in raw_slice.iter().enumerate() {
@@ -1849,8 +2048,14 @@ fn main() -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::{validate_subcell_assignments, CpuAssignment, Cpumask, Opts};
+    use super::{
+        policy_borrowable_cpumasks, validate_subcell_assignments, CellPolicy, CpuAssignment,
+        Cpumask, Metrics, Opts, RebalanceCellDecision, RebalanceDecision,
+    };
+    use crate::cell_config::Borrowing;
+    use crate::stats::MAX_REBALANCE_DECISIONS;
     use clap::Parser;
+    use std::collections::HashMap;
 
     fn cpumask(cpus: &[usize]) -> Cpumask {
         let mut mask = Cpumask::new();
@@ -1883,6 +2088,39 @@ mod tests {
         assert!(Opts::try_parse_from(["scx_mitosis", "--version"]).is_ok());
     }
 
+    #[test]
+    fn allows_cell_config_without_cell_parent_cgroup() {
+        assert!(Opts::try_parse_from(["scx_mitosis", "--cell-config", "cells.json"]).is_ok());
+        assert!(Opts::try_parse_from([
+            "scx_mitosis",
+            "--cell-config",
+            "cells.json",
+            "--cell-parent-cgroup",
+            "/workloads",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_cell_exclude_without_cell_parent_cgroup() {
+        assert!(Opts::try_parse_from([
+            "scx_mitosis",
+            "--cell-config",
+            "cells.json",
+            "--cell-exclude",
+            "system.slice",
+        ])
+        .is_err());
+        assert!(Opts::try_parse_from([
+            "scx_mitosis",
+            "--monitor",
+            "1",
+            "--cell-exclude",
+            "system.slice",
+        ])
+        .is_err());
+    }
+
     #[test]
     fn accepts_complete_unpinned_subcell_partition() {
         let cell = assignment(3, &[0, 1, 2, 3], None);
@@ -1912,4 +2150,77 @@ mod tests {
 
         assert!(validate_subcell_assignments(&cell, &subcells, false).is_err());
     }
+
+    #[test]
+    fn borrowing_policies_restrict_borrowable_cpus() {
+        let assignments = [
+            assignment(0, &[0, 1], Some(&[2, 3, 4, 5])),
+            assignment(1, &[2, 3], Some(&[0, 1, 4, 5])),
+            assignment(2, &[4, 5], Some(&[0, 1, 2, 3])),
+        ];
+        let policies = HashMap::from([
+            (
+                1,
+                CellPolicy {
+                    borrowing: Borrowing::BorrowOnly,
+                    ..Default::default()
+                },
+            ),
+            (
+                2,
+                CellPolicy {
+                    borrowing: Borrowing::LendOnly,
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let borrowable = policy_borrowable_cpumasks(&assignments, &policies);
+        assert_eq!(borrowable[0], Some(cpumask(&[4, 5])));
+        assert_eq!(borrowable[1], Some(cpumask(&[0, 1, 4, 5])));
+        assert_eq!(borrowable[2], Some(cpumask(&[])));
+
+        let no_borrowing = [assignment(0, &[0, 1], None)];
+        assert_eq!(
+            policy_borrowable_cpumasks(&no_borrowing, &policies),
+            vec![None]
+        );
+    }
+
+    #[test]
+    fn rebalance_dry_run_requires_rebalancing() {
+        let base = ["scx_mitosis", "--cell-parent-cgroup", "/workloads"];
+        assert!(Opts::try_parse_from(base.iter().chain(&["--rebalance-dry-run"])).is_err());
+        assert!(Opts::try_parse_from(
+            base.iter()
+                .chain(&["--enable-rebalancing", "--rebalance-dry-run"])
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn rebalance_cell_decision_reports_moved_cpus() {
+        let decision = RebalanceCellDecision::new(60.0, &cpumask(&[0, 1, 2]), &cpumask(&[2, 3]));
+
+        assert_eq!(decision.cpus_before, 3);
+        assert_eq!(decision.cpus_after, 2);
+        assert_eq!(decision.cpus_gained, "3");
+        assert_eq!(decision.cpus_lost, "0-1");
+        assert!((decision.projected_util_pct - 90.0).abs() < 1e-9);
+        assert!(decision.cpus_moved());
+    }
+
+    #[test]
+    fn rebalance_decision_log_is_bounded() {
+        let mut metrics = Metrics::default();
+        for seq in 1..=(MAX_REBALANCE_DECISIONS as u64 + 3) {
+            metrics.record_rebalance(RebalanceDecision {
+                seq,
+                ..Default::default()
+            });
+        }
+
+        assert_eq!(metrics.rebalance_decisions.len(), MAX_REBALANCE_DECISIONS);
+        assert_eq!(metrics.rebalance_decisions[0].seq, 4);
+    }
 }
@@ -13,8 +13,14 @@ use scx_stats::prelude::*;
 use scx_stats_derive::stat_doc;
 use scx_stats_derive::Stats;
 
+use scx_utils::Cpumask;
+
+use crate::cell_config::CellPolicy;
 use crate::DistributionStats;
 
+/// Number of recent rebalancing decisions kept in the metrics.
+pub const MAX_REBALANCE_DECISIONS: usize = 16;
+
 #[stat_doc]
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
 #[stat(_om_prefix = "c_")]
@@ -65,6 +71,15 @@ pub struct CellMetrics {
     pub lent_pct: f64,
     #[stat(desc = "EWMA-smoothed utilization %")]
     pub smoothed_util_pct: f64,
+    #[stat(desc = "Slice of the cell's tasks (us)")]
+    pub slice_us: u64,
+    #[stat(
+        desc = "CPU borrowing policy (both, lend_only, borrow_only or none)",
+        _om_skip
+    )]
+    pub borrowing: String,
+    #[stat(desc = "1 if the cell uses per-LLC queues, else 0")]
+    pub llc_aware: u64,
 }
 
 impl CellMetrics {
@@ -85,6 +100,118 @@ impl CellMetrics {
         self.demand_borrow_pct = demand_borrow_pct;
         self.lent_pct = lent_pct;
     }
+
+    /// Report @policy, as resolved against the default slice and whether LLC
+    /// awareness is enabled.
+    pub fn update_policy(&mut self, policy: &CellPolicy, slice_ns: u64, llc_awareness: bool) {
+        self.slice_us = policy.slice_us.unwrap_or(slice_ns / 1000);
+        self.borrowing = policy.borrowing.to_string();
+        self.llc_aware = (llc_awareness && policy.llc_aware) as u64;
+    }
+}
+
+#[stat_doc]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
+pub struct RebalanceCellDecision {
+    #[stat(desc = "EWMA-smoothed utilization % that drove the decision")]
+    pub demand_pct: f64,
+    #[stat(desc = "Utilization % projected onto the new CPU count")]
+    pub projected_util_pct: f64,
+    #[stat(desc = "Number of cpus before")]
+    pub cpus_before: u32,
+    #[stat(desc = "Number of cpus after")]
+    pub cpus_after: u32,
+    #[stat(desc = "CPUs gained, as a cpulist (empty if none)", _om_skip)]
+    pub cpus_gained: String,
+    #[stat(desc = "CPUs lost, as a cpulist (empty if none)", _om_skip)]
+    pub cpus_lost: String,
+}
+
+/// Like Cpumask::to_cpulist() but empty, rather than "none", for no CPUs.
+fn cpulist(mask: &Cpumask) -> String {
+    if mask.is_empty() {
+        String::new()
+    } else {
+        mask.to_cpulist()
+    }
+}
+
+impl RebalanceCellDecision {
+    pub fn new(demand_pct: f64, before: &Cpumask, after: &Cpumask) -> Self {
+        let cpus_before = before.weight() as u32;
+        let cpus_after = after.weight() as u32;
+        let projected_util_pct = if cpus_after > 0 {
+            demand_pct * cpus_before as f64 / cpus_after as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            demand_pct,
+            projected_util_pct,
+            cpus_before,
+            cpus_after,
+            cpus_gained: cpulist(&after.and(&before.not())),
+            cpus_lost: cpulist(&before.and(&after.not())),
+        }
+    }
+
+    pub fn cpus_moved(&self) -> bool {
+        !self.cpus_gained.is_empty() || !self.cpus_lost.is_empty()
+    }
+}
+
+#[stat_doc]
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Stats)]
+pub struct RebalanceDecision {
+    #[stat(desc = "Decision sequence number")]
+    pub seq: u64,
+    #[stat(desc = "Wall-clock time of the decision, in ms since the epoch")]
+    pub timestamp_ms: u64,
+    #[stat(desc = "1 if the decision was only computed (--rebalance-dry-run), else 0")]
+    pub dry_run: u64,
+    #[stat(desc = "Utilization spread (max - min) that triggered the decision")]
+    pub spread_pct: f64,
+    #[stat(desc = "Configured spread threshold")]
+    pub threshold_pct: f64,
+    #[stat(desc = "Least utilized cell")]
+    pub min_cell: u32,
+    #[stat(desc = "Most utilized cell")]
+    pub max_cell: u32,
+    #[stat(desc = "Per-cell demand and CPU changes")]
+    pub cells: BTreeMap<u32, RebalanceCellDecision>,
+}
+
+impl RebalanceDecision {
+    pub fn summary(&self) -> String {
+        let moves: Vec<String> = self
+            .cells
+            .iter()
+            .filter(|(_, c)| c.cpus_moved())
+            .map(|(id, c)| {
+                let mut s = format!(
+                    "cell {} {:.1}% {}->{} cpus",
+                    id, c.demand_pct, c.cpus_before, c.cpus_after
+                );
+                if !c.cpus_gained.is_empty() {
+                    s += &format!(" +[{}]", c.cpus_gained);
+                }
+                if !c.cpus_lost.is_empty() {
+                    s += &format!(" -[{}]", c.cpus_lost);
+                }
+                s
+            })
+            .collect();
+
+        format!(
+            "spread={:.1}% (cell {} - cell {}) >= {:.1}%: {}",
+            self.spread_pct,
+            self.max_cell,
+            self.min_cell,
+            self.threshold_pct,
+            moves.join(", ")
+        )
+    }
 }
 
 #[stat_doc]
@@ -131,6 +258,12 @@ pub struct Metrics {
     pub lent_pct: f64,
     #[stat(desc = "Number of rebalancing events")]
     pub rebalance_count: u64,
+    #[stat(
+        desc = "Number of rebalancing decisions computed but not applied (--rebalance-dry-run)"
+    )]
+    pub rebalance_dry_run_count: u64,
+    #[stat(desc = "Most recent rebalancing decisions, oldest first", _om_skip)]
+    pub rebalance_decisions: Vec<RebalanceDecision>,
     #[stat(
         desc = "1 if the cell-0 holdout has taken a CPU already claimed by a workload cell, else 0"
     )]
@@ -158,6 +291,13 @@ impl Metrics {
         self.lent_pct = lent_pct;
     }
 
+    pub fn record_rebalance(&mut self, decision: RebalanceDecision) {
+        if self.rebalance_decisions.len() >= MAX_REBALANCE_DECISIONS {
+            self.rebalance_decisions.remove(0);
+        }
+        self.rebalance_decisions.push(decision);
+    }
+
     fn delta(&self, _: &Self) -> Self {
         Self { ..self.clone() }
     }
@@ -187,6 +327,8 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
     StatsServerData::new()
         .add_meta(Metrics::meta())
         .add_meta(CellMetrics::meta())
+        .add_meta(RebalanceCellDecision::meta())
+        .add_meta(RebalanceDecision::meta())
         .add_ops("top", StatsOps { open, close: None })
 }
 
@@ -88,31 +88,19 @@ const volatile struct {
 
 const volatile struct {
 	u64 backoff_ns;
-	u64 dispatch_lb_busy;
-	u64 min_llc_runs_pick2;
-	u64 min_nr_queued_pick2;
-	u64 slack_factor;
 	u64 wakeup_lb_busy;
 
-	bool dispatch_lb_interactive;
 	bool dispatch_pick2_disable;
 	bool eager_load_balance;
 	bool max_dsq_pick2;
-	bool wakeup_llc_migrations;
 	bool single_llc_mode;
 } lb_config = {
 	.backoff_ns = 5LLU * NSEC_PER_MSEC,
-	.dispatch_lb_busy = 75,
-	.min_llc_runs_pick2 = 4,
-	.min_nr_queued_pick2 = 10,
-	.slack_factor = LOAD_BALANCE_SLACK,
 	.wakeup_lb_busy = 90,
 
-	.dispatch_lb_interactive = false,
 	.dispatch_pick2_disable = false,
 	.eager_load_balance = true,
 	.max_dsq_pick2 = false,
-	.wakeup_llc_migrations = false,
 	.single_llc_mode = false,
 };
 
@@ -120,23 +108,16 @@ const volatile struct {
 	u32 nr_dsqs_per_llc;
 	int init_dsq_index;
 	u64 dsq_shift;
-	u32 interactive_ratio;
-	u32 saturated_percent;
 	u32 sched_mode;
 	u32 llc_shards;
-	u64 dhq_max_imbalance;
 
 	bool atq_enabled;
 	bool dhq_enabled;
 	bool cpu_priority;
 	bool task_slice;
 	bool freq_control;
-	bool interactive_sticky;
-	bool keep_running_enabled;
 	bool kthreads_local;
 	bool pelt_enabled;
-	bool fork_balance;
-	bool exec_balance;
 	bool enable_eas;
 	bool thermal_enabled;
 	u16 small_task_threshold;
@@ -146,28 +127,57 @@ const volatile struct {
 	.nr_dsqs_per_llc = 3,
 	.init_dsq_index = 0,
 	.dsq_shift = 2,
-	.interactive_ratio = 10,
-	.saturated_percent = 5,
 	.llc_shards = 0,
-	.dhq_max_imbalance = 3,
 
 	.atq_enabled = false,
 	.dhq_enabled = false,
 	.cpu_priority = false,
 	.task_slice = true,
 	.freq_control = false,
-	.interactive_sticky = false,
-	.keep_running_enabled = true,
 	.kthreads_local = true,
 	.pelt_enabled = true,
-	.fork_balance = true,
-	.exec_balance = true,
 	.enable_eas = false,
 	.thermal_enabled = false,
 	.small_task_threshold = 256,
 	.large_task_threshold = 768,
 };
 
+/*
+ * Tunables that are safe to change while the scheduler is running. Unlike the
+ * const volatile configs above these live in writable data so userspace can
+ * update them through the stats server without reloading the scheduler.
+ */
+struct {
+	u64 dispatch_lb_busy;
+	u64 min_llc_runs_pick2;
+	u64 min_nr_queued_pick2;
+	u64 slack_factor;
+	u64 dhq_max_imbalance;
+	u32 interactive_ratio;
+	u32 saturated_percent;
+
+	bool dispatch_lb_interactive;
+	bool wakeup_llc_migrations;
+	bool interactive_sticky;
+	bool keep_running_enabled;
+	bool fork_balance;
+	bool exec_balance;
+} tune_config = {
+	.min_llc_runs_pick2 = 4,
+	.min_nr_queued_pick2 = 10,
+	.slack_factor = LOAD_BALANCE_SLACK,
+	.dhq_max_imbalance = 3,
+	.interactive_ratio = 10,
+	.saturated_percent = 5,
+
+	.dispatch_lb_interactive = false,
+	.wakeup_llc_migrations = false,
+	.interactive_sticky = false,
+	.keep_running_enabled = true,
+	.fork_balance = true,
+	.exec_balance = true,
+};
+
 /* Latency priority and preemption configuration */
 const volatile struct {
 	bool latency_priority_enabled;
@@ -467,6 +477,17 @@ static __always_inline u64 llc_get_load(const struct llc_ctx *llcx)
 	return p2dq_config.pelt_enabled ? llcx->util_avg : llcx->load;
 }
 
+/*
+ * Get LLC utilization in percent of its CPU capacity, only tracked with PELT.
+ */
+static __always_inline u64 llc_util_percent(const struct llc_ctx *llcx)
+{
+	if (!p2dq_config.pelt_enabled || llcx->nr_cpus == 0)
+		return 0;
+
+	return (100 * llcx->util_avg) / (llcx->nr_cpus * PELT_MAX_UTIL);
+}
+
 static u64 llc_nr_queued(struct llc_ctx *llcx)
 {
 	if (!llcx)
@@ -554,7 +575,7 @@ static int llc_create_dhqs(struct llc_ctx *llcx)
 			false,                          /* vtime mode */
 			dhq_capacity,                   /* fixed capacity */
 			SCX_DHQ_MODE_PRIORITY,          /* lowest vtime wins */
-			p2dq_config.dhq_max_imbalance   /* max_imbalance from config */
+			tune_config.dhq_max_imbalance   /* max_imbalance from config */
 		);
 		if (!llc_pair_dhqs[dhq_index]) {
 			scx_bpf_error("DHQ failed to create DHQ %u for node %u",
@@ -960,7 +981,7 @@ static bool can_migrate(task_ctx *taskc, struct llc_ctx *llcx)
 
 	if (topo_config.nr_llcs < 2 ||
 	    !task_ctx_test_flag(taskc, TASK_CTX_F_ALL_CPUS) ||
-	    (!lb_config.dispatch_lb_interactive && task_ctx_test_flag(taskc, TASK_CTX_F_INTERACTIVE)))
+	    (!tune_config.dispatch_lb_interactive && task_ctx_test_flag(taskc, TASK_CTX_F_INTERACTIVE)))
 		return false;
 
 	if (lb_config.max_dsq_pick2 &&
@@ -1043,7 +1064,7 @@ static bool keep_running(struct cpu_ctx *cpuc, struct llc_ctx *llcx,
 			 struct task_struct *p)
 {
 	// Only tasks in the most interactive DSQs can keep running.
-	if (!p2dq_config.keep_running_enabled ||
+	if (!tune_config.keep_running_enabled ||
 	    !llcx || !cpuc ||
 	    cpuc->dsq_index == p2dq_config.nr_dsqs_per_llc - 1 ||
 	    p->scx.flags & SCX_TASK_QUEUED ||
@@ -1453,7 +1474,7 @@ static s32 pick_idle_cpu(struct task_struct *p, task_ctx *taskc,
 		goto found_cpu;
 	}
 
-	if (p2dq_config.interactive_sticky && task_ctx_test_flag(taskc, TASK_CTX_F_INTERACTIVE)) {
+	if (tune_config.interactive_sticky && task_ctx_test_flag(taskc, TASK_CTX_F_INTERACTIVE)) {
 		*is_idle = scx_bpf_test_and_clear_cpu_idle(prev_cpu);
 		goto found_cpu;
 	}
@@ -1509,7 +1530,7 @@ static s32 pick_idle_cpu(struct task_struct *p, task_ctx *taskc,
 		}
 
 		if (waker_taskc->llc_id == llcx->id ||
-		    !lb_config.wakeup_llc_migrations) {
+		    !tune_config.wakeup_llc_migrations) {
 			// Try an idle smt core in the LLC.
 			if (topo_config.smt_enabled &&
 			    llcx->cpumask &&
@@ -1837,7 +1858,7 @@ static void async_p2dq_enqueue(struct enqueue_promise *ret,
 
 	/* Exec balancing: balance tasks transitioning from fork to exec */
 	if (task_ctx_test_flag(taskc, TASK_CTX_F_FORKNOEXEC) && !(p->flags & PF_FORKNOEXEC) &&
-	    p2dq_config.exec_balance &&
+	    tune_config.exec_balance &&
 	    !lb_config.single_llc_mode &&
 	    task_ctx_test_flag(taskc, TASK_CTX_F_ALL_CPUS)) {
 		struct cpu_ctx *curr_cpuc = lookup_cpu_ctx(cpu);
@@ -1860,7 +1881,7 @@ static void async_p2dq_enqueue(struct enqueue_promise *ret,
 
 	/* Fork balancing: balance newly forked tasks across LLCs */
 	if (task_ctx_test_flag(taskc, TASK_CTX_F_FORKNOEXEC) && taskc->llc_runs == 0 &&
-	    p2dq_config.fork_balance &&
+	    tune_config.fork_balance &&
 	    !lb_config.single_llc_mode &&
 	    task_ctx_test_flag(taskc, TASK_CTX_F_ALL_CPUS)) {
 		struct cpu_ctx *curr_cpuc = lookup_cpu_ctx(cpu);
@@ -2613,9 +2634,14 @@ static __always_inline int dispatch_pick_two(s32 cpu, struct llc_ctx *cur_llcx,
 		return -EINVAL;
 
 
-	if (lb_config.min_nr_queued_pick2 > 0) {
+	// Optionally only pull from other LLCs while the local LLC has spare capacity.
+	if (tune_config.dispatch_lb_busy > 0 &&
+	    llc_util_percent(cur_llcx) >= tune_config.dispatch_lb_busy)
+		return -EINVAL;
+
+	if (tune_config.min_nr_queued_pick2 > 0) {
 		u64 nr_queued = llc_nr_queued(cur_llcx);
-		if (nr_queued < lb_config.min_nr_queued_pick2)
+		if (nr_queued < tune_config.min_nr_queued_pick2)
 			return -EINVAL;
 	}
 
@@ -2666,7 +2692,7 @@ static __always_inline int dispatch_pick_two(s32 cpu, struct llc_ctx *cur_llcx,
 	trace("PICK2 cpu[%d] first[%d] %llu second[%d] %llu",
 	      cpu, first->id, llc_get_load(first), second->id, llc_get_load(second));
 
-	cur_load = llc_get_load(cur_llcx) + ((llc_get_load(cur_llcx) * lb_config.slack_factor) / 100);
+	cur_load = llc_get_load(cur_llcx) + ((llc_get_load(cur_llcx) * tune_config.slack_factor) / 100);
 
 	if (llc_get_load(first) >= cur_load &&
 	    consume_llc(first))
@@ -3069,13 +3095,13 @@ void BPF_STRUCT_OPS(p2dq_update_idle, s32 cpu, bool idle)
 	idle_cpumask = scx_bpf_get_idle_cpumask();
 
 	percent_idle = idle_cpu_percent(idle_cpumask);
-	saturated = percent_idle < p2dq_config.saturated_percent;
+	saturated = percent_idle < tune_config.saturated_percent;
 
 	if (saturated) {
-		min_llc_runs_pick2 = min(2, lb_config.min_llc_runs_pick2);
+		min_llc_runs_pick2 = min(2, tune_config.min_llc_runs_pick2);
 	} else {
 		u32 llc_scaler = log2_u32(topo_config.nr_llcs);
-		min_llc_runs_pick2 = min(log2_u32(percent_idle) + llc_scaler, lb_config.min_llc_runs_pick2);
+		min_llc_runs_pick2 = min(log2_u32(percent_idle) + llc_scaler, tune_config.min_llc_runs_pick2);
 	}
 
 	if (!(llcx = lookup_cpu_llc_ctx(cpu))) {
@@ -3457,8 +3483,8 @@ static bool load_balance_timer(void)
 		if(llc_load > lb_llc_load)
 			load_imbalance = (100 * (llc_load - lb_llc_load)) / llc_load;
 
-		u32 lb_slack = (lb_config.slack_factor > 0 ?
-				lb_config.slack_factor : LOAD_BALANCE_SLACK);
+		u32 lb_slack = (tune_config.slack_factor > 0 ?
+				tune_config.slack_factor : LOAD_BALANCE_SLACK);
 
 		if (load_imbalance > lb_slack)
 			llcx->lb_llc_id = lb_llc_id;
@@ -3483,7 +3509,7 @@ static bool load_balance_timer(void)
 			dsq_time_slices[j] = dsq_time_slices[0] << j << p2dq_config.dsq_shift;
 		}
 	} else {
-		ideal_sum = (load_sum * p2dq_config.interactive_ratio) / 100;
+		ideal_sum = (load_sum * tune_config.interactive_ratio) / 100;
 		dbg("LB autoslice ideal/sum %llu/%llu", ideal_sum, interactive_sum);
 		if (interactive_sum < ideal_sum) {
 			dsq_time_slices[0] = (11 * dsq_time_slices[0]) / 10;
@@ -3519,6 +3545,10 @@ reset_load:
 
 		llcx->last_period_ns = scx_bpf_now();
 
+		/* Pick up runtime changes to the DHQ strand imbalance limit. */
+		if (p2dq_config.dhq_enabled && llcx->mig_dhq)
+			llcx->mig_dhq->max_imbalance = tune_config.dhq_max_imbalance;
+
 		if (!p2dq_config.pelt_enabled) {
 			bpf_for(j, 0, p2dq_config.nr_dsqs_per_llc) {
 				llcx->dsq_load[j] = 0;
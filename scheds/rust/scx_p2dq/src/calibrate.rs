@@ -0,0 +1,242 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Energy profile calibration from RAPL/powercap counters.
+//!
+//! Calibration first measures package power with the system idle, then runs
+//! a busy loop pinned to every CPU of one core type at a time. The difference
+//! between loaded and idle power divided by the number of loaded CPUs gives
+//! the per-CPU dynamic power for that core type.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use scx_utils::Topology;
+use tracing::info;
+use tracing::warn;
+
+use crate::energy::CoreTypeProfile;
+use crate::energy::EnergyProfile;
+use crate::energy::ProfileCoreType;
+
+const POWERCAP_PATH: &str = "/sys/class/powercap";
+
+/// A top level powercap package domain (e.g. intel-rapl:0).
+#[derive(Debug, Clone)]
+struct PowercapDomain {
+    name: String,
+    path: PathBuf,
+    max_energy_range_uj: u64,
+}
+
+impl PowercapDomain {
+    fn read_energy_uj(&self) -> Result<u64> {
+        let path = self.path.join("energy_uj");
+        let buf = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(buf.trim().parse::<u64>()?)
+    }
+
+    /// Energy consumed between two readings, accounting for counter wrap.
+    fn delta_uj(&self, start: u64, end: u64) -> u64 {
+        if end >= start {
+            end - start
+        } else {
+            self.max_energy_range_uj - start + end
+        }
+    }
+}
+
+/// Discover package level powercap domains. Sub-domains such as core or dram
+/// (intel-rapl:0:0) are skipped as package energy covers them.
+fn powercap_domains() -> Result<Vec<PowercapDomain>> {
+    let mut domains = vec![];
+    let entries = std::fs::read_dir(POWERCAP_PATH)
+        .with_context(|| format!("Failed to read {POWERCAP_PATH}, is powercap enabled?"))?;
+
+    for entry in entries {
+        let entry = entry?;
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let Some((_, zone)) = dir_name.split_once(':') else {
+            continue;
+        };
+        if zone.contains(':') {
+            continue;
+        }
+
+        let path = entry.path();
+        let name = std::fs::read_to_string(path.join("name"))
+            .map(|s| s.trim().to_string())
+            .unwrap_or(dir_name.clone());
+        if !name.starts_with("package") {
+            continue;
+        }
+        let max_energy_range_uj = std::fs::read_to_string(path.join("max_energy_range_uj"))
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(u64::MAX);
+
+        domains.push(PowercapDomain {
+            name,
+            path,
+            max_energy_range_uj,
+        });
+    }
+
+    if domains.is_empty() {
+        bail!("No powercap package domains found under {POWERCAP_PATH}");
+    }
+    Ok(domains)
+}
+
+/// Average power in mW across all domains over the given window.
+fn measure_power_mw(domains: &[PowercapDomain], window: Duration) -> Result<f64> {
+    let start: Vec<u64> = domains
+        .iter()
+        .map(|d| d.read_energy_uj())
+        .collect::<Result<_>>()?;
+    let t0 = Instant::now();
+    std::thread::sleep(window);
+    let elapsed = t0.elapsed();
+
+    let mut total_uj = 0u64;
+    for (d, start_uj) in domains.iter().zip(start) {
+        total_uj += d.delta_uj(start_uj, d.read_energy_uj()?);
+    }
+
+    Ok(total_uj as f64 / elapsed.as_micros() as f64 * 1000.0)
+}
+
+fn pin_to_cpu(cpu: usize) -> Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            bail!(
+                "Failed to pin calibration thread to CPU {}: {}",
+                cpu,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Run a busy loop on each of the given CPUs while measuring power.
+fn measure_loaded_power_mw(
+    domains: &[PowercapDomain],
+    cpus: &[usize],
+    window: Duration,
+) -> Result<f64> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut handles = vec![];
+
+    for &cpu in cpus {
+        let stop = stop.clone();
+        handles.push(std::thread::spawn(move || -> Result<()> {
+            pin_to_cpu(cpu)?;
+            let mut x: u64 = cpu as u64;
+            while !stop.load(Ordering::Relaxed) {
+                x = std::hint::black_box(x.wrapping_mul(6364136223846793005).wrapping_add(1));
+            }
+            Ok(())
+        }));
+    }
+
+    // Let frequency governors ramp up before sampling.
+    std::thread::sleep(Duration::from_millis(500));
+    let res = measure_power_mw(domains, window);
+
+    stop.store(true, Ordering::Relaxed);
+    for h in handles {
+        h.join()
+            .map_err(|_| anyhow::anyhow!("calibration thread panicked"))??;
+    }
+    res
+}
+
+/// Derive an energy profile for each core type present in the topology.
+pub fn calibrate(topo: &Topology, window: Duration) -> Result<EnergyProfile> {
+    let domains = powercap_domains()?;
+    info!(
+        "Calibrating energy profile using powercap domains: {}",
+        domains
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let mut cpus_by_type: BTreeMap<ProfileCoreType, Vec<usize>> = BTreeMap::new();
+    for cpu in topo.all_cpus.values() {
+        cpus_by_type
+            .entry(ProfileCoreType::from(&cpu.core_type))
+            .or_default()
+            .push(cpu.id);
+    }
+
+    let nr_cpus = topo.all_cpus.len().max(1) as f64;
+    let idle_mw = measure_power_mw(&domains, window)?;
+    info!("Idle package power: {:.0} mW", idle_mw);
+
+    let mut core_types = vec![];
+    for (core_type, cpus) in cpus_by_type.iter() {
+        let loaded_mw = measure_loaded_power_mw(&domains, cpus, window)?;
+        let dynamic_mw = ((loaded_mw - idle_mw) / cpus.len() as f64).max(1.0);
+        let capacity = cpus
+            .iter()
+            .map(|cpu| topo.all_cpus[cpu].cpu_capacity)
+            .sum::<usize>()
+            / cpus.len();
+
+        info!(
+            "{:?}: {} CPUs, loaded {:.0} mW, {:.0} mW/CPU dynamic, capacity {}",
+            core_type,
+            cpus.len(),
+            loaded_mw,
+            dynamic_mw,
+            capacity
+        );
+        if loaded_mw <= idle_mw {
+            warn!(
+                "{:?}: loaded power not above idle, system may not be quiet",
+                core_type
+            );
+        }
+
+        core_types.push(CoreTypeProfile {
+            core_type: *core_type,
+            capacity: capacity.clamp(1, 1024) as u32,
+            base_power_mw: (idle_mw / nr_cpus).round() as u32,
+            dynamic_power_mw: dynamic_mw.round() as u32,
+        });
+    }
+
+    Ok(EnergyProfile {
+        source: format!(
+            "calibrated {} over {}s windows",
+            chrono::Local::now().to_rfc3339(),
+            window.as_secs_f64()
+        ),
+        core_types,
+    })
+}
+
+/// Calibrate and write the resulting profile to `output`.
+pub fn calibrate_to_file(topo: &Topology, window: Duration, output: &Path) -> Result<()> {
+    let profile = calibrate(topo, window)?;
+    profile.save(output)?;
+    info!("Wrote energy profile to {}", output.display());
+    Ok(())
+}
@@ -1,7 +1,9 @@
-use anyhow::Result;
-use scx_utils::{EnergyModel as KernelEnergyModel, Topology};
+use anyhow::{bail, Context, Result};
+use scx_utils::{CoreType, EnergyModel as KernelEnergyModel, Topology};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use tracing::info;
+use std::path::Path;
+use tracing::{info, warn};
 
 /// Energy characteristics for a CPU type
 #[derive(Debug, Clone)]
@@ -23,6 +25,98 @@ impl CpuEnergyProfile {
     }
 }
 
+/// Core type key used in energy profile files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileCoreType {
+    BigTurbo,
+    Big,
+    Little,
+}
+
+impl From<&CoreType> for ProfileCoreType {
+    fn from(core_type: &CoreType) -> Self {
+        match core_type {
+            CoreType::Big { turbo: true } => ProfileCoreType::BigTurbo,
+            CoreType::Big { turbo: false } => ProfileCoreType::Big,
+            CoreType::Little => ProfileCoreType::Little,
+        }
+    }
+}
+
+/// Measured energy characteristics of a single core type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoreTypeProfile {
+    pub core_type: ProfileCoreType,
+    pub capacity: u32,         // Relative performance (0-1024)
+    pub base_power_mw: u32,    // Per-CPU idle power (mW)
+    pub dynamic_power_mw: u32, // Per-CPU power at 100% util above idle (mW)
+}
+
+/// Energy profile file contents, one entry per core type
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnergyProfile {
+    /// Free form description of where the numbers came from
+    #[serde(default)]
+    pub source: String,
+    pub core_types: Vec<CoreTypeProfile>,
+}
+
+impl EnergyProfile {
+    /// Load and validate an energy profile from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let buf = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read energy profile {}", path.display()))?;
+        let profile: EnergyProfile = serde_json::from_str(&buf)
+            .with_context(|| format!("Failed to parse energy profile {}", path.display()))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Write the energy profile as pretty printed JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let buf = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, buf)
+            .with_context(|| format!("Failed to write energy profile {}", path.display()))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.core_types.is_empty() {
+            bail!("Energy profile has no core types");
+        }
+        for (i, ct) in self.core_types.iter().enumerate() {
+            if ct.capacity == 0 || ct.capacity > 1024 {
+                bail!(
+                    "Invalid capacity {} for {:?}, must be between 1-1024",
+                    ct.capacity,
+                    ct.core_type
+                );
+            }
+            if ct.dynamic_power_mw == 0 {
+                bail!("Dynamic power for {:?} must be non-zero", ct.core_type);
+            }
+            if self.core_types[..i]
+                .iter()
+                .any(|other| other.core_type == ct.core_type)
+            {
+                bail!("Duplicate energy profile for {:?}", ct.core_type);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the profile for a core type, turbo big cores fall back to the
+    /// plain big core profile and vice versa.
+    pub fn get(&self, core_type: ProfileCoreType) -> Option<&CoreTypeProfile> {
+        let find = |t| self.core_types.iter().find(|ct| ct.core_type == t);
+        find(core_type).or_else(|| match core_type {
+            ProfileCoreType::BigTurbo => find(ProfileCoreType::Big),
+            ProfileCoreType::Big => find(ProfileCoreType::BigTurbo),
+            ProfileCoreType::Little => None,
+        })
+    }
+}
+
 pub struct EnergyModel {
     /// Map from CPU ID to energy profile
     cpu_profiles: BTreeMap<usize, CpuEnergyProfile>,
@@ -36,10 +130,31 @@ impl EnergyModel {
     /// Create new energy model from system topology
     /// Tries to use kernel energy model first, falls back to heuristics
     pub fn new(topo: &Topology) -> Result<Self> {
+        Self::with_profile(topo, None)
+    }
+
+    /// Create new energy model, preferring a measured energy profile when
+    /// given, then the kernel energy model, then heuristics
+    pub fn with_profile(topo: &Topology, profile: Option<&EnergyProfile>) -> Result<Self> {
         let mut cpu_profiles = BTreeMap::new();
 
-        // Try to use kernel energy model if available
-        if let Ok(kernel_em) = KernelEnergyModel::new() {
+        if let Some(profile) = profile {
+            info!("Using measured energy profile ({})", profile.source);
+
+            for cpu in topo.all_cpus.values() {
+                let cpu_profile = match profile.get(ProfileCoreType::from(&cpu.core_type)) {
+                    Some(ct) => Self::create_profile_from_measured(ct),
+                    None => {
+                        warn!(
+                            "No energy profile for CPU {} ({:?}), using heuristics",
+                            cpu.id, cpu.core_type
+                        );
+                        Self::create_profile_from_heuristics(cpu, topo)
+                    }
+                };
+                cpu_profiles.insert(cpu.id, cpu_profile);
+            }
+        } else if let Ok(kernel_em) = KernelEnergyModel::new() {
             info!("Using kernel energy model from /sys/kernel/debug/energy_model");
 
             for cpu in topo.all_cpus.values() {
@@ -92,6 +207,16 @@ impl EnergyModel {
         (small_thresh, large_thresh)
     }
 
+    /// Create energy profile from a measured core type profile
+    fn create_profile_from_measured(ct: &CoreTypeProfile) -> CpuEnergyProfile {
+        CpuEnergyProfile {
+            capacity: ct.capacity,
+            base_power_mw: ct.base_power_mw,
+            dynamic_power_mw: ct.dynamic_power_mw,
+            efficiency: (ct.capacity as f32) / (ct.dynamic_power_mw as f32),
+        }
+    }
+
     /// Create energy profile from kernel energy model
     fn create_profile_from_kernel_em(
         cpu: &scx_utils::Cpu,
@@ -190,3 +315,94 @@ impl EnergyModel {
             .unwrap_or(1024)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn core_type(core_type: ProfileCoreType, capacity: u32) -> CoreTypeProfile {
+        CoreTypeProfile {
+            core_type,
+            capacity,
+            base_power_mw: 50,
+            dynamic_power_mw: 2000,
+        }
+    }
+
+    fn profile(core_types: Vec<CoreTypeProfile>) -> EnergyProfile {
+        EnergyProfile {
+            source: "test".to_string(),
+            core_types,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = profile(vec![
+            core_type(ProfileCoreType::Big, 1024),
+            core_type(ProfileCoreType::Little, 512),
+        ]);
+        assert!(valid.validate().is_ok());
+
+        assert!(profile(vec![]).validate().is_err());
+        assert!(profile(vec![core_type(ProfileCoreType::Big, 0)])
+            .validate()
+            .is_err());
+        assert!(profile(vec![core_type(ProfileCoreType::Big, 1025)])
+            .validate()
+            .is_err());
+        assert!(profile(vec![
+            core_type(ProfileCoreType::Little, 512),
+            core_type(ProfileCoreType::Little, 400),
+        ])
+        .validate()
+        .is_err());
+
+        let mut no_dynamic = core_type(ProfileCoreType::Little, 512);
+        no_dynamic.dynamic_power_mw = 0;
+        assert!(profile(vec![no_dynamic]).validate().is_err());
+    }
+
+    #[test]
+    fn test_get_fallback() {
+        let big_only = profile(vec![core_type(ProfileCoreType::Big, 1024)]);
+        assert_eq!(
+            big_only.get(ProfileCoreType::BigTurbo).unwrap().core_type,
+            ProfileCoreType::Big
+        );
+        assert!(big_only.get(ProfileCoreType::Little).is_none());
+
+        let turbo_only = profile(vec![core_type(ProfileCoreType::BigTurbo, 1024)]);
+        assert_eq!(
+            turbo_only.get(ProfileCoreType::Big).unwrap().core_type,
+            ProfileCoreType::BigTurbo
+        );
+
+        let both = profile(vec![
+            core_type(ProfileCoreType::Big, 900),
+            core_type(ProfileCoreType::BigTurbo, 1024),
+        ]);
+        assert_eq!(both.get(ProfileCoreType::Big).unwrap().capacity, 900);
+        assert_eq!(both.get(ProfileCoreType::BigTurbo).unwrap().capacity, 1024);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("p2dq_energy_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("profile.json");
+        let saved = profile(vec![core_type(ProfileCoreType::Little, 512)]);
+        saved.save(&path).unwrap();
+        let loaded = EnergyProfile::load(&path).unwrap();
+        assert_eq!(loaded.source, "test");
+        assert_eq!(loaded.core_types.len(), 1);
+        assert_eq!(loaded.core_types[0].core_type, ProfileCoreType::Little);
+
+        let invalid = dir.join("invalid.json");
+        std::fs::write(&invalid, r#"{"core_types": []}"#).unwrap();
+        assert!(EnergyProfile::load(&invalid).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
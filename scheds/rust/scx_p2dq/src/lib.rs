@@ -4,7 +4,9 @@
 // GNU General Public License version 2.
 pub mod bpf_intf;
 pub mod bpf_skel;
+pub mod calibrate;
 pub mod energy;
+pub mod tune;
 pub use bpf_skel::types;
 
 use scx_utils::cli::TopologyArgs;
@@ -186,8 +188,9 @@ pub struct SchedulerOpts {
     pub dispatch_pick2_disable: bool,
 
     /// Enables pick2 load balancing on the dispatch path when LLC utilization is under the
-    /// specified utilization.
-    #[clap(long, default_value = "75", value_parser = clap::value_parser!(u64).range(0..100))]
+    /// specified utilization, in percent of the LLC CPU capacity (requires PELT). 0 (default)
+    /// to always enable it.
+    #[clap(long, default_value = "0", value_parser = clap::value_parser!(u64).range(0..100))]
     pub dispatch_lb_busy: u64,
 
     /// Enables pick2 load balancing on the dispatch path for interactive tasks.
@@ -347,6 +350,12 @@ pub struct SchedulerOpts {
     #[clap(long, default_value_t = false, action = clap::ArgAction::Set)]
     pub enable_eas: bool,
 
+    /// Path to a JSON energy profile with measured capacity, base and dynamic
+    /// power per core type. Takes precedence over the kernel energy model and
+    /// frequency based heuristics. Generate one with `calibrate-energy`.
+    #[clap(long)]
+    pub energy_profile: Option<std::path::PathBuf>,
+
     /// Set max uncore frequency in MHz for efficiency mode (Intel only).
     /// When set, limits the uncore (L3 cache, memory controller) frequency
     /// to reduce power consumption. Original values restored on exit.
@@ -437,29 +446,20 @@ macro_rules! init_open_skel {
             rodata.timeline_config.deadline = MaybeUninit::new(opts.deadline);
 
             // load balance config
-            rodata.lb_config.slack_factor = opts.lb_slack_factor;
-            rodata.lb_config.min_nr_queued_pick2 = opts.min_nr_queued_pick2;
-            rodata.lb_config.min_llc_runs_pick2 = opts.min_llc_runs_pick2;
             rodata.lb_config.max_dsq_pick2 = MaybeUninit::new(opts.max_dsq_pick2);
             rodata.lb_config.eager_load_balance = MaybeUninit::new(!opts.eager_load_balance);
             rodata.lb_config.dispatch_pick2_disable = MaybeUninit::new(opts.dispatch_pick2_disable);
-            rodata.lb_config.dispatch_lb_busy = opts.dispatch_lb_busy;
-            rodata.lb_config.dispatch_lb_interactive =
-                MaybeUninit::new(opts.dispatch_lb_interactive);
             rodata.lb_config.wakeup_lb_busy = opts.wakeup_lb_busy;
-            rodata.lb_config.wakeup_llc_migrations = MaybeUninit::new(opts.wakeup_llc_migrations);
             rodata.lb_config.single_llc_mode = MaybeUninit::new(
                 opts.single_llc_fast_path || (opts.hw_auto_optimize && hw_profile.single_llc),
             );
 
             // p2dq config
-            rodata.p2dq_config.interactive_ratio = opts.interactive_ratio as u32;
             rodata.p2dq_config.dsq_shift = opts.dsq_shift as u64;
             rodata.p2dq_config.task_slice = MaybeUninit::new(opts.task_slice);
             rodata.p2dq_config.kthreads_local = MaybeUninit::new(!opts.disable_kthreads_local);
             rodata.p2dq_config.nr_dsqs_per_llc = opts.dumb_queues as u32;
             rodata.p2dq_config.init_dsq_index = opts.init_dsq_index as i32;
-            rodata.p2dq_config.saturated_percent = opts.saturated_percent;
             rodata.p2dq_config.sched_mode = opts.sched_mode.clone() as u32;
             rodata.p2dq_config.llc_shards = opts.llc_shards.max(1);
 
@@ -471,8 +471,6 @@ macro_rules! init_open_skel {
                 opts.dhq_enabled && compat::ksym_exists("bpf_spin_unlock").unwrap_or(false),
             );
 
-            rodata.p2dq_config.dhq_max_imbalance = opts.dhq_max_imbalance;
-
             // Check if cpu_priority is supported by the kernel
             let cpu_priority_supported = compat::ksym_exists("sched_core_priority").unwrap_or(false);
             if opts.cpu_priority && !cpu_priority_supported {
@@ -482,11 +480,7 @@ macro_rules! init_open_skel {
             }
             rodata.p2dq_config.cpu_priority = MaybeUninit::new(opts.cpu_priority && cpu_priority_supported);
             rodata.p2dq_config.freq_control = MaybeUninit::new(opts.freq_control);
-            rodata.p2dq_config.interactive_sticky = MaybeUninit::new(opts.interactive_sticky);
-            rodata.p2dq_config.keep_running_enabled = MaybeUninit::new(opts.keep_running);
             rodata.p2dq_config.pelt_enabled = MaybeUninit::new(opts.enable_pelt);
-            rodata.p2dq_config.fork_balance = MaybeUninit::new(opts.fork_balance);
-            rodata.p2dq_config.exec_balance = MaybeUninit::new(opts.exec_balance);
             rodata.p2dq_config.enable_eas = MaybeUninit::new(opts.enable_eas);
             rodata.p2dq_config.small_task_threshold = 256;  // 25% utilization
             rodata.p2dq_config.large_task_threshold = 768;  // 75% utilization
@@ -498,6 +492,12 @@ macro_rules! init_open_skel {
             rodata.debug = verbose as u32;
             rodata.nr_cpu_ids = *NR_CPU_IDS as u32;
 
+            // runtime tunable config
+            $crate::write_tune_config!(
+                skel.maps.data_data.as_mut().unwrap(),
+                &$crate::tune::TunableOpts::from(opts)
+            );
+
             Ok(())
         }
     };
@@ -505,11 +505,15 @@ macro_rules! init_open_skel {
 
 #[macro_export]
 macro_rules! init_skel {
-    ($skel: expr, $topo: expr) => {{
+    ($skel: expr, $topo: expr) => {
+        $crate::init_skel!($skel, $topo, None)
+    };
+    ($skel: expr, $topo: expr, $energy_profile: expr) => {{
         use $crate::energy::EnergyModel;
+        let energy_profile: Option<&$crate::energy::EnergyProfile> = $energy_profile;
 
         // Initialize energy model for EAS
-        let energy_model = EnergyModel::new(&$topo).unwrap_or_else(|e| {
+        let energy_model = EnergyModel::with_profile(&$topo, energy_profile).unwrap_or_else(|e| {
             eprintln!("Warning: Failed to create energy model: {}", e);
             eprintln!("Energy-aware scheduling will use fallback values");
             EnergyModel::new(&$topo).unwrap() // This should not fail
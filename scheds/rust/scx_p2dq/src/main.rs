@@ -4,8 +4,12 @@
 // GNU General Public License version 2.
 pub mod stats;
 use stats::Metrics;
+use stats::StatsReq;
+use stats::StatsRes;
 
+use std::collections::BTreeMap;
 use std::mem::MaybeUninit;
+use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
@@ -15,6 +19,7 @@ use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use clap::Parser;
+use clap::Subcommand;
 use crossbeam::channel::RecvTimeoutError;
 use libbpf_rs::skel::Skel;
 use libbpf_rs::AsRawLibbpf;
@@ -74,6 +79,9 @@ use bpf_intf::stat_idx_P2DQ_STAT_WAKE_MIG;
 use bpf_intf::stat_idx_P2DQ_STAT_WAKE_PREV;
 use scx_p2dq::bpf_intf;
 use scx_p2dq::bpf_skel::*;
+use scx_p2dq::energy::EnergyProfile;
+use scx_p2dq::tune::TunableOpts;
+use scx_p2dq::tune::TuneState;
 use scx_p2dq::SchedulerOpts;
 use scx_p2dq::TOPO;
 
@@ -103,6 +111,17 @@ struct CliOpts {
     #[clap(long)]
     pub monitor: Option<f64>,
 
+    /// Print the runtime tunable options of a running scheduler and exit.
+    #[clap(long)]
+    pub get_opts: bool,
+
+    /// Update runtime tunable options of a running scheduler and exit.
+    /// Accepts a comma separated list of KEY=VALUE pairs, e.g.
+    /// `--set-opts dispatch_lb_busy=60,interactive_ratio=5`. All pairs are
+    /// validated before any is applied.
+    #[clap(long, value_delimiter = ',', value_parser = parse_opt_pair)]
+    pub set_opts: Vec<(String, String)>,
+
     /// Print version and exit.
     #[clap(long)]
     pub version: bool,
@@ -116,19 +135,47 @@ struct CliOpts {
 
     #[clap(flatten, next_help_heading = "Libbpf Options")]
     pub libbpf: LibbpfOpts,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+fn parse_opt_pair(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.trim().to_string(), v.trim().to_string())),
+        _ => bail!("expected KEY=VALUE, got {:?}", s),
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Measure per core type power using RAPL/powercap counters while running
+    /// synthetic load and write an energy profile for --energy-profile. The
+    /// system should otherwise be idle during calibration.
+    CalibrateEnergy {
+        /// Output path for the JSON energy profile.
+        #[clap(short, long, default_value = "p2dq_energy_profile.json")]
+        output: PathBuf,
+
+        /// Measurement window in seconds for each phase.
+        #[clap(short, long, default_value = "5")]
+        window_secs: u64,
+    },
 }
 
 struct Scheduler<'a> {
     skel: BpfSkel<'a>,
     struct_ops: Option<libbpf_rs::Link>,
     debug_level: u8,
+    tune: TuneState,
 
-    stats_server: StatsServer<(), Metrics>,
+    stats_server: StatsServer<StatsReq, StatsRes>,
 }
 
 impl<'a> Scheduler<'a> {
     fn init(
         opts: &SchedulerOpts,
+        energy_profile: Option<&EnergyProfile>,
         libbpf_ops: &LibbpfOpts,
         open_object: &'a mut MaybeUninit<OpenObject>,
         log_level: &str,
@@ -239,7 +286,7 @@ impl<'a> Scheduler<'a> {
         }
 
         let mut skel = scx_ops_load!(open_skel, p2dq, uei)?;
-        scx_p2dq::init_skel!(&mut skel, topo);
+        scx_p2dq::init_skel!(&mut skel, topo, energy_profile);
 
         let stats_server = StatsServer::new(stats::server_data()).launch()?;
 
@@ -247,6 +294,7 @@ impl<'a> Scheduler<'a> {
             skel,
             struct_ops: None,
             debug_level,
+            tune: TuneState::new(TunableOpts::from(&opts_optimized)),
             stats_server,
         })
     }
@@ -296,12 +344,36 @@ impl<'a> Scheduler<'a> {
         }
     }
 
+    fn set_opts(&mut self, args: &BTreeMap<String, String>) -> Result<()> {
+        if self.tune.apply(args)? {
+            scx_p2dq::write_tune_config!(
+                self.skel.maps.data_data.as_mut().unwrap(),
+                self.tune.opts()
+            );
+        }
+        Ok(())
+    }
+
+    fn stats_req_to_res(&mut self, req: &StatsReq) -> StatsRes {
+        match req {
+            StatsReq::Metrics => StatsRes::Metrics(self.get_metrics()),
+            StatsReq::GetOpts => StatsRes::Opts(self.tune.report()),
+            StatsReq::SetOpts(args) => match self.set_opts(args) {
+                Ok(()) => StatsRes::Opts(self.tune.report()),
+                Err(e) => {
+                    warn!("set_opts rejected: {:#}", e);
+                    StatsRes::Error(format!("{:#}", e))
+                }
+            },
+        }
+    }
+
     fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
         let (res_ch, req_ch) = self.stats_server.channels();
 
         while !shutdown.load(Ordering::Relaxed) && !uei_exited!(&self.skel, uei) {
             match req_ch.recv_timeout(Duration::from_secs(1)) {
-                Ok(()) => res_ch.send(self.get_metrics())?,
+                Ok(req) => res_ch.send(self.stats_req_to_res(&req))?,
                 Err(RecvTimeoutError::Timeout) => {}
                 Err(e) => Err(e)?,
             }
@@ -389,6 +461,22 @@ fn main(opts: CliOpts) -> Result<()> {
         warn!("Setting verbose via -v is deprecated and will be an error in future releases.");
     }
 
+    if opts.get_opts || !opts.set_opts.is_empty() {
+        return stats::request_opts(&opts.set_opts);
+    }
+
+    if let Some(Command::CalibrateEnergy {
+        output,
+        window_secs,
+    }) = &opts.command
+    {
+        return scx_p2dq::calibrate::calibrate_to_file(
+            &TOPO,
+            Duration::from_secs(*window_secs),
+            output,
+        );
+    }
+
     if let Some(run_id) = opts.run_id {
         info!("scx_p2dq run_id: {}", run_id);
     }
@@ -531,10 +619,22 @@ fn main(opts: CliOpts) -> Result<()> {
         None
     };
 
+    let energy_profile = opts
+        .sched
+        .energy_profile
+        .as_deref()
+        .map(EnergyProfile::load)
+        .transpose()?;
+
     let mut open_object = MaybeUninit::uninit();
     loop {
-        let mut sched =
-            Scheduler::init(&opts.sched, &opts.libbpf, &mut open_object, &opts.log_level)?;
+        let mut sched = Scheduler::init(
+            &opts.sched,
+            energy_profile.as_ref(),
+            &opts.libbpf,
+            &mut open_object,
+            &opts.log_level,
+        )?;
         let task_size = std::mem::size_of::<types::task_p2dq>();
         let arenalib = ArenaLib::init(sched.skel.object_mut(), task_size, *NR_CPU_IDS)?;
         arenalib.setup()?;
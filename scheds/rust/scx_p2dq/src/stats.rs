@@ -1,10 +1,14 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
+use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Result;
+use scx_p2dq::tune::OptsReport;
 use scx_p2dq::TOPO;
 use scx_stats::prelude::*;
 use scx_stats_derive::stat_doc;
@@ -201,18 +205,56 @@ impl Metrics {
         }
     }
 }
-pub fn server_data() -> StatsServerData<(), Metrics> {
-    let open: Box<dyn StatsOpener<(), Metrics>> = Box::new(move |(req_ch, res_ch)| {
-        req_ch.send(())?;
-        let mut prev = res_ch.recv()?;
-
-        let read: Box<dyn StatsReader<(), Metrics>> = Box::new(move |_args, (req_ch, res_ch)| {
-            req_ch.send(())?;
-            let cur = res_ch.recv()?;
-            let delta = cur.delta(&prev);
-            prev = cur;
-            delta.to_json()
-        });
+#[derive(Debug)]
+pub enum StatsReq {
+    Metrics,
+    GetOpts,
+    SetOpts(BTreeMap<String, String>),
+}
+
+#[derive(Debug)]
+pub enum StatsRes {
+    Metrics(Metrics),
+    Opts(OptsReport),
+    Error(String),
+}
+
+fn opts_reader(
+    to_req: fn(&BTreeMap<String, String>) -> StatsReq,
+) -> Box<dyn StatsOpener<StatsReq, StatsRes>> {
+    Box::new(move |_| {
+        let read: Box<dyn StatsReader<StatsReq, StatsRes>> =
+            Box::new(move |args, (req_ch, res_ch)| {
+                req_ch.send(to_req(args))?;
+                match res_ch.recv()? {
+                    StatsRes::Opts(report) => Ok(serde_json::to_value(report)?),
+                    StatsRes::Error(e) => Err(anyhow!(e).context(StatsErrno(libc::EINVAL))),
+                    res => bail!("invalid response: {:?}", res),
+                }
+            });
+        Ok(read)
+    })
+}
+
+pub fn server_data() -> StatsServerData<StatsReq, StatsRes> {
+    let open: Box<dyn StatsOpener<StatsReq, StatsRes>> = Box::new(move |(req_ch, res_ch)| {
+        req_ch.send(StatsReq::Metrics)?;
+        let mut prev = match res_ch.recv()? {
+            StatsRes::Metrics(m) => m,
+            res => bail!("invalid response: {:?}", res),
+        };
+
+        let read: Box<dyn StatsReader<StatsReq, StatsRes>> =
+            Box::new(move |_args, (req_ch, res_ch)| {
+                req_ch.send(StatsReq::Metrics)?;
+                let cur = match res_ch.recv()? {
+                    StatsRes::Metrics(m) => m,
+                    res => bail!("invalid response: {:?}", res),
+                };
+                let delta = cur.delta(&prev);
+                prev = cur;
+                delta.to_json()
+            });
 
         Ok(read)
     });
@@ -220,6 +262,44 @@ pub fn server_data() -> StatsServerData<(), Metrics> {
     StatsServerData::new()
         .add_meta(Metrics::meta())
         .add_ops("top", StatsOps { open, close: None })
+        .add_ops(
+            "get_opts",
+            StatsOps {
+                open: opts_reader(|_| StatsReq::GetOpts),
+                close: None,
+            },
+        )
+        .add_ops(
+            "set_opts",
+            StatsOps {
+                open: opts_reader(|args| {
+                    StatsReq::SetOpts(
+                        args.iter()
+                            .filter(|(k, _)| k.as_str() != "target")
+                            .map(|(k, v)| (k.clone(), v.clone()))
+                            .collect(),
+                    )
+                }),
+                close: None,
+            },
+        )
+}
+
+/// Send a `get_opts` or `set_opts` request to a running scheduler and print
+/// the resulting tunables and change history.
+pub fn request_opts(set: &[(String, String)]) -> Result<()> {
+    let mut client = StatsClient::new().connect(None)?;
+    let target = if set.is_empty() {
+        "get_opts"
+    } else {
+        "set_opts"
+    };
+    let mut args = vec![("target".to_string(), target.to_string())];
+    args.extend(set.iter().cloned());
+
+    let report: OptsReport = client.request("stats", args)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
 }
 
 pub fn monitor(intv: Duration, shutdown: Arc<AtomicBool>) -> Result<()> {
@@ -0,0 +1,361 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Subset of `SchedulerOpts` that can be changed while the scheduler is
+//! running. These values live in the writable `tune_config` BPF global
+//! rather than rodata and are updated through the `set_opts` stats target.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::info;
+
+use crate::SchedulerOpts;
+
+/// Number of changes retained in the in-memory audit log.
+const AUDIT_LOG_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunableOpts {
+    pub dispatch_lb_busy: u64,
+    pub dispatch_lb_interactive: bool,
+    pub min_llc_runs_pick2: u64,
+    pub min_nr_queued_pick2: u64,
+    pub lb_slack_factor: u64,
+    pub dhq_max_imbalance: u64,
+    pub interactive_ratio: u32,
+    pub saturated_percent: u32,
+    pub min_slice_us: u64,
+    pub wakeup_llc_migrations: bool,
+    pub interactive_sticky: bool,
+    pub keep_running: bool,
+    pub fork_balance: bool,
+    pub exec_balance: bool,
+}
+
+impl From<&SchedulerOpts> for TunableOpts {
+    fn from(opts: &SchedulerOpts) -> Self {
+        Self {
+            dispatch_lb_busy: opts.dispatch_lb_busy,
+            dispatch_lb_interactive: opts.dispatch_lb_interactive,
+            min_llc_runs_pick2: opts.min_llc_runs_pick2,
+            min_nr_queued_pick2: opts.min_nr_queued_pick2,
+            lb_slack_factor: opts.lb_slack_factor,
+            dhq_max_imbalance: opts.dhq_max_imbalance,
+            interactive_ratio: opts.interactive_ratio as u32,
+            saturated_percent: opts.saturated_percent,
+            min_slice_us: opts.min_slice_us,
+            wakeup_llc_migrations: opts.wakeup_llc_migrations,
+            interactive_sticky: opts.interactive_sticky,
+            keep_running: opts.keep_running,
+            fork_balance: opts.fork_balance,
+            exec_balance: opts.exec_balance,
+        }
+    }
+}
+
+fn parse_bool(val: &str) -> Result<bool> {
+    match val {
+        "1" | "true" | "on" | "yes" => Ok(true),
+        "0" | "false" | "off" | "no" => Ok(false),
+        _ => bail!("invalid boolean {:?}", val),
+    }
+}
+
+fn parse_range(val: &str, min: u64, max: u64) -> Result<u64> {
+    let v: u64 = val
+        .parse()
+        .with_context(|| format!("invalid integer {:?}", val))?;
+    if v < min || v > max {
+        bail!("{} out of range {}-{}", v, min, max);
+    }
+    Ok(v)
+}
+
+impl TunableOpts {
+    /// Names of all runtime tunable options.
+    pub const KEYS: &'static [&'static str] = &[
+        "dispatch_lb_busy",
+        "dispatch_lb_interactive",
+        "min_llc_runs_pick2",
+        "min_nr_queued_pick2",
+        "lb_slack_factor",
+        "dhq_max_imbalance",
+        "interactive_ratio",
+        "saturated_percent",
+        "min_slice_us",
+        "wakeup_llc_migrations",
+        "interactive_sticky",
+        "keep_running",
+        "fork_balance",
+        "exec_balance",
+    ];
+
+    /// Current value of `key` formatted as a string.
+    pub fn get(&self, key: &str) -> Result<String> {
+        Ok(match key {
+            "dispatch_lb_busy" => self.dispatch_lb_busy.to_string(),
+            "dispatch_lb_interactive" => self.dispatch_lb_interactive.to_string(),
+            "min_llc_runs_pick2" => self.min_llc_runs_pick2.to_string(),
+            "min_nr_queued_pick2" => self.min_nr_queued_pick2.to_string(),
+            "lb_slack_factor" => self.lb_slack_factor.to_string(),
+            "dhq_max_imbalance" => self.dhq_max_imbalance.to_string(),
+            "interactive_ratio" => self.interactive_ratio.to_string(),
+            "saturated_percent" => self.saturated_percent.to_string(),
+            "min_slice_us" => self.min_slice_us.to_string(),
+            "wakeup_llc_migrations" => self.wakeup_llc_migrations.to_string(),
+            "interactive_sticky" => self.interactive_sticky.to_string(),
+            "keep_running" => self.keep_running.to_string(),
+            "fork_balance" => self.fork_balance.to_string(),
+            "exec_balance" => self.exec_balance.to_string(),
+            _ => bail!("unknown or non-tunable option {:?}", key),
+        })
+    }
+
+    fn set_inner(&mut self, key: &str, val: &str) -> Result<()> {
+        match key {
+            "dispatch_lb_busy" => self.dispatch_lb_busy = parse_range(val, 0, 99)?,
+            "dispatch_lb_interactive" => self.dispatch_lb_interactive = parse_bool(val)?,
+            "min_llc_runs_pick2" => self.min_llc_runs_pick2 = parse_range(val, 0, u64::MAX)?,
+            "min_nr_queued_pick2" => self.min_nr_queued_pick2 = parse_range(val, 0, u64::MAX)?,
+            "lb_slack_factor" => self.lb_slack_factor = parse_range(val, 0, 98)?,
+            "dhq_max_imbalance" => self.dhq_max_imbalance = parse_range(val, 0, 100)?,
+            "interactive_ratio" => self.interactive_ratio = parse_range(val, 1, 99)? as u32,
+            "saturated_percent" => self.saturated_percent = parse_range(val, 0, 100)? as u32,
+            "min_slice_us" => self.min_slice_us = parse_range(val, 1, 1_000_000)?,
+            "wakeup_llc_migrations" => self.wakeup_llc_migrations = parse_bool(val)?,
+            "interactive_sticky" => self.interactive_sticky = parse_bool(val)?,
+            "keep_running" => self.keep_running = parse_bool(val)?,
+            "fork_balance" => self.fork_balance = parse_bool(val)?,
+            "exec_balance" => self.exec_balance = parse_bool(val)?,
+            _ => bail!("unknown or non-tunable option"),
+        }
+        Ok(())
+    }
+
+    /// Parse and validate `val` and assign it to `key`. Ranges follow the clap
+    /// value parsers on `SchedulerOpts` where there is one, otherwise the
+    /// values the BPF side can handle.
+    pub fn set(&mut self, key: &str, val: &str) -> Result<()> {
+        self.set_inner(key, val)
+            .with_context(|| format!("failed to set {}={}", key, val))
+    }
+}
+
+/// A single applied option change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TuneChange {
+    pub timestamp: String,
+    pub key: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Response to `get_opts` and `set_opts` stats requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptsReport {
+    pub opts: TunableOpts,
+    pub history: Vec<TuneChange>,
+}
+
+/// Tracks the live tunables and an audit log of changes to them.
+#[derive(Debug)]
+pub struct TuneState {
+    opts: TunableOpts,
+    history: VecDeque<TuneChange>,
+}
+
+impl TuneState {
+    pub fn new(opts: TunableOpts) -> Self {
+        Self {
+            opts,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn opts(&self) -> &TunableOpts {
+        &self.opts
+    }
+
+    pub fn report(&self) -> OptsReport {
+        OptsReport {
+            opts: self.opts.clone(),
+            history: self.history.iter().cloned().collect(),
+        }
+    }
+
+    /// Apply all `key=value` pairs in `args` or none of them. Returns true if
+    /// any value changed and BPF data needs to be refreshed.
+    pub fn apply(&mut self, args: &BTreeMap<String, String>) -> Result<bool> {
+        let mut next = self.opts.clone();
+        for (key, val) in args.iter() {
+            next.set(key, val)?;
+        }
+
+        let timestamp = chrono::Local::now().to_rfc3339();
+        let mut changed = false;
+        for key in TunableOpts::KEYS {
+            let (old, new) = (self.opts.get(key)?, next.get(key)?);
+            if old == new {
+                continue;
+            }
+            info!(target: "scx_p2dq::audit", "set_opts {}: {} -> {}", key, old, new);
+            if self.history.len() >= AUDIT_LOG_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(TuneChange {
+                timestamp: timestamp.clone(),
+                key: key.to_string(),
+                old,
+                new,
+            });
+            changed = true;
+        }
+
+        self.opts = next;
+        Ok(changed)
+    }
+}
+
+/// Copy the tunables into the `tune_config` BPF global. Works on both the
+/// open skeleton before load and the loaded skeleton's mmapped data.
+#[macro_export]
+macro_rules! write_tune_config {
+    ($data: expr, $tune: expr) => {{
+        let data = &mut *$data;
+        let tune: &$crate::tune::TunableOpts = $tune;
+
+        data.tune_config.dispatch_lb_busy = tune.dispatch_lb_busy;
+        data.tune_config.dispatch_lb_interactive = MaybeUninit::new(tune.dispatch_lb_interactive);
+        data.tune_config.min_llc_runs_pick2 = tune.min_llc_runs_pick2;
+        data.tune_config.min_nr_queued_pick2 = tune.min_nr_queued_pick2;
+        data.tune_config.slack_factor = tune.lb_slack_factor;
+        data.tune_config.dhq_max_imbalance = tune.dhq_max_imbalance;
+        data.tune_config.interactive_ratio = tune.interactive_ratio;
+        data.tune_config.saturated_percent = tune.saturated_percent;
+        data.tune_config.wakeup_llc_migrations = MaybeUninit::new(tune.wakeup_llc_migrations);
+        data.tune_config.interactive_sticky = MaybeUninit::new(tune.interactive_sticky);
+        data.tune_config.keep_running_enabled = MaybeUninit::new(tune.keep_running);
+        data.tune_config.fork_balance = MaybeUninit::new(tune.fork_balance);
+        data.tune_config.exec_balance = MaybeUninit::new(tune.exec_balance);
+        data.min_slice_ns = 1000 * tune.min_slice_us;
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tunables() -> TunableOpts {
+        TunableOpts {
+            dispatch_lb_busy: 0,
+            dispatch_lb_interactive: true,
+            min_llc_runs_pick2: 4,
+            min_nr_queued_pick2: 10,
+            lb_slack_factor: 5,
+            dhq_max_imbalance: 3,
+            interactive_ratio: 10,
+            saturated_percent: 5,
+            min_slice_us: 100,
+            wakeup_llc_migrations: false,
+            interactive_sticky: false,
+            keep_running: false,
+            fork_balance: true,
+            exec_balance: true,
+        }
+    }
+
+    fn args(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_get_all_keys() {
+        let opts = tunables();
+        for key in TunableOpts::KEYS {
+            assert!(opts.get(key).is_ok(), "{key}");
+        }
+        assert!(opts.get("dsq_shift").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut state = TuneState::new(tunables());
+        let changed = state
+            .apply(&args(&[("interactive_ratio", "5"), ("keep_running", "on")]))
+            .unwrap();
+        assert!(changed);
+        assert_eq!(state.opts().interactive_ratio, 5);
+        assert!(state.opts().keep_running);
+
+        let report = state.report();
+        assert_eq!(report.history.len(), 2);
+        assert_eq!(report.history[0].key, "interactive_ratio");
+        assert_eq!(report.history[0].old, "10");
+        assert_eq!(report.history[0].new, "5");
+
+        // Setting the current value is not a change
+        assert!(!state.apply(&args(&[("interactive_ratio", "5")])).unwrap());
+        assert_eq!(state.report().history.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_all_or_nothing() {
+        let mut state = TuneState::new(tunables());
+        let before = state.opts().clone();
+
+        assert!(state
+            .apply(&args(&[("interactive_ratio", "5"), ("unknown", "1")]))
+            .is_err());
+        assert!(state
+            .apply(&args(&[("keep_running", "1"), ("min_slice_us", "0")]))
+            .is_err());
+        assert_eq!(state.opts(), &before);
+        assert!(state.report().history.is_empty());
+    }
+
+    #[test]
+    fn test_set_range() {
+        let mut opts = tunables();
+        assert!(opts.set("dispatch_lb_busy", "100").is_err());
+        assert!(opts.set("interactive_ratio", "0").is_err());
+        assert!(opts.set("interactive_ratio", "100").is_err());
+        assert!(opts.set("saturated_percent", "101").is_err());
+        assert!(opts.set("lb_slack_factor", "99").is_err());
+        assert!(opts.set("dhq_max_imbalance", "-1").is_err());
+        assert!(opts.set("min_slice_us", "1000001").is_err());
+        assert!(opts.set("fork_balance", "maybe").is_err());
+        assert_eq!(opts, tunables());
+
+        opts.set("dispatch_lb_busy", "60").unwrap();
+        opts.set("interactive_ratio", "99").unwrap();
+        opts.set("lb_slack_factor", "98").unwrap();
+        opts.set("fork_balance", "off").unwrap();
+        assert_eq!(opts.dispatch_lb_busy, 60);
+        assert_eq!(opts.interactive_ratio, 99);
+        assert_eq!(opts.lb_slack_factor, 98);
+        assert!(!opts.fork_balance);
+    }
+
+    #[test]
+    fn test_audit_log_len() {
+        let mut state = TuneState::new(tunables());
+        for i in 0..AUDIT_LOG_LEN + 10 {
+            let ratio = (i % 90 + 1).to_string();
+            state
+                .apply(&args(&[("interactive_ratio", &ratio)]))
+                .unwrap();
+        }
+        assert_eq!(state.report().history.len(), AUDIT_LOG_LEN);
+    }
+}
@@ -11,11 +11,9 @@ mod bpf;
 use bpf::*;
 
 mod stats;
-use std::collections::BTreeSet;
 use std::io::{self};
 use std::mem::MaybeUninit;
 use std::time::Duration;
-use std::time::SystemTime;
 
 use anyhow::Result;
 use clap::Parser;
@@ -23,6 +21,8 @@ use libbpf_rs::OpenObject;
 use log::info;
 use log::warn;
 use procfs::process::Process;
+use scx_rustland_core::DeadlinePolicy;
+use scx_rustland_core::SchedRuntime;
 use scx_stats::prelude::*;
 use scx_utils::build_id;
 use scx_utils::libbpf_clap_opts::LibbpfOpts;
@@ -56,8 +56,8 @@ const SCHEDULER_NAME: &str = "RustLand";
 /// exec_runtime, resulting in earlier deadlines. In contrast, CPU-intensive tasks that don’t sleep
 /// accumulate a larger exec_runtime and thus get scheduled later.
 ///
-/// All the tasks are stored in a BTreeSet (TaskTree), using the deadline as the ordering key.
-/// Once the order of execution is determined all tasks are sent back to the BPF counterpart
+/// The policy is implemented by DeadlinePolicy (scx_rustland_core), that keeps the tasks ordered
+/// by deadline, and driven by SchedRuntime, that sends them back to the BPF counterpart
 /// (scx_rustland_core) to be dispatched.
 ///
 /// The BPF dispatcher is completely agnostic of the particular scheduling policy implemented in
@@ -128,45 +128,16 @@ struct Opts {
 // Time constants.
 const NSEC_PER_USEC: u64 = 1_000;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-struct Task {
-    qtask: QueuedTask, // queued task
-    deadline: u64,     // task deadline (that determines the order how tasks are dispatched)
-    timestamp: u64,    // task enqueue timestamp
-}
-
-// Sort tasks by their interactive status first (interactive tasks are always scheduled before
-// regular tasks), then sort them by their vruntime, then by their timestamp and lastly by their
-// pid.
-impl Ord for Task {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.deadline
-            .cmp(&other.deadline)
-            .then_with(|| self.timestamp.cmp(&other.timestamp))
-            .then_with(|| self.qtask.pid.cmp(&other.qtask.pid))
-    }
-}
-
-impl PartialOrd for Task {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 // Main scheduler object
 struct Scheduler<'a> {
-    bpf: BpfScheduler<'a>,                  // BPF connector
-    opts: &'a Opts,                         // scheduler options
-    stats_server: StatsServer<(), Metrics>, // statistics
-    tasks: BTreeSet<Task>,                  // tasks ordered by deadline
-    vruntime_now: u64,     // Tracks the latest observed (max) vruntime across tasks
-    init_page_faults: u64, // Initial page faults counter
-    slice_ns: u64,         // Default time slice (in ns)
-    slice_ns_min: u64,     // Minimum time slice (in ns)
+    runtime: SchedRuntime<BpfScheduler<'a>, DeadlinePolicy>, // Scheduling policy + BPF connector
+    stats_server: StatsServer<(), Metrics>,                  // statistics
+    init_page_faults: u64,                                   // Initial page faults counter
+    nr_dequeue_errors: u64,                                  // Dequeue errors already reported
 }
 
 impl<'a> Scheduler<'a> {
-    fn init(opts: &'a Opts, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Self> {
+    fn init(opts: &Opts, open_object: &'a mut MaybeUninit<OpenObject>) -> Result<Self> {
         let stats_server = StatsServer::new(stats::server_data()).launch()?;
 
         let slice_ns = opts.slice_us * NSEC_PER_USEC;
@@ -181,6 +152,7 @@ impl<'a> Scheduler<'a> {
             opts.verbose,
             true, // Enable built-in idle CPU selection policy
             opts.numa_local,
+            false, // Extended task context is not needed
             slice_ns_min,
             "rustland",
         )?;
@@ -192,16 +164,17 @@ impl<'a> Scheduler<'a> {
             scx_rustland_core::VERSION
         );
 
+        // Dispatch one task per scheduling cycle, ordered by deadline (if percpu_local is
+        // enabled, send tasks directly to their previously used CPU).
+        let runtime = SchedRuntime::new(bpf, DeadlinePolicy::new(slice_ns, slice_ns_min))
+            .select_idle_cpu(!opts.percpu_local);
+
         // Return scheduler object.
         Ok(Self {
-            bpf,
-            opts,
+            runtime,
             stats_server,
-            tasks: BTreeSet::new(),
-            vruntime_now: 0,
             init_page_faults: 0,
-            slice_ns,
-            slice_ns_min,
+            nr_dequeue_errors: 0,
         })
     }
 
@@ -211,149 +184,23 @@ impl<'a> Scheduler<'a> {
             self.init_page_faults = page_faults;
         }
         let nr_page_faults = page_faults - self.init_page_faults;
+        let bpf = self.runtime.backend_mut();
 
         Metrics {
-            nr_running: *self.bpf.nr_running_mut(),
-            nr_cpus: *self.bpf.nr_online_cpus_mut(),
-            nr_queued: *self.bpf.nr_queued_mut(),
-            nr_scheduled: *self.bpf.nr_scheduled_mut(),
+            nr_running: *bpf.nr_running_mut(),
+            nr_cpus: *bpf.nr_online_cpus_mut(),
+            nr_queued: *bpf.nr_queued_mut(),
+            nr_scheduled: *bpf.nr_scheduled_mut(),
             nr_page_faults,
-            nr_user_dispatches: *self.bpf.nr_user_dispatches_mut(),
-            nr_kernel_dispatches: *self.bpf.nr_kernel_dispatches_mut(),
-            nr_cancel_dispatches: *self.bpf.nr_cancel_dispatches_mut(),
-            nr_bounce_dispatches: *self.bpf.nr_bounce_dispatches_mut(),
-            nr_failed_dispatches: *self.bpf.nr_failed_dispatches_mut(),
-            nr_sched_congested: *self.bpf.nr_sched_congested_mut(),
+            nr_user_dispatches: *bpf.nr_user_dispatches_mut(),
+            nr_kernel_dispatches: *bpf.nr_kernel_dispatches_mut(),
+            nr_cancel_dispatches: *bpf.nr_cancel_dispatches_mut(),
+            nr_bounce_dispatches: *bpf.nr_bounce_dispatches_mut(),
+            nr_failed_dispatches: *bpf.nr_failed_dispatches_mut(),
+            nr_sched_congested: *bpf.nr_sched_congested_mut(),
         }
     }
 
-    // Return current timestamp in ns.
-    fn now() -> u64 {
-        let ts = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-        ts.as_nanos() as u64
-    }
-
-    // Return a value proportional to the task's weight.
-    fn scale_by_task_weight(task: &QueuedTask, value: u64) -> u64 {
-        value * task.weight / 100
-    }
-
-    // Return a value inversely proportional to the task's weight.
-    fn scale_by_task_weight_inverse(task: &QueuedTask, value: u64) -> u64 {
-        value * 100 / task.weight
-    }
-
-    /// Updates a task's virtual runtime based on kernel information and
-    /// returns the evaluated deadline.
-    ///
-    /// This method implements the main task ordering logic of the scheduler.
-    fn update_enqueued(&mut self, task: &mut QueuedTask) -> u64 {
-        // Update task's vruntime.
-        task.vtime = if task.vtime == 0 {
-            // Re-align new tasks to the current vruntime.
-            self.vruntime_now
-        } else {
-            // Prevent sleeping tasks from gaining more than one full slice of vruntime credit.
-            let vruntime_min = self.vruntime_now.saturating_sub(self.slice_ns);
-            task.vtime.max(vruntime_min)
-        };
-
-        // Compute the time slice the task just consumed.
-        let slice_ns = task.stop_ts.saturating_sub(task.start_ts);
-
-        // Update task and global vruntimes.
-        let vslice = Self::scale_by_task_weight_inverse(task, slice_ns);
-        task.vtime += vslice;
-        self.vruntime_now += vslice;
-
-        // Compute the deadline, adding the accumulated runtime since the last sleep. Cap
-        // exec_runtime to 100 time slices to prevent starvation of CPU-intensive tasks.
-        task.vtime + task.exec_runtime.min(self.slice_ns.saturating_mul(100))
-    }
-
-    /// Dispatch the next task in the queue.
-    ///
-    /// Return true if dispatching succeeded or there was no task to dispatch, or false if
-    /// dispatching failed (the task is automatically re-enqueued in that case).
-    fn dispatch_task(&mut self) -> bool {
-        // Retrieve the next task to dispatch, if any.
-        let Some(task) = self.tasks.pop_first() else {
-            return true;
-        };
-
-        // Initialize a dispatched task from the queued one.
-        let mut dispatched_task = DispatchedTask::new(&task.qtask);
-
-        // Assign the minimum time slice scaled by the task's priority.
-        dispatched_task.slice_ns = Self::scale_by_task_weight(&task.qtask, self.slice_ns_min);
-
-        // Propagate the evaluated deadline to the BPF backend.
-        dispatched_task.vtime = task.deadline;
-
-        // Attempt to select an idle CPU for the task (if percpu_local is enabled, send per-CPU
-        // tasks directly to their only usable CPU).
-        dispatched_task.cpu = if self.opts.percpu_local {
-            task.qtask.cpu
-        } else {
-            match self
-                .bpf
-                .select_cpu(task.qtask.pid, task.qtask.cpu, task.qtask.flags)
-            {
-                cpu if cpu >= 0 => cpu,
-                _ => RL_CPU_ANY,
-            }
-        };
-
-        // Send the task to the BPF dispatcher.
-        if self.bpf.dispatch_task(&dispatched_task).is_err() {
-            // Dispatching failed: reinsert the task and stop dispatching.
-            self.tasks.insert(task);
-            return false;
-        }
-
-        true
-    }
-
-    // Drain all the tasks from the queued list, update their vruntime (Self::update_enqueued()),
-    // then push them all to the task pool (doing so will sort them by their vruntime).
-    fn drain_queued_tasks(&mut self) {
-        loop {
-            match self.bpf.dequeue_task() {
-                Ok(Some(mut task)) => {
-                    // Update task information and determine vruntime.
-                    let deadline = self.update_enqueued(&mut task);
-                    let timestamp = Self::now();
-
-                    // Insert task in the task pool (ordered by vruntime).
-                    self.tasks.insert(Task {
-                        qtask: task,
-                        deadline,
-                        timestamp,
-                    });
-                }
-                Ok(None) => {
-                    break;
-                }
-                Err(err) => {
-                    warn!("Error: {err}");
-                    break;
-                }
-            }
-        }
-    }
-
-    // Main scheduling function (called in a loop to periodically drain tasks from the queued list
-    // and dispatch them to the BPF part via the dispatched list).
-    fn schedule(&mut self) {
-        self.drain_queued_tasks();
-        self.dispatch_task();
-
-        // Notify the dispatcher if there are still pending tasks to be processed.
-        self.bpf.notify_complete(self.tasks.len() as u64);
-    }
-
     // Get total page faults from the process.
     fn get_page_faults() -> Result<u64, io::Error> {
         let myself = Process::myself().map_err(io::Error::other)?;
@@ -365,9 +212,15 @@ impl<'a> Scheduler<'a> {
     fn run(&mut self) -> Result<UserExitInfo> {
         let (res_ch, req_ch) = self.stats_server.channels();
 
-        while !self.bpf.exited() {
+        while !self.runtime.backend_mut().exited() {
             // Call the main scheduler body.
-            self.schedule();
+            self.runtime.schedule();
+
+            let nr_dequeue_errors = self.runtime.stats().nr_dequeue_errors;
+            if nr_dequeue_errors > self.nr_dequeue_errors {
+                warn!("Error: failed to receive queued tasks");
+                self.nr_dequeue_errors = nr_dequeue_errors;
+            }
 
             // Handle monitor requests asynchronously.
             if req_ch.try_recv().is_ok() {
@@ -375,7 +228,7 @@ impl<'a> Scheduler<'a> {
             }
         }
 
-        self.bpf.shutdown_and_report()
+        self.runtime.backend_mut().shutdown_and_report()
     }
 }
 
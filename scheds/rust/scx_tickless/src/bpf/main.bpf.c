@@ -43,6 +43,18 @@ const volatile u64 preferred_cpus[MAX_CPUS];
 volatile u64 nr_ticks, nr_preemptions;
 volatile u64 nr_direct_dispatches, nr_timer_dispatches, nr_primary_dispatches;
 
+/*
+ * Scheduling CPUs load, sampled by the scheduling timer and used by
+ * user-space to resize the primary domain: number of timer runs, sum of
+ * the tasks waiting in the shared queue and sum of the timer latencies.
+ */
+volatile u64 nr_timer_runs, nr_queued_sum, timer_lat_sum_ns;
+
+/*
+ * Expected expiration time of the scheduling timer.
+ */
+static u64 timer_expected_at;
+
 struct cpu_ctx {
 	struct bpf_timer timer;
 	bool timer_initialized;
@@ -402,8 +414,17 @@ static bool dispatch_all_cpus(bool do_idle_smt, bool from_dispatch)
 
 static int sched_timerfn(void *map, int *key, struct bpf_timer *timer)
 {
+	u64 now = bpf_ktime_get_ns();
 	s32 cpu;
 
+	/*
+	 * Sample the shared queue backlog and the timer latency.
+	 */
+	if (timer_expected_at && time_after(now, timer_expected_at))
+		__sync_fetch_and_add(&timer_lat_sum_ns, now - timer_expected_at);
+	__sync_fetch_and_add(&nr_queued_sum, scx_bpf_dsq_nr_queued(SHARED_DSQ));
+	__sync_fetch_and_add(&nr_timer_runs, 1);
+
 	/*
 	 * Dispatch tasks on the available CPUs.
 	 */
@@ -441,6 +462,7 @@ static int sched_timerfn(void *map, int *key, struct bpf_timer *timer)
 	}
 	bpf_rcu_read_unlock();
 
+	timer_expected_at = bpf_ktime_get_ns() + tick_interval_ns();
 	bpf_timer_start(timer, tick_interval_ns(), 0);
 
 	return 0;
@@ -716,6 +738,41 @@ int enable_primary_cpu(struct cpu_arg *input)
 	return ret;
 }
 
+/*
+ * Remove a CPU from the pool of CPUs dedicated to process scheduling
+ * events.
+ *
+ * The CPU running the scheduling timer can't be removed.
+ */
+SEC("syscall")
+int disable_primary_cpu(struct cpu_arg *input)
+{
+	struct bpf_cpumask *mask;
+	s32 cpu = input->cpu_id;
+	int ret = -ENOENT;
+
+	if (cpu < 0 || cpu >= nr_cpu_ids)
+		return -EINVAL;
+
+	bpf_rcu_read_lock();
+
+	mask = primary_cpumask;
+	if (mask) {
+		struct cpu_ctx *cctx = try_lookup_cpu_ctx(cpu);
+
+		if (cctx && cctx->timer_initialized) {
+			ret = -EBUSY;
+		} else {
+			bpf_cpumask_clear_cpu(cpu, mask);
+			ret = 0;
+		}
+	}
+
+	bpf_rcu_read_unlock();
+
+	return ret;
+}
+
 SEC("syscall")
 int start_timer(struct cpu_arg *input)
 {
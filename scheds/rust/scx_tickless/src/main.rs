@@ -10,6 +10,7 @@ pub use bpf_skel::*;
 pub mod bpf_intf;
 pub use bpf_intf::*;
 
+mod scaler;
 mod stats;
 use std::ffi::c_int;
 use std::fs;
@@ -17,7 +18,7 @@ use std::mem::MaybeUninit;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use affinity::set_thread_affinity;
 use anyhow::bail;
@@ -29,6 +30,7 @@ use libbpf_rs::OpenObject;
 use libbpf_rs::ProgramInput;
 use log::warn;
 use log::{debug, info};
+use scaler::{PrimaryScaler, ScalerConfig, TimerSample};
 use scx_stats::prelude::*;
 use scx_utils::build_id;
 use scx_utils::compat;
@@ -73,6 +75,43 @@ struct Opts {
     #[clap(short = 'f', long, default_value = "0")]
     frequency: u64,
 
+    /// Dynamically resize the set of primary CPUs.
+    ///
+    /// The primary domain (--primary-domain) is used as the minimum set of scheduling CPUs and
+    /// more CPUs (starting from the slowest ones) are added when the shared queue backlog or the
+    /// scheduling tick latency grow, and removed again when the load goes away.
+    #[clap(short = 'd', long, action = clap::ArgAction::SetTrue)]
+    dynamic_primary: bool,
+
+    /// Minimum amount of primary CPUs with --dynamic-primary.
+    #[clap(long, default_value = "1")]
+    primary_min_cpus: usize,
+
+    /// Maximum amount of primary CPUs with --dynamic-primary (0 = 1/4 of the CPUs).
+    #[clap(long, default_value = "0")]
+    primary_max_cpus: usize,
+
+    /// Average amount of queued tasks per primary CPU above which a primary CPU is added.
+    #[clap(long, default_value = "4.0")]
+    primary_backlog_high: f64,
+
+    /// Average amount of queued tasks per primary CPU below which a primary CPU is removed.
+    #[clap(long, default_value = "0.5")]
+    primary_backlog_low: f64,
+
+    /// Average scheduling tick latency in microseconds above which a primary CPU is added.
+    #[clap(long, default_value = "500")]
+    primary_tick_lat_us: u64,
+
+    /// Consecutive intervals the load must stay above (or below) the thresholds before the
+    /// primary domain is resized.
+    #[clap(long, default_value = "3")]
+    primary_hysteresis: u32,
+
+    /// Interval in milliseconds used to evaluate the primary domain size.
+    #[clap(long, default_value = "100")]
+    primary_interval_ms: u64,
+
     /// Disable SMT topology awareness.
     #[clap(short = 'n', long, action = clap::ArgAction::SetTrue)]
     nosmt: bool,
@@ -112,8 +151,11 @@ pub fn is_nohz_enabled() -> bool {
 
 struct Scheduler<'a> {
     skel: BpfSkel<'a>,
+    opts: &'a Opts,
     struct_ops: Option<libbpf_rs::Link>,
     stats_server: StatsServer<(), Metrics>,
+    scaler: Option<PrimaryScaler>,
+    nr_primary_resizes: u64,
 }
 
 impl<'a> Scheduler<'a> {
@@ -197,6 +239,39 @@ impl<'a> Scheduler<'a> {
             warn!("failed to initialize primary domain: error {}", err);
         }
 
+        // Initialize dynamic primary domain resizing.
+        let scaler = if opts.dynamic_primary {
+            let mut order: Vec<usize> = domain.iter().collect();
+            order.extend(
+                cpus.iter()
+                    .rev()
+                    .map(|cpu| cpu.id)
+                    .filter(|id| !domain.test_cpu(*id)),
+            );
+            let max_cpus = match opts.primary_max_cpus {
+                0 => (cpus.len() / 4).max(domain.weight()),
+                n => n,
+            };
+            let config = ScalerConfig {
+                min_cpus: opts.primary_min_cpus,
+                max_cpus,
+                backlog_high: opts.primary_backlog_high,
+                backlog_low: opts.primary_backlog_low,
+                tick_lat_high_ns: opts.primary_tick_lat_us * 1000,
+                hysteresis: opts.primary_hysteresis,
+            };
+            let mut scaler = PrimaryScaler::new(config, order, domain.weight());
+            let (enable, _) = scaler.resize(scaler.min_cpus());
+            for cpu in enable {
+                if let Err(err) = Self::enable_primary_cpu(&mut skel, cpu as i32) {
+                    warn!("failed to add CPU {} to primary domain: error {}", cpu, err);
+                }
+            }
+            Some(scaler)
+        } else {
+            None
+        };
+
         // Attach the scheduler.
         let struct_ops = Some(scx_ops_attach!(skel, tickless_ops)?);
         if let Err(err) = Self::start_timer(&mut skel, timer_cpu as i32) {
@@ -215,8 +290,11 @@ impl<'a> Scheduler<'a> {
 
         Ok(Self {
             skel,
+            opts,
             struct_ops,
             stats_server,
+            scaler,
+            nr_primary_resizes: 0,
         })
     }
 
@@ -242,6 +320,28 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
+    fn disable_primary_cpu(skel: &mut BpfSkel<'_>, cpu: i32) -> Result<(), u32> {
+        let prog = &mut skel.progs.disable_primary_cpu;
+        let mut args = cpu_arg {
+            cpu_id: cpu as c_int,
+        };
+        let input = ProgramInput {
+            context_in: Some(unsafe {
+                std::slice::from_raw_parts_mut(
+                    &mut args as *mut _ as *mut u8,
+                    std::mem::size_of_val(&args),
+                )
+            }),
+            ..Default::default()
+        };
+        let out = prog.test_run(input).unwrap();
+        if out.return_value != 0 {
+            return Err(out.return_value);
+        }
+
+        Ok(())
+    }
+
     fn start_timer(skel: &mut BpfSkel<'_>, cpu: i32) -> Result<(), u32> {
         let prog = &mut skel.progs.start_timer;
         let mut args = cpu_arg {
@@ -281,15 +381,61 @@ impl<'a> Scheduler<'a> {
         Ok(())
     }
 
+    /// Grow or shrink the primary domain based on the load of the scheduling CPUs.
+    fn update_primary_domain(&mut self) {
+        let Some(scaler) = self.scaler.as_mut() else {
+            return;
+        };
+        let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
+        let sample = TimerSample {
+            nr_timer_runs: bss_data.nr_timer_runs,
+            nr_queued_sum: bss_data.nr_queued_sum,
+            timer_lat_sum_ns: bss_data.timer_lat_sum_ns,
+        };
+        let Some(nr_cpus) = scaler.update(sample) else {
+            return;
+        };
+
+        let (enable, disable) = scaler.resize(nr_cpus);
+        for cpu in enable {
+            if let Err(err) = Self::enable_primary_cpu(&mut self.skel, cpu as i32) {
+                warn!("failed to add CPU {} to primary domain: error {}", cpu, err);
+            }
+        }
+        for cpu in disable {
+            if let Err(err) = Self::disable_primary_cpu(&mut self.skel, cpu as i32) {
+                warn!(
+                    "failed to remove CPU {} from primary domain: error {}",
+                    cpu, err as i32
+                );
+            }
+        }
+        self.nr_primary_resizes += 1;
+    }
+
     fn get_metrics(&self) -> Metrics {
         let bss_data = self.skel.maps.bss_data.as_ref().unwrap();
-        Metrics {
+        let mut metrics = Metrics {
             nr_ticks: bss_data.nr_ticks,
             nr_preemptions: bss_data.nr_preemptions,
             nr_direct_dispatches: bss_data.nr_direct_dispatches,
             nr_primary_dispatches: bss_data.nr_primary_dispatches,
             nr_timer_dispatches: bss_data.nr_timer_dispatches,
+            nr_primary_resizes: self.nr_primary_resizes,
+            ..Default::default()
+        };
+        if let Some(scaler) = &self.scaler {
+            let mut mask = Cpumask::new();
+            for &cpu in scaler.active_cpus() {
+                let _ = mask.set_cpu(cpu);
+            }
+            metrics.nr_primary_cpus = scaler.active_cpus().len() as u64;
+            metrics.primary_cpumask = format!("0x{:x}", mask);
+            metrics.avg_backlog = scaler.avg_backlog;
+            metrics.avg_tick_lat_us = scaler.avg_tick_lat_ns / 1000.0;
         }
+
+        metrics
     }
 
     pub fn exited(&mut self) -> bool {
@@ -298,8 +444,20 @@ impl<'a> Scheduler<'a> {
 
     fn run(&mut self, shutdown: Arc<AtomicBool>) -> Result<UserExitInfo> {
         let (res_ch, req_ch) = self.stats_server.channels();
+        let interval = if self.scaler.is_some() {
+            Duration::from_millis(self.opts.primary_interval_ms.max(1))
+        } else {
+            Duration::from_secs(1)
+        };
+        let mut last_update = Instant::now();
         while !shutdown.load(Ordering::Relaxed) && !self.exited() {
-            match req_ch.recv_timeout(Duration::from_secs(1)) {
+            if last_update.elapsed() >= interval {
+                self.update_primary_domain();
+                last_update = Instant::now();
+            }
+
+            let timeout = interval.saturating_sub(last_update.elapsed());
+            match req_ch.recv_timeout(timeout) {
                 Ok(()) => res_ch.send(self.get_metrics())?,
                 Err(RecvTimeoutError::Timeout) => {}
                 Err(e) => Err(e)?,
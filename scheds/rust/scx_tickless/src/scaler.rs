@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: GPL-2.0
+//
+// Copyright (c) 2025 Andrea Righi <arighi@nvidia.com>
+
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use log::info;
+
+/// Scheduling timer counters sampled from BPF.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimerSample {
+    pub nr_timer_runs: u64,
+    pub nr_queued_sum: u64,
+    pub timer_lat_sum_ns: u64,
+}
+
+/// Thresholds used to resize the pool of primary CPUs.
+#[derive(Clone, Debug)]
+pub struct ScalerConfig {
+    /// Minimum and maximum amount of primary CPUs.
+    pub min_cpus: usize,
+    pub max_cpus: usize,
+    /// Average shared queue backlog per primary CPU above which the pool grows.
+    pub backlog_high: f64,
+    /// Average shared queue backlog per primary CPU below which the pool shrinks.
+    pub backlog_low: f64,
+    /// Average scheduling timer latency (ns) above which the pool grows.
+    pub tick_lat_high_ns: u64,
+    /// Consecutive intervals a condition must hold before resizing.
+    pub hysteresis: u32,
+}
+
+/// Grow and shrink the pool of primary CPUs based on the shared queue backlog
+/// and the scheduling timer latency.
+pub struct PrimaryScaler {
+    config: ScalerConfig,
+    /// CPUs that can be used as primary, in the order they are added.
+    order: Vec<usize>,
+    nr_active: usize,
+    prev: TimerSample,
+    grow_streak: u32,
+    shrink_streak: u32,
+    /// Averages observed in the last interval.
+    pub avg_backlog: f64,
+    pub avg_tick_lat_ns: f64,
+}
+
+impl PrimaryScaler {
+    /// Create a scaler over @order, where the first @nr_active CPUs are the
+    /// initial primary domain.
+    pub fn new(config: ScalerConfig, order: Vec<usize>, nr_active: usize) -> Self {
+        // Use max()/min() rather than clamp(), @order can be empty.
+        let nr_active = nr_active.min(order.len());
+        let max_cpus = config.max_cpus.max(nr_active.max(1)).min(order.len());
+        let min_cpus = config.min_cpus.max(nr_active).min(max_cpus);
+        Self {
+            config: ScalerConfig {
+                min_cpus,
+                max_cpus,
+                ..config
+            },
+            order,
+            nr_active,
+            prev: TimerSample::default(),
+            grow_streak: 0,
+            shrink_streak: 0,
+            avg_backlog: 0.0,
+            avg_tick_lat_ns: 0.0,
+        }
+    }
+
+    /// Number of primary CPUs required at startup.
+    pub fn min_cpus(&self) -> usize {
+        self.config.min_cpus
+    }
+
+    /// CPUs currently assigned to the primary domain.
+    pub fn active_cpus(&self) -> &[usize] {
+        &self.order[..self.nr_active]
+    }
+
+    /// Set the amount of active primary CPUs, returning the CPUs to enable
+    /// and the CPUs to disable.
+    pub fn resize(&mut self, nr_active: usize) -> (Vec<usize>, Vec<usize>) {
+        let nr_active = nr_active.max(1).min(self.order.len());
+        let (lo, hi) = if nr_active > self.nr_active {
+            (self.nr_active, nr_active)
+        } else {
+            (nr_active, self.nr_active)
+        };
+        let cpus = self.order[lo..hi].to_vec();
+        let grow = nr_active > self.nr_active;
+        self.nr_active = nr_active;
+
+        if grow {
+            (cpus, vec![])
+        } else {
+            (vec![], cpus)
+        }
+    }
+
+    /// Evaluate the counters collected since the last update and return the
+    /// new amount of primary CPUs, if it needs to change.
+    pub fn update(&mut self, cur: TimerSample) -> Option<usize> {
+        let nr_runs = cur.nr_timer_runs.wrapping_sub(self.prev.nr_timer_runs);
+        let queued = cur.nr_queued_sum.wrapping_sub(self.prev.nr_queued_sum);
+        let lat = cur
+            .timer_lat_sum_ns
+            .wrapping_sub(self.prev.timer_lat_sum_ns);
+        self.prev = cur;
+        if nr_runs == 0 {
+            return None;
+        }
+
+        self.avg_backlog = queued as f64 / nr_runs as f64;
+        self.avg_tick_lat_ns = lat as f64 / nr_runs as f64;
+        let backlog = self.avg_backlog / self.nr_active as f64;
+
+        let overloaded = backlog > self.config.backlog_high
+            || self.avg_tick_lat_ns > self.config.tick_lat_high_ns as f64;
+        let underloaded = backlog < self.config.backlog_low
+            && self.avg_tick_lat_ns < self.config.tick_lat_high_ns as f64 / 2.0;
+
+        if overloaded {
+            self.grow_streak += 1;
+            self.shrink_streak = 0;
+        } else if underloaded {
+            self.shrink_streak += 1;
+            self.grow_streak = 0;
+        } else {
+            self.grow_streak = 0;
+            self.shrink_streak = 0;
+        }
+
+        let target = if self.grow_streak >= self.config.hysteresis
+            && self.nr_active < self.config.max_cpus
+        {
+            self.nr_active + 1
+        } else if self.shrink_streak >= self.config.hysteresis
+            && self.nr_active > self.config.min_cpus
+        {
+            self.nr_active - 1
+        } else {
+            return None;
+        };
+        self.grow_streak = 0;
+        self.shrink_streak = 0;
+
+        info!(
+            "primary CPUs {} -> {} (backlog {:.1}, tick latency {:.0} us)",
+            self.nr_active,
+            target,
+            self.avg_backlog,
+            self.avg_tick_lat_ns / 1000.0
+        );
+
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(min_cpus: usize, max_cpus: usize) -> ScalerConfig {
+        ScalerConfig {
+            min_cpus,
+            max_cpus,
+            backlog_high: 2.0,
+            backlog_low: 0.5,
+            tick_lat_high_ns: 100_000,
+            hysteresis: 2,
+        }
+    }
+
+    /// Counters of @nr_runs timer runs with an average backlog of @backlog
+    /// and an average latency of @lat_ns, on top of @prev.
+    fn sample(prev: TimerSample, nr_runs: u64, backlog: u64, lat_ns: u64) -> TimerSample {
+        TimerSample {
+            nr_timer_runs: prev.nr_timer_runs + nr_runs,
+            nr_queued_sum: prev.nr_queued_sum + nr_runs * backlog,
+            timer_lat_sum_ns: prev.timer_lat_sum_ns + nr_runs * lat_ns,
+        }
+    }
+
+    #[test]
+    fn test_new_limits() {
+        let scaler = PrimaryScaler::new(config(0, 64), vec![0, 1, 2, 3], 2);
+        assert_eq!(scaler.min_cpus(), 2);
+        assert_eq!(scaler.config.max_cpus, 4);
+        assert_eq!(scaler.active_cpus(), &[0, 1]);
+
+        let scaler = PrimaryScaler::new(config(3, 1), vec![0, 1, 2, 3], 2);
+        assert_eq!(scaler.min_cpus(), 2);
+        assert_eq!(scaler.config.max_cpus, 2);
+    }
+
+    #[test]
+    fn test_empty_order() {
+        let mut scaler = PrimaryScaler::new(config(1, 4), vec![], 1);
+        assert_eq!(scaler.min_cpus(), 0);
+        assert!(scaler.active_cpus().is_empty());
+        assert_eq!(scaler.resize(2), (vec![], vec![]));
+
+        let s = sample(TimerSample::default(), 10, 5, 0);
+        assert_eq!(scaler.update(s), None);
+        assert_eq!(scaler.update(sample(s, 10, 5, 0)), None);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut scaler = PrimaryScaler::new(config(1, 4), vec![3, 2, 1, 0], 1);
+        assert_eq!(scaler.resize(3), (vec![2, 1], vec![]));
+        assert_eq!(scaler.active_cpus(), &[3, 2, 1]);
+        assert_eq!(scaler.resize(1), (vec![], vec![2, 1]));
+        assert_eq!(scaler.resize(0), (vec![], vec![]));
+        assert_eq!(scaler.resize(10), (vec![2, 1, 0], vec![]));
+    }
+
+    #[test]
+    fn test_update_hysteresis() {
+        let mut scaler = PrimaryScaler::new(config(1, 3), vec![0, 1, 2], 1);
+
+        // No timer runs, nothing to evaluate.
+        assert_eq!(scaler.update(TimerSample::default()), None);
+
+        // Backlog above backlog_high must hold for two intervals.
+        let mut s = sample(TimerSample::default(), 10, 4, 0);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 4, 0);
+        assert_eq!(scaler.update(s), Some(2));
+        scaler.resize(2);
+
+        // An interval in the target range resets the streak.
+        s = sample(s, 10, 8, 0);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 2, 0);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 8, 0);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 8, 0);
+        assert_eq!(scaler.update(s), Some(3));
+        scaler.resize(3);
+
+        // Never grow above max_cpus.
+        for _ in 0..4 {
+            s = sample(s, 10, 100, 0);
+            assert_eq!(scaler.update(s), None);
+        }
+
+        // Shrink once the backlog and the latency are low.
+        s = sample(s, 10, 0, 0);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 0, 0);
+        assert_eq!(scaler.update(s), Some(2));
+    }
+
+    #[test]
+    fn test_update_tick_latency() {
+        let mut scaler = PrimaryScaler::new(config(1, 2), vec![0, 1], 1);
+        let mut s = sample(TimerSample::default(), 10, 0, 200_000);
+        assert_eq!(scaler.update(s), None);
+        s = sample(s, 10, 0, 200_000);
+        assert_eq!(scaler.update(s), Some(2));
+        assert_eq!(scaler.avg_tick_lat_ns, 200_000.0);
+    }
+}
@@ -32,6 +32,16 @@ pub struct Metrics {
     pub nr_primary_dispatches: u64,
     #[stat(desc = "Number of dispatches routed by the primary CPU timers")]
     pub nr_timer_dispatches: u64,
+    #[stat(desc = "Number of primary CPUs (--dynamic-primary)")]
+    pub nr_primary_cpus: u64,
+    #[stat(desc = "Current primary CPU mask (--dynamic-primary)")]
+    pub primary_cpumask: String,
+    #[stat(desc = "Number of primary domain resizes")]
+    pub nr_primary_resizes: u64,
+    #[stat(desc = "Average tasks waiting in the shared queue")]
+    pub avg_backlog: f64,
+    #[stat(desc = "Average scheduling tick latency (us)")]
+    pub avg_tick_lat_us: f64,
 }
 
 impl Metrics {
@@ -46,6 +56,17 @@ impl Metrics {
             self.nr_primary_dispatches,
             self.nr_timer_dispatches
         )?;
+        if self.nr_primary_cpus > 0 {
+            writeln!(
+                w,
+                "  primary -> cpus: {:<3} mask: {} resizes: {:<3} backlog: {:.1} tick_lat: {:.1}us",
+                self.nr_primary_cpus,
+                self.primary_cpumask,
+                self.nr_primary_resizes,
+                self.avg_backlog,
+                self.avg_tick_lat_us
+            )?;
+        }
         Ok(())
     }
 
@@ -56,6 +77,7 @@ impl Metrics {
             nr_direct_dispatches: self.nr_direct_dispatches - rhs.nr_direct_dispatches,
             nr_primary_dispatches: self.nr_primary_dispatches - rhs.nr_primary_dispatches,
             nr_timer_dispatches: self.nr_timer_dispatches - rhs.nr_timer_dispatches,
+            nr_primary_resizes: self.nr_primary_resizes - rhs.nr_primary_resizes,
             ..self.clone()
         }
     }
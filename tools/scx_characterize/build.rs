@@ -10,4 +10,12 @@ fn main() {
         .enable_skel("src/bpf/hints_tls.bpf.c", "hints_tls")
         .compile_link_gen()
         .unwrap();
+
+    // The sched tracer gets its own skeleton so that recording hints doesn't
+    // load its programs and ring buffer.
+    scx_cargo::BpfBuilder::new()
+        .unwrap()
+        .enable_skel("src/bpf/sched_trace.bpf.c", "sched_trace")
+        .build()
+        .unwrap();
 }
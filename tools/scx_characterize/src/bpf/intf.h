@@ -18,4 +18,45 @@ struct hints_bss {
     unsigned int target_map_id;
 };
 
+#define TRACE_COMM_LEN 16
+#define TRACE_NAME_LEN 32
+
+enum trace_event_kind {
+    TRACE_SCHED_SWITCH,
+    TRACE_SCHED_WAKEUP,
+    TRACE_SCHED_WAKEUP_NEW,
+    TRACE_SCHED_WAKING,
+    TRACE_SCHED_STAT_RUNTIME,
+    TRACE_IRQ_HANDLER_ENTRY,
+    TRACE_IRQ_HANDLER_EXIT,
+    TRACE_SOFTIRQ_ENTRY,
+    TRACE_SOFTIRQ_EXIT,
+    TRACE_SOFTIRQ_RAISE,
+    TRACE_LOCAL_TIMER_ENTRY,
+    TRACE_LOCAL_TIMER_EXIT,
+    TRACE_RESCHEDULE_ENTRY,
+    TRACE_RESCHEDULE_EXIT,
+    TRACE_NMI_HANDLER,
+};
+
+/*
+ * One sched/irq tracepoint hit. comm, pid and tid describe the current task,
+ * the meaning of arg_comm, args and name depends on kind.
+ */
+struct trace_event {
+    unsigned int kind;
+    unsigned int cpu;
+    int pid;
+    int tid;
+    unsigned long long timestamp;
+    char comm[TRACE_COMM_LEN];
+    char arg_comm[2][TRACE_COMM_LEN];
+    long long args[5];
+    char name[TRACE_NAME_LEN];
+};
+
+struct trace_bss {
+    unsigned long long dropped_events;
+};
+
 #endif
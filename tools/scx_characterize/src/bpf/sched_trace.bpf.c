@@ -0,0 +1,290 @@
+// Copyright (c) 2026 Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+#include <linux/types.h>
+#include <bpf/bpf_helpers.h>
+#include <bpf/bpf_tracing.h>
+#include "intf.h"
+
+char _license[] SEC("license") = "GPL";
+
+/*
+ * Native replacement for `perf record -e sched:... -e irq:...`. Every
+ * tracepoint hit is forwarded to userspace which renders it the way
+ * `perf script` would.
+ *
+ * Cannot include vmlinux.h, so the tracepoint contexts below mirror the
+ * formats in /sys/kernel/tracing/events/<category>/<event>/format.
+ */
+struct trace_common {
+    unsigned short type;
+    unsigned char flags;
+    unsigned char preempt_count;
+    int pid;
+};
+
+struct sched_switch_args {
+    struct trace_common common;
+    char prev_comm[TRACE_COMM_LEN];
+    int prev_pid;
+    int prev_prio;
+    long prev_state;
+    char next_comm[TRACE_COMM_LEN];
+    int next_pid;
+    int next_prio;
+};
+
+struct sched_wakeup_args {
+    struct trace_common common;
+    char comm[TRACE_COMM_LEN];
+    int pid;
+    int prio;
+    int target_cpu;
+};
+
+struct sched_stat_runtime_args {
+    struct trace_common common;
+    char comm[TRACE_COMM_LEN];
+    int pid;
+    unsigned long long runtime;
+};
+
+struct irq_handler_entry_args {
+    struct trace_common common;
+    int irq;
+    unsigned int name_loc;
+};
+
+struct irq_handler_exit_args {
+    struct trace_common common;
+    int irq;
+    int ret;
+};
+
+struct softirq_args {
+    struct trace_common common;
+    unsigned int vec;
+};
+
+struct irq_vector_args {
+    struct trace_common common;
+    int vector;
+};
+
+struct nmi_handler_args {
+    struct trace_common common;
+    void *handler;
+    long long delta_ns;
+    int handled;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_RINGBUF);
+    __uint(max_entries, 1024 * 1024);
+} trace_ringbuf SEC(".maps");
+
+struct trace_bss trace_bss SEC(".bss");
+
+static struct trace_event *reserve_event(unsigned int kind)
+{
+    struct trace_event *ev;
+    __u64 pid_tgid;
+
+    ev = bpf_ringbuf_reserve(&trace_ringbuf, sizeof(*ev), 0);
+    if (!ev) {
+        __sync_fetch_and_add(&trace_bss.dropped_events, 1);
+        return NULL;
+    }
+
+    __builtin_memset(ev, 0, sizeof(*ev));
+    pid_tgid = bpf_get_current_pid_tgid();
+    ev->kind = kind;
+    ev->cpu = bpf_get_smp_processor_id();
+    ev->pid = pid_tgid >> 32;
+    ev->tid = (__u32)pid_tgid;
+    ev->timestamp = bpf_ktime_get_ns();
+    bpf_get_current_comm(ev->comm, sizeof(ev->comm));
+
+    return ev;
+}
+
+SEC("tp/sched/sched_switch")
+int trace_sched_switch(struct sched_switch_args *ctx)
+{
+    struct trace_event *ev = reserve_event(TRACE_SCHED_SWITCH);
+
+    if (!ev)
+        return 0;
+
+    __builtin_memcpy(ev->arg_comm[0], ctx->prev_comm, TRACE_COMM_LEN);
+    __builtin_memcpy(ev->arg_comm[1], ctx->next_comm, TRACE_COMM_LEN);
+    ev->args[0] = ctx->prev_pid;
+    ev->args[1] = ctx->prev_prio;
+    ev->args[2] = ctx->prev_state;
+    ev->args[3] = ctx->next_pid;
+    ev->args[4] = ctx->next_prio;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+static int emit_wakeup(struct sched_wakeup_args *ctx, unsigned int kind)
+{
+    struct trace_event *ev = reserve_event(kind);
+
+    if (!ev)
+        return 0;
+
+    __builtin_memcpy(ev->arg_comm[0], ctx->comm, TRACE_COMM_LEN);
+    ev->args[0] = ctx->pid;
+    ev->args[1] = ctx->prio;
+    ev->args[2] = ctx->target_cpu;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+SEC("tp/sched/sched_wakeup")
+int trace_sched_wakeup(struct sched_wakeup_args *ctx)
+{
+    return emit_wakeup(ctx, TRACE_SCHED_WAKEUP);
+}
+
+SEC("tp/sched/sched_wakeup_new")
+int trace_sched_wakeup_new(struct sched_wakeup_args *ctx)
+{
+    return emit_wakeup(ctx, TRACE_SCHED_WAKEUP_NEW);
+}
+
+SEC("tp/sched/sched_waking")
+int trace_sched_waking(struct sched_wakeup_args *ctx)
+{
+    return emit_wakeup(ctx, TRACE_SCHED_WAKING);
+}
+
+SEC("tp/sched/sched_stat_runtime")
+int trace_sched_stat_runtime(struct sched_stat_runtime_args *ctx)
+{
+    struct trace_event *ev = reserve_event(TRACE_SCHED_STAT_RUNTIME);
+
+    if (!ev)
+        return 0;
+
+    __builtin_memcpy(ev->arg_comm[0], ctx->comm, TRACE_COMM_LEN);
+    ev->args[0] = ctx->pid;
+    ev->args[1] = ctx->runtime;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+SEC("tp/irq/irq_handler_entry")
+int trace_irq_handler_entry(struct irq_handler_entry_args *ctx)
+{
+    struct trace_event *ev = reserve_event(TRACE_IRQ_HANDLER_ENTRY);
+
+    if (!ev)
+        return 0;
+
+    ev->args[0] = ctx->irq;
+    /* __data_loc: offset from the start of the record in the low 16 bits */
+    bpf_probe_read_kernel_str(ev->name, sizeof(ev->name),
+                              (void *)ctx + (ctx->name_loc & 0xffff));
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+SEC("tp/irq/irq_handler_exit")
+int trace_irq_handler_exit(struct irq_handler_exit_args *ctx)
+{
+    struct trace_event *ev = reserve_event(TRACE_IRQ_HANDLER_EXIT);
+
+    if (!ev)
+        return 0;
+
+    ev->args[0] = ctx->irq;
+    ev->args[1] = ctx->ret;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+static int emit_softirq(struct softirq_args *ctx, unsigned int kind)
+{
+    struct trace_event *ev = reserve_event(kind);
+
+    if (!ev)
+        return 0;
+
+    ev->args[0] = ctx->vec;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+SEC("tp/irq/softirq_entry")
+int trace_softirq_entry(struct softirq_args *ctx)
+{
+    return emit_softirq(ctx, TRACE_SOFTIRQ_ENTRY);
+}
+
+SEC("tp/irq/softirq_exit")
+int trace_softirq_exit(struct softirq_args *ctx)
+{
+    return emit_softirq(ctx, TRACE_SOFTIRQ_EXIT);
+}
+
+SEC("tp/irq/softirq_raise")
+int trace_softirq_raise(struct softirq_args *ctx)
+{
+    return emit_softirq(ctx, TRACE_SOFTIRQ_RAISE);
+}
+
+static int emit_irq_vector(struct irq_vector_args *ctx, unsigned int kind)
+{
+    struct trace_event *ev = reserve_event(kind);
+
+    if (!ev)
+        return 0;
+
+    ev->args[0] = ctx->vector;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
+
+/* irq_vectors and nmi are x86 only, userspace skips them when missing */
+SEC("tp/irq_vectors/local_timer_entry")
+int trace_local_timer_entry(struct irq_vector_args *ctx)
+{
+    return emit_irq_vector(ctx, TRACE_LOCAL_TIMER_ENTRY);
+}
+
+SEC("tp/irq_vectors/local_timer_exit")
+int trace_local_timer_exit(struct irq_vector_args *ctx)
+{
+    return emit_irq_vector(ctx, TRACE_LOCAL_TIMER_EXIT);
+}
+
+SEC("tp/irq_vectors/reschedule_entry")
+int trace_reschedule_entry(struct irq_vector_args *ctx)
+{
+    return emit_irq_vector(ctx, TRACE_RESCHEDULE_ENTRY);
+}
+
+SEC("tp/irq_vectors/reschedule_exit")
+int trace_reschedule_exit(struct irq_vector_args *ctx)
+{
+    return emit_irq_vector(ctx, TRACE_RESCHEDULE_EXIT);
+}
+
+SEC("tp/nmi/nmi_handler")
+int trace_nmi_handler(struct nmi_handler_args *ctx)
+{
+    struct trace_event *ev = reserve_event(TRACE_NMI_HANDLER);
+
+    if (!ev)
+        return 0;
+
+    ev->args[0] = (long long)ctx->handler;
+    ev->args[1] = ctx->delta_ns;
+    ev->args[2] = ctx->handled;
+    bpf_ringbuf_submit(ev, 0);
+    return 0;
+}
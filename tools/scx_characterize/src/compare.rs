@@ -0,0 +1,626 @@
+// Copyright (c) 2026 Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::extract::normalize_comm_for_cluster;
+use crate::process::PerfSchedScriptRecord;
+use crate::record::PERF_SCHED_JSONL_FILE;
+use crate::sched_util::{
+    parse_sched_categories, sched_time_to_ns, window_overlaps, BucketAggregator, BusyInterval,
+    BusyIntervalSink, SchedBusyTracker, TraceStats, DEFAULT_SYSTEM_CATEGORY_NAMES,
+};
+use crate::util::percentile;
+use anyhow::{Context as _, Result};
+use clap::Parser;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Sum of all DEFAULT_SYSTEM_CATEGORY_NAMES categories.
+const IRQ_CATEGORY: &str = "irq";
+
+#[derive(Debug, Parser)]
+#[command(
+    after_help = "Busy time and CPU shares are compared per window as utilization of the machine (%). Wakeup latency is the time from sched_wakeup(_new) to the woken task being switched in.\n\nSignificance uses Welch's t-test over windows (wakeups for latency) with a normal approximation:\n  *    p < 0.05\n  **   p < 0.01\n  ***  p < 0.001\nWindows are treated as independent samples, so use windows long enough for that to roughly hold."
+)]
+pub struct CompareOpts {
+    /// Baseline processed profile directory or perf.sched.jsonl file
+    #[clap(short = 'a', long)]
+    pub baseline: PathBuf,
+
+    /// Processed profile directory or perf.sched.jsonl file to compare against the baseline
+    #[clap(short = 'b', long)]
+    pub candidate: PathBuf,
+
+    /// Comma-separated mutually exclusive categories, same syntax as `extract sched util`
+    #[clap(long, default_value = "")]
+    pub categories: String,
+
+    /// Aggregation window size in milliseconds
+    #[clap(long, default_value = "100")]
+    pub window_ms: u64,
+
+    /// Number of comms to report, ordered by CPU share in either profile
+    #[clap(long, default_value = "20")]
+    pub top_comms: usize,
+
+    /// Print the report as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+/// Pending wakeups and the resulting wakeup-to-run latencies.
+#[derive(Debug, Default)]
+struct WakeupLatencyTracker {
+    pending: HashMap<i64, u64>,
+    latencies_us: Vec<f64>,
+}
+
+impl WakeupLatencyTracker {
+    fn observe(&mut self, record: &PerfSchedScriptRecord, time_ns: u64) {
+        let Some(fields) = record.fields.as_ref() else {
+            return;
+        };
+
+        match record.event.as_str() {
+            "sched:sched_wakeup" | "sched:sched_wakeup_new" => {
+                if let Some(pid) = fields.get("pid").and_then(Value::as_i64) {
+                    self.pending.entry(pid).or_insert(time_ns);
+                }
+            }
+            "sched:sched_switch" => {
+                let Some(pid) = fields.get("next_pid").and_then(Value::as_i64) else {
+                    return;
+                };
+                if let Some(wakeup_ns) = self.pending.remove(&pid) {
+                    self.latencies_us
+                        .push(time_ns.saturating_sub(wakeup_ns) as f64 / 1000.0);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Feeds busy intervals to a BucketAggregator and keeps per-comm busy time
+/// on the side. Comms are normalized like `extract mem` clusters so thread
+/// numbering differences between runs don't split a comm.
+#[derive(Debug)]
+struct ProfileSink {
+    buckets: BucketAggregator,
+    comm_busy_ns: HashMap<String, Vec<u64>>,
+}
+
+impl BusyIntervalSink for ProfileSink {
+    fn on_interval(&mut self, interval: &BusyInterval) {
+        self.buckets.on_interval(interval);
+        if DEFAULT_SYSTEM_CATEGORY_NAMES.contains(&interval.comm.as_str()) {
+            return;
+        }
+
+        let busy_ns = self
+            .comm_busy_ns
+            .entry(normalize_comm_for_cluster(&interval.comm))
+            .or_default();
+        let (trace_start_ns, window_ns) = (self.buckets.trace_start_ns, self.buckets.window_ns);
+        for (idx, overlap_ns) in window_overlaps(interval, trace_start_ns, window_ns) {
+            if busy_ns.len() <= idx {
+                busy_ns.resize(idx + 1, 0);
+            }
+            busy_ns[idx] += overlap_ns;
+        }
+    }
+}
+
+/// Per-window metrics of one profile.
+#[derive(Debug, Default)]
+struct ProfileSummary {
+    path: PathBuf,
+    cpu_count: usize,
+    duration_ms: u64,
+    windows: usize,
+    /// Utilization (%) per window for total, uncategorized, irq and each category
+    category_util: BTreeMap<String, Vec<f64>>,
+    /// Utilization (%) per window for each normalized comm
+    comm_util: HashMap<String, Vec<f64>>,
+    wakeup_latencies_us: Vec<f64>,
+}
+
+fn sched_jsonl_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(PERF_SCHED_JSONL_FILE)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn load_profile(path: &Path, categories: &str, window_ms: u64) -> Result<ProfileSummary> {
+    let jsonl = sched_jsonl_path(path);
+    let file = File::open(&jsonl).with_context(|| format!("failed to open {}", jsonl.display()))?;
+    let reader = BufReader::new(file);
+
+    let window_ns = window_ms * 1_000_000;
+    let mut stats = TraceStats::default();
+    let mut wakeups = WakeupLatencyTracker::default();
+    let mut tracker = SchedBusyTracker::new(ProfileSink {
+        buckets: BucketAggregator::new(0, window_ns, parse_sched_categories(categories)?),
+        comm_busy_ns: HashMap::new(),
+    });
+    let mut first_record = true;
+
+    for line in reader.lines() {
+        let line = line.context("failed to read line")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: PerfSchedScriptRecord =
+            serde_json::from_str(&line).context("failed to parse perf.sched.jsonl record")?;
+        let Some(time_ns) = record
+            .sample_time_ns()
+            .or_else(|| sched_time_to_ns(record.time))
+        else {
+            continue;
+        };
+
+        if first_record {
+            tracker.sink.buckets.trace_start_ns = time_ns;
+            first_record = false;
+        }
+        stats.observe(&record, time_ns);
+        wakeups.observe(&record, time_ns);
+        tracker.process_record(record);
+    }
+
+    let (trace_end_ns, cpu_count) = stats
+        .finish()
+        .with_context(|| format!("invalid sched trace {}", jsonl.display()))?;
+    let trace_start_ns = stats.trace_start_ns();
+    let sink = tracker.finish(trace_end_ns);
+    let (windows, _) = sink.buckets.finalize(trace_end_ns, cpu_count, window_ms)?;
+
+    let mut summary = ProfileSummary {
+        path: path.to_path_buf(),
+        cpu_count,
+        duration_ms: (trace_end_ns - trace_start_ns) / 1_000_000,
+        windows: windows.len(),
+        wakeup_latencies_us: wakeups.latencies_us,
+        ..ProfileSummary::default()
+    };
+
+    let mut push = |name: &str, value: f64| {
+        summary
+            .category_util
+            .entry(name.to_string())
+            .or_default()
+            .push(value);
+    };
+    for window in &windows {
+        push("total", window.total);
+        push("uncategorized", window.uncategorized);
+        let mut irq = 0.0;
+        for (name, value) in &window.categories {
+            push(name, *value);
+            if DEFAULT_SYSTEM_CATEGORY_NAMES.contains(&name.as_str()) {
+                irq += value;
+            }
+        }
+        push(IRQ_CATEGORY, irq);
+    }
+
+    let capacities_ns: Vec<f64> = (0..windows.len() as u64)
+        .map(|idx| {
+            let window_start_ns = trace_start_ns + idx * window_ns;
+            let width_ns = (window_start_ns + window_ns).min(trace_end_ns) - window_start_ns;
+            (width_ns * cpu_count as u64) as f64
+        })
+        .collect();
+    summary.comm_util = sink
+        .comm_busy_ns
+        .into_iter()
+        .map(|(comm, busy_ns)| {
+            let util = capacities_ns
+                .iter()
+                .enumerate()
+                .map(|(idx, capacity_ns)| {
+                    busy_ns.get(idx).copied().unwrap_or(0) as f64 * 100.0 / capacity_ns
+                })
+                .collect();
+            (comm, util)
+        })
+        .collect();
+
+    Ok(summary)
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+/// Welch's t statistic of `candidate` against `baseline`. None when there
+/// are too few samples or no variance to compare against.
+fn welch_t(baseline: &[f64], candidate: &[f64]) -> Option<f64> {
+    if baseline.len() < 2 || candidate.len() < 2 {
+        return None;
+    }
+    let stderr = (variance(baseline) / baseline.len() as f64
+        + variance(candidate) / candidate.len() as f64)
+        .sqrt();
+    if stderr == 0.0 {
+        return None;
+    }
+    Some((mean(candidate) - mean(baseline)) / stderr)
+}
+
+/// Two-sided significance marker for a t statistic, normal approximation.
+fn significance(t: Option<f64>) -> &'static str {
+    match t.map(f64::abs) {
+        Some(t) if t >= 3.291 => "***",
+        Some(t) if t >= 2.576 => "**",
+        Some(t) if t >= 1.960 => "*",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct MetricDiff {
+    name: String,
+    baseline: f64,
+    candidate: f64,
+    delta: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    t: Option<f64>,
+    significance: &'static str,
+}
+
+impl MetricDiff {
+    fn new(name: &str, baseline: f64, candidate: f64, t: Option<f64>) -> Self {
+        let delta = candidate - baseline;
+        Self {
+            name: name.to_string(),
+            baseline,
+            candidate,
+            delta,
+            delta_pct: (baseline != 0.0).then(|| delta * 100.0 / baseline),
+            t,
+            significance: significance(t),
+        }
+    }
+
+    fn from_samples(name: &str, baseline: &[f64], candidate: &[f64]) -> Self {
+        Self::new(
+            name,
+            mean(baseline),
+            mean(candidate),
+            welch_t(baseline, candidate),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ProfileInfo {
+    path: PathBuf,
+    cpu_count: usize,
+    duration_ms: u64,
+    windows: usize,
+    wakeups: usize,
+}
+
+impl From<&ProfileSummary> for ProfileInfo {
+    fn from(summary: &ProfileSummary) -> Self {
+        Self {
+            path: summary.path.clone(),
+            cpu_count: summary.cpu_count,
+            duration_ms: summary.duration_ms,
+            windows: summary.windows,
+            wakeups: summary.wakeup_latencies_us.len(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CompareReport {
+    baseline: ProfileInfo,
+    candidate: ProfileInfo,
+    /// Busy utilization (%) per category
+    categories: Vec<MetricDiff>,
+    /// Wakeup-to-run latency (us)
+    wakeup_latency_us: Vec<MetricDiff>,
+    /// Busy utilization (%) per comm
+    comms: Vec<MetricDiff>,
+}
+
+fn compare_profiles(
+    baseline: &ProfileSummary,
+    candidate: &ProfileSummary,
+    top_comms: usize,
+) -> CompareReport {
+    let mut category_names: Vec<&String> = baseline
+        .category_util
+        .keys()
+        .chain(candidate.category_util.keys())
+        .collect();
+    category_names.sort();
+    category_names.dedup();
+    // Lead with the aggregate rows, the remaining categories in name order.
+    let leading = ["total", IRQ_CATEGORY, "uncategorized"];
+    category_names.sort_by_key(|name| {
+        leading
+            .iter()
+            .position(|lead| *lead == name.as_str())
+            .unwrap_or(leading.len())
+    });
+
+    // A comm or category missing from a profile was idle in all of its windows.
+    let (idle_a, idle_b) = (vec![0.0; baseline.windows], vec![0.0; candidate.windows]);
+    let categories = category_names
+        .into_iter()
+        .map(|name| {
+            MetricDiff::from_samples(
+                name,
+                baseline.category_util.get(name).unwrap_or(&idle_a),
+                candidate.category_util.get(name).unwrap_or(&idle_b),
+            )
+        })
+        .collect();
+
+    let (lat_a, lat_b) = (
+        &baseline.wakeup_latencies_us,
+        &candidate.wakeup_latencies_us,
+    );
+    let mut wakeup_latency_us = vec![MetricDiff::from_samples("mean", lat_a, lat_b)];
+    for pct in [50.0, 90.0, 99.0] {
+        wakeup_latency_us.push(MetricDiff::new(
+            &format!("p{pct}"),
+            percentile(lat_a, pct),
+            percentile(lat_b, pct),
+            None,
+        ));
+    }
+
+    let mut comms: Vec<MetricDiff> = {
+        let mut names: Vec<&String> = baseline
+            .comm_util
+            .keys()
+            .chain(candidate.comm_util.keys())
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| {
+                MetricDiff::from_samples(
+                    name,
+                    baseline.comm_util.get(name).unwrap_or(&idle_a),
+                    candidate.comm_util.get(name).unwrap_or(&idle_b),
+                )
+            })
+            .collect()
+    };
+    comms.sort_by(|a, b| {
+        b.baseline
+            .max(b.candidate)
+            .total_cmp(&a.baseline.max(a.candidate))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    comms.truncate(top_comms);
+
+    CompareReport {
+        baseline: baseline.into(),
+        candidate: candidate.into(),
+        categories,
+        wakeup_latency_us,
+        comms,
+    }
+}
+
+fn print_section(title: &str, unit: &str, rows: &[MetricDiff]) {
+    let width = rows
+        .iter()
+        .map(|row| row.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(title.len());
+    println!(
+        "\n{:<width$}  {:>12}  {:>12}  {:>12}  {:>9}",
+        title, "baseline", "candidate", "delta", "delta%"
+    );
+    for row in rows {
+        let delta_pct = row
+            .delta_pct
+            .map_or_else(|| "-".to_string(), |pct| format!("{pct:+.1}%"));
+        println!(
+            "{:<width$}  {:>12}  {:>12}  {:>12}  {:>9} {}",
+            row.name,
+            format!("{:.2}{unit}", row.baseline),
+            format!("{:.2}{unit}", row.candidate),
+            format!("{:+.2}{unit}", row.delta),
+            delta_pct,
+            row.significance,
+        );
+    }
+}
+
+fn print_report(report: &CompareReport) {
+    for (label, info) in [
+        ("baseline", &report.baseline),
+        ("candidate", &report.candidate),
+    ] {
+        println!(
+            "{label}: {} ({} cpus, {}ms, {} windows, {} wakeups)",
+            info.path.display(),
+            info.cpu_count,
+            info.duration_ms,
+            info.windows,
+            info.wakeups
+        );
+    }
+    print_section("category", "%", &report.categories);
+    print_section("wakeup latency", "us", &report.wakeup_latency_us);
+    print_section("comm", "%", &report.comms);
+    println!("\n* p<0.05  ** p<0.01  *** p<0.001 (Welch's t-test)");
+}
+
+pub fn cmd_compare(opts: CompareOpts) -> Result<()> {
+    if opts.window_ms == 0 {
+        anyhow::bail!("--window-ms must be greater than zero");
+    }
+
+    let baseline = load_profile(&opts.baseline, &opts.categories, opts.window_ms)?;
+    let candidate = load_profile(&opts.candidate, &opts.categories, opts.window_ms)?;
+    let report = compare_profiles(&baseline, &candidate, opts.top_comms);
+
+    if opts.json {
+        let json =
+            serde_json::to_string_pretty(&report).context("failed to serialize comparison")?;
+        println!("{}", json);
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn secs(time_us: u64) -> String {
+        format!("{}.{:06}", time_us / 1_000_000, time_us % 1_000_000)
+    }
+
+    fn switch(time_us: u64, prev: (&str, i32), next: (&str, i32)) -> String {
+        format!(
+            r#"{{"comm":"{pc}","pid":{pp},"tid":{pp},"cpu":0,"time":{time},"event":"sched:sched_switch","trace":"{pc}:{pp} [120] R ==> {nc}:{np} [120]","fields":{{"prev_comm":"{pc}","prev_pid":{pp},"prev_prio":120,"prev_state":"R","next_comm":"{nc}","next_pid":{np},"next_prio":120}},"hint":0}}"#,
+            time = secs(time_us),
+            pc = prev.0,
+            pp = prev.1,
+            nc = next.0,
+            np = next.1,
+        )
+    }
+
+    fn wakeup(time_us: u64, comm: &str, pid: i32) -> String {
+        format!(
+            r#"{{"comm":"swapper/0","pid":0,"tid":0,"cpu":0,"time":{time},"event":"sched:sched_wakeup","trace":"comm={comm} pid={pid} prio=120 target_cpu=000","fields":{{"comm":"{comm}","pid":{pid},"prio":120,"target_cpu":0}},"hint":0}}"#,
+            time = secs(time_us),
+        )
+    }
+
+    /// `busy_ms` of worker-1 out of every 10ms window, woken up `latency_ms`
+    /// before being switched in.
+    fn write_profile(tempdir: &TempDir, name: &str, busy_ms: &[u64], latency_ms: u64) -> PathBuf {
+        let dir = tempdir.path().join(name);
+        fs::create_dir_all(&dir).expect("failed to create profile dir");
+
+        let mut lines = Vec::new();
+        for (idx, busy) in busy_ms.iter().enumerate() {
+            let start_us = idx as u64 * 10_000;
+            lines.push(wakeup(start_us, "worker-1", 10));
+            let run_us = start_us + latency_ms * 1000;
+            lines.push(switch(run_us, ("swapper/0", 0), ("worker-1", 10)));
+            lines.push(switch(
+                run_us + busy * 1000,
+                ("worker-1", 10),
+                ("swapper/0", 0),
+            ));
+        }
+        lines.push(switch(
+            busy_ms.len() as u64 * 10_000,
+            ("swapper/0", 0),
+            ("swapper/0", 0),
+        ));
+        fs::write(dir.join(PERF_SCHED_JSONL_FILE), lines.join("\n") + "\n")
+            .expect("failed to write perf.sched.jsonl");
+        dir
+    }
+
+    #[test]
+    fn load_profile_collects_windows_comms_and_wakeups() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let dir = write_profile(&tempdir, "a", &[2, 4, 6], 1);
+        let summary = load_profile(&dir, "", 10)?;
+
+        assert_eq!(summary.cpu_count, 1);
+        assert_eq!(summary.windows, 3);
+        let total = &summary.category_util["total"];
+        assert_eq!(total.len(), 3);
+        assert!((total[1] - 40.0).abs() < 1e-3);
+        assert_eq!(summary.category_util[IRQ_CATEGORY], vec![0.0; 3]);
+        // worker-1 is normalized to worker-
+        let worker = &summary.comm_util["worker-"];
+        assert!((worker[2] - 60.0).abs() < 1e-3);
+        assert_eq!(summary.wakeup_latencies_us.len(), 3);
+        assert!((summary.wakeup_latencies_us[0] - 1000.0).abs() < 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn compare_marks_significant_differences() -> Result<()> {
+        let tempdir = TempDir::new()?;
+        let a = write_profile(&tempdir, "a", &[2, 3, 2, 3, 2, 3, 2, 3], 1);
+        let b = write_profile(&tempdir, "b", &[6, 7, 6, 7, 6, 7, 6, 7], 1);
+        let report = compare_profiles(&load_profile(&a, "", 10)?, &load_profile(&b, "", 10)?, 5);
+
+        let total = &report.categories[0];
+        assert_eq!(total.name, "total");
+        assert!((total.delta - 40.0).abs() < 1e-3);
+        assert_eq!(total.significance, "***");
+        assert_eq!(report.categories[1].name, IRQ_CATEGORY);
+        assert_eq!(report.categories[1].significance, "");
+        assert_eq!(report.comms[0].name, "worker-");
+        assert_eq!(report.comms[0].significance, "***");
+        assert_eq!(report.wakeup_latency_us[0].name, "mean");
+        assert!(report.wakeup_latency_us[0].delta.abs() < 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn compare_zero_fills_missing_comms() {
+        let summary = |comm: &str, util: Vec<f64>| ProfileSummary {
+            windows: util.len(),
+            category_util: BTreeMap::from([("total".to_string(), util.clone())]),
+            comm_util: HashMap::from([(comm.to_string(), util)]),
+            ..ProfileSummary::default()
+        };
+        let a = summary("old-", vec![5.0, 6.0, 5.0, 6.0]);
+        let b = summary("new-", vec![20.0, 22.0, 20.0, 22.0, 20.0]);
+        let report = compare_profiles(&a, &b, 5);
+
+        let new = report.comms.iter().find(|c| c.name == "new-").unwrap();
+        assert_eq!(new.baseline, 0.0);
+        assert_eq!(new.significance, "***");
+        let old = report.comms.iter().find(|c| c.name == "old-").unwrap();
+        assert_eq!(old.candidate, 0.0);
+        assert_eq!(old.significance, "***");
+    }
+
+    #[test]
+    fn significance_thresholds() {
+        assert_eq!(significance(None), "");
+        assert_eq!(significance(Some(1.0)), "");
+        assert_eq!(significance(Some(-2.0)), "*");
+        assert_eq!(significance(Some(3.0)), "**");
+        assert_eq!(significance(Some(10.0)), "***");
+        assert_eq!(welch_t(&[1.0, 1.0], &[2.0, 2.0]), None);
+    }
+}
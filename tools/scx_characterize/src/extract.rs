@@ -3,6 +3,7 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use crate::layered::cmd_extract_layered;
 use crate::process::PerfMemRecord;
 use crate::sched_util::cmd_extract_sched_util;
 use anyhow::{Context as _, Result};
@@ -12,10 +13,12 @@ use serde::Serialize;
 use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const DEFAULT_WORKLOAD_CGROUP_REGEX: &str = "workload.slice";
 const DEFAULT_WORKLOAD_ALLOTMENT_CGROUP_REGEX: &str = r"workload-tw-[^/]+\.allotment\.slice";
+/// Share of a group's samples a comm (or hint) needs to get its own subcell.
+pub(crate) const CLUSTER_THRESHOLD_PCT: f64 = 5.0;
 
 #[derive(Debug, Parser)]
 pub struct ExtractMemOpts {
@@ -52,6 +55,8 @@ pub enum ExtractCommand {
     Mem(ExtractMemOpts),
     /// Extract derived metrics from perf.sched.jsonl
     Sched(ExtractSchedOpts),
+    /// Extract scx_layered config from perf.mem.jsonl and perf.sched.jsonl
+    Layered(ExtractLayeredOpts),
 }
 
 #[derive(Debug, Parser)]
@@ -85,6 +90,48 @@ pub struct ExtractSchedUtilOpts {
     pub verbose: bool,
 }
 
+#[derive(Debug, Parser)]
+#[command(
+    after_help = "Layers come from the same cgroup groups and comm clusters as `extract mem`. Each layer's busy CPUs are measured per window from perf.sched.jsonl:\n  Open      average load below --open-threshold-pct of the machine\n  Grouped   bursty, p99 load at least --burst-ratio times the p50 load\n  Confined  everything else\n\nutil_range is (p50, p90) of the load relative to its p99, i.e. how busy the layer's CPUs would be when sized for the p99 load, clamped to [0.1, 0.95]."
+)]
+pub struct ExtractLayeredOpts {
+    /// Path to perf.mem.jsonl file
+    #[clap(short = 'm', long)]
+    pub mem_file: PathBuf,
+
+    /// Path to perf.sched.jsonl file
+    #[clap(short = 's', long)]
+    pub sched_file: PathBuf,
+
+    /// Regex pattern for workload cgroup
+    #[clap(long, default_value = DEFAULT_WORKLOAD_CGROUP_REGEX)]
+    pub workload_cgroup_regex: String,
+
+    /// Regex pattern for workload allotment cgroups
+    #[clap(long, default_value = DEFAULT_WORKLOAD_ALLOTMENT_CGROUP_REGEX)]
+    pub workload_allotment_cgroup_regex: String,
+
+    /// Give each significant hint value of a comm its own HintEquals layer
+    #[clap(long)]
+    pub use_hints: bool,
+
+    /// Utilization window size in milliseconds
+    #[clap(long, default_value = "100")]
+    pub window_ms: u64,
+
+    /// Layers using less than this percentage of the machine's CPUs on average become Open
+    #[clap(long, default_value = "5.0")]
+    pub open_threshold_pct: f64,
+
+    /// Layers whose p99 load is at least this many times their p50 load become Grouped
+    #[clap(long, default_value = "2.0")]
+    pub burst_ratio: f64,
+
+    /// Print per-layer utilization summary to stderr
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
 fn classify_cgroup<'a>(cgroup: &'a str, workload_cgroup: &'a str, allotment_re: &Regex) -> &'a str {
     if !cgroup.contains(workload_cgroup) {
         return "rest";
@@ -98,7 +145,7 @@ fn classify_cgroup<'a>(cgroup: &'a str, workload_cgroup: &'a str, allotment_re:
 }
 
 /// Samples belonging to a group, in time order
-struct GroupData {
+pub(crate) struct GroupData {
     samples: Vec<PerfMemRecord>,
 }
 
@@ -175,16 +222,16 @@ impl GroupData {
 }
 
 /// Result of clustering analysis for a group
-struct ClusterResult {
+pub(crate) struct ClusterResult {
     /// Comms that exceed the significance threshold, optionally with hint splits
-    significant_comms: Vec<CommCluster>,
+    pub(crate) significant_comms: Vec<CommCluster>,
 }
 
 /// Clustering result for a single comm
-struct CommCluster {
-    name: String,
+pub(crate) struct CommCluster {
+    pub(crate) name: String,
     match_comms: Vec<String>,
-    significant_hints: Vec<u64>,
+    pub(crate) significant_hints: Vec<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -204,8 +251,8 @@ struct GroupedCommSamples<'a> {
 }
 
 /// Group type for clustering decisions
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum GroupType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum GroupType {
     Allotment,
     Workload,
     Rest,
@@ -213,7 +260,7 @@ enum GroupType {
 
 /// Compute clustering for samples. Returns empty result for group types
 /// where clustering is not yet implemented.
-fn compute_clusters(
+pub(crate) fn compute_clusters(
     group_type: GroupType,
     samples: &[&PerfMemRecord],
     threshold_pct: f64,
@@ -278,7 +325,7 @@ fn group_samples_by_normalized_comm<'a>(
     grouped
 }
 
-fn normalize_comm_for_cluster(comm: &str) -> String {
+pub(crate) fn normalize_comm_for_cluster(comm: &str) -> String {
     let normalized = comm.trim_end_matches(|ch: char| ch.is_ascii_digit());
     if normalized.is_empty() {
         comm.to_string()
@@ -383,7 +430,7 @@ fn build_comm_match_clauses(comm: &CommCluster, hint: Option<u64>) -> Vec<Vec<Ce
         .collect()
 }
 
-fn emitted_comm_patterns(comm: &CommCluster) -> Vec<String> {
+pub(crate) fn emitted_comm_patterns(comm: &CommCluster) -> Vec<String> {
     if comm.match_comms.len() > 1
         && !comm.name.is_empty()
         && comm
@@ -458,43 +505,74 @@ struct CellConfig {
     specs: Vec<CellSpec>,
 }
 
-pub fn cmd_extract_mem(opts: ExtractMemOpts) -> Result<()> {
-    let file = File::open(&opts.file).context("failed to open perf.mem.jsonl")?;
-    let reader = BufReader::new(file);
+/// Mem samples of a recording split by cgroup group, see classify_cgroup().
+pub(crate) struct MemGroups {
+    pub(crate) groups: HashMap<String, GroupData>,
+    pub(crate) group_names: Vec<String>,
+    pub(crate) global_total: u64,
+}
 
-    let allotment_re =
-        Regex::new(&opts.workload_allotment_cgroup_regex).context("invalid allotment regex")?;
-    let workload_cgroup = &opts.workload_cgroup_regex;
+impl MemGroups {
+    pub(crate) fn load(
+        path: &Path,
+        workload_cgroup: &str,
+        workload_allotment_cgroup_regex: &str,
+    ) -> Result<Self> {
+        let file = File::open(path).context("failed to open perf.mem.jsonl")?;
+        let reader = BufReader::new(file);
 
-    let mut groups: HashMap<String, GroupData> = HashMap::new();
-    let mut global_total: u64 = 0;
+        let allotment_re =
+            Regex::new(workload_allotment_cgroup_regex).context("invalid allotment regex")?;
 
-    for line in reader.lines() {
-        let line = line.context("failed to read line")?;
-        let record: PerfMemRecord =
-            serde_json::from_str(&line).context("failed to parse record")?;
+        let mut groups: HashMap<String, GroupData> = HashMap::new();
+        let mut global_total: u64 = 0;
 
-        let group = classify_cgroup(&record.cgroup, workload_cgroup, &allotment_re);
-        groups
-            .entry(group.to_string())
-            .or_insert_with(GroupData::new)
-            .push(record);
-        global_total += 1;
-    }
-
-    let mut group_names: Vec<_> = groups.keys().cloned().collect();
-    group_names.sort_by(|a, b| {
-        let order = |s: &str| -> u8 {
-            if s == "rest" {
-                2
-            } else if s == workload_cgroup {
-                1
-            } else {
-                0
-            }
-        };
-        order(a).cmp(&order(b)).then_with(|| a.cmp(b))
-    });
+        for line in reader.lines() {
+            let line = line.context("failed to read line")?;
+            let record: PerfMemRecord =
+                serde_json::from_str(&line).context("failed to parse record")?;
+
+            let group = classify_cgroup(&record.cgroup, workload_cgroup, &allotment_re);
+            groups
+                .entry(group.to_string())
+                .or_insert_with(GroupData::new)
+                .push(record);
+            global_total += 1;
+        }
+
+        let mut group_names: Vec<_> = groups.keys().cloned().collect();
+        group_names.sort_by(|a, b| {
+            let order = |s: &str| -> u8 {
+                if s == "rest" {
+                    2
+                } else if s == workload_cgroup {
+                    1
+                } else {
+                    0
+                }
+            };
+            order(a).cmp(&order(b)).then_with(|| a.cmp(b))
+        });
+
+        Ok(Self {
+            groups,
+            group_names,
+            global_total,
+        })
+    }
+}
+
+pub fn cmd_extract_mem(opts: ExtractMemOpts) -> Result<()> {
+    let workload_cgroup = &opts.workload_cgroup_regex;
+    let MemGroups {
+        groups,
+        group_names,
+        global_total,
+    } = MemGroups::load(
+        &opts.file,
+        workload_cgroup,
+        &opts.workload_allotment_cgroup_regex,
+    )?;
 
     if opts.verbose > 0 {
         eprintln!("Total samples: {}", global_total);
@@ -522,6 +600,7 @@ pub fn cmd_extract(opts: ExtractOpts) -> Result<()> {
     match opts.command {
         ExtractCommand::Mem(opts) => cmd_extract_mem(opts),
         ExtractCommand::Sched(opts) => cmd_extract_sched(opts),
+        ExtractCommand::Layered(opts) => cmd_extract_layered(opts),
     }
 }
 
@@ -531,21 +610,20 @@ pub fn cmd_extract_sched(opts: ExtractSchedOpts) -> Result<()> {
     }
 }
 
-fn generate_config(
-    groups: &HashMap<String, GroupData>,
+/// Samples of each non-empty group type along with the name of the cell
+/// built for it, in cell order.
+pub(crate) fn samples_by_group_type<'a>(
+    groups: &'a HashMap<String, GroupData>,
     group_names: &[String],
-    workload_cgroup: &str,
-    allotment_regex: &str,
-    use_hints: bool,
-) -> CellConfig {
-    let mut specs = Vec::new();
-
+    workload_cgroup: &'a str,
+) -> Vec<(GroupType, &'a str, Vec<&'a PerfMemRecord>)> {
     let group_types = [
         (GroupType::Allotment, "allotment"),
         (GroupType::Workload, workload_cgroup),
         (GroupType::Rest, "rest"),
     ];
 
+    let mut out = Vec::new();
     for (group_type, name) in group_types {
         let samples: Vec<&PerfMemRecord> = match group_type {
             GroupType::Allotment => group_names
@@ -564,11 +642,24 @@ fn generate_config(
                 .unwrap_or_default(),
         };
 
-        if samples.is_empty() {
-            continue;
+        if !samples.is_empty() {
+            out.push((group_type, name, samples));
         }
+    }
+    out
+}
+
+fn generate_config(
+    groups: &HashMap<String, GroupData>,
+    group_names: &[String],
+    workload_cgroup: &str,
+    allotment_regex: &str,
+    use_hints: bool,
+) -> CellConfig {
+    let mut specs = Vec::new();
 
-        let clusters = compute_clusters(group_type, &samples, 5.0, use_hints);
+    for (group_type, name, samples) in samples_by_group_type(groups, group_names, workload_cgroup) {
+        let clusters = compute_clusters(group_type, &samples, CLUSTER_THRESHOLD_PCT, use_hints);
         let subcells = build_subcells_from_clusters(&clusters);
 
         let matches = match group_type {
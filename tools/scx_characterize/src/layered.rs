@@ -0,0 +1,790 @@
+// Copyright (c) 2026 Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::extract::{
+    compute_clusters, emitted_comm_patterns, samples_by_group_type, ExtractLayeredOpts, GroupType,
+    MemGroups, CLUSTER_THRESHOLD_PCT,
+};
+use crate::process::{PerfMemRecord, PerfSchedScriptRecord};
+use crate::sched_util::{
+    sched_time_to_ns, window_overlaps, BusyInterval, BusyIntervalSink, SchedBusyTracker,
+    TraceStats, DEFAULT_SYSTEM_CATEGORY_NAMES,
+};
+use crate::util::percentile;
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Name of the trailing layer which takes everything not matched earlier.
+const CATCH_ALL_LAYER: &str = "rest";
+
+/// scx_layered's limit on the number of layers, including the catch-all.
+const MAX_LAYERS: usize = 16;
+
+/// scx_layered's limit on OR blocks per layer.
+const MAX_LAYER_MATCH_ORS: usize = 32;
+
+/// Largest value scx_layered accepts for HintEquals.
+const MAX_HINT: u64 = 1024;
+
+/// Subset of scx_layered's LayerMatch emitted by this tool.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+enum LayerMatch {
+    CgroupContains(String),
+    CgroupRegex(String),
+    CommPrefix(String),
+    HintEquals(u64),
+}
+
+/// Subset of scx_layered's LayerKind. All other fields are left to their
+/// scx_layered defaults.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+enum LayerKind {
+    Confined { util_range: (f64, f64) },
+    Grouped { util_range: (f64, f64) },
+    Open {},
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+struct LayerSpec {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    matches: Vec<Vec<LayerMatch>>,
+    kind: LayerKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+struct LayerConfig {
+    specs: Vec<LayerSpec>,
+}
+
+/// A layer to be emitted along with the conditions a task has to meet to
+/// land in it. Mirrors the generated matches so busy time can be attributed
+/// the same way scx_layered would.
+#[derive(Debug)]
+struct LayerCandidate {
+    name: String,
+    /// Group whose tasks may match, None for hint and catch-all layers
+    group: Option<String>,
+    cgroup_match: Option<LayerMatch>,
+    comm_patterns: Vec<String>,
+    hint: Option<u64>,
+    /// Memory samples the layer would take, used to pick the layers to
+    /// drop when there are too many
+    nr_samples: u64,
+}
+
+impl LayerCandidate {
+    fn matches(&self) -> Vec<Vec<LayerMatch>> {
+        // scx_layered only accepts HintEquals on its own in an AND block.
+        if let Some(hint) = self.hint {
+            return vec![vec![LayerMatch::HintEquals(hint)]];
+        }
+
+        if self.comm_patterns.is_empty() {
+            return vec![self.cgroup_match.iter().cloned().collect()];
+        }
+
+        self.comm_patterns
+            .iter()
+            .map(|pattern| {
+                let mut clause: Vec<_> = self.cgroup_match.iter().cloned().collect();
+                clause.push(LayerMatch::CommPrefix(pattern.clone()));
+                clause
+            })
+            .collect()
+    }
+
+    fn is_catch_all(&self) -> bool {
+        self.group.is_none() && self.hint.is_none()
+    }
+
+    fn accepts(&self, group: Option<&str>, comm: &str, hint: u64) -> bool {
+        if let Some(own_hint) = self.hint {
+            return own_hint == hint;
+        }
+        if let Some(own_group) = &self.group {
+            if group != Some(own_group.as_str()) {
+                return false;
+            }
+        }
+        self.comm_patterns.is_empty()
+            || self
+                .comm_patterns
+                .iter()
+                .any(|pattern| comm.starts_with(pattern.as_str()))
+    }
+}
+
+/// Layers in match order and the group each comm was mostly sampled in.
+/// Sched records carry no cgroup, so the latter stands in for it when
+/// attributing busy time.
+#[derive(Debug)]
+struct LayerPlan {
+    candidates: Vec<LayerCandidate>,
+    comm_groups: HashMap<String, String>,
+    /// Layers dropped to stay within MAX_LAYERS
+    folded: Vec<String>,
+}
+
+impl LayerPlan {
+    fn layer_index(&self, comm: &str, hint: u64) -> usize {
+        let group = self.comm_groups.get(comm).map(String::as_str);
+        self.candidates
+            .iter()
+            .position(|candidate| candidate.accepts(group, comm, hint))
+            .unwrap_or(self.candidates.len() - 1)
+    }
+}
+
+fn count_samples(samples: &[&PerfMemRecord], comm_patterns: &[String], hint: Option<u64>) -> u64 {
+    samples
+        .iter()
+        .filter(|sample| {
+            comm_patterns
+                .iter()
+                .any(|pattern| sample.comm.starts_with(pattern.as_str()))
+                && hint.is_none_or(|hint| sample.hint == hint)
+        })
+        .count() as u64
+}
+
+/// Drop the layers with the fewest samples until the plan fits in
+/// MAX_LAYERS. Their tasks fall through to the following layers, the
+/// catch-all layer at the latest. Returns the names of the dropped layers.
+fn fold_smallest_layers(candidates: &mut Vec<LayerCandidate>) -> Vec<String> {
+    let excess = candidates.len().saturating_sub(MAX_LAYERS);
+    if excess == 0 {
+        return Vec::new();
+    }
+
+    // The catch-all layer is last and always kept. On ties, later layers
+    // are dropped first.
+    let mut order: Vec<usize> = (0..candidates.len() - 1).collect();
+    order.sort_by_key(|&idx| (candidates[idx].nr_samples, std::cmp::Reverse(idx)));
+    let dropped: HashSet<usize> = order[..excess].iter().copied().collect();
+
+    let mut folded = Vec::new();
+    let mut idx = 0;
+    candidates.retain(|candidate| {
+        let keep = !dropped.contains(&idx);
+        if !keep {
+            folded.push(candidate.name.clone());
+        }
+        idx += 1;
+        keep
+    });
+    folded
+}
+
+fn build_layer_plan(
+    group_samples: &[(GroupType, &str, Vec<&PerfMemRecord>)],
+    workload_cgroup: &str,
+    allotment_regex: &str,
+    use_hints: bool,
+) -> Result<LayerPlan> {
+    // A hint maps to a single layer in scx_layered, so each distinct hint
+    // gets one layer ahead of the comm and group layers.
+    let mut hint_samples: HashMap<u64, u64> = HashMap::new();
+    let mut candidates = Vec::new();
+    let mut comm_groups: HashMap<String, (String, u64)> = HashMap::new();
+
+    for (group_type, name, samples) in group_samples {
+        let cgroup_match = match group_type {
+            GroupType::Allotment => Some(LayerMatch::CgroupRegex(allotment_regex.to_string())),
+            GroupType::Workload => Some(LayerMatch::CgroupContains(workload_cgroup.to_string())),
+            GroupType::Rest => None,
+        };
+
+        let clusters = compute_clusters(*group_type, samples, CLUSTER_THRESHOLD_PCT, use_hints);
+        for comm in &clusters.significant_comms {
+            let mut comm_patterns = emitted_comm_patterns(comm);
+            comm_patterns.truncate(MAX_LAYER_MATCH_ORS);
+            if comm.significant_hints.is_empty() {
+                candidates.push(LayerCandidate {
+                    name: format!("{}/{}", name, comm.name),
+                    group: Some(name.to_string()),
+                    cgroup_match: cgroup_match.clone(),
+                    nr_samples: count_samples(samples, &comm_patterns, None),
+                    comm_patterns,
+                    hint: None,
+                });
+                continue;
+            }
+
+            for &hint in &comm.significant_hints {
+                if hint > MAX_HINT {
+                    anyhow::bail!(
+                        "hint {hint} of {}/{} is above scx_layered's limit of {MAX_HINT}",
+                        name,
+                        comm.name
+                    );
+                }
+                *hint_samples.entry(hint).or_insert(0) +=
+                    count_samples(samples, &comm_patterns, Some(hint));
+            }
+        }
+
+        // Without a cgroup to match on, the group layer would be the
+        // catch-all layer.
+        if cgroup_match.is_some() {
+            candidates.push(LayerCandidate {
+                name: name.to_string(),
+                group: Some(name.to_string()),
+                cgroup_match,
+                comm_patterns: Vec::new(),
+                hint: None,
+                nr_samples: samples.len() as u64,
+            });
+        }
+
+        let mut comm_counts: HashMap<&str, u64> = HashMap::new();
+        for sample in samples {
+            *comm_counts.entry(sample.comm.as_str()).or_insert(0) += 1;
+        }
+        for (comm, count) in comm_counts {
+            let entry = comm_groups
+                .entry(comm.to_string())
+                .or_insert_with(|| (name.to_string(), 0));
+            if count > entry.1 {
+                *entry = (name.to_string(), count);
+            }
+        }
+    }
+
+    candidates.push(LayerCandidate {
+        name: CATCH_ALL_LAYER.to_string(),
+        group: None,
+        cgroup_match: None,
+        comm_patterns: Vec::new(),
+        hint: None,
+        nr_samples: 0,
+    });
+
+    let mut hint_candidates: Vec<_> = hint_samples
+        .into_iter()
+        .map(|(hint, nr_samples)| LayerCandidate {
+            name: format!("hint={hint}"),
+            group: None,
+            cgroup_match: None,
+            comm_patterns: Vec::new(),
+            hint: Some(hint),
+            nr_samples,
+        })
+        .collect();
+    hint_candidates.sort_by_key(|candidate| candidate.hint);
+    hint_candidates.extend(candidates);
+    let folded = fold_smallest_layers(&mut hint_candidates);
+
+    Ok(LayerPlan {
+        candidates: hint_candidates,
+        folded,
+        comm_groups: comm_groups
+            .into_iter()
+            .map(|(comm, (group, _))| (comm, group))
+            .collect(),
+    })
+}
+
+/// Accumulates busy time per layer and window.
+#[derive(Debug)]
+struct LayerLoadSink<'a> {
+    plan: &'a LayerPlan,
+    trace_start_ns: u64,
+    window_ns: u64,
+    layer_cache: HashMap<(String, u64), usize>,
+    busy_ns: Vec<Vec<u64>>,
+}
+
+impl<'a> LayerLoadSink<'a> {
+    fn new(plan: &'a LayerPlan, trace_start_ns: u64, window_ns: u64) -> Self {
+        Self {
+            plan,
+            trace_start_ns,
+            window_ns,
+            layer_cache: HashMap::new(),
+            busy_ns: vec![Vec::new(); plan.candidates.len()],
+        }
+    }
+
+    fn layer_loads(&self, trace_end_ns: u64) -> Result<Vec<LayerLoad>> {
+        if trace_end_ns <= self.trace_start_ns {
+            anyhow::bail!("non-positive sched trace duration");
+        }
+
+        let bucket_count = (trace_end_ns - self.trace_start_ns).div_ceil(self.window_ns) as usize;
+        let widths_ns: Vec<u64> = (0..bucket_count)
+            .map(|idx| {
+                let bucket_start_ns = self.trace_start_ns + idx as u64 * self.window_ns;
+                (bucket_start_ns + self.window_ns).min(trace_end_ns) - bucket_start_ns
+            })
+            .collect();
+        let all_busy_ns: u64 = self.busy_ns.iter().flatten().sum();
+
+        Ok(self
+            .busy_ns
+            .iter()
+            .map(|busy_ns| {
+                let cpus: Vec<f64> = widths_ns
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, width_ns)| {
+                        busy_ns.get(idx).copied().unwrap_or(0) as f64 / *width_ns as f64
+                    })
+                    .collect();
+
+                let layer_busy_ns: u64 = busy_ns.iter().sum();
+                LayerLoad {
+                    mean: cpus.iter().sum::<f64>() / cpus.len() as f64,
+                    p50: percentile(&cpus, 50.0),
+                    p90: percentile(&cpus, 90.0),
+                    p99: percentile(&cpus, 99.0),
+                    busy_pct: if all_busy_ns == 0 {
+                        0.0
+                    } else {
+                        layer_busy_ns as f64 * 100.0 / all_busy_ns as f64
+                    },
+                }
+            })
+            .collect())
+    }
+}
+
+impl BusyIntervalSink for LayerLoadSink<'_> {
+    fn on_interval(&mut self, interval: &BusyInterval) {
+        if DEFAULT_SYSTEM_CATEGORY_NAMES.contains(&interval.comm.as_str()) {
+            return;
+        }
+
+        if interval.end_ns <= interval.start_ns.max(self.trace_start_ns) {
+            return;
+        }
+
+        let plan = self.plan;
+        let layer = *self
+            .layer_cache
+            .entry((interval.comm.clone(), interval.hint))
+            .or_insert_with(|| plan.layer_index(&interval.comm, interval.hint));
+
+        let busy_ns = &mut self.busy_ns[layer];
+        for (idx, overlap_ns) in window_overlaps(interval, self.trace_start_ns, self.window_ns) {
+            if busy_ns.len() <= idx {
+                busy_ns.resize(idx + 1, 0);
+            }
+            busy_ns[idx] += overlap_ns;
+        }
+    }
+}
+
+/// Per-window load of a layer in number of busy CPUs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LayerLoad {
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    /// Share of all non-system busy time
+    busy_pct: f64,
+}
+
+impl LayerLoad {
+    fn comment(&self) -> String {
+        format!(
+            "p50 {:.2} / p90 {:.2} / p99 {:.2} CPUs, {:.1}% of busy time",
+            self.p50, self.p90, self.p99, self.busy_pct
+        )
+    }
+}
+
+fn layer_kind(
+    load: &LayerLoad,
+    cpu_count: usize,
+    open_threshold_pct: f64,
+    burst_ratio: f64,
+) -> LayerKind {
+    if load.p99 <= 0.0 || load.mean < cpu_count as f64 * open_threshold_pct / 100.0 {
+        return LayerKind::Open {};
+    }
+
+    let lo = (load.p50 / load.p99).clamp(0.1, 0.85);
+    let hi = (load.p90 / load.p99).clamp(lo + 0.05, 0.95);
+    if load.p99 >= burst_ratio * load.p50 {
+        LayerKind::Grouped {
+            util_range: (lo, hi),
+        }
+    } else {
+        LayerKind::Confined {
+            util_range: (lo, hi),
+        }
+    }
+}
+
+fn generate_config(
+    plan: &LayerPlan,
+    loads: &[LayerLoad],
+    cpu_count: usize,
+    open_threshold_pct: f64,
+    burst_ratio: f64,
+) -> LayerConfig {
+    let specs = plan
+        .candidates
+        .iter()
+        .zip(loads)
+        .map(|(candidate, load)| {
+            let kind = if candidate.is_catch_all() {
+                LayerKind::Open {}
+            } else {
+                layer_kind(load, cpu_count, open_threshold_pct, burst_ratio)
+            };
+            LayerSpec {
+                name: candidate.name.clone(),
+                comment: Some(load.comment()),
+                matches: candidate.matches(),
+                kind,
+            }
+        })
+        .collect();
+
+    LayerConfig { specs }
+}
+
+pub fn cmd_extract_layered(opts: ExtractLayeredOpts) -> Result<()> {
+    if opts.window_ms == 0 {
+        anyhow::bail!("--window-ms must be greater than zero");
+    }
+    if opts.burst_ratio < 1.0 {
+        anyhow::bail!("--burst-ratio must be at least 1.0");
+    }
+
+    let workload_cgroup = &opts.workload_cgroup_regex;
+    let MemGroups {
+        groups,
+        group_names,
+        ..
+    } = MemGroups::load(
+        &opts.mem_file,
+        workload_cgroup,
+        &opts.workload_allotment_cgroup_regex,
+    )?;
+    let group_samples = samples_by_group_type(&groups, &group_names, workload_cgroup);
+    let plan = build_layer_plan(
+        &group_samples,
+        workload_cgroup,
+        &opts.workload_allotment_cgroup_regex,
+        opts.use_hints,
+    )?;
+
+    let file = File::open(&opts.sched_file).context("failed to open perf.sched.jsonl")?;
+    let reader = BufReader::new(file);
+
+    let mut stats = TraceStats::default();
+    let sink = LayerLoadSink::new(&plan, 0, opts.window_ms * 1_000_000);
+    let mut tracker = SchedBusyTracker::new(sink);
+    let mut first_record = true;
+
+    for line in reader.lines() {
+        let line = line.context("failed to read line")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: PerfSchedScriptRecord =
+            serde_json::from_str(&line).context("failed to parse perf.sched.jsonl record")?;
+        let Some(time_ns) = record
+            .sample_time_ns()
+            .or_else(|| sched_time_to_ns(record.time))
+        else {
+            continue;
+        };
+
+        if first_record {
+            tracker.sink.trace_start_ns = time_ns;
+            first_record = false;
+        }
+        stats.observe(&record, time_ns);
+        tracker.process_record(record);
+    }
+
+    let (trace_end_ns, cpu_count) = stats.finish()?;
+    let sink = tracker.finish(trace_end_ns);
+    let loads = sink.layer_loads(trace_end_ns)?;
+    let config = generate_config(
+        &plan,
+        &loads,
+        cpu_count,
+        opts.open_threshold_pct,
+        opts.burst_ratio,
+    );
+
+    if opts.verbose {
+        eprintln!(
+            "layered: {} layers, {} cpus, {}ms windows",
+            config.specs.len(),
+            cpu_count,
+            opts.window_ms
+        );
+        if !plan.folded.is_empty() {
+            eprintln!(
+                "  folded into later layers to stay within {MAX_LAYERS} layers: {}",
+                plan.folded.join(", ")
+            );
+        }
+        for spec in &config.specs {
+            let kind = match spec.kind {
+                LayerKind::Confined { .. } => "Confined",
+                LayerKind::Grouped { .. } => "Grouped",
+                LayerKind::Open {} => "Open",
+            };
+            eprintln!(
+                "  {}: {} ({})",
+                spec.name,
+                kind,
+                spec.comment.as_deref().unwrap_or_default()
+            );
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&config).context("failed to serialize config")?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ALLOTMENT_REGEX: &str = r"workload-tw-[^/]+\.allotment\.slice";
+
+    fn mem_sample(comm: &str, hint: u64) -> PerfMemRecord {
+        serde_json::from_value(json!({
+            "comm": comm,
+            "tid": 1,
+            "pid": 1,
+            "time": "0",
+            "addr": "0",
+            "cgroup": "workload-tw-a.allotment.slice",
+            "ip": "0",
+            "sym": "sym",
+            "dso": "dso",
+            "phys_addr": "0",
+            "data_page_size": 4096,
+            "hint": hint,
+        }))
+        .expect("failed to build PerfMemRecord test sample")
+    }
+
+    fn allotment_plan(samples: &[PerfMemRecord], use_hints: bool) -> LayerPlan {
+        let group_samples = vec![(GroupType::Allotment, "allotment", samples.iter().collect())];
+        build_layer_plan(&group_samples, "workload.slice", ALLOTMENT_REGEX, use_hints)
+            .expect("failed to build layer plan")
+    }
+
+    fn idle_load() -> LayerLoad {
+        LayerLoad {
+            mean: 0.0,
+            p50: 0.0,
+            p90: 0.0,
+            p99: 0.0,
+            busy_pct: 0.0,
+        }
+    }
+
+    /// Checks the subset of scx_layered's verify_layer_specs() rules that
+    /// apply to the matches emitted here.
+    fn verify_layered_specs(specs: &[LayerSpec]) {
+        const MAX_COMM: usize = 16;
+
+        assert!(!specs.is_empty() && specs.len() <= MAX_LAYERS);
+        let (terminal, rest) = specs.split_last().unwrap();
+        assert_eq!(terminal.matches, vec![Vec::<LayerMatch>::new()]);
+
+        let mut hint_layers = HashMap::new();
+        for spec in rest {
+            assert!(!spec.matches.is_empty(), "{} has no matches", spec.name);
+            assert!(spec.matches.len() <= MAX_LAYER_MATCH_ORS);
+            for clause in &spec.matches {
+                for one in clause {
+                    match one {
+                        LayerMatch::CommPrefix(prefix) => assert!(prefix.len() <= MAX_COMM),
+                        LayerMatch::HintEquals(hint) => {
+                            assert!(*hint <= MAX_HINT);
+                            assert_eq!(clause.len(), 1, "{} mixes HintEquals", spec.name);
+                            let layer = hint_layers.entry(*hint).or_insert(spec.name.as_str());
+                            assert_eq!(*layer, spec.name, "hint {hint} in two layers");
+                        }
+                        LayerMatch::CgroupContains(_) | LayerMatch::CgroupRegex(_) => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn interval(start_ns: u64, end_ns: u64, comm: &str, hint: u64) -> BusyInterval {
+        BusyInterval {
+            start_ns,
+            end_ns,
+            comm: comm.to_string(),
+            hint,
+        }
+    }
+
+    #[test]
+    fn plan_orders_comm_layers_before_group_and_catch_all() {
+        let mut samples: Vec<_> = (0..60).map(|_| mem_sample("web", 0)).collect();
+        samples.extend((0..40).map(|_| mem_sample("db", 0)));
+        let plan = allotment_plan(&samples, false);
+
+        let names: Vec<_> = plan.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["allotment/db", "allotment/web", "allotment", "rest"]
+        );
+        assert_eq!(
+            plan.candidates[1].matches(),
+            vec![vec![
+                LayerMatch::CgroupRegex(ALLOTMENT_REGEX.to_string()),
+                LayerMatch::CommPrefix("web".to_string()),
+            ]]
+        );
+        assert_eq!(
+            plan.candidates[2].matches(),
+            vec![vec![LayerMatch::CgroupRegex(ALLOTMENT_REGEX.to_string())]]
+        );
+        assert_eq!(plan.candidates[3].matches(), vec![Vec::<LayerMatch>::new()]);
+
+        let loads = vec![idle_load(); plan.candidates.len()];
+        verify_layered_specs(&generate_config(&plan, &loads, 16, 5.0, 2.0).specs);
+    }
+
+    #[test]
+    fn plan_splits_comm_layers_by_hint() {
+        let mut samples: Vec<_> = (0..50).map(|_| mem_sample("web", 1)).collect();
+        samples.extend((0..50).map(|_| mem_sample("web", 2)));
+        let plan = allotment_plan(&samples, true);
+
+        let names: Vec<_> = plan.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["hint=1", "hint=2", "allotment", "rest"]);
+        assert_eq!(
+            plan.candidates[0].matches(),
+            vec![vec![LayerMatch::HintEquals(1)]]
+        );
+        assert_eq!(plan.layer_index("web", 2), 1);
+        assert_eq!(plan.layer_index("web", 3), 2);
+        assert_eq!(plan.layer_index("unknown", 1), 0);
+        assert_eq!(plan.layer_index("unknown", 3), 3);
+    }
+
+    #[test]
+    fn plan_emits_one_layer_per_hint() {
+        let mut samples: Vec<_> = (0..30).map(|_| mem_sample("web", 2)).collect();
+        samples.extend((0..30).map(|_| mem_sample("db", 2)));
+        samples.extend((0..30).map(|_| mem_sample("db", 1)));
+        samples.extend((0..10).map(|_| mem_sample("cron", 0)));
+        let plan = allotment_plan(&samples, true);
+
+        let names: Vec<_> = plan.candidates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["hint=0", "hint=1", "hint=2", "allotment", "rest"]);
+
+        let loads = vec![idle_load(); plan.candidates.len()];
+        verify_layered_specs(&generate_config(&plan, &loads, 16, 5.0, 2.0).specs);
+    }
+
+    #[test]
+    fn plan_folds_smallest_layers_into_later_ones() {
+        // 17 significant comms with 6, 7 or 8 samples each.
+        let comm = |idx: u64| format!("comm-{}", (b'a' + idx as u8) as char);
+        let samples: Vec<_> = (0..17)
+            .flat_map(|idx| (0..6 + idx % 3).map(move |_| mem_sample(&comm(idx), idx)))
+            .collect();
+
+        for use_hints in [false, true] {
+            let plan = allotment_plan(&samples, use_hints);
+            assert_eq!(plan.candidates.len(), MAX_LAYERS);
+            assert_eq!(plan.candidates[MAX_LAYERS - 2].name, "allotment");
+            assert_eq!(plan.candidates[MAX_LAYERS - 1].name, CATCH_ALL_LAYER);
+
+            // Of the 6 sample layers, the last ones are dropped first.
+            let expected: Vec<String> = [9, 12, 15]
+                .into_iter()
+                .map(|idx| {
+                    if use_hints {
+                        format!("hint={idx}")
+                    } else {
+                        format!("allotment/{}", comm(idx))
+                    }
+                })
+                .collect();
+            assert_eq!(plan.folded, expected);
+            assert_eq!(plan.layer_index(&comm(9), 9), MAX_LAYERS - 2);
+
+            let loads = vec![idle_load(); plan.candidates.len()];
+            verify_layered_specs(&generate_config(&plan, &loads, 16, 5.0, 2.0).specs);
+        }
+    }
+
+    #[test]
+    fn plan_rejects_hints_above_layered_limit() {
+        let samples: Vec<_> = (0..10).map(|_| mem_sample("web", MAX_HINT + 1)).collect();
+        let group_samples = vec![(GroupType::Allotment, "allotment", samples.iter().collect())];
+        assert!(build_layer_plan(&group_samples, "workload.slice", ALLOTMENT_REGEX, true).is_err());
+        assert!(build_layer_plan(&group_samples, "workload.slice", ALLOTMENT_REGEX, false).is_ok());
+    }
+
+    #[test]
+    fn sink_attributes_busy_time_per_layer_and_window() -> Result<()> {
+        let samples: Vec<_> = (0..10).map(|_| mem_sample("web", 0)).collect();
+        let plan = allotment_plan(&samples, false);
+        let mut sink = LayerLoadSink::new(&plan, 0, 10_000_000);
+
+        sink.on_interval(&interval(5_000_000, 15_000_000, "web", 0));
+        sink.on_interval(&interval(0, 10_000_000, "cron", 0));
+        sink.on_interval(&interval(0, 10_000_000, "hardirq", 0));
+
+        assert_eq!(sink.busy_ns[0], vec![5_000_000, 5_000_000]);
+        assert!(sink.busy_ns[1].is_empty());
+        assert_eq!(sink.busy_ns[2], vec![10_000_000]);
+
+        let loads = sink.layer_loads(20_000_000)?;
+        assert_eq!(loads[0].p50, 0.5);
+        assert_eq!(loads[2].p99, 1.0);
+        assert_eq!(loads[2].p50, 0.0);
+        assert_eq!(loads[0].busy_pct, 50.0);
+        Ok(())
+    }
+
+    #[test]
+    fn layer_kind_follows_load_shape() {
+        let load = |mean, p50, p90, p99| LayerLoad {
+            mean,
+            p50,
+            p90,
+            p99,
+            busy_pct: 0.0,
+        };
+
+        assert_eq!(
+            layer_kind(&load(0.1, 0.1, 0.1, 0.1), 16, 5.0, 2.0),
+            LayerKind::Open {}
+        );
+        assert_eq!(
+            layer_kind(&load(4.0, 4.0, 4.5, 5.0), 16, 5.0, 2.0),
+            LayerKind::Confined {
+                util_range: (0.8, 0.9)
+            }
+        );
+        assert_eq!(
+            layer_kind(&load(3.0, 2.0, 8.0, 10.0), 16, 5.0, 2.0),
+            LayerKind::Grouped {
+                util_range: (0.2, 0.8)
+            }
+        );
+    }
+}
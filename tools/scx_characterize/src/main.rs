@@ -7,14 +7,19 @@ use anyhow::{bail, Context as _, Result};
 use clap::{Parser, Subcommand};
 use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 
+mod compare;
 mod extract;
+mod layered;
+mod native;
 mod process;
 mod record;
 mod sched_util;
+mod util;
 
 pub mod bpf_intf;
 pub mod bpf_skel;
 pub use bpf_skel as bpf;
+pub mod sched_trace_skel;
 
 fn create_eventfd() -> Result<OwnedFd> {
     let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
@@ -65,12 +70,14 @@ struct Opts {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    /// Record workload profile using perf
+    /// Record workload profile using perf, or natively with --native
     Record(record::RecordOpts),
     /// Process a recorded profile
     Process(process::ProcessOpts),
     /// Extract data from processed profile
     Extract(extract::ExtractOpts),
+    /// Compare two processed profiles of the same workload
+    Compare(compare::CompareOpts),
 }
 
 fn main() -> Result<()> {
@@ -81,5 +88,6 @@ fn main() -> Result<()> {
         Commands::Record(record_opts) => record::cmd_record(&ctx, record_opts),
         Commands::Process(process_opts) => process::cmd_process(process_opts),
         Commands::Extract(extract_opts) => extract::cmd_extract(extract_opts),
+        Commands::Compare(compare_opts) => compare::cmd_compare(compare_opts),
     }
 }
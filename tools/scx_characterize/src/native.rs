@@ -0,0 +1,872 @@
+// Copyright (c) 2026 Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Recording without the perf binary.
+//!
+//! Sched and irq tracepoints are captured by the programs in
+//! `sched_trace.bpf.c` and mem samples by perf_event_open() ring buffers.
+//! Both are written straight to perf.sched.jsonl and perf.mem.jsonl in the
+//! same shape `process` derives from perf script output, so `process` only
+//! has to annotate hints and `extract` works unchanged.
+
+use crate::bpf_intf;
+use crate::process::{PerfMemRecord, PerfSchedScriptRecord};
+use crate::record::{PERF_MEM_JSONL_FILE, PERF_SCHED_JSONL_FILE};
+use crate::sched_trace_skel::{SchedTraceSkel, SchedTraceSkelBuilder};
+use anyhow::{bail, Context as _, Result};
+use libbpf_rs::skel::{OpenSkel, Skel, SkelBuilder};
+use libbpf_rs::{OpenObject, RingBufferBuilder};
+use scx_utils::perf;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::raw::c_char;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{fence, Ordering};
+use std::time::{Duration, Instant};
+
+/// Size of the sched trace ring buffer. Sched events are far more frequent
+/// than hint updates, match the per-CPU buffers of `perf record -m 8M`.
+const SCHED_RING_SZ_MB: u32 = 64;
+/// Data pages of each per-CPU mem sample ring buffer (8M with 4K pages).
+const MEM_RING_PAGES: usize = 2048;
+/// Same default frequency `perf mem record` samples at.
+const MEM_SAMPLE_FREQ: u64 = 4000;
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+/// PMUs providing the mem-loads and mem-stores aliases, hybrid parts expose
+/// them on the big core PMU.
+const MEM_PMUS: &[&str] = &["cpu", "cpu_core"];
+
+const SOFTIRQ_NAMES: &[&str] = &[
+    "HI", "TIMER", "NET_TX", "NET_RX", "BLOCK", "IRQ_POLL", "TASKLET", "SCHED", "HRTIMER", "RCU",
+];
+
+/// Task state letters in TASK_REPORT bit order, as sched_switch prints them.
+const TASK_STATE_CHARS: &[&str] = &["S", "D", "T", "t", "X", "Z", "P", "I"];
+const TASK_REPORT_MAX: i64 = 1 << TASK_STATE_CHARS.len();
+
+fn c_str(buf: &[c_char]) -> String {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn tracepoint_exists(category: &str, name: &str) -> bool {
+    ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"]
+        .iter()
+        .any(|root| {
+            Path::new(root)
+                .join("events")
+                .join(category)
+                .join(name)
+                .exists()
+        })
+}
+
+fn prev_state_str(state: i64) -> String {
+    let bits = state & (TASK_REPORT_MAX - 1);
+    let mut out = if bits == 0 {
+        "R".to_string()
+    } else {
+        TASK_STATE_CHARS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| bits & (1 << bit) != 0)
+            .map(|(_, c)| *c)
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    if state & TASK_REPORT_MAX != 0 {
+        out.push('+');
+    }
+    out
+}
+
+/// Renders a tracepoint hit as its perf event name and `perf script` trace
+/// text.
+fn render_trace_event(ev: &bpf_intf::trace_event) -> Option<(&'static str, String)> {
+    let args = &ev.args;
+    let rendered = match ev.kind {
+        bpf_intf::trace_event_kind_TRACE_SCHED_SWITCH => (
+            "sched:sched_switch",
+            format!(
+                "{}:{} [{}] {} ==> {}:{} [{}]",
+                c_str(&ev.arg_comm[0]),
+                args[0],
+                args[1],
+                prev_state_str(args[2]),
+                c_str(&ev.arg_comm[1]),
+                args[3],
+                args[4]
+            ),
+        ),
+        bpf_intf::trace_event_kind_TRACE_SCHED_WAKEUP
+        | bpf_intf::trace_event_kind_TRACE_SCHED_WAKEUP_NEW
+        | bpf_intf::trace_event_kind_TRACE_SCHED_WAKING => (
+            match ev.kind {
+                bpf_intf::trace_event_kind_TRACE_SCHED_WAKEUP => "sched:sched_wakeup",
+                bpf_intf::trace_event_kind_TRACE_SCHED_WAKEUP_NEW => "sched:sched_wakeup_new",
+                _ => "sched:sched_waking",
+            },
+            format!(
+                "comm={} pid={} prio={} target_cpu={:03}",
+                c_str(&ev.arg_comm[0]),
+                args[0],
+                args[1],
+                args[2]
+            ),
+        ),
+        bpf_intf::trace_event_kind_TRACE_SCHED_STAT_RUNTIME => (
+            "sched:sched_stat_runtime",
+            format!(
+                "comm={} pid={} runtime={} [ns]",
+                c_str(&ev.arg_comm[0]),
+                args[0],
+                args[1] as u64
+            ),
+        ),
+        bpf_intf::trace_event_kind_TRACE_IRQ_HANDLER_ENTRY => (
+            "irq:irq_handler_entry",
+            format!("irq={} name={}", args[0], c_str(&ev.name)),
+        ),
+        bpf_intf::trace_event_kind_TRACE_IRQ_HANDLER_EXIT => (
+            "irq:irq_handler_exit",
+            format!(
+                "irq={} ret={}",
+                args[0],
+                if args[1] != 0 { "handled" } else { "unhandled" }
+            ),
+        ),
+        bpf_intf::trace_event_kind_TRACE_SOFTIRQ_ENTRY
+        | bpf_intf::trace_event_kind_TRACE_SOFTIRQ_EXIT
+        | bpf_intf::trace_event_kind_TRACE_SOFTIRQ_RAISE => (
+            match ev.kind {
+                bpf_intf::trace_event_kind_TRACE_SOFTIRQ_ENTRY => "irq:softirq_entry",
+                bpf_intf::trace_event_kind_TRACE_SOFTIRQ_EXIT => "irq:softirq_exit",
+                _ => "irq:softirq_raise",
+            },
+            format!(
+                "vec={} [action={}]",
+                args[0],
+                SOFTIRQ_NAMES
+                    .get(args[0] as usize)
+                    .copied()
+                    .unwrap_or("UNKNOWN")
+            ),
+        ),
+        bpf_intf::trace_event_kind_TRACE_LOCAL_TIMER_ENTRY => (
+            "irq_vectors:local_timer_entry",
+            format!("vector={}", args[0]),
+        ),
+        bpf_intf::trace_event_kind_TRACE_LOCAL_TIMER_EXIT => (
+            "irq_vectors:local_timer_exit",
+            format!("vector={}", args[0]),
+        ),
+        bpf_intf::trace_event_kind_TRACE_RESCHEDULE_ENTRY => (
+            "irq_vectors:reschedule_entry",
+            format!("vector={}", args[0]),
+        ),
+        bpf_intf::trace_event_kind_TRACE_RESCHEDULE_EXIT => {
+            ("irq_vectors:reschedule_exit", format!("vector={}", args[0]))
+        }
+        bpf_intf::trace_event_kind_TRACE_NMI_HANDLER => (
+            "nmi:nmi_handler",
+            format!(
+                "{:#x}() delta_ns: {} handled: {}",
+                args[0] as u64, args[1], args[2]
+            ),
+        ),
+        _ => return None,
+    };
+    Some(rendered)
+}
+
+fn sched_record(ev: &bpf_intf::trace_event) -> Option<PerfSchedScriptRecord> {
+    let (event, trace) = render_trace_event(ev)?;
+    Some(PerfSchedScriptRecord::new(
+        c_str(&ev.comm),
+        ev.pid,
+        ev.tid,
+        ev.cpu,
+        ev.timestamp,
+        event,
+        trace,
+    ))
+}
+
+struct SchedTracer<'a> {
+    links: Vec<libbpf_rs::Link>,
+    ringbuf: libbpf_rs::RingBuffer<'a>,
+    records: Rc<RefCell<Vec<PerfSchedScriptRecord>>>,
+    writer: BufWriter<File>,
+    skel: SchedTraceSkel<'a>,
+    _open_object: Box<MaybeUninit<OpenObject>>,
+}
+
+impl SchedTracer<'static> {
+    fn new(output_path: &Path) -> Result<Self> {
+        let open_object = Box::new(MaybeUninit::uninit());
+        let open_object_ptr = Box::into_raw(open_object);
+
+        let open_object_ref: &'static mut MaybeUninit<OpenObject> =
+            unsafe { &mut *open_object_ptr };
+
+        let builder = SchedTraceSkelBuilder::default();
+        let mut open_skel = builder
+            .open(open_object_ref)
+            .context("failed to open BPF skeleton")?;
+
+        let progs = &mut open_skel.progs;
+        // irq_vectors and nmi tracepoints only exist on x86.
+        if !tracepoint_exists("irq_vectors", "local_timer_entry") {
+            progs.trace_local_timer_entry.set_autoload(false);
+            progs.trace_local_timer_exit.set_autoload(false);
+        }
+        if !tracepoint_exists("irq_vectors", "reschedule_entry") {
+            progs.trace_reschedule_entry.set_autoload(false);
+            progs.trace_reschedule_exit.set_autoload(false);
+        }
+        if !tracepoint_exists("nmi", "nmi_handler") {
+            progs.trace_nmi_handler.set_autoload(false);
+        }
+
+        open_skel
+            .maps
+            .trace_ringbuf
+            .set_max_entries(SCHED_RING_SZ_MB * 1024 * 1024)
+            .context("failed to set sched trace ringbuf size")?;
+
+        let skel = open_skel.load().context("failed to load BPF skeleton")?;
+
+        let mut links = Vec::new();
+        for prog in skel.object().progs() {
+            if !prog.autoload() {
+                continue;
+            }
+            let name = prog.name().to_string_lossy().into_owned();
+            links.push(
+                prog.attach()
+                    .with_context(|| format!("failed to attach BPF program {}", name))?,
+            );
+        }
+
+        let records: Rc<RefCell<Vec<PerfSchedScriptRecord>>> = Rc::new(RefCell::new(Vec::new()));
+        let records_clone = records.clone();
+
+        let mut ringbuf_builder = RingBufferBuilder::new();
+        ringbuf_builder
+            .add(&skel.maps.trace_ringbuf, move |data: &[u8]| {
+                if data.len() >= std::mem::size_of::<bpf_intf::trace_event>() {
+                    let ev: bpf_intf::trace_event = unsafe {
+                        std::ptr::read_unaligned(data.as_ptr() as *const bpf_intf::trace_event)
+                    };
+                    if let Some(record) = sched_record(&ev) {
+                        records_clone.borrow_mut().push(record);
+                    }
+                }
+                0
+            })
+            .context("failed to add ringbuf callback")?;
+
+        let ringbuf = ringbuf_builder
+            .build()
+            .context("failed to build ring buffer")?;
+
+        let file = File::create(output_path).context("failed to create sched trace output")?;
+
+        Ok(Self {
+            links,
+            ringbuf,
+            records,
+            writer: BufWriter::new(file),
+            skel,
+            _open_object: unsafe { Box::from_raw(open_object_ptr) },
+        })
+    }
+}
+
+impl SchedTracer<'_> {
+    fn consume_and_write(&mut self) -> Result<()> {
+        self.ringbuf
+            .consume()
+            .context("failed to consume ringbuf")?;
+        let records: Vec<_> = self.records.borrow_mut().drain(..).collect();
+        for record in &records {
+            let json = serde_json::to_string(record).context("failed to serialize record")?;
+            writeln!(self.writer, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.links.clear();
+        self.consume_and_write()?;
+        self.writer.flush()?;
+
+        let dropped = self
+            .skel
+            .maps
+            .bss_data
+            .as_ref()
+            .map(|bss| bss.trace_bss.dropped_events)
+            .unwrap_or(0);
+        if dropped > 0 {
+            eprintln!(
+                "warning: {} sched trace events were dropped due to full ring buffer",
+                dropped
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Resolves cgroup ids to paths the way `perf script` prints them, i.e.
+/// relative to the cgroup2 mount. cgroup ids are the inode numbers of the
+/// cgroup directories.
+struct CgroupPaths {
+    paths: HashMap<u64, String>,
+    scanned_at: Option<Instant>,
+}
+
+impl CgroupPaths {
+    fn new() -> Self {
+        let mut cgroups = Self {
+            paths: HashMap::new(),
+            scanned_at: None,
+        };
+        cgroups.rescan();
+        cgroups
+    }
+
+    fn rescan(&mut self) {
+        fn walk(root: &Path, dir: &Path, paths: &mut HashMap<u64, String>) {
+            let Ok(meta) = fs::metadata(dir) else {
+                return;
+            };
+            let rel = dir.strip_prefix(root).unwrap_or(dir);
+            paths.insert(meta.ino(), format!("/{}", rel.display()));
+
+            let Ok(entries) = fs::read_dir(dir) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                    walk(root, &entry.path(), paths);
+                }
+            }
+        }
+
+        self.paths.clear();
+        walk(
+            Path::new(CGROUP_ROOT),
+            Path::new(CGROUP_ROOT),
+            &mut self.paths,
+        );
+        self.scanned_at = Some(Instant::now());
+    }
+
+    fn path(&mut self, id: u64) -> String {
+        if !self.paths.contains_key(&id)
+            && self
+                .scanned_at
+                .map_or(true, |at| at.elapsed() >= CGROUP_RESCAN_INTERVAL)
+        {
+            self.rescan();
+        }
+        self.paths
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// Parses the terms of a PMU event alias such as
+/// `event=0xcd,umask=0x1,ldlat=3`.
+fn parse_event_terms(alias: &str) -> Result<Vec<(String, u64)>> {
+    alias
+        .trim()
+        .split(',')
+        .filter(|term| !term.is_empty())
+        .map(|term| {
+            let (name, value) = term.split_once('=').unwrap_or((term, "1"));
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => value.parse::<u64>(),
+            }
+            .with_context(|| format!("invalid PMU event term '{}'", term))?;
+            Ok((name.to_string(), value))
+        })
+        .collect()
+}
+
+/// Scatters @value into the config words according to a PMU format spec
+/// such as `config:0-7` or `config1:0-15`.
+fn apply_format(config: &mut [u64; 3], format: &str, mut value: u64) -> Result<()> {
+    let (word, ranges) = format
+        .trim()
+        .split_once(':')
+        .with_context(|| format!("invalid PMU format '{}'", format.trim()))?;
+    let word = match word {
+        "config" => 0,
+        "config1" => 1,
+        "config2" => 2,
+        _ => bail!("unsupported PMU format word '{}'", word),
+    };
+    for range in ranges.split(',') {
+        let (lo, hi) = match range.split_once('-') {
+            Some((lo, hi)) => (lo.parse::<u32>()?, hi.parse::<u32>()?),
+            None => {
+                let bit = range.parse::<u32>()?;
+                (bit, bit)
+            }
+        };
+        let width = hi - lo + 1;
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        };
+        config[word] |= (value & mask) << lo;
+        value = value.checked_shr(width).unwrap_or(0);
+    }
+    Ok(())
+}
+
+/// Resolves the `cpu/<event>/` alias into perf_event_attr type and config
+/// words, overriding the load latency threshold if the alias takes one.
+fn resolve_mem_event(event: &str, ldlat: u32) -> Result<Option<(u32, [u64; 3])>> {
+    for pmu in MEM_PMUS {
+        let pmu_dir = Path::new("/sys/bus/event_source/devices").join(pmu);
+        let Ok(alias) = fs::read_to_string(pmu_dir.join("events").join(event)) else {
+            continue;
+        };
+        let type_ = fs::read_to_string(pmu_dir.join("type"))
+            .with_context(|| format!("failed to read {} PMU type", pmu))?
+            .trim()
+            .parse::<u32>()
+            .with_context(|| format!("invalid {} PMU type", pmu))?;
+
+        let mut config = [0u64; 3];
+        for (term, value) in parse_event_terms(&alias)? {
+            let value = if term == "ldlat" { ldlat as u64 } else { value };
+            let format = fs::read_to_string(pmu_dir.join("format").join(&term))
+                .with_context(|| format!("unknown {} PMU format term '{}'", pmu, term))?;
+            apply_format(&mut config, &format, value)?;
+        }
+        return Ok(Some((type_, config)));
+    }
+    Ok(None)
+}
+
+/// A per-CPU perf_event_open() sample ring buffer.
+struct PerfRing {
+    _fd: OwnedFd,
+    base: *mut u8,
+    mmap_len: usize,
+    data_offset: usize,
+    data_size: usize,
+}
+
+impl PerfRing {
+    /// Returns None for CPUs which can't be sampled, offline ones or, on
+    /// hybrid parts, CPUs of the other core type.
+    fn open(attr: &mut perf::bindings::perf_event_attr, cpu: i32) -> Result<Option<Self>> {
+        let fd = unsafe { perf::perf_event_open(attr, -1, cpu, -1, 0) };
+        if fd < 0 {
+            let err = std::io::Error::last_os_error();
+            if matches!(err.raw_os_error(), Some(libc::ENODEV) | Some(libc::ENOENT)) {
+                return Ok(None);
+            }
+            bail!("perf_event_open failed on CPU {}: {}", cpu, err);
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let mmap_len = page_size * (MEM_RING_PAGES + 1);
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                mmap_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            bail!(
+                "failed to mmap perf ring buffer on CPU {}: {}",
+                cpu,
+                std::io::Error::last_os_error()
+            );
+        }
+
+        Ok(Some(Self {
+            _fd: fd,
+            base: base as *mut u8,
+            mmap_len,
+            data_offset: page_size,
+            data_size: page_size * MEM_RING_PAGES,
+        }))
+    }
+
+    /// Calls @f with every record written since the last drain.
+    fn drain(&mut self, mut f: impl FnMut(u32, &[u8])) {
+        let header = self.base as *mut perf::bindings::perf_event_mmap_page;
+        let head = unsafe { std::ptr::read_volatile(&(*header).data_head) };
+        fence(Ordering::Acquire);
+        let mut tail = unsafe { std::ptr::read_volatile(&(*header).data_tail) };
+
+        let data =
+            unsafe { std::slice::from_raw_parts(self.base.add(self.data_offset), self.data_size) };
+        // Records may wrap around the end of the data area.
+        let read = |buf: &mut Vec<u8>, from: u64, len: usize| {
+            buf.clear();
+            buf.extend((0..len).map(|i| data[(from as usize + i) % data.len()]));
+        };
+        let header_len = std::mem::size_of::<perf::bindings::perf_event_header>();
+        let mut record = Vec::new();
+        while tail < head {
+            read(&mut record, tail, header_len);
+            let type_ = u32::from_ne_bytes(record[0..4].try_into().unwrap());
+            let size = u16::from_ne_bytes(record[6..8].try_into().unwrap()) as usize;
+            if size < header_len {
+                break;
+            }
+            read(&mut record, tail, size);
+            f(type_, &record[header_len..]);
+            tail += size as u64;
+        }
+
+        fence(Ordering::Release);
+        unsafe { std::ptr::write_volatile(&mut (*header).data_tail, tail) };
+    }
+}
+
+impl Drop for PerfRing {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.mmap_len);
+        }
+    }
+}
+
+fn read_u64(body: &[u8], off: &mut usize) -> Option<u64> {
+    let val = u64::from_ne_bytes(body.get(*off..*off + 8)?.try_into().ok()?);
+    *off += 8;
+    Some(val)
+}
+
+fn read_u32(body: &[u8], off: &mut usize) -> Option<u32> {
+    let val = u32::from_ne_bytes(body.get(*off..*off + 4)?.try_into().ok()?);
+    *off += 4;
+    Some(val)
+}
+
+/// A PERF_RECORD_SAMPLE laid out per MEM_SAMPLE_TYPE.
+struct MemSample {
+    ip: u64,
+    pid: u32,
+    tid: u32,
+    time_ns: u64,
+    addr: u64,
+    phys_addr: u64,
+    cgroup: u64,
+    data_page_size: u64,
+}
+
+impl MemSample {
+    fn parse(body: &[u8]) -> Option<Self> {
+        let mut off = 0;
+        Some(Self {
+            ip: read_u64(body, &mut off)?,
+            pid: read_u32(body, &mut off)?,
+            tid: read_u32(body, &mut off)?,
+            time_ns: read_u64(body, &mut off)?,
+            addr: read_u64(body, &mut off)?,
+            phys_addr: read_u64(body, &mut off)?,
+            cgroup: read_u64(body, &mut off)?,
+            data_page_size: read_u64(body, &mut off)?,
+        })
+    }
+}
+
+fn mem_sample_type() -> u64 {
+    (perf::bindings::PERF_SAMPLE_IP
+        | perf::bindings::PERF_SAMPLE_TID
+        | perf::bindings::PERF_SAMPLE_TIME
+        | perf::bindings::PERF_SAMPLE_ADDR
+        | perf::bindings::PERF_SAMPLE_PHYS_ADDR
+        | perf::bindings::PERF_SAMPLE_CGROUP
+        | perf::bindings::PERF_SAMPLE_DATA_PAGE_SIZE) as u64
+}
+
+struct MemSampler {
+    rings: Vec<PerfRing>,
+    comms: HashMap<u32, String>,
+    cgroups: CgroupPaths,
+    writer: BufWriter<File>,
+    self_pid: u32,
+    lost: u64,
+}
+
+impl MemSampler {
+    fn new(output_path: &Path, ldlat: u32) -> Result<Self> {
+        let loads = resolve_mem_event("mem-loads", ldlat)?.context(
+            "no mem-loads event found on this CPU, native mem sampling is unsupported; \
+             record with perf or pass --disable-mem-trace",
+        )?;
+        let stores = resolve_mem_event("mem-stores", ldlat)?;
+
+        let mut rings = Vec::new();
+        for (type_, config) in std::iter::once(loads).chain(stores) {
+            let mut attr = perf::bindings::perf_event_attr {
+                size: std::mem::size_of::<perf::bindings::perf_event_attr>() as u32,
+                type_,
+                config: config[0],
+                sample_type: mem_sample_type(),
+                clockid: libc::CLOCK_MONOTONIC,
+                ..Default::default()
+            };
+            attr.__bindgen_anon_1.sample_freq = MEM_SAMPLE_FREQ;
+            attr.__bindgen_anon_3.config1 = config[1];
+            attr.__bindgen_anon_4.config2 = config[2];
+            attr.set_freq(1);
+            attr.set_precise_ip(2);
+            attr.set_use_clockid(1);
+            attr.set_comm(1);
+            attr.set_comm_exec(1);
+            attr.set_exclude_guest(1);
+
+            for cpu in 0..*scx_utils::NR_CPUS_POSSIBLE {
+                rings.extend(PerfRing::open(&mut attr, cpu as i32)?);
+            }
+        }
+        if rings.is_empty() {
+            bail!("failed to open mem sampling events on any CPU");
+        }
+
+        let file = File::create(output_path).context("failed to create mem trace output")?;
+
+        Ok(Self {
+            rings,
+            comms: HashMap::new(),
+            cgroups: CgroupPaths::new(),
+            writer: BufWriter::new(file),
+            self_pid: std::process::id(),
+            lost: 0,
+        })
+    }
+
+    fn comm(&mut self, pid: u32, tid: u32) -> String {
+        self.comms
+            .entry(tid)
+            .or_insert_with(|| {
+                fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid))
+                    .map(|comm| comm.trim_end().to_string())
+                    .unwrap_or_else(|_| format!(":{}", tid))
+            })
+            .clone()
+    }
+
+    fn consume_and_write(&mut self) -> Result<()> {
+        let mut samples = Vec::new();
+        let mut comms = Vec::new();
+        let mut lost = 0;
+        for ring in &mut self.rings {
+            ring.drain(|type_, body| match type_ {
+                perf::bindings::PERF_RECORD_SAMPLE => samples.extend(MemSample::parse(body)),
+                perf::bindings::PERF_RECORD_COMM => {
+                    let mut off = 0;
+                    if let (Some(_pid), Some(tid)) =
+                        (read_u32(body, &mut off), read_u32(body, &mut off))
+                    {
+                        let comm = body[off..].split(|&b| b == 0).next().unwrap_or_default();
+                        comms.push((tid, String::from_utf8_lossy(comm).into_owned()));
+                    }
+                }
+                perf::bindings::PERF_RECORD_LOST => {
+                    let mut off = 8;
+                    lost += read_u64(body, &mut off).unwrap_or(0);
+                }
+                _ => {}
+            });
+        }
+        self.comms.extend(comms);
+        self.lost += lost;
+
+        for sample in samples {
+            if sample.pid == self.self_pid {
+                continue;
+            }
+            let record = PerfMemRecord::new(
+                self.comm(sample.pid, sample.tid),
+                sample.pid,
+                sample.tid,
+                sample.time_ns,
+                sample.addr,
+                self.cgroups.path(sample.cgroup),
+                sample.ip,
+                sample.phys_addr,
+                sample.data_page_size,
+            );
+            let json = serde_json::to_string(&record).context("failed to serialize record")?;
+            writeln!(self.writer, "{}", json)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.consume_and_write()?;
+        self.writer.flush()?;
+        if self.lost > 0 {
+            eprintln!(
+                "warning: {} mem samples were lost due to full perf ring buffers",
+                self.lost
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Records sched/irq tracepoints and mem samples without the perf binary.
+pub struct NativeRecorder {
+    sched: Option<SchedTracer<'static>>,
+    mem: Option<MemSampler>,
+}
+
+impl NativeRecorder {
+    pub fn new(output_dir: &Path, sched_trace: bool, mem_trace: bool, ldlat: u32) -> Result<Self> {
+        let path = |file: &str| -> PathBuf { output_dir.join(file) };
+        let mem = if mem_trace {
+            Some(MemSampler::new(&path(PERF_MEM_JSONL_FILE), ldlat)?)
+        } else {
+            None
+        };
+        let sched = if sched_trace {
+            Some(SchedTracer::new(&path(PERF_SCHED_JSONL_FILE))?)
+        } else {
+            None
+        };
+        Ok(Self { sched, mem })
+    }
+
+    pub fn consume_and_write(&mut self) -> Result<()> {
+        if let Some(sched) = self.sched.as_mut() {
+            sched.consume_and_write()?;
+        }
+        if let Some(mem) = self.mem.as_mut() {
+            mem.consume_and_write()?;
+        }
+        Ok(())
+    }
+
+    /// Stops recording and writes out everything still buffered.
+    pub fn finish(self) -> Result<()> {
+        if let Some(sched) = self.sched {
+            sched.finish()?;
+        }
+        if let Some(mem) = self.mem {
+            mem.finish()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn comm(s: &str) -> [c_char; bpf_intf::TRACE_COMM_LEN as usize] {
+        let mut buf = [0; bpf_intf::TRACE_COMM_LEN as usize];
+        for (dst, src) in buf.iter_mut().zip(s.bytes()) {
+            *dst = src as c_char;
+        }
+        buf
+    }
+
+    fn trace_event(kind: u32) -> bpf_intf::trace_event {
+        let mut ev: bpf_intf::trace_event = unsafe { std::mem::zeroed() };
+        ev.kind = kind;
+        ev.pid = 1000;
+        ev.tid = 10;
+        ev.cpu = 3;
+        ev.timestamp = 1_500_000_000;
+        ev.comm = comm("worker-a");
+        ev
+    }
+
+    #[test]
+    fn sched_switch_renders_like_perf_script() {
+        let mut ev = trace_event(bpf_intf::trace_event_kind_TRACE_SCHED_SWITCH);
+        ev.arg_comm = [comm("worker-a"), comm("swapper/3")];
+        ev.args = [10, 120, 1, 0, 120];
+
+        let record = sched_record(&ev).expect("expected sched_switch record");
+        assert_eq!(record.event, "sched:sched_switch");
+        assert_eq!(record.trace, "worker-a:10 [120] S ==> swapper/3:0 [120]");
+        assert_eq!(record.time, 1.5);
+        assert_eq!(record.sample_time_ns(), Some(1_500_000_000));
+
+        let fields = record.fields.expect("expected parsed fields");
+        assert_eq!(fields.get("prev_state"), Some(&Value::from("S")));
+        assert_eq!(fields.get("next_pid").and_then(Value::as_i64), Some(0));
+        assert_eq!(fields.get("cpu_idle"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn softirq_and_runtime_render_fields_like_perf_script() {
+        let mut ev = trace_event(bpf_intf::trace_event_kind_TRACE_SOFTIRQ_ENTRY);
+        ev.args[0] = 3;
+        let fields = sched_record(&ev).and_then(|r| r.fields).unwrap();
+        assert_eq!(fields.get("action"), Some(&Value::from("NET_RX")));
+        assert_eq!(fields.get("vec").and_then(Value::as_i64), Some(3));
+
+        let mut ev = trace_event(bpf_intf::trace_event_kind_TRACE_SCHED_STAT_RUNTIME);
+        ev.arg_comm[0] = comm("worker-a");
+        ev.args[0] = 10;
+        ev.args[1] = 500_000;
+        let fields = sched_record(&ev).and_then(|r| r.fields).unwrap();
+        assert_eq!(fields.get("runtime").and_then(Value::as_i64), Some(500_000));
+    }
+
+    #[test]
+    fn prev_state_matches_sched_switch_format() {
+        assert_eq!(prev_state_str(0), "R");
+        assert_eq!(prev_state_str(TASK_REPORT_MAX), "R+");
+        assert_eq!(prev_state_str(0x2), "D");
+        assert_eq!(prev_state_str(0x1 | 0x80), "S|I");
+    }
+
+    #[test]
+    fn pmu_event_alias_is_scattered_into_config_words() -> Result<()> {
+        let mut config = [0u64; 3];
+        for (term, value) in parse_event_terms("event=0xcd,umask=0x1,ldlat=3")? {
+            let (format, value) = match term.as_str() {
+                "event" => ("config:0-7", value),
+                "umask" => ("config:8-15", value),
+                "ldlat" => ("config1:0-15", 30),
+                _ => unreachable!(),
+            };
+            apply_format(&mut config, format, value)?;
+        }
+        assert_eq!(config, [0x1cd, 30, 0]);
+
+        let mut config = [0u64; 3];
+        apply_format(&mut config, "config:0-3,8-11", 0xab)?;
+        assert_eq!(config[0], 0xa0b);
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ use crate::record::{
 use anyhow::{bail, Context as _, Result};
 use clap::Parser;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Number, Value};
 use std::collections::{HashMap, HashSet};
@@ -69,7 +70,66 @@ pub struct PerfSchedScriptRecord {
     sample_time_ns: Option<u64>,
 }
 
+impl PerfMemRecord {
+    /// Builds a record as `perf script` would print it, for samples recorded
+    /// natively rather than parsed from perf output.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        comm: String,
+        pid: u32,
+        tid: u32,
+        time_ns: u64,
+        addr: u64,
+        cgroup: String,
+        ip: u64,
+        phys_addr: u64,
+        data_page_size: u64,
+    ) -> Self {
+        Self {
+            comm,
+            tid,
+            pid,
+            time: format_perf_time_ns(time_ns),
+            addr: format!("{:x}", addr),
+            cgroup,
+            ip: format!("{:x}", ip),
+            sym: "[unknown]".to_string(),
+            dso: "[unknown]".to_string(),
+            phys_addr: format!("{:x}", phys_addr),
+            data_page_size,
+            hint: 0,
+            sample_time_ns: Some(time_ns),
+        }
+    }
+}
+
 impl PerfSchedScriptRecord {
+    /// Builds a record from an event rendered in `perf script` trace format,
+    /// for events recorded natively rather than parsed from perf output.
+    pub(crate) fn new(
+        comm: String,
+        pid: i32,
+        tid: i32,
+        cpu: u32,
+        time_ns: u64,
+        event: &str,
+        trace: String,
+    ) -> Self {
+        let fields = parse_sched_fields(event, &trace);
+        Self {
+            comm,
+            pid,
+            tid,
+            cpu,
+            time: time_ns as f64 / 1_000_000_000.0,
+            event: event.to_string(),
+            trace,
+            fields,
+            hint: 0,
+            sample_time_ns: Some(time_ns),
+        }
+    }
+
     pub fn sample_time_ns(&self) -> Option<u64> {
         self.sample_time_ns
     }
@@ -127,6 +187,14 @@ trait HintAssignable {
     fn set_hint(&mut self, hint: u64);
 }
 
+/// Records written directly to JSONL by `record --native`. They skip perf
+/// script parsing but still go through filtering and hint annotation.
+trait NativeTraceRecord: Serialize + DeserializeOwned {
+    fn restore_sample_time(&mut self);
+    fn skip_reason(&self) -> Option<&'static str>;
+    fn annotate(&mut self, hint_annotator: &mut HintAnnotator<'_>);
+}
+
 impl HintIndex {
     fn load_if_exists(profile_dir: &Path) -> Result<Option<Self>> {
         let hints_path = profile_dir.join("hints.jsonl");
@@ -292,6 +360,40 @@ impl HintAssignable for PerfSchedScriptRecord {
     }
 }
 
+impl NativeTraceRecord for PerfMemRecord {
+    fn restore_sample_time(&mut self) {
+        self.sample_time_ns = parse_perf_time_ns(&self.time);
+    }
+
+    fn skip_reason(&self) -> Option<&'static str> {
+        if self.phys_addr == "0" || self.phys_addr.is_empty() {
+            return Some("no phys_addr");
+        }
+        if self.comm == "perf" || self.comm == "swapper" {
+            return Some("filtered comm");
+        }
+        None
+    }
+
+    fn annotate(&mut self, hint_annotator: &mut HintAnnotator<'_>) {
+        hint_annotator.annotate(self);
+    }
+}
+
+impl NativeTraceRecord for PerfSchedScriptRecord {
+    fn restore_sample_time(&mut self) {
+        self.sample_time_ns = perf_time_f64_to_ns(self.time);
+    }
+
+    fn skip_reason(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn annotate(&mut self, hint_annotator: &mut HintAnnotator<'_>) {
+        hint_annotator.annotate_sched_record(self);
+    }
+}
+
 impl OrderingIssues {
     fn new(label: &'static str) -> Self {
         Self {
@@ -382,7 +484,15 @@ fn run_processing(profile_dir: &Path, output_dir: &Path, verbose: bool) -> Resul
         }
     }
     copy_hints_if_present(profile_dir, output_dir)?;
-    if let Some(mem_perf_script_dst) =
+    if profile_dir.join(mem_trace.jsonl_file).exists() {
+        annotate_native_jsonl::<PerfMemRecord>(
+            profile_dir,
+            output_dir,
+            hint_index.as_ref(),
+            mem_trace,
+            verbose,
+        )?;
+    } else if let Some(mem_perf_script_dst) =
         prepare_trace_script_if_present(profile_dir, output_dir, mem_trace)?
     {
         parse_perf_mem_script_to_jsonl(
@@ -394,7 +504,15 @@ fn run_processing(profile_dir: &Path, output_dir: &Path, verbose: bool) -> Resul
         )?;
     }
 
-    if let Some(sched_perf_script_dst) =
+    if profile_dir.join(sched_trace.jsonl_file).exists() {
+        annotate_native_jsonl::<PerfSchedScriptRecord>(
+            profile_dir,
+            output_dir,
+            hint_index.as_ref(),
+            sched_trace,
+            verbose,
+        )?;
+    } else if let Some(sched_perf_script_dst) =
         prepare_trace_script_if_present(profile_dir, output_dir, sched_trace)?
     {
         parse_sched_perf_script_to_jsonl(
@@ -681,6 +799,10 @@ fn parse_u32_pair(part: &str) -> Option<(u32, u32)> {
     Some((first.parse().ok()?, second.parse().ok()?))
 }
 
+fn format_perf_time_ns(time_ns: u64) -> String {
+    format!("{}.{:09}", time_ns / 1_000_000_000, time_ns % 1_000_000_000)
+}
+
 fn parse_perf_time_ns(s: &str) -> Option<u64> {
     let s = s.trim();
     if s.is_empty() {
@@ -783,7 +905,7 @@ fn maybe_int_value(value: &str) -> Value {
     }
 }
 
-fn parse_sched_fields(event: &str, trace: &str) -> Option<Map<String, Value>> {
+pub(crate) fn parse_sched_fields(event: &str, trace: &str) -> Option<Map<String, Value>> {
     let mut fields = Map::new();
 
     if event == "sched:sched_switch" {
@@ -912,17 +1034,10 @@ fn parse_perf_mem_script_to_jsonl(
         let raw_line = line.trim_end_matches(['\n', '\r']);
         match parse_perf_mem_script_line(raw_line) {
             Some(mut record) => {
-                if record.phys_addr == "0" || record.phys_addr.is_empty() {
-                    skipped += 1;
-                    if verbose {
-                        eprintln!("skipped (no phys_addr): {}", raw_line);
-                    }
-                    continue;
-                }
-                if record.comm == "perf" || record.comm == "swapper" {
+                if let Some(reason) = record.skip_reason() {
                     skipped += 1;
                     if verbose {
-                        eprintln!("skipped (filtered comm): {}", raw_line);
+                        eprintln!("skipped ({}): {}", reason, raw_line);
                     }
                     continue;
                 }
@@ -1025,6 +1140,82 @@ fn parse_sched_perf_script_to_jsonl(
     Ok(())
 }
 
+fn annotate_native_jsonl<R: NativeTraceRecord>(
+    profile_dir: &Path,
+    output_dir: &Path,
+    hint_index: Option<&HintIndex>,
+    artifacts: TraceArtifacts<'_>,
+    verbose: bool,
+) -> Result<()> {
+    let input_path = profile_dir.join(artifacts.jsonl_file);
+    println!("Annotating natively recorded {}...", artifacts.jsonl_kind);
+
+    let file = File::open(&input_path)
+        .with_context(|| format!("failed to open {}", artifacts.jsonl_kind))?;
+    let mut reader = BufReader::new(file);
+
+    let output_file = File::create(output_dir.join(artifacts.jsonl_file))
+        .with_context(|| format!("failed to create {}", artifacts.jsonl_kind))?;
+    let mut writer = BufWriter::new(output_file);
+
+    let mut count = 0;
+    let mut skipped = 0;
+    let mut errors = 0;
+    let mut hint_annotator = hint_index.map(HintAnnotator::new);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).context("failed to read line")? == 0 {
+            break;
+        }
+        let raw_line = line.trim_end_matches(['\n', '\r']);
+        if raw_line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<R>(raw_line) {
+            Ok(mut record) => {
+                if let Some(reason) = record.skip_reason() {
+                    skipped += 1;
+                    if verbose {
+                        eprintln!("skipped ({}): {}", reason, raw_line);
+                    }
+                    continue;
+                }
+                record.restore_sample_time();
+                if let Some(hint_annotator) = hint_annotator.as_mut() {
+                    record.annotate(hint_annotator);
+                }
+                let json = serde_json::to_string(&record).context("failed to serialize record")?;
+                writeln!(writer, "{}", json)?;
+                count += 1;
+            }
+            Err(e) => {
+                errors += 1;
+                if verbose {
+                    eprintln!("unparseable ({}): {}", e, raw_line);
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+
+    if let Some(ordering_issues) = hint_annotator.and_then(HintAnnotator::finish) {
+        ordering_issues.warn(
+            &input_path.display().to_string(),
+            "Hint lookup fell back conservatively for out-of-order samples.",
+        );
+    }
+
+    let total = count + skipped + errors;
+    println!(
+        "Annotated {} of {} records ({} skipped, {} unparseable)",
+        count, total, skipped, errors
+    );
+
+    Ok(())
+}
+
 fn print_profile_contents(profile_dir: &Path) -> Result<()> {
     println!("Output contents:");
 
@@ -1050,7 +1241,6 @@ fn print_profile_contents(profile_dir: &Path) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde::de::DeserializeOwned;
     use tempfile::TempDir;
 
     fn test_data_dir(name: &str) -> PathBuf {
@@ -1148,6 +1338,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn process_annotates_native_jsonl_like_perf_script() -> Result<()> {
+        let (tempdir, script_profile_dir) = materialize_test_profile("profile_basic")?;
+        let native_profile_dir = tempdir.path().join("profile_native");
+        fs::create_dir_all(&native_profile_dir)?;
+        fs::copy(
+            script_profile_dir.join("hints.jsonl"),
+            native_profile_dir.join("hints.jsonl"),
+        )?;
+
+        let mut mem_jsonl = String::new();
+        for line in fs::read_to_string(script_profile_dir.join(PERF_MEM_SCRIPT_FILE))?.lines() {
+            if let Some(record) = parse_perf_mem_script_line(line) {
+                mem_jsonl += &format!("{}\n", serde_json::to_string(&record)?);
+            }
+        }
+        fs::write(native_profile_dir.join(PERF_MEM_JSONL_FILE), mem_jsonl)?;
+
+        let mut sched_jsonl = String::new();
+        for line in fs::read_to_string(script_profile_dir.join(PERF_SCHED_SCRIPT_FILE))?.lines() {
+            if let Some(record) = parse_sched_perf_script_line(line) {
+                sched_jsonl += &format!("{}\n", serde_json::to_string(&record)?);
+            }
+        }
+        fs::write(native_profile_dir.join(PERF_SCHED_JSONL_FILE), sched_jsonl)?;
+
+        let script_output_dir = script_profile_dir.with_extension("post");
+        let native_output_dir = native_profile_dir.with_extension("post");
+        fs::create_dir_all(&script_output_dir)?;
+        fs::create_dir_all(&native_output_dir)?;
+        run_processing(&script_profile_dir, &script_output_dir, false)?;
+        run_processing(&native_profile_dir, &native_output_dir, false)?;
+
+        for file in [PERF_MEM_JSONL_FILE, PERF_SCHED_JSONL_FILE] {
+            let script: Vec<Value> = read_jsonl(&script_output_dir.join(file))?;
+            let native: Vec<Value> = read_jsonl(&native_output_dir.join(file))?;
+            assert_eq!(script, native, "{file} differs from the perf script path");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn native_mem_record_time_round_trips() {
+        let record = PerfMemRecord::new(
+            "worker".to_string(),
+            1000,
+            2001,
+            1_000_000_050,
+            0x1000,
+            "/workload.slice".to_string(),
+            0x2000,
+            0x3000,
+            4096,
+        );
+        assert_eq!(record.time, "1.000000050");
+        assert_eq!(parse_perf_time_ns(&record.time), Some(1_000_000_050));
+        assert_eq!(record.skip_reason(), None);
+    }
+
     #[test]
     fn process_annotates_sched_switch_prev_and_next_hints_independently() -> Result<()> {
         let mut timelines = HashMap::new();
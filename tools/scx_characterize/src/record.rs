@@ -5,6 +5,7 @@
 
 use crate::bpf::{BpfSkel, BpfSkelBuilder};
 use crate::bpf_intf::hints_event;
+use crate::native::NativeRecorder;
 use crate::Context;
 use anyhow::{bail, Context as _, Result};
 use clap::Parser;
@@ -97,6 +98,12 @@ pub struct RecordOpts {
     /// Disable recording perf mem trace into perf.mem.data
     #[clap(long)]
     pub disable_mem_trace: bool,
+
+    /// Record sched/irq tracepoints through BPF and mem samples through
+    /// perf_event_open instead of running perf. perf.sched.jsonl and
+    /// perf.mem.jsonl are written directly.
+    #[clap(long)]
+    pub native: bool,
 }
 
 struct SpawnedProcess {
@@ -418,9 +425,15 @@ pub fn cmd_record(ctx: &Context, opts: RecordOpts) -> Result<()> {
         bail!("--file cannot be used with --disable-archive");
     }
 
+    if opts.native && opts.enable_perf_script {
+        bail!("--enable-perf-script cannot be used with --native");
+    }
+
     fs::create_dir_all(&opts.output).context("failed to create output directory")?;
 
-    save_perf_version(&opts.output)?;
+    if !opts.native {
+        save_perf_version(&opts.output)?;
+    }
 
     let completed = match run_recording(ctx, &opts) {
         Ok(completed) => completed,
@@ -478,8 +491,16 @@ fn run_recording(ctx: &Context, opts: &RecordOpts) -> Result<bool> {
     }
 
     let mut processes = Vec::new();
-
-    if !opts.disable_mem_trace {
+    let mut native_recorder = None;
+
+    if opts.native {
+        native_recorder = Some(NativeRecorder::new(
+            &opts.output,
+            !opts.disable_sched_trace,
+            !opts.disable_mem_trace,
+            opts.ldlat,
+        )?);
+    } else if !opts.disable_mem_trace {
         let perf_data_path = opts.output.join(PERF_MEM_DATA_FILE);
         let mem_perf_args = vec![
             perf_binary(),
@@ -498,7 +519,7 @@ fn run_recording(ctx: &Context, opts: &RecordOpts) -> Result<bool> {
         processes.push(SpawnedProcess::spawn(&mem_perf_args)?);
     }
 
-    if !opts.disable_sched_trace {
+    if !opts.native && !opts.disable_sched_trace {
         let sched_data_path = opts.output.join(PERF_SCHED_DATA_FILE);
         let sched_perf_args = build_sched_perf_args(&sched_data_path);
         processes.push(SpawnedProcess::spawn(&sched_perf_args)?);
@@ -531,6 +552,9 @@ fn run_recording(ctx: &Context, opts: &RecordOpts) -> Result<bool> {
              * perf teardown and no longer matches the perf capture window.
              */
             stop_hints_recorder(&mut hints_recorder);
+            if let Some(recorder) = native_recorder.take() {
+                recorder.finish()?;
+            }
             for process in &processes {
                 process.signal(libc::SIGINT);
             }
@@ -574,6 +598,9 @@ fn run_recording(ctx: &Context, opts: &RecordOpts) -> Result<bool> {
                 if let Some(ref mut recorder) = hints_recorder {
                     recorder.consume_and_write()?;
                 }
+                if let Some(ref mut recorder) = native_recorder {
+                    recorder.consume_and_write()?;
+                }
             }
         }
     }
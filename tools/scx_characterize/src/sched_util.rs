@@ -12,7 +12,7 @@ use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 
-const DEFAULT_SYSTEM_CATEGORY_NAMES: [&str; 5] = [
+pub(crate) const DEFAULT_SYSTEM_CATEGORY_NAMES: [&str; 5] = [
     "hardirq",
     "softirq-rx",
     "softirq-tx",
@@ -26,21 +26,21 @@ const SOFTIRQ_OTHER_CATEGORY: &str = "softirq-other";
 const NMI_CATEGORY: &str = "nmi";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct BusyUtilRecord {
-    time_ms: u64,
-    window_ms: u64,
-    cpu_count: usize,
-    total: f64,
-    uncategorized: f64,
-    categories: HashMap<String, f64>,
+pub(crate) struct BusyUtilRecord {
+    pub(crate) time_ms: u64,
+    pub(crate) window_ms: u64,
+    pub(crate) cpu_count: usize,
+    pub(crate) total: f64,
+    pub(crate) uncategorized: f64,
+    pub(crate) categories: HashMap<String, f64>,
 }
 
 #[derive(Debug, Clone)]
-struct BusyInterval {
-    start_ns: u64,
-    end_ns: u64,
-    comm: String,
-    hint: u64,
+pub(crate) struct BusyInterval {
+    pub(crate) start_ns: u64,
+    pub(crate) end_ns: u64,
+    pub(crate) comm: String,
+    pub(crate) hint: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -50,7 +50,7 @@ enum CategoryCommMatcher {
 }
 
 #[derive(Debug, Clone)]
-struct CategorySpec {
+pub(crate) struct CategorySpec {
     name: String,
     comm_matcher: CategoryCommMatcher,
     hint: Option<u64>,
@@ -153,14 +153,14 @@ enum ClassifiedEvent {
     Other,
 }
 
-trait BusyIntervalSink {
+pub(crate) trait BusyIntervalSink {
     fn on_interval(&mut self, interval: &BusyInterval);
 }
 
 #[derive(Debug)]
-struct BucketAggregator {
-    trace_start_ns: u64,
-    window_ns: u64,
+pub(crate) struct BucketAggregator {
+    pub(crate) trace_start_ns: u64,
+    pub(crate) window_ns: u64,
     total_busy_ns: Vec<u64>,
     uncategorized_busy_ns: Vec<u64>,
     category_busy_ns: Vec<Vec<u64>>,
@@ -170,7 +170,7 @@ struct BucketAggregator {
 }
 
 impl BucketAggregator {
-    fn new(trace_start_ns: u64, window_ns: u64, categories: Vec<CategorySpec>) -> Self {
+    pub(crate) fn new(trace_start_ns: u64, window_ns: u64, categories: Vec<CategorySpec>) -> Self {
         let category_busy_ns = vec![Vec::new(); categories.len()];
         let matcher = compile_category_matcher(&categories);
         Self {
@@ -198,7 +198,7 @@ impl BucketAggregator {
         }
     }
 
-    fn finalize(
+    pub(crate) fn finalize(
         self,
         trace_end_ns: u64,
         cpu_count: usize,
@@ -291,25 +291,13 @@ impl BucketAggregator {
 
 impl BusyIntervalSink for BucketAggregator {
     fn on_interval(&mut self, interval: &BusyInterval) {
-        let interval_start = interval.start_ns.max(self.trace_start_ns);
-        let interval_end = interval.end_ns;
-        if interval_end <= interval_start {
+        if interval.end_ns <= interval.start_ns.max(self.trace_start_ns) {
             return;
         }
 
-        let start_idx = ((interval_start - self.trace_start_ns) / self.window_ns) as usize;
-        let end_idx = ((interval_end - self.trace_start_ns - 1) / self.window_ns) as usize;
-        self.ensure_bucket(end_idx);
-
         let matched_categories = self.matcher.match_indices(interval);
-        for idx in start_idx..=end_idx {
-            let bucket_start = self.trace_start_ns + idx as u64 * self.window_ns;
-            let bucket_end = bucket_start + self.window_ns;
-            let overlap_ns = interval_end.min(bucket_end) - interval_start.max(bucket_start);
-            if overlap_ns == 0 {
-                continue;
-            }
-
+        for (idx, overlap_ns) in window_overlaps(interval, self.trace_start_ns, self.window_ns) {
+            self.ensure_bucket(idx);
             self.total_busy_ns[idx] += overlap_ns;
             if matched_categories.is_empty() {
                 self.uncategorized_busy_ns[idx] += overlap_ns;
@@ -324,8 +312,35 @@ impl BusyIntervalSink for BucketAggregator {
     }
 }
 
+/// Splits an interval into (window index, overlap ns) pairs for windows of
+/// `window_ns` starting at `trace_start_ns`. Time before the trace start is
+/// dropped.
+pub(crate) fn window_overlaps(
+    interval: &BusyInterval,
+    trace_start_ns: u64,
+    window_ns: u64,
+) -> impl Iterator<Item = (usize, u64)> {
+    let start_ns = interval.start_ns.max(trace_start_ns);
+    let end_ns = interval.end_ns;
+    let (first_idx, last_idx) = if end_ns <= start_ns {
+        (1, 0)
+    } else {
+        (
+            (start_ns - trace_start_ns) / window_ns,
+            (end_ns - trace_start_ns - 1) / window_ns,
+        )
+    };
+
+    (first_idx..=last_idx).filter_map(move |idx| {
+        let window_start = trace_start_ns + idx * window_ns;
+        let window_end = window_start + window_ns;
+        let overlap_ns = end_ns.min(window_end) - start_ns.max(window_start);
+        (overlap_ns > 0).then_some((idx as usize, overlap_ns))
+    })
+}
+
 #[derive(Debug, Default)]
-struct TraceStats {
+pub(crate) struct TraceStats {
     trace_start_ns: Option<u64>,
     trace_end_ns: Option<u64>,
     observed_cpus: BTreeSet<u32>,
@@ -333,7 +348,7 @@ struct TraceStats {
 }
 
 impl TraceStats {
-    fn observe(&mut self, record: &PerfSchedScriptRecord, time_ns: u64) {
+    pub(crate) fn observe(&mut self, record: &PerfSchedScriptRecord, time_ns: u64) {
         self.observed_cpus.insert(record.cpu);
         self.trace_start_ns.get_or_insert(time_ns);
         self.trace_end_ns = Some(time_ns);
@@ -342,11 +357,11 @@ impl TraceStats {
         }
     }
 
-    fn trace_start_ns(&self) -> u64 {
+    pub(crate) fn trace_start_ns(&self) -> u64 {
         self.trace_start_ns.unwrap_or(0)
     }
 
-    fn finish(&self) -> Result<(u64, usize)> {
+    pub(crate) fn finish(&self) -> Result<(u64, usize)> {
         if !self.saw_switch {
             anyhow::bail!("no sched:sched_switch events found in perf.sched.jsonl");
         }
@@ -363,20 +378,20 @@ impl TraceStats {
 }
 
 #[derive(Debug)]
-struct SchedBusyTracker<S> {
+pub(crate) struct SchedBusyTracker<S> {
     cpu_states: HashMap<u32, CpuState>,
-    sink: S,
+    pub(crate) sink: S,
 }
 
 impl<S: BusyIntervalSink> SchedBusyTracker<S> {
-    fn new(sink: S) -> Self {
+    pub(crate) fn new(sink: S) -> Self {
         Self {
             cpu_states: HashMap::new(),
             sink,
         }
     }
 
-    fn process_record(&mut self, record: PerfSchedScriptRecord) {
+    pub(crate) fn process_record(&mut self, record: PerfSchedScriptRecord) {
         let Some(time_ns) = record
             .sample_time_ns()
             .or_else(|| sched_time_to_ns(record.time))
@@ -471,7 +486,7 @@ impl<S: BusyIntervalSink> SchedBusyTracker<S> {
         }
     }
 
-    fn finish(mut self, _trace_end_ns: u64) -> S {
+    pub(crate) fn finish(mut self, _trace_end_ns: u64) -> S {
         for state in self.cpu_states.values_mut() {
             let cpu_end_ns = state.last_seen_ns.max(state.segment_start_ns);
             if let Some(interval) = flush_current_interval(state, cpu_end_ns) {
@@ -556,7 +571,7 @@ fn pop_softirq_override(state: &mut CpuState) {
     }
 }
 
-fn parse_sched_categories(spec: &str) -> Result<Vec<CategorySpec>> {
+pub(crate) fn parse_sched_categories(spec: &str) -> Result<Vec<CategorySpec>> {
     let mut categories = Vec::new();
     let mut seen_names = HashSet::new();
     let mut hinted_comm_parts = BTreeSet::new();
@@ -790,7 +805,7 @@ fn parse_nmi_duration_ns(record: &PerfSchedScriptRecord) -> Option<u64> {
         .ok()
 }
 
-fn sched_time_to_ns(time: f64) -> Option<u64> {
+pub(crate) fn sched_time_to_ns(time: f64) -> Option<u64> {
     if !time.is_finite() || time < 0.0 {
         return None;
     }
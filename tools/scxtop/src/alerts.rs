@@ -0,0 +1,1059 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::{CpuData, EventData, LlcData, NodeData, ProcData};
+
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::os::unix::net::UnixDatagram;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// Fields that are built from events, or aren't collected, rather than sampled on ticks.
+const UNSAMPLED_FIELDS: &[&str] = &[
+    "dsq_lat_us",
+    "hw_pressure",
+    "perf",
+    "lat_us",
+    "slice_consumed",
+    "min_freq",
+    "max_freq",
+];
+
+// Syslog socket and priority of alert messages (LOG_USER | LOG_WARNING).
+const SYSLOG_PATH: &str = "/dev/log";
+const SYSLOG_PRIORITY: u8 = 12;
+
+/// Alert rule from the `[[alerts]]` tables of `scxtop.toml`:
+/// ```text
+/// [[alerts]]
+/// name = "llc-imbalance"
+/// condition = "spread(llc.cpu_total_util_percent) > 30"
+/// for_ms = 5000
+/// actions = ["highlight", "syslog", "command:logger -t scxtop llc imbalance"]
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AlertRule {
+    /// Name of the alert.
+    pub name: String,
+    /// Condition of the alert, see [`Condition`].
+    pub condition: String,
+    /// Time the condition must hold before the alert fires.
+    #[serde(default)]
+    pub for_ms: u64,
+    /// Actions run when the alert fires.
+    #[serde(default = "default_actions")]
+    pub actions: Vec<AlertAction>,
+}
+
+fn default_actions() -> Vec<AlertAction> {
+    vec![AlertAction::Highlight]
+}
+
+/// Action run when an alert fires.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AlertAction {
+    /// Show the alert in the TUI while its condition holds.
+    Highlight,
+    /// Write the alert to syslog.
+    Syslog,
+    /// Send a desktop notification with notify-send.
+    Notify,
+    /// Run a shell command, the alert is passed in the SCXTOP_ALERT and SCXTOP_ALERT_MESSAGE
+    /// environment variables.
+    Command(String),
+    /// Record a perfetto trace.
+    Trace,
+}
+
+impl FromStr for AlertAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "highlight" => Ok(AlertAction::Highlight),
+            "syslog" => Ok(AlertAction::Syslog),
+            "notify" => Ok(AlertAction::Notify),
+            "trace" => Ok(AlertAction::Trace),
+            _ => match s.strip_prefix("command:") {
+                Some(cmd) if !cmd.trim().is_empty() => Ok(AlertAction::Command(cmd.to_string())),
+                _ => Err(anyhow!(
+                    "Invalid alert action {s}, expected highlight, syslog, notify, trace or command:<cmd>"
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for AlertAction {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for AlertAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AlertAction::Highlight => write!(f, "highlight"),
+            AlertAction::Syslog => write!(f, "syslog"),
+            AlertAction::Notify => write!(f, "notify"),
+            AlertAction::Command(cmd) => write!(f, "command:{cmd}"),
+            AlertAction::Trace => write!(f, "trace"),
+        }
+    }
+}
+
+impl From<AlertAction> for String {
+    fn from(action: AlertAction) -> String {
+        action.to_string()
+    }
+}
+
+impl AlertAction {
+    /// Runs the syslog, notify and command actions, the highlight and trace actions are handled
+    /// by the app.
+    pub fn run(&self, alert: &FiredAlert) -> Result<()> {
+        match self {
+            AlertAction::Highlight | AlertAction::Trace => {}
+            AlertAction::Syslog => {
+                let socket = UnixDatagram::unbound()?;
+                let msg = format!(
+                    "<{SYSLOG_PRIORITY}>scxtop[{}]: {}",
+                    std::process::id(),
+                    alert.message
+                );
+                socket.send_to(msg.as_bytes(), SYSLOG_PATH)?;
+            }
+            AlertAction::Notify => {
+                let child = Command::new("notify-send")
+                    .args(["--urgency=critical", "scxtop", &alert.message])
+                    .spawn()?;
+                reap(child);
+            }
+            AlertAction::Command(cmd) => {
+                let child = Command::new("sh")
+                    .args(["-c", cmd])
+                    .env("SCXTOP_ALERT", &alert.name)
+                    .env("SCXTOP_ALERT_MESSAGE", &alert.message)
+                    .spawn()?;
+                reap(child);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Waits for a child in the background so that it doesn't linger as a zombie.
+fn reap(mut child: std::process::Child) {
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
+
+/// Aggregation of the values of a metric.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregation {
+    Min,
+    Max,
+    Avg,
+    Sum,
+    /// Difference between the max and min values.
+    Spread,
+    /// Number of values.
+    Count,
+    /// Percentile of the history of values.
+    Percentile(f64),
+}
+
+impl Aggregation {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "min" => Some(Aggregation::Min),
+            "max" => Some(Aggregation::Max),
+            "avg" => Some(Aggregation::Avg),
+            "sum" => Some(Aggregation::Sum),
+            "spread" => Some(Aggregation::Spread),
+            "count" => Some(Aggregation::Count),
+            "p50" => Some(Aggregation::Percentile(50.0)),
+            "p90" => Some(Aggregation::Percentile(90.0)),
+            "p99" => Some(Aggregation::Percentile(99.0)),
+            "p999" => Some(Aggregation::Percentile(99.9)),
+            _ => None,
+        }
+    }
+
+    /// Aggregates values, returns None when there are no values.
+    fn apply(&self, mut values: Vec<f64>) -> Option<f64> {
+        if values.is_empty() {
+            return (*self == Aggregation::Count).then_some(0.0);
+        }
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let sum: f64 = values.iter().sum();
+        Some(match self {
+            Aggregation::Min => min,
+            Aggregation::Max => max,
+            Aggregation::Avg => sum / values.len() as f64,
+            Aggregation::Sum => sum,
+            Aggregation::Spread => max - min,
+            Aggregation::Count => values.len() as f64,
+            Aggregation::Percentile(p) => {
+                values.sort_by(f64::total_cmp);
+                let rank = ((p / 100.0) * (values.len() - 1) as f64).round() as usize;
+                values[rank]
+            }
+        })
+    }
+}
+
+/// Collected data a metric reads from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scope {
+    Cpu,
+    Llc,
+    Node,
+    Proc,
+}
+
+/// Selects the CPU, LLC or node with an id, or processes with a tgid or name.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Selector {
+    All,
+    Id(i64),
+    Name(String),
+}
+
+impl Selector {
+    fn matches(&self, id: i64, name: Option<&str>) -> bool {
+        match self {
+            Selector::All => true,
+            Selector::Id(sel_id) => *sel_id == id,
+            Selector::Name(sel_name) => name == Some(sel_name.as_str()),
+        }
+    }
+}
+
+/// Aggregation of a field of the collected data, ex: `p99(proc[nginx].lat_us)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    pub aggregation: Aggregation,
+    pub scope: Scope,
+    pub selector: Selector,
+    pub field: String,
+}
+
+impl Metric {
+    fn samples(&self) -> Samples {
+        match (self.scope, self.field.as_str()) {
+            (_, field) if UNSAMPLED_FIELDS.contains(&field) => Samples::default(),
+            (Scope::Cpu, "cpu_freq") => Samples {
+                cpu_stats: true,
+                cpu_freq: true,
+                ..Default::default()
+            },
+            (Scope::Proc, "cpu_util_perc" | "num_threads") => Samples {
+                cpu_stats: true,
+                procs: true,
+                ..Default::default()
+            },
+            (Scope::Proc, _) => Samples::default(),
+            // Profiling events, the CPU utilization events are computed from the CPU stats
+            _ => Samples {
+                cpu_stats: true,
+                prof_events: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Data that is sampled from the system on ticks rather than built from events. Views only
+/// sample the data they display, the data alerts need is sampled on every tick.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Samples {
+    /// CPU utilization.
+    pub cpu_stats: bool,
+    /// CPU frequencies.
+    pub cpu_freq: bool,
+    /// Values of the active profiling events.
+    pub prof_events: bool,
+    /// Process stats read from /proc.
+    pub procs: bool,
+}
+
+impl Samples {
+    fn union(self, other: Samples) -> Samples {
+        Samples {
+            cpu_stats: self.cpu_stats || other.cpu_stats,
+            cpu_freq: self.cpu_freq || other.cpu_freq,
+            prof_events: self.prof_events || other.prof_events,
+            procs: self.procs || other.procs,
+        }
+    }
+}
+
+/// Value compared by a condition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Value(f64),
+    Metric(Metric),
+    /// 1 when a sched_ext scheduler is loaded, 0 otherwise.
+    SchedulerLoaded,
+}
+
+/// Comparison operator.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CmpOp {
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+}
+
+impl CmpOp {
+    fn compare(&self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+        }
+    }
+}
+
+/// Condition of an alert rule. Conditions compare metrics and numbers and are combined with
+/// `&&`, `||` and parentheses:
+/// ```text
+/// spread(llc.cpu_total_util_percent) > 30
+/// scheduler_loaded == 0
+/// max(cpu.cpu_freq) <= min(cpu.min_freq)
+/// p99(proc[nginx].lat_us) > 5000 && count(proc[nginx].lat_us) > 0
+/// ```
+/// A metric is `<aggregation>(<scope>[<selector>].<field>)`:
+/// - aggregations are min, max, avg, sum, spread (max - min) and count of the latest value of
+///   each selected CPU, LLC, node or process, and p50, p90, p99 and p999 of their history.
+/// - scopes are cpu, llc, node and proc, the optional selector is an id, a tgid or a process
+///   name.
+/// - fields are the names of collected events (ex: cpu_freq, dsq_lat_us, lat_us,
+///   slice_consumed, cpu_total_util_percent), along with min_freq and max_freq for CPUs and
+///   cpu_util_perc and num_threads for processes.
+///
+/// Comparisons with a metric that has no values are false.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Compare(Operand, CmpOp, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Returns the sampled data the condition reads.
+    pub fn samples(&self) -> Samples {
+        let operand_samples = |operand: &Operand| match operand {
+            Operand::Metric(metric) => metric.samples(),
+            Operand::Value(_) | Operand::SchedulerLoaded => Samples::default(),
+        };
+        match self {
+            Condition::Compare(lhs, _, rhs) => operand_samples(lhs).union(operand_samples(rhs)),
+            Condition::And(lhs, rhs) | Condition::Or(lhs, rhs) => {
+                lhs.samples().union(rhs.samples())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Selector(String),
+    Op(CmpOp),
+    And,
+    Or,
+    LParen,
+    RParen,
+    Dot,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '.' => {
+                chars.next();
+                tokens.push(Token::Dot);
+            }
+            '[' => {
+                chars.next();
+                let mut selector = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => selector.push(c),
+                        None => bail!("Unclosed selector in {s}"),
+                    }
+                }
+                tokens.push(Token::Selector(selector.trim().to_string()));
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    bail!("Invalid operator {c} in {s}, expected {c}{c}");
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '>' | '<' | '=' | '!' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                let op = match (c, eq) {
+                    ('>', false) => CmpOp::Gt,
+                    ('>', true) => CmpOp::Ge,
+                    ('<', false) => CmpOp::Lt,
+                    ('<', true) => CmpOp::Le,
+                    ('=', true) => CmpOp::Eq,
+                    ('!', true) => CmpOp::Ne,
+                    _ => bail!("Invalid operator {c} in {s}"),
+                };
+                tokens.push(Token::Op(op));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                number.push(c);
+                chars.next();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid number {number} in {s}"))?,
+                ));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            _ => bail!("Invalid character {c} in {s}"),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser of conditions.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            token => bail!("Expected {expected:?}, found {token:?}"),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition> {
+        let mut cond = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            cond = Condition::Or(Box::new(cond), Box::new(self.parse_and()?));
+        }
+        Ok(cond)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition> {
+        let mut cond = self.parse_term()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            cond = Condition::And(Box::new(cond), Box::new(self.parse_term()?));
+        }
+        Ok(cond)
+    }
+
+    fn parse_term(&mut self) -> Result<Condition> {
+        if self.peek() == Some(&Token::LParen) {
+            self.next();
+            let cond = self.parse_or()?;
+            self.expect(Token::RParen)?;
+            return Ok(cond);
+        }
+        let lhs = self.parse_operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            token => bail!("Expected a comparison operator, found {token:?}"),
+        };
+        let rhs = self.parse_operand()?;
+        Ok(Condition::Compare(lhs, op, rhs))
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Operand::Value(value)),
+            Some(Token::Ident(ident)) if ident == "scheduler_loaded" => {
+                Ok(Operand::SchedulerLoaded)
+            }
+            Some(Token::Ident(ident)) => {
+                let aggregation = Aggregation::parse(&ident)
+                    .ok_or_else(|| anyhow!("Invalid aggregation {ident}"))?;
+                self.expect(Token::LParen)?;
+                let scope = match self.next() {
+                    Some(Token::Ident(scope)) => match scope.as_str() {
+                        "cpu" => Scope::Cpu,
+                        "llc" => Scope::Llc,
+                        "node" => Scope::Node,
+                        "proc" => Scope::Proc,
+                        _ => bail!("Invalid scope {scope}, expected cpu, llc, node or proc"),
+                    },
+                    token => bail!("Expected a scope, found {token:?}"),
+                };
+                let selector = match self.peek() {
+                    Some(Token::Selector(selector)) => {
+                        let selector = match selector.parse::<i64>() {
+                            Ok(id) => Selector::Id(id),
+                            Err(_) if scope == Scope::Proc => Selector::Name(selector.clone()),
+                            Err(_) => bail!("Invalid {scope:?} selector {selector}"),
+                        };
+                        self.next();
+                        selector
+                    }
+                    _ => Selector::All,
+                };
+                self.expect(Token::Dot)?;
+                let field = match self.next() {
+                    Some(Token::Ident(field)) => field,
+                    token => bail!("Expected a field, found {token:?}"),
+                };
+                self.expect(Token::RParen)?;
+                Ok(Operand::Metric(Metric {
+                    aggregation,
+                    scope,
+                    selector,
+                    field,
+                }))
+            }
+            token => bail!("Expected a number or metric, found {token:?}"),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let cond = parser
+            .parse_or()
+            .map_err(|e| anyhow!("Invalid condition {s}: {e}"))?;
+        if let Some(token) = parser.peek() {
+            bail!("Invalid condition {s}: unexpected {token:?}");
+        }
+        Ok(cond)
+    }
+}
+
+/// Data alert conditions are evaluated against.
+pub struct AlertContext<'a> {
+    pub cpu_data: &'a BTreeMap<usize, CpuData>,
+    pub llc_data: &'a BTreeMap<usize, LlcData>,
+    pub node_data: &'a BTreeMap<usize, NodeData>,
+    pub proc_data: &'a BTreeMap<i32, ProcData>,
+    pub scheduler: &'a str,
+}
+
+/// Alert that fired during an evaluation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FiredAlert {
+    pub name: String,
+    pub message: String,
+    pub actions: Vec<AlertAction>,
+}
+
+#[derive(Clone, Debug)]
+struct Alert {
+    rule: AlertRule,
+    condition: Condition,
+    // Time since the condition holds
+    pending_since: Option<Instant>,
+    active: bool,
+}
+
+/// Evaluates alert rules against the collected data.
+#[derive(Clone, Debug, Default)]
+pub struct AlertEngine {
+    alerts: Vec<Alert>,
+    // Min and max frequency of CPUs, in the unit of the cpu_freq event
+    cpu_freq_limits: BTreeMap<usize, (u64, u64)>,
+    // Sampled data read by the conditions
+    samples: Samples,
+}
+
+impl AlertEngine {
+    /// Returns an AlertEngine for the rules, fails if a condition is invalid.
+    pub fn new(rules: &[AlertRule]) -> Result<Self> {
+        let alerts = rules
+            .iter()
+            .map(|rule| {
+                Ok(Alert {
+                    condition: rule
+                        .condition
+                        .parse()
+                        .map_err(|e| anyhow!("Invalid alert {}: {e}", rule.name))?,
+                    rule: rule.clone(),
+                    pending_since: None,
+                    active: false,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let samples = alerts
+            .iter()
+            .fold(Samples::default(), |samples, alert: &Alert| {
+                samples.union(alert.condition.samples())
+            });
+        Ok(Self {
+            alerts,
+            cpu_freq_limits: BTreeMap::new(),
+            samples,
+        })
+    }
+
+    /// Sets the min and max frequency of a CPU.
+    pub fn set_cpu_freq_limits(&mut self, cpu: usize, min_freq: u64, max_freq: u64) {
+        self.cpu_freq_limits.insert(cpu, (min_freq, max_freq));
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    /// Returns the sampled data the rules read.
+    pub fn samples(&self) -> Samples {
+        self.samples
+    }
+
+    /// Returns the names of the active alerts with a highlight action.
+    pub fn highlighted(&self) -> impl Iterator<Item = &str> {
+        self.alerts
+            .iter()
+            .filter(|alert| alert.active && alert.rule.actions.contains(&AlertAction::Highlight))
+            .map(|alert| alert.rule.name.as_str())
+    }
+
+    /// Evaluates the rules, returns the alerts whose condition has held for long enough since
+    /// they were last evaluated as false.
+    pub fn evaluate(&mut self, ctx: &AlertContext, now: Instant) -> Vec<FiredAlert> {
+        let mut fired = Vec::new();
+        for i in 0..self.alerts.len() {
+            let holds = self.eval_condition(&self.alerts[i].condition, ctx);
+            let alert = &mut self.alerts[i];
+            if !holds {
+                if alert.active {
+                    info!("alert {} resolved", alert.rule.name);
+                }
+                alert.pending_since = None;
+                alert.active = false;
+                continue;
+            }
+
+            let since = *alert.pending_since.get_or_insert(now);
+            if !alert.active
+                && now.duration_since(since) >= Duration::from_millis(alert.rule.for_ms)
+            {
+                alert.active = true;
+                fired.push(FiredAlert {
+                    name: alert.rule.name.clone(),
+                    message: format!("alert {}: {}", alert.rule.name, alert.rule.condition),
+                    actions: alert.rule.actions.clone(),
+                });
+            }
+        }
+        fired
+    }
+
+    fn eval_condition(&self, condition: &Condition, ctx: &AlertContext) -> bool {
+        match condition {
+            Condition::Compare(lhs, op, rhs) => {
+                match (self.eval_operand(lhs, ctx), self.eval_operand(rhs, ctx)) {
+                    (Some(lhs), Some(rhs)) => op.compare(lhs, rhs),
+                    _ => false,
+                }
+            }
+            Condition::And(lhs, rhs) => {
+                self.eval_condition(lhs, ctx) && self.eval_condition(rhs, ctx)
+            }
+            Condition::Or(lhs, rhs) => {
+                self.eval_condition(lhs, ctx) || self.eval_condition(rhs, ctx)
+            }
+        }
+    }
+
+    fn eval_operand(&self, operand: &Operand, ctx: &AlertContext) -> Option<f64> {
+        match operand {
+            Operand::Value(value) => Some(*value),
+            Operand::SchedulerLoaded => Some(if ctx.scheduler.is_empty() { 0.0 } else { 1.0 }),
+            Operand::Metric(metric) => metric.aggregation.apply(self.metric_values(metric, ctx)),
+        }
+    }
+
+    /// Returns the values of a metric, the history of values for percentiles and the latest
+    /// value otherwise.
+    fn metric_values(&self, metric: &Metric, ctx: &AlertContext) -> Vec<f64> {
+        let history = matches!(metric.aggregation, Aggregation::Percentile(_));
+        let field = metric.field.as_str();
+        let mut values = Vec::new();
+        match metric.scope {
+            Scope::Cpu => {
+                for (cpu, cpu_data) in ctx.cpu_data {
+                    if !metric.selector.matches(*cpu as i64, None) {
+                        continue;
+                    }
+                    let limits = self.cpu_freq_limits.get(cpu);
+                    match field {
+                        "min_freq" => values.extend(limits.map(|(min, _)| *min as f64)),
+                        "max_freq" => values.extend(limits.map(|(_, max)| *max as f64)),
+                        _ => event_values(&cpu_data.data, field, history, &mut values),
+                    }
+                }
+            }
+            Scope::Llc => {
+                for (llc, llc_data) in ctx.llc_data {
+                    if metric.selector.matches(*llc as i64, None) {
+                        event_values(&llc_data.data, field, history, &mut values);
+                    }
+                }
+            }
+            Scope::Node => {
+                for (node, node_data) in ctx.node_data {
+                    if metric.selector.matches(*node as i64, None) {
+                        event_values(&node_data.data, field, history, &mut values);
+                    }
+                }
+            }
+            Scope::Proc => {
+                for (tgid, proc_data) in ctx.proc_data {
+                    if !metric
+                        .selector
+                        .matches(*tgid as i64, Some(&proc_data.process_name))
+                    {
+                        continue;
+                    }
+                    match field {
+                        "cpu_util_perc" => values.push(proc_data.cpu_util_perc),
+                        "num_threads" => values.push(proc_data.num_threads as f64),
+                        _ => event_values(&proc_data.data, field, history, &mut values),
+                    }
+                }
+            }
+        }
+        values
+    }
+}
+
+/// Adds the latest value or the history of values of an event.
+fn event_values(data: &EventData, event: &str, history: bool, values: &mut Vec<f64>) {
+    let Some(event_data) = data.data.get(event) else {
+        return;
+    };
+    if history {
+        values.extend(event_data.iter().map(|v| *v as f64));
+    } else {
+        values.extend(event_data.back().map(|v| *v as f64));
+    }
+}
+
+/// Logs a fired alert and runs its syslog, notify and command actions.
+pub fn run_alert_actions(alert: &FiredAlert) {
+    warn!("{}", alert.message);
+    for action in &alert.actions {
+        if let Err(e) = action.run(alert) {
+            warn!("alert {} failed to run {action}: {e}", alert.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use procfs::process::ProcState;
+
+    fn proc_data(tgid: i32, name: &str, lat_us: &[u64]) -> ProcData {
+        let mut data = EventData::new(100);
+        for lat in lat_us {
+            data.add_event_data("lat_us", *lat);
+        }
+        ProcData {
+            tgid,
+            process_name: name.to_string(),
+            cpu: 0,
+            llc: None,
+            node: None,
+            dsq: None,
+            layer_id: None,
+            prev_cpu_time: 0,
+            current_cpu_time: 0,
+            cpu_util_perc: 0.0,
+            state: ProcState::Running,
+            cmdline: vec![],
+            threads: BTreeMap::new(),
+            num_threads: 1,
+            data,
+            max_data_size: 100,
+        }
+    }
+
+    struct TestData {
+        cpu_data: BTreeMap<usize, CpuData>,
+        llc_data: BTreeMap<usize, LlcData>,
+        node_data: BTreeMap<usize, NodeData>,
+        proc_data: BTreeMap<i32, ProcData>,
+        scheduler: String,
+    }
+
+    impl TestData {
+        fn new() -> Self {
+            let mut llc_data = BTreeMap::new();
+            for (llc, util) in [(0, 90), (1, 20)] {
+                let mut data = LlcData::new(llc, 0, 4, 10);
+                data.add_event_data("cpu_total_util_percent", util);
+                llc_data.insert(llc, data);
+            }
+            let mut cpu_data = BTreeMap::new();
+            for cpu in 0..2 {
+                let mut data = CpuData::new(cpu, cpu, 0, 0, 10);
+                data.add_event_data("cpu_freq", 800_000);
+                cpu_data.insert(cpu, data);
+            }
+            let proc_data = BTreeMap::from([
+                (100, proc_data(100, "nginx", &[100, 200, 9_000])),
+                (200, proc_data(200, "bash", &[10])),
+            ]);
+            Self {
+                cpu_data,
+                llc_data,
+                node_data: BTreeMap::new(),
+                proc_data,
+                scheduler: "scx_test".to_string(),
+            }
+        }
+
+        fn ctx(&self) -> AlertContext<'_> {
+            AlertContext {
+                cpu_data: &self.cpu_data,
+                llc_data: &self.llc_data,
+                node_data: &self.node_data,
+                proc_data: &self.proc_data,
+                scheduler: &self.scheduler,
+            }
+        }
+    }
+
+    fn rule(condition: &str, for_ms: u64) -> AlertRule {
+        AlertRule {
+            name: "test".to_string(),
+            condition: condition.to_string(),
+            for_ms,
+            actions: default_actions(),
+        }
+    }
+
+    fn holds(condition: &str, data: &TestData) -> bool {
+        let mut engine = AlertEngine::new(&[rule(condition, 0)]).unwrap();
+        engine.set_cpu_freq_limits(0, 800_000, 3_000_000);
+        engine.set_cpu_freq_limits(1, 800_000, 3_000_000);
+        !engine.evaluate(&data.ctx(), Instant::now()).is_empty()
+    }
+
+    #[test]
+    fn test_parse_condition() {
+        let cond: Condition = "p99(proc[nginx].lat_us) > 5000 || scheduler_loaded == 0"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            cond,
+            Condition::Or(
+                Box::new(Condition::Compare(
+                    Operand::Metric(Metric {
+                        aggregation: Aggregation::Percentile(99.0),
+                        scope: Scope::Proc,
+                        selector: Selector::Name("nginx".to_string()),
+                        field: "lat_us".to_string(),
+                    }),
+                    CmpOp::Gt,
+                    Operand::Value(5000.0),
+                )),
+                Box::new(Condition::Compare(
+                    Operand::SchedulerLoaded,
+                    CmpOp::Eq,
+                    Operand::Value(0.0),
+                )),
+            )
+        );
+
+        assert!("max(cpu.cpu_freq)".parse::<Condition>().is_err());
+        assert!("median(cpu.cpu_freq) > 1".parse::<Condition>().is_err());
+        assert!("max(cpu[a].cpu_freq) > 1".parse::<Condition>().is_err());
+        assert!("max(cpu.cpu_freq) > 1 &".parse::<Condition>().is_err());
+        assert!("(max(cpu.cpu_freq) > 1".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let rule: AlertRule = toml::from_str(
+            r#"
+            name = "lat"
+            condition = "p99(proc[nginx].lat_us) > 5000"
+            actions = ["syslog", "command:echo $SCXTOP_ALERT"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            rule.actions,
+            vec![
+                AlertAction::Syslog,
+                AlertAction::Command("echo $SCXTOP_ALERT".to_string())
+            ]
+        );
+        assert_eq!(rule.for_ms, 0);
+        assert!("page".parse::<AlertAction>().is_err());
+        assert_eq!(
+            AlertAction::Command("ls".to_string()).to_string(),
+            "command:ls"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let data = TestData::new();
+        assert!(holds("spread(llc.cpu_total_util_percent) > 50", &data));
+        assert!(!holds("spread(llc[0].cpu_total_util_percent) > 50", &data));
+        assert!(holds("max(cpu.cpu_freq) <= min(cpu.min_freq)", &data));
+        assert!(holds("p99(proc[nginx].lat_us) > 5000", &data));
+        assert!(!holds("p99(proc[bash].lat_us) > 5000", &data));
+        assert!(holds("max(proc[200].lat_us) == 10", &data));
+        assert!(!holds("scheduler_loaded == 0", &data));
+        assert!(holds(
+            "scheduler_loaded == 0 || count(proc.lat_us) == 2",
+            &data
+        ));
+        assert!(!holds(
+            "scheduler_loaded == 1 && avg(cpu.missing) > 0",
+            &data
+        ));
+        assert!(holds("count(proc[missing].lat_us) == 0", &data));
+    }
+
+    #[test]
+    fn test_samples() {
+        let samples = |condition: &str| AlertEngine::new(&[rule(condition, 0)]).unwrap().samples();
+        assert_eq!(
+            samples("p99(proc[nginx].lat_us) > 5000"),
+            Samples::default()
+        );
+        assert_eq!(samples("scheduler_loaded == 0"), Samples::default());
+        assert_eq!(
+            samples("max(cpu.cpu_freq) <= min(cpu.min_freq)"),
+            Samples {
+                cpu_stats: true,
+                cpu_freq: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            samples("spread(llc.cpu_total_util_percent) > 30 || max(proc.num_threads) > 100"),
+            Samples {
+                cpu_stats: true,
+                prof_events: true,
+                cpu_freq: false,
+                procs: true,
+            }
+        );
+        assert_eq!(
+            samples("max(cpu.dsq_lat_us) > 10 && sum(cpu.hw_pressure) > 0"),
+            Samples::default()
+        );
+
+        let engine = AlertEngine::new(&[
+            rule("max(cpu.cpu_freq) > 0", 0),
+            rule("avg(proc.cpu_util_perc) > 90", 0),
+        ])
+        .unwrap();
+        assert_eq!(
+            engine.samples(),
+            Samples {
+                cpu_stats: true,
+                cpu_freq: true,
+                prof_events: false,
+                procs: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_evaluate_for_duration() {
+        let mut data = TestData::new();
+        let mut engine = AlertEngine::new(&[rule("scheduler_loaded == 0", 5000)]).unwrap();
+        let start = Instant::now();
+
+        data.scheduler.clear();
+        assert!(engine.evaluate(&data.ctx(), start).is_empty());
+        assert!(engine
+            .evaluate(&data.ctx(), start + Duration::from_millis(4000))
+            .is_empty());
+        let fired = engine.evaluate(&data.ctx(), start + Duration::from_millis(5000));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].name, "test");
+        assert_eq!(engine.highlighted().collect::<Vec<_>>(), vec!["test"]);
+
+        // An active alert doesn't fire again until it is resolved
+        assert!(engine
+            .evaluate(&data.ctx(), start + Duration::from_millis(6000))
+            .is_empty());
+        data.scheduler = "scx_test".to_string();
+        assert!(engine
+            .evaluate(&data.ctx(), start + Duration::from_millis(7000))
+            .is_empty());
+        assert_eq!(engine.highlighted().count(), 0);
+    }
+}
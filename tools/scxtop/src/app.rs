@@ -3,12 +3,14 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use crate::alerts::{run_alert_actions, AlertAction, AlertContext, AlertEngine, Samples};
 use crate::available_kprobe_events;
 use crate::available_perf_events;
 use crate::bpf_intf;
 use crate::bpf_prog_data::{BpfProgData, BpfProgStats};
 use crate::bpf_skel::BpfSkel;
 use crate::bpf_stats::BpfStats;
+use crate::cgroup_data::CGROUP_ROOT;
 use crate::columns::{
     get_bpf_program_columns, get_perf_top_columns, get_perf_top_columns_no_bpf,
     get_process_columns, get_process_columns_no_bpf, get_thread_columns, get_thread_columns_no_bpf,
@@ -18,12 +20,17 @@ use crate::config::get_config_path;
 use crate::config::Config;
 use crate::get_default_events;
 use crate::render::bpf_programs::{ProgramDetailParams, ProgramsListParams};
+use crate::render::cgroup::{CgroupMembersParams, CgroupTreeParams};
+use crate::render::latency::LatencyViewParams;
 use crate::render::scheduler::{DsqSummaryParams, ProcessLatencyParams, SchedulerViewParams};
 use crate::render::{
-    BandwidthRenderer, BpfProgramRenderer, MemoryRenderer, NetworkRenderer, ProcessRenderer,
-    SchedulerRenderer,
+    BandwidthRenderer, BpfProgramRenderer, CgroupRenderer, LatencyRenderer, MemoryRenderer,
+    NetworkRenderer, ProcessRenderer, SchedulerRenderer,
 };
 use crate::search;
+use crate::snapshot::{
+    CpuSnapshot, LlcSnapshot, NodeSnapshot, SchedulerSnapshot, Snapshot, SnapshotField,
+};
 use crate::symbol_data::SymbolData;
 use crate::util::{
     check_perf_capability, default_scxtop_sched_ext_stats, format_hz, read_file_string,
@@ -31,6 +38,7 @@ use crate::util::{
 };
 use crate::AppState;
 use crate::AppTheme;
+use crate::CgroupTree;
 use crate::ComponentViewState;
 use crate::CpuData;
 use crate::CpuStatTracker;
@@ -38,6 +46,7 @@ use crate::EventData;
 use crate::FilterItem;
 use crate::FilteredState;
 use crate::KprobeEvent;
+use crate::LatencyTracker;
 use crate::LlcData;
 use crate::MemStatSnapshot;
 use crate::NetworkStatSnapshot;
@@ -98,6 +107,18 @@ use std::path::Path;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex as StdMutex, RwLock};
+use std::time::{Instant, SystemTime};
+
+/// Data built from scheduler events, saved while replaying a trace.
+#[derive(Clone, Debug)]
+pub struct EventDataSnapshot {
+    cpu_data: BTreeMap<usize, EventData>,
+    llc_data: BTreeMap<usize, EventData>,
+    node_data: BTreeMap<usize, EventData>,
+    dsq_data: BTreeMap<u64, EventData>,
+    proc_data: BTreeMap<i32, ProcData>,
+    latency_tracker: LatencyTracker,
+}
 
 /// App is the struct for scxtop application state.
 pub struct App<'a> {
@@ -197,6 +218,29 @@ pub struct App<'a> {
     proc_latency_table_state: TableState,
     proc_latency_row_count: usize,
 
+    // cgroup view, selected_cgroup is set when drilled down into member processes
+    cgroup_tree: CgroupTree,
+    cgroup_table_state: TableState,
+    cgroup_row_count: usize,
+    cgroup_member_table_state: TableState,
+    cgroup_member_row_count: usize,
+    selected_cgroup: Option<u64>,
+
+    // latency view, histograms are only updated while the view is active
+    latency_tracker: LatencyTracker,
+    latency_table_state: TableState,
+    latency_row_count: usize,
+
+    // status line of the replayed trace when running with --replay
+    replay_status: Option<String>,
+    // set when the events come from a collector attached with --connect
+    remote: bool,
+
+    // alert rules evaluated on every tick
+    alert_engine: AlertEngine,
+    // data sampled by the view during the current tick
+    tick_samples: Samples,
+
     // layout related
     events_list_size: u16,
 
@@ -240,6 +284,15 @@ impl<'a> App<'a> {
         skel: BpfSkel<'a>,
     ) -> Result<Self> {
         let topo = Topology::new()?;
+        let mut alert_engine = AlertEngine::new(config.alerts())?;
+        for cpu in topo.all_cpus.values() {
+            // cpu_freq is recorded in Hz
+            alert_engine.set_cpu_freq_limits(
+                cpu.id,
+                cpu.min_freq as u64 * 1000,
+                cpu.max_freq as u64 * 1000,
+            );
+        }
         let mut cpu_data = BTreeMap::new();
         let mut llc_data = BTreeMap::new();
         let mut node_data = BTreeMap::new();
@@ -450,6 +503,19 @@ impl<'a> App<'a> {
             dsq_summary_row_count: 0,
             proc_latency_table_state: TableState::default(),
             proc_latency_row_count: 0,
+            cgroup_tree: CgroupTree::new(CGROUP_ROOT, max_cpu_events),
+            cgroup_table_state: TableState::default(),
+            cgroup_row_count: 0,
+            cgroup_member_table_state: TableState::default(),
+            cgroup_member_row_count: 0,
+            selected_cgroup: None,
+            latency_tracker: LatencyTracker::new(max_cpu_events),
+            latency_table_state: TableState::default(),
+            latency_row_count: 0,
+            replay_status: None,
+            remote: false,
+            alert_engine,
+            tick_samples: Samples::default(),
             events_list_size: 1,
             prev_bpf_sample_rate: sample_rate,
             trace_start: 0,
@@ -497,6 +563,15 @@ impl<'a> App<'a> {
         action_tx: UnboundedSender<Action>,
     ) -> Result<Self> {
         let topo = Topology::new()?;
+        let mut alert_engine = AlertEngine::new(config.alerts())?;
+        for cpu in topo.all_cpus.values() {
+            // cpu_freq is recorded in Hz
+            alert_engine.set_cpu_freq_limits(
+                cpu.id,
+                cpu.min_freq as u64 * 1000,
+                cpu.max_freq as u64 * 1000,
+            );
+        }
         let mut cpu_data = BTreeMap::new();
         let mut llc_data = BTreeMap::new();
         let mut node_data = BTreeMap::new();
@@ -716,6 +791,19 @@ impl<'a> App<'a> {
             dsq_summary_row_count: 0,
             proc_latency_table_state: TableState::default(),
             proc_latency_row_count: 0,
+            cgroup_tree: CgroupTree::new(CGROUP_ROOT, max_cpu_events),
+            cgroup_table_state: TableState::default(),
+            cgroup_row_count: 0,
+            cgroup_member_table_state: TableState::default(),
+            cgroup_member_row_count: 0,
+            selected_cgroup: None,
+            latency_tracker: LatencyTracker::new(max_cpu_events),
+            latency_table_state: TableState::default(),
+            latency_row_count: 0,
+            replay_status: None,
+            remote: false,
+            alert_engine,
+            tick_samples: Samples::default(),
             events_list_size: 1,
             prev_bpf_sample_rate: sample_rate,
             trace_start: 0,
@@ -878,6 +966,66 @@ impl<'a> App<'a> {
         self.capability_warnings = warnings;
     }
 
+    /// Sets the status line of a replayed trace, None hides it.
+    pub fn set_replay_status(&mut self, status: Option<String>) {
+        self.replay_status = status;
+    }
+
+    /// Marks the events as coming from a remote collector.
+    pub fn set_remote(&mut self, remote: bool) {
+        self.remote = remote;
+    }
+
+    /// Returns true if the events don't come from the local system, in which case the
+    /// local system isn't sampled as its stats would be mixed with the events.
+    fn external_events(&self) -> bool {
+        self.replay_status.is_some() || self.remote
+    }
+
+    /// Renders the replay status in the bottom right corner of the screen
+    fn render_replay_status(&self, frame: &mut Frame) {
+        let Some(status) = &self.replay_status else {
+            return;
+        };
+        let area = frame.area();
+        let width = (status.chars().count() as u16 + 2).min(area.width);
+        if area.height == 0 || width == 0 {
+            return;
+        }
+        let status_area = Rect::new(
+            area.x + area.width - width,
+            area.y + area.height - 1,
+            width,
+            1,
+        );
+        let paragraph = Paragraph::new(format!(" {status} "))
+            .style(self.theme().text_important_color())
+            .alignment(Alignment::Right);
+        frame.render_widget(Clear, status_area);
+        frame.render_widget(paragraph, status_area);
+    }
+
+    /// Renders the highlighted alerts in the top right corner.
+    fn render_alerts(&self, frame: &mut Frame) {
+        let alerts: Vec<&str> = self.alert_engine.highlighted().collect();
+        if alerts.is_empty() {
+            return;
+        }
+        let text = format!(" ALERT: {} ", alerts.join(", "));
+        let area = frame.area();
+        let width = (text.chars().count() as u16).min(area.width);
+        if area.height == 0 || width == 0 {
+            return;
+        }
+        let alert_area = Rect::new(area.x + area.width - width, area.y, width, 1);
+        let paragraph = Paragraph::new(text)
+            .style(self.theme().text_important_color())
+            .bold()
+            .reversed();
+        frame.render_widget(Clear, alert_area);
+        frame.render_widget(paragraph, alert_area);
+    }
+
     /// Returns capability warnings
     pub fn get_capability_warnings(&self) -> &Vec<String> {
         &self.capability_warnings
@@ -1033,6 +1181,7 @@ impl<'a> App<'a> {
                 .expect("CpuData should have been present");
             cpu_data.add_event_data("cpu_freq", data.freq_khz * 1000);
         }
+        self.tick_samples.cpu_freq = true;
         Ok(())
     }
 
@@ -1122,8 +1271,14 @@ impl<'a> App<'a> {
     /// Runs callbacks to update application state on tick.
     /// Uses view-specific data collection to optimize performance.
     fn on_tick(&mut self) -> Result<()> {
+        self.tick_samples = Samples::default();
+        if self.external_events() {
+            return self.on_tick_external_events();
+        }
         match self.state {
             AppState::Bandwidth => self.on_tick_bandwidth(),
+            AppState::Cgroup => self.on_tick_cgroup(),
+            AppState::Latency => self.on_tick_latency(),
             AppState::BpfProgramDetail => self.on_tick_bpf_program_detail(),
             AppState::BpfPrograms => self.on_tick_bpf_programs(),
             AppState::Default => self.on_tick_default(),
@@ -1141,6 +1296,53 @@ impl<'a> App<'a> {
         }
     }
 
+    /// Samples the data read by the alert rules that the view didn't sample on this tick.
+    fn sample_alert_data(&mut self) -> Result<()> {
+        let needed = self.alert_engine.samples();
+        if needed.cpu_stats && !self.tick_samples.cpu_stats {
+            self.update_cpu_stats()?;
+        }
+        if needed.cpu_freq && !self.tick_samples.cpu_freq && self.collect_cpu_freq {
+            self.record_cpu_freq()?;
+        }
+        if needed.prof_events && !self.tick_samples.prof_events {
+            self.record_prof_events()?;
+        }
+        if needed.procs && !self.tick_samples.procs {
+            self.update_all_process_data()?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates the alert rules and runs the actions of the alerts that fired.
+    fn on_tick_alerts(&mut self) {
+        if self.alert_engine.is_empty() {
+            return;
+        }
+        // Replayed and remote events are evaluated without the local system data
+        if !self.external_events() {
+            if let Err(e) = self.sample_alert_data() {
+                log::warn!("failed to sample the alert data: {e}");
+            }
+        }
+        let ctx = AlertContext {
+            cpu_data: &self.cpu_data,
+            llc_data: &self.llc_data,
+            node_data: &self.node_data,
+            proc_data: &self.proc_data,
+            scheduler: &self.scheduler,
+        };
+        let fired = self.alert_engine.evaluate(&ctx, Instant::now());
+        for alert in fired {
+            run_alert_actions(&alert);
+            if alert.actions.contains(&AlertAction::Trace) {
+                if let Err(e) = self.request_start_trace() {
+                    log::warn!("alert {} failed to start a trace: {e}", alert.name);
+                }
+            }
+        }
+    }
+
     /// Filters BPF programs based on the current filter input
     fn filter_bpf_programs(&mut self) {
         self.filtered_bpf_programs.clear();
@@ -2547,8 +2749,94 @@ impl<'a> App<'a> {
         )
     }
 
+    /// Returns the table state and row count of the active cgroup view table.
+    fn cgroup_table_state(&mut self) -> (&mut TableState, usize) {
+        if self.selected_cgroup.is_some() {
+            (
+                &mut self.cgroup_member_table_state,
+                self.cgroup_member_row_count,
+            )
+        } else {
+            (&mut self.cgroup_table_state, self.cgroup_row_count)
+        }
+    }
+
+    /// Renders the cgroup application state.
+    fn render_cgroup(&mut self, frame: &mut Frame) -> Result<()> {
+        let area = frame.area();
+        let theme = self.config.theme().clone();
+
+        if let Some(cgroup) = self
+            .selected_cgroup
+            .and_then(|id| self.cgroup_tree.cgroups.get(&id))
+        {
+            let params = CgroupMembersParams {
+                cgroup,
+                proc_data: &self.proc_data,
+                theme: &theme,
+            };
+            self.cgroup_member_row_count = CgroupRenderer::render_cgroup_members(
+                frame,
+                area,
+                &params,
+                &mut self.cgroup_member_table_state,
+            )?;
+        } else {
+            let params = CgroupTreeParams {
+                cgroup_tree: &self.cgroup_tree,
+                theme: &theme,
+            };
+            self.cgroup_row_count = CgroupRenderer::render_cgroup_tree(
+                frame,
+                area,
+                &params,
+                &mut self.cgroup_table_state,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders the latency histogram application state.
+    fn render_latency(&mut self, frame: &mut Frame) -> Result<()> {
+        let theme = self.config.theme().clone();
+        let params = LatencyViewParams {
+            tracker: &self.latency_tracker,
+            theme: &theme,
+        };
+        self.latency_row_count = LatencyRenderer::render_latency_view(
+            frame,
+            frame.area(),
+            &params,
+            &mut self.latency_table_state,
+        )?;
+
+        Ok(())
+    }
+
+    /// Writes the latency histograms and heatmap to a JSON file.
+    fn export_latency(&self) -> Result<()> {
+        let secs = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let path = format!("{}_latency_{}.json", self.config.trace_file_prefix(), secs);
+        let export = self.latency_tracker.export();
+        std::fs::write(&path, serde_json::to_string_pretty(&export)?)?;
+        log::info!("Exported latency histograms to {path}");
+
+        Ok(())
+    }
+
     /// Renders the application to the frame.
     pub fn render(&mut self, frame: &mut Frame) -> Result<()> {
+        self.render_state(frame)?;
+        self.render_replay_status(frame);
+        self.render_alerts(frame);
+        Ok(())
+    }
+
+    /// Renders the view of the current state.
+    fn render_state(&mut self, frame: &mut Frame) -> Result<()> {
         let area = frame.area();
         // Update terminal width for overhead history sizing
         self.terminal_width = area.width;
@@ -2556,6 +2844,8 @@ impl<'a> App<'a> {
             AppState::Bandwidth => self.render_bandwidth(frame),
             AppState::BpfPrograms => self.render_bpf_programs(frame),
             AppState::BpfProgramDetail => self.render_bpf_program_detail(frame),
+            AppState::Cgroup => self.render_cgroup(frame),
+            AppState::Latency => self.render_latency(frame),
             AppState::Help => self.render_help(frame),
             AppState::PerfEvent | AppState::KprobeEvent => self.render_event_list(frame),
             AppState::Process => self.render_table(frame, area, true),
@@ -2660,7 +2950,7 @@ impl<'a> App<'a> {
         if pause == " " {
             pause = "Space".to_string();
         }
-        let text = vec![
+        let mut text = vec![
             Line::from(Span::styled(
                 LICENSE,
                 Style::default().add_modifier(Modifier::ITALIC),
@@ -2858,6 +3148,33 @@ impl<'a> App<'a> {
                 ),
                 Style::default(),
             )),
+            Line::from(Span::styled(
+                format!(
+                    "{}: display cgroup view",
+                    self.config
+                        .active_keymap
+                        .action_keys_string(Action::SetState(AppState::Cgroup))
+                ),
+                Style::default(),
+            )),
+            Line::from(Span::styled(
+                format!(
+                    "{}: display latency histogram view",
+                    self.config
+                        .active_keymap
+                        .action_keys_string(Action::SetState(AppState::Latency))
+                ),
+                Style::default(),
+            )),
+            Line::from(Span::styled(
+                format!(
+                    "{}: export latency histograms to JSON",
+                    self.config
+                        .active_keymap
+                        .action_keys_string(Action::ExportLatency)
+                ),
+                Style::default(),
+            )),
             Line::from(Span::styled(
                 format!(
                     "{}: display Network view",
@@ -2994,6 +3311,35 @@ impl<'a> App<'a> {
                 ),
                 Style::default(),
             )),
+        ];
+        if self.replay_status.is_some() {
+            let keymap = &self.config.active_keymap;
+            text.extend([
+                "\n".into(),
+                Line::from(Span::styled("Replay Key Bindings:", Style::default())),
+                Line::from(Span::styled(
+                    format!("{pause}: play/pause the replay"),
+                    Style::default(),
+                )),
+                Line::from(Span::styled(
+                    format!(
+                        "{}/{}: slow down/speed up the replay",
+                        keymap.action_keys_string(Action::ReplaySlowDown),
+                        keymap.action_keys_string(Action::ReplaySpeedUp),
+                    ),
+                    Style::default(),
+                )),
+                Line::from(Span::styled(
+                    format!(
+                        "{}/{}: seek backward/forward in the replay",
+                        keymap.action_keys_string(Action::ReplaySeekBackward),
+                        keymap.action_keys_string(Action::ReplaySeekForward),
+                    ),
+                    Style::default(),
+                )),
+            ]);
+        }
+        text.extend([
             "\n".into(),
             Line::from(Span::styled(
                 "For bug reporting and project updates, visit:",
@@ -3003,7 +3349,7 @@ impl<'a> App<'a> {
                 "https://github.com/sched-ext/scx",
                 Style::default(),
             )),
-        ];
+        ]);
         frame.render_widget(
             Paragraph::new(text)
                 .block(
@@ -3985,6 +4331,17 @@ impl<'a> App<'a> {
                 self.perf_top_table_state
                     .select(Some(self.selected_symbol_index));
             }
+        } else if self.state == AppState::Cgroup {
+            let (table_state, row_count) = self.cgroup_table_state();
+            let max_index = row_count.saturating_sub(1);
+            let current = table_state.selected().unwrap_or(0);
+            let new_selected = if current < max_index { current + 1 } else { 0 };
+            table_state.select(Some(new_selected));
+        } else if self.state == AppState::Latency {
+            let max_index = self.latency_row_count.saturating_sub(1);
+            let current = self.latency_table_state.selected().unwrap_or(0);
+            let new_selected = if current < max_index { current + 1 } else { 0 };
+            self.latency_table_state.select(Some(new_selected));
         } else if self.state == AppState::Scheduler {
             // Scroll the process latency table (bottom pane)
             let max_index = self.proc_latency_row_count.saturating_sub(1);
@@ -4063,6 +4420,23 @@ impl<'a> App<'a> {
             }
             self.perf_top_table_state
                 .select(Some(self.selected_symbol_index));
+        } else if self.state == AppState::Cgroup {
+            let (table_state, row_count) = self.cgroup_table_state();
+            let current = table_state.selected().unwrap_or(0);
+            let new_selected = if current > 0 {
+                current - 1
+            } else {
+                row_count.saturating_sub(1)
+            };
+            table_state.select(Some(new_selected));
+        } else if self.state == AppState::Latency {
+            let current = self.latency_table_state.selected().unwrap_or(0);
+            let new_selected = if current > 0 {
+                current - 1
+            } else {
+                self.latency_row_count.saturating_sub(1)
+            };
+            self.latency_table_state.select(Some(new_selected));
         } else if self.state == AppState::Scheduler {
             let current = self.proc_latency_table_state.selected().unwrap_or(0);
             let new_selected = if current > 0 {
@@ -4140,6 +4514,18 @@ impl<'a> App<'a> {
             }
             self.perf_top_table_state
                 .select(Some(self.selected_symbol_index));
+        } else if self.state == AppState::Cgroup {
+            let page_size = 10;
+            let (table_state, row_count) = self.cgroup_table_state();
+            let max_index = row_count.saturating_sub(1);
+            let current = table_state.selected().unwrap_or(0);
+            table_state.select(Some((current + page_size).min(max_index)));
+        } else if self.state == AppState::Latency {
+            let page_size = 10;
+            let max_index = self.latency_row_count.saturating_sub(1);
+            let current = self.latency_table_state.selected().unwrap_or(0);
+            self.latency_table_state
+                .select(Some((current + page_size).min(max_index)));
         } else if self.state == AppState::Scheduler {
             let page_size = 10;
             let max_index = self.proc_latency_row_count.saturating_sub(1);
@@ -4211,6 +4597,16 @@ impl<'a> App<'a> {
             }
             self.perf_top_table_state
                 .select(Some(self.selected_symbol_index));
+        } else if self.state == AppState::Cgroup {
+            let page_size = 10;
+            let (table_state, _) = self.cgroup_table_state();
+            let current = table_state.selected().unwrap_or(0);
+            table_state.select(Some(current.saturating_sub(page_size)));
+        } else if self.state == AppState::Latency {
+            let page_size = 10;
+            let current = self.latency_table_state.selected().unwrap_or(0);
+            self.latency_table_state
+                .select(Some(current.saturating_sub(page_size)));
         } else if self.state == AppState::Scheduler {
             let page_size = 10;
             let current = self.proc_latency_table_state.selected().unwrap_or(0);
@@ -4366,6 +4762,14 @@ impl<'a> App<'a> {
                 self.apply_dsq_filter();
                 self.filtering = false;
             }
+            AppState::Cgroup if self.selected_cgroup.is_none() => {
+                // Drill down into the member processes of the selected cgroup
+                let selected = self.cgroup_table_state.selected().unwrap_or(0);
+                if let Some(cgroup) = self.cgroup_tree.tree_order().get(selected) {
+                    self.selected_cgroup = Some(cgroup.id);
+                    self.cgroup_member_table_state.select(Some(0));
+                }
+            }
             _ => {
                 // Handle other states (Help, MangoApp, Memory, Pause, Scheduler, etc.)
                 // For these states, do nothing on Enter
@@ -4382,6 +4786,13 @@ impl<'a> App<'a> {
 
     fn on_escape(&mut self) -> Result<()> {
         match self.state() {
+            AppState::Cgroup => {
+                if self.selected_cgroup.is_some() {
+                    self.selected_cgroup = None;
+                } else {
+                    self.handle_action(&Action::Quit)?;
+                }
+            }
             AppState::Scheduler => {
                 if self.filtering {
                     self.filtering = false;
@@ -4709,6 +5120,12 @@ impl<'a> App<'a> {
             }
         }
 
+        if self.state == AppState::Cgroup {
+            self.cgroup_tree.on_sched_wakeup(action);
+        }
+        if self.state == AppState::Latency {
+            self.latency_tracker.on_sched_wakeup(action);
+        }
         if self.state == AppState::Tracing && action.ts > self.trace_start {
             self.trace_manager.on_sched_wakeup(action);
         }
@@ -4746,6 +5163,12 @@ impl<'a> App<'a> {
 
     /// Updates the app when a task is scheduled.
     fn on_sched_switch(&mut self, action: &SchedSwitchAction) {
+        if self.state == AppState::Cgroup {
+            self.cgroup_tree.on_sched_switch(action);
+        }
+        if self.state == AppState::Latency {
+            self.latency_tracker.on_sched_switch(action);
+        }
         let SchedSwitchAction {
             cpu,
             next_dsq_id,
@@ -4919,6 +5342,9 @@ impl<'a> App<'a> {
     }
 
     fn on_sched_migrate(&mut self, action: &SchedMigrateTaskAction) {
+        if self.state == AppState::Cgroup {
+            self.cgroup_tree.on_sched_migrate(action);
+        }
         if self.state == AppState::Tracing && action.ts > self.trace_start {
             self.trace_manager.on_sched_migrate(action);
         }
@@ -5509,6 +5935,7 @@ impl<'a> App<'a> {
         match action {
             Action::Tick => {
                 self.on_tick()?;
+                self.on_tick_alerts();
             }
             Action::Down => self.on_down(),
             Action::Up => self.on_up(),
@@ -5594,6 +6021,9 @@ impl<'a> App<'a> {
             Action::SaveConfig => {
                 self.on_save_config()?;
             }
+            Action::ExportLatency => {
+                self.export_latency()?;
+            }
             Action::SchedSwitch(a) => {
                 self.on_sched_switch(a);
             }
@@ -5655,6 +6085,10 @@ impl<'a> App<'a> {
                         self.selected_symbol_index = 0;
                         self.filter_symbols(); // Update filtered symbols after clearing
                     }
+                    AppState::Latency => {
+                        self.latency_tracker.clear();
+                        self.latency_table_state.select(Some(0));
+                    }
                     _ => {
                         self.reset_prof_events()?;
                     }
@@ -5749,6 +6183,9 @@ impl<'a> App<'a> {
                 {
                     self.in_thread_view = false;
                 }
+                AppState::Cgroup if self.selected_cgroup.is_some() => {
+                    self.selected_cgroup = None;
+                }
                 _ => {
                     self.should_quit.store(true, Ordering::Relaxed);
                 }
@@ -7207,6 +7644,7 @@ impl<'a> App<'a> {
         for key in to_remove {
             self.proc_data.remove(&key);
         }
+        self.tick_samples.procs = true;
 
         Ok(())
     }
@@ -7218,6 +7656,38 @@ impl<'a> App<'a> {
             .write()
             .unwrap()
             .update(&mut system_guard)?;
+        self.tick_samples.cpu_stats = true;
+        Ok(())
+    }
+
+    /// Records the values of the profiling events, rolled up per LLC and node.
+    fn record_prof_events(&mut self) -> Result<()> {
+        for node_data in self.node_data.values_mut() {
+            node_data.add_event_data(self.active_event.event_name(), 0);
+        }
+        for llc_data in self.llc_data.values_mut() {
+            llc_data.add_event_data(self.active_event.event_name(), 0);
+        }
+
+        for (cpu, event) in &mut self.active_prof_events {
+            let val = event.value(true)?;
+            let cpu_data = self
+                .cpu_data
+                .get_mut(cpu)
+                .expect("CpuData should have been present");
+            cpu_data.add_event_data(event.event_name(), val);
+            let llc_data = self
+                .llc_data
+                .get_mut(&cpu_data.llc)
+                .expect("LlcData should have been present");
+            llc_data.add_cpu_event_data(event.event_name(), val);
+            let node_data = self
+                .node_data
+                .get_mut(&cpu_data.node)
+                .expect("NodeData should have been present");
+            node_data.add_cpu_event_data(event.event_name(), val);
+        }
+        self.tick_samples.prof_events = true;
         Ok(())
     }
 
@@ -7247,40 +7717,7 @@ impl<'a> App<'a> {
             proc_data.update_threads(system_util, num_cpus);
         }
 
-        for node in self.topo.nodes.keys() {
-            let node_data = self
-                .node_data
-                .get_mut(node)
-                .expect("NodeData should have been present");
-            node_data.add_event_data(self.active_event.event_name(), 0);
-        }
-
-        for llc in self.topo.all_llcs.keys() {
-            let llc_data = self
-                .llc_data
-                .get_mut(llc)
-                .expect("LlcData should have been present");
-            llc_data.add_event_data(self.active_event.event_name(), 0);
-        }
-
-        for (cpu, event) in &mut self.active_prof_events {
-            let val = event.value(true)?;
-            let cpu_data = self
-                .cpu_data
-                .get_mut(cpu)
-                .expect("CpuData should have been present");
-            cpu_data.add_event_data(event.event_name(), val);
-            let llc_data = self
-                .llc_data
-                .get_mut(&cpu_data.llc)
-                .expect("LlcData should have been present");
-            llc_data.add_cpu_event_data(event.event_name(), val);
-            let node_data = self
-                .node_data
-                .get_mut(&cpu_data.node)
-                .expect("NodeData should have been present");
-            node_data.add_cpu_event_data(event.event_name(), val);
-        }
+        self.record_prof_events()?;
 
         if self.collect_cpu_freq {
             self.record_cpu_freq()?;
@@ -7405,27 +7842,7 @@ impl<'a> App<'a> {
             self.bpf_stats = BpfStats::get_from_skel(skel)?;
         }
 
-        for llc in self.topo.all_llcs.keys() {
-            let llc_data = self
-                .llc_data
-                .get_mut(llc)
-                .expect("LlcData should have been present");
-            llc_data.add_event_data(self.active_event.event_name(), 0);
-        }
-
-        for (cpu, event) in &mut self.active_prof_events {
-            let val = event.value(true)?;
-            let cpu_data = self
-                .cpu_data
-                .get_mut(cpu)
-                .expect("CpuData should have been present");
-            cpu_data.add_event_data(event.event_name(), val);
-            let llc_data = self
-                .llc_data
-                .get_mut(&cpu_data.llc)
-                .expect("LlcData should have been present");
-            llc_data.add_cpu_event_data(event.event_name(), val);
-        }
+        self.record_prof_events()?;
 
         if self.collect_cpu_freq {
             self.record_cpu_freq()?;
@@ -7453,27 +7870,7 @@ impl<'a> App<'a> {
             self.bpf_stats = BpfStats::get_from_skel(skel)?;
         }
 
-        for node in self.topo.nodes.keys() {
-            let node_data = self
-                .node_data
-                .get_mut(node)
-                .expect("NodeData should have been present");
-            node_data.add_event_data(self.active_event.event_name(), 0);
-        }
-
-        for (cpu, event) in &mut self.active_prof_events {
-            let val = event.value(true)?;
-            let cpu_data = self
-                .cpu_data
-                .get_mut(cpu)
-                .expect("CpuData should have been present");
-            cpu_data.add_event_data(event.event_name(), val);
-            let node_data = self
-                .node_data
-                .get_mut(&cpu_data.node)
-                .expect("NodeData should have been present");
-            node_data.add_cpu_event_data(event.event_name(), val);
-        }
+        self.record_prof_events()?;
 
         if self.collect_cpu_freq {
             self.record_cpu_freq()?;
@@ -7488,6 +7885,40 @@ impl<'a> App<'a> {
         Ok(())
     }
 
+    /// Latency view: rolls the heatmap and drops processes that exited
+    fn on_tick_latency(&mut self) -> Result<()> {
+        if let Some(ref mut skel) = self.skel {
+            self.bpf_stats = BpfStats::get_from_skel(skel)?;
+        }
+        self.latency_tracker.on_tick();
+        self.latency_tracker
+            .retain_processes(|tgid| Path::new(&format!("/proc/{tgid}")).exists());
+
+        Ok(())
+    }
+
+    /// Cgroup view: cgroup hierarchy and the processes of the drilled down cgroup
+    fn on_tick_cgroup(&mut self) -> Result<()> {
+        if let Some(ref mut skel) = self.skel {
+            self.bpf_stats = BpfStats::get_from_skel(skel)?;
+        }
+        if !self.cgroup_tree.is_available() {
+            return Ok(());
+        }
+        self.cgroup_tree.update(self.topo.all_cpus.len())?;
+
+        match self.selected_cgroup {
+            Some(id) if !self.cgroup_tree.cgroups.contains_key(&id) => {
+                // The cgroup was removed
+                self.selected_cgroup = None;
+            }
+            Some(_) => self.update_all_process_data()?,
+            None => {}
+        }
+
+        Ok(())
+    }
+
     /// Power view: power-specific data collection
     fn on_tick_power(&mut self) -> Result<()> {
         if self.has_capability_warnings() {
@@ -7581,14 +8012,7 @@ impl<'a> App<'a> {
             self.bpf_stats = BpfStats::get_from_skel(skel)?;
         }
 
-        for (cpu, event) in &mut self.active_prof_events {
-            let val = event.value(true)?;
-            let cpu_data = self
-                .cpu_data
-                .get_mut(cpu)
-                .expect("CpuData should have been present");
-            cpu_data.add_event_data(event.event_name(), val);
-        }
+        self.record_prof_events()?;
 
         if self.filtering() {
             self.filter_events();
@@ -7605,6 +8029,134 @@ impl<'a> App<'a> {
         Ok(())
     }
 
+    /// Collects the data of the selected snapshot fields and returns a snapshot of it.
+    pub fn collect_snapshot(
+        &mut self,
+        fields: &[SnapshotField],
+        max_procs: Option<usize>,
+    ) -> Result<Snapshot> {
+        self.on_tick_default()?;
+        let scheduler = fields.contains(&SnapshotField::Scheduler);
+        if scheduler && self.skel.is_some() {
+            self.on_tick_scheduler()?;
+        }
+
+        let mut snapshot = Snapshot::new();
+        for field in fields {
+            match field {
+                SnapshotField::Cpu => {
+                    snapshot.cpus = Some(self.cpu_data.values().map(CpuSnapshot::from).collect())
+                }
+                SnapshotField::Llc => {
+                    snapshot.llcs = Some(self.llc_data.values().map(LlcSnapshot::from).collect())
+                }
+                SnapshotField::Node => {
+                    snapshot.nodes = Some(self.node_data.values().map(NodeSnapshot::from).collect())
+                }
+                SnapshotField::Process => {
+                    snapshot.set_processes(self.proc_data.values(), max_procs)
+                }
+                SnapshotField::Scheduler => {
+                    snapshot.scheduler = Some(SchedulerSnapshot {
+                        name: self.scheduler.clone(),
+                        dropped_events: self.bpf_stats.dropped_events,
+                        select_cpu_fallback: self.scx_stats.select_cpu_fallback,
+                        dispatch_local_dsq_offline: self.scx_stats.dispatch_local_dsq_offline,
+                        dispatch_keep_last: self.scx_stats.dispatch_keep_last,
+                        enq_skip_exiting: self.scx_stats.enq_skip_exiting,
+                        enq_skip_migration_disabled: self.scx_stats.enq_skip_migration_disabled,
+                        stats: serde_json::from_str(&self.sched_stats_raw).ok(),
+                    })
+                }
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Replayed or remote events: only the views built from events are updated.
+    fn on_tick_external_events(&mut self) -> Result<()> {
+        for node_data in self.node_data.values_mut() {
+            node_data.add_event_data(self.active_event.event_name(), 0);
+        }
+        for llc_data in self.llc_data.values_mut() {
+            llc_data.add_event_data(self.active_event.event_name(), 0);
+        }
+        if self.state == AppState::Latency {
+            self.latency_tracker.on_tick();
+        }
+        if self.state != AppState::BpfPrograms {
+            self.filter_events();
+        }
+
+        Ok(())
+    }
+
+    /// Returns a copy of the data built from scheduler events.
+    pub fn event_data_snapshot(&self) -> EventDataSnapshot {
+        EventDataSnapshot {
+            cpu_data: self
+                .cpu_data
+                .iter()
+                .map(|(&cpu, cpu_data)| (cpu, cpu_data.data.clone()))
+                .collect(),
+            llc_data: self
+                .llc_data
+                .iter()
+                .map(|(&llc, llc_data)| (llc, llc_data.data.clone()))
+                .collect(),
+            node_data: self
+                .node_data
+                .iter()
+                .map(|(&node, node_data)| (node, node_data.data.clone()))
+                .collect(),
+            dsq_data: self.dsq_data.clone(),
+            proc_data: self.proc_data.clone(),
+            latency_tracker: self.latency_tracker.clone(),
+        }
+    }
+
+    /// Restores the data built from scheduler events, the views of a replay resume from the
+    /// closest snapshot when it seeks backwards.
+    pub fn restore_event_data(&mut self, snapshot: &EventDataSnapshot) {
+        self.reset_event_data();
+        for (cpu, data) in &snapshot.cpu_data {
+            if let Some(cpu_data) = self.cpu_data.get_mut(cpu) {
+                cpu_data.data = data.clone();
+            }
+        }
+        for (llc, data) in &snapshot.llc_data {
+            if let Some(llc_data) = self.llc_data.get_mut(llc) {
+                llc_data.data = data.clone();
+            }
+        }
+        for (node, data) in &snapshot.node_data {
+            if let Some(node_data) = self.node_data.get_mut(node) {
+                node_data.data = data.clone();
+            }
+        }
+        self.dsq_data = snapshot.dsq_data.clone();
+        self.proc_data = snapshot.proc_data.clone();
+        self.latency_tracker = snapshot.latency_tracker.clone();
+    }
+
+    /// Clears the data built from scheduler events, the views of a replay are rebuilt from the
+    /// start of the trace when it seeks backwards before the first snapshot.
+    pub fn reset_event_data(&mut self) {
+        for cpu_data in self.cpu_data.values_mut() {
+            cpu_data.data.clear();
+        }
+        for llc_data in self.llc_data.values_mut() {
+            llc_data.data.clear();
+        }
+        for node_data in self.node_data.values_mut() {
+            node_data.data.clear();
+        }
+        self.dsq_data.clear();
+        self.proc_data.clear();
+        self.latency_tracker.clear();
+    }
+
     /// Static views: minimal or no updates needed
     fn on_tick_static(&mut self) -> Result<()> {
         Ok(())
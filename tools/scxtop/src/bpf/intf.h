@@ -81,6 +81,7 @@ struct sched_switch_event {
 	u32  next_tgid;
 	int  next_prio;
 	int  next_layer_id;
+	u64  next_cgroup_id;
 	u8   prev_comm[MAX_COMM];
 	u64  prev_dsq_id;
 	u64  prev_used_slice_ns;
@@ -97,6 +98,7 @@ struct wakeup_event {
 	u32 tgid;
 	int prio;
 	u32 waker_pid;
+	u64 cgroup_id;
 	u8  comm[MAX_COMM];
 	u8  waker_comm[MAX_COMM];
 };
@@ -105,6 +107,7 @@ struct migrate_event {
 	u32 pid;
 	int prio;
 	u32 dest_cpu;
+	u64 cgroup_id;
 	u8  comm[MAX_COMM];
 };
 
@@ -545,6 +545,15 @@ static void record_real_comm(u8 *comm, struct task_struct *task)
 	}
 }
 
+/*
+ * Returns the id of the cgroup of a task on the default hierarchy, which is
+ * also the inode number of the cgroup directory.
+ */
+static __always_inline u64 task_cgroup_id(struct task_struct *p)
+{
+	return BPF_CORE_READ(p, cgroups, dfl_cgrp, kn, id);
+}
+
 static __always_inline int __on_sched_wakeup(struct task_struct *p)
 {
 	struct task_ctx	   *tctx;
@@ -600,6 +609,7 @@ static __always_inline int __on_sched_wakeup(struct task_struct *p)
 	event->event.wakeup.pid	 = p->pid;
 	event->event.wakeup.tgid = p->tgid;
 	event->event.wakeup.prio = (int)p->prio;
+	event->event.wakeup.cgroup_id = task_cgroup_id(p);
 	record_real_comm(event->event.wakeup.comm, p);
 
 	// Include waker information in the event
@@ -681,6 +691,7 @@ int BPF_PROG(on_sched_waking, struct task_struct *p)
 	event->event.wakeup.pid	 = p->pid;
 	event->event.wakeup.tgid = p->tgid;
 	event->event.wakeup.prio = (int)p->prio;
+	event->event.wakeup.cgroup_id = task_cgroup_id(p);
 	record_real_comm(event->event.wakeup.comm, p);
 
 	// Include waker information in the event
@@ -785,6 +796,8 @@ int BPF_PROG(on_sched_switch, bool preempt, struct task_struct *prev,
 			event->event.sched_switch.next_pid  = next->pid;
 			event->event.sched_switch.next_tgid = next->tgid;
 			event->event.sched_switch.next_prio = (int)next->prio;
+			event->event.sched_switch.next_cgroup_id =
+				task_cgroup_id(next);
 			// Pass wakeup timestamp directly so userspace doesn't
 			// need to correlate across ring buffers
 			event->event.sched_switch.next_wakeup_ts =
@@ -828,6 +841,7 @@ int BPF_PROG(on_sched_switch, bool preempt, struct task_struct *prev,
 			event->event.sched_switch.next_wakeup_ts  = 0;
 			event->event.sched_switch.next_pid	  = 0;
 			event->event.sched_switch.next_tgid	  = 0;
+			event->event.sched_switch.next_cgroup_id  = 0;
 		}
 
 		bpf_ringbuf_submit(event, 0);
@@ -874,6 +888,7 @@ int BPF_PROG(on_sched_migrate_task, struct task_struct *p, int dest_cpu)
 	event->event.migrate.pid      = p->pid;
 	event->event.migrate.dest_cpu = dest_cpu;
 	event->event.migrate.prio     = (int)p->prio;
+	event->event.migrate.cgroup_id = task_cgroup_id(p);
 
 	bpf_ringbuf_submit(event, 0);
 
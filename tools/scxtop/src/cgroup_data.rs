@@ -0,0 +1,460 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::EventData;
+use crate::{SchedMigrateTaskAction, SchedSwitchAction, SchedWakeupAction};
+
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Container for cgroup data.
+#[derive(Clone, Debug)]
+pub struct CgroupData {
+    /// Inode number of the cgroup directory, which is also the cgroup id of BPF events.
+    pub id: u64,
+    /// Path relative to the cgroup root ("/" for the root cgroup).
+    pub path: String,
+    pub parent: Option<u64>,
+    pub depth: usize,
+    /// cpu.weight, None when the cpu controller isn't enabled.
+    pub weight: Option<u64>,
+    /// cpu.max quota in usecs, None when unlimited.
+    pub max_quota_us: Option<u64>,
+    pub max_period_us: u64,
+    pub prev_usage_us: u64,
+    pub usage_us: u64,
+    pub cpu_util_perc: f64,
+    pub nr_throttled: u64,
+    pub throttled_us: u64,
+    /// Throttling events during the last interval.
+    pub nr_throttled_delta: u64,
+    /// Throttled time during the last interval.
+    pub throttled_us_delta: u64,
+    /// Migrations seen during the current interval.
+    pub nr_migrations: u64,
+    /// Wakeups seen during the current interval.
+    pub nr_wakeups: u64,
+    pub tgids: BTreeSet<i32>,
+    pub data: EventData,
+    pub max_data_size: usize,
+}
+
+impl CgroupData {
+    /// Creates a new CgroupData.
+    pub fn new(id: u64, path: &str, parent: Option<u64>, max_data_size: usize) -> CgroupData {
+        let depth = path.split('/').filter(|c| !c.is_empty()).count();
+
+        Self {
+            id,
+            path: path.to_string(),
+            parent,
+            depth,
+            weight: None,
+            max_quota_us: None,
+            max_period_us: 0,
+            prev_usage_us: 0,
+            usage_us: 0,
+            cpu_util_perc: 0.0,
+            nr_throttled: 0,
+            throttled_us: 0,
+            nr_throttled_delta: 0,
+            throttled_us_delta: 0,
+            nr_migrations: 0,
+            nr_wakeups: 0,
+            tgids: BTreeSet::new(),
+            data: EventData::new(max_data_size),
+            max_data_size,
+        }
+    }
+
+    /// Returns the last component of the cgroup path.
+    pub fn name(&self) -> &str {
+        if self.depth == 0 {
+            return "/";
+        }
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// Returns cpu.max formatted like the cgroup file.
+    pub fn cpu_max(&self) -> String {
+        match self.max_quota_us {
+            Some(quota) => format!("{} {}", quota, self.max_period_us),
+            None if self.max_period_us > 0 => format!("max {}", self.max_period_us),
+            None => "-".to_string(),
+        }
+    }
+
+    /// Updates the cgroup from its cgroupfs directory, interval_us is the time elapsed since
+    /// the previous update.
+    pub fn update(&mut self, dir: &Path, interval_us: u64, num_cpus: usize) -> Result<()> {
+        let cpu_stat = parse_cpu_stat(&fs::read_to_string(dir.join("cpu.stat"))?);
+        let usage_us = cpu_stat.get("usage_usec").copied().unwrap_or(0);
+        let nr_throttled = cpu_stat.get("nr_throttled").copied().unwrap_or(0);
+        let throttled_us = cpu_stat.get("throttled_usec").copied().unwrap_or(0);
+
+        if self.usage_us > 0 {
+            self.nr_throttled_delta = nr_throttled.saturating_sub(self.nr_throttled);
+            self.throttled_us_delta = throttled_us.saturating_sub(self.throttled_us);
+        }
+        self.prev_usage_us = std::mem::replace(&mut self.usage_us, usage_us);
+        self.nr_throttled = nr_throttled;
+        self.throttled_us = throttled_us;
+        self.set_cpu_util(interval_us, num_cpus);
+
+        self.weight = fs::read_to_string(dir.join("cpu.weight"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok());
+        if let Some((quota, period)) = fs::read_to_string(dir.join("cpu.max"))
+            .ok()
+            .and_then(|s| parse_cpu_max(&s))
+        {
+            self.max_quota_us = quota;
+            self.max_period_us = period;
+        }
+
+        self.tgids = fs::read_to_string(dir.join("cgroup.procs"))
+            .map(|s| s.lines().filter_map(|l| l.trim().parse().ok()).collect())
+            .unwrap_or_default();
+
+        let nr_migrations = std::mem::take(&mut self.nr_migrations);
+        self.add_event_data("migrations", nr_migrations);
+        let nr_wakeups = std::mem::take(&mut self.nr_wakeups);
+        self.add_event_data("wakeups", nr_wakeups);
+
+        Ok(())
+    }
+
+    fn set_cpu_util(&mut self, interval_us: u64, num_cpus: usize) {
+        self.cpu_util_perc = if self.prev_usage_us == 0 || interval_us == 0 || num_cpus == 0 {
+            0.0
+        } else {
+            let delta = self.usage_us.saturating_sub(self.prev_usage_us);
+            // Percentage of the whole system, like the LLC and node views
+            (delta as f64 / (interval_us as f64 * num_cpus as f64)) * 100.0
+        };
+    }
+
+    /// Returns the data for an event. Returns empty Vec if event doesn't exist.
+    pub fn event_data_immut(&self, event: &str) -> Vec<u64> {
+        self.data.event_data_immut(event)
+    }
+
+    /// Adds data for an event.
+    pub fn add_event_data(&mut self, event: &str, val: u64) {
+        self.data.add_event_data(event, val)
+    }
+}
+
+/// Parses a flat keyed cgroup file such as cpu.stat.
+pub fn parse_cpu_stat(content: &str) -> BTreeMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, val) = line.split_once(' ')?;
+            Some((key.to_string(), val.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parses cpu.max ("$MAX $PERIOD"), returning the quota (None for "max") and the period.
+pub fn parse_cpu_max(content: &str) -> Option<(Option<u64>, u64)> {
+    let mut fields = content.split_whitespace();
+    let quota = match fields.next()? {
+        "max" => None,
+        quota => Some(quota.parse().ok()?),
+    };
+    let period = fields.next()?.parse().ok()?;
+    Some((quota, period))
+}
+
+/// Cgroup v2 hierarchy with per-cgroup scheduling metrics.
+#[derive(Clone, Debug)]
+pub struct CgroupTree {
+    root: PathBuf,
+    pub cgroups: BTreeMap<u64, CgroupData>,
+    tgid_cgroup: HashMap<i32, u64>,
+    wakeup_ts: HashMap<u32, u64>,
+    last_update: Option<std::time::Instant>,
+    max_data_size: usize,
+}
+
+impl CgroupTree {
+    /// Creates a new CgroupTree for the cgroup v2 hierarchy mounted at root.
+    pub fn new(root: impl Into<PathBuf>, max_data_size: usize) -> CgroupTree {
+        Self {
+            root: root.into(),
+            cgroups: BTreeMap::new(),
+            tgid_cgroup: HashMap::new(),
+            wakeup_ts: HashMap::new(),
+            last_update: None,
+            max_data_size,
+        }
+    }
+
+    /// Returns true if a cgroup v2 hierarchy is mounted at the root.
+    pub fn is_available(&self) -> bool {
+        self.root.join("cgroup.controllers").exists()
+    }
+
+    /// Rescans the hierarchy and updates the metrics of all the cgroups.
+    pub fn update(&mut self, num_cpus: usize) -> Result<()> {
+        let now = std::time::Instant::now();
+        let interval_us = self
+            .last_update
+            .map(|last| now.duration_since(last).as_micros() as u64)
+            .unwrap_or(0);
+        self.last_update = Some(now);
+
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![(self.root.clone(), None)];
+        while let Some((dir, parent)) = stack.pop() {
+            let Ok(meta) = fs::metadata(&dir) else {
+                continue;
+            };
+            let id = meta.ino();
+            let rel = dir.strip_prefix(&self.root).unwrap_or(&dir);
+            let path = format!("/{}", rel.display());
+            let max_data_size = self.max_data_size;
+
+            let cgroup = self
+                .cgroups
+                .entry(id)
+                .or_insert_with(|| CgroupData::new(id, &path, parent, max_data_size));
+            // The cpu controller may not be enabled in every subtree.
+            if cgroup.update(&dir, interval_us, num_cpus).is_err() {
+                cgroup.tgids = fs::read_to_string(dir.join("cgroup.procs"))
+                    .map(|s| s.lines().filter_map(|l| l.trim().parse().ok()).collect())
+                    .unwrap_or_default();
+            }
+            seen.insert(id);
+
+            if let Ok(entries) = fs::read_dir(&dir) {
+                for entry in entries.flatten() {
+                    if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                        stack.push((entry.path(), Some(id)));
+                    }
+                }
+            }
+        }
+        self.cgroups.retain(|id, _| seen.contains(id));
+
+        self.tgid_cgroup.clear();
+        for (id, cgroup) in &self.cgroups {
+            for tgid in &cgroup.tgids {
+                self.tgid_cgroup.insert(*tgid, *id);
+            }
+        }
+        // Sampled events may never see the matching sched_switch of a wakeup.
+        self.wakeup_ts.clear();
+
+        Ok(())
+    }
+
+    /// Returns the id of the cgroup of a process.
+    pub fn cgroup_of(&self, tgid: i32) -> Option<u64> {
+        self.tgid_cgroup.get(&tgid).copied()
+    }
+
+    /// Records the wakeup time of a task to compute its runqueue latency.
+    pub fn on_sched_wakeup(&mut self, action: &SchedWakeupAction) {
+        self.wakeup_ts.insert(action.pid, action.ts);
+        if let Some(cgroup) = self.cgroups.get_mut(&action.cgroup_id) {
+            cgroup.nr_wakeups += 1;
+        }
+    }
+
+    /// Accounts the runqueue latency of the task switched in.
+    pub fn on_sched_switch(&mut self, action: &SchedSwitchAction) {
+        let Some(wakeup_ts) = self.wakeup_ts.remove(&action.next_pid) else {
+            return;
+        };
+        let lat_us = action.ts.saturating_sub(wakeup_ts) / 1000;
+        if let Some(cgroup) = self.cgroups.get_mut(&action.next_cgroup_id) {
+            cgroup.add_event_data("rq_lat_us", lat_us);
+        }
+    }
+
+    /// Counts a task migration.
+    pub fn on_sched_migrate(&mut self, action: &SchedMigrateTaskAction) {
+        if let Some(cgroup) = self.cgroups.get_mut(&action.cgroup_id) {
+            cgroup.nr_migrations += 1;
+        }
+    }
+
+    /// Returns the cgroups in tree order (parents before their children, siblings by name).
+    pub fn tree_order(&self) -> Vec<&CgroupData> {
+        let mut children: BTreeMap<Option<u64>, Vec<&CgroupData>> = BTreeMap::new();
+        for cgroup in self.cgroups.values() {
+            children.entry(cgroup.parent).or_default().push(cgroup);
+        }
+        for siblings in children.values_mut() {
+            siblings.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        let mut ordered = Vec::with_capacity(self.cgroups.len());
+        let mut stack: Vec<&CgroupData> = children
+            .get(&None)
+            .map(|roots| roots.iter().rev().copied().collect())
+            .unwrap_or_default();
+        while let Some(cgroup) = stack.pop() {
+            ordered.push(cgroup);
+            if let Some(kids) = children.get(&Some(cgroup.id)) {
+                stack.extend(kids.iter().rev().copied());
+            }
+        }
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cgroup(dir: &Path, usage_us: u64, nr_throttled: u64, procs: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(
+            dir.join("cpu.stat"),
+            format!(
+                "usage_usec {usage_us}\nuser_usec 0\nsystem_usec 0\nnr_periods 10\n\
+                 nr_throttled {nr_throttled}\nthrottled_usec {}\n",
+                nr_throttled * 1000
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("cpu.weight"), "100\n").unwrap();
+        fs::write(dir.join("cpu.max"), "50000 100000\n").unwrap();
+        fs::write(dir.join("cgroup.procs"), procs).unwrap();
+    }
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let stat = parse_cpu_stat("usage_usec 1234\nnr_throttled 5\nbogus\n");
+        assert_eq!(stat.get("usage_usec"), Some(&1234));
+        assert_eq!(stat.get("nr_throttled"), Some(&5));
+        assert_eq!(stat.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_cpu_max() {
+        assert_eq!(parse_cpu_max("max 100000\n"), Some((None, 100000)));
+        assert_eq!(parse_cpu_max("50000 100000"), Some((Some(50000), 100000)));
+        assert_eq!(parse_cpu_max("garbage"), None);
+    }
+
+    #[test]
+    fn test_cgroup_tree_update() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::write(root.join("cgroup.controllers"), "cpu\n").unwrap();
+        write_cgroup(root, 1000, 0, "1\n");
+        write_cgroup(&root.join("workload.slice"), 500, 0, "");
+        write_cgroup(&root.join("workload.slice/app"), 100, 1, "42\n43\n");
+
+        let mut tree = CgroupTree::new(root, 10);
+        assert!(tree.is_available());
+        tree.update(4).unwrap();
+
+        let ordered: Vec<&str> = tree.tree_order().iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(ordered, vec!["/", "/workload.slice", "/workload.slice/app"]);
+
+        let app = tree.tree_order()[2].clone();
+        assert_eq!(app.name(), "app");
+        assert_eq!(app.depth, 2);
+        assert_eq!(app.weight, Some(100));
+        assert_eq!(app.cpu_max(), "50000 100000");
+        assert_eq!(tree.cgroup_of(43), Some(app.id));
+
+        // A second update computes the deltas.
+        write_cgroup(&root.join("workload.slice/app"), 300, 3, "42\n");
+        tree.update(4).unwrap();
+        let app = &tree.cgroups[&app.id];
+        assert_eq!(app.nr_throttled_delta, 2);
+        assert_eq!(app.throttled_us_delta, 2000);
+        assert_eq!(tree.cgroup_of(43), None);
+    }
+
+    #[test]
+    fn test_cgroup_tree_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        write_cgroup(root, 1000, 0, "42\n");
+        // A thread of 42 moved to a threaded child cgroup.
+        write_cgroup(&root.join("threads"), 100, 0, "");
+        let mut tree = CgroupTree::new(root, 10);
+        tree.update(1).unwrap();
+        let root_id = tree.cgroup_of(42).unwrap();
+        let id = tree.tree_order()[1].id;
+
+        tree.on_sched_wakeup(&SchedWakeupAction {
+            ts: 1_000_000,
+            cpu: 0,
+            pid: 44,
+            tgid: 42,
+            prio: 120,
+            cgroup_id: id,
+            comm: "worker".into(),
+            waker_pid: 1,
+            waker_comm: "init".into(),
+        });
+        tree.on_sched_switch(&SchedSwitchAction {
+            ts: 1_250_000,
+            cpu: 0,
+            preempt: false,
+            next_dsq_id: 0,
+            next_dsq_lat_us: 0,
+            next_dsq_nr_queued: 0,
+            next_dsq_vtime: 0,
+            next_slice_ns: 0,
+            next_pid: 44,
+            next_tgid: 42,
+            next_prio: 120,
+            next_layer_id: -1,
+            next_cgroup_id: id,
+            next_comm: "worker".into(),
+            prev_dsq_id: 0,
+            prev_used_slice_ns: 0,
+            prev_slice_ns: 0,
+            prev_pid: 0,
+            prev_tgid: 0,
+            prev_prio: 120,
+            prev_comm: "swapper".into(),
+            prev_state: 0,
+            prev_layer_id: -1,
+        });
+        tree.on_sched_migrate(&SchedMigrateTaskAction {
+            ts: 1_300_000,
+            cpu: 0,
+            dest_cpu: 1,
+            pid: 44,
+            prio: 120,
+            cgroup_id: id,
+            comm: "worker".into(),
+        });
+        // Events of tasks in unknown cgroups are dropped.
+        tree.on_sched_migrate(&SchedMigrateTaskAction {
+            ts: 1_400_000,
+            cpu: 1,
+            dest_cpu: 0,
+            pid: 45,
+            prio: 120,
+            cgroup_id: 0,
+            comm: "other".into(),
+        });
+
+        let cgroup = &tree.cgroups[&id];
+        assert_eq!(cgroup.event_data_immut("rq_lat_us").last(), Some(&250));
+        assert_eq!(cgroup.nr_wakeups, 1);
+        assert_eq!(cgroup.nr_migrations, 1);
+        let root_cgroup = &tree.cgroups[&root_id];
+        assert!(root_cgroup.event_data_immut("rq_lat_us").is_empty());
+        assert_eq!(root_cgroup.nr_wakeups, 0);
+        assert_eq!(root_cgroup.nr_migrations, 0);
+    }
+}
@@ -2,6 +2,10 @@
 //
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
+use crate::flight_recorder::StatLimit;
+use crate::remote::{RemoteAddr, DEFAULT_COLLECTOR_ADDR};
+use crate::snapshot::SnapshotField;
+use crate::trace_export::TraceFormat;
 use crate::APP;
 use crate::STATS_SOCKET_PATH;
 use crate::TRACE_FILE_PREFIX;
@@ -102,6 +106,16 @@ pub struct TuiArgs {
     /// Mangoapp path for System V IPC key
     #[arg(long, default_value = "mangoapp")]
     pub mangoapp_path: String,
+
+    /// Replay a perfetto trace recorded by scxtop instead of collecting live events.
+    #[arg(long, value_parser(clap::value_parser!(PathBuf)))]
+    pub replay: Option<PathBuf>,
+
+    /// Attach to a collector started with `scxtop collect` instead of collecting live events,
+    /// either unix:<path> or tcp:<host>:<port>. The local system stats aren't collected while
+    /// attached.
+    #[arg(long, conflicts_with = "replay")]
+    pub connect: Option<RemoteAddr>,
 }
 
 #[derive(Clone, Parser, Debug)]
@@ -113,6 +127,14 @@ pub struct TraceArgs {
     /// Trace output file.
     #[arg(short = 'o', long)]
     pub output_file: Option<String>,
+    /// Trace output format, csv writes one file per event type prefixed by the output file.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = TraceFormat::Perfetto,
+        conflicts_with = "flight_recorder"
+    )]
+    pub format: TraceFormat,
     /// Enable verbose output, including libbpf details. Specify multiple
     /// times to increase verbosity.
     #[clap(short = 'v', long, action = clap::ArgAction::Count)]
@@ -123,6 +145,35 @@ pub struct TraceArgs {
     /// Collect system statistics (CPU, memory, etc).
     #[clap(short = 's', long)]
     pub system_stats: bool,
+
+    /// Run as a flight recorder until interrupted, keeping the last --trace-ms of events in
+    /// memory and writing a trace when a trigger fires. Scheduler hangs and unregistrations
+    /// always trigger a trace.
+    #[arg(long)]
+    pub flight_recorder: bool,
+    /// Trigger a trace when a task waits longer than this on the runqueue.
+    #[arg(long, requires = "flight_recorder")]
+    pub trigger_rq_lat_us: Option<u64>,
+    /// Trigger a trace when a scheduler stat reaches a limit, the stat is a dot separated path
+    /// into the scheduler stats (ex: "layers.batch.util=90").
+    #[arg(long, requires = "flight_recorder")]
+    pub trigger_stat: Vec<StatLimit>,
+    /// Stats unix socket path, used by stat triggers.
+    #[arg(long, default_value = STATS_SOCKET_PATH, requires = "flight_recorder")]
+    pub stats_socket_path: String,
+    /// Minimum time between two flight recorder traces in ms.
+    #[arg(long, default_value_t = 60000, requires = "flight_recorder")]
+    pub trigger_interval_ms: u64,
+    /// Maximum number of events kept in memory by the flight recorder, by default enough for
+    /// --trace-ms of events that fit in --max-memory-mb.
+    #[arg(long, requires = "flight_recorder")]
+    pub max_events: Option<usize>,
+    /// Memory budget of the events kept by the flight recorder in MB.
+    #[arg(long, default_value_t = 256, requires = "flight_recorder")]
+    pub max_memory_mb: u64,
+    /// Maximum disk usage of all flight recorder traces in MB.
+    #[arg(long, default_value_t = 1024, requires = "flight_recorder")]
+    pub max_disk_mb: u64,
 }
 
 #[derive(Clone, Parser, Debug)]
@@ -154,6 +205,69 @@ pub struct McpArgs {
     pub layered: bool,
 }
 
+#[derive(Clone, Parser, Debug)]
+#[command(about = "Writes periodic JSON snapshots of the collected data")]
+pub struct SnapshotArgs {
+    /// Interval between snapshots in ms.
+    #[arg(short = 'i', long, default_value_t = 1000)]
+    pub interval_ms: u64,
+    /// Stop after this duration in ms, runs until interrupted if not set.
+    #[arg(short = 'd', long)]
+    pub duration_ms: Option<u64>,
+    /// Stop after this number of snapshots, runs until interrupted if not set.
+    #[arg(short = 'n', long)]
+    pub count: Option<u64>,
+    /// Comma separated sections to include in each snapshot.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "cpu,llc,node,process,scheduler"
+    )]
+    pub fields: Vec<SnapshotField>,
+    /// Maximum number of processes (highest CPU utilization first) per snapshot.
+    #[arg(long)]
+    pub max_procs: Option<usize>,
+    /// Output file for the JSON lines, stdout if not present.
+    #[arg(short = 'o', long)]
+    pub output_file: Option<String>,
+    /// Enable verbose output, including libbpf details. Specify multiple
+    /// times to increase verbosity.
+    #[clap(short = 'v', long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Process ID to monitor (or -1 for all processes).
+    #[arg(long, default_value_t = -1)]
+    pub process_id: i32,
+    /// Show scx_layered data (process level layer_id's).
+    #[arg(long)]
+    pub layered: bool,
+}
+
+#[derive(Clone, Parser, Debug)]
+#[command(about = "Collects events and streams them to attached TUIs")]
+pub struct CollectArgs {
+    /// Address to listen on, either unix:<path> or tcp:<host>:<port>. The stream isn't
+    /// authenticated, TCP listeners should be bound to a trusted address.
+    #[arg(short = 'l', long, default_value = DEFAULT_COLLECTOR_ADDR)]
+    pub listen: RemoteAddr,
+    /// Number of events buffered for each client, slower clients drop the events they miss.
+    #[arg(long, default_value_t = 1 << 16)]
+    pub client_buffer_events: usize,
+    /// Stats unix socket path for scx_stats integration.
+    #[arg(long, default_value = STATS_SOCKET_PATH)]
+    pub stats_socket_path: String,
+    /// Interval in ms at which scheduler stats are polled and streamed.
+    #[arg(long, default_value_t = 1000)]
+    pub stats_interval_ms: u64,
+    /// Enable verbose output, including libbpf details. Specify multiple
+    /// times to increase verbosity.
+    #[clap(short = 'v', long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+    /// Show scx_layered data (process level layer_id's).
+    #[arg(long)]
+    pub layered: bool,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
@@ -166,6 +280,13 @@ pub enum Commands {
     /// Runs the MCP (Model Context Protocol) server.
     Mcp(McpArgs),
 
+    /// Runs headless, writing periodic JSON snapshots (like top -b).
+    #[command(long_flag = "batch")]
+    Snapshot(SnapshotArgs),
+
+    /// Runs headless, streaming events to TUIs started with --connect.
+    Collect(CollectArgs),
+
     #[clap(hide = true)]
     GenerateCompletions {
         /// The shell type
@@ -3,6 +3,7 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use crate::alerts::AlertRule;
 use crate::cli::TuiArgs;
 use crate::keymap::parse_action;
 use crate::keymap::parse_key;
@@ -61,6 +62,16 @@ use xdg;
 /// "Page Up" = "PageUp"
 /// x = "ClearEvent"
 /// ```
+///
+/// Alert rules are evaluated on every tick, see [`crate::alerts::Condition`] for the condition
+/// syntax:
+/// ```text
+/// [[alerts]]
+/// name = "nginx-latency"
+/// condition = "p99(proc[nginx].lat_us) > 5000"
+/// for_ms = 5000
+/// actions = ["highlight", "syslog", "trace"]
+/// ```
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
@@ -98,6 +109,8 @@ pub struct Config {
     /// Default profiling event string, in the format <source>:<event>
     /// where `source` is one of kprobe, perf, or cpu.
     default_profiling_event: Option<String>,
+    /// Alert rules.
+    alerts: Option<Vec<AlertRule>>,
 }
 
 impl From<TuiArgs> for Config {
@@ -119,6 +132,7 @@ impl From<TuiArgs> for Config {
             trace_duration_ms: args.trace_duration_ms,
             worker_threads: args.worker_threads,
             default_profiling_event: Some(args.default_profiling_event),
+            alerts: None,
         }
     }
 }
@@ -164,6 +178,7 @@ impl Config {
             trace_tick_warmup: self.trace_tick_warmup.or(rhs.trace_tick_warmup),
             trace_warmup_ms: self.trace_warmup_ms.or(rhs.trace_warmup_ms),
             default_profiling_event: self.default_profiling_event.or(rhs.default_profiling_event),
+            alerts: self.alerts.or(rhs.alerts),
         }
     }
 
@@ -243,6 +258,11 @@ impl Config {
         self.trace_warmup_ms.unwrap_or(750) * 1_000_000
     }
 
+    /// Alert rules.
+    pub fn alerts(&self) -> &[AlertRule] {
+        self.alerts.as_deref().unwrap_or_default()
+    }
+
     /// Returns a config with nothing set.
     pub fn empty_config() -> Config {
         Config {
@@ -262,6 +282,7 @@ impl Config {
             trace_tick_warmup: None,
             trace_warmup_ms: None,
             default_profiling_event: None,
+            alerts: None,
         }
     }
 
@@ -284,6 +305,7 @@ impl Config {
             trace_tick_warmup: None,
             trace_warmup_ms: None,
             default_profiling_event: None,
+            alerts: None,
         };
         config.tick_rate_ms = Some(config.tick_rate_ms());
         config.frame_rate_ms = Some(config.frame_rate_ms());
@@ -339,6 +361,7 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::alerts::{AlertAction, AlertEngine};
     use crate::Action;
     use clap::Parser;
     use std::path::Path;
@@ -845,6 +868,42 @@ mod tests {
             .is_some_and(|action| *action == Action::Enter));
     }
 
+    #[test]
+    fn test_load_config_alerts() {
+        let saved_config = r#"
+        perf_events = []
+
+        [[alerts]]
+        name = "sched-unreg"
+        condition = "scheduler_loaded == 0"
+        actions = ["highlight", "notify"]
+
+        [[alerts]]
+        name = "nginx-latency"
+        condition = "p99(proc[nginx].lat_us) > 5000"
+        for_ms = 5000
+        "#;
+
+        let loaded_config: Config = toml::from_str(saved_config).expect("Failed to deserialize");
+        let alerts = loaded_config.alerts();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].name, "sched-unreg");
+        assert_eq!(
+            alerts[0].actions,
+            vec![AlertAction::Highlight, AlertAction::Notify]
+        );
+        assert_eq!(alerts[1].for_ms, 5000);
+        assert_eq!(alerts[1].actions, vec![AlertAction::Highlight]);
+        assert!(AlertEngine::new(alerts).is_ok());
+
+        // Alerts survive a save and reload
+        let saved = toml::to_string(&loaded_config).expect("Failed to serialize config");
+        let reloaded: Config = toml::from_str(&saved).expect("Failed to deserialize");
+        assert_eq!(reloaded.alerts(), alerts);
+
+        assert!(Config::empty_config().alerts().is_empty());
+    }
+
     #[test]
     fn test_config_integration_test_complex() {
         let dir = tempdir().expect("Failed to create temporary directory");
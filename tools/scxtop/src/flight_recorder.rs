@@ -0,0 +1,568 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::edm::ActionHandler;
+use crate::{Action, PerfettoTraceManager};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Pending wakeups are pruned once there are more than this many of them.
+const MAX_PENDING_WAKEUPS: usize = 1 << 16;
+// Event rate per CPU used to size the ring buffer for the window.
+const EVENTS_PER_CPU_MS: u64 = 100;
+// Heap memory used by an event on top of its size (comms, stats).
+const EVENT_HEAP_BYTES: usize = 64;
+
+/// Limit on a scheduler stat, the stat is selected with a dot separated path into the stats
+/// JSON (ex: "layers.batch.util").
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatLimit {
+    pub path: String,
+    pub limit: f64,
+}
+
+impl FromStr for StatLimit {
+    type Err = anyhow::Error;
+
+    /// Parses a limit in the form of "<path>=<limit>".
+    fn from_str(s: &str) -> Result<Self> {
+        let (path, limit) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid stat limit {s}, expected <path>=<limit>"))?;
+        if path.is_empty() {
+            return Err(anyhow!("Invalid stat limit {s}, empty stat path"));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            limit: limit
+                .parse()
+                .map_err(|e| anyhow!("Invalid stat limit {s}: {e}"))?,
+        })
+    }
+}
+
+impl StatLimit {
+    /// Returns the value of the stat, if it exists and is a number.
+    fn value(&self, stats: &JsonValue) -> Option<f64> {
+        self.path
+            .split('.')
+            .try_fold(stats, |v, key| v.get(key))?
+            .as_f64()
+    }
+}
+
+/// Event that causes the flight recorder to dump a trace.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trigger {
+    SchedHang { pid: u32, comm: String },
+    SchedUnreg,
+    RunqueueLatency { pid: u32, lat_us: u64 },
+    StatLimit { path: String, value: f64 },
+}
+
+impl Trigger {
+    /// Returns the name of the trigger, used in the trace file name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Trigger::SchedHang { .. } => "hang",
+            Trigger::SchedUnreg => "unreg",
+            Trigger::RunqueueLatency { .. } => "rqlat",
+            Trigger::StatLimit { .. } => "stat",
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trigger::SchedHang { pid, comm } => write!(f, "hung task {comm}[{pid}]"),
+            Trigger::SchedUnreg => write!(f, "scheduler unregistered"),
+            Trigger::RunqueueLatency { pid, lat_us } => {
+                write!(f, "runqueue latency of {lat_us}us for pid {pid}")
+            }
+            Trigger::StatLimit { path, value } => write!(f, "stat {path} reached {value}"),
+        }
+    }
+}
+
+/// Configuration of the flight recorder.
+#[derive(Clone, Debug)]
+pub struct FlightRecorderConfig {
+    /// Amount of trace time kept in the ring buffer.
+    pub window_ns: u64,
+    /// Maximum number of events kept in the ring buffer.
+    pub max_events: usize,
+    /// Dump a trace when a task waits longer than this on the runqueue.
+    pub rq_lat_threshold_us: Option<u64>,
+    /// Dump a trace when a scheduler stat crosses a limit.
+    pub stat_limits: Vec<StatLimit>,
+    /// Minimum time between two dumps.
+    pub min_dump_interval: Duration,
+    /// Maximum number of bytes written by all dumps.
+    pub max_disk_bytes: u64,
+    /// Prefix of the trace files.
+    pub output_prefix: String,
+}
+
+impl FlightRecorderConfig {
+    /// Returns the number of events needed to fill a window on nr_cpus CPUs, limited to the
+    /// events that fit in the memory budget.
+    pub fn default_max_events(window_ns: u64, nr_cpus: usize, memory_bytes: u64) -> usize {
+        let window_events = (window_ns / 1_000_000)
+            .max(1)
+            .saturating_mul(EVENTS_PER_CPU_MS)
+            .saturating_mul(nr_cpus as u64);
+        let event_bytes = std::mem::size_of::<(u64, Action)>() + EVENT_HEAP_BYTES;
+        let budget_events = memory_bytes / event_bytes as u64;
+        window_events.min(budget_events).max(1) as usize
+    }
+}
+
+// State shared between the flight recorder and the traces written in the background.
+struct DumpState {
+    trace_manager: Mutex<PerfettoTraceManager>,
+    disk_usage: AtomicU64,
+    dumps: Mutex<Vec<PathBuf>>,
+}
+
+/// Keeps the most recent BPF events in a bounded ring buffer and hands them over to a
+/// FlightDump when a trigger fires.
+pub struct FlightRecorder {
+    config: FlightRecorderConfig,
+    state: Arc<DumpState>,
+    events: VecDeque<(u64, Action)>,
+    wakeup_ts: HashMap<u32, u64>,
+    // Whether each stat limit was exceeded by the last stats sample.
+    stat_exceeded: Vec<bool>,
+    last_dump: Option<Instant>,
+    nr_dumps: usize,
+}
+
+impl FlightRecorder {
+    /// Creates a flight recorder that writes traces with the trace manager.
+    pub fn new(config: FlightRecorderConfig, trace_manager: PerfettoTraceManager) -> Self {
+        let stat_exceeded = vec![false; config.stat_limits.len()];
+        Self {
+            config,
+            state: Arc::new(DumpState {
+                trace_manager: Mutex::new(trace_manager),
+                disk_usage: AtomicU64::new(0),
+                dumps: Mutex::new(Vec::new()),
+            }),
+            events: VecDeque::new(),
+            wakeup_ts: HashMap::new(),
+            stat_exceeded,
+            last_dump: None,
+            nr_dumps: 0,
+        }
+    }
+
+    /// Returns the number of events in the ring buffer.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if the ring buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the traces that have been written.
+    pub fn dumps(&self) -> Vec<PathBuf> {
+        self.state.dumps.lock().unwrap().clone()
+    }
+
+    /// Records the action, returns the trace to write if it fires a trigger.
+    pub fn on_action(&mut self, action: &Action) -> Option<FlightDump> {
+        if let Some(ts) = Self::action_ts(action) {
+            self.record(ts, action);
+        }
+        let trigger = self.check_trigger(action)?;
+        self.dump(trigger)
+    }
+
+    /// Returns the timestamp of actions that are written to traces.
+    fn action_ts(action: &Action) -> Option<u64> {
+        match action {
+            Action::SchedSwitch(a) => Some(a.ts),
+            Action::SchedWakeup(a) | Action::SchedWaking(a) => Some(a.ts),
+            Action::SchedMigrateTask(a) => Some(a.ts),
+            Action::SchedHang(a) => Some(a.ts),
+            Action::SoftIRQ(a) => Some(a.exit_ts),
+            Action::IPI(a) => Some(a.ts),
+            Action::Exec(a) => Some(a.ts),
+            Action::Fork(a) => Some(a.ts),
+            Action::Exit(a) => Some(a.ts),
+            Action::Wait(a) => Some(a.ts),
+            Action::GpuMem(a) => Some(a.ts),
+            Action::CpuhpEnter(a) => Some(a.ts),
+            Action::CpuhpExit(a) => Some(a.ts),
+            Action::Kprobe(a) => Some(a.ts),
+            Action::SystemStat(a) => Some(a.ts),
+            _ => None,
+        }
+    }
+
+    /// Adds an event to the ring buffer, evicting the events that fell out of the window.
+    fn record(&mut self, ts: u64, action: &Action) {
+        self.events.push_back((ts, action.clone()));
+        let min_ts = ts.saturating_sub(self.config.window_ns);
+        while let Some((front_ts, _)) = self.events.front() {
+            if *front_ts >= min_ts && self.events.len() <= self.config.max_events {
+                break;
+            }
+            self.events.pop_front();
+        }
+        if self.wakeup_ts.len() > MAX_PENDING_WAKEUPS {
+            self.wakeup_ts.retain(|_, wakeup_ts| *wakeup_ts >= min_ts);
+        }
+    }
+
+    /// Returns the trigger fired by the action, if any.
+    fn check_trigger(&mut self, action: &Action) -> Option<Trigger> {
+        match action {
+            Action::SchedHang(a) => Some(Trigger::SchedHang {
+                pid: a.pid,
+                comm: a.comm.to_string(),
+            }),
+            Action::SchedUnreg => Some(Trigger::SchedUnreg),
+            Action::SchedWakeup(a) => {
+                if self.config.rq_lat_threshold_us.is_some() {
+                    self.wakeup_ts.insert(a.pid, a.ts);
+                }
+                None
+            }
+            Action::SchedSwitch(a) => {
+                let threshold_us = self.config.rq_lat_threshold_us?;
+                let wakeup_ts = self.wakeup_ts.remove(&a.next_pid)?;
+                let lat_us = a.ts.saturating_sub(wakeup_ts) / 1000;
+                (lat_us > threshold_us).then_some(Trigger::RunqueueLatency {
+                    pid: a.next_pid,
+                    lat_us,
+                })
+            }
+            Action::SchedStats(raw) => {
+                let stats: JsonValue = serde_json::from_str(raw).ok()?;
+                let mut trigger = None;
+                for (limit, exceeded) in self
+                    .config
+                    .stat_limits
+                    .iter()
+                    .zip(self.stat_exceeded.iter_mut())
+                {
+                    let value = limit.value(&stats);
+                    let now_exceeded = value.is_some_and(|v| v >= limit.limit);
+                    // Only fire when the stat crosses the limit, not while it stays above it.
+                    if now_exceeded && !*exceeded && trigger.is_none() {
+                        trigger = Some(Trigger::StatLimit {
+                            path: limit.path.clone(),
+                            value: value.unwrap_or_default(),
+                        });
+                    }
+                    *exceeded = now_exceeded;
+                }
+                trigger
+            }
+            _ => None,
+        }
+    }
+
+    /// Drains the ring buffer into a trace to write, unless the dump is rate limited or would
+    /// exceed the disk usage cap.
+    pub fn dump(&mut self, trigger: Trigger) -> Option<FlightDump> {
+        if let Some(last_dump) = self.last_dump {
+            if last_dump.elapsed() < self.config.min_dump_interval {
+                info!("skipping trace for {trigger}, rate limited");
+                return None;
+            }
+        }
+        if self.state.disk_usage.load(Ordering::Relaxed) >= self.config.max_disk_bytes {
+            warn!("skipping trace for {trigger}, disk usage cap reached");
+            return None;
+        }
+        self.last_dump = Some(Instant::now());
+
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = PathBuf::from(format!(
+            "{}_flight_{}_{}_{}.proto",
+            self.config.output_prefix,
+            secs,
+            self.nr_dumps,
+            trigger.name()
+        ));
+        self.nr_dumps += 1;
+
+        Some(FlightDump {
+            trigger,
+            path,
+            events: self.events.drain(..).map(|(_, action)| action).collect(),
+            max_disk_bytes: self.config.max_disk_bytes,
+            state: self.state.clone(),
+        })
+    }
+}
+
+/// Events drained from the flight recorder when a trigger fired, written to a trace with
+/// write(), which blocks and should run outside of the task consuming the actions.
+pub struct FlightDump {
+    trigger: Trigger,
+    path: PathBuf,
+    events: Vec<Action>,
+    max_disk_bytes: u64,
+    state: Arc<DumpState>,
+}
+
+impl FlightDump {
+    /// Writes the trace, returns its path unless it was removed for exceeding the disk usage
+    /// cap.
+    pub fn write(self) -> Result<Option<PathBuf>> {
+        let trigger = &self.trigger;
+        let path = &self.path;
+        {
+            let mut trace_manager = self.state.trace_manager.lock().unwrap();
+            trace_manager.start()?;
+            for action in &self.events {
+                match action {
+                    Action::SchedMigrateTask(a) => trace_manager.on_sched_migrate(a),
+                    Action::SchedHang(a) => trace_manager.on_sched_hang(a),
+                    Action::Wait(a) => trace_manager.on_wait(a),
+                    _ => trace_manager.on_action(action)?,
+                }
+            }
+            trace_manager.stop(Some(path.to_string_lossy().to_string()), None)?;
+        }
+
+        let size = fs::metadata(path)?.len();
+        let disk_usage = self.state.disk_usage.fetch_add(size, Ordering::Relaxed) + size;
+        if disk_usage > self.max_disk_bytes {
+            fs::remove_file(path)?;
+            self.state
+                .disk_usage
+                .store(self.max_disk_bytes, Ordering::Relaxed);
+            warn!("removed trace for {trigger}, disk usage cap reached");
+            return Ok(None);
+        }
+        info!(
+            "wrote trace for {trigger} to {} ({} events)",
+            path.display(),
+            self.events.len()
+        );
+        self.state.dumps.lock().unwrap().push(path.clone());
+
+        Ok(Some(self.path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SchedHangAction, SchedSwitchAction, SchedWakeActionCtx};
+    use scx_utils::scx_enums;
+
+    fn test_config(dir: &std::path::Path) -> FlightRecorderConfig {
+        FlightRecorderConfig {
+            window_ns: 1_000_000,
+            max_events: 100,
+            rq_lat_threshold_us: Some(100),
+            stat_limits: vec!["layers.batch.util=90".parse().unwrap()],
+            min_dump_interval: Duration::ZERO,
+            max_disk_bytes: 1 << 30,
+            output_prefix: dir.join("test").to_string_lossy().to_string(),
+        }
+    }
+
+    fn test_recorder(config: FlightRecorderConfig) -> FlightRecorder {
+        let trace_manager = PerfettoTraceManager::new(config.output_prefix.clone(), Some(0));
+        FlightRecorder::new(config, trace_manager)
+    }
+
+    // Records the action and writes the trace it triggers, if any.
+    fn trace(recorder: &mut FlightRecorder, action: &Action) -> Option<PathBuf> {
+        recorder
+            .on_action(action)
+            .and_then(|dump| dump.write().unwrap())
+    }
+
+    fn wakeup(ts: u64, pid: u32) -> Action {
+        Action::SchedWakeup(SchedWakeActionCtx {
+            ts,
+            cpu: 0,
+            pid,
+            tgid: pid,
+            prio: 120,
+            cgroup_id: 0,
+            comm: "test".into(),
+            waker_pid: 0,
+            waker_comm: "".into(),
+        })
+    }
+
+    fn switch(ts: u64, next_pid: u32) -> Action {
+        Action::SchedSwitch(SchedSwitchAction {
+            ts,
+            cpu: 0,
+            preempt: false,
+            next_dsq_id: scx_enums.SCX_DSQ_INVALID,
+            next_dsq_lat_us: 0,
+            next_dsq_nr_queued: 0,
+            next_dsq_vtime: 0,
+            next_slice_ns: 0,
+            next_pid,
+            next_tgid: next_pid,
+            next_prio: 120,
+            next_layer_id: -1,
+            next_cgroup_id: 0,
+            next_comm: "test".into(),
+            prev_dsq_id: scx_enums.SCX_DSQ_INVALID,
+            prev_used_slice_ns: 0,
+            prev_slice_ns: 0,
+            prev_pid: 0,
+            prev_tgid: 0,
+            prev_prio: 0,
+            prev_comm: "".into(),
+            prev_state: 0,
+            prev_layer_id: -1,
+        })
+    }
+
+    #[test]
+    fn test_parse_stat_limit() {
+        let limit: StatLimit = "layers.batch.util=90.5".parse().unwrap();
+        assert_eq!(limit.path, "layers.batch.util");
+        assert_eq!(limit.limit, 90.5);
+        assert!("util".parse::<StatLimit>().is_err());
+        assert!("=1".parse::<StatLimit>().is_err());
+        assert!("util=high".parse::<StatLimit>().is_err());
+    }
+
+    #[test]
+    fn test_window() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut recorder = test_recorder(FlightRecorderConfig {
+            max_events: 3,
+            ..test_config(tmp.path())
+        });
+        trace(&mut recorder, &switch(0, 1));
+        trace(&mut recorder, &Action::Tick);
+        trace(&mut recorder, &switch(500_000, 1));
+        assert_eq!(recorder.len(), 2);
+
+        // Events older than the window are evicted
+        trace(&mut recorder, &switch(1_600_000, 1));
+        assert_eq!(recorder.len(), 1);
+
+        // The ring buffer never holds more than max_events
+        for i in 0..5 {
+            trace(&mut recorder, &switch(1_600_000 + i, 1));
+        }
+        assert_eq!(recorder.len(), 3);
+    }
+
+    #[test]
+    fn test_triggers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut recorder = test_recorder(test_config(tmp.path()));
+
+        trace(&mut recorder, &wakeup(0, 10));
+        assert!(trace(&mut recorder, &switch(50_000, 10)).is_none());
+        trace(&mut recorder, &wakeup(100_000, 10));
+        let path = trace(&mut recorder, &switch(300_000, 10)).unwrap();
+        assert!(path.exists());
+        assert!(path.to_string_lossy().ends_with("rqlat.proto"));
+
+        let hang = Action::SchedHang(SchedHangAction {
+            ts: 400_000,
+            cpu: 0,
+            comm: "hung".into(),
+            pid: 20,
+        });
+        assert!(trace(&mut recorder, &hang).is_some());
+        assert!(trace(&mut recorder, &Action::SchedUnreg).is_some());
+
+        // Stat triggers fire when the stat crosses the limit
+        let stats = |util: f64| {
+            Action::SchedStats(format!(r#"{{"layers":{{"batch":{{"util":{util}}}}}}}"#))
+        };
+        assert!(trace(&mut recorder, &stats(50.0)).is_none());
+        assert!(trace(&mut recorder, &stats(95.0)).is_some());
+        assert!(trace(&mut recorder, &stats(96.0)).is_none());
+        assert!(trace(&mut recorder, &stats(10.0)).is_none());
+        assert!(trace(&mut recorder, &stats(91.0)).is_some());
+
+        assert_eq!(recorder.dumps().len(), 4);
+    }
+
+    #[test]
+    fn test_rate_limit_and_disk_cap() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut recorder = test_recorder(FlightRecorderConfig {
+            min_dump_interval: Duration::from_secs(3600),
+            ..test_config(tmp.path())
+        });
+        trace(&mut recorder, &switch(0, 1));
+        assert!(trace(&mut recorder, &Action::SchedUnreg).is_some());
+        assert!(trace(&mut recorder, &Action::SchedUnreg).is_none());
+
+        let mut recorder = test_recorder(FlightRecorderConfig {
+            max_disk_bytes: 1,
+            output_prefix: tmp.path().join("capped").to_string_lossy().to_string(),
+            ..test_config(tmp.path())
+        });
+        trace(&mut recorder, &switch(0, 1));
+        assert!(trace(&mut recorder, &Action::SchedUnreg).is_none());
+        assert!(recorder.dumps().is_empty());
+        assert_eq!(fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_dump_drains_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut recorder = test_recorder(test_config(tmp.path()));
+        trace(&mut recorder, &switch(0, 1));
+        trace(&mut recorder, &switch(10, 1));
+
+        // The trace is written from the drained events, new events start a new window
+        let dump = recorder.on_action(&Action::SchedUnreg).unwrap();
+        assert!(recorder.is_empty());
+        trace(&mut recorder, &switch(20, 1));
+        assert_eq!(recorder.len(), 1);
+
+        let path = std::thread::spawn(move || dump.write().unwrap())
+            .join()
+            .unwrap()
+            .unwrap();
+        assert!(path.exists());
+        assert_eq!(recorder.dumps(), [path]);
+    }
+
+    #[test]
+    fn test_default_max_events() {
+        let event_bytes = (std::mem::size_of::<(u64, Action)>() + EVENT_HEAP_BYTES) as u64;
+
+        // The window fits in the memory budget
+        assert_eq!(
+            FlightRecorderConfig::default_max_events(1_000_000_000, 4, 1 << 40),
+            1000 * 4 * EVENTS_PER_CPU_MS as usize
+        );
+        // The memory budget limits large windows
+        assert_eq!(
+            FlightRecorderConfig::default_max_events(60_000_000_000, 256, 1000 * event_bytes),
+            1000
+        );
+    }
+}
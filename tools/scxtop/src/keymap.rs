@@ -38,6 +38,7 @@ impl Default for KeyMap {
     fn default() -> Self {
         let mut bindings = HashMap::new();
         bindings.insert(Key::Char('b'), Action::SetState(AppState::BpfPrograms));
+        bindings.insert(Key::Char('c'), Action::SetState(AppState::Cgroup));
         bindings.insert(Key::Char('d'), Action::SetState(AppState::Default));
         bindings.insert(Key::Char(' '), Action::SetState(AppState::Pause));
         bindings.insert(Key::Char('e'), Action::SetState(AppState::PerfEvent));
@@ -50,6 +51,8 @@ impl Default for KeyMap {
         bindings.insert(Key::Char('L'), Action::ToggleLocalization);
         bindings.insert(Key::Char('P'), Action::ToggleHwPressure);
         bindings.insert(Key::Char('h'), Action::SetState(AppState::Help));
+        bindings.insert(Key::Char('H'), Action::SetState(AppState::Latency));
+        bindings.insert(Key::Char('J'), Action::ExportLatency);
         bindings.insert(Key::Char('m'), Action::SetState(AppState::MangoApp));
         bindings.insert(Key::Char('M'), Action::SetState(AppState::Memory));
         bindings.insert(Key::Char('?'), Action::SetState(AppState::Help));
@@ -72,6 +75,10 @@ impl Default for KeyMap {
         bindings.insert(Key::Char('['), Action::DecBpfSampleRate);
         bindings.insert(Key::Char(']'), Action::IncBpfSampleRate);
         bindings.insert(Key::Char('v'), Action::NextViewState);
+        bindings.insert(Key::Char('<'), Action::ReplaySlowDown);
+        bindings.insert(Key::Char('>'), Action::ReplaySpeedUp);
+        bindings.insert(Key::Char(','), Action::ReplaySeekBackward);
+        bindings.insert(Key::Char('.'), Action::ReplaySeekForward);
         bindings.insert(Key::Code(KeyCode::Down), Action::Down);
         bindings.insert(Key::Code(KeyCode::Up), Action::Up);
         bindings.insert(Key::Code(KeyCode::PageDown), Action::PageDown);
@@ -381,6 +388,8 @@ pub fn parse_action(action_str: &str) -> Result<Action> {
         "ToggleHwPressure" => Ok(Action::ToggleHwPressure),
         "AppStateHelp" | "SetState(Help)" => Ok(Action::SetState(AppState::Help)),
         "AppStateBandwidth" | "SetState(Bandwidth)" => Ok(Action::SetState(AppState::Bandwidth)),
+        "AppStateCgroup" | "SetState(Cgroup)" => Ok(Action::SetState(AppState::Cgroup)),
+        "AppStateLatency" | "SetState(Latency)" => Ok(Action::SetState(AppState::Latency)),
         "AppStateLlc" | "SetState(Llc)" => Ok(Action::SetState(AppState::Llc)),
         "AppStateMangoApp" | "SetState(MangoApp)" => Ok(Action::SetState(AppState::MangoApp)),
         "AppStateMemory" | "SetState(Memory)" => Ok(Action::SetState(AppState::Memory)),
@@ -388,7 +397,12 @@ pub fn parse_action(action_str: &str) -> Result<Action> {
         "AppStateScheduler" | "SetState(Scheduler)" => Ok(Action::SetState(AppState::Scheduler)),
         "AppStateNetwork" | "SetState(Network)" => Ok(Action::SetState(AppState::Network)),
         "SaveConfig" => Ok(Action::SaveConfig),
+        "ExportLatency" => Ok(Action::ExportLatency),
         "RequestTrace" => Ok(Action::RequestTrace),
+        "ReplaySeekBackward" => Ok(Action::ReplaySeekBackward),
+        "ReplaySeekForward" => Ok(Action::ReplaySeekForward),
+        "ReplaySlowDown" => Ok(Action::ReplaySlowDown),
+        "ReplaySpeedUp" => Ok(Action::ReplaySpeedUp),
         "ClearEvent" => Ok(Action::ClearEvent),
         "PrevEvent" => Ok(Action::PrevEvent),
         "NextEvent" => Ok(Action::NextEvent),
@@ -0,0 +1,562 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::{SchedSwitchAction, SchedWakeupAction};
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+// Each power of two is split in 2^SUB_BUCKET_BITS linear sub buckets, which bounds the relative
+// error of a recorded value to 1/2^SUB_BUCKET_BITS.
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Number of latency rows of the heatmap, row n holds latencies in [2^n, 2^(n+1)) μs and the
+/// last row holds everything above.
+pub const HEATMAP_ROWS: usize = 21;
+
+// Pending wakeups and preemptions are pruned once there are more than this many of them.
+const MAX_PENDING_TASKS: usize = 1 << 16;
+// Pending timestamps older than this are dropped when pruning.
+const MAX_PENDING_NS: u64 = 10_000_000_000;
+
+/// Histogram with logarithmic buckets that are split linearly, similar to HdrHistogram.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyHistogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+/// Bucket of a histogram, values are in [lower, upper].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistogramBucket {
+    pub lower: u64,
+    pub upper: u64,
+    pub count: u64,
+}
+
+/// Summary of a histogram, used for exports.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct HistogramSummary {
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl LatencyHistogram {
+    /// Returns the index of the bucket of a value.
+    fn bucket_index(value: u64) -> usize {
+        if value < SUB_BUCKETS as u64 {
+            return value as usize;
+        }
+        let shift = 63 - value.leading_zeros() - SUB_BUCKET_BITS;
+        let sub_bucket = (value >> shift) as usize & (SUB_BUCKETS - 1);
+        (shift as usize + 1) * SUB_BUCKETS + sub_bucket
+    }
+
+    /// Returns the smallest value of a bucket.
+    fn bucket_lower(index: usize) -> u64 {
+        if index < SUB_BUCKETS {
+            return index as u64;
+        }
+        let shift = index / SUB_BUCKETS - 1;
+        ((SUB_BUCKETS + index % SUB_BUCKETS) as u64) << shift
+    }
+
+    /// Returns the largest value of a bucket.
+    fn bucket_upper(index: usize) -> u64 {
+        if index >= Self::bucket_index(u64::MAX) {
+            return u64::MAX;
+        }
+        Self::bucket_lower(index + 1) - 1
+    }
+
+    /// Records a value.
+    pub fn record(&mut self, value: u64) {
+        let index = Self::bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// Returns the number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the smallest recorded value.
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    /// Returns the largest recorded value.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Returns the mean of the recorded values.
+    pub fn mean(&self) -> u64 {
+        self.sum.checked_div(self.count).unwrap_or(0)
+    }
+
+    /// Returns the value at a percentile in [0, 100], the value is the upper bound of the bucket
+    /// holding the percentile.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((percentile / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::bucket_upper(index).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Returns the buckets that hold values.
+    pub fn buckets(&self) -> impl Iterator<Item = HistogramBucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(index, count)| HistogramBucket {
+                lower: Self::bucket_lower(index),
+                upper: Self::bucket_upper(index),
+                count: *count,
+            })
+    }
+
+    /// Adds the values of another histogram.
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        if other.count == 0 {
+            return;
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+    }
+
+    /// Returns the summary of the histogram.
+    pub fn summary(&self) -> HistogramSummary {
+        HistogramSummary {
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            mean: self.mean(),
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            p999: self.percentile(99.9),
+            buckets: self.buckets().collect(),
+        }
+    }
+}
+
+/// Scheduling latency histograms of a process or CPU, values are in ns.
+#[derive(Clone, Debug, Default)]
+pub struct SchedLatency {
+    /// Time from a wakeup until the task runs.
+    pub wakeup_lat: LatencyHistogram,
+    /// Time a task waits on the runqueue after a wakeup or a preemption.
+    pub rq_wait: LatencyHistogram,
+    /// Time a task runs before it is switched out.
+    pub slice: LatencyHistogram,
+}
+
+/// Export of the histograms of a process.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessLatencyExport {
+    pub tgid: u32,
+    pub comm: String,
+    pub wakeup_lat_ns: HistogramSummary,
+    pub rq_wait_ns: HistogramSummary,
+    pub slice_ns: HistogramSummary,
+}
+
+/// Export of the histograms of a CPU.
+#[derive(Clone, Debug, Serialize)]
+pub struct CpuLatencyExport {
+    pub cpu: u32,
+    pub wakeup_lat_ns: HistogramSummary,
+    pub rq_wait_ns: HistogramSummary,
+    pub slice_ns: HistogramSummary,
+}
+
+/// Export of the wakeup latency heatmap, columns are ordered from oldest to newest.
+#[derive(Clone, Debug, Serialize)]
+pub struct HeatmapExport {
+    pub row_lower_us: Vec<u64>,
+    pub columns: Vec<Vec<u64>>,
+}
+
+/// Export of all the latency data.
+#[derive(Clone, Debug, Serialize)]
+pub struct LatencyExport {
+    pub processes: Vec<ProcessLatencyExport>,
+    pub cpus: Vec<CpuLatencyExport>,
+    pub heatmap: HeatmapExport,
+}
+
+/// Tracks per-process and per-CPU scheduling latency histograms along with a heatmap of the
+/// wakeup latency over time.
+#[derive(Clone, Debug)]
+pub struct LatencyTracker {
+    pub processes: BTreeMap<u32, SchedLatency>,
+    pub cpus: BTreeMap<u32, SchedLatency>,
+    comms: HashMap<u32, String>,
+    // Timestamps at which tasks became runnable, keyed by pid.
+    wakeup_ts: HashMap<u32, u64>,
+    preempt_ts: HashMap<u32, u64>,
+    heatmap: VecDeque<[u64; HEATMAP_ROWS]>,
+    heatmap_column: [u64; HEATMAP_ROWS],
+    max_columns: usize,
+}
+
+impl LatencyTracker {
+    /// Creates a tracker that keeps max_columns heatmap columns.
+    pub fn new(max_columns: usize) -> Self {
+        Self {
+            processes: BTreeMap::new(),
+            cpus: BTreeMap::new(),
+            comms: HashMap::new(),
+            wakeup_ts: HashMap::new(),
+            preempt_ts: HashMap::new(),
+            heatmap: VecDeque::new(),
+            heatmap_column: [0; HEATMAP_ROWS],
+            max_columns,
+        }
+    }
+
+    /// Clears all the latency data.
+    pub fn clear(&mut self) {
+        *self = Self::new(self.max_columns);
+    }
+
+    /// Returns the command name of a process.
+    pub fn comm(&self, tgid: u32) -> &str {
+        self.comms.get(&tgid).map_or("", |comm| comm.as_str())
+    }
+
+    /// Returns the heatmap row of a latency in ns.
+    pub fn heatmap_row(lat_ns: u64) -> usize {
+        let lat_us = lat_ns / 1000;
+        if lat_us == 0 {
+            return 0;
+        }
+        ((63 - lat_us.leading_zeros()) as usize).min(HEATMAP_ROWS - 1)
+    }
+
+    /// Returns the heatmap columns from oldest to newest.
+    pub fn heatmap(&self) -> impl Iterator<Item = &[u64; HEATMAP_ROWS]> {
+        self.heatmap.iter()
+    }
+
+    /// Updates the tracker with a wakeup.
+    pub fn on_sched_wakeup(&mut self, action: &SchedWakeupAction) {
+        if action.pid == 0 {
+            return;
+        }
+        self.preempt_ts.remove(&action.pid);
+        self.wakeup_ts.insert(action.pid, action.ts);
+        self.prune(action.ts);
+    }
+
+    /// Updates the tracker with a context switch.
+    pub fn on_sched_switch(&mut self, action: &SchedSwitchAction) {
+        if action.prev_pid > 0 {
+            if action.prev_used_slice_ns > 0 {
+                self.processes
+                    .entry(action.prev_tgid)
+                    .or_default()
+                    .slice
+                    .record(action.prev_used_slice_ns);
+                self.cpus
+                    .entry(action.cpu)
+                    .or_default()
+                    .slice
+                    .record(action.prev_used_slice_ns);
+            }
+            // A task that is switched out while running stays on the runqueue
+            if action.prev_state == 0 {
+                self.preempt_ts.insert(action.prev_pid, action.ts);
+            }
+        }
+
+        if action.next_pid == 0 {
+            return;
+        }
+        if action.next_pid == action.next_tgid || !self.comms.contains_key(&action.next_tgid) {
+            self.comms
+                .insert(action.next_tgid, action.next_comm.to_string());
+        }
+
+        let wakeup_lat = self
+            .wakeup_ts
+            .remove(&action.next_pid)
+            // Events from different ringbuffers can be reordered
+            .filter(|ts| *ts <= action.ts)
+            .map(|ts| action.ts - ts);
+        let preempt_wait = self
+            .preempt_ts
+            .remove(&action.next_pid)
+            .filter(|ts| *ts <= action.ts)
+            .map(|ts| action.ts - ts);
+
+        let process = self.processes.entry(action.next_tgid).or_default();
+        let cpu = self.cpus.entry(action.cpu).or_default();
+        if let Some(lat) = wakeup_lat {
+            process.wakeup_lat.record(lat);
+            cpu.wakeup_lat.record(lat);
+            self.heatmap_column[Self::heatmap_row(lat)] += 1;
+        }
+        if let Some(wait) = wakeup_lat.or(preempt_wait) {
+            process.rq_wait.record(wait);
+            cpu.rq_wait.record(wait);
+        }
+    }
+
+    /// Drops pending wakeups and preemptions that never got switched in.
+    fn prune(&mut self, now: u64) {
+        if self.wakeup_ts.len() + self.preempt_ts.len() <= MAX_PENDING_TASKS {
+            return;
+        }
+        let min_ts = now.saturating_sub(MAX_PENDING_NS);
+        self.wakeup_ts.retain(|_, ts| *ts >= min_ts);
+        self.preempt_ts.retain(|_, ts| *ts >= min_ts);
+    }
+
+    /// Closes the current heatmap column, should be called once per tick.
+    pub fn on_tick(&mut self) {
+        self.heatmap.push_back(self.heatmap_column);
+        self.heatmap_column = [0; HEATMAP_ROWS];
+        while self.heatmap.len() > self.max_columns {
+            self.heatmap.pop_front();
+        }
+    }
+
+    /// Removes the processes that are not in the set of live processes.
+    pub fn retain_processes(&mut self, is_alive: impl Fn(u32) -> bool) {
+        self.processes.retain(|tgid, _| is_alive(*tgid));
+        self.comms.retain(|tgid, _| is_alive(*tgid));
+    }
+
+    /// Returns an export of all the latency data.
+    pub fn export(&self) -> LatencyExport {
+        LatencyExport {
+            processes: self
+                .processes
+                .iter()
+                .map(|(tgid, lat)| ProcessLatencyExport {
+                    tgid: *tgid,
+                    comm: self.comm(*tgid).to_string(),
+                    wakeup_lat_ns: lat.wakeup_lat.summary(),
+                    rq_wait_ns: lat.rq_wait.summary(),
+                    slice_ns: lat.slice.summary(),
+                })
+                .collect(),
+            cpus: self
+                .cpus
+                .iter()
+                .map(|(cpu, lat)| CpuLatencyExport {
+                    cpu: *cpu,
+                    wakeup_lat_ns: lat.wakeup_lat.summary(),
+                    rq_wait_ns: lat.rq_wait.summary(),
+                    slice_ns: lat.slice.summary(),
+                })
+                .collect(),
+            heatmap: HeatmapExport {
+                row_lower_us: (0..HEATMAP_ROWS)
+                    .map(|row| if row == 0 { 0 } else { 1 << row })
+                    .collect(),
+                columns: self.heatmap.iter().map(|column| column.to_vec()).collect(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SchedWakeActionCtx;
+
+    fn wakeup(ts: u64, pid: u32) -> SchedWakeupAction {
+        SchedWakeActionCtx {
+            ts,
+            cpu: 0,
+            pid,
+            tgid: pid,
+            prio: 120,
+            cgroup_id: 0,
+            comm: "test".into(),
+            waker_pid: 0,
+            waker_comm: "".into(),
+        }
+    }
+
+    fn switch(
+        ts: u64,
+        prev_pid: u32,
+        prev_slice_ns: u64,
+        prev_state: u64,
+        next_pid: u32,
+    ) -> SchedSwitchAction {
+        SchedSwitchAction {
+            ts,
+            cpu: 1,
+            preempt: false,
+            next_dsq_id: 0,
+            next_dsq_lat_us: 0,
+            next_dsq_nr_queued: 0,
+            next_dsq_vtime: 0,
+            next_slice_ns: 0,
+            next_pid,
+            next_tgid: next_pid,
+            next_prio: 120,
+            next_layer_id: -1,
+            next_cgroup_id: 0,
+            next_comm: format!("task{next_pid}").into(),
+            prev_dsq_id: 0,
+            prev_used_slice_ns: prev_slice_ns,
+            prev_slice_ns: 0,
+            prev_pid,
+            prev_tgid: prev_pid,
+            prev_prio: 120,
+            prev_comm: format!("task{prev_pid}").into(),
+            prev_state,
+            prev_layer_id: -1,
+        }
+    }
+
+    #[test]
+    fn test_buckets() {
+        for value in [0, 1, 7, 8, 9, 15, 16, 17, 18, 1000, 123_456_789, u64::MAX] {
+            let index = LatencyHistogram::bucket_index(value);
+            assert!(LatencyHistogram::bucket_lower(index) <= value);
+            assert!(LatencyHistogram::bucket_upper(index) >= value);
+        }
+        // Buckets are contiguous
+        for index in 0..200 {
+            assert_eq!(
+                LatencyHistogram::bucket_upper(index) + 1,
+                LatencyHistogram::bucket_lower(index + 1)
+            );
+        }
+        // The relative error is bounded by the number of sub buckets
+        let index = LatencyHistogram::bucket_index(1_000_000);
+        let width =
+            LatencyHistogram::bucket_upper(index) - LatencyHistogram::bucket_lower(index) + 1;
+        assert!(width <= 1_000_000 / SUB_BUCKETS as u64);
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut hist = LatencyHistogram::default();
+        assert_eq!(hist.percentile(99.0), 0);
+        for value in 1..=1000 {
+            hist.record(value);
+        }
+        assert_eq!(hist.count(), 1000);
+        assert_eq!(hist.min(), 1);
+        assert_eq!(hist.max(), 1000);
+        assert_eq!(hist.mean(), 500);
+        let p50 = hist.percentile(50.0);
+        assert!((500..=500 + 500 / SUB_BUCKETS as u64).contains(&p50));
+        assert_eq!(hist.percentile(100.0), 1000);
+        assert_eq!(hist.percentile(0.0), 1);
+
+        let mut merged = LatencyHistogram::default();
+        merged.merge(&hist);
+        merged.merge(&hist);
+        assert_eq!(merged.count(), 2000);
+        assert_eq!(merged.percentile(50.0), p50);
+        assert_eq!(
+            merged.buckets().map(|b| b.count).sum::<u64>(),
+            merged.count()
+        );
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = LatencyTracker::new(2);
+        // pid 10 wakes up and runs 50us later
+        tracker.on_sched_wakeup(&wakeup(1_000, 10));
+        tracker.on_sched_switch(&switch(51_000, 0, 0, 0, 10));
+        // pid 10 is preempted by pid 20 after running 2ms and runs again 3ms later
+        tracker.on_sched_switch(&switch(2_051_000, 10, 2_000_000, 0, 20));
+        tracker.on_sched_switch(&switch(5_051_000, 20, 3_000_000, 1, 10));
+        tracker.on_tick();
+
+        let process = &tracker.processes[&10];
+        assert_eq!(process.wakeup_lat.count(), 1);
+        assert_eq!(process.wakeup_lat.max(), 50_000);
+        assert_eq!(process.rq_wait.count(), 2);
+        assert_eq!(process.rq_wait.max(), 3_000_000);
+        assert_eq!(process.slice.max(), 2_000_000);
+        assert_eq!(tracker.processes[&20].slice.count(), 1);
+        assert_eq!(tracker.cpus[&1].slice.count(), 2);
+        assert_eq!(tracker.comm(10), "task10");
+
+        // pid 20 went to sleep, its next switch in has no runqueue wait
+        tracker.on_sched_switch(&switch(6_000_000, 10, 1_000, 1, 20));
+        assert_eq!(tracker.processes[&20].rq_wait.count(), 0);
+
+        tracker.on_tick();
+        tracker.on_tick();
+        let columns: Vec<_> = tracker.heatmap().collect();
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0].iter().sum::<u64>(), 0);
+
+        let export = tracker.export();
+        assert_eq!(export.processes.len(), 2);
+        assert_eq!(export.cpus[0].cpu, 1);
+        assert_eq!(export.heatmap.row_lower_us[..3], [0, 2, 4]);
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["processes"][0]["wakeup_lat_ns"]["max"], 50_000);
+
+        tracker.retain_processes(|tgid| tgid == 10);
+        assert_eq!(tracker.processes.len(), 1);
+    }
+
+    #[test]
+    fn test_heatmap_row() {
+        assert_eq!(LatencyTracker::heatmap_row(500), 0);
+        assert_eq!(LatencyTracker::heatmap_row(1_500), 0);
+        assert_eq!(LatencyTracker::heatmap_row(2_000), 1);
+        assert_eq!(LatencyTracker::heatmap_row(50_000), 5);
+        assert_eq!(LatencyTracker::heatmap_row(u64::MAX), HEATMAP_ROWS - 1);
+    }
+}
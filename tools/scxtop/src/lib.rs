@@ -3,12 +3,14 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+pub mod alerts;
 mod app;
 pub mod bandwidth_stats;
 pub mod bpf_intf;
 mod bpf_prog_data;
 pub mod bpf_skel;
 mod bpf_stats;
+mod cgroup_data;
 pub mod cli;
 mod columns;
 pub mod config;
@@ -16,7 +18,9 @@ mod cpu_data;
 mod cpu_stats;
 pub mod edm;
 mod event_data;
+pub mod flight_recorder;
 mod keymap;
+mod latency_data;
 pub mod layered_util;
 mod llc_data;
 pub mod mangoapp;
@@ -28,28 +32,38 @@ mod perfetto_trace;
 mod power_data;
 mod proc_data;
 pub mod profiling_events;
+pub mod remote;
 pub mod render;
+pub mod replay;
 pub mod search;
+pub mod snapshot;
 mod stats;
 mod symbol_data;
 mod theme;
 mod thread_data;
 pub mod topology;
+pub mod trace_export;
 pub mod tracer;
 mod tui;
 pub mod util;
 
 pub use crate::bpf_skel::types::bpf_event;
 pub use app::App;
+pub use app::EventDataSnapshot;
 pub use bandwidth_stats::{BandwidthSnapshot, BandwidthStats, LlcBandwidth};
 pub use bpf_prog_data::{BpfProgData, BpfProgStats};
 pub use bpf_skel::*;
+pub use cgroup_data::{CgroupData, CgroupTree};
 pub use columns::{Column, Columns};
 pub use cpu_data::CpuData;
 pub use cpu_stats::{CpuStatSnapshot, CpuStatTracker};
 pub use event_data::EventData;
 pub use keymap::Key;
 pub use keymap::KeyMap;
+pub use latency_data::{
+    HistogramBucket, HistogramSummary, LatencyExport, LatencyHistogram, LatencyTracker,
+    SchedLatency,
+};
 pub use llc_data::LlcData;
 pub use mem_stats::MemStatSnapshot;
 pub use network_stats::NetworkStatSnapshot;
@@ -96,12 +110,16 @@ pub enum AppState {
     BpfPrograms,
     /// Application is in the BPF program detail state.
     BpfProgramDetail,
+    /// Application is in the cgroup state.
+    Cgroup,
     /// Application is in the default state.
     Default,
     /// Application is in the help state.
     Help,
     /// Application is in the KprobeEvent list state.
     KprobeEvent,
+    /// Application is in the latency histogram state.
+    Latency,
     /// Application is in the Llc state.
     Llc,
     /// Application is in the mangoapp state.
@@ -287,6 +305,7 @@ pub struct SchedSwitchAction {
     pub next_tgid: u32,
     pub next_prio: i32,
     pub next_layer_id: i32,
+    pub next_cgroup_id: u64,
     pub next_comm: SsoString,
     pub prev_dsq_id: u64,
     pub prev_used_slice_ns: u64,
@@ -306,6 +325,7 @@ pub struct SchedWakeActionCtx {
     pub pid: u32,
     pub tgid: u32,
     pub prio: i32,
+    pub cgroup_id: u64,
     pub comm: SsoString,
     pub waker_pid: u32,
     pub waker_comm: SsoString,
@@ -322,6 +342,7 @@ pub struct SchedMigrateTaskAction {
     pub dest_cpu: u32,
     pub pid: u32,
     pub prio: i32,
+    pub cgroup_id: u64,
     pub comm: SsoString,
 }
 
@@ -462,6 +483,7 @@ pub enum Action {
     Esc,
     Exec(ExecAction),
     Exit(ExitAction),
+    ExportLatency,
     Filter,
     Fork(ForkAction),
     Kprobe(KprobeAction),
@@ -486,6 +508,10 @@ pub enum Action {
     TraceStarted(TraceStartedAction),
     TraceStopped(TraceStoppedAction),
     ReloadStatsClient,
+    ReplaySeekBackward,
+    ReplaySeekForward,
+    ReplaySlowDown,
+    ReplaySpeedUp,
     SaveConfig,
     SchedCpuPerfSet(SchedCpuPerfSetAction),
     SchedHang(SchedHangAction),
@@ -598,6 +624,7 @@ impl TryFrom<&bpf_event> for Action {
                     pid: wakeup.pid,
                     tgid: wakeup.tgid,
                     prio: wakeup.prio,
+                    cgroup_id: wakeup.cgroup_id,
                     comm: comm.into(),
                     waker_pid: wakeup.waker_pid,
                     waker_comm: waker_comm.into(),
@@ -616,6 +643,7 @@ impl TryFrom<&bpf_event> for Action {
                     pid: waking.pid,
                     tgid: waking.tgid,
                     prio: waking.prio,
+                    cgroup_id: waking.cgroup_id,
                     comm: comm.into(),
                     waker_pid: waking.waker_pid,
                     waker_comm: waker_comm.into(),
@@ -633,6 +661,7 @@ impl TryFrom<&bpf_event> for Action {
                     dest_cpu: migrate.dest_cpu,
                     pid: migrate.pid,
                     prio: migrate.prio,
+                    cgroup_id: migrate.cgroup_id,
                     comm: comm.into(),
                 }))
             }
@@ -752,6 +781,7 @@ impl TryFrom<&bpf_event> for Action {
                     next_tgid: sched_switch.next_tgid,
                     next_prio: sched_switch.next_prio,
                     next_layer_id: sched_switch.next_layer_id,
+                    next_cgroup_id: sched_switch.next_cgroup_id,
                     next_comm: next_comm.into(),
                     prev_dsq_id: sched_switch.prev_dsq_id,
                     prev_used_slice_ns: sched_switch.prev_used_slice_ns,
@@ -815,9 +845,16 @@ impl std::fmt::Display for Action {
             Action::SetState(AppState::Llc) => write!(f, "AppStateLlc"),
             Action::SetState(AppState::Network) => write!(f, "AppStateNetwork"),
             Action::SetState(AppState::Node) => write!(f, "AppStateNode"),
+            Action::SetState(AppState::Cgroup) => write!(f, "AppStateCgroup"),
+            Action::SetState(AppState::Latency) => write!(f, "AppStateLatency"),
             Action::SetState(AppState::Scheduler) => write!(f, "AppStateScheduler"),
             Action::SaveConfig => write!(f, "SaveConfig"),
             Action::RequestTrace => write!(f, "RequestTrace"),
+            Action::ExportLatency => write!(f, "ExportLatency"),
+            Action::ReplaySeekBackward => write!(f, "ReplaySeekBackward"),
+            Action::ReplaySeekForward => write!(f, "ReplaySeekForward"),
+            Action::ReplaySlowDown => write!(f, "ReplaySlowDown"),
+            Action::ReplaySpeedUp => write!(f, "ReplaySpeedUp"),
             Action::TraceStarted(_) => write!(f, "TraceStarted"),
             Action::PerfSampleRateIncrease => write!(f, "PerfSampleRateIncrease"),
             Action::PerfSampleRateDecrease => write!(f, "PerfSampleRateDecrease"),
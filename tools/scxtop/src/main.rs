@@ -3,13 +3,23 @@
 // This software may be used and distributed according to the terms of the
 // GNU General Public License version 2.
 
+use scx_stats::prelude::StatsClient;
 use scx_utils::compat;
+use scx_utils::Topology;
 use scxtop::bpf_skel::types::bpf_event;
-use scxtop::cli::{generate_completions, Cli, Commands, TraceArgs, TuiArgs};
+use scxtop::cli::{
+    generate_completions, Cli, CollectArgs, Commands, SnapshotArgs, TraceArgs, TuiArgs,
+};
 use scxtop::config::Config;
 use scxtop::edm::{ActionHandler, BpfEventActionPublisher, BpfEventHandler, EventDispatchManager};
+use scxtop::flight_recorder::{FlightRecorder, FlightRecorderConfig};
 use scxtop::layered_util;
 use scxtop::mangoapp::poll_mangoapp;
+use scxtop::mcp::perfetto_parser::PerfettoTrace;
+use scxtop::remote::{
+    decode_bpf_event, remove_socket, CollectorServer, Hello, RemoteAddr, RemoteClient,
+};
+use scxtop::replay::{trace_events, Replay, ReplaySnapshots, REPLAY_SNAPSHOT_NS};
 use scxtop::search;
 use scxtop::tracer::Tracer;
 use scxtop::util::{
@@ -54,14 +64,65 @@ use std::fs::File;
 use std::mem::MaybeUninit;
 use std::os::fd::AsFd;
 use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
 use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use sysinfo::System;
 use tokio::sync::mpsc;
 
+/// Size of each event ringbuffer (must be power of 2)
+const EVENT_RINGBUF_SIZE: u32 = 32 * 1024 * 1024;
+
+/// Callback on the events read from the event ringbuffers, gets the event and its raw bytes.
+type EventCallback = Box<dyn FnMut(&bpf_event, &[u8]) + Send>;
+
+/// Returns an EventCallback publishing the events as Actions.
+fn publish_actions(action_tx: mpsc::UnboundedSender<Action>) -> EventCallback {
+    let mut publisher = BpfEventActionPublisher::new(action_tx);
+    Box::new(move |event, _| {
+        let _ = publisher.on_event(event);
+    })
+}
+
+struct RingBufContext {
+    shutdown: Arc<AtomicBool>,
+    on_event: EventCallback,
+}
+
+extern "C" fn ring_buffer_sample_callback(
+    ctx: *mut std::ffi::c_void,
+    data: *mut std::ffi::c_void,
+    size: u64,
+) -> std::ffi::c_int {
+    // SAFETY: ctx is the context owned by the EventRingBuffer being polled and data points to
+    // an event of size bytes.
+    let (ctx, data) = unsafe {
+        (
+            &mut *(ctx as *mut RingBufContext),
+            std::slice::from_raw_parts(data as *const u8, size as usize),
+        )
+    };
+
+    // Stop processing if shutdown requested
+    if ctx.shutdown.load(Ordering::Relaxed) {
+        return 0;
+    }
+
+    let event = decode_bpf_event(data);
+    // Drop events with invalid timestamps
+    if event.ts == 0 {
+        return 0;
+    }
+
+    let len = std::cmp::min(data.len(), std::mem::size_of::<bpf_event>());
+    (ctx.on_event)(&event, &data[..len]);
+    0
+}
+
 // Wrapper to make ring buffer pointer Send-safe for tokio spawn
 // SAFETY: We ensure the pointer remains valid for the task lifetime
 struct SendRingBuffer(*mut libbpf_sys::ring_buffer);
@@ -81,6 +142,170 @@ impl SendRingBuffer {
     }
 }
 
+/// Ring buffer manager of one of the event ringbuffers. We use the raw libbpf C API because the
+/// ringbuffers are created dynamically.
+struct EventRingBuffer {
+    rb: *mut libbpf_sys::ring_buffer,
+    ctx: *mut RingBufContext,
+    _fd: OwnedFd,
+}
+
+// SAFETY: The ring buffer manager and its context are only used by the task polling it.
+unsafe impl Send for EventRingBuffer {}
+
+impl EventRingBuffer {
+    fn new(fd: OwnedFd, shutdown: Arc<AtomicBool>, on_event: EventCallback) -> Result<Self> {
+        let ctx = Box::into_raw(Box::new(RingBufContext { shutdown, on_event }));
+        let rb = unsafe {
+            libbpf_sys::ring_buffer__new(
+                fd.as_raw_fd(),
+                Some(ring_buffer_sample_callback),
+                ctx as *mut std::ffi::c_void,
+                std::ptr::null(),
+            )
+        };
+        if rb.is_null() {
+            drop(unsafe { Box::from_raw(ctx) });
+            bail!("Failed to create ring buffer manager");
+        }
+        Ok(Self { rb, ctx, _fd: fd })
+    }
+
+    fn poll(&self, timeout: i32) -> i32 {
+        unsafe { libbpf_sys::ring_buffer__poll(self.rb, timeout) }
+    }
+
+    fn consume(&self) -> i32 {
+        unsafe { libbpf_sys::ring_buffer__consume(self.rb) }
+    }
+}
+
+impl Drop for EventRingBuffer {
+    fn drop(&mut self) {
+        // The context is freed after the manager calling back into it.
+        unsafe {
+            libbpf_sys::ring_buffer__free(self.rb);
+            drop(Box::from_raw(self.ctx));
+        }
+    }
+}
+
+/// Sizes the CPU-to-ringbuffer map and the events hash-of-maps of an opened skel.
+fn size_event_ringbufs(skel: &mut OpenBpfSkel, num_cpus: usize, rb_cnt: usize) -> Result<()> {
+    let cpu_cnt_pow2 = num_cpus.next_power_of_two();
+    skel.maps.rodata_data.as_mut().unwrap().rb_cpu_map_mask = (cpu_cnt_pow2 - 1) as u64;
+    skel.maps
+        .data_rb_cpu_map
+        .set_max_entries(cpu_cnt_pow2 as u32)?;
+    skel.maps.events.set_max_entries(rb_cnt as u32)?;
+    Ok(())
+}
+
+/// Populates the CPU-to-ringbuffer mapping of a loaded skel, then creates the ringbuffers and
+/// adds them to the events hash-of-maps.
+fn create_event_ringbufs(skel: &BpfSkel, num_cpus: usize, rb_cnt: usize) -> Result<Vec<OwnedFd>> {
+    log::info!("Using {} ringbuffers for {} CPUs", rb_cnt, num_cpus);
+
+    let rb_cpu_mapping = scxtop::topology::setup_cpu_to_ringbuf_mapping(rb_cnt, num_cpus)?;
+    let cpu_cnt_pow2 = num_cpus.next_power_of_two();
+    for (cpu_id, &rb_id) in rb_cpu_mapping.iter().enumerate() {
+        if cpu_id < cpu_cnt_pow2 {
+            skel.maps.data_rb_cpu_map.update(
+                &(cpu_id as u32).to_ne_bytes(),
+                &rb_id.to_ne_bytes(),
+                libbpf_rs::MapFlags::ANY,
+            )?;
+        }
+    }
+
+    let events_map_fd = skel.maps.events.as_fd().as_raw_fd();
+    let mut rb_fds = Vec::with_capacity(rb_cnt);
+    for rb_id in 0..rb_cnt {
+        let rb_fd = unsafe {
+            libbpf_sys::bpf_map_create(
+                libbpf_sys::BPF_MAP_TYPE_RINGBUF,
+                std::ptr::null(),
+                0,
+                0,
+                EVENT_RINGBUF_SIZE,
+                std::ptr::null(),
+            )
+        };
+        if rb_fd < 0 {
+            bail!(
+                "Failed to create ringbuffer #{}: {}",
+                rb_id,
+                std::io::Error::last_os_error()
+            );
+        }
+        // SAFETY: rb_fd was just created and isn't owned by anything else.
+        let rb_fd = unsafe { OwnedFd::from_raw_fd(rb_fd) };
+
+        // Add ringbuffer to hash-of-maps
+        let rb_id_u32 = rb_id as u32;
+        let raw_fd = rb_fd.as_raw_fd();
+        let ret = unsafe {
+            libbpf_sys::bpf_map_update_elem(
+                events_map_fd,
+                &rb_id_u32 as *const u32 as *const std::ffi::c_void,
+                &raw_fd as *const i32 as *const std::ffi::c_void,
+                libbpf_sys::BPF_NOEXIST.into(),
+            )
+        };
+        if ret < 0 {
+            bail!(
+                "Failed to add ringbuffer #{} to hash-of-maps: {}",
+                rb_id,
+                std::io::Error::last_os_error()
+            );
+        }
+
+        rb_fds.push(rb_fd);
+    }
+
+    Ok(rb_fds)
+}
+
+/// Creates a ring buffer manager for each ringbuffer, calling the callback returned by
+/// `new_callback` on its events.
+fn new_ringbuf_managers(
+    rb_fds: Vec<OwnedFd>,
+    shutdown: &Arc<AtomicBool>,
+    mut new_callback: impl FnMut() -> EventCallback,
+) -> Result<Vec<EventRingBuffer>> {
+    rb_fds
+        .into_iter()
+        .map(|rb_fd| EventRingBuffer::new(rb_fd, shutdown.clone(), new_callback()))
+        .collect()
+}
+
+/// Polls each ring buffer manager on a blocking task until shutdown is requested.
+fn spawn_ringbuf_pollers(
+    rb_managers: Vec<EventRingBuffer>,
+    shutdown: &Arc<AtomicBool>,
+) -> Vec<tokio::task::JoinHandle<()>> {
+    rb_managers
+        .into_iter()
+        .enumerate()
+        .map(|(rb_id, rb)| {
+            let stop_poll = shutdown.clone();
+            // Use spawn_blocking because rb.poll() is a blocking C FFI call
+            tokio::task::spawn_blocking(move || {
+                loop {
+                    // Poll with 1ms timeout (blocking call)
+                    rb.poll(1);
+                    if stop_poll.load(Ordering::Relaxed) {
+                        // Consume remaining events
+                        rb.consume();
+                        break;
+                    }
+                }
+                debug!("ringbuffer #{} polling stopped", rb_id);
+            })
+        })
+        .collect()
+}
+
 fn get_action(app: &App, keymap: &KeyMap, event: Event) -> Action {
     match event {
         Event::Error => Action::None,
@@ -422,6 +647,12 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
             if let Ok(link) = skel.progs.on_softirq_exit.attach() {
                 links.push(link);
             }
+            // Hung tasks trigger flight recorder traces
+            if trace_args.flight_recorder {
+                if let Ok(link) = skel.progs.on_sched_hang.attach() {
+                    links.push(link);
+                }
+            }
 
             // Counter for events dropped due to invalid timestamps (userspace filtering)
             let dropped_invalid_ts = Arc::new(std::sync::atomic::AtomicU64::new(0));
@@ -628,9 +859,42 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 }));
             }
 
+            if trace_args.flight_recorder && !trace_args.trigger_stat.is_empty() {
+                let stop_sched_stats = shutdown.clone();
+                let stats_socket_path = trace_args.stats_socket_path.clone();
+                let action_tx_clone = action_tx.clone();
+
+                // The stats client blocks on the socket
+                producer_handles.push(tokio::task::spawn_blocking(move || {
+                    info!("scheduler stats task started");
+                    let mut client = None;
+                    while !stop_sched_stats.load(Ordering::Relaxed) {
+                        if client.is_none() {
+                            client = StatsClient::new()
+                                .set_path(&stats_socket_path)
+                                .connect(None)
+                                .ok();
+                        }
+                        if let Some(ref mut stats_client) = client {
+                            match stats_client.request::<serde_json::Value>("stats", vec![]) {
+                                Ok(stats) => {
+                                    let _ =
+                                        action_tx_clone.send(Action::SchedStats(stats.to_string()));
+                                }
+                                // Reconnect on the next poll, the scheduler may have restarted
+                                Err(_) => client = None,
+                            }
+                        }
+                        std::thread::sleep(Duration::from_secs(1));
+                    }
+                    info!("scheduler stats task exiting");
+                }));
+            }
+
             let trace_file_prefix = config.trace_file_prefix().to_string();
             let trace_file = trace_args.output_file.clone();
             let mut trace_manager = PerfettoTraceManager::new(trace_file_prefix, None);
+            trace_manager.set_format(trace_args.format);
 
             // Embed topology metadata in traces for cross-machine analysis
             {
@@ -648,8 +912,39 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 }
             }
 
-            info!("starting trace for {}ms", trace_args.trace_ms);
-            trace_manager.start()?;
+            // The flight recorder owns the trace manager and writes a trace on every trigger
+            let (mut recorder, mut trace_manager) = if trace_args.flight_recorder {
+                info!(
+                    "starting flight recorder with a {}ms window",
+                    trace_args.trace_ms
+                );
+                let window_ns = trace_args.trace_ms * 1_000_000;
+                let max_events = trace_args.max_events.unwrap_or_else(|| {
+                    FlightRecorderConfig::default_max_events(
+                        window_ns,
+                        num_cpus,
+                        trace_args.max_memory_mb * 1024 * 1024,
+                    )
+                });
+                info!("flight recorder keeps at most {max_events} events");
+                let recorder = FlightRecorder::new(
+                    FlightRecorderConfig {
+                        window_ns,
+                        max_events,
+                        rq_lat_threshold_us: trace_args.trigger_rq_lat_us,
+                        stat_limits: trace_args.trigger_stat.clone(),
+                        min_dump_interval: Duration::from_millis(trace_args.trigger_interval_ms),
+                        max_disk_bytes: trace_args.max_disk_mb * 1024 * 1024,
+                        output_prefix: config.trace_file_prefix().to_string(),
+                    },
+                    trace_manager,
+                );
+                (Some(recorder), None)
+            } else {
+                info!("starting trace for {}ms", trace_args.trace_ms);
+                trace_manager.start()?;
+                (None, Some(trace_manager))
+            };
             let mut tracer = Tracer::new(skel);
             tracer.trace(&trace_args.kprobes)?;
 
@@ -658,6 +953,8 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 debug!("trace generation task started");
                 let mut count = 0;
                 let mut last_log = std::time::Instant::now();
+                // Flight recorder traces are written off this task to keep consuming actions
+                let mut dump_handles = Vec::new();
                 loop {
                     tokio::select! {
                         // Check shutdown flag to stop early if requested
@@ -673,9 +970,22 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                                     debug!("trace task: {} events processed", count);
                                     last_log = std::time::Instant::now();
                                 }
-                                trace_manager
-                                    .on_action(&a)
-                                    .expect("Action should have been resolved");
+                                if let Some(ref mut recorder) = recorder {
+                                    if let Some(dump) = recorder.on_action(&a) {
+                                        dump_handles.retain(|h| !h.is_finished());
+                                        dump_handles.push(tokio::task::spawn_blocking(move || {
+                                            if let Err(e) = dump.write() {
+                                                log::error!(
+                                                    "flight recorder failed to write trace: {e}"
+                                                );
+                                            }
+                                        }));
+                                    }
+                                } else if let Some(ref mut trace_manager) = trace_manager {
+                                    trace_manager
+                                        .on_action(&a)
+                                        .expect("Action should have been resolved");
+                                }
                                 // After processing, check shutdown to avoid
                                 // draining the entire buffered channel through
                                 // select! one event at a time.
@@ -691,6 +1001,16 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 // Drain remaining events in a tight loop without select!
                 // overhead. This is much faster for large buffered channels.
                 info!("trace task: draining remaining events");
+                let Some(mut trace_manager) = trace_manager else {
+                    join_all(dump_handles).await;
+                    if let Some(recorder) = recorder {
+                        info!(
+                            "flight recorder stopped, wrote {} traces from {count} events",
+                            recorder.dumps().len()
+                        );
+                    }
+                    return;
+                };
                 while let Ok(a) = action_rx.try_recv() {
                     count += 1;
                     trace_manager
@@ -702,9 +1022,15 @@ fn run_trace(trace_args: &TraceArgs) -> Result<()> {
                 info!("trace file compiled, collected {count} events");
             });
 
-            info!("waiting for trace duration ({}ms)", trace_args.trace_ms);
-            tokio::time::sleep(Duration::from_millis(trace_args.trace_ms)).await;
-            info!("trace duration complete, beginning shutdown");
+            if trace_args.flight_recorder {
+                info!("flight recorder running, press Ctrl-C to stop");
+                tokio::signal::ctrl_c().await?;
+                info!("flight recorder interrupted, beginning shutdown");
+            } else {
+                info!("waiting for trace duration ({}ms)", trace_args.trace_ms);
+                tokio::time::sleep(Duration::from_millis(trace_args.trace_ms)).await;
+                info!("trace duration complete, beginning shutdown");
+            }
 
             // Proper shutdown sequence to avoid hanging:
             // 1) Stop new BPF events by detaching programs
@@ -830,7 +1156,27 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
             )> = None;
             let mut skel_opt = None;
 
-            if has_bpf_cap {
+            // Events of a replayed trace are fed through the action channel instead of BPF
+            let replay_trace = tui_args
+                .replay
+                .as_ref()
+                .map(|path| PerfettoTrace::from_file(path))
+                .transpose()?;
+            // Events of a remote collector are fed through the action channel as well
+            let remote = match &tui_args.connect {
+                Some(addr) => Some(
+                    RemoteClient::connect(addr)
+                        .await
+                        .map_err(|e| anyhow!("Failed to attach to collector {addr}: {e}"))?,
+                ),
+                None => None,
+            };
+
+            if replay_trace.is_some() {
+                info!("replaying trace, BPF event collection disabled");
+            } else if remote.is_some() {
+                info!("attached to collector, BPF event collection disabled");
+            } else if has_bpf_cap {
                 // Try to initialize BPF components
                 let mut builder = BpfSkelBuilder::default();
                 if config.debug() {
@@ -1057,7 +1403,14 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
             }
 
             let mut tui = Tui::new(keymap.clone(), config.tick_rate_ms(), config.frame_rate_ms())?;
-            let scheduler = read_file_string(SCHED_NAME_PATH).unwrap_or("".to_string());
+            let scheduler = match (&replay_trace, &remote) {
+                (Some(trace), _) => trace
+                    .get_scx_metadata()
+                    .and_then(|metadata| metadata.scheduler_name.clone())
+                    .unwrap_or_default(),
+                (None, Some(remote)) => remote.hello().scheduler.clone(),
+                (None, None) => read_file_string(SCHED_NAME_PATH).unwrap_or("".to_string()),
+            };
 
             // Create app with or without BPF skeleton
             let mut app = if let Some(skel) = skel_opt {
@@ -1196,8 +1549,59 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
                 });
             }
 
+            if let Some(remote) = remote {
+                app.set_remote(true);
+                let topo = Topology::new()?;
+                if remote.hello().nr_cpus as usize != num_cpus {
+                    log::warn!(
+                        "collector has {} CPUs and this host {}, events of CPUs missing locally are dropped",
+                        remote.hello().nr_cpus,
+                        num_cpus
+                    );
+                }
+                let tx = action_tx.clone();
+                tokio::spawn(async move {
+                    let is_valid_cpu = |cpu: u32| topo.all_cpus.contains_key(&(cpu as usize));
+                    match remote.forward_actions(tx, is_valid_cpu).await {
+                        Ok(()) => log::warn!("collector disconnected"),
+                        Err(e) => log::error!("collector stream failed: {e}"),
+                    }
+                });
+            }
+
+            // Events on CPUs that aren't in the local topology can't be shown
+            let mut replay = match replay_trace {
+                Some(trace) => {
+                    let topo = Topology::new()?;
+                    let events = trace_events(&trace, |cpu| {
+                        topo.all_cpus.contains_key(&(cpu as usize))
+                    });
+                    info!("replaying {} events", events.len());
+                    Some(Replay::new(events))
+                }
+                None => None,
+            };
+            let mut replay_snapshots = ReplaySnapshots::new(REPLAY_SNAPSHOT_NS);
+            let mut replay_interval = tokio::time::interval(Duration::from_millis(10));
+            let mut last_replay_tick = Instant::now();
+
             loop {
                 tokio::select! {
+                    _ = replay_interval.tick(), if replay.is_some() => {
+                        let now = Instant::now();
+                        if let Some(replay) = replay.as_mut() {
+                            // Replayed actions are applied right away so that snapshots match them
+                            for action in replay.advance(now - last_replay_tick) {
+                                app.handle_action(&action)?;
+                            }
+                            if replay_snapshots.is_due(replay) {
+                                replay_snapshots.push(replay, app.event_data_snapshot());
+                            }
+                            app.set_replay_status(Some(replay.status()));
+                        }
+                        last_replay_tick = now;
+                    }
+
                     ev = tui.next() => {
                         let ev = ev?;
                         match ev {
@@ -1223,6 +1627,23 @@ fn run_tui(tui_args: &TuiArgs) -> Result<()> {
 
                     ac = action_rx.recv() => {
                         let ac = ac.ok_or(anyhow!("actions channel closed"))?;
+                        if let Some(replay) = replay.as_mut() {
+                            if replay.handle_action(&ac) {
+                                if matches!(ac, Action::ReplaySeekBackward) {
+                                    // The views accumulate events, rebuild them from the closest snapshot
+                                    let (snapshot, actions) = replay_snapshots.rewind(replay);
+                                    match snapshot {
+                                        Some(snapshot) => app.restore_event_data(snapshot),
+                                        None => app.reset_event_data(),
+                                    }
+                                    for action in actions {
+                                        app.handle_action(action)?;
+                                    }
+                                }
+                                app.set_replay_status(Some(replay.status()));
+                                continue;
+                            }
+                        }
                         app.handle_action(&ac)?;
                     }
                 }
@@ -1896,6 +2317,262 @@ fn run_mcp(mcp_args: &scxtop::cli::McpArgs) -> Result<()> {
     }
 }
 
+fn run_snapshot(snapshot_args: &SnapshotArgs) -> Result<()> {
+    // Log to stderr, stdout is used for the snapshots
+    TermLogger::init(
+        match snapshot_args.verbose {
+            0 => LevelFilter::Warn,
+            1 => LevelFilter::Info,
+            2 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        },
+        SimplelogConfig::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )?;
+
+    let mut out: Box<dyn std::io::Write> = match &snapshot_args.output_file {
+        Some(path) => Box::new(std::io::BufWriter::new(File::create(path)?)),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let num_cpus = num_possible_cpus()?;
+    let rb_cnt = scxtop::topology::calculate_default_ringbuf_count(num_cpus);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .worker_threads(rb_cnt + 2)
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut open_object = MaybeUninit::uninit();
+            let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+            let shutdown = Arc::new(AtomicBool::new(false));
+            let config = Config::default_config();
+            let scheduler = read_file_string(SCHED_NAME_PATH).unwrap_or_default();
+
+            let mut links = Vec::new();
+            let mut rb_managers = Vec::new();
+            let mut _map_handle = None;
+
+            let mut app = if check_bpf_capability() {
+                let mut skel = BpfSkelBuilder::default().open(&mut open_object)?;
+                if snapshot_args.layered {
+                    skel.maps.rodata_data.as_mut().unwrap().layered = true;
+                    _map_handle = Some(layered_util::attach_to_existing_map(
+                        "task_ctxs",
+                        &mut skel.maps.task_ctxs,
+                    )?);
+                }
+
+                size_event_ringbufs(&mut skel, num_cpus, rb_cnt)?;
+                let mut skel = skel.load()?;
+                let rb_fds = create_event_ringbufs(&skel, num_cpus, rb_cnt)?;
+
+                let (skel_links, attach_warnings) = attach_progs(&mut skel)?;
+                for warning in attach_warnings {
+                    log::warn!("{warning}");
+                }
+                links = skel_links;
+                skel.progs.scxtop_init.test_run(ProgramInput::default())?;
+
+                rb_managers =
+                    new_ringbuf_managers(rb_fds, &shutdown, || publish_actions(action_tx.clone()))?;
+
+                App::new(
+                    config,
+                    scheduler,
+                    100,
+                    snapshot_args.process_id,
+                    snapshot_args.layered,
+                    action_tx.clone(),
+                    skel,
+                )?
+            } else {
+                for warning in get_capability_warning_message() {
+                    log::warn!("{warning}");
+                }
+                App::new_without_bpf(
+                    config,
+                    scheduler,
+                    100,
+                    snapshot_args.process_id,
+                    snapshot_args.layered,
+                    action_tx.clone(),
+                )?
+            };
+
+            let ringbuffer_handles = spawn_ringbuf_pollers(rb_managers, &shutdown);
+
+            // The first collection only initializes the utilization deltas.
+            let (fields, max_procs) = (&snapshot_args.fields, snapshot_args.max_procs);
+            app.collect_snapshot(fields, max_procs)?;
+
+            let start = std::time::Instant::now();
+            let mut interval =
+                tokio::time::interval(Duration::from_millis(snapshot_args.interval_ms));
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            interval.tick().await;
+            let mut nr_snapshots = 0;
+            let result: Result<()> = loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let snapshot = match app.collect_snapshot(fields, max_procs) {
+                            Ok(snapshot) => snapshot,
+                            Err(e) => break Err(e),
+                        };
+                        if let Err(e) = snapshot.write_json_line(&mut out) {
+                            break Err(e);
+                        }
+                        nr_snapshots += 1;
+
+                        if snapshot_args.count.is_some_and(|count| nr_snapshots >= count)
+                            || snapshot_args
+                                .duration_ms
+                                .is_some_and(|ms| start.elapsed() >= Duration::from_millis(ms))
+                        {
+                            break Ok(());
+                        }
+                    }
+
+                    Some(action) = action_rx.recv() => {
+                        if let Err(e) = app.handle_action(&action) {
+                            break Err(e);
+                        }
+                    }
+
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("Received interrupt, stopping snapshots");
+                        break Ok(());
+                    }
+                }
+            };
+
+            shutdown.store(true, Ordering::Relaxed);
+            for handle in ringbuffer_handles {
+                if let Err(e) = handle.await {
+                    log::error!("Ringbuffer task panicked: {e}");
+                }
+            }
+            drop(links);
+
+            result
+        })
+}
+
+fn run_collect(collect_args: &CollectArgs) -> Result<()> {
+    TermLogger::init(
+        match collect_args.verbose {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        },
+        SimplelogConfig::default(),
+        TerminalMode::Stderr,
+        ColorChoice::Auto,
+    )?;
+
+    if !check_bpf_capability() {
+        for warning in get_capability_warning_message() {
+            log::warn!("{warning}");
+        }
+        bail!("The collector requires BPF capabilities");
+    }
+
+    let num_cpus = num_possible_cpus()?;
+    let rb_cnt = scxtop::topology::calculate_default_ringbuf_count(num_cpus);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .worker_threads(rb_cnt + 2)
+        .build()
+        .unwrap()
+        .block_on(async {
+            let mut open_object = MaybeUninit::uninit();
+            let shutdown = Arc::new(AtomicBool::new(false));
+            let scheduler = read_file_string(SCHED_NAME_PATH).unwrap_or_default();
+            let server = CollectorServer::new(
+                &Hello::new(num_cpus as u32, scheduler),
+                collect_args.client_buffer_events,
+            );
+
+            let mut skel = BpfSkelBuilder::default().open(&mut open_object)?;
+            let mut _map_handle = None;
+            if collect_args.layered {
+                skel.maps.rodata_data.as_mut().unwrap().layered = true;
+                _map_handle = Some(layered_util::attach_to_existing_map(
+                    "task_ctxs",
+                    &mut skel.maps.task_ctxs,
+                )?);
+            }
+
+            size_event_ringbufs(&mut skel, num_cpus, rb_cnt)?;
+            let mut skel = skel.load()?;
+            let rb_fds = create_event_ringbufs(&skel, num_cpus, rb_cnt)?;
+
+            let (links, attach_warnings) = attach_progs(&mut skel)?;
+            for warning in attach_warnings {
+                log::warn!("{warning}");
+            }
+            skel.progs.scxtop_init.test_run(ProgramInput::default())?;
+
+            let rb_managers = new_ringbuf_managers(rb_fds, &shutdown, || {
+                let server = server.clone();
+                // Records are forwarded as they are, each one holds a full bpf_event
+                Box::new(move |_, data| server.publish_event(data))
+            })?;
+
+            let ringbuffer_handles = spawn_ringbuf_pollers(rb_managers, &shutdown);
+
+            let stats_server = server.clone();
+            let stop_sched_stats = shutdown.clone();
+            let stats_socket_path = collect_args.stats_socket_path.clone();
+            let stats_interval = Duration::from_millis(collect_args.stats_interval_ms);
+            // The stats client blocks on the socket
+            tokio::task::spawn_blocking(move || {
+                let mut client = None;
+                while !stop_sched_stats.load(Ordering::Relaxed) {
+                    if client.is_none() && stats_server.nr_clients() > 0 {
+                        client = StatsClient::new()
+                            .set_path(&stats_socket_path)
+                            .connect(None)
+                            .ok();
+                    }
+                    if let Some(ref mut stats_client) = client {
+                        match stats_client.request::<serde_json::Value>("stats", vec![]) {
+                            Ok(stats) => stats_server.publish_stats(&stats.to_string()),
+                            // Reconnect on the next poll, the scheduler may have restarted
+                            Err(_) => client = None,
+                        }
+                    }
+                    std::thread::sleep(stats_interval);
+                }
+            });
+
+            info!("collector listening on {}", collect_args.listen);
+            let result = tokio::select! {
+                res = server.serve(&collect_args.listen, shutdown.clone()) => res,
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Received interrupt, stopping collector");
+                    Ok(())
+                }
+            };
+
+            shutdown.store(true, Ordering::Relaxed);
+            for handle in ringbuffer_handles {
+                if let Err(e) = handle.await {
+                    log::error!("Ringbuffer task panicked: {e}");
+                }
+            }
+            drop(links);
+            if let RemoteAddr::Unix(path) = &collect_args.listen {
+                let _ = remove_socket(path);
+            }
+
+            result
+        })
+}
+
 fn main() -> Result<()> {
     let args = Cli::parse();
 
@@ -1909,6 +2586,12 @@ fn main() -> Result<()> {
         Commands::Mcp(mcp_args) => {
             run_mcp(mcp_args)?;
         }
+        Commands::Snapshot(snapshot_args) => {
+            run_snapshot(snapshot_args)?;
+        }
+        Commands::Collect(collect_args) => {
+            run_collect(collect_args)?;
+        }
         Commands::GenerateCompletions { shell, output } => {
             generate_completions(Cli::command(), *shell, output.clone())
                 .unwrap_or_else(|_| panic!("Failed to generate completions for {shell}"));
@@ -18,6 +18,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use rayon::prelude::*;
 
 use crate::edm::ActionHandler;
+use crate::trace_export::{TraceEvents, TraceFormat};
 use crate::util::get_clock_value;
 use crate::{
     Action, CpuhpEnterAction, CpuhpExitAction, ExecAction, ExitAction, ForkAction, GpuMemAction,
@@ -64,6 +65,7 @@ pub struct PerfettoTraceManager {
     trusted_pid: i32,
     rng: StdRng,
     output_file_prefix: String,
+    format: TraceFormat,
 
     // per cpu ftrace events
     ftrace_events: BTreeMap<u32, Vec<FtraceEvent>>,
@@ -102,6 +104,7 @@ impl PerfettoTraceManager {
             trusted_pid: std::process::id() as i32,
             rng,
             output_file_prefix,
+            format: TraceFormat::default(),
             ftrace_events: BTreeMap::new(),
             dsq_uuids: BTreeMap::new(),
             dsq_lat_events: BTreeMap::new(),
@@ -148,6 +151,11 @@ impl PerfettoTraceManager {
         self.topology_json = Some(topo.to_string());
     }
 
+    /// Sets the format traces are written in.
+    pub fn set_format(&mut self, format: TraceFormat) {
+        self.format = format;
+    }
+
     /// Starts a new perfetto trace.
     pub fn start(&mut self) -> Result<()> {
         self.clear();
@@ -165,7 +173,12 @@ impl PerfettoTraceManager {
 
     /// Returns the trace file.
     pub fn trace_file(&self) -> String {
-        format!("{}_{}.proto", self.output_file_prefix, self.trace_id)
+        format!(
+            "{}_{}.{}",
+            self.output_file_prefix,
+            self.trace_id,
+            self.format.extension()
+        )
     }
 
     /// Creates the TrackDescriptors for the trace.
@@ -567,10 +580,24 @@ impl PerfettoTraceManager {
             None => self.trace_file(),
         };
         const TRACE_WRITE_BUF_SIZE: usize = 4 * 1024 * 1024;
-        let file = File::create(&trace_path)?;
-        let mut writer = BufWriter::with_capacity(TRACE_WRITE_BUF_SIZE, file);
-        self.trace.write_to_writer(&mut writer)?;
-        writer.flush()?;
+        match self.format {
+            TraceFormat::Perfetto => {
+                let file = File::create(&trace_path)?;
+                let mut writer = BufWriter::with_capacity(TRACE_WRITE_BUF_SIZE, file);
+                self.trace.write_to_writer(&mut writer)?;
+                writer.flush()?;
+            }
+            TraceFormat::Chrome => {
+                let file = File::create(&trace_path)?;
+                let writer = BufWriter::with_capacity(TRACE_WRITE_BUF_SIZE, file);
+                TraceEvents::from_trace(&self.trace).write_chrome_json(writer)?;
+            }
+            // The trace file is used as the prefix of the per event type files
+            TraceFormat::Csv => {
+                let prefix = trace_path.strip_suffix(".csv").unwrap_or(&trace_path);
+                TraceEvents::from_trace(&self.trace).write_csv(prefix)?;
+            }
+        }
 
         self.clear();
         self.trace_id += 1;
@@ -1224,6 +1251,7 @@ mod tests {
             next_tgid,
             next_prio: 120,
             next_layer_id: -1,
+            next_cgroup_id: 0,
             next_comm: next_comm.into(),
             prev_dsq_id: 0,
             prev_used_slice_ns: 0,
@@ -1329,6 +1357,31 @@ mod tests {
         std::fs::remove_file(&path).ok();
     }
 
+    /// stop() should write a Chrome JSON trace when the format is set to chrome.
+    #[test]
+    fn test_stop_writes_chrome_json() {
+        let mut mgr = new_manager();
+        mgr.set_format(TraceFormat::Chrome);
+        mgr.on_sched_switch(&sched_switch(1_000, 0, 100, 100, "a", 0, 0, ""));
+        mgr.on_sched_switch(&sched_switch(3_000, 0, 0, 0, "", 100, 100, "a"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.json");
+        mgr.stop(Some(path.to_str().unwrap().to_string()), None)
+            .unwrap();
+
+        let trace: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let slice = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["ph"] == "X")
+            .expect("should have a sched slice");
+        assert_eq!(slice["name"], "a");
+        assert_eq!(slice["dur"], 2.0);
+    }
+
     /// ftrace events within a CPU bundle must be sorted by timestamp in the
     /// emitted trace, even when recorded out of order.
     #[test]
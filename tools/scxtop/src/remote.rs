@@ -0,0 +1,538 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+//! Streaming of collected events between a collector and remote TUI clients.
+//!
+//! The collector forwards the raw BPF events it reads from the ringbuffers, clients convert them
+//! to `Action`s the same way a local TUI does. Every frame starts with a 5 byte header made of the
+//! frame kind and the little endian payload length. The first frame sent to a client is a hello
+//! frame describing the collector, BPF events are sent in the native layout of the collector so
+//! clients reject collectors with a different event layout.
+
+use crate::bpf_skel::types::bpf_event;
+use crate::Action;
+
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+
+/// Version of the wire protocol, bumped on incompatible changes.
+pub const PROTOCOL_VERSION: u16 = 1;
+/// Default address of the collector.
+pub const DEFAULT_COLLECTOR_ADDR: &str = "unix:/run/scxtop.sock";
+
+const MAGIC: [u8; 4] = *b"SCXT";
+const FRAME_HEADER_LEN: usize = 5;
+const MAX_FRAME_LEN: usize = 1 << 20;
+/// Delay before accepting clients again after an error (e.g. out of file descriptors).
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Kind of a frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum FrameKind {
+    Hello = 1,
+    BpfEvent = 2,
+    SchedStats = 3,
+}
+
+impl TryFrom<u8> for FrameKind {
+    type Error = anyhow::Error;
+
+    fn try_from(kind: u8) -> Result<Self> {
+        match kind {
+            1 => Ok(FrameKind::Hello),
+            2 => Ok(FrameKind::BpfEvent),
+            3 => Ok(FrameKind::SchedStats),
+            _ => Err(anyhow!("unknown frame kind {kind}")),
+        }
+    }
+}
+
+/// Address of a collector, either `unix:<path>` or `tcp:<host>:<port>`. Addresses without a
+/// prefix are unix socket paths if they contain a `/` and TCP addresses otherwise.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RemoteAddr {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl FromStr for RemoteAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let addr = if let Some(path) = s.strip_prefix("unix:") {
+            RemoteAddr::Unix(PathBuf::from(path))
+        } else if let Some(addr) = s.strip_prefix("tcp:") {
+            RemoteAddr::Tcp(addr.to_string())
+        } else if s.contains('/') {
+            RemoteAddr::Unix(PathBuf::from(s))
+        } else {
+            RemoteAddr::Tcp(s.to_string())
+        };
+        match &addr {
+            RemoteAddr::Unix(path) if path.as_os_str().is_empty() => {
+                bail!("empty unix socket path")
+            }
+            RemoteAddr::Tcp(addr) if !addr.contains(':') => {
+                bail!("TCP address {addr} has no port")
+            }
+            _ => Ok(addr),
+        }
+    }
+}
+
+impl fmt::Display for RemoteAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemoteAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+            RemoteAddr::Tcp(addr) => write!(f, "tcp:{addr}"),
+        }
+    }
+}
+
+/// Description of a collector, sent to clients when they connect.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hello {
+    pub version: u16,
+    pub little_endian: bool,
+    pub event_size: u32,
+    pub nr_cpus: u32,
+    pub scheduler: String,
+}
+
+impl Hello {
+    /// Returns the hello of this host.
+    pub fn new(nr_cpus: u32, scheduler: String) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            little_endian: cfg!(target_endian = "little"),
+            event_size: std::mem::size_of::<bpf_event>() as u32,
+            nr_cpus,
+            scheduler,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(15 + self.scheduler.len());
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&self.version.to_le_bytes());
+        buf.push(self.little_endian as u8);
+        buf.extend_from_slice(&self.event_size.to_le_bytes());
+        buf.extend_from_slice(&self.nr_cpus.to_le_bytes());
+        buf.extend_from_slice(self.scheduler.as_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 15 || buf[..4] != MAGIC {
+            bail!("not a scxtop collector");
+        }
+        Ok(Self {
+            version: u16::from_le_bytes([buf[4], buf[5]]),
+            little_endian: buf[6] != 0,
+            event_size: u32::from_le_bytes(buf[7..11].try_into()?),
+            nr_cpus: u32::from_le_bytes(buf[11..15].try_into()?),
+            scheduler: String::from_utf8_lossy(&buf[15..]).to_string(),
+        })
+    }
+
+    /// Checks that events of the collector that sent this hello can be decoded locally.
+    pub fn check_compatible(&self) -> Result<()> {
+        let local = Hello::new(0, String::new());
+        if self.version != local.version {
+            bail!(
+                "collector protocol version {} is not supported (expected {})",
+                self.version,
+                local.version
+            );
+        }
+        if self.little_endian != local.little_endian || self.event_size != local.event_size {
+            bail!("collector was built with a different BPF event layout");
+        }
+        Ok(())
+    }
+}
+
+/// Returns a frame with its header.
+pub fn encode_frame(kind: FrameKind, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.push(kind as u8);
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Reads a frame, returns None when the stream is closed between frames.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<(FrameKind, Vec<u8>)>> {
+    let mut header = [0u8; FRAME_HEADER_LEN];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let kind = FrameKind::try_from(header[0])?;
+    let len = u32::from_le_bytes(header[1..].try_into()?) as usize;
+    if len > MAX_FRAME_LEN {
+        bail!("frame of {len} bytes is too large");
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(Some((kind, payload)))
+}
+
+/// Returns the BPF event of a payload. The collector forwards the ringbuffer records as they are,
+/// which always hold a full bpf_event, shorter payloads are zero filled.
+pub fn decode_bpf_event(payload: &[u8]) -> bpf_event {
+    let mut event = bpf_event::default();
+    let copy_size = std::cmp::min(payload.len(), std::mem::size_of::<bpf_event>());
+    // SAFETY: bpf_event is Plain and copy_size is bounded by both buffers.
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            payload.as_ptr(),
+            &mut event as *mut bpf_event as *mut u8,
+            copy_size,
+        );
+    }
+    event
+}
+
+/// Removes the unix socket at path, fails if something else than a socket is there.
+pub fn remove_socket(path: &Path) -> Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => bail!("{} exists and isn't a socket", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Publishes frames to all connected clients.
+#[derive(Clone)]
+pub struct CollectorServer {
+    hello: Arc<[u8]>,
+    tx: broadcast::Sender<Arc<[u8]>>,
+}
+
+impl CollectorServer {
+    /// Creates a server that buffers up to capacity frames for each client, slower clients
+    /// drop the frames they missed.
+    pub fn new(hello: &Hello, capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self {
+            hello: encode_frame(FrameKind::Hello, &hello.encode()).into(),
+            tx,
+        }
+    }
+
+    /// Returns the number of connected clients.
+    pub fn nr_clients(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Publishes a raw BPF event.
+    pub fn publish_event(&self, event: &[u8]) {
+        if self.nr_clients() > 0 {
+            let _ = self
+                .tx
+                .send(encode_frame(FrameKind::BpfEvent, event).into());
+        }
+    }
+
+    /// Publishes scheduler stats in JSON.
+    pub fn publish_stats(&self, stats: &str) {
+        if self.nr_clients() > 0 {
+            let _ = self
+                .tx
+                .send(encode_frame(FrameKind::SchedStats, stats.as_bytes()).into());
+        }
+    }
+
+    /// Accepts clients until shutdown is set.
+    pub async fn serve(&self, addr: &RemoteAddr, shutdown: Arc<AtomicBool>) -> Result<()> {
+        match addr {
+            RemoteAddr::Unix(path) => {
+                // Remove the socket of a previous collector
+                remove_socket(path)?;
+                let listener = UnixListener::bind(path)?;
+                while !shutdown.load(Ordering::Relaxed) {
+                    match listener.accept().await {
+                        Ok((stream, _)) => self.spawn_client(stream, "unix client".to_string()),
+                        Err(e) => {
+                            log::warn!("failed to accept client: {e}");
+                            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        }
+                    }
+                }
+            }
+            RemoteAddr::Tcp(addr) => {
+                let listener = TcpListener::bind(addr).await?;
+                while !shutdown.load(Ordering::Relaxed) {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            let _ = stream.set_nodelay(true);
+                            self.spawn_client(stream, peer.to_string());
+                        }
+                        Err(e) => {
+                            log::warn!("failed to accept client: {e}");
+                            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn_client<S: AsyncWrite + Unpin + Send + 'static>(&self, stream: S, peer: String) {
+        let hello = self.hello.clone();
+        let rx = self.tx.subscribe();
+        tokio::spawn(async move {
+            log::info!("{peer} connected");
+            if let Err(e) = Self::stream_frames(stream, &hello, rx).await {
+                log::info!("{peer} disconnected: {e}");
+            }
+        });
+    }
+
+    async fn stream_frames<S: AsyncWrite + Unpin>(
+        stream: S,
+        hello: &[u8],
+        mut rx: broadcast::Receiver<Arc<[u8]>>,
+    ) -> Result<()> {
+        let mut writer = BufWriter::new(stream);
+        writer.write_all(hello).await?;
+        writer.flush().await?;
+        loop {
+            let frame = match rx.recv().await {
+                Ok(frame) => frame,
+                Err(broadcast::error::RecvError::Lagged(nr_frames)) => {
+                    log::warn!("client lagging, dropped {nr_frames} frames");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            };
+            writer.write_all(&frame).await?;
+            // Batch the frames that are already queued before flushing
+            while let Ok(frame) = rx.try_recv() {
+                writer.write_all(&frame).await?;
+            }
+            writer.flush().await?;
+        }
+    }
+}
+
+/// Connection of a TUI to a collector.
+pub struct RemoteClient {
+    reader: Box<dyn AsyncRead + Unpin + Send>,
+    hello: Hello,
+}
+
+impl RemoteClient {
+    /// Connects to a collector and checks that its events can be decoded.
+    pub async fn connect(addr: &RemoteAddr) -> Result<Self> {
+        let reader: Box<dyn AsyncRead + Unpin + Send> = match addr {
+            RemoteAddr::Unix(path) => Box::new(BufReader::new(UnixStream::connect(path).await?)),
+            RemoteAddr::Tcp(addr) => Box::new(BufReader::new(TcpStream::connect(addr).await?)),
+        };
+        Self::from_reader(reader).await
+    }
+
+    async fn from_reader(mut reader: Box<dyn AsyncRead + Unpin + Send>) -> Result<Self> {
+        let hello = match read_frame(&mut reader).await? {
+            Some((FrameKind::Hello, payload)) => Hello::decode(&payload)?,
+            _ => bail!("collector did not send a hello"),
+        };
+        hello.check_compatible()?;
+        Ok(Self { reader, hello })
+    }
+
+    /// Returns the hello of the collector.
+    pub fn hello(&self) -> &Hello {
+        &self.hello
+    }
+
+    /// Returns the next action sent by the collector, None when the collector disconnects.
+    /// Events of CPUs for which is_valid_cpu returns false are skipped.
+    pub async fn next_action(
+        &mut self,
+        is_valid_cpu: impl Fn(u32) -> bool,
+    ) -> Result<Option<Action>> {
+        loop {
+            let Some((kind, payload)) = read_frame(&mut self.reader).await? else {
+                return Ok(None);
+            };
+            match kind {
+                FrameKind::BpfEvent => {
+                    let event = decode_bpf_event(&payload);
+                    if !is_valid_cpu(event.cpu) {
+                        continue;
+                    }
+                    if let Ok(action) = Action::try_from(&event) {
+                        return Ok(Some(action));
+                    }
+                }
+                FrameKind::SchedStats => {
+                    return Ok(Some(Action::SchedStats(
+                        String::from_utf8_lossy(&payload).to_string(),
+                    )));
+                }
+                FrameKind::Hello => bail!("unexpected hello from collector"),
+            }
+        }
+    }
+
+    /// Sends the actions of the collector until it disconnects or the channel is closed.
+    pub async fn forward_actions(
+        mut self,
+        tx: UnboundedSender<Action>,
+        is_valid_cpu: impl Fn(u32) -> bool,
+    ) -> Result<()> {
+        while let Some(action) = self.next_action(&is_valid_cpu).await? {
+            if tx.send(action).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpf_intf;
+
+    #[test]
+    fn test_remote_addr() {
+        assert_eq!(
+            "unix:/run/scxtop.sock".parse::<RemoteAddr>().unwrap(),
+            RemoteAddr::Unix(PathBuf::from("/run/scxtop.sock"))
+        );
+        assert_eq!(
+            "/tmp/scxtop.sock".parse::<RemoteAddr>().unwrap(),
+            RemoteAddr::Unix(PathBuf::from("/tmp/scxtop.sock"))
+        );
+        assert_eq!(
+            "tcp:host:4242".parse::<RemoteAddr>().unwrap(),
+            RemoteAddr::Tcp("host:4242".to_string())
+        );
+        assert_eq!(
+            "[::1]:4242".parse::<RemoteAddr>().unwrap(),
+            RemoteAddr::Tcp("[::1]:4242".to_string())
+        );
+        assert!("unix:".parse::<RemoteAddr>().is_err());
+        assert!("host".parse::<RemoteAddr>().is_err());
+        assert_eq!(
+            RemoteAddr::Tcp("host:4242".to_string()).to_string(),
+            "tcp:host:4242"
+        );
+    }
+
+    #[test]
+    fn test_hello() {
+        let hello = Hello::new(64, "scx_lavd".to_string());
+        assert_eq!(Hello::decode(&hello.encode()).unwrap(), hello);
+        assert!(hello.check_compatible().is_ok());
+
+        let other = Hello {
+            event_size: hello.event_size + 8,
+            ..hello.clone()
+        };
+        assert!(other.check_compatible().is_err());
+        assert!(Hello::decode(b"HTTP/1.1 200 OK").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_frames() {
+        let mut buf = encode_frame(FrameKind::SchedStats, b"{}");
+        buf.extend(encode_frame(FrameKind::BpfEvent, &[]));
+        let mut reader = buf.as_slice();
+        assert_eq!(
+            read_frame(&mut reader).await.unwrap(),
+            Some((FrameKind::SchedStats, b"{}".to_vec()))
+        );
+        assert_eq!(
+            read_frame(&mut reader).await.unwrap(),
+            Some((FrameKind::BpfEvent, vec![]))
+        );
+        assert_eq!(read_frame(&mut reader).await.unwrap(), None);
+
+        let mut bad = [9u8, 0, 0, 0, 0].as_slice();
+        assert!(read_frame(&mut bad).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collector_to_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = RemoteAddr::Unix(dir.path().join("scxtop.sock"));
+        let server = CollectorServer::new(&Hello::new(4, "scx_test".to_string()), 16);
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let serve = server.clone();
+        let serve_addr = addr.clone();
+        let serve_shutdown = shutdown.clone();
+        tokio::spawn(async move { serve.serve(&serve_addr, serve_shutdown).await });
+
+        let mut client = loop {
+            if let Ok(client) = RemoteClient::connect(&addr).await {
+                break client;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        assert_eq!(client.hello().scheduler, "scx_test");
+        assert_eq!(client.hello().nr_cpus, 4);
+        while server.nr_clients() == 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let event = |cpu: u32| bpf_event {
+            r#type: bpf_intf::event_type_SCHED_REG as i32,
+            ts: 1,
+            cpu,
+            ..Default::default()
+        };
+        // SAFETY: bpf_event is Plain.
+        let bytes = |event: &bpf_event| unsafe { plain::as_bytes(event) }.to_vec();
+        server.publish_event(&bytes(&event(8)));
+        server.publish_event(&bytes(&event(2)));
+        server.publish_stats("{\"nr_running\":1}");
+
+        let is_valid_cpu = |cpu: u32| cpu < 4;
+        assert_eq!(
+            client.next_action(is_valid_cpu).await.unwrap(),
+            Some(Action::SchedReg)
+        );
+        assert_eq!(
+            client.next_action(is_valid_cpu).await.unwrap(),
+            Some(Action::SchedStats("{\"nr_running\":1}".to_string()))
+        );
+        shutdown.store(true, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_remove_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scxtop.sock");
+        remove_socket(&path).unwrap();
+
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        remove_socket(&path).unwrap();
+        assert!(!path.exists());
+
+        std::fs::write(&path, "data").unwrap();
+        assert!(remove_socket(&path).is_err());
+        assert!(path.exists());
+    }
+}
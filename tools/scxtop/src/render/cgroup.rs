@@ -0,0 +1,285 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::{AppTheme, CgroupData, CgroupTree, ProcData, StatAggregation, VecStats};
+use anyhow::Result;
+use ratatui::layout::{Alignment, Constraint, Rect};
+use ratatui::prelude::Stylize;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{
+    Block, BorderType, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState,
+    Table, TableState,
+};
+use ratatui::Frame;
+use std::collections::{BTreeMap, HashSet};
+
+/// Parameters for rendering the cgroup tree
+pub struct CgroupTreeParams<'a> {
+    pub cgroup_tree: &'a CgroupTree,
+    pub theme: &'a AppTheme,
+}
+
+/// Parameters for rendering the member processes of a cgroup
+pub struct CgroupMembersParams<'a> {
+    pub cgroup: &'a CgroupData,
+    pub proc_data: &'a BTreeMap<i32, ProcData>,
+    pub theme: &'a AppTheme,
+}
+
+/// Renderer for the cgroup view
+pub struct CgroupRenderer;
+
+impl CgroupRenderer {
+    /// Renders the cgroup hierarchy with per-cgroup scheduling metrics, returns the number of
+    /// rows.
+    pub fn render_cgroup_tree(
+        frame: &mut Frame,
+        area: Rect,
+        params: &CgroupTreeParams,
+        table_state: &mut TableState,
+    ) -> Result<usize> {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(params.theme.border_style())
+            .title_top(
+                Line::from(format!(
+                    "Cgroups ({} cgroups)",
+                    params.cgroup_tree.cgroups.len()
+                ))
+                .style(params.theme.title_style())
+                .centered(),
+            );
+
+        if !params.cgroup_tree.is_available() {
+            let paragraph = Paragraph::new("cgroup v2 hierarchy not found")
+                .style(params.theme.text_color())
+                .alignment(Alignment::Center)
+                .block(block);
+            frame.render_widget(paragraph, area);
+            return Ok(0);
+        }
+
+        let header = Row::new(vec![
+            Cell::from("CGROUP"),
+            Cell::from("CPU%"),
+            Cell::from("WEIGHT"),
+            Cell::from("MAX"),
+            Cell::from("THROTTLED"),
+            Cell::from("thr(ms)"),
+            Cell::from("rq p50"),
+            Cell::from("rq p99"),
+            Cell::from("MIGR"),
+            Cell::from("PROCS"),
+        ])
+        .style(params.theme.text_color())
+        .bold()
+        .underlined();
+
+        let constraints = vec![
+            Constraint::Fill(1),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(14),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(6),
+        ];
+
+        let cgroups = params.cgroup_tree.tree_order();
+        let rows: Vec<Row> = cgroups
+            .iter()
+            .map(|cgroup| {
+                let (p50, p99) = Self::rq_lat_percentiles(cgroup);
+                let color = if cgroup.nr_throttled_delta > 0 {
+                    params.theme.text_important_color()
+                } else {
+                    Self::latency_group_color(p99, params.theme)
+                };
+                Row::new(vec![
+                    Cell::from(format!("{}{}", "  ".repeat(cgroup.depth), cgroup.name())),
+                    Cell::from(format!("{:.1}", cgroup.cpu_util_perc)),
+                    Cell::from(
+                        cgroup
+                            .weight
+                            .map(|w| w.to_string())
+                            .unwrap_or_else(|| "-".to_string()),
+                    ),
+                    Cell::from(cgroup.cpu_max()),
+                    Cell::from(format!("{}", cgroup.nr_throttled_delta)),
+                    Cell::from(format!("{}", cgroup.throttled_us_delta / 1000)),
+                    Cell::from(format!("{}", p50)),
+                    Cell::from(format!("{}", p99)),
+                    Cell::from(format!(
+                        "{}",
+                        cgroup.event_data_immut("migrations").last().unwrap_or(&0)
+                    )),
+                    Cell::from(format!("{}", cgroup.tgids.len())),
+                ])
+                .style(Style::default().fg(color))
+            })
+            .collect();
+
+        let row_count = rows.len();
+        let table = Table::new(rows, constraints)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, table_state);
+        Self::render_scrollbar(frame, area, row_count, table_state);
+
+        Ok(row_count)
+    }
+
+    /// Renders the member processes of a cgroup, returns the number of rows.
+    pub fn render_cgroup_members(
+        frame: &mut Frame,
+        area: Rect,
+        params: &CgroupMembersParams,
+        table_state: &mut TableState,
+    ) -> Result<usize> {
+        let percentile_set: HashSet<StatAggregation> = [StatAggregation::P50, StatAggregation::P99]
+            .into_iter()
+            .collect();
+
+        let mut members: Vec<&ProcData> = params
+            .cgroup
+            .tgids
+            .iter()
+            .filter_map(|tgid| params.proc_data.get(tgid))
+            .collect();
+        // Sort by CPU utilization descending
+        members.sort_by(|a, b| b.cpu_util_perc.total_cmp(&a.cpu_util_perc));
+
+        let header = Row::new(vec![
+            Cell::from("PID"),
+            Cell::from("COMM"),
+            Cell::from("CPU"),
+            Cell::from("CPU%"),
+            Cell::from("THREADS"),
+            Cell::from("lat p50"),
+            Cell::from("lat p99"),
+        ])
+        .style(params.theme.text_color())
+        .bold()
+        .underlined();
+
+        let constraints = vec![
+            Constraint::Min(8),
+            Constraint::Fill(1),
+            Constraint::Min(5),
+            Constraint::Min(7),
+            Constraint::Min(8),
+            Constraint::Min(8),
+            Constraint::Min(8),
+        ];
+
+        let rows: Vec<Row> = members
+            .iter()
+            .map(|proc_data| {
+                let lat_data: Vec<u64> = proc_data
+                    .event_data_immut("lat_us")
+                    .into_iter()
+                    .filter(|&v| v > 0)
+                    .collect();
+                let stats = VecStats::new(&lat_data, Some(percentile_set.clone()));
+                let percentile = |agg: StatAggregation| {
+                    stats
+                        .percentiles
+                        .as_ref()
+                        .and_then(|m| m.get(&agg))
+                        .copied()
+                        .unwrap_or(0)
+                };
+                Row::new(vec![
+                    Cell::from(format!("{}", proc_data.tgid)),
+                    Cell::from(proc_data.process_name.clone()),
+                    Cell::from(format!("{}", proc_data.cpu)),
+                    Cell::from(format!("{:.1}", proc_data.cpu_util_perc)),
+                    Cell::from(format!("{}", proc_data.num_threads)),
+                    Cell::from(format!("{}", percentile(StatAggregation::P50))),
+                    Cell::from(format!("{}", percentile(StatAggregation::P99))),
+                ])
+                .style(params.theme.text_color())
+            })
+            .collect();
+
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(params.theme.border_style())
+            .title_top(
+                Line::from(format!(
+                    "Cgroup {} ({} procs)",
+                    params.cgroup.path,
+                    members.len()
+                ))
+                .style(params.theme.title_style())
+                .centered(),
+            );
+
+        let row_count = rows.len();
+        let table = Table::new(rows, constraints)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, table_state);
+        Self::render_scrollbar(frame, area, row_count, table_state);
+
+        Ok(row_count)
+    }
+
+    /// Returns the p50 and p99 runqueue latency of a cgroup in μs.
+    fn rq_lat_percentiles(cgroup: &CgroupData) -> (u64, u64) {
+        let lat_data: Vec<u64> = cgroup
+            .event_data_immut("rq_lat_us")
+            .into_iter()
+            .filter(|&v| v > 0)
+            .collect();
+        if lat_data.is_empty() {
+            return (0, 0);
+        }
+        let percentile_set: HashSet<StatAggregation> = [StatAggregation::P50, StatAggregation::P99]
+            .into_iter()
+            .collect();
+        let stats = VecStats::new(&lat_data, Some(percentile_set));
+        let pmap = stats.percentiles.as_ref();
+        let p50 = pmap
+            .and_then(|m| m.get(&StatAggregation::P50))
+            .copied()
+            .unwrap_or(0);
+        let p99 = pmap
+            .and_then(|m| m.get(&StatAggregation::P99))
+            .copied()
+            .unwrap_or(0);
+        (p50, p99)
+    }
+
+    fn latency_group_color(p99_us: u64, theme: &AppTheme) -> Color {
+        theme.gradient_5(p99_us as f64, 10.0, 100.0, 1000.0, 10000.0, false)
+    }
+
+    fn render_scrollbar(frame: &mut Frame, area: Rect, row_count: usize, state: &TableState) {
+        let visible_rows = area.height.saturating_sub(4) as usize;
+        if row_count > visible_rows {
+            let scrollbar = Scrollbar::default()
+                .orientation(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(Some("↑"))
+                .end_symbol(Some("↓"));
+            let scroll_pos = state.selected().unwrap_or(0);
+            let mut scrollbar_state = ScrollbarState::new(row_count).position(scroll_pos);
+            frame.render_stateful_widget(
+                scrollbar,
+                area.inner(ratatui::layout::Margin {
+                    vertical: 1,
+                    horizontal: 0,
+                }),
+                &mut scrollbar_state,
+            );
+        }
+    }
+}
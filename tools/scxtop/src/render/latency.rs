@@ -0,0 +1,301 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::latency_data::{LatencyTracker, SchedLatency, HEATMAP_ROWS};
+use crate::AppTheme;
+use anyhow::Result;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::Stylize;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Cell, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+
+// Width of the latency labels of the heatmap rows.
+const HEATMAP_LABEL_WIDTH: usize = 7;
+
+/// Parameters for rendering the latency view
+pub struct LatencyViewParams<'a> {
+    pub tracker: &'a LatencyTracker,
+    pub theme: &'a AppTheme,
+}
+
+/// Renderer for the latency histogram view
+pub struct LatencyRenderer;
+
+impl LatencyRenderer {
+    /// Renders the wakeup latency heatmap along with the per-process and per-CPU latency
+    /// percentiles, returns the number of process rows.
+    pub fn render_latency_view(
+        frame: &mut Frame,
+        area: Rect,
+        params: &LatencyViewParams,
+        table_state: &mut TableState,
+    ) -> Result<usize> {
+        let [heatmap_area, tables_area] = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .areas(area);
+        let [process_area, cpu_area] = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+            .areas(tables_area);
+
+        Self::render_heatmap(frame, heatmap_area, params)?;
+        let row_count = Self::render_process_table(frame, process_area, params, table_state)?;
+        Self::render_cpu_table(frame, cpu_area, params)?;
+
+        Ok(row_count)
+    }
+
+    /// Renders the wakeup latency heatmap, time goes from left to right and latency from bottom
+    /// to top.
+    pub fn render_heatmap(frame: &mut Frame, area: Rect, params: &LatencyViewParams) -> Result<()> {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(params.theme.border_style())
+            .title_top(
+                Line::from("Wakeup Latency Heatmap")
+                    .style(params.theme.title_style())
+                    .centered(),
+            );
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let width = (inner.width as usize).saturating_sub(HEATMAP_LABEL_WIDTH + 1);
+        let height = inner.height as usize;
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        let columns: Vec<_> = params.tracker.heatmap().collect();
+        let columns = &columns[columns.len().saturating_sub(width)..];
+        let max_count = columns
+            .iter()
+            .flat_map(|column| column.iter())
+            .copied()
+            .max()
+            .unwrap_or(0);
+        if max_count == 0 {
+            let paragraph = Paragraph::new("No wakeup latency samples")
+                .style(params.theme.text_color())
+                .centered();
+            frame.render_widget(paragraph, inner);
+            return Ok(());
+        }
+
+        // Show the rows that hold samples, keeping the highest latencies when they don't fit
+        let is_used = |row: usize| columns.iter().any(|column| column[row] > 0);
+        let top = (0..HEATMAP_ROWS)
+            .rev()
+            .find(|row| is_used(*row))
+            .unwrap_or(0);
+        let bottom = (0..HEATMAP_ROWS).find(|row| is_used(*row)).unwrap_or(0);
+        let bottom = bottom.max((top + 1).saturating_sub(height));
+
+        let lines: Vec<Line> = (bottom..=top)
+            .rev()
+            .map(|row| {
+                let mut spans = vec![Span::styled(
+                    format!(
+                        "{:>width$} ",
+                        Self::row_label(row),
+                        width = HEATMAP_LABEL_WIDTH
+                    ),
+                    params.theme.text_color(),
+                )];
+                spans.extend(columns.iter().map(|column| {
+                    let count = column[row];
+                    if count == 0 {
+                        Span::raw(" ")
+                    } else {
+                        Span::styled(
+                            "█",
+                            Style::default().fg(Self::heatmap_color(
+                                count,
+                                max_count,
+                                params.theme,
+                            )),
+                        )
+                    }
+                }));
+                Line::from(spans)
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), inner);
+
+        Ok(())
+    }
+
+    /// Renders the latency percentiles of processes sorted by p99 wakeup latency, returns the
+    /// number of rows.
+    pub fn render_process_table(
+        frame: &mut Frame,
+        area: Rect,
+        params: &LatencyViewParams,
+        table_state: &mut TableState,
+    ) -> Result<usize> {
+        let mut processes: Vec<(&u32, &SchedLatency)> = params.tracker.processes.iter().collect();
+        processes.sort_by_key(|(_, lat)| std::cmp::Reverse(lat.wakeup_lat.percentile(99.0)));
+
+        let header = Row::new(vec![
+            Cell::from("PID"),
+            Cell::from("COMM"),
+            Cell::from("WAKEUPS"),
+            Cell::from("lat p50"),
+            Cell::from("lat p99"),
+            Cell::from("lat p999"),
+            Cell::from("lat max"),
+            Cell::from("rq p99"),
+            Cell::from("slice p50"),
+            Cell::from("slice p99"),
+        ])
+        .style(params.theme.text_color())
+        .bold()
+        .underlined();
+
+        let constraints = vec![
+            Constraint::Length(8),
+            Constraint::Fill(1),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+        ];
+
+        let rows: Vec<Row> = processes
+            .iter()
+            .map(|(tgid, lat)| {
+                let p99 = lat.wakeup_lat.percentile(99.0);
+                Row::new(vec![
+                    Cell::from(format!("{}", tgid)),
+                    Cell::from(params.tracker.comm(**tgid).to_string()),
+                    Cell::from(format!("{}", lat.wakeup_lat.count())),
+                    Cell::from(Self::format_ns(lat.wakeup_lat.percentile(50.0))),
+                    Cell::from(Self::format_ns(p99)),
+                    Cell::from(Self::format_ns(lat.wakeup_lat.percentile(99.9))),
+                    Cell::from(Self::format_ns(lat.wakeup_lat.max())),
+                    Cell::from(Self::format_ns(lat.rq_wait.percentile(99.0))),
+                    Cell::from(Self::format_ns(lat.slice.percentile(50.0))),
+                    Cell::from(Self::format_ns(lat.slice.percentile(99.0))),
+                ])
+                .style(Style::default().fg(Self::latency_color(p99, params.theme)))
+            })
+            .collect();
+
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(params.theme.border_style())
+            .title_top(
+                Line::from(format!("Process Latency ({} processes)", rows.len()))
+                    .style(params.theme.title_style())
+                    .centered(),
+            );
+
+        let row_count = rows.len();
+        let table = Table::new(rows, constraints)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, table_state);
+
+        Ok(row_count)
+    }
+
+    /// Renders the latency percentiles of CPUs.
+    pub fn render_cpu_table(
+        frame: &mut Frame,
+        area: Rect,
+        params: &LatencyViewParams,
+    ) -> Result<()> {
+        let header = Row::new(vec![
+            Cell::from("CPU"),
+            Cell::from("lat p50"),
+            Cell::from("lat p99"),
+            Cell::from("rq p99"),
+            Cell::from("slice p50"),
+        ])
+        .style(params.theme.text_color())
+        .bold()
+        .underlined();
+
+        let constraints = vec![
+            Constraint::Length(4),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ];
+
+        let rows: Vec<Row> = params
+            .tracker
+            .cpus
+            .iter()
+            .map(|(cpu, lat)| {
+                let p99 = lat.wakeup_lat.percentile(99.0);
+                Row::new(vec![
+                    Cell::from(format!("{}", cpu)),
+                    Cell::from(Self::format_ns(lat.wakeup_lat.percentile(50.0))),
+                    Cell::from(Self::format_ns(p99)),
+                    Cell::from(Self::format_ns(lat.rq_wait.percentile(99.0))),
+                    Cell::from(Self::format_ns(lat.slice.percentile(50.0))),
+                ])
+                .style(Style::default().fg(Self::latency_color(p99, params.theme)))
+            })
+            .collect();
+
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(params.theme.border_style())
+            .title_top(
+                Line::from("CPU Latency")
+                    .style(params.theme.title_style())
+                    .centered(),
+            );
+
+        let table = Table::new(rows, constraints).header(header).block(block);
+        frame.render_widget(table, area);
+
+        Ok(())
+    }
+
+    /// Returns the label of a heatmap row.
+    fn row_label(row: usize) -> String {
+        if row == 0 {
+            return "<2us".to_string();
+        }
+        let label = Self::format_ns((1u64 << row) * 1000);
+        if row == HEATMAP_ROWS - 1 {
+            format!(">={label}")
+        } else {
+            label
+        }
+    }
+
+    /// Formats a duration in ns with a unit.
+    pub fn format_ns(ns: u64) -> String {
+        if ns >= 1_000_000_000 {
+            format!("{:.1}s", ns as f64 / 1_000_000_000.0)
+        } else if ns >= 1_000_000 {
+            format!("{:.1}ms", ns as f64 / 1_000_000.0)
+        } else {
+            format!("{}us", ns / 1000)
+        }
+    }
+
+    fn heatmap_color(count: u64, max_count: u64, theme: &AppTheme) -> Color {
+        let ratio = count as f64 / max_count as f64;
+        theme.gradient_5(ratio, 0.05, 0.2, 0.4, 0.7, false)
+    }
+
+    fn latency_color(p99_ns: u64, theme: &AppTheme) -> Color {
+        let p99_us = p99_ns as f64 / 1000.0;
+        theme.gradient_5(p99_us, 10.0, 100.0, 1000.0, 10000.0, false)
+    }
+}
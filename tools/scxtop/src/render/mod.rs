@@ -16,9 +16,15 @@ pub mod network;
 pub mod scheduler;
 // BPF program rendering
 pub mod bpf_programs;
+// Cgroup rendering
+pub mod cgroup;
+// Latency histogram rendering
+pub mod latency;
 
 pub use bandwidth::BandwidthRenderer;
 pub use bpf_programs::BpfProgramRenderer;
+pub use cgroup::CgroupRenderer;
+pub use latency::LatencyRenderer;
 pub use memory::MemoryRenderer;
 pub use network::NetworkRenderer;
 pub use process::ProcessRenderer;
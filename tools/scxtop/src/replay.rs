@@ -0,0 +1,552 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::mcp::perfetto_parser::{DsqEvent, PerfettoTrace};
+use crate::{
+    Action, AppState, SchedMigrateTaskAction, SchedSwitchAction, SchedWakeActionCtx, SoftIRQAction,
+};
+
+use perfetto_protos::ftrace_event::ftrace_event;
+use scx_utils::scx_enums;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Slowest replay speed.
+pub const MIN_REPLAY_SPEED: f64 = 0.125;
+/// Fastest replay speed.
+pub const MAX_REPLAY_SPEED: f64 = 64.0;
+/// Amount of trace time skipped by a seek.
+pub const REPLAY_SEEK_NS: u64 = 10_000_000_000;
+/// Amount of trace time between two snapshots of the replayed state.
+pub const REPLAY_SNAPSHOT_NS: u64 = 2_000_000_000;
+
+/// Action recorded in a trace along with its timestamp.
+#[derive(Clone, Debug)]
+pub struct ReplayEvent {
+    pub ts: u64,
+    pub action: Action,
+}
+
+/// Converts the scheduler events of a perfetto trace into actions sorted by timestamp. Events
+/// that reference a CPU for which is_valid_cpu returns false are dropped.
+pub fn trace_events(trace: &PerfettoTrace, is_valid_cpu: impl Fn(u32) -> bool) -> Vec<ReplayEvent> {
+    // DSQ counters are recorded with μs resolution at the time of the sched_switch.
+    let mut dsq_events: HashMap<u64, &DsqEvent> = HashMap::new();
+    for dsq_event in trace.get_all_dsq_events().values().flatten() {
+        dsq_events.insert(dsq_event.timestamp / 1000, dsq_event);
+    }
+    let tgid_of = |pid: i32| trace.get_tgid_for_tid(pid).unwrap_or(pid).max(0) as u32;
+
+    let mut events = Vec::new();
+    for cpu in 0..trace.num_cpus() as u32 {
+        if !is_valid_cpu(cpu) {
+            continue;
+        }
+        let mut softirq_entries: HashMap<u32, u64> = HashMap::new();
+
+        for event_with_idx in trace.get_events_by_cpu(cpu) {
+            let event = &event_with_idx.event;
+            let Some(ts) = event.timestamp else {
+                continue;
+            };
+            let action = match &event.event {
+                Some(ftrace_event::Event::SchedSwitch(switch)) => {
+                    let next_pid = switch.next_pid.unwrap_or(0);
+                    let prev_pid = switch.prev_pid.unwrap_or(0);
+                    let dsq_event = dsq_events.get(&(ts / 1000));
+                    let dsq_lat_us = dsq_event.and_then(|e| e.latency_us).unwrap_or(0);
+                    let dsq_nr_queued = dsq_event.and_then(|e| e.nr_queued).unwrap_or(0);
+                    Action::SchedSwitch(SchedSwitchAction {
+                        ts,
+                        cpu,
+                        preempt: false,
+                        next_dsq_id: dsq_event
+                            .map(|e| e.dsq_id)
+                            .unwrap_or(scx_enums.SCX_DSQ_INVALID),
+                        next_dsq_lat_us: dsq_lat_us.max(0) as u64,
+                        next_dsq_nr_queued: dsq_nr_queued.max(0) as u32,
+                        next_dsq_vtime: 0,
+                        next_slice_ns: 0,
+                        next_pid: next_pid.max(0) as u32,
+                        next_tgid: tgid_of(next_pid),
+                        next_prio: switch.next_prio.unwrap_or(0),
+                        next_layer_id: -1,
+                        next_cgroup_id: 0,
+                        next_comm: switch.next_comm.clone().unwrap_or_default().into(),
+                        prev_dsq_id: scx_enums.SCX_DSQ_INVALID,
+                        prev_used_slice_ns: 0,
+                        prev_slice_ns: 0,
+                        prev_pid: prev_pid.max(0) as u32,
+                        prev_tgid: tgid_of(prev_pid),
+                        prev_prio: switch.prev_prio.unwrap_or(0),
+                        prev_comm: switch.prev_comm.clone().unwrap_or_default().into(),
+                        prev_state: switch.prev_state.unwrap_or(0).max(0) as u64,
+                        prev_layer_id: -1,
+                    })
+                }
+                Some(ftrace_event::Event::SchedWakeup(wakeup)) => {
+                    let Some(ctx) = wake_ctx(
+                        ts,
+                        cpu,
+                        wakeup.pid,
+                        wakeup.target_cpu,
+                        &wakeup.comm,
+                        &tgid_of,
+                    ) else {
+                        continue;
+                    };
+                    if !is_valid_cpu(ctx.cpu) {
+                        continue;
+                    }
+                    Action::SchedWakeup(SchedWakeActionCtx {
+                        prio: wakeup.prio.unwrap_or(0),
+                        ..ctx
+                    })
+                }
+                Some(ftrace_event::Event::SchedWaking(waking)) => {
+                    let Some(ctx) = wake_ctx(
+                        ts,
+                        cpu,
+                        waking.pid,
+                        waking.target_cpu,
+                        &waking.comm,
+                        &tgid_of,
+                    ) else {
+                        continue;
+                    };
+                    if !is_valid_cpu(ctx.cpu) {
+                        continue;
+                    }
+                    Action::SchedWaking(SchedWakeActionCtx {
+                        prio: waking.prio.unwrap_or(0),
+                        ..ctx
+                    })
+                }
+                Some(ftrace_event::Event::SchedMigrateTask(migrate)) => {
+                    let (Some(pid), Some(dest_cpu)) = (migrate.pid, migrate.dest_cpu) else {
+                        continue;
+                    };
+                    if pid < 0 || dest_cpu < 0 || !is_valid_cpu(dest_cpu as u32) {
+                        continue;
+                    }
+                    Action::SchedMigrateTask(SchedMigrateTaskAction {
+                        ts,
+                        cpu,
+                        dest_cpu: dest_cpu as u32,
+                        pid: pid as u32,
+                        prio: migrate.prio.unwrap_or(0),
+                        cgroup_id: 0,
+                        comm: migrate.comm.clone().unwrap_or_default().into(),
+                    })
+                }
+                Some(ftrace_event::Event::SoftirqEntry(entry)) => {
+                    if let Some(vec) = entry.vec {
+                        softirq_entries.insert(vec, ts);
+                    }
+                    continue;
+                }
+                Some(ftrace_event::Event::SoftirqExit(exit)) => {
+                    let Some(entry_ts) = exit.vec.and_then(|vec| softirq_entries.remove(&vec))
+                    else {
+                        continue;
+                    };
+                    Action::SoftIRQ(SoftIRQAction {
+                        cpu,
+                        pid: event.pid.unwrap_or(0),
+                        entry_ts,
+                        exit_ts: ts,
+                        softirq_nr: exit.vec.unwrap_or(0) as usize,
+                    })
+                }
+                _ => continue,
+            };
+            events.push(ReplayEvent { ts, action });
+        }
+    }
+
+    events.sort_by_key(|e| e.ts);
+    events
+}
+
+/// Builds the context of a wakeup event, the waker and the cgroup aren't recorded in traces.
+fn wake_ctx(
+    ts: u64,
+    cpu: u32,
+    pid: Option<i32>,
+    target_cpu: Option<i32>,
+    comm: &Option<String>,
+    tgid_of: impl Fn(i32) -> u32,
+) -> Option<SchedWakeActionCtx> {
+    let pid = pid.filter(|&pid| pid > 0)?;
+    Some(SchedWakeActionCtx {
+        ts,
+        cpu: target_cpu
+            .filter(|&target_cpu| target_cpu >= 0)
+            .map_or(cpu, |target_cpu| target_cpu as u32),
+        pid: pid as u32,
+        tgid: tgid_of(pid),
+        prio: 0,
+        cgroup_id: 0,
+        comm: comm.clone().unwrap_or_default().into(),
+        waker_pid: 0,
+        waker_comm: "".into(),
+    })
+}
+
+/// Plays back the events of a recorded trace in real time, scaled by the replay speed.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    events: Vec<ReplayEvent>,
+    start_ts: u64,
+    end_ts: u64,
+    // Current position in trace time.
+    position: u64,
+    // Index of the next event to replay.
+    cursor: usize,
+    speed: f64,
+    paused: bool,
+}
+
+impl Replay {
+    /// Creates a replay of the events, the events must be sorted by timestamp.
+    pub fn new(events: Vec<ReplayEvent>) -> Replay {
+        let start_ts = events.first().map_or(0, |e| e.ts);
+        let end_ts = events.last().map_or(0, |e| e.ts);
+        Self {
+            events,
+            start_ts,
+            end_ts,
+            position: start_ts,
+            cursor: 0,
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Advances the replay by elapsed wall time and returns the actions that are due.
+    pub fn advance(&mut self, elapsed: Duration) -> Vec<Action> {
+        if self.paused || self.is_finished() {
+            return Vec::new();
+        }
+        let delta = (elapsed.as_nanos() as f64 * self.speed) as u64;
+        self.position = self.position.saturating_add(delta).min(self.end_ts);
+
+        let start = self.cursor;
+        while self.cursor < self.events.len() && self.events[self.cursor].ts <= self.position {
+            self.cursor += 1;
+        }
+        self.events[start..self.cursor]
+            .iter()
+            .map(|e| e.action.clone())
+            .collect()
+    }
+
+    /// Returns true once all events have been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.events.len()
+    }
+
+    /// Returns true if the replay is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the replay.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Returns the replay speed.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Doubles the replay speed.
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_REPLAY_SPEED);
+    }
+
+    /// Halves the replay speed.
+    pub fn slow_down(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_REPLAY_SPEED);
+    }
+
+    /// Moves the replay position by delta_ns of trace time, events that are skipped over are
+    /// not replayed.
+    pub fn seek(&mut self, delta_ns: i64) {
+        let position = if delta_ns < 0 {
+            self.position.saturating_sub(delta_ns.unsigned_abs())
+        } else {
+            self.position.saturating_add(delta_ns as u64)
+        };
+        self.position = position.clamp(self.start_ts, self.end_ts);
+        self.cursor = self.events.partition_point(|e| e.ts < self.position);
+    }
+
+    /// Returns the actions of the events before the replay position, the views are rebuilt from
+    /// them after seeking backwards.
+    pub fn replayed(&self) -> impl Iterator<Item = &Action> {
+        self.events[..self.cursor].iter().map(|e| &e.action)
+    }
+
+    /// Applies a replay control action, returns false if the action isn't a replay control. The
+    /// pause action controls the replay rather than pausing the rendering.
+    pub fn handle_action(&mut self, action: &Action) -> bool {
+        match action {
+            Action::SetState(AppState::Pause) => self.toggle_pause(),
+            Action::ReplaySpeedUp => self.speed_up(),
+            Action::ReplaySlowDown => self.slow_down(),
+            Action::ReplaySeekForward => self.seek(REPLAY_SEEK_NS as i64),
+            Action::ReplaySeekBackward => self.seek(-(REPLAY_SEEK_NS as i64)),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns the replay position relative to the start of the trace.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.position - self.start_ts)
+    }
+
+    /// Returns the duration of the trace.
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.end_ts - self.start_ts)
+    }
+
+    /// Returns a one line summary of the replay.
+    pub fn status(&self) -> String {
+        let state = if self.is_finished() {
+            " (finished)"
+        } else if self.paused {
+            " (paused)"
+        } else {
+            ""
+        };
+        format!(
+            "Replay {:.1}s/{:.1}s x{}{}",
+            self.elapsed().as_secs_f64(),
+            self.duration().as_secs_f64(),
+            self.speed,
+            state
+        )
+    }
+}
+
+/// States saved while replaying, seeking backwards resumes from the closest snapshot rather than
+/// from the start of the trace.
+#[derive(Debug)]
+pub struct ReplaySnapshots<S> {
+    interval_ns: u64,
+    // Snapshots sorted by the cursor of the replay when they were taken, with its position.
+    snapshots: Vec<(usize, u64, S)>,
+}
+
+impl<S> ReplaySnapshots<S> {
+    /// Creates an empty set of snapshots taken every interval_ns of trace time.
+    pub fn new(interval_ns: u64) -> Self {
+        Self {
+            interval_ns,
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Returns the number of snapshots.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns true if there are no snapshots.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Returns true if the state at the replay position should be saved.
+    pub fn is_due(&self, replay: &Replay) -> bool {
+        match self.snapshots.last() {
+            Some(&(cursor, position, _)) => {
+                replay.cursor > cursor && replay.position >= position + self.interval_ns
+            }
+            None => replay.cursor > 0,
+        }
+    }
+
+    /// Saves the state reached after replaying the events before the replay position.
+    pub fn push(&mut self, replay: &Replay, state: S) {
+        self.snapshots.push((replay.cursor, replay.position, state));
+    }
+
+    /// Returns the latest snapshot before the replay position, or None to start from an empty
+    /// state, and the actions replayed since. Snapshots after the position are discarded.
+    pub fn rewind<'a>(
+        &mut self,
+        replay: &'a Replay,
+    ) -> (Option<&S>, impl Iterator<Item = &'a Action>) {
+        let nr_valid = self
+            .snapshots
+            .partition_point(|(cursor, _, _)| *cursor <= replay.cursor);
+        self.snapshots.truncate(nr_valid);
+        let (start, state) = match self.snapshots.last() {
+            Some((cursor, _, state)) => (*cursor, Some(state)),
+            None => (0, None),
+        };
+        let actions = replay.events[start..replay.cursor]
+            .iter()
+            .map(|e| &e.action);
+        (state, actions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_replay(timestamps: &[u64]) -> Replay {
+        let events = timestamps
+            .iter()
+            .map(|&ts| ReplayEvent {
+                ts,
+                action: Action::TraceStopped(crate::TraceStoppedAction { ts }),
+            })
+            .collect();
+        Replay::new(events)
+    }
+
+    fn action_ts(actions: &[Action]) -> Vec<u64> {
+        actions
+            .iter()
+            .map(|a| match a {
+                Action::TraceStopped(a) => a.ts,
+                _ => panic!("unexpected action {a}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut replay = test_replay(&[1_000, 1_500, 3_000, 10_000]);
+        assert_eq!(action_ts(&replay.advance(Duration::ZERO)), vec![1_000]);
+        assert_eq!(
+            action_ts(&replay.advance(Duration::from_nanos(2_000))),
+            vec![1_500, 3_000]
+        );
+        assert!(replay.advance(Duration::from_nanos(1_000)).is_empty());
+
+        replay.toggle_pause();
+        assert!(replay.advance(Duration::from_secs(1)).is_empty());
+        assert!(replay.status().ends_with("(paused)"));
+
+        replay.toggle_pause();
+        assert_eq!(
+            action_ts(&replay.advance(Duration::from_secs(1))),
+            vec![10_000]
+        );
+        assert!(replay.is_finished());
+        assert_eq!(replay.elapsed(), replay.duration());
+    }
+
+    #[test]
+    fn test_speed() {
+        let mut replay = test_replay(&[0, 4_000, 8_000]);
+        replay.speed_up();
+        replay.speed_up();
+        assert_eq!(replay.speed(), 4.0);
+        assert_eq!(
+            action_ts(&replay.advance(Duration::from_nanos(1_000))),
+            vec![0, 4_000]
+        );
+
+        for _ in 0..20 {
+            replay.speed_up();
+        }
+        assert_eq!(replay.speed(), MAX_REPLAY_SPEED);
+        for _ in 0..20 {
+            replay.slow_down();
+        }
+        assert_eq!(replay.speed(), MIN_REPLAY_SPEED);
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut replay = test_replay(&[0, 5_000, 10_000, 15_000]);
+        replay.seek(10_000);
+        assert_eq!(replay.elapsed(), Duration::from_nanos(10_000));
+        assert_eq!(action_ts(&replay.advance(Duration::ZERO)), vec![10_000]);
+
+        replay.seek(-7_000);
+        assert_eq!(
+            action_ts(&replay.advance(Duration::from_nanos(2_000))),
+            vec![5_000]
+        );
+
+        replay.seek(-100_000);
+        assert_eq!(replay.elapsed(), Duration::ZERO);
+        replay.seek(100_000);
+        assert_eq!(replay.elapsed(), replay.duration());
+        assert_eq!(action_ts(&replay.advance(Duration::ZERO)), vec![15_000]);
+        assert!(replay.status().ends_with("(finished)"));
+    }
+
+    #[test]
+    fn test_replayed() {
+        let mut replay = test_replay(&[0, 5_000, 10_000, 15_000]);
+        assert_eq!(replay.replayed().count(), 0);
+        replay.advance(Duration::from_nanos(12_000));
+        let replayed: Vec<_> = replay.replayed().cloned().collect();
+        assert_eq!(action_ts(&replayed), vec![0, 5_000, 10_000]);
+
+        replay.seek(-4_000);
+        let replayed: Vec<_> = replay.replayed().cloned().collect();
+        assert_eq!(action_ts(&replayed), vec![0, 5_000]);
+        assert!(replay.advance(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut replay = test_replay(&[0, 1_000, 2_000, 3_000, 4_000, 5_000, 6_000]);
+        let mut snapshots = ReplaySnapshots::new(2_000);
+        let mut replayed = Vec::new();
+        for _ in 0..7 {
+            replayed.extend(action_ts(&replay.advance(Duration::from_nanos(1_000))));
+            if snapshots.is_due(&replay) {
+                snapshots.push(&replay, replayed.clone());
+            }
+        }
+        // Snapshots after 1_000, 3_000 and 5_000
+        assert_eq!(snapshots.len(), 3);
+
+        // Resume from the snapshot taken after 3_000
+        replay.seek(-2_500);
+        let (state, actions) = snapshots.rewind(&replay);
+        let mut rebuilt = state.unwrap().clone();
+        rebuilt.extend(action_ts(&actions.cloned().collect::<Vec<_>>()));
+        let expected: Vec<_> = replay.replayed().cloned().collect();
+        assert_eq!(rebuilt, action_ts(&expected));
+        assert_eq!(rebuilt, vec![0, 1_000, 2_000, 3_000]);
+        assert_eq!(snapshots.len(), 2);
+
+        // Without an earlier snapshot all the replayed actions are returned
+        replay.seek(-100_000);
+        replay.advance(Duration::ZERO);
+        let (state, actions) = snapshots.rewind(&replay);
+        assert!(state.is_none());
+        assert_eq!(actions.count(), 1);
+        assert!(snapshots.is_empty());
+    }
+
+    #[test]
+    fn test_handle_action() {
+        let mut replay = test_replay(&[0, REPLAY_SEEK_NS, 2 * REPLAY_SEEK_NS]);
+        assert!(replay.handle_action(&Action::SetState(AppState::Pause)));
+        assert!(replay.is_paused());
+        assert!(replay.handle_action(&Action::ReplaySpeedUp));
+        assert_eq!(replay.speed(), 2.0);
+        assert!(replay.handle_action(&Action::ReplaySeekForward));
+        assert_eq!(replay.elapsed(), Duration::from_nanos(REPLAY_SEEK_NS));
+        assert!(replay.handle_action(&Action::ReplaySeekBackward));
+        assert_eq!(replay.elapsed(), Duration::ZERO);
+        assert!(!replay.handle_action(&Action::Tick));
+        assert!(!replay.handle_action(&Action::SetState(AppState::Help)));
+    }
+}
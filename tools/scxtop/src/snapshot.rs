@@ -0,0 +1,297 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::{CpuData, EventData, LlcData, NodeData, ProcData, StatAggregation, VecStats};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Sections that can be included in a snapshot.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, ValueEnum)]
+pub enum SnapshotField {
+    Cpu,
+    Llc,
+    Node,
+    Process,
+    Scheduler,
+}
+
+/// Per-CPU snapshot, events hold the latest value of each collected event.
+#[derive(Clone, Debug, Serialize)]
+pub struct CpuSnapshot {
+    pub cpu: usize,
+    pub core: usize,
+    pub llc: usize,
+    pub node: usize,
+    pub events: BTreeMap<String, u64>,
+}
+
+impl From<&CpuData> for CpuSnapshot {
+    fn from(cpu_data: &CpuData) -> Self {
+        Self {
+            cpu: cpu_data.cpu,
+            core: cpu_data.core,
+            llc: cpu_data.llc,
+            node: cpu_data.node,
+            events: latest_events(&cpu_data.data),
+        }
+    }
+}
+
+/// Per-LLC snapshot.
+#[derive(Clone, Debug, Serialize)]
+pub struct LlcSnapshot {
+    pub llc: usize,
+    pub node: usize,
+    pub num_cpus: usize,
+    pub events: BTreeMap<String, u64>,
+}
+
+impl From<&LlcData> for LlcSnapshot {
+    fn from(llc_data: &LlcData) -> Self {
+        Self {
+            llc: llc_data.llc,
+            node: llc_data.node,
+            num_cpus: llc_data.num_cpus,
+            events: latest_events(&llc_data.data),
+        }
+    }
+}
+
+/// Per-NUMA node snapshot.
+#[derive(Clone, Debug, Serialize)]
+pub struct NodeSnapshot {
+    pub node: usize,
+    pub num_cpus: usize,
+    pub events: BTreeMap<String, u64>,
+}
+
+impl From<&NodeData> for NodeSnapshot {
+    fn from(node_data: &NodeData) -> Self {
+        Self {
+            node: node_data.node,
+            num_cpus: node_data.num_cpus,
+            events: latest_events(&node_data.data),
+        }
+    }
+}
+
+/// Per-process snapshot, latencies are in μs.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessSnapshot {
+    pub pid: i32,
+    pub comm: String,
+    pub cpu: i32,
+    pub llc: Option<u32>,
+    pub node: Option<u32>,
+    pub dsq: Option<u64>,
+    pub layer_id: Option<i32>,
+    pub cpu_util_perc: f64,
+    pub num_threads: i64,
+    pub lat_p50_us: u64,
+    pub lat_p99_us: u64,
+}
+
+impl From<&ProcData> for ProcessSnapshot {
+    fn from(proc_data: &ProcData) -> Self {
+        let lat_data: Vec<u64> = proc_data
+            .event_data_immut("lat_us")
+            .into_iter()
+            .filter(|&v| v > 0)
+            .collect();
+        let percentile_set: HashSet<StatAggregation> = [StatAggregation::P50, StatAggregation::P99]
+            .into_iter()
+            .collect();
+        let stats = VecStats::new(&lat_data, Some(percentile_set));
+        let percentile = |agg: StatAggregation| {
+            stats
+                .percentiles
+                .as_ref()
+                .and_then(|m| m.get(&agg))
+                .copied()
+                .unwrap_or(0)
+        };
+
+        Self {
+            pid: proc_data.tgid,
+            comm: proc_data.process_name.clone(),
+            cpu: proc_data.cpu,
+            llc: proc_data.llc,
+            node: proc_data.node,
+            dsq: proc_data.dsq,
+            layer_id: proc_data.layer_id,
+            cpu_util_perc: proc_data.cpu_util_perc,
+            num_threads: proc_data.num_threads,
+            lat_p50_us: percentile(StatAggregation::P50),
+            lat_p99_us: percentile(StatAggregation::P99),
+        }
+    }
+}
+
+/// Scheduler snapshot.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SchedulerSnapshot {
+    /// Name of the attached sched_ext scheduler, empty if none.
+    pub name: String,
+    pub dropped_events: u64,
+    pub select_cpu_fallback: i64,
+    pub dispatch_local_dsq_offline: i64,
+    pub dispatch_keep_last: i64,
+    pub enq_skip_exiting: i64,
+    pub enq_skip_migration_disabled: i64,
+    /// Stats reported by the scheduler over scx_stats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<JsonValue>,
+}
+
+/// Snapshot of the data collected by scxtop, sections that were not selected are omitted.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Snapshot {
+    pub timestamp_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<Vec<CpuSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub llcs: Option<Vec<LlcSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes: Option<Vec<NodeSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub processes: Option<Vec<ProcessSnapshot>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<SchedulerSnapshot>,
+}
+
+impl Snapshot {
+    /// Creates an empty snapshot with the current timestamp.
+    pub fn new() -> Snapshot {
+        Self {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the processes, keeping the max_procs processes with the highest CPU utilization.
+    pub fn set_processes<'a>(
+        &mut self,
+        procs: impl IntoIterator<Item = &'a ProcData>,
+        max_procs: Option<usize>,
+    ) {
+        let mut processes: Vec<ProcessSnapshot> =
+            procs.into_iter().map(ProcessSnapshot::from).collect();
+        processes.sort_by(|a, b| b.cpu_util_perc.total_cmp(&a.cpu_util_perc));
+        if let Some(max_procs) = max_procs {
+            processes.truncate(max_procs);
+        }
+        self.processes = Some(processes);
+    }
+
+    /// Writes the snapshot as a single JSON line.
+    pub fn write_json_line<W: Write>(&self, writer: &mut W) -> Result<()> {
+        serde_json::to_writer(&mut *writer, self)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns the latest value of each event.
+fn latest_events(data: &EventData) -> BTreeMap<String, u64> {
+    data.data
+        .iter()
+        .filter_map(|(event, vals)| Some((event.clone(), *vals.back()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use procfs::process::ProcState;
+
+    fn test_proc_data(tgid: i32, cpu_util_perc: f64, lat_us: &[u64]) -> ProcData {
+        let mut data = EventData::new(10);
+        for lat in lat_us {
+            data.add_event_data("lat_us", *lat);
+        }
+        ProcData {
+            tgid,
+            process_name: format!("proc{tgid}"),
+            cpu: 0,
+            llc: None,
+            node: None,
+            dsq: None,
+            layer_id: None,
+            prev_cpu_time: 0,
+            current_cpu_time: 0,
+            cpu_util_perc,
+            state: ProcState::Running,
+            cmdline: vec![],
+            threads: BTreeMap::new(),
+            num_threads: 1,
+            data,
+            max_data_size: 10,
+        }
+    }
+
+    #[test]
+    fn test_latest_events() {
+        let mut cpu_data = CpuData::new(3, 1, 0, 0, 10);
+        cpu_data.data.clear();
+        cpu_data.add_event_data("cpu_total_util_percent", 10);
+        cpu_data.add_event_data("cpu_total_util_percent", 42);
+        cpu_data.data.event_data("empty");
+
+        let snapshot = CpuSnapshot::from(&cpu_data);
+        assert_eq!(snapshot.cpu, 3);
+        assert_eq!(snapshot.core, 1);
+        assert_eq!(snapshot.events.len(), 1);
+        assert_eq!(snapshot.events["cpu_total_util_percent"], 42);
+    }
+
+    #[test]
+    fn test_set_processes() {
+        let procs = [
+            test_proc_data(1, 5.0, &[]),
+            test_proc_data(2, 50.0, &[10, 20, 30]),
+            test_proc_data(3, 20.0, &[]),
+        ];
+        let mut snapshot = Snapshot::new();
+        snapshot.set_processes(procs.iter(), Some(2));
+
+        let processes = snapshot.processes.unwrap();
+        assert_eq!(processes.len(), 2);
+        assert_eq!(processes[0].pid, 2);
+        assert_eq!(processes[0].lat_p50_us, 20);
+        assert_eq!(processes[1].pid, 3);
+        assert_eq!(processes[1].lat_p99_us, 0);
+    }
+
+    #[test]
+    fn test_write_json_line() {
+        let mut snapshot = Snapshot::new();
+        snapshot.scheduler = Some(SchedulerSnapshot {
+            name: "scx_rustland".to_string(),
+            ..Default::default()
+        });
+
+        let mut out = Vec::new();
+        snapshot.write_json_line(&mut out).unwrap();
+        snapshot.write_json_line(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let json: JsonValue = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(json["scheduler"]["name"], "scx_rustland");
+        assert!(json.get("cpus").is_none());
+        assert!(json.get("processes").is_none());
+    }
+}
@@ -0,0 +1,841 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use crate::mcp::perfetto_event_types::softirq_type_name;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use perfetto_protos::{
+    ftrace_event::{ftrace_event, FtraceEvent},
+    trace::Trace,
+    trace_packet::trace_packet,
+    track_descriptor::track_descriptor::Static_or_dynamic_name,
+    track_event::track_event,
+};
+use serde_json::{json, Value as JsonValue};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+// Chrome trace pid that holds the per-CPU tracks and counters.
+const CHROME_CPU_PID: u32 = 0;
+
+/// Output formats of a trace.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum TraceFormat {
+    /// Perfetto protobuf.
+    #[default]
+    Perfetto,
+    /// Chrome trace-event JSON.
+    Chrome,
+    /// One CSV file per event type.
+    Csv,
+}
+
+impl TraceFormat {
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TraceFormat::Perfetto => "proto",
+            TraceFormat::Chrome => "json",
+            TraceFormat::Csv => "csv",
+        }
+    }
+}
+
+/// Time a task ran on a CPU, between two sched_switch events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchedSlice {
+    pub cpu: u32,
+    pub ts: u64,
+    pub dur: u64,
+    pub pid: i32,
+    pub comm: String,
+    pub prio: i32,
+    pub end_state: i64,
+}
+
+/// A sched_wakeup or sched_waking event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WakeupEvent {
+    pub ts: u64,
+    pub cpu: u32,
+    pub pid: i32,
+    pub comm: String,
+    pub prio: i32,
+    pub target_cpu: i32,
+}
+
+/// A sched_migrate_task event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MigrateEvent {
+    pub ts: u64,
+    pub cpu: u32,
+    pub pid: i32,
+    pub comm: String,
+    pub prio: i32,
+    pub dest_cpu: i32,
+}
+
+/// Time spent handling a softirq, between the softirq entry and exit events.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftirqSlice {
+    pub cpu: u32,
+    pub ts: u64,
+    pub dur: u64,
+    pub pid: u32,
+    pub vec: u32,
+}
+
+/// An IPI raise event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IpiEvent {
+    pub ts: u64,
+    pub cpu: u32,
+    pub pid: u32,
+    pub target_cpus: u32,
+}
+
+/// A kprobe hit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KprobeHit {
+    pub ts: u64,
+    pub cpu: u32,
+    pub pid: u32,
+    pub name: String,
+}
+
+/// A counter value such as a DSQ latency, CPU frequency or memory usage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CounterSample {
+    pub ts: u64,
+    pub name: String,
+    pub value: f64,
+}
+
+/// Events of a perfetto trace flattened for the Chrome JSON and CSV exports, timestamps are in
+/// ns.
+#[derive(Clone, Debug, Default)]
+pub struct TraceEvents {
+    pub sched_slices: Vec<SchedSlice>,
+    pub wakeups: Vec<WakeupEvent>,
+    pub wakings: Vec<WakeupEvent>,
+    pub migrations: Vec<MigrateEvent>,
+    pub softirqs: Vec<SoftirqSlice>,
+    pub ipis: Vec<IpiEvent>,
+    pub kprobes: Vec<KprobeHit>,
+    pub counters: Vec<CounterSample>,
+}
+
+impl TraceEvents {
+    /// Collects the events of a trace built by the PerfettoTraceManager. Tasks still running at
+    /// the end of the trace get a slice ending at the last event of the trace.
+    pub fn from_trace(trace: &Trace) -> Self {
+        let mut events = Self::default();
+
+        let mut track_names: HashMap<u64, &str> = HashMap::new();
+        for packet in &trace.packet {
+            if let Some(trace_packet::Data::TrackDescriptor(desc)) = &packet.data {
+                if let (Some(uuid), Some(Static_or_dynamic_name::StaticName(name))) =
+                    (desc.uuid, &desc.static_or_dynamic_name)
+                {
+                    track_names.insert(uuid, name.as_str());
+                }
+            }
+        }
+
+        let end_ts = trace
+            .packet
+            .iter()
+            .filter_map(|packet| match &packet.data {
+                Some(trace_packet::Data::FtraceEvents(bundle)) => {
+                    bundle.event.iter().filter_map(|e| e.timestamp).max()
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+
+        for packet in &trace.packet {
+            match &packet.data {
+                Some(trace_packet::Data::FtraceEvents(bundle)) => {
+                    let mut cpu_events: Vec<&FtraceEvent> = bundle.event.iter().collect();
+                    cpu_events.sort_by_key(|e| e.timestamp.unwrap_or(0));
+                    events.add_cpu_events(bundle.cpu.unwrap_or(0), &cpu_events, end_ts);
+                }
+                Some(trace_packet::Data::TrackEvent(event)) => {
+                    if event.type_ != Some(track_event::Type::TYPE_COUNTER.into()) {
+                        continue;
+                    }
+                    let Some(name) = event.track_uuid.and_then(|uuid| track_names.get(&uuid))
+                    else {
+                        continue;
+                    };
+                    let value = match event.counter_value_field {
+                        Some(track_event::Counter_value_field::CounterValue(v)) => v as f64,
+                        Some(track_event::Counter_value_field::DoubleCounterValue(v)) => v,
+                        _ => continue,
+                    };
+                    events.counters.push(CounterSample {
+                        ts: packet.timestamp.unwrap_or(0),
+                        name: name.to_string(),
+                        value,
+                    });
+                }
+                Some(trace_packet::Data::SysStats(stats)) => {
+                    let ts = packet.timestamp.unwrap_or(0);
+                    for (cpu, freq) in stats.cpufreq_khz.iter().enumerate() {
+                        events.counters.push(CounterSample {
+                            ts,
+                            name: format!("cpu{cpu} freq"),
+                            value: *freq as f64,
+                        });
+                    }
+                    for meminfo in &stats.meminfo {
+                        let (Some(key), Some(value)) =
+                            (meminfo.key.and_then(|k| k.enum_value().ok()), meminfo.value)
+                        else {
+                            continue;
+                        };
+                        let key = format!("{key:?}");
+                        events.counters.push(CounterSample {
+                            ts,
+                            name: format!(
+                                "{}_kb",
+                                key.trim_start_matches("MEMINFO_").to_lowercase()
+                            ),
+                            value: value as f64,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        events.counters.sort_by_key(|c| c.ts);
+        events
+    }
+
+    /// Adds the sorted ftrace events of a CPU.
+    fn add_cpu_events(&mut self, cpu: u32, cpu_events: &[&FtraceEvent], end_ts: u64) {
+        // Task running on the CPU along with the time it was switched in
+        let mut running: Option<(u64, i32, String, i32)> = None;
+        let mut softirq_entries: HashMap<u32, u64> = HashMap::new();
+
+        for event in cpu_events {
+            let Some(ts) = event.timestamp else {
+                continue;
+            };
+            match &event.event {
+                Some(ftrace_event::Event::SchedSwitch(switch)) => {
+                    if let Some((start, pid, comm, prio)) = running.take() {
+                        self.sched_slices.push(SchedSlice {
+                            cpu,
+                            ts: start,
+                            dur: ts - start,
+                            pid,
+                            comm,
+                            prio,
+                            end_state: switch.prev_state.unwrap_or(0),
+                        });
+                    }
+                    running = switch.next_pid.filter(|&pid| pid > 0).map(|pid| {
+                        (
+                            ts,
+                            pid,
+                            switch.next_comm.clone().unwrap_or_default(),
+                            switch.next_prio.unwrap_or(0),
+                        )
+                    });
+                }
+                Some(ftrace_event::Event::SchedWakeup(wakeup)) => {
+                    self.wakeups.push(WakeupEvent {
+                        ts,
+                        cpu,
+                        pid: wakeup.pid.unwrap_or(0),
+                        comm: wakeup.comm.clone().unwrap_or_default(),
+                        prio: wakeup.prio.unwrap_or(0),
+                        target_cpu: wakeup.target_cpu.unwrap_or(cpu as i32),
+                    });
+                }
+                Some(ftrace_event::Event::SchedWaking(waking)) => {
+                    self.wakings.push(WakeupEvent {
+                        ts,
+                        cpu,
+                        pid: waking.pid.unwrap_or(0),
+                        comm: waking.comm.clone().unwrap_or_default(),
+                        prio: waking.prio.unwrap_or(0),
+                        target_cpu: waking.target_cpu.unwrap_or(cpu as i32),
+                    });
+                }
+                Some(ftrace_event::Event::SchedMigrateTask(migrate)) => {
+                    self.migrations.push(MigrateEvent {
+                        ts,
+                        cpu,
+                        pid: migrate.pid.unwrap_or(0),
+                        comm: migrate.comm.clone().unwrap_or_default(),
+                        prio: migrate.prio.unwrap_or(0),
+                        dest_cpu: migrate.dest_cpu.unwrap_or(0),
+                    });
+                }
+                Some(ftrace_event::Event::SoftirqEntry(entry)) => {
+                    if let Some(vec) = entry.vec {
+                        softirq_entries.insert(vec, ts);
+                    }
+                }
+                Some(ftrace_event::Event::SoftirqExit(exit)) => {
+                    let Some(vec) = exit.vec else {
+                        continue;
+                    };
+                    if let Some(entry_ts) = softirq_entries.remove(&vec) {
+                        self.softirqs.push(SoftirqSlice {
+                            cpu,
+                            ts: entry_ts,
+                            dur: ts.saturating_sub(entry_ts),
+                            pid: event.pid.unwrap_or(0),
+                            vec,
+                        });
+                    }
+                }
+                Some(ftrace_event::Event::IpiRaise(ipi)) => {
+                    self.ipis.push(IpiEvent {
+                        ts,
+                        cpu,
+                        pid: event.pid.unwrap_or(0),
+                        target_cpus: ipi.target_cpus.unwrap_or(0),
+                    });
+                }
+                Some(ftrace_event::Event::KprobeEvent(kprobe)) => {
+                    self.kprobes.push(KprobeHit {
+                        ts,
+                        cpu,
+                        pid: event.pid.unwrap_or(0),
+                        name: kprobe.name.clone().unwrap_or_default(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((start, pid, comm, prio)) = running {
+            self.sched_slices.push(SchedSlice {
+                cpu,
+                ts: start,
+                dur: end_ts.saturating_sub(start),
+                pid,
+                comm,
+                prio,
+                end_state: 0,
+            });
+        }
+    }
+
+    /// Returns the CPUs that have events.
+    fn cpus(&self) -> BTreeSet<u32> {
+        self.sched_slices
+            .iter()
+            .map(|e| e.cpu)
+            .chain(self.wakeups.iter().map(|e| e.cpu))
+            .chain(self.wakings.iter().map(|e| e.cpu))
+            .chain(self.migrations.iter().map(|e| e.cpu))
+            .chain(self.softirqs.iter().map(|e| e.cpu))
+            .chain(self.ipis.iter().map(|e| e.cpu))
+            .chain(self.kprobes.iter().map(|e| e.cpu))
+            .collect()
+    }
+
+    /// Writes the events in the Chrome trace-event JSON format. Each CPU is a thread of a "CPUs"
+    /// process, tasks are complete events on the CPU they ran on.
+    pub fn write_chrome_json<W: Write>(&self, writer: W) -> Result<()> {
+        let mut out = ChromeEventWriter::new(writer)?;
+
+        out.write(&json!({
+            "ph": "M",
+            "name": "process_name",
+            "pid": CHROME_CPU_PID,
+            "args": { "name": "CPUs" },
+        }))?;
+        for cpu in self.cpus() {
+            out.write(&json!({
+                "ph": "M",
+                "name": "thread_name",
+                "pid": CHROME_CPU_PID,
+                "tid": cpu,
+                "args": { "name": format!("CPU {cpu}") },
+            }))?;
+            out.write(&json!({
+                "ph": "M",
+                "name": "thread_sort_index",
+                "pid": CHROME_CPU_PID,
+                "tid": cpu,
+                "args": { "sort_index": cpu },
+            }))?;
+        }
+
+        for slice in &self.sched_slices {
+            out.write(&json!({
+                "ph": "X",
+                "cat": "sched",
+                "name": slice.comm,
+                "pid": CHROME_CPU_PID,
+                "tid": slice.cpu,
+                "ts": ns_to_us(slice.ts),
+                "dur": ns_to_us(slice.dur),
+                "args": {
+                    "pid": slice.pid,
+                    "prio": slice.prio,
+                    "end_state": slice.end_state,
+                },
+            }))?;
+        }
+        for (name, wakeups) in [
+            ("sched_wakeup", &self.wakeups),
+            ("sched_waking", &self.wakings),
+        ] {
+            for wakeup in wakeups {
+                out.write(&instant_event(
+                    name,
+                    "sched",
+                    wakeup.cpu,
+                    wakeup.ts,
+                    json!({
+                        "pid": wakeup.pid,
+                        "comm": wakeup.comm,
+                        "prio": wakeup.prio,
+                        "target_cpu": wakeup.target_cpu,
+                    }),
+                ))?;
+            }
+        }
+        for migrate in &self.migrations {
+            out.write(&instant_event(
+                "sched_migrate_task",
+                "sched",
+                migrate.cpu,
+                migrate.ts,
+                json!({
+                    "pid": migrate.pid,
+                    "comm": migrate.comm,
+                    "prio": migrate.prio,
+                    "dest_cpu": migrate.dest_cpu,
+                }),
+            ))?;
+        }
+        for softirq in &self.softirqs {
+            out.write(&json!({
+                "ph": "X",
+                "cat": "irq",
+                "name": format!("softirq {}", softirq_type_name(softirq.vec)),
+                "pid": CHROME_CPU_PID,
+                "tid": softirq.cpu,
+                "ts": ns_to_us(softirq.ts),
+                "dur": ns_to_us(softirq.dur),
+                "args": {
+                    "pid": softirq.pid,
+                    "vec": softirq.vec,
+                },
+            }))?;
+        }
+        for ipi in &self.ipis {
+            out.write(&instant_event(
+                "ipi_raise",
+                "ipi",
+                ipi.cpu,
+                ipi.ts,
+                json!({
+                    "pid": ipi.pid,
+                    "target_cpus": ipi.target_cpus,
+                }),
+            ))?;
+        }
+        for kprobe in &self.kprobes {
+            out.write(&instant_event(
+                "kprobe",
+                "kprobe",
+                kprobe.cpu,
+                kprobe.ts,
+                json!({
+                    "pid": kprobe.pid,
+                    "name": kprobe.name,
+                }),
+            ))?;
+        }
+        for counter in &self.counters {
+            out.write(&json!({
+                "ph": "C",
+                "name": counter.name,
+                "pid": CHROME_CPU_PID,
+                "ts": ns_to_us(counter.ts),
+                "args": { "value": counter.value },
+            }))?;
+        }
+
+        out.finish()
+    }
+
+    /// Writes the events to one CSV file per event type named {prefix}_{event}.csv, returns the
+    /// written files.
+    pub fn write_csv(&self, prefix: &str) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        let mut write_file = |event: &str, header: &str, rows: Vec<String>| -> Result<()> {
+            let path = PathBuf::from(format!("{prefix}_{event}.csv"));
+            let mut writer = BufWriter::new(File::create(&path)?);
+            writeln!(writer, "{header}")?;
+            for row in rows {
+                writeln!(writer, "{row}")?;
+            }
+            writer.flush()?;
+            paths.push(path);
+            Ok(())
+        };
+
+        write_file(
+            "sched_switch",
+            "ts_ns,dur_ns,cpu,pid,comm,prio,end_state",
+            self.sched_slices
+                .iter()
+                .map(|s| {
+                    format!(
+                        "{},{},{},{},{},{},{}",
+                        s.ts,
+                        s.dur,
+                        s.cpu,
+                        s.pid,
+                        csv_field(&s.comm),
+                        s.prio,
+                        s.end_state
+                    )
+                })
+                .collect(),
+        )?;
+        for (event, wakeups) in [
+            ("sched_wakeup", &self.wakeups),
+            ("sched_waking", &self.wakings),
+        ] {
+            write_file(
+                event,
+                "ts_ns,cpu,pid,comm,prio,target_cpu",
+                wakeups
+                    .iter()
+                    .map(|w| {
+                        format!(
+                            "{},{},{},{},{},{}",
+                            w.ts,
+                            w.cpu,
+                            w.pid,
+                            csv_field(&w.comm),
+                            w.prio,
+                            w.target_cpu
+                        )
+                    })
+                    .collect(),
+            )?;
+        }
+        write_file(
+            "sched_migrate_task",
+            "ts_ns,cpu,pid,comm,prio,dest_cpu",
+            self.migrations
+                .iter()
+                .map(|m| {
+                    format!(
+                        "{},{},{},{},{},{}",
+                        m.ts,
+                        m.cpu,
+                        m.pid,
+                        csv_field(&m.comm),
+                        m.prio,
+                        m.dest_cpu
+                    )
+                })
+                .collect(),
+        )?;
+        write_file(
+            "softirq",
+            "ts_ns,dur_ns,cpu,pid,vec,name",
+            self.softirqs
+                .iter()
+                .map(|s| {
+                    format!(
+                        "{},{},{},{},{},{}",
+                        s.ts,
+                        s.dur,
+                        s.cpu,
+                        s.pid,
+                        s.vec,
+                        softirq_type_name(s.vec)
+                    )
+                })
+                .collect(),
+        )?;
+        write_file(
+            "ipi",
+            "ts_ns,cpu,pid,target_cpus",
+            self.ipis
+                .iter()
+                .map(|i| format!("{},{},{},{}", i.ts, i.cpu, i.pid, i.target_cpus))
+                .collect(),
+        )?;
+        write_file(
+            "kprobe",
+            "ts_ns,cpu,pid,name",
+            self.kprobes
+                .iter()
+                .map(|k| format!("{},{},{},{}", k.ts, k.cpu, k.pid, csv_field(&k.name)))
+                .collect(),
+        )?;
+        write_file(
+            "counters",
+            "ts_ns,name,value",
+            self.counters
+                .iter()
+                .map(|c| format!("{},{},{}", c.ts, csv_field(&c.name), c.value))
+                .collect(),
+        )?;
+
+        Ok(paths)
+    }
+}
+
+/// Streams events into the traceEvents array of a Chrome trace.
+struct ChromeEventWriter<W: Write> {
+    writer: W,
+    first: bool,
+}
+
+impl<W: Write> ChromeEventWriter<W> {
+    fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(b"{\"displayTimeUnit\":\"ns\",\"traceEvents\":[")?;
+        Ok(Self {
+            writer,
+            first: true,
+        })
+    }
+
+    fn write(&mut self, event: &JsonValue) -> Result<()> {
+        if !self.first {
+            self.writer.write_all(b",\n")?;
+        }
+        self.first = false;
+        serde_json::to_writer(&mut self.writer, event)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.writer.write_all(b"]}\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Returns a thread scoped Chrome instant event on a CPU track.
+fn instant_event(name: &str, cat: &str, cpu: u32, ts: u64, args: JsonValue) -> JsonValue {
+    json!({
+        "ph": "i",
+        "s": "t",
+        "cat": cat,
+        "name": name,
+        "pid": CHROME_CPU_PID,
+        "tid": cpu,
+        "ts": ns_to_us(ts),
+        "args": args,
+    })
+}
+
+/// Chrome trace timestamps are in μs.
+fn ns_to_us(ns: u64) -> f64 {
+    ns as f64 / 1000.0
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use perfetto_protos::{
+        ftrace_event_bundle::FtraceEventBundle,
+        irq::{SoftirqEntryFtraceEvent, SoftirqExitFtraceEvent},
+        sched::{SchedSwitchFtraceEvent, SchedWakeupFtraceEvent},
+        trace_packet::TracePacket,
+    };
+
+    fn switch(ts: u64, prev_pid: i32, next_pid: i32, next_comm: &str) -> FtraceEvent {
+        FtraceEvent {
+            timestamp: Some(ts),
+            pid: Some(prev_pid as u32),
+            event: Some(ftrace_event::Event::SchedSwitch(SchedSwitchFtraceEvent {
+                prev_pid: Some(prev_pid),
+                prev_state: Some(1),
+                next_pid: (next_pid > 0).then_some(next_pid),
+                next_comm: (next_pid > 0).then(|| next_comm.to_string()),
+                next_prio: Some(120),
+                ..SchedSwitchFtraceEvent::default()
+            })),
+            ..FtraceEvent::default()
+        }
+    }
+
+    fn bundle(cpu: u32, events: Vec<FtraceEvent>) -> TracePacket {
+        TracePacket {
+            data: Some(trace_packet::Data::FtraceEvents(FtraceEventBundle {
+                cpu: Some(cpu),
+                event: events,
+                ..FtraceEventBundle::default()
+            })),
+            ..TracePacket::default()
+        }
+    }
+
+    fn test_trace() -> Trace {
+        let wakeup = FtraceEvent {
+            timestamp: Some(500),
+            pid: Some(100),
+            event: Some(ftrace_event::Event::SchedWakeup(SchedWakeupFtraceEvent {
+                pid: Some(100),
+                comm: Some("worker, a".to_string()),
+                prio: Some(120),
+                target_cpu: Some(0),
+                ..SchedWakeupFtraceEvent::default()
+            })),
+            ..FtraceEvent::default()
+        };
+        let softirq_entry = FtraceEvent {
+            timestamp: Some(1_500),
+            pid: Some(100),
+            event: Some(ftrace_event::Event::SoftirqEntry(SoftirqEntryFtraceEvent {
+                vec: Some(1),
+                ..SoftirqEntryFtraceEvent::default()
+            })),
+            ..FtraceEvent::default()
+        };
+        let softirq_exit = FtraceEvent {
+            timestamp: Some(1_700),
+            pid: Some(100),
+            event: Some(ftrace_event::Event::SoftirqExit(SoftirqExitFtraceEvent {
+                vec: Some(1),
+                ..SoftirqExitFtraceEvent::default()
+            })),
+            ..FtraceEvent::default()
+        };
+
+        Trace {
+            packet: vec![
+                bundle(
+                    0,
+                    vec![
+                        switch(3_000, 100, 0, ""),
+                        wakeup,
+                        switch(1_000, 0, 100, "worker, a"),
+                        softirq_entry,
+                        softirq_exit,
+                    ],
+                ),
+                bundle(1, vec![switch(2_000, 0, 200, "other")]),
+            ],
+            ..Trace::default()
+        }
+    }
+
+    #[test]
+    fn test_from_trace() {
+        let events = TraceEvents::from_trace(&test_trace());
+
+        assert_eq!(
+            events.sched_slices,
+            vec![
+                SchedSlice {
+                    cpu: 0,
+                    ts: 1_000,
+                    dur: 2_000,
+                    pid: 100,
+                    comm: "worker, a".to_string(),
+                    prio: 120,
+                    end_state: 1,
+                },
+                // Still running at the end of the trace
+                SchedSlice {
+                    cpu: 1,
+                    ts: 2_000,
+                    dur: 1_000,
+                    pid: 200,
+                    comm: "other".to_string(),
+                    prio: 120,
+                    end_state: 0,
+                },
+            ]
+        );
+        assert_eq!(events.wakeups.len(), 1);
+        assert_eq!(
+            events.softirqs,
+            vec![SoftirqSlice {
+                cpu: 0,
+                ts: 1_500,
+                dur: 200,
+                pid: 100,
+                vec: 1,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_write_chrome_json() {
+        let events = TraceEvents::from_trace(&test_trace());
+        let mut buf = Vec::new();
+        events.write_chrome_json(&mut buf).unwrap();
+
+        let trace: JsonValue = serde_json::from_slice(&buf).unwrap();
+        let trace_events = trace["traceEvents"].as_array().unwrap();
+        let slice = trace_events
+            .iter()
+            .find(|e| e["ph"] == "X" && e["cat"] == "sched" && e["tid"] == 0)
+            .unwrap();
+        assert_eq!(slice["name"], "worker, a");
+        assert_eq!(slice["ts"], 1.0);
+        assert_eq!(slice["dur"], 2.0);
+        assert!(trace_events
+            .iter()
+            .any(|e| e["name"] == "softirq TIMER" && e["dur"] == 0.2));
+        assert!(trace_events
+            .iter()
+            .any(|e| e["ph"] == "i" && e["name"] == "sched_wakeup"));
+        assert!(trace_events
+            .iter()
+            .any(|e| e["ph"] == "M" && e["args"]["name"] == "CPU 1"));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("trace");
+        let events = TraceEvents::from_trace(&test_trace());
+        let paths = events.write_csv(prefix.to_str().unwrap()).unwrap();
+        assert_eq!(paths.len(), 8);
+
+        let sched_switch =
+            std::fs::read_to_string(dir.path().join("trace_sched_switch.csv")).unwrap();
+        let lines: Vec<&str> = sched_switch.lines().collect();
+        assert_eq!(lines[0], "ts_ns,dur_ns,cpu,pid,comm,prio,end_state");
+        assert_eq!(lines[1], "1000,2000,0,100,\"worker, a\",120,1");
+        assert_eq!(lines.len(), 3);
+
+        let softirq = std::fs::read_to_string(dir.path().join("trace_softirq.csv")).unwrap();
+        assert!(softirq.contains("1500,200,0,100,1,TIMER"));
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("worker"), "worker");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
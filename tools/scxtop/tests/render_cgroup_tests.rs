@@ -0,0 +1,147 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use procfs::process::ProcState;
+use ratatui::backend::TestBackend;
+use ratatui::widgets::TableState;
+use ratatui::Terminal;
+use scxtop::render::cgroup::{CgroupMembersParams, CgroupTreeParams};
+use scxtop::{render::CgroupRenderer, AppTheme, CgroupTree, EventData, ProcData};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// Helper function to create a fake cgroup directory
+fn create_test_cgroup(dir: &Path, procs: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join("cpu.stat"),
+        "usage_usec 1000\nnr_periods 0\nnr_throttled 0\nthrottled_usec 0\n",
+    )
+    .unwrap();
+    fs::write(dir.join("cpu.weight"), "100\n").unwrap();
+    fs::write(dir.join("cpu.max"), "max 100000\n").unwrap();
+    fs::write(dir.join("cgroup.procs"), procs).unwrap();
+}
+
+// Helper function to create a fake cgroup v2 hierarchy
+fn create_test_cgroup_tree(root: &Path) -> CgroupTree {
+    fs::write(root.join("cgroup.controllers"), "cpu memory\n").unwrap();
+    create_test_cgroup(root, "1\n");
+    create_test_cgroup(&root.join("system.slice"), "");
+    create_test_cgroup(&root.join("system.slice/sshd.service"), "100\n101\n");
+
+    let mut tree = CgroupTree::new(root, 100);
+    tree.update(4).unwrap();
+    tree
+}
+
+fn create_test_proc_data(tgid: i32, name: &str) -> ProcData {
+    ProcData {
+        tgid,
+        process_name: name.to_string(),
+        cpu: 0,
+        llc: None,
+        node: None,
+        dsq: None,
+        layer_id: None,
+        prev_cpu_time: 0,
+        current_cpu_time: 0,
+        cpu_util_perc: 1.0,
+        state: ProcState::Running,
+        cmdline: vec![],
+        threads: BTreeMap::new(),
+        num_threads: 1,
+        data: EventData::new(100),
+        max_data_size: 100,
+    }
+}
+
+#[test]
+fn test_render_cgroup_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tree = create_test_cgroup_tree(tmp.path());
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+    let theme = AppTheme::Default;
+    let mut table_state = TableState::default();
+
+    terminal
+        .draw(|frame| {
+            let params = CgroupTreeParams {
+                cgroup_tree: &tree,
+                theme: &theme,
+            };
+            let rows =
+                CgroupRenderer::render_cgroup_tree(frame, frame.area(), &params, &mut table_state)
+                    .unwrap();
+            assert_eq!(rows, 3);
+        })
+        .unwrap();
+
+    let content: String = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|c| c.symbol())
+        .collect();
+    assert!(content.contains("system.slice"));
+    assert!(content.contains("sshd.service"));
+    assert!(content.contains("max 100000"));
+}
+
+#[test]
+fn test_render_cgroup_tree_unavailable() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tree = CgroupTree::new(tmp.path(), 100);
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+    let theme = AppTheme::Default;
+    let mut table_state = TableState::default();
+
+    terminal
+        .draw(|frame| {
+            let params = CgroupTreeParams {
+                cgroup_tree: &tree,
+                theme: &theme,
+            };
+            let rows =
+                CgroupRenderer::render_cgroup_tree(frame, frame.area(), &params, &mut table_state)
+                    .unwrap();
+            assert_eq!(rows, 0);
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_render_cgroup_members() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tree = create_test_cgroup_tree(tmp.path());
+    let id = tree.cgroup_of(100).unwrap();
+    let mut proc_data = BTreeMap::new();
+    proc_data.insert(1, create_test_proc_data(1, "init"));
+    proc_data.insert(100, create_test_proc_data(100, "sshd"));
+    let mut terminal = Terminal::new(TestBackend::new(160, 30)).unwrap();
+    let theme = AppTheme::Default;
+    let mut table_state = TableState::default();
+
+    terminal
+        .draw(|frame| {
+            let params = CgroupMembersParams {
+                cgroup: &tree.cgroups[&id],
+                proc_data: &proc_data,
+                theme: &theme,
+            };
+            // Only the processes of the cgroup that are still alive are listed
+            let rows = CgroupRenderer::render_cgroup_members(
+                frame,
+                frame.area(),
+                &params,
+                &mut table_state,
+            )
+            .unwrap();
+            assert_eq!(rows, 1);
+        })
+        .unwrap();
+}
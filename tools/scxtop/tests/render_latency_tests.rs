@@ -0,0 +1,149 @@
+// Copyright (c) Meta Platforms, Inc. and affiliates.
+//
+// This software may be used and distributed according to the terms of the
+// GNU General Public License version 2.
+
+use ratatui::backend::TestBackend;
+use ratatui::widgets::TableState;
+use ratatui::Terminal;
+use scxtop::render::latency::LatencyViewParams;
+use scxtop::{
+    render::LatencyRenderer, AppTheme, LatencyTracker, SchedSwitchAction, SchedWakeupAction,
+};
+
+fn wakeup(ts: u64, pid: u32) -> SchedWakeupAction {
+    SchedWakeupAction {
+        ts,
+        cpu: 0,
+        pid,
+        tgid: pid,
+        prio: 120,
+        cgroup_id: 0,
+        comm: "worker".into(),
+        waker_pid: 0,
+        waker_comm: "".into(),
+    }
+}
+
+fn switch(ts: u64, cpu: u32, next_pid: u32) -> SchedSwitchAction {
+    SchedSwitchAction {
+        ts,
+        cpu,
+        preempt: false,
+        next_dsq_id: 0,
+        next_dsq_lat_us: 0,
+        next_dsq_nr_queued: 0,
+        next_dsq_vtime: 0,
+        next_slice_ns: 0,
+        next_pid,
+        next_tgid: next_pid,
+        next_prio: 120,
+        next_layer_id: -1,
+        next_cgroup_id: 0,
+        next_comm: format!("worker{next_pid}").into(),
+        prev_dsq_id: 0,
+        prev_used_slice_ns: 1_000_000,
+        prev_slice_ns: 0,
+        prev_pid: 0,
+        prev_tgid: 0,
+        prev_prio: 120,
+        prev_comm: "swapper".into(),
+        prev_state: 0,
+        prev_layer_id: -1,
+    }
+}
+
+// Helper function to create a tracker with two processes with different wakeup latencies
+fn create_test_tracker() -> LatencyTracker {
+    let mut tracker = LatencyTracker::new(100);
+    for i in 0..10 {
+        let ts = i * 10_000_000;
+        tracker.on_sched_wakeup(&wakeup(ts, 100));
+        tracker.on_sched_switch(&switch(ts + 20_000, 0, 100));
+        tracker.on_sched_wakeup(&wakeup(ts, 200));
+        tracker.on_sched_switch(&switch(ts + 5_000_000, 1, 200));
+        tracker.on_tick();
+    }
+    tracker
+}
+
+fn buffer_content(terminal: &Terminal<TestBackend>) -> String {
+    terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|c| c.symbol())
+        .collect()
+}
+
+#[test]
+fn test_render_latency_view() {
+    let tracker = create_test_tracker();
+    let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+    let theme = AppTheme::Default;
+    let mut table_state = TableState::default();
+
+    terminal
+        .draw(|frame| {
+            let params = LatencyViewParams {
+                tracker: &tracker,
+                theme: &theme,
+            };
+            let rows = LatencyRenderer::render_latency_view(
+                frame,
+                frame.area(),
+                &params,
+                &mut table_state,
+            )
+            .unwrap();
+            assert_eq!(rows, 2);
+        })
+        .unwrap();
+
+    let content = buffer_content(&terminal);
+    assert!(content.contains("Wakeup Latency Heatmap"));
+    assert!(content.contains("worker100"));
+    assert!(content.contains("worker200"));
+    assert!(content.contains("20us"));
+    assert!(content.contains("5.0ms"));
+    assert!(content.contains("█"));
+    // The process with the highest p99 latency is listed first
+    assert!(content.find("worker200").unwrap() < content.find("worker100").unwrap());
+}
+
+#[test]
+fn test_render_latency_view_empty() {
+    let tracker = LatencyTracker::new(100);
+    let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+    let theme = AppTheme::Default;
+    let mut table_state = TableState::default();
+
+    terminal
+        .draw(|frame| {
+            let params = LatencyViewParams {
+                tracker: &tracker,
+                theme: &theme,
+            };
+            let rows = LatencyRenderer::render_latency_view(
+                frame,
+                frame.area(),
+                &params,
+                &mut table_state,
+            )
+            .unwrap();
+            assert_eq!(rows, 0);
+        })
+        .unwrap();
+
+    let content = buffer_content(&terminal);
+    assert!(content.contains("No wakeup latency samples"));
+}
+
+#[test]
+fn test_format_ns() {
+    assert_eq!(LatencyRenderer::format_ns(999), "0us");
+    assert_eq!(LatencyRenderer::format_ns(20_000), "20us");
+    assert_eq!(LatencyRenderer::format_ns(5_000_000), "5.0ms");
+    assert_eq!(LatencyRenderer::format_ns(2_500_000_000), "2.5s");
+}